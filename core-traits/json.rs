@@ -0,0 +1,401 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The canonical JSON and Transit-JSON encodings of values.
+//!
+//! Both encodings are lossless: every `TypedValue` decodes to the value it was encoded from.
+//! (The `serde` derivations on these types are for Rust-to-Rust use, and have no such promise.)
+//!
+//! | Type       | JSON                                          | Transit-JSON                    |
+//! |------------|-----------------------------------------------|---------------------------------|
+//! | `boolean`  | `true`                                        | `true`                          |
+//! | `long`     | `42`                                          | `42`, or `"~i…"` beyond 2^53    |
+//! | `double`   | `4.0`, or `{"double": "NaN"}` if not finite   | `4.0`, or `"~zNaN"`, `"~zINF"`  |
+//! | `string`   | `"Alice"`                                     | `"Alice"`, `"~~…"` if escaped   |
+//! | `ref`      | `{"ref": 65536}`                              | `["~#ref", 65536]`              |
+//! | `keyword`  | `{"keyword": ":person/name"}`                 | `"~:person/name"`               |
+//! | `instant`  | `{"instant": "2018-01-01T00:00:00.000001Z"}`  | `"~t2018-01-01T00:00:00.000001Z"` |
+//! | `uuid`     | `{"uuid": "…"}`                               | `"~u…"`                         |
+//! | `bytes`    | `{"bytes": "AAEC"}` (base64)                  | `"~bAAEC"` (base64)             |
+//!
+//! Doubles are always written with a fractional part or an exponent, and longs never are, so
+//! that the two can be told apart. Instants are RFC 3339 strings in UTC, to the microsecond.
+//!
+//! In results, a `Binding::Vec` is an array, `Binding::Nil` is `null`, and a `Binding::Map` is
+//! an object keyed by keyword text such as `":person/name"` — in Transit, a map
+//! `["^ ", "~:person/name", …]`. Since every key of a structured map starts with `:`, and no
+//! tag does, maps can't be mistaken for tagged values.
+//!
+//! The Transit-JSON written here is in normal (not verbose) mode, and never uses the cache.
+//! `to_transit` writes values to be nested in a document: a bare scalar at the root of a
+//! document has to be quoted as `["~#'", value]`.
+
+use chrono::{DateTime, SecondsFormat};
+
+use serde_json::{Map, Number, Value};
+
+use uuid::Uuid;
+
+use edn;
+use edn::{Keyword, Utc};
+
+use crate::{Binding, MicrosecondPrecision, TypedValue};
+
+/// Transit-JSON integers beyond this are written as strings, because JavaScript can't hold them
+/// in a number.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` as standard, padded base64.
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard base64, with or without padding.
+pub fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let mut n: u32 = 0;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let v = BASE64.iter().position(|&b| b == c)? as u32;
+        n = (n << 6) | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    // A single leftover character can't have come from a whole byte.
+    if bits >= 6 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Formats `instant` as RFC 3339, to the microsecond: `2018-01-01T00:00:00.000001Z`.
+pub fn format_instant(instant: &DateTime<Utc>) -> String {
+    instant.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Parses an RFC 3339 instant, truncating it to the microsecond.
+pub fn parse_instant(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|instant| instant.with_timezone(&Utc).microsecond_precision())
+}
+
+/// Parses keyword text such as `":person/name"`.
+pub fn parse_keyword(s: &str) -> Option<Keyword> {
+    match edn::parse::value(s).map(|v| v.without_spans()) {
+        Ok(edn::Value::Keyword(keyword)) => Some(keyword),
+        _ => None,
+    }
+}
+
+fn tagged(tag: &str, value: Value) -> Value {
+    let mut map = Map::new();
+    map.insert(tag.to_string(), value);
+    Value::Object(map)
+}
+
+fn non_finite_name(d: f64) -> &'static str {
+    if d.is_nan() {
+        "NaN"
+    } else if d > 0.0 {
+        "Infinity"
+    } else {
+        "-Infinity"
+    }
+}
+
+/// Escapes a string that Transit would otherwise read as something else.
+fn transit_string(s: &str) -> Value {
+    if s.starts_with('~') || s.starts_with('^') || s.starts_with('`') {
+        Value::String(format!("~{}", s))
+    } else {
+        Value::String(s.to_string())
+    }
+}
+
+impl TypedValue {
+    /// This value in the canonical JSON encoding.
+    pub fn to_json(&self) -> Value {
+        match self {
+            TypedValue::Ref(entid) => tagged("ref", (*entid).into()),
+            TypedValue::Boolean(b) => Value::Bool(*b),
+            TypedValue::Long(long) => (*long).into(),
+            TypedValue::Double(d) => match Number::from_f64(d.into_inner()) {
+                Some(number) => Value::Number(number),
+                None => tagged("double", non_finite_name(d.into_inner()).into()),
+            },
+            TypedValue::Instant(instant) => tagged("instant", format_instant(instant).into()),
+            TypedValue::String(s) => Value::String(s.as_ref().clone()),
+            TypedValue::Keyword(keyword) => tagged("keyword", keyword.to_string().into()),
+            TypedValue::Uuid(uuid) => tagged("uuid", uuid.to_hyphenated().to_string().into()),
+            TypedValue::Bytes(bytes) => tagged("bytes", encode_base64(bytes).into()),
+        }
+    }
+
+    /// Reads a value in the canonical JSON encoding, returning `None` if `json` isn't one.
+    pub fn from_json(json: &Value) -> Option<TypedValue> {
+        match json {
+            Value::Bool(b) => Some(TypedValue::Boolean(*b)),
+            Value::Number(number) if number.is_f64() => number.as_f64().map(TypedValue::from),
+            Value::Number(number) => number.as_i64().map(TypedValue::Long),
+            Value::String(s) => Some(TypedValue::typed_string(s.as_str())),
+            Value::Object(map) if map.len() == 1 => {
+                let (tag, value) = map.iter().next().unwrap();
+                match (tag.as_str(), value) {
+                    ("ref", Value::Number(number)) => number.as_i64().map(TypedValue::Ref),
+                    ("keyword", Value::String(s)) => parse_keyword(s).map(TypedValue::from),
+                    ("instant", Value::String(s)) => parse_instant(s).map(TypedValue::Instant),
+                    ("uuid", Value::String(s)) => Uuid::parse_str(s).ok().map(TypedValue::Uuid),
+                    ("bytes", Value::String(s)) => {
+                        decode_base64(s).map(|bytes| TypedValue::Bytes(bytes.into()))
+                    }
+                    ("double", Value::String(s)) => match s.as_str() {
+                        "NaN" => Some(TypedValue::from(f64::NAN)),
+                        "Infinity" => Some(TypedValue::from(f64::INFINITY)),
+                        "-Infinity" => Some(TypedValue::from(f64::NEG_INFINITY)),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            Value::Null | Value::Array(_) | Value::Object(_) => None,
+        }
+    }
+
+    /// This value in Transit-JSON, to be nested in a document.
+    pub fn to_transit(&self) -> Value {
+        match self {
+            TypedValue::Ref(entid) => Value::Array(vec!["~#ref".into(), (*entid).into()]),
+            TypedValue::Boolean(b) => Value::Bool(*b),
+            TypedValue::Long(long) if long.abs() <= MAX_SAFE_INTEGER => (*long).into(),
+            TypedValue::Long(long) => Value::String(format!("~i{}", long)),
+            TypedValue::Double(d) => match Number::from_f64(d.into_inner()) {
+                Some(number) => Value::Number(number),
+                None if d.is_nan() => "~zNaN".into(),
+                None if d.into_inner() > 0.0 => "~zINF".into(),
+                None => "~z-INF".into(),
+            },
+            TypedValue::Instant(instant) => Value::String(format!("~t{}", format_instant(instant))),
+            TypedValue::String(s) => transit_string(s),
+            // Transit keywords are written without their leading colon.
+            TypedValue::Keyword(keyword) => Value::String(format!("~{}", keyword)),
+            TypedValue::Uuid(uuid) => Value::String(format!("~u{}", uuid.to_hyphenated())),
+            TypedValue::Bytes(bytes) => Value::String(format!("~b{}", encode_base64(bytes))),
+        }
+    }
+}
+
+impl Binding {
+    /// This binding in the canonical JSON encoding.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Binding::Scalar(value) => value.to_json(),
+            Binding::Vec(values) => Value::Array(values.iter().map(Binding::to_json).collect()),
+            Binding::Map(map) => Value::Object(
+                map.iter()
+                    .map(|(keyword, value)| (keyword.to_string(), value.to_json()))
+                    .collect(),
+            ),
+            Binding::Nil => Value::Null,
+        }
+    }
+
+    /// This binding in Transit-JSON, to be nested in a document.
+    pub fn to_transit(&self) -> Value {
+        match self {
+            Binding::Scalar(value) => value.to_transit(),
+            Binding::Vec(values) => Value::Array(values.iter().map(Binding::to_transit).collect()),
+            Binding::Map(map) => {
+                let mut array = Vec::with_capacity(1 + 2 * map.len());
+                array.push("^ ".into());
+                for (keyword, value) in map.iter() {
+                    array.push(Value::String(format!("~{}", keyword)));
+                    array.push(value.to_transit());
+                }
+                Value::Array(array)
+            }
+            Binding::Nil => Value::Null,
+        }
+    }
+}
+
+/// A Transit-JSON map, `["^ ", k, v, …]`, with keyword keys.
+pub fn transit_map<'a, I>(entries: I) -> Value
+where
+    I: IntoIterator<Item = (&'a str, Value)>,
+{
+    let mut array = vec![Value::from("^ ")];
+    for (keyword, value) in entries {
+        array.push(Value::String(format!("~:{}", keyword)));
+        array.push(value);
+    }
+    Value::Array(array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+
+    use serde_json;
+
+    use crate::StructuredMap;
+    use edn::FromMicros;
+
+    fn values() -> Vec<TypedValue> {
+        vec![
+            TypedValue::Ref(65536),
+            TypedValue::Boolean(true),
+            TypedValue::Long(42),
+            TypedValue::Long(i64::MIN),
+            TypedValue::Double(4.0.into()),
+            TypedValue::Double(f64::NAN.into()),
+            TypedValue::Double(f64::NEG_INFINITY.into()),
+            TypedValue::Instant(DateTime::<Utc>::from_micros(1_514_764_800_000_001)),
+            TypedValue::typed_string("~tricky"),
+            TypedValue::typed_ns_keyword("person", "name"),
+            TypedValue::Uuid(Uuid::from_str("550e8400-e29b-41d4-a716-446655440000").unwrap()),
+            TypedValue::Bytes(vec![0u8, 1, 2, 254, 255].into()),
+        ]
+    }
+
+    #[test]
+    fn test_base64() {
+        for (bytes, encoded) in &[
+            (&b""[..], ""),
+            (&b"f"[..], "Zg=="),
+            (&b"fo"[..], "Zm8="),
+            (&b"foo"[..], "Zm9v"),
+            (&b"foob"[..], "Zm9vYg=="),
+        ] {
+            assert_eq!(&encode_base64(bytes), encoded);
+            assert_eq!(decode_base64(encoded).as_deref(), Some(*bytes));
+        }
+        assert_eq!(decode_base64("Zm9vYg").as_deref(), Some(&b"foob"[..]));
+        assert_eq!(decode_base64("Z"), None);
+        assert_eq!(decode_base64("Zm9v!"), None);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        for value in values() {
+            let json = value.to_json();
+            let text = json.to_string();
+            let reread: Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(
+                TypedValue::from_json(&reread),
+                Some(value.clone()),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(TypedValue::Double(4.0.into()).to_json().to_string(), "4.0");
+        assert_eq!(
+            TypedValue::typed_ns_keyword("person", "name").to_json(),
+            json!({"keyword": ":person/name"})
+        );
+        assert_eq!(
+            TypedValue::Instant(DateTime::<Utc>::from_micros(1_514_764_800_000_001)).to_json(),
+            json!({"instant": "2018-01-01T00:00:00.000001Z"})
+        );
+        assert_eq!(TypedValue::from_json(&json!({"ref": "x"})), None);
+        assert_eq!(
+            TypedValue::from_json(&json!({"keyword": ":a", "ref": 1})),
+            None
+        );
+        assert_eq!(TypedValue::from_json(&Value::Null), None);
+    }
+
+    #[test]
+    fn test_transit() {
+        assert_eq!(TypedValue::Ref(65536).to_transit(), json!(["~#ref", 65536]));
+        assert_eq!(
+            TypedValue::Long(1 << 60).to_transit(),
+            json!("~i1152921504606846976")
+        );
+        assert_eq!(TypedValue::Long(-42).to_transit(), json!(-42));
+        assert_eq!(
+            TypedValue::typed_string("~tricky").to_transit(),
+            json!("~~tricky")
+        );
+        assert_eq!(TypedValue::typed_string("^ ").to_transit(), json!("~^ "));
+        assert_eq!(
+            TypedValue::typed_ns_keyword("person", "name").to_transit(),
+            json!("~:person/name")
+        );
+        assert_eq!(
+            TypedValue::Bytes(vec![0u8, 1, 2].into()).to_transit(),
+            json!("~bAAEC")
+        );
+        assert_eq!(
+            TypedValue::Double(f64::INFINITY.into()).to_transit(),
+            json!("~zINF")
+        );
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut map = StructuredMap::default();
+        map.insert(
+            Keyword::namespaced("person", "name"),
+            Binding::Scalar(TypedValue::typed_string("Alice")),
+        );
+        map.insert(
+            Keyword::namespaced("person", "friend"),
+            Binding::Vec(vec![Binding::Scalar(TypedValue::Ref(65537))].into()),
+        );
+        let binding = Binding::Map(map.into());
+        assert_eq!(
+            binding.to_json(),
+            json!({":person/name": "Alice", ":person/friend": [{"ref": 65537}]})
+        );
+        assert_eq!(
+            binding.to_transit(),
+            json!([
+                "^ ",
+                "~:person/name",
+                "Alice",
+                "~:person/friend",
+                [["~#ref", 65537]]
+            ])
+        );
+        assert_eq!(Binding::Nil.to_json(), Value::Null);
+
+        // No tagged value looks like a structured map.
+        for value in values() {
+            if let Value::Object(map) = value.to_json() {
+                assert!(map.keys().all(|tag| !tag.starts_with(':')));
+            }
+        }
+    }
+}
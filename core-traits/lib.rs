@@ -16,6 +16,8 @@ extern crate ordered_float;
 extern crate serde_derive;
 extern crate bytes;
 extern crate edn;
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
 extern crate uuid;
 #[macro_use]
 extern crate lazy_static;
@@ -51,6 +53,7 @@ use edn::entities::{
     AttributePlace, EntidOrIdent, EntityPlace, TransactableValueMarker, ValuePlace,
 };
 
+pub mod json;
 mod value_type_set;
 pub mod values;
 
@@ -128,6 +131,57 @@ pub mod attribute {
             }
         }
     }
+
+    /// How the values of a fulltext attribute are split into searchable tokens, i.e., its
+    /// `:db.fulltext/tokenizer`.
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialOrd, PartialEq)]
+    pub enum FulltextTokenizer {
+        /// Unicode-aware word splitting and case folding, preserving diacritics.  The default.
+        #[default]
+        Unicode61,
+        /// Like `Unicode61`, but words are also reduced to their English stems, so that "running"
+        /// matches "runs".
+        Porter,
+        /// Overlapping three-character sequences, so that any substring of at least three
+        /// characters matches.
+        Trigram,
+    }
+
+    impl FulltextTokenizer {
+        pub fn into_typed_value(self) -> TypedValue {
+            TypedValue::typed_ns_keyword(
+                "db.fulltext.tokenizer",
+                match self {
+                    FulltextTokenizer::Unicode61 => "unicode61",
+                    FulltextTokenizer::Porter => "porter",
+                    FulltextTokenizer::Trigram => "trigram",
+                },
+            )
+        }
+    }
+
+    /// The fulltext index searched for an attribute's values.  Attributes with the same tokenizer
+    /// and prefix settings share an index.
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialOrd, PartialEq)]
+    pub struct FulltextIndex {
+        pub tokenizer: FulltextTokenizer,
+        /// `true` if the index includes prefix indexes, making prefix queries like "sear*" fast.
+        pub prefix: bool,
+    }
+
+    impl FulltextIndex {
+        /// The name of the SQL table holding this index.
+        pub fn table_name(&self) -> &'static str {
+            match (self.tokenizer, self.prefix) {
+                (FulltextTokenizer::Unicode61, false) => "fulltext_index",
+                (FulltextTokenizer::Unicode61, true) => "fulltext_index_prefix",
+                (FulltextTokenizer::Porter, false) => "fulltext_index_porter",
+                (FulltextTokenizer::Porter, true) => "fulltext_index_porter_prefix",
+                (FulltextTokenizer::Trigram, false) => "fulltext_index_trigram",
+                (FulltextTokenizer::Trigram, true) => "fulltext_index_trigram_prefix",
+            }
+        }
+    }
 }
 
 /// A Mentat schema attribute has a value type and several other flags determining how assertions
@@ -169,6 +223,14 @@ pub struct Attribute {
     /// Fulltext attributes always have string values.
     pub fulltext: bool,
 
+    /// How the values of this fulltext attribute are tokenized, i.e., its
+    /// `:db.fulltext/tokenizer`.  Meaningful only if `fulltext` is `true`.
+    pub fulltext_tokenizer: attribute::FulltextTokenizer,
+
+    /// `true` if this fulltext attribute's index includes prefix indexes, i.e., it is
+    /// `:db.fulltext/prefix true`.  Meaningful only if `fulltext` is `true`.
+    pub fulltext_prefix: bool,
+
     /// `true` if this attribute is a component, i.e., it is `:db/isComponent true`.
     ///
     /// Component attributes always have value type `Ref`.
@@ -201,6 +263,14 @@ impl Attribute {
         flags
     }
 
+    /// The fulltext index searched for this attribute's values.
+    pub fn fulltext_index(&self) -> attribute::FulltextIndex {
+        attribute::FulltextIndex {
+            tokenizer: self.fulltext_tokenizer,
+            prefix: self.fulltext_prefix,
+        }
+    }
+
     pub fn to_edn_value(&self, ident: Option<Keyword>) -> edn::Value {
         let mut attribute_map: BTreeMap<edn::Value, edn::Value> = BTreeMap::default();
         if let Some(ident) = ident {
@@ -240,6 +310,29 @@ impl Attribute {
 
         if self.fulltext {
             attribute_map.insert(values::DB_FULLTEXT.clone(), edn::Value::Boolean(true));
+
+            match self.fulltext_tokenizer {
+                attribute::FulltextTokenizer::Unicode61 => (),
+                attribute::FulltextTokenizer::Porter => {
+                    attribute_map.insert(
+                        values::DB_FULLTEXT_TOKENIZER.clone(),
+                        values::DB_FULLTEXT_TOKENIZER_PORTER.clone(),
+                    );
+                }
+                attribute::FulltextTokenizer::Trigram => {
+                    attribute_map.insert(
+                        values::DB_FULLTEXT_TOKENIZER.clone(),
+                        values::DB_FULLTEXT_TOKENIZER_TRIGRAM.clone(),
+                    );
+                }
+            }
+
+            if self.fulltext_prefix {
+                attribute_map.insert(
+                    values::DB_FULLTEXT_PREFIX.clone(),
+                    edn::Value::Boolean(true),
+                );
+            }
         }
 
         if self.component {
@@ -260,6 +353,8 @@ impl Default for Attribute {
             // There's no particular reason to favour one value type, so Ref it is.
             value_type: ValueType::Ref,
             fulltext: false,
+            fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+            fulltext_prefix: false,
             index: false,
             multival: false,
             unique: None,
@@ -732,8 +827,9 @@ impl MicrosecondPrecision for DateTime<Utc> {
 /// * Vecs of structured values, for multi-valued component attributes or nested expressions.
 /// * Single structured values, for single-valued component attributes or nested expressions.
 /// * Single typed values, for simple attributes.
+/// * Nothing at all, for variables bound only by an `optional` clause that didn't match.
 ///
-/// The `Binding` enum defines these three options.
+/// The `Binding` enum defines these four options.
 ///
 /// Datomic also supports structured inputs; at present Mentat does not, but this type
 /// would also serve that purpose.
@@ -744,6 +840,7 @@ pub enum Binding {
     Scalar(TypedValue),
     Vec(ValueRc<Vec<Binding>>),
     Map(ValueRc<StructuredMap>),
+    Nil,
 }
 
 impl<T> From<T> for Binding
@@ -809,6 +906,10 @@ impl Binding {
             _ => None,
         }
     }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Binding::Nil)
+    }
 }
 
 /// A pull expression expands a binding into a structure. The returned structure
@@ -881,6 +982,7 @@ impl Binding {
 
             Binding::Map(_) => None,
             Binding::Vec(_) => None,
+            Binding::Nil => None,
         }
     }
 }
@@ -1065,6 +1167,8 @@ mod tests {
             index: true,
             value_type: ValueType::Ref,
             fulltext: false,
+            fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+            fulltext_prefix: false,
             unique: None,
             multival: false,
             component: false,
@@ -1080,6 +1184,8 @@ mod tests {
             index: false,
             value_type: ValueType::Boolean,
             fulltext: true,
+            fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+            fulltext_prefix: false,
             unique: Some(attribute::Unique::Value),
             multival: false,
             component: false,
@@ -1095,6 +1201,8 @@ mod tests {
             index: false,
             value_type: ValueType::Boolean,
             fulltext: true,
+            fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+            fulltext_prefix: false,
             unique: Some(attribute::Unique::Identity),
             multival: false,
             component: false,
@@ -42,6 +42,23 @@ lazy_static_namespaced_keyword_value!(DB_CARDINALITY, "db", "cardinality");
 lazy_static_namespaced_keyword_value!(DB_CARDINALITY_MANY, "db.cardinality", "many");
 lazy_static_namespaced_keyword_value!(DB_CARDINALITY_ONE, "db.cardinality", "one");
 lazy_static_namespaced_keyword_value!(DB_FULLTEXT, "db", "fulltext");
+lazy_static_namespaced_keyword_value!(DB_FULLTEXT_PREFIX, "db.fulltext", "prefix");
+lazy_static_namespaced_keyword_value!(DB_FULLTEXT_TOKENIZER, "db.fulltext", "tokenizer");
+lazy_static_namespaced_keyword_value!(
+    DB_FULLTEXT_TOKENIZER_PORTER,
+    "db.fulltext.tokenizer",
+    "porter"
+);
+lazy_static_namespaced_keyword_value!(
+    DB_FULLTEXT_TOKENIZER_TRIGRAM,
+    "db.fulltext.tokenizer",
+    "trigram"
+);
+lazy_static_namespaced_keyword_value!(
+    DB_FULLTEXT_TOKENIZER_UNICODE61,
+    "db.fulltext.tokenizer",
+    "unicode61"
+);
 lazy_static_namespaced_keyword_value!(DB_IDENT, "db", "ident");
 lazy_static_namespaced_keyword_value!(DB_INDEX, "db", "index");
 lazy_static_namespaced_keyword_value!(DB_INSTALL_ATTRIBUTE, "db.install", "attribute");
@@ -38,10 +38,13 @@ pub use edn::{Cloned, FromMicros, FromRc, Keyword, ToMicros, Utc, ValueRc};
 pub use crate::cache::{CachedAttributes, UpdateableCache};
 
 mod sql_types;
+mod statistics;
 mod tx_report;
 /// Core types defining a Mentat knowledge base.
 mod types;
 
+pub use crate::statistics::{AttributeStatistics, Statistics};
+
 pub use crate::tx_report::TxReport;
 
 pub use crate::types::ValueTypeTag;
@@ -283,6 +286,8 @@ mod test {
             index: true,
             value_type: ValueType::Ref,
             fulltext: false,
+            fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+            fulltext_prefix: false,
             unique: None,
             multival: false,
             component: false,
@@ -295,6 +300,8 @@ mod test {
             index: false,
             value_type: ValueType::String,
             fulltext: true,
+            fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+            fulltext_prefix: false,
             unique: Some(attribute::Unique::Value),
             multival: true,
             component: false,
@@ -307,6 +314,8 @@ mod test {
             index: false,
             value_type: ValueType::Boolean,
             fulltext: false,
+            fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+            fulltext_prefix: false,
             unique: Some(attribute::Unique::Identity),
             multival: false,
             component: true,
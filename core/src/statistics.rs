@@ -0,0 +1,33 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use std::collections::BTreeMap;
+
+use core_traits::Entid;
+
+/// Counts of the datoms of an attribute.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct AttributeStatistics {
+    pub datoms: usize,
+    /// The number of distinct entities that have the attribute.
+    pub entities: usize,
+    /// The number of distinct values that the attribute has.
+    pub values: usize,
+}
+
+/// Estimates of the size of a store, used to plan queries.  They're computed on demand and not
+/// maintained as the store changes, so they're only ever estimates.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Statistics {
+    pub datoms: usize,
+    /// The number of distinct entities in the store.
+    pub entities: usize,
+    pub attributes: BTreeMap<Entid, AttributeStatistics>,
+}
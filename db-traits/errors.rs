@@ -63,6 +63,20 @@ pub enum SchemaConstraintViolation {
 
     /// A transaction tried to assert datoms that don't observe the schema's cardinality constraints.
     CardinalityConflicts { conflicts: Vec<CardinalityConflict> },
+
+    /// A transaction tried to assert a datom or datoms with values that don't satisfy the
+    /// attribute's `:db.attr/preds`.
+    AttributePredicateViolations {
+        /// The key (`[e a v]`) has a value `v` that fails the given predicate, written as EDN.
+        violating_datoms: BTreeMap<(Entid, Entid, TypedValue), String>,
+    },
+
+    /// A transaction asserted `[e :db/ensure spec]` but `e` lacks some of the attributes the spec
+    /// requires via `:db.entity/attrs`.
+    EntitySpecViolations {
+        /// A map from `(e, spec)` to the required attributes that `e` does not have.
+        missing_attributes: BTreeMap<(Entid, Entid), BTreeSet<Entid>>,
+    },
 }
 
 impl ::std::fmt::Display for SchemaConstraintViolation {
@@ -98,6 +112,32 @@ impl ::std::fmt::Display for SchemaConstraintViolation {
                 }
                 Ok(())
             }
+            AttributePredicateViolations {
+                ref violating_datoms,
+            } => {
+                writeln!(f, "attribute predicate violations:")?;
+                for (datom, predicate) in violating_datoms {
+                    writeln!(
+                        f,
+                        "  datom [{} {} {:?}] does not satisfy {}",
+                        datom.0, datom.1, datom.2, predicate
+                    )?;
+                }
+                Ok(())
+            }
+            EntitySpecViolations {
+                ref missing_attributes,
+            } => {
+                writeln!(f, "entity spec violations:")?;
+                for (&(e, spec), attributes) in missing_attributes {
+                    writeln!(
+                        f,
+                        "  entity {} does not satisfy spec {}: missing attributes {:?}",
+                        e, spec, attributes
+                    )?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -227,6 +267,10 @@ pub enum DbErrorKind {
     #[fail(display = "schema alteration failed: {}", _0)]
     SchemaAlterationFailed(String),
 
+    /// A transaction asserted a `:db/excise` that can't be applied.
+    #[fail(display = "bad excision: {}", _0)]
+    BadExcision(String),
+
     /// A transaction tried to violate a constraint of the schema of the Mentat store.
     #[fail(display = "schema constraint violation: {}", _0)]
     SchemaConstraintViolation(SchemaConstraintViolation),
@@ -293,6 +337,24 @@ pub enum DbErrorKind {
     #[fail(display = "Supplied an invalid transaction range")]
     TimelinesInvalidRange,
 
+    #[fail(display = "Unknown timeline: {}", _0)]
+    TimelinesUnknown(String),
+
+    #[fail(display = "Timeline already exists: {}", _0)]
+    TimelinesExists(String),
+
+    #[fail(display = "Can't do this while timeline {} is active", _0)]
+    TimelinesActive(String),
+
+    #[fail(display = "Can't undo transaction {}: no such transaction", _0)]
+    UndoUnknownTransaction(Entid),
+
+    #[fail(
+        display = "Can't undo transaction {}: transaction {} has since changed the same attributes",
+        _0, _1
+    )]
+    UndoConflict(Entid, Entid),
+
     // It would be better to capture the underlying `rusqlite::Error`, but that type doesn't
     // implement many useful traits, including `Clone`, `Eq`, and `PartialEq`.
     #[fail(display = "SQL error: {}", _0)]
@@ -33,7 +33,14 @@ pub const TX0: i64 = 0x1000_0000;
 pub const USER0: i64 = 0x10000;
 
 // Corresponds to the version of the :db.schema/core vocabulary.
-pub const CORE_SCHEMA_VERSION: u32 = 1;
+//
+// Version history:
+//
+// 1: initial core vocabulary.
+// 2: declarative constraints: :db.attr/preds, :db/ensure and :db.entity/attrs; and excision:
+//    :db/excise, :db.excise/attrs, :db.excise/beforeT and :db.excise/before; and fulltext
+//    tokenizers: :db.fulltext/tokenizer and :db.fulltext/prefix.
+pub const CORE_SCHEMA_VERSION: u32 = 2;
 
 lazy_static! {
     static ref V1_IDENTS: [(symbols::Keyword, i64); 40] = {
@@ -116,7 +123,7 @@ lazy_static! {
                 ns_keyword!("db.part", "db"),
                 0,
                 USER0 - 1,
-                (1 + V1_IDENTS.len()) as i64,
+                (1 + V1_IDENTS.len() + V2_IDENTS.len()) as i64,
                 false,
             ),
             (ns_keyword!("db.part", "user"), USER0, TX0 - 1, USER0, true),
@@ -201,6 +208,84 @@ lazy_static! {
             })
             .unwrap()
     };
+    static ref V2_IDENTS: [(symbols::Keyword, i64); 8] = {
+        [
+            (ns_keyword!("db.attr", "preds"), entids::DB_ATTR_PREDS),
+            (ns_keyword!("db", "ensure"), entids::DB_ENSURE),
+            (ns_keyword!("db.entity", "attrs"), entids::DB_ENTITY_ATTRS),
+            (
+                ns_keyword!("db.fulltext", "tokenizer"),
+                entids::DB_FULLTEXT_TOKENIZER,
+            ),
+            (
+                ns_keyword!("db.fulltext", "prefix"),
+                entids::DB_FULLTEXT_PREFIX,
+            ),
+            (
+                ns_keyword!("db.fulltext.tokenizer", "unicode61"),
+                entids::DB_FULLTEXT_TOKENIZER_UNICODE61,
+            ),
+            (
+                ns_keyword!("db.fulltext.tokenizer", "porter"),
+                entids::DB_FULLTEXT_TOKENIZER_PORTER,
+            ),
+            (
+                ns_keyword!("db.fulltext.tokenizer", "trigram"),
+                entids::DB_FULLTEXT_TOKENIZER_TRIGRAM,
+            ),
+        ]
+    };
+    static ref V2_CORE_SCHEMA: [symbols::Keyword; 9] = {
+        [
+            (ns_keyword!("db.attr", "preds")),
+            (ns_keyword!("db", "ensure")),
+            (ns_keyword!("db.entity", "attrs")),
+            (ns_keyword!("db", "excise")),
+            (ns_keyword!("db.excise", "attrs")),
+            (ns_keyword!("db.excise", "beforeT")),
+            (ns_keyword!("db.excise", "before")),
+            (ns_keyword!("db.fulltext", "tokenizer")),
+            (ns_keyword!("db.fulltext", "prefix")),
+        ]
+    };
+    static ref V2_SYMBOLIC_SCHEMA: Value = {
+        let s = r#"
+{;; Declarative constraints.  Each :db.attr/preds value is an EDN predicate form, like
+ ;; "(re-matches \"[^@]+@[^@]+\")", that asserted values of the attribute must satisfy.
+ :db.attr/preds        {:db/valueType   :db.type/string
+                        :db/cardinality :db.cardinality/many}
+ ;; :db/ensure is never stored: asserting [e :db/ensure spec] checks that e has every attribute
+ ;; named by the spec's :db.entity/attrs when the transaction commits.
+ :db/ensure            {:db/valueType   :db.type/ref
+                        :db/cardinality :db.cardinality/many}
+ :db.entity/attrs      {:db/valueType   :db.type/ref
+                        :db/cardinality :db.cardinality/many}
+
+ ;; Asserting [x :db/excise e] permanently purges e's datoms and history when the transaction
+ ;; commits.  The excision entity x remains as an audit record of what was excised.
+ :db/excise            {:db/valueType   :db.type/ref
+                        :db/cardinality :db.cardinality/one}
+ :db.excise/attrs      {:db/valueType   :db.type/ref
+                        :db/cardinality :db.cardinality/many}
+ :db.excise/beforeT    {:db/valueType   :db.type/long
+                        :db/cardinality :db.cardinality/one}
+ :db.excise/before     {:db/valueType   :db.type/instant
+                        :db/cardinality :db.cardinality/one}
+
+ ;; How the values of a :db/fulltext attribute are tokenized: one of
+ ;; :db.fulltext.tokenizer/unicode61 (the default), :db.fulltext.tokenizer/porter, or
+ ;; :db.fulltext.tokenizer/trigram.  With :db.fulltext/prefix true, prefix queries are indexed.
+ :db.fulltext/tokenizer {:db/valueType   :db.type/ref
+                         :db/cardinality :db.cardinality/one}
+ :db.fulltext/prefix   {:db/valueType   :db.type/boolean
+                        :db/cardinality :db.cardinality/one}}"#;
+        edn::parse::value(s)
+            .map(|v| v.without_spans())
+            .map_err(|_| {
+                DbErrorKind::BadBootstrapDefinition("Unable to parse V2_SYMBOLIC_SCHEMA".into())
+            })
+            .unwrap()
+    };
 }
 
 /// Convert (ident, entid) pairs into [:db/add IDENT :db/ident IDENT] `Value` instances.
@@ -355,23 +440,34 @@ pub(crate) fn bootstrap_partition_map() -> PartitionMap {
 pub(crate) fn bootstrap_ident_map() -> IdentMap {
     V1_IDENTS
         .iter()
+        .chain(V2_IDENTS.iter())
         .map(|&(ref ident, entid)| (ident.clone(), entid))
         .collect()
 }
 
 pub(crate) fn bootstrap_schema() -> Schema {
     let ident_map = bootstrap_ident_map();
-    let bootstrap_triples =
-        symbolic_schema_to_triples(&ident_map, &V1_SYMBOLIC_SCHEMA).expect("symbolic schema");
+    let bootstrap_triples = [
+        symbolic_schema_to_triples(&ident_map, &V1_SYMBOLIC_SCHEMA).expect("symbolic schema"),
+        symbolic_schema_to_triples(&ident_map, &V2_SYMBOLIC_SCHEMA).expect("symbolic schema"),
+    ]
+    .concat();
     Schema::from_ident_map_and_triples(ident_map, bootstrap_triples).unwrap()
 }
 
 pub(crate) fn bootstrap_entities() -> Vec<Entity<edn::ValueAndSpan>> {
+    let core_schema: Vec<symbols::Keyword> = V1_CORE_SCHEMA
+        .iter()
+        .chain(V2_CORE_SCHEMA.iter())
+        .cloned()
+        .collect();
     let bootstrap_assertions: Value = Value::Vector(
         [
             symbolic_schema_to_assertions(&V1_SYMBOLIC_SCHEMA).expect("symbolic schema"),
+            symbolic_schema_to_assertions(&V2_SYMBOLIC_SCHEMA).expect("symbolic schema"),
             idents_to_assertions(&V1_IDENTS[..]),
-            schema_attrs_to_assertions(CORE_SCHEMA_VERSION, V1_CORE_SCHEMA.as_ref()),
+            idents_to_assertions(&V2_IDENTS[..]),
+            schema_attrs_to_assertions(CORE_SCHEMA_VERSION, &core_schema),
         ]
         .concat(),
     );
@@ -380,3 +476,24 @@ pub(crate) fn bootstrap_entities() -> Vec<Entity<edn::ValueAndSpan>> {
     // TODO: represent these bootstrap entity data errors rather than just panicing.
     edn::parse::entities(&bootstrap_assertions.to_string()).expect("bootstrap assertions")
 }
+
+/// The idents that `:db.schema/core` version 2 adds to a store bootstrapped at version 1.
+pub(crate) fn v2_idents() -> &'static [(symbols::Keyword, i64)] {
+    &V2_IDENTS[..]
+}
+
+/// The assertions that upgrade the `:db.schema/core` vocabulary of a store bootstrapped at
+/// version 1 to version 2.  They must be transacted against a schema that already knows the
+/// idents in `v2_idents`.
+pub(crate) fn v2_upgrade_entities() -> Vec<Entity<edn::ValueAndSpan>> {
+    let upgrade_assertions: Value = Value::Vector(
+        [
+            symbolic_schema_to_assertions(&V2_SYMBOLIC_SCHEMA).expect("symbolic schema"),
+            idents_to_assertions(&V2_IDENTS[..]),
+            schema_attrs_to_assertions(2, V2_CORE_SCHEMA.as_ref()),
+        ]
+        .concat(),
+    );
+
+    edn::parse::entities(&upgrade_assertions.to_string()).expect("upgrade assertions")
+}
@@ -0,0 +1,250 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! History compaction drops the parts of the transaction log that no longer describe the current
+//! state of the store.
+//!
+//! Every assertion and retraction is kept in `transactions`, so long-lived stores grow without
+//! bound.  Compacting history before a transaction removes, from each earlier transaction, the
+//! retractions and the assertions that were retracted before that transaction.  The assertions
+//! that still hold at that transaction are kept, so that the compacted log still replays to the
+//! current state; `datoms` itself is never touched.
+//!
+//! Each partition's next entid is derived from the largest entid in the log, so the history of
+//! the largest entid of each partition is kept, too: otherwise compaction could lower it, and
+//! entids would be reused.
+//!
+//! History that named timelines fork from is never compacted.
+
+use std::collections::BTreeSet;
+
+use rusqlite;
+use rusqlite::types::ToSql;
+
+use core_traits::Entid;
+
+use db_traits::errors::Result;
+
+use mentat_core::{DateTime, HasSchema, Schema, ToMicros, Utc};
+
+use crate::db;
+use crate::entids;
+
+/// The point before which history is compacted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HistoryBound {
+    /// Compact transactions before the given transaction.
+    Tx(Entid),
+    /// Compact transactions whose `:db/txInstant` is before the given instant.
+    Instant(DateTime<Utc>),
+}
+
+/// Return the transaction before which `bound` compacts history.
+pub fn resolve_history_bound(conn: &rusqlite::Connection, bound: HistoryBound) -> Result<Entid> {
+    match bound {
+        HistoryBound::Tx(tx) => Ok(tx),
+        HistoryBound::Instant(instant) => {
+            // The first transaction at or after the instant; if there is none, every transaction
+            // is before the instant.
+            let tx: Option<Entid> = conn.query_row(
+                "SELECT COALESCE(
+                   (SELECT MIN(e) FROM datoms WHERE a = ? AND v >= ?),
+                   (SELECT MAX(tx) + 1 FROM timelined_transactions))",
+                [&entids::DB_TX_INSTANT as &dyn ToSql, &instant.to_micros()],
+                |row| row.get(0),
+            )?;
+            Ok(tx.unwrap_or(0))
+        }
+    }
+}
+
+/// Compact the history of the main timeline before the transaction `before_tx`.
+///
+/// The history of the attributes in `keep_history` is left intact.  Returns the number of
+/// transaction log rows removed.
+pub fn compact_history(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    before_tx: Entid,
+    keep_history: &BTreeSet<Entid>,
+) -> Result<usize> {
+    // Named timelines replay on top of the main timeline as of their base.
+    let oldest_base: Option<Entid> = conn.query_row(
+        "SELECT MIN(base) FROM timelines",
+        rusqlite::params![],
+        |row| row.get(0),
+    )?;
+    let before_tx = oldest_base.map_or(before_tx, |base| before_tx.min(base + 1));
+
+    let mut condition = format!(
+        "timeline = {main} AND tx < ?1
+           AND NOT (added IS 1 AND NOT EXISTS (SELECT 1 FROM timelined_transactions AS r
+             WHERE r.timeline = {main} AND r.added IS 0 AND r.tx > timelined_transactions.tx
+               AND r.tx < ?1
+               AND r.e = timelined_transactions.e
+               AND r.a = timelined_transactions.a
+               AND r.value_type_tag = timelined_transactions.value_type_tag
+               AND r.v = timelined_transactions.v))
+           AND e NOT IN (SELECT idx - 1 FROM parts)",
+        main = crate::TIMELINE_MAIN
+    );
+    let mut params: Vec<&dyn ToSql> = vec![&before_tx];
+    if !keep_history.is_empty() {
+        condition.push_str(&format!(
+            " AND a NOT IN {}",
+            crate::repeat_values(keep_history.len(), 1)
+        ));
+        params.extend(keep_history.iter().map(|a| a as &dyn ToSql));
+    }
+
+    // Fulltext values referenced only by the removed history can go, too.
+    let mut fulltext_rowids: BTreeSet<i64> = BTreeSet::new();
+    {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a, v FROM timelined_transactions WHERE {}",
+            condition
+        ))?;
+        let mut rows = stmt.query(&params[..])?;
+        while let Some(row) = rows.next()? {
+            let a: Entid = row.get(0)?;
+            if schema.attribute_for_entid(a).is_some_and(|a| a.fulltext) {
+                fulltext_rowids.insert(row.get(1)?);
+            }
+        }
+    }
+
+    let removed = conn.execute(
+        &format!("DELETE FROM timelined_transactions WHERE {}", condition),
+        &params[..],
+    )?;
+
+    db::delete_unreferenced_fulltext_values(conn, schema, &fulltext_rowids)?;
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::borrow::Borrow;
+
+    use crate::debug::TestConn;
+
+    #[test]
+    fn test_compact_history() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+            {:db/id 201 :db/ident :test/note :db/valueType :db.type/string :db/cardinality :db.cardinality/many :db/index true :db/fulltext true}
+            {:db/id 202 :db/ident :test/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+        ]"#
+        );
+        let schema_tx = conn.last_tx_id();
+
+        assert_transact!(
+            conn,
+            r#"[{:db/id 100 :test/name "Ivan" :test/note "first" :test/age 30}]"#
+        );
+        assert_transact!(
+            conn,
+            r#"[[:db/add 100 :test/name "Vanya"]
+                [:db/retract 100 :test/note "first"]
+                [:db/add 100 :test/note "second"]
+                [:db/add 100 :test/age 31]]"#
+        );
+        let before = conn.last_tx_id() + 1;
+        assert_transact!(conn, r#"[[:db/add 100 :test/age 32]]"#);
+
+        let datoms = conn.datoms().to_edn();
+
+        let keep: BTreeSet<Entid> = vec![202].into_iter().collect();
+        let removed =
+            compact_history(&conn.sqlite, &conn.schema, before, &keep).expect("compacted history");
+        assert_eq!(removed, 4);
+
+        // Current datoms are untouched; only history goes.
+        assert_eq!(conn.datoms().to_edn(), datoms);
+        assert_matches!(
+            crate::debug::transactions_after(&conn.sqlite, &conn.schema, schema_tx)
+                .expect("transactions"),
+            r#"[[[100 :test/age 30 ?tx1 true]
+                 [?tx1 :db/txInstant ?ms1 ?tx1 true]]
+                [[100 :test/name "Vanya" ?tx2 true]
+                 [100 :test/note 2 ?tx2 true]
+                 [100 :test/age 30 ?tx2 false]
+                 [100 :test/age 31 ?tx2 true]
+                 [?tx2 :db/txInstant ?ms2 ?tx2 true]]
+                [[100 :test/age 31 ?tx3 false]
+                 [100 :test/age 32 ?tx3 true]
+                 [?tx3 :db/txInstant ?ms3 ?tx3 true]]]"#
+        );
+        assert_matches!(conn.fulltext_values(), r#"[[2 "second"]]"#);
+    }
+
+    #[test]
+    fn test_compact_history_keeps_assertions_retracted_later() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[{:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}]"#
+        );
+        let schema_tx = conn.last_tx_id();
+
+        assert_transact!(conn, r#"[{:db/id 100 :test/name "Ivan"}]"#);
+        let before = conn.last_tx_id() + 1;
+        assert_transact!(conn, r#"[[:db/retract 100 :test/name "Ivan"]]"#);
+
+        let removed = compact_history(&conn.sqlite, &conn.schema, before, &BTreeSet::new())
+            .expect("compacted history");
+        assert_eq!(removed, 0);
+
+        // The retraction after the bound still has the assertion it retracts.
+        assert_matches!(
+            crate::debug::transactions_after(&conn.sqlite, &conn.schema, schema_tx)
+                .expect("transactions"),
+            r#"[[[100 :test/name "Ivan" ?tx1 true]
+                 [?tx1 :db/txInstant ?ms1 ?tx1 true]]
+                [[100 :test/name "Ivan" ?tx2 false]
+                 [?tx2 :db/txInstant ?ms2 ?tx2 true]]]"#
+        );
+    }
+
+    #[test]
+    fn test_compact_history_keeps_partition_maxima() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[{:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}]"#
+        );
+        let report = assert_transact!(conn, r#"[{:db/id "a" :test/name "Ivan"}]"#);
+        let ivan = report.tempids["a"];
+        assert_transact!(
+            conn,
+            format!(r#"[[:db/retract {} :test/name "Ivan"]]"#, ivan)
+        );
+        let before = conn.last_tx_id() + 1;
+
+        // Ivan's history is all that marks the entids up to Ivan's as used.
+        let removed = compact_history(&conn.sqlite, &conn.schema, before, &BTreeSet::new())
+            .expect("compacted history");
+        assert_eq!(removed, 0);
+
+        // A reopened store allocates past Ivan.
+        conn.partition_map = db::read_partition_map(&conn.sqlite).expect("partition map");
+        let report = assert_transact!(conn, r#"[{:db/id "b" :test/name "Petr"}]"#);
+        assert_eq!(report.tempids["b"], ivan + 1);
+    }
+}
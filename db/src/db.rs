@@ -13,7 +13,7 @@
 use failure::ResultExt;
 
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::iter::{once, repeat};
 use std::ops::Range;
 use std::path::Path;
@@ -24,6 +24,7 @@ use rusqlite;
 use rusqlite::limits::Limit;
 use rusqlite::params_from_iter;
 use rusqlite::types::{ToSql, ToSqlOutput};
+use rusqlite::OptionalExtension;
 use rusqlite::TransactionBehavior;
 
 use crate::bootstrap;
@@ -35,7 +36,9 @@ use crate::entids;
 
 use core_traits::{attribute, Attribute, AttributeBitFlags, Entid, TypedValue, ValueType};
 
-use mentat_core::{AttributeMap, FromMicros, IdentMap, Schema, ToMicros, ValueRc};
+use mentat_core::{
+    AttributeMap, AttributeStatistics, FromMicros, IdentMap, Schema, Statistics, ToMicros, ValueRc,
+};
 
 use db_traits::errors::{DbErrorKind, Result};
 
@@ -104,9 +107,77 @@ fn make_connection(
         initial_pragmas
     ))?;
 
+    register_regexp(&conn)?;
+    register_checked_arithmetic(&conn)?;
+
     Ok(conn)
 }
 
+/// Install the `regexp(pattern, text)` function that backs SQLite's `REGEXP` operator, which the
+/// `re-find` query predicate compiles to. Non-text values never match.
+fn register_regexp(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    use rusqlite::functions::FunctionFlags;
+    use rusqlite::types::ValueRef;
+
+    conn.create_scalar_function(
+        "regexp",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            // The pattern is usually constant, so SQLite keeps the compiled regex around for us.
+            let re =
+                ctx.get_or_create_aux(
+                    0,
+                    |pattern| -> std::result::Result<
+                        _,
+                        Box<dyn std::error::Error + Send + Sync + 'static>,
+                    > { Ok(regex::Regex::new(pattern.as_str()?)?) },
+                )?;
+            match ctx.get_raw(1) {
+                ValueRef::Text(text) => Ok(std::str::from_utf8(text)
+                    .map(|text| re.is_match(text))
+                    .unwrap_or(false)),
+                _ => Ok(false),
+            }
+        },
+    )
+}
+
+/// Install `checked_add`, `checked_sub`, `checked_mul` and `checked_neg`, which the arithmetic
+/// expression functions compile to when they work on longs. SQLite's own operators quietly give a
+/// double when a long overflows; these fail, as Clojure's do.
+fn register_checked_arithmetic(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    use rusqlite::functions::FunctionFlags;
+
+    fn overflow() -> rusqlite::Error {
+        rusqlite::Error::UserFunctionError("integer overflow".into())
+    }
+
+    type Operator = fn(i64, i64) -> Option<i64>;
+
+    let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    let operators: [(&str, Operator); 3] = [
+        ("checked_add", i64::checked_add),
+        ("checked_sub", i64::checked_sub),
+        ("checked_mul", i64::checked_mul),
+    ];
+    for &(name, operator) in operators.iter() {
+        conn.create_scalar_function(name, 2, flags, move |ctx| {
+            // `NULL`, as bound by an `optional` clause that didn't match, stays `NULL`.
+            match (ctx.get::<Option<i64>>(0)?, ctx.get::<Option<i64>>(1)?) {
+                (Some(x), Some(y)) => operator(x, y).map(Some).ok_or_else(overflow),
+                _ => Ok(None),
+            }
+        })?;
+    }
+    conn.create_scalar_function("checked_neg", 1, flags, |ctx| {
+        match ctx.get::<Option<i64>>(0)? {
+            Some(x) => x.checked_neg().map(Some).ok_or_else(overflow),
+            None => Ok(None),
+        }
+    })
+}
+
 pub fn new_connection<T>(uri: T) -> rusqlite::Result<rusqlite::Connection>
 where
     T: AsRef<Path>,
@@ -143,7 +214,9 @@ where
 /// Version history:
 ///
 /// 1: initial Rust Mentat schema.
-pub const CURRENT_VERSION: i32 = 1;
+/// 2: named timelines; fulltext values indexed by FTS5 tables, one per tokenizer and prefix
+///    setting in use.
+pub const CURRENT_VERSION: i32 = 2;
 
 /// MIN_SQLITE_VERSION should be changed when there's a new minimum version of sqlite required
 /// for the project to work.
@@ -250,6 +323,63 @@ lazy_static! {
         r#"CREATE TABLE known_parts (part TEXT NOT NULL PRIMARY KEY, start INTEGER NOT NULL, end INTEGER NOT NULL, allow_excision SMALLINT NOT NULL)"#,
         ]
     };
+
+    /// SQL statements to be executed, in order, to upgrade the Mentat SQL schema from version 1
+    /// to version 2.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    static ref V2_STATEMENTS: Vec<&'static str> = { vec![
+        // Named timelines, forked from the main timeline after transaction `base`.  At most one is
+        // active, i.e., materialized in `datoms` in place of the main timeline.
+        r#"CREATE TABLE timelines (timeline INTEGER NOT NULL PRIMARY KEY, name TEXT NOT NULL UNIQUE, base INTEGER NOT NULL, active TINYINT NOT NULL DEFAULT 0)"#,
+
+        // Entids that were allocated, but whose history has since been removed.  They still count
+        // towards the partition maxima, so that they are never allocated again; see
+        // `retire_partition_maxima`.
+        r#"CREATE TABLE retired_entids (e INTEGER NOT NULL PRIMARY KEY)"#,
+
+        // Fulltext values move out of the FTS4 table into a plain table, keeping their rowids.
+        // They are indexed by one FTS5 table per tokenizer and prefix setting in use; see
+        // `ensure_fulltext_index`.  The views over the values are rebuilt around the new table.
+        r#"DROP VIEW all_datoms"#,
+        r#"DROP VIEW fulltext_datoms"#,
+        r#"DROP VIEW fulltext_values_view"#,
+        r#"CREATE TABLE fulltext_values_v2 (id INTEGER PRIMARY KEY, text TEXT NOT NULL UNIQUE, searchid INT)"#,
+        r#"INSERT INTO fulltext_values_v2 (id, text, searchid) SELECT rowid, text, searchid FROM fulltext_values"#,
+        r#"DROP TABLE fulltext_values"#,
+        r#"ALTER TABLE fulltext_values_v2 RENAME TO fulltext_values"#,
+
+        // This combination of view and triggers allows you to transparently
+        // update-or-insert into fulltext_values. Just INSERT INTO fulltext_values_view (text, searchid).
+        r#"CREATE VIEW fulltext_values_view AS SELECT * FROM fulltext_values"#,
+        r#"CREATE TRIGGER replace_fulltext_searchid
+             INSTEAD OF INSERT ON fulltext_values_view
+             WHEN EXISTS (SELECT 1 FROM fulltext_values WHERE text = new.text)
+             BEGIN
+               UPDATE fulltext_values SET searchid = new.searchid WHERE text = new.text;
+             END"#,
+        r#"CREATE TRIGGER insert_fulltext_searchid
+             INSTEAD OF INSERT ON fulltext_values_view
+             WHEN NOT EXISTS (SELECT 1 FROM fulltext_values WHERE text = new.text)
+             BEGIN
+               INSERT INTO fulltext_values (text, searchid) VALUES (new.text, new.searchid);
+             END"#,
+
+        // A view transparently interpolating fulltext indexed values into the datom structure.
+        r#"CREATE VIEW fulltext_datoms AS
+             SELECT e, a, fulltext_values.text AS v, tx, value_type_tag, index_avet, index_vaet, index_fulltext, unique_value
+               FROM datoms, fulltext_values
+               WHERE datoms.index_fulltext IS NOT 0 AND datoms.v = fulltext_values.rowid"#,
+
+        // A view transparently interpolating all entities (fulltext and non-fulltext) into the datom structure.
+        r#"CREATE VIEW all_datoms AS
+             SELECT e, a, v, tx, value_type_tag, index_avet, index_vaet, index_fulltext, unique_value
+               FROM datoms
+               WHERE index_fulltext IS 0
+             UNION ALL
+             SELECT e, a, v, tx, value_type_tag, index_avet, index_vaet, index_fulltext, unique_value
+               FROM fulltext_datoms"#,
+        ]
+    };
 }
 
 /// Set the SQLite user version.
@@ -282,10 +412,12 @@ pub fn create_empty_current_version(
 ) -> Result<(rusqlite::Transaction, DB)> {
     let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
 
-    for statement in (&V1_STATEMENTS).iter() {
+    for statement in V1_STATEMENTS.iter().chain(V2_STATEMENTS.iter()) {
         tx.execute(statement, rusqlite::params![])?;
     }
 
+    ensure_fulltext_index(&tx, attribute::FulltextIndex::default())?;
+
     set_user_version(&tx, CURRENT_VERSION)?;
 
     let bootstrap_schema = bootstrap::bootstrap_schema();
@@ -294,8 +426,63 @@ pub fn create_empty_current_version(
     Ok((tx, DB::new(bootstrap_partition_map, bootstrap_schema)))
 }
 
+/// Create the FTS5 table backing `index`, unless it already exists.
+///
+/// Each index is an external content table over `fulltext_values`, kept up to date by triggers, so
+/// every fulltext value is indexed by every index; queries restrict matches to the attribute's own
+/// datoms.  A newly created index is populated from the existing values.
+pub(crate) fn ensure_fulltext_index(
+    conn: &rusqlite::Connection,
+    index: attribute::FulltextIndex,
+) -> Result<()> {
+    let table = index.table_name();
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [&table],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(());
+    }
+
+    // By default we use Unicode-aware tokenizing (particularly for case folding), but preserve
+    // diacritics.
+    let tokenize = match index.tokenizer {
+        attribute::FulltextTokenizer::Unicode61 => "unicode61 remove_diacritics 0",
+        attribute::FulltextTokenizer::Porter => "porter unicode61 remove_diacritics 0",
+        attribute::FulltextTokenizer::Trigram => "trigram",
+    };
+    // The trigram tokenizer answers prefix (and substring) queries without a prefix index.
+    let prefix = if index.prefix && index.tokenizer != attribute::FulltextTokenizer::Trigram {
+        ", prefix='2 3'"
+    } else {
+        ""
+    };
+
+    conn.execute_batch(&format!(
+        r#"CREATE VIRTUAL TABLE {t}
+             USING fts5(text, content='fulltext_values', content_rowid='id', tokenize="{tokenize}"{prefix});
+           CREATE TRIGGER {t}_insert AFTER INSERT ON fulltext_values
+             BEGIN
+               INSERT INTO {t} (rowid, text) VALUES (new.id, new.text);
+             END;
+           CREATE TRIGGER {t}_delete AFTER DELETE ON fulltext_values
+             BEGIN
+               INSERT INTO {t} ({t}, rowid, text) VALUES ('delete', old.id, old.text);
+             END;
+           INSERT INTO {t} ({t}) VALUES ('rebuild');"#,
+        t = table,
+        tokenize = tokenize,
+        prefix = prefix
+    ))?;
+    Ok(())
+}
+
 /// Creates a partition map view for the main timeline based on partitions
 /// defined in 'known_parts'.
+///
+/// Entids used by named timelines, and by the main timeline while a named timeline is active, are
+/// never reallocated; nor are retired entids.
 fn create_current_partition_view(conn: &rusqlite::Connection) -> Result<()> {
     let mut stmt = conn.prepare("SELECT part, end FROM known_parts ORDER BY end ASC")?;
     let known_parts: Result<Vec<(String, i64)>> = stmt
@@ -313,9 +500,15 @@ fn create_current_partition_view(conn: &rusqlite::Connection) -> Result<()> {
             CASE {} END AS part,
             min(e) AS start,
             max(e) + 1 AS idx
-        FROM timelined_transactions WHERE timeline = {} GROUP BY part",
+        FROM (
+            SELECT e FROM timelined_transactions
+            WHERE timeline IN ({}, {}) OR timeline IN (SELECT timeline FROM timelines)
+            UNION ALL
+            SELECT e FROM retired_entids)
+        GROUP BY part",
         case.join(" "),
-        crate::TIMELINE_MAIN
+        crate::TIMELINE_MAIN,
+        crate::TIMELINE_STASH
     );
 
     conn.execute(&view_stmt, rusqlite::params![])?;
@@ -374,6 +567,88 @@ pub fn create_current_version(conn: &mut rusqlite::Connection) -> Result<DB> {
     Ok(db)
 }
 
+/// Upgrade the `:db.schema/core` vocabulary of a store bootstrapped by an earlier version of
+/// Mentat, by transacting the idents and core attributes it lacks.
+fn ensure_current_core_schema(conn: &mut rusqlite::Connection, mut db: DB) -> Result<DB> {
+    // Check and upgrade under the same lock, so that only one connection upgrades.
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+    let version: Option<i64> = tx
+        .query_row(
+            "SELECT v FROM datoms WHERE e = ? AND a = ?",
+            rusqlite::params![entids::DB_SCHEMA_CORE, entids::DB_SCHEMA_VERSION],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match version {
+        Some(1) => (),
+        // A store without a core vocabulary is for `verify_core_schema` to reject, and a store
+        // with a newer one is for its own version of Mentat to manage.
+        _ => return Ok(db),
+    }
+
+    // The upgrade allocates its idents from `:db.part/db`, which may already hold user
+    // vocabulary: unlike a freshly bootstrapped store, an upgraded one doesn't have them at the
+    // entids in `entids`.
+    let part_db = db
+        .partition_map
+        .get_mut(":db.part/db")
+        .ok_or_else(|| DbErrorKind::NotYetImplemented(":db.part/db is missing".to_string()))?;
+    let idents = bootstrap::v2_idents();
+    if !part_db.allows_entid(part_db.next_entid() + idents.len() as Entid) {
+        bail!(DbErrorKind::NotYetImplemented(format!(
+            "Upgrading :db.schema/core version 1: :db.part/db has no room for {} idents",
+            idents.len()
+        )));
+    }
+    let entids = part_db.allocate_entids(idents.len());
+
+    let mut schema = db.schema.clone();
+    for ((ident, _), entid) in idents.iter().zip(entids) {
+        schema.ident_map.insert(ident.clone(), entid);
+        schema.entid_map.insert(entid, ident.clone());
+    }
+
+    let (_report, next_partition_map, next_schema, _watcher) = transact(
+        &tx,
+        db.partition_map,
+        &db.schema,
+        &schema,
+        NullWatcher(),
+        bootstrap::v2_upgrade_entities(),
+    )?;
+    tx.commit()?;
+
+    db.partition_map = next_partition_map;
+    if let Some(next_schema) = next_schema {
+        db.schema = next_schema;
+    }
+    Ok(db)
+}
+
+/// Upgrade the SQL schema of a store created at version 1 to version 2.
+fn upgrade_from_v1(conn: &mut rusqlite::Connection) -> Result<()> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    // Another connection may have upgraded the store since we looked.
+    if get_user_version(&tx)? != 1 {
+        return Ok(());
+    }
+
+    for statement in V2_STATEMENTS.iter() {
+        tx.execute(statement, rusqlite::params![])?;
+    }
+
+    ensure_fulltext_index(&tx, attribute::FulltextIndex::default())?;
+
+    // Partitions now account for the entids allocated on named timelines.
+    tx.execute("DROP VIEW parts", rusqlite::params![])?;
+    create_current_partition_view(&tx)?;
+
+    set_user_version(&tx, 2)?;
+    tx.commit()?;
+    Ok(())
+}
+
 pub fn ensure_current_version(conn: &mut rusqlite::Connection) -> Result<DB> {
     if rusqlite::version_number() < MIN_SQLITE_VERSION {
         panic!("Mentat requires at least sqlite {}", MIN_SQLITE_VERSION);
@@ -381,15 +656,18 @@ pub fn ensure_current_version(conn: &mut rusqlite::Connection) -> Result<DB> {
 
     let user_version = get_user_version(&conn)?;
     match user_version {
-        0 => create_current_version(conn),
-        CURRENT_VERSION => read_db(conn),
+        0 => return create_current_version(conn),
+        1 => upgrade_from_v1(conn)?,
+        CURRENT_VERSION => (),
 
-        // TODO: support updating an existing store.
         v => bail!(DbErrorKind::NotYetImplemented(format!(
             "Opening databases with Mentat version: {}",
             v
         ))),
     }
+
+    let db = read_db(conn)?;
+    ensure_current_core_schema(conn, db)
 }
 
 pub trait TypedSQLValue {
@@ -553,7 +831,11 @@ pub fn read_partition_map(conn: &rusqlite::Connection) -> Result<PartitionMap> {
 
 /// Read the ident map materialized view from the given SQL store.
 pub(crate) fn read_ident_map(conn: &rusqlite::Connection) -> Result<IdentMap> {
-    let v = read_materialized_view(conn, "idents")?;
+    read_ident_map_from(conn, "idents")
+}
+
+fn read_ident_map_from(conn: &rusqlite::Connection, table: &str) -> Result<IdentMap> {
+    let v = read_materialized_view(conn, table)?;
     v.into_iter().map(|(e, a, typed_value)| {
         if a != entids::DB_IDENT {
             bail!(DbErrorKind::NotYetImplemented(format!("bad idents materialized view: expected :db/ident but got {}", a)));
@@ -567,19 +849,79 @@ pub(crate) fn read_ident_map(conn: &rusqlite::Connection) -> Result<IdentMap> {
 }
 
 /// Read the schema materialized view from the given SQL store.
-pub(crate) fn read_attribute_map(conn: &rusqlite::Connection) -> Result<AttributeMap> {
-    let entid_triples = read_materialized_view(conn, "schema")?;
+pub(crate) fn read_attribute_map(
+    conn: &rusqlite::Connection,
+    ident_map: &IdentMap,
+) -> Result<AttributeMap> {
+    read_attribute_map_from(conn, "schema", ident_map)
+}
+
+fn read_attribute_map_from(
+    conn: &rusqlite::Connection,
+    table: &str,
+    ident_map: &IdentMap,
+) -> Result<AttributeMap> {
+    let entid_triples = read_materialized_view(conn, table)?;
     let mut attribute_map = AttributeMap::default();
-    metadata::update_attribute_map_from_entid_triples(&mut attribute_map, entid_triples, vec![])?;
+    metadata::update_attribute_map_from_entid_triples(
+        &mut attribute_map,
+        ident_map,
+        entid_triples,
+        vec![],
+    )?;
     Ok(attribute_map)
 }
 
+/// Read the schema of the Mentat store attached to the given SQL store as `database`.
+pub fn read_attached_schema(conn: &rusqlite::Connection, database: &str) -> Result<Schema> {
+    let ident_map = read_ident_map_from(conn, &format!("`{}`.idents", database))?;
+    let attribute_map =
+        read_attribute_map_from(conn, &format!("`{}`.schema", database), &ident_map)?;
+    Schema::from_ident_map_and_attribute_map(ident_map, attribute_map)
+}
+
+/// Count the datoms of the given SQL store, in total and per attribute.  This reads every datom.
+pub fn read_statistics(conn: &rusqlite::Connection) -> Result<Statistics> {
+    let (datoms, entities) = conn.query_row(
+        "SELECT COUNT(*), COUNT(DISTINCT e) FROM datoms",
+        rusqlite::params![],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)? as usize,
+                row.get::<_, i64>(1)? as usize,
+            ))
+        },
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT a, COUNT(*), COUNT(DISTINCT e), COUNT(DISTINCT v) FROM datoms GROUP BY a",
+    )?;
+    let attributes: rusqlite::Result<BTreeMap<Entid, AttributeStatistics>> = stmt
+        .query_map(rusqlite::params![], |row| {
+            Ok((
+                row.get(0)?,
+                AttributeStatistics {
+                    datoms: row.get::<_, i64>(1)? as usize,
+                    entities: row.get::<_, i64>(2)? as usize,
+                    values: row.get::<_, i64>(3)? as usize,
+                },
+            ))
+        })?
+        .collect();
+
+    Ok(Statistics {
+        datoms,
+        entities,
+        attributes: attributes?,
+    })
+}
+
 /// Read the materialized views from the given SQL store and return a Mentat `DB` for querying and
 /// applying transactions.
 pub(crate) fn read_db(conn: &rusqlite::Connection) -> Result<DB> {
     let partition_map = read_partition_map(conn)?;
     let ident_map = read_ident_map(conn)?;
-    let attribute_map = read_attribute_map(conn)?;
+    let attribute_map = read_attribute_map(conn, &ident_map)?;
     let schema = Schema::from_ident_map_and_attribute_map(ident_map, attribute_map)?;
     Ok(DB::new(partition_map, schema))
 }
@@ -641,7 +983,10 @@ pub trait MentatStoring {
 
     /// Extract metadata-related [e a typed_value added] datoms resolved in the last
     /// materialized transaction.
-    fn resolved_metadata_assertions(&self) -> Result<Vec<(Entid, Entid, TypedValue, bool)>>;
+    fn resolved_metadata_assertions(
+        &self,
+        ident_map: &IdentMap,
+    ) -> Result<Vec<(Entid, Entid, TypedValue, bool)>>;
 }
 
 /// Take search rows and complete `temp.search_results`.
@@ -1100,7 +1445,11 @@ impl MentatStoring for rusqlite::Connection {
         Ok(())
     }
 
-    fn resolved_metadata_assertions(&self) -> Result<Vec<(Entid, Entid, TypedValue, bool)>> {
+    fn resolved_metadata_assertions(
+        &self,
+        ident_map: &IdentMap,
+    ) -> Result<Vec<(Entid, Entid, TypedValue, bool)>> {
+        let metadata_sql_list = entids::metadata_sql_list(ident_map);
         let sql_stmt = format!(
             r#"
             SELECT e, a, v, value_type_tag, added FROM
@@ -1119,8 +1468,7 @@ impl MentatStoring for rusqlite::Connection {
                     (added0 IS 1 AND search_type IS ':db.cardinality/one' AND v0 IS NOT v))
 
             ) ORDER BY e, a, v, value_type_tag, added"#,
-            entids::METADATA_SQL_LIST.as_str(),
-            entids::METADATA_SQL_LIST.as_str()
+            metadata_sql_list, metadata_sql_list
         );
 
         let mut stmt = self.prepare_cached(&sql_stmt)?;
@@ -1131,9 +1479,64 @@ impl MentatStoring for rusqlite::Connection {
     }
 }
 
+/// Delete the given `fulltext_values` rows unless a datom, or a transaction in any timeline, still
+/// refers to them.
+///
+/// Fulltext values are shared between datoms with equal text, so a row can only be deleted once
+/// nothing refers to it.
+pub(crate) fn delete_unreferenced_fulltext_values(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    rowids: &BTreeSet<i64>,
+) -> Result<()> {
+    let fulltext_attrs: Vec<Entid> = schema
+        .attribute_map
+        .iter()
+        .filter(|(_, attribute)| attribute.fulltext)
+        .map(|(&a, _)| a)
+        .collect();
+
+    if rowids.is_empty() || fulltext_attrs.is_empty() {
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "DELETE FROM fulltext_values WHERE rowid = ?
+           AND NOT EXISTS (SELECT 1 FROM datoms WHERE index_fulltext IS NOT 0 AND v = ?)
+           AND NOT EXISTS (SELECT 1 FROM timelined_transactions WHERE a IN {} AND v = ?)",
+        repeat_values(fulltext_attrs.len(), 1)
+    ))?;
+    for rowid in rowids {
+        let params: Vec<&dyn ToSql> = vec![rowid as &dyn ToSql, rowid]
+            .into_iter()
+            .chain(fulltext_attrs.iter().map(|a| a as &dyn ToSql))
+            .chain(once(rowid as &dyn ToSql))
+            .collect();
+        stmt.execute(&params[..])?;
+    }
+
+    Ok(())
+}
+
+/// Record the largest entid allocated in each partition as retired, so that removing its history
+/// doesn't make it, or the entids before it, available for allocation again.
+pub(crate) fn retire_partition_maxima(conn: &rusqlite::Connection) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO retired_entids (e) SELECT idx - 1 FROM parts",
+        rusqlite::params![],
+    )?;
+    // Only the largest in each partition matters.
+    conn.execute(
+        "DELETE FROM retired_entids WHERE e NOT IN (SELECT idx - 1 FROM parts)",
+        rusqlite::params![],
+    )?;
+    Ok(())
+}
+
 /// Extract metadata-related [e a typed_value added] datoms committed in the given transaction.
 pub fn committed_metadata_assertions(
     conn: &rusqlite::Connection,
+    ident_map: &IdentMap,
     tx_id: Entid,
 ) -> Result<Vec<(Entid, Entid, TypedValue, bool)>> {
     let sql_stmt = format!(
@@ -1142,7 +1545,7 @@ pub fn committed_metadata_assertions(
         FROM transactions
         WHERE tx = ? AND a IN {}
         ORDER BY e, a, v, value_type_tag, added"#,
-        entids::METADATA_SQL_LIST.as_str()
+        entids::metadata_sql_list(ident_map)
     );
 
     let mut stmt = conn.prepare_cached(&sql_stmt)?;
@@ -1228,11 +1631,20 @@ pub fn update_metadata(
             WHERE s.e = datoms.e AND a IN {}
         "#,
             entids::DB_VALUE_TYPE,
-            entids::SCHEMA_SQL_LIST.as_str()
+            entids::schema_sql_list(&new_schema.ident_map)
         );
         conn.execute(&s, rusqlite::params![])?;
     }
 
+    // Fulltext attributes are installed with their tokenizer and prefix settings, which can't be
+    // altered later; make sure their index exists.
+    for &entid in &metadata_report.attributes_installed {
+        let attribute = new_schema.require_attribute_for_entid(entid)?;
+        if attribute.fulltext {
+            ensure_fulltext_index(conn, attribute.fulltext_index())?;
+        }
+    }
+
     let mut index_stmt = conn.prepare("UPDATE datoms SET index_avet = ? WHERE a = ?")?;
     let mut unique_value_stmt = conn.prepare("UPDATE datoms SET unique_value = ? WHERE a = ?")?;
     let mut cardinality_stmt = conn.prepare(
@@ -2520,6 +2932,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_db_fulltext_tokenizer() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            "[[:db/add 111 :db/ident :test/stemmed]
+                                 [:db/add 111 :db/valueType :db.type/string]
+                                 [:db/add 111 :db/cardinality :db.cardinality/many]
+                                 [:db/add 111 :db/index true]
+                                 [:db/add 111 :db/fulltext true]
+                                 [:db/add 111 :db.fulltext/tokenizer :db.fulltext.tokenizer/porter]
+                                 [:db/add 111 :db.fulltext/prefix true]]"
+        );
+
+        let attribute = conn
+            .schema
+            .attribute_for_entid(111)
+            .expect(":test/stemmed")
+            .clone();
+        assert_eq!(
+            attribute.fulltext_tokenizer,
+            attribute::FulltextTokenizer::Porter
+        );
+        assert!(attribute.fulltext_prefix);
+        assert_eq!(
+            attribute.fulltext_index().table_name(),
+            "fulltext_index_porter_prefix"
+        );
+
+        // Installing the attribute created its index, which indexes every fulltext value.
+        assert_transact!(conn, "[[:db/add 301 :test/stemmed \"running\"]]");
+        let count: i64 = conn
+            .sqlite
+            .query_row(
+                "SELECT COUNT(*) FROM fulltext_index_porter_prefix WHERE text MATCH 'run'",
+                rusqlite::params![],
+                |row| row.get(0),
+            )
+            .expect("index");
+        assert_eq!(count, 1);
+
+        // Tokenizer settings can't be altered...
+        assert_transact!(conn,
+                         "[[:db/add 111 :db.fulltext/tokenizer :db.fulltext.tokenizer/trigram]]",
+                         Err("bad schema assertion: Schema alteration for existing attribute with entid 111 is not valid"));
+
+        // ... and only make sense for fulltext attributes.
+        assert_transact!(conn,
+                         "[[:db/add 222 :db/ident :test/string]
+                           [:db/add 222 :db/valueType :db.type/string]
+                           [:db/add 222 :db/cardinality :db.cardinality/one]
+                           [:db/add 222 :db.fulltext/prefix true]]",
+                         Err("bad schema assertion: :db.fulltext/tokenizer or :db.fulltext/prefix without :db/fulltext true for entid: 222"));
+    }
+
     #[test]
     fn test_lookup_refs_entity_column() {
         let mut conn = TestConn::default();
@@ -3339,6 +3807,462 @@ mod tests {
         Err("schema constraint violation: cardinality conflicts:\n  AddRetractConflict { e: 100, a: 200, vs: {Long(7)} }\n  AddRetractConflict { e: 100, a: 201, vs: {Long(8)} }\n"));
     }
 
+    #[test]
+    fn test_attribute_predicates() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 200 :db/ident :test/email :db/valueType :db.type/string :db/cardinality :db.cardinality/many
+             :db.attr/preds ["(re-matches \"[^@]+@[^@]+\")" "(length nil 16)"]}
+            {:db/id 201 :db/ident :test/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one
+             :db.attr/preds "(range 0 150)"}
+        ]"#
+        );
+
+        assert_transact!(
+            conn,
+            r#"[
+            [:db/add 100 :test/email "a@example.com"]
+            [:db/add 100 :test/age 0]
+            [:db/add 101 :test/age 150]
+        ]"#
+        );
+
+        // Every violating datom is reported, along with the first predicate it fails.
+        assert_transact!(conn, r#"[
+            [:db/add 100 :test/email "not an email"]
+            [:db/add 100 :test/email "a.very.long@example.com"]
+            [:db/add 101 :test/age 151]
+        ]"#,
+        Err("schema constraint violation: attribute predicate violations:\n  datom [100 200 String(\"a.very.long@example.com\")] does not satisfy (length nil 16)\n  datom [100 200 String(\"not an email\")] does not satisfy (re-matches \"[^@]+@[^@]+\")\n  datom [101 201 Long(151)] does not satisfy (range 0 150)\n"));
+
+        // Predicates apply in the transaction that asserts them.
+        assert_transact!(conn, r#"[
+            [:db/add :test/age :db.attr/preds "(range nil 100)"]
+            [:db/add 102 :test/age 120]
+        ]"#,
+        Err("schema constraint violation: attribute predicate violations:\n  datom [102 201 Long(120)] does not satisfy (range nil 100)\n"));
+
+        // Retracting a predicate lifts it.
+        assert_transact!(
+            conn,
+            r#"[
+            [:db/retract :test/age :db.attr/preds "(range 0 150)"]
+            [:db/add 102 :test/age 151]
+        ]"#
+        );
+
+        // Malformed predicates are rejected.
+        assert_transact!(conn, r#"[
+            [:db/add :test/age :db.attr/preds "(between 0 150)"]
+        ]"#,
+        Err("bad schema assertion: Expected :db.attr/preds (re-matches \"pattern\"), (range lo hi), or (length lo hi) but got '(between 0 150)'"));
+    }
+
+    #[test]
+    fn test_entity_specs() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+            {:db/id 201 :db/ident :test/email :db/valueType :db.type/string :db/cardinality :db.cardinality/many}
+        ]"#
+        );
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 202 :db/ident :test/person :db.entity/attrs [:test/name :test/email]}
+        ]"#
+        );
+
+        // :db/ensure is checked but never stored.
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 100 :test/name "Alice" :test/email "alice@example.com" :db/ensure :test/person}
+        ]"#
+        );
+        assert_matches!(
+            conn.last_transaction(),
+            r#"[[100 :test/name "Alice" ?tx true]
+                [100 :test/email "alice@example.com" ?tx true]
+                [?tx :db/txInstant ?ms ?tx true]]"#
+        );
+
+        // Attributes already in the store count.
+        assert_transact!(
+            conn,
+            r#"[
+            [:db/add 100 :test/name "Alicia"]
+            [:db/add 100 :db/ensure :test/person]
+        ]"#
+        );
+
+        // Attributes retracted in the same transaction don't.
+        assert_transact!(conn, r#"[
+            [:db/retract 100 :test/email "alice@example.com"]
+            [:db/add 100 :db/ensure :test/person]
+            {:db/id 101 :db/ensure :test/person}
+        ]"#,
+        Err("schema constraint violation: entity spec violations:\n  entity 100 does not satisfy spec 202: missing attributes {201}\n  entity 101 does not satisfy spec 202: missing attributes {200, 201}\n"));
+    }
+
+    #[test]
+    fn test_excision() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+            {:db/id 201 :db/ident :test/note :db/valueType :db.type/string :db/cardinality :db.cardinality/many :db/index true :db/fulltext true}
+            {:db/id 202 :db/ident :test/secret :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        ]"#
+        );
+        let before_excisions = conn.last_tx_id();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 100 :test/name "Alice" :test/note "hello world" :test/secret "s1"}
+            {:db/id 101 :test/name "Bob" :test/note "goodbye" :test/secret "s2"}
+        ]"#
+        );
+        assert_transact!(conn, r#"[[:db/add 100 :test/name "Alicia"]]"#);
+
+        // Restricted to some attributes: the values and their history go, the rest stays.
+        assert_transact!(
+            conn,
+            r#"[{:db/id 300 :db/excise 100 :db.excise/attrs [:test/name]}]"#
+        );
+        assert_matches!(
+            conn.datoms(),
+            r#"[[100 :test/note 1]
+                [100 :test/secret "s1"]
+                [101 :test/name "Bob"]
+                [101 :test/note 2]
+                [101 :test/secret "s2"]
+                [200 :db/ident :test/name]
+                [200 :db/valueType :db.type/string]
+                [200 :db/cardinality :db.cardinality/one]
+                [201 :db/ident :test/note]
+                [201 :db/valueType :db.type/string]
+                [201 :db/cardinality :db.cardinality/many]
+                [201 :db/index true]
+                [201 :db/fulltext true]
+                [202 :db/ident :test/secret]
+                [202 :db/valueType :db.type/string]
+                [202 :db/cardinality :db.cardinality/one]
+                [300 :db/excise 100]
+                [300 :db.excise/attrs :test/name]]"#
+        );
+
+        // The whole entity, including its fulltext values.
+        assert_transact!(conn, r#"[{:db/id 301 :db/excise 100}]"#);
+        assert_matches!(
+            crate::debug::transactions_after(&conn.sqlite, &conn.schema, before_excisions)
+                .expect("transactions"),
+            r#"[[[101 :test/name "Bob" ?tx1 true]
+                 [101 :test/note 2 ?tx1 true]
+                 [101 :test/secret "s2" ?tx1 true]
+                 [?tx1 :db/txInstant ?ms1 ?tx1 true]]
+                [[?tx2 :db/txInstant ?ms2 ?tx2 true]]
+                [[300 :db/excise 100 ?tx3 true]
+                 [300 :db.excise/attrs :test/name ?tx3 true]
+                 [?tx3 :db/txInstant ?ms3 ?tx3 true]]
+                [[301 :db/excise 100 ?tx4 true]
+                 [?tx4 :db/txInstant ?ms4 ?tx4 true]]]"#
+        );
+        assert_matches!(conn.fulltext_values(), r#"[[2 "goodbye"]]"#);
+
+        // Every value of an attribute.
+        assert_transact!(conn, r#"[{:db/id 302 :db/excise :test/secret}]"#);
+        assert_eq!(
+            conn.sqlite
+                .query_row(
+                    "SELECT COUNT(*) FROM timelined_transactions WHERE a = 202",
+                    rusqlite::params![],
+                    |row| row.get::<_, i64>(0)
+                )
+                .unwrap(),
+            0
+        );
+
+        assert_transact!(conn, r#"[{:db/id 303 :db/excise :db/doc}]"#,
+            Err("bad excision: excision 303 of 37: entity is in a partition that does not allow excision"));
+        assert_transact!(conn, r#"[{:db/id 303 :db/excise :test/note :db.excise/attrs [:test/name]}]"#,
+            Err("bad excision: excision 303 of 201: :db.excise/attrs cannot restrict the excision of an attribute"));
+        assert_transact!(conn, r#"[{:db/id 102 :db/ident :test/thing}]"#);
+        assert_transact!(
+            conn,
+            r#"[{:db/id 303 :db/excise :test/thing}]"#,
+            Err("bad excision: excision 303 of 102: entity has a :db/ident")
+        );
+    }
+
+    #[test]
+    fn test_excision_keeps_partition_maxima() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[{:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}]"#
+        );
+        let report = assert_transact!(conn, r#"[{:db/id "a" :test/name "Ivan"}]"#);
+        let ivan = report.tempids["a"];
+
+        // Ivan is the newest entity in the user partition, and the excision leaves no trace of him.
+        assert_transact!(conn, format!(r#"[{{:db/id 300 :db/excise {}}}]"#, ivan));
+        assert_eq!(
+            conn.sqlite
+                .query_row(
+                    "SELECT COUNT(*) FROM timelined_transactions WHERE e = ?",
+                    [&ivan],
+                    |row| row.get::<_, i64>(0)
+                )
+                .unwrap(),
+            0
+        );
+
+        // A reopened store still allocates past Ivan.
+        conn.partition_map = read_partition_map(&conn.sqlite).expect("partition map");
+        let report = assert_transact!(conn, r#"[{:db/id "b" :test/name "Petr"}]"#);
+        assert_eq!(report.tempids["b"], ivan + 1);
+    }
+
+    /// Open a copy of `fixtures/v1people.db`, a store created by the first version of the SQL
+    /// schema with version 1 of `:db.schema/core`.  It defines `:person/name`, `:person/bio`
+    /// (fulltext) and `:person/email`; Ivan (65539) and Petr (65540) were added in one
+    /// transaction, and Ivan's bio was changed in the next.
+    fn open_v1_fixture(name: &str) -> (tempfile::TempDir, TestConn) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join(name);
+        ::std::fs::copy(format!("../fixtures/{}", name), &path).expect("copied fixture");
+        let mut sqlite = new_connection(&path).expect("opened fixture");
+        let db = ensure_current_version(&mut sqlite).expect("upgraded fixture");
+        let conn = TestConn {
+            sqlite,
+            partition_map: db.partition_map,
+            schema: db.schema,
+        };
+        (dir, conn)
+    }
+
+    #[test]
+    fn test_upgrade_core_schema() {
+        let (_dir, mut conn) = open_v1_fixture("v1people.db");
+
+        let version: i64 = conn
+            .sqlite
+            .query_row(
+                "SELECT v FROM datoms WHERE e = ? AND a = ?",
+                rusqlite::params![entids::DB_SCHEMA_CORE, entids::DB_SCHEMA_VERSION],
+                |row| row.get(0),
+            )
+            .expect("core schema version");
+        assert_eq!(version, bootstrap::CORE_SCHEMA_VERSION as i64);
+
+        // The store ends up with the core vocabulary of a freshly bootstrapped one.
+        let bootstrap_schema = bootstrap::bootstrap_schema();
+        for (ident, entid) in bootstrap_schema.ident_map.iter() {
+            assert_eq!(conn.schema.ident_map.get(ident), Some(entid));
+            assert_eq!(
+                conn.schema.attribute_map.get(entid),
+                bootstrap_schema.attribute_map.get(entid)
+            );
+        }
+        assert_eq!(
+            conn.partition_map[":db.part/db"].next_entid(),
+            bootstrap::bootstrap_partition_map()[":db.part/db"].next_entid()
+        );
+
+        // Opening the upgraded store again changes nothing.
+        let db = ensure_current_version(&mut conn.sqlite).expect("reopened");
+        assert_eq!(db.schema, conn.schema);
+        assert_eq!(db.partition_map, conn.partition_map);
+
+        // The user data is untouched, and the new attributes work with it.
+        assert_transact!(
+            conn,
+            r#"[{:db/id "s" :db/ident :person/contactable :db.entity/attrs [:person/name :person/email]}]"#
+        );
+        assert_transact!(conn, r#"[[:db/add 65539 :db/ensure :person/contactable]]"#);
+        assert_transact!(conn, r#"[[:db/add 65540 :db/ensure :person/contactable]]"#,
+            Err("schema constraint violation: entity spec violations:\n  entity 65540 does not satisfy spec 65541: missing attributes {65538}\n"));
+
+        assert_transact!(
+            conn,
+            r#"[{:db/id "x" :db/excise 65539 :db.excise/attrs [:person/email]}]"#
+        );
+        assert_eq!(
+            conn.sqlite
+                .query_row(
+                    "SELECT COUNT(*) FROM timelined_transactions WHERE a = 65538",
+                    rusqlite::params![],
+                    |row| row.get::<_, i64>(0)
+                )
+                .unwrap(),
+            0
+        );
+
+        // A store whose vocabulary extends into `:db.part/db` gets the new idents past it.
+        let (_dir, mut conn) = open_v1_fixture("v1vocabulary.db");
+        assert_eq!(
+            conn.schema
+                .ident_map
+                .get(&Keyword::namespaced("person", "age")),
+            Some(&41)
+        );
+        assert_eq!(
+            conn.schema
+                .ident_map
+                .get(&Keyword::namespaced("person", "nickname")),
+            Some(&42)
+        );
+        assert_eq!(
+            conn.schema
+                .ident_map
+                .get(&Keyword::namespaced("db.attr", "preds")),
+            Some(&43)
+        );
+        assert_eq!(conn.partition_map[":db.part/db"].next_entid(), 51);
+        for (ident, entid) in bootstrap_schema.ident_map.iter() {
+            let upgraded = conn.schema.ident_map[ident];
+            assert_eq!(
+                conn.schema.attribute_map.get(&upgraded),
+                bootstrap_schema.attribute_map.get(entid)
+            );
+        }
+
+        let db = ensure_current_version(&mut conn.sqlite).expect("reopened");
+        assert_eq!(db.schema, conn.schema);
+        assert_eq!(db.partition_map, conn.partition_map);
+
+        // The new attributes work wherever they were allocated.
+        assert_transact!(
+            conn,
+            r#"[[:db/add :person/age :db.attr/preds "(range 0 150)"]]"#
+        );
+        assert_transact!(conn, r#"[[:db/add 65540 :person/age 151]]"#,
+            Err("schema constraint violation: attribute predicate violations:\n  datom [65540 41 Long(151)] does not satisfy (range 0 150)\n"));
+        assert_transact!(
+            conn,
+            r#"[{:db/id "s" :db/ident :person/named :db.entity/attrs [:person/name :person/age]}]"#
+        );
+        assert_transact!(conn, r#"[[:db/add 65539 :db/ensure :person/named]]"#);
+        assert_transact!(conn, r#"[[:db/add 65540 :db/ensure :person/named]]"#,
+            Err("schema constraint violation: entity spec violations:\n  entity 65540 does not satisfy spec 65541: missing attributes {41}\n"));
+
+        assert_transact!(
+            conn,
+            r#"[{:db/ident :person/motto :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+                 :db/index true :db/fulltext true :db.fulltext/tokenizer :db.fulltext.tokenizer/trigram}]"#
+        );
+        let motto = conn.schema.ident_map[&Keyword::namespaced("person", "motto")];
+        assert_eq!(
+            conn.schema.attribute_map[&motto].fulltext_tokenizer,
+            attribute::FulltextTokenizer::Trigram
+        );
+        let db = ensure_current_version(&mut conn.sqlite).expect("reopened");
+        assert_eq!(db.schema, conn.schema);
+    }
+
+    #[test]
+    fn test_upgrade_sql_schema() {
+        let (_dir, mut conn) = open_v1_fixture("v1people.db");
+        assert_eq!(
+            get_user_version(&conn.sqlite).expect("version"),
+            CURRENT_VERSION
+        );
+
+        // Entids allocated on a named timeline stay allocated on the main timeline.
+        let base = conn.last_tx_id();
+        crate::timelines::fork_timeline(&conn.sqlite, "draft", base).expect("forked");
+        let (_, partition_map) = crate::timelines::switch_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            "draft",
+            NullWatcher(),
+        )
+        .expect("switched to draft");
+        conn.partition_map = partition_map;
+        let report = assert_transact!(conn, r#"[{:db/id "o" :person/name "Olga"}]"#);
+        let olga = report.tempids["o"];
+
+        let (_, partition_map) = crate::timelines::switch_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            crate::timelines::TIMELINE_MAIN_NAME,
+            NullWatcher(),
+        )
+        .expect("switched to main");
+        assert_eq!(partition_map[":db.part/user"].next_entid(), olga + 1);
+    }
+
+    #[test]
+    fn test_upgrade_fulltext_values() {
+        let (_dir, mut conn) = open_v1_fixture("v1people.db");
+
+        // Values keep their rowids, so the datoms referring to them are untouched.
+        assert_matches!(
+            conn.fulltext_values(),
+            r#"[[1 "Ivan likes dancing"]
+                [2 "Petr plays the piano"]
+                [3 "Ivan likes dancing and singing"]]"#
+        );
+        let bio = |conn: &TestConn, e: Entid| -> String {
+            conn.sqlite
+                .query_row(
+                    "SELECT v FROM all_datoms WHERE e = ? AND a = 65537",
+                    [e],
+                    |row| row.get(0),
+                )
+                .expect("bio")
+        };
+        assert_eq!(bio(&conn, 65539), "Ivan likes dancing and singing");
+
+        // Old and new values alike are in the default index.
+        assert_transact!(conn, r#"[[:db/add 65540 :person/bio "Petr sings, too"]]"#);
+        let search = |conn: &TestConn, terms: &str| -> Vec<i64> {
+            let mut stmt = conn
+                .sqlite
+                .prepare(
+                    "SELECT rowid FROM fulltext_index WHERE fulltext_index MATCH ? ORDER BY rowid",
+                )
+                .expect("prepared");
+            let rowids: rusqlite::Result<Vec<i64>> = stmt
+                .query_map([terms], |row| row.get(0))
+                .expect("searched")
+                .collect();
+            rowids.expect("rowids")
+        };
+        assert_eq!(search(&conn, "singing"), vec![3]);
+        assert_eq!(search(&conn, "petr"), vec![2, 4]);
+
+        // Attributes with other tokenizers get their own indexes.
+        assert_transact!(
+            conn,
+            r#"[{:db/ident :person/motto :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+                 :db/index true :db/fulltext true :db.fulltext/tokenizer :db.fulltext.tokenizer/porter}]"#
+        );
+        assert_transact!(conn, r#"[[:db/add 65539 :person/motto "keep dancing"]]"#);
+        let dancers: i64 = conn
+            .sqlite
+            .query_row(
+                "SELECT COUNT(*) FROM fulltext_index_porter WHERE fulltext_index_porter MATCH 'dance'",
+                rusqlite::params![],
+                |row| row.get(0),
+            )
+            .expect("searched");
+        assert_eq!(dancers, 3);
+    }
+
     #[test]
     #[cfg(feature = "sqlcipher")]
     fn test_sqlcipher_openable() {
@@ -365,7 +365,8 @@ pub struct TestConn {
 impl TestConn {
     fn assert_materialized_views(&self) {
         let materialized_ident_map = read_ident_map(&self.sqlite).expect("ident map");
-        let materialized_attribute_map = read_attribute_map(&self.sqlite).expect("schema map");
+        let materialized_attribute_map =
+            read_attribute_map(&self.sqlite, &materialized_ident_map).expect("schema map");
 
         let materialized_schema = Schema::from_ident_map_and_attribute_map(
             materialized_ident_map,
@@ -488,12 +489,12 @@ impl TestConn {
 
         // Does not include :db/txInstant.
         let datoms = datoms_after(&conn, &db.schema, 0).unwrap();
-        assert_eq!(datoms.0.len(), 94);
+        assert_eq!(datoms.0.len(), 129);
 
         // Includes :db/txInstant.
         let transactions = transactions_after(&conn, &db.schema, 0).unwrap();
         assert_eq!(transactions.0.len(), 1);
-        assert_eq!(transactions.0[0].0.len(), 95);
+        assert_eq!(transactions.0[0].0.len(), 130);
 
         let mut parts = db.partition_map;
 
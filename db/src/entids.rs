@@ -14,6 +14,10 @@
 ///
 /// Used through-out the transactor to match core DB constructs.
 use core_traits::Entid;
+use itertools::Itertools;
+use mentat_core::IdentMap;
+
+use crate::bootstrap;
 
 // Added in SQL schema v1.
 pub const DB_IDENT: Entid = 1;
@@ -56,12 +60,44 @@ pub const DB_DOC: Entid = 37;
 pub const DB_SCHEMA_VERSION: Entid = 38;
 pub const DB_SCHEMA_ATTRIBUTE: Entid = 39;
 pub const DB_SCHEMA_CORE: Entid = 40;
+pub const DB_ATTR_PREDS: Entid = 41;
+pub const DB_ENSURE: Entid = 42;
+pub const DB_ENTITY_ATTRS: Entid = 43;
+pub const DB_FULLTEXT_TOKENIZER: Entid = 44;
+pub const DB_FULLTEXT_PREFIX: Entid = 45;
+pub const DB_FULLTEXT_TOKENIZER_UNICODE61: Entid = 46;
+pub const DB_FULLTEXT_TOKENIZER_PORTER: Entid = 47;
+pub const DB_FULLTEXT_TOKENIZER_TRIGRAM: Entid = 48;
+
+/// Return the entid that the given store has for the core ident that stores bootstrapped at the
+/// current version of `:db.schema/core` have at `entid`, if any.
+///
+/// Stores upgraded from version 1 allocated the idents that version 2 added from `:db.part/db`,
+/// wherever that had got to, so outside of bootstrapping those must be looked up by ident.
+pub fn core_entid(ident_map: &IdentMap, entid: Entid) -> Option<Entid> {
+    if entid < DB_ATTR_PREDS {
+        return Some(entid);
+    }
+    bootstrap::v2_idents()
+        .iter()
+        .find(|&&(_, e)| e == entid)
+        .and_then(|(ident, _)| ident_map.get(ident).cloned())
+}
+
+/// Return `true` if the given attribute is the given store's `:db.fulltext/tokenizer` or
+/// `:db.fulltext/prefix`.
+fn is_a_fulltext_setting(ident_map: &IdentMap, attribute: Entid) -> bool {
+    [DB_FULLTEXT_TOKENIZER, DB_FULLTEXT_PREFIX]
+        .iter()
+        .any(|&entid| core_entid(ident_map, entid) == Some(attribute))
+}
 
 /// Return `false` if the given attribute will not change the metadata: recognized idents, schema,
 /// partitions in the partition map.
-pub fn might_update_metadata(attribute: Entid) -> bool {
+pub fn might_update_metadata(ident_map: &IdentMap, attribute: Entid) -> bool {
     if attribute >= DB_DOC {
-        return false;
+        // Schema attributes added after :db/doc.
+        return is_a_fulltext_setting(ident_map, attribute);
     }
     matches!(
         attribute,
@@ -78,7 +114,7 @@ pub fn might_update_metadata(attribute: Entid) -> bool {
 }
 
 /// Return 'false' if the given attribute might be used to describe a schema attribute.
-pub fn is_a_schema_attribute(attribute: Entid) -> bool {
+pub fn is_a_schema_attribute(ident_map: &IdentMap, attribute: Entid) -> bool {
     matches!(
         attribute,
         DB_IDENT
@@ -88,6 +124,16 @@ pub fn is_a_schema_attribute(attribute: Entid) -> bool {
             | DB_IS_COMPONENT
             | DB_UNIQUE
             | DB_VALUE_TYPE
+    ) || is_a_fulltext_setting(ident_map, attribute)
+}
+
+fn sql_list(ident_map: &IdentMap, entids: &[Entid]) -> String {
+    format!(
+        "({})",
+        entids
+            .iter()
+            .filter_map(|&entid| core_entid(ident_map, entid))
+            .join(", ")
     )
 }
 
@@ -97,27 +143,39 @@ lazy_static! {
         format!("({})",
                 DB_IDENT)
     };
+}
 
-    /// Attributes that are "schema related".  These might change the "schema" materialized view.
-    pub static ref SCHEMA_SQL_LIST: String = {
-        format!("({}, {}, {}, {}, {}, {})",
-                DB_CARDINALITY,
-                DB_FULLTEXT,
-                DB_INDEX,
-                DB_IS_COMPONENT,
-                DB_UNIQUE,
-                DB_VALUE_TYPE)
-    };
+/// Attributes that are "schema related".  These might change the "schema" materialized view.
+pub fn schema_sql_list(ident_map: &IdentMap) -> String {
+    sql_list(
+        ident_map,
+        &[
+            DB_CARDINALITY,
+            DB_FULLTEXT,
+            DB_FULLTEXT_TOKENIZER,
+            DB_FULLTEXT_PREFIX,
+            DB_INDEX,
+            DB_IS_COMPONENT,
+            DB_UNIQUE,
+            DB_VALUE_TYPE,
+        ],
+    )
+}
 
-    /// Attributes that are "metadata" related.  These might change one of the materialized views.
-    pub static ref METADATA_SQL_LIST: String = {
-        format!("({}, {}, {}, {}, {}, {}, {})",
-                DB_CARDINALITY,
-                DB_FULLTEXT,
-                DB_IDENT,
-                DB_INDEX,
-                DB_IS_COMPONENT,
-                DB_UNIQUE,
-                DB_VALUE_TYPE)
-    };
+/// Attributes that are "metadata" related.  These might change one of the materialized views.
+pub fn metadata_sql_list(ident_map: &IdentMap) -> String {
+    sql_list(
+        ident_map,
+        &[
+            DB_CARDINALITY,
+            DB_FULLTEXT,
+            DB_FULLTEXT_TOKENIZER,
+            DB_FULLTEXT_PREFIX,
+            DB_IDENT,
+            DB_INDEX,
+            DB_IS_COMPONENT,
+            DB_UNIQUE,
+            DB_VALUE_TYPE,
+        ],
+    )
 }
@@ -0,0 +1,270 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Excision permanently purges datoms, and their history, from the store.
+//!
+//! An excision is requested by transacting an excision entity:
+//!
+//! ```edn
+//! [{:db/excise 65536
+//!   :db.excise/attrs [:person/email]
+//!   :db.excise/beforeT 268435460}]
+//! ```
+//!
+//! If the target is an entity, its datoms are purged.  If the target is an attribute, every
+//! value of that attribute is purged.  `:db.excise/attrs` restricts an entity excision to the
+//! given attributes; `:db.excise/beforeT` and `:db.excise/before` restrict an excision to datoms
+//! transacted before the given transaction or instant.  Only datoms transacted before the
+//! excising transaction are ever purged.
+//!
+//! The excision entity itself is retained: it is the audit record of what was excised, and since
+//! it is an ordinary transaction it is synchronized to other devices, where transacting it purges
+//! the same datoms.
+//!
+//! Each partition's next entid is derived from the largest entid in the log, so the maxima are
+//! retired before anything is purged: otherwise excising the newest entity would make its entid
+//! available again.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use rusqlite;
+use rusqlite::types::ToSql;
+
+use core_traits::{Entid, TypedValue};
+
+use db_traits::errors::{DbErrorKind, Result};
+
+use edn::entities::OpType;
+
+use mentat_core::{DateTime, HasSchema, Schema, ToMicros, Utc};
+
+use crate::db;
+use crate::db::TypedSQLValue;
+use crate::entids;
+use crate::internal_types::AEVTrie;
+use crate::types::PartitionMap;
+use crate::watcher::TransactWatcher;
+
+/// What an excision purges.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ExcisionTarget {
+    /// Datoms about the given entity.
+    Entity(Entid),
+    /// Datoms asserting the given attribute, about any entity.
+    Attribute(Entid),
+}
+
+/// An excision requested by asserting `:db/excise` in a transaction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Excision {
+    /// The excision entity, retained as the audit record of this excision.
+    pub(crate) excision: Entid,
+    pub(crate) target: ExcisionTarget,
+    /// `:db.excise/attrs`: if present, only datoms asserting these attributes are purged.
+    pub(crate) attrs: Option<BTreeSet<Entid>>,
+    /// `:db.excise/beforeT`: if present, only datoms transacted before this tx are purged.
+    pub(crate) before_t: Option<Entid>,
+    /// `:db.excise/before`: if present, only datoms transacted before this instant are purged.
+    pub(crate) before: Option<DateTime<Utc>>,
+}
+
+/// Collect the excisions asserted in the given `aev_trie`, checking that each can be applied.
+pub(crate) fn excisions(
+    partition_map: &PartitionMap,
+    schema: &Schema,
+    aev_trie: &AEVTrie<'_>,
+) -> Result<Vec<Excision>> {
+    // Added values of the given attribute, by entity.
+    let added = |a: Entid| -> BTreeMap<Entid, Vec<&TypedValue>> {
+        aev_trie
+            .iter()
+            .filter(|((attr, _), _)| *attr == a)
+            .flat_map(|(_, evs)| evs.iter())
+            .map(|(&e, ars)| (e, ars.add.iter().collect()))
+            .collect()
+    };
+
+    let targets = added(entids::DB_EXCISE);
+    let attrs = added(entids::DB_EXCISE_ATTRS);
+    let before_ts = added(entids::DB_EXCISE_BEFORE_T);
+    let befores = added(entids::DB_EXCISE_BEFORE);
+
+    let mut excisions = vec![];
+    for (excision, vs) in targets {
+        for v in vs {
+            let target = match *v {
+                TypedValue::Ref(target) => target,
+                _ => unreachable!(), // The transactor has already checked value types.
+            };
+
+            let bad = |reason: &str| {
+                DbErrorKind::BadExcision(format!("excision {} of {}: {}", excision, target, reason))
+            };
+
+            match partition_map.values().find(|p| p.contains_entid(target)) {
+                Some(partition) if partition.allow_excision => (),
+                Some(_) => bail!(bad("entity is in a partition that does not allow excision")),
+                None => bail!(bad("entity has not been allocated")),
+            }
+
+            let attrs: Option<BTreeSet<Entid>> = attrs.get(&excision).map(|vs| {
+                vs.iter()
+                    .filter_map(|v| match **v {
+                        TypedValue::Ref(a) => Some(a),
+                        _ => None,
+                    })
+                    .collect()
+            });
+
+            let target = if schema.attribute_for_entid(target).is_some() {
+                if attrs.is_some() {
+                    bail!(bad(
+                        ":db.excise/attrs cannot restrict the excision of an attribute"
+                    ));
+                }
+                ExcisionTarget::Attribute(target)
+            } else if schema.get_ident(target).is_some() {
+                // Purging a :db/ident would leave the materialized idents behind.
+                bail!(bad("entity has a :db/ident"))
+            } else {
+                ExcisionTarget::Entity(target)
+            };
+
+            let before_t =
+                before_ts
+                    .get(&excision)
+                    .and_then(|vs| vs.first())
+                    .and_then(|v| match **v {
+                        TypedValue::Long(t) => Some(t),
+                        _ => None,
+                    });
+            let before = befores
+                .get(&excision)
+                .and_then(|vs| vs.first())
+                .and_then(|v| match **v {
+                    TypedValue::Instant(i) => Some(i),
+                    _ => None,
+                });
+
+            excisions.push(Excision {
+                excision,
+                target,
+                attrs,
+                before_t,
+                before,
+            });
+        }
+    }
+
+    Ok(excisions)
+}
+
+/// Purge the datoms selected by each of the given `excisions` from `datoms`, from every timeline
+/// of `timelined_transactions`, and from `fulltext_values`.
+///
+/// Purged datoms that were current are reported to `watcher` as retractions, so that attribute
+/// caches drop them.
+pub(crate) fn excise<W>(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    tx_id: Entid,
+    excisions: &[Excision],
+    watcher: &mut W,
+) -> Result<()>
+where
+    W: TransactWatcher,
+{
+    if !excisions.is_empty() {
+        db::retire_partition_maxima(conn)?;
+    }
+
+    for excision in excisions {
+        let mut clauses: Vec<String> = vec![];
+        let mut params: Vec<Box<dyn ToSql>> = vec![];
+
+        match excision.target {
+            ExcisionTarget::Entity(e) => {
+                clauses.push("e = ?".to_string());
+                params.push(Box::new(e));
+            }
+            ExcisionTarget::Attribute(a) => {
+                clauses.push("a = ?".to_string());
+                params.push(Box::new(a));
+            }
+        }
+
+        if let Some(ref attrs) = excision.attrs {
+            if attrs.is_empty() {
+                continue;
+            }
+            clauses.push(format!("a IN {}", crate::repeat_values(attrs.len(), 1)));
+            params.extend(attrs.iter().map(|&a| Box::new(a) as Box<dyn ToSql>));
+        }
+
+        // Never purge what the excising transaction itself asserts.
+        let before_t = excision.before_t.map_or(tx_id, |t| t.min(tx_id));
+        clauses.push("tx < ?".to_string());
+        params.push(Box::new(before_t));
+
+        if let Some(before) = excision.before {
+            clauses.push("tx IN (SELECT e FROM datoms WHERE a = ? AND v < ?)".to_string());
+            params.push(Box::new(entids::DB_TX_INSTANT));
+            params.push(Box::new(before.to_micros()));
+        }
+
+        let condition = clauses.join(" AND ");
+        let params: Vec<&dyn ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        // Fulltext values are shared by value: collect those the purged datoms reference, so that
+        // we can drop the ones that nothing references once we're done.
+        let mut fulltext_rowids: BTreeSet<i64> = BTreeSet::new();
+
+        {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT e, a, v, value_type_tag FROM all_datoms WHERE {}",
+                condition
+            ))?;
+            let mut rows = stmt.query(&params[..])?;
+            while let Some(row) = rows.next()? {
+                let e: Entid = row.get(0)?;
+                let a: Entid = row.get(1)?;
+                let v = TypedValue::from_sql_value_pair(row.get(2)?, row.get(3)?)?;
+                watcher.datom(OpType::Retract, e, a, &v);
+            }
+        }
+
+        {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT a, v FROM timelined_transactions WHERE {}",
+                condition
+            ))?;
+            let mut rows = stmt.query(&params[..])?;
+            while let Some(row) = rows.next()? {
+                let a: Entid = row.get(0)?;
+                if schema.attribute_for_entid(a).is_some_and(|a| a.fulltext) {
+                    fulltext_rowids.insert(row.get(1)?);
+                }
+            }
+        }
+
+        conn.execute(
+            &format!("DELETE FROM datoms WHERE {}", condition),
+            &params[..],
+        )?;
+        conn.execute(
+            &format!("DELETE FROM timelined_transactions WHERE {}", condition),
+            &params[..],
+        )?;
+
+        db::delete_unreferenced_fulltext_values(conn, schema, &fulltext_rowids)?;
+    }
+
+    Ok(())
+}
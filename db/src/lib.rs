@@ -21,6 +21,7 @@ extern crate log;
 extern crate serde_derive;
 
 extern crate petgraph;
+extern crate regex;
 extern crate rusqlite;
 extern crate tabwriter;
 extern crate time;
@@ -46,8 +47,10 @@ pub mod debug;
 mod add_retract_alter_set;
 mod bootstrap;
 pub mod cache;
+pub mod compaction;
 pub mod db;
 pub mod entids;
+mod excision;
 pub mod internal_types; // pub because we need them for building entities programmatically.
 mod metadata;
 mod schema;
@@ -56,14 +59,21 @@ mod tx;
 mod tx_checking;
 pub mod tx_observer;
 pub mod types;
+mod undo;
 mod upsert_resolution;
 mod watcher;
 
 // Export these for reference from sync code and tests.
 pub use crate::bootstrap::{TX0, USER0, V1_PARTS};
 
+pub use crate::compaction::{compact_history, resolve_history_bound, HistoryBound};
+
 pub static TIMELINE_MAIN: i64 = 0;
 
+/// While a named timeline is active, the main timeline's transactions after that timeline's base
+/// are kept here.
+pub static TIMELINE_STASH: i64 = -1;
+
 pub use crate::schema::{AttributeBuilder, AttributeValidation};
 
 pub use crate::bootstrap::CORE_SCHEMA_VERSION;
@@ -72,7 +82,7 @@ use edn::symbols;
 
 pub use crate::entids::DB_SCHEMA_CORE;
 
-pub use crate::db::{new_connection, TypedSQLValue};
+pub use crate::db::{new_connection, read_attached_schema, read_statistics, TypedSQLValue};
 
 #[cfg(feature = "sqlcipher")]
 pub use db::{change_encryption_key, new_connection_with_key};
@@ -81,6 +91,8 @@ pub use crate::watcher::TransactWatcher;
 
 pub use crate::tx::{transact, transact_terms};
 
+pub use crate::undo::undo;
+
 pub use crate::tx_observer::{InProgressObserverTransactWatcher, TxObservationService, TxObserver};
 
 pub use crate::types::{AttributeSet, Partition, PartitionMap, TransactableValue, DB};
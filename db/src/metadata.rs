@@ -36,7 +36,7 @@ use edn::symbols;
 
 use core_traits::{attribute, Entid, TypedValue, ValueType};
 
-use mentat_core::{AttributeMap, Schema};
+use mentat_core::{AttributeMap, IdentMap, Schema};
 
 use crate::schema::{AttributeBuilder, AttributeValidation};
 
@@ -98,6 +98,7 @@ impl MetadataReport {
 /// Returns a set of attribute retractions which do not involve schema-defining attributes.
 fn update_attribute_map_from_schema_retractions(
     attribute_map: &mut AttributeMap,
+    ident_map: &IdentMap,
     retractions: Vec<EAV>,
     ident_retractions: &BTreeMap<Entid, symbols::Keyword>,
 ) -> Result<Vec<EAV>> {
@@ -110,7 +111,7 @@ fn update_attribute_map_from_schema_retractions(
     // Filter out sets of schema altering retractions.
     let mut eas = BTreeMap::new();
     for (e, a, v) in retractions.into_iter() {
-        if entids::is_a_schema_attribute(a) {
+        if entids::is_a_schema_attribute(ident_map, a) {
             eas.entry(e).or_insert_with(Vec::new).push(a);
             suspect_retractions.push((e, a, v));
         } else {
@@ -163,6 +164,7 @@ fn update_attribute_map_from_schema_retractions(
 /// Returns a report summarizing the mutations that were applied.
 pub fn update_attribute_map_from_entid_triples(
     attribute_map: &mut AttributeMap,
+    ident_map: &IdentMap,
     assertions: Vec<EAV>,
     retractions: Vec<EAV>,
 ) -> Result<MetadataReport> {
@@ -176,6 +178,14 @@ pub fn update_attribute_map_from_entid_triples(
             .unwrap_or_else(AttributeBuilder::default)
     }
 
+    // The version 2 core idents are wherever the store allocated them.
+    let core_entid = |entid| entids::core_entid(ident_map, entid);
+    let tokenizer = core_entid(entids::DB_FULLTEXT_TOKENIZER);
+    let prefix = core_entid(entids::DB_FULLTEXT_PREFIX);
+    let unicode61 = core_entid(entids::DB_FULLTEXT_TOKENIZER_UNICODE61);
+    let porter = core_entid(entids::DB_FULLTEXT_TOKENIZER_PORTER);
+    let trigram = core_entid(entids::DB_FULLTEXT_TOKENIZER_TRIGRAM);
+
     // Group mutations by impacted entid.
     let mut builders: BTreeMap<Entid, AttributeBuilder> = BTreeMap::new();
 
@@ -226,6 +236,10 @@ pub fn update_attribute_map_from_entid_triples(
                 bail!(DbErrorKind::BadSchemaAssertion(format!("Retracting attribute {} for entity {} not permitted.", attr, entid)));
             },
 
+            a if Some(a) == tokenizer || Some(a) == prefix => {
+                bail!(DbErrorKind::BadSchemaAssertion(format!("Retracting attribute {} for entity {} not permitted.", attr, entid)));
+            },
+
             _ => {
                 bail!(DbErrorKind::BadSchemaAssertion(format!("Do not recognize attribute {} for entid {}", attr, entid)))
             }
@@ -283,6 +297,22 @@ pub fn update_attribute_map_from_entid_triples(
                 }
             },
 
+            a if Some(a) == tokenizer => {
+                match *value {
+                    TypedValue::Ref(v) if Some(v) == unicode61 => { builder.fulltext_tokenizer(attribute::FulltextTokenizer::Unicode61); },
+                    TypedValue::Ref(v) if Some(v) == porter => { builder.fulltext_tokenizer(attribute::FulltextTokenizer::Porter); },
+                    TypedValue::Ref(v) if Some(v) == trigram => { builder.fulltext_tokenizer(attribute::FulltextTokenizer::Trigram); },
+                    _ => bail!(DbErrorKind::BadSchemaAssertion(format!("Expected [... :db.fulltext/tokenizer :db.fulltext.tokenizer/unicode61|:db.fulltext.tokenizer/porter|:db.fulltext.tokenizer/trigram] but got [... :db.fulltext/tokenizer {:?}]", value)))
+                }
+            },
+
+            a if Some(a) == prefix => {
+                match *value {
+                    TypedValue::Boolean(x) => { builder.fulltext_prefix(x); },
+                    _ => bail!(DbErrorKind::BadSchemaAssertion(format!("Expected [... :db.fulltext/prefix true|false] but got [... :db.fulltext/prefix {:?}]", value)))
+                }
+            },
+
             entids::DB_IS_COMPONENT => {
                 match *value {
                     TypedValue::Boolean(x) => { builder.component(x); },
@@ -349,11 +379,14 @@ pub fn update_attribute_map_from_entid_triples(
 /// This layer enforces that ident assertions of the form [entid :db/ident ...] (as distinct from
 /// attribute assertions) are present and correct.
 ///
-/// This is suitable for mutating a `Schema` from an applied transaction.
+/// This is suitable for mutating a `Schema` from an applied transaction.  The core attributes and
+/// values are recognized by the given `ident_map`, which should be that of the schema the
+/// transaction was resolved against.
 ///
 /// Returns a report summarizing the mutations that were applied.
 pub fn update_schema_from_entid_quadruples<U>(
     schema: &mut Schema,
+    ident_map: &IdentMap,
     assertions: U,
 ) -> Result<MetadataReport>
 where
@@ -400,6 +433,7 @@ where
     // This operation consumes our current list of attribute retractions, producing a filtered one.
     let non_schema_retractions = update_attribute_map_from_schema_retractions(
         &mut schema.attribute_map,
+        ident_map,
         retracted_triples.collect(),
         &ident_set.retracted,
     )?;
@@ -407,6 +441,7 @@ where
     // Now we process all other retractions.
     let report = update_attribute_map_from_entid_triples(
         &mut schema.attribute_map,
+        ident_map,
         asserted_triples.chain(altered_triples).collect(),
         non_schema_retractions,
     )?;
@@ -50,6 +50,15 @@ impl AttributeValidation for Attribute {
                 ident()
             )))
         }
+        if !self.fulltext
+            && (self.fulltext_tokenizer != attribute::FulltextTokenizer::default()
+                || self.fulltext_prefix)
+        {
+            bail!(DbErrorKind::BadSchemaAssertion(format!(
+                ":db.fulltext/tokenizer or :db.fulltext/prefix without :db/fulltext true for entid: {}",
+                ident()
+            )))
+        }
         if self.fulltext && !self.index {
             bail!(DbErrorKind::BadSchemaAssertion(format!(
                 ":db/fulltext true without :db/index true for entid: {}",
@@ -92,6 +101,8 @@ pub struct AttributeBuilder {
     pub unique: Option<Option<attribute::Unique>>,
     pub index: Option<bool>,
     pub fulltext: Option<bool>,
+    pub fulltext_tokenizer: Option<attribute::FulltextTokenizer>,
+    pub fulltext_prefix: Option<bool>,
     pub component: Option<bool>,
     pub no_history: Option<bool>,
 }
@@ -152,6 +163,16 @@ impl AttributeBuilder {
         self
     }
 
+    pub fn fulltext_tokenizer(&mut self, tokenizer: attribute::FulltextTokenizer) -> &mut Self {
+        self.fulltext_tokenizer = Some(tokenizer);
+        self
+    }
+
+    pub fn fulltext_prefix(&mut self, prefix: bool) -> &mut Self {
+        self.fulltext_prefix = Some(prefix);
+        self
+    }
+
     pub fn component(&mut self, component: bool) -> &mut Self {
         self.component = Some(component);
         self
@@ -182,6 +203,12 @@ impl AttributeBuilder {
                 "Schema alteration must not set :db/fulltext".into()
             ));
         }
+        if self.fulltext_tokenizer.is_some() || self.fulltext_prefix.is_some() {
+            bail!(DbErrorKind::BadSchemaAssertion(
+                "Schema alteration must not set :db.fulltext/tokenizer or :db.fulltext/prefix"
+                    .into()
+            ));
+        }
         Ok(())
     }
 
@@ -193,6 +220,12 @@ impl AttributeBuilder {
         if let Some(fulltext) = self.fulltext {
             attribute.fulltext = fulltext;
         }
+        if let Some(tokenizer) = self.fulltext_tokenizer {
+            attribute.fulltext_tokenizer = tokenizer;
+        }
+        if let Some(prefix) = self.fulltext_prefix {
+            attribute.fulltext_prefix = prefix;
+        }
         if let Some(multival) = self.multival {
             attribute.multival = multival;
         }
@@ -316,6 +349,7 @@ impl SchemaBuilding for Schema {
             Schema::from_ident_map_and_attribute_map(ident_map, AttributeMap::default())?;
         let metadata_report = metadata::update_attribute_map_from_entid_triples(
             &mut schema.attribute_map,
+            &schema.ident_map,
             entid_assertions?,
             // No retractions.
             vec![],
@@ -417,6 +451,8 @@ mod test {
                 index: false,
                 value_type: ValueType::Boolean,
                 fulltext: false,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: None,
                 multival: false,
                 component: false,
@@ -432,6 +468,8 @@ mod test {
                 index: true,
                 value_type: ValueType::Long,
                 fulltext: false,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: Some(attribute::Unique::Value),
                 multival: false,
                 component: false,
@@ -447,6 +485,8 @@ mod test {
                 index: true,
                 value_type: ValueType::Ref,
                 fulltext: false,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: Some(attribute::Unique::Identity),
                 multival: false,
                 component: false,
@@ -462,6 +502,8 @@ mod test {
                 index: false,
                 value_type: ValueType::Ref,
                 fulltext: false,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: None,
                 multival: false,
                 component: true,
@@ -477,6 +519,8 @@ mod test {
                 index: true,
                 value_type: ValueType::String,
                 fulltext: true,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: None,
                 multival: false,
                 component: false,
@@ -500,6 +544,8 @@ mod test {
                 index: false,
                 value_type: ValueType::Boolean,
                 fulltext: false,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: Some(attribute::Unique::Value),
                 multival: false,
                 component: false,
@@ -530,6 +576,8 @@ mod test {
                 index: false,
                 value_type: ValueType::Long,
                 fulltext: false,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: Some(attribute::Unique::Identity),
                 multival: false,
                 component: false,
@@ -560,6 +608,8 @@ mod test {
                 index: false,
                 value_type: ValueType::Boolean,
                 fulltext: false,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: None,
                 multival: false,
                 component: true,
@@ -591,6 +641,8 @@ mod test {
                 index: false,
                 value_type: ValueType::String,
                 fulltext: true,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: None,
                 multival: false,
                 component: false,
@@ -620,6 +672,8 @@ mod test {
                 index: true,
                 value_type: ValueType::Long,
                 fulltext: true,
+                fulltext_tokenizer: attribute::FulltextTokenizer::Unicode61,
+                fulltext_prefix: false,
                 unique: None,
                 multival: false,
                 component: false,
@@ -8,15 +8,27 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+//! Timelines are sequences of transactions.  The main timeline is the one materialized in
+//! `datoms`; sync moves transactions off it when rewinding local changes.
+//!
+//! Named timelines fork from the main timeline after a given transaction.  Switching to a named
+//! timeline rewinds the main timeline to that transaction, keeping the rewound transactions aside,
+//! and replays the named timeline's transactions in its place, so that transacting and querying
+//! work against the named timeline.  Switching back to `main` does the reverse.  Transactions keep
+//! their tx ids, and entids are never reused across timelines.  Merging a named timeline
+//! transacts its changes anew on top of the main timeline; discarding it forgets them.
+
+use std::collections::BTreeSet;
 use std::ops::RangeFrom;
 
+use rusqlite::types::ToSql;
 use rusqlite::{self, params_from_iter};
 
 use db_traits::errors::{DbErrorKind, Result};
 
 use core_traits::{Entid, KnownEntid, TypedValue};
 
-use mentat_core::Schema;
+use mentat_core::{HasSchema, Schema, TxReport};
 
 use edn::InternSet;
 
@@ -31,7 +43,21 @@ use crate::types::PartitionMap;
 
 use crate::internal_types::{Term, TermWithoutTempIds};
 
-use crate::watcher::NullWatcher;
+use crate::watcher::{NullWatcher, TransactWatcher};
+
+/// The name by which the main timeline is switched to.
+pub const TIMELINE_MAIN_NAME: &str = "main";
+
+/// A named timeline.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Timeline {
+    pub id: Entid,
+    pub name: String,
+    /// The main timeline transaction this timeline forks from.
+    pub base: Entid,
+    /// Whether this timeline is materialized in `datoms` in place of the main timeline.
+    pub active: bool,
+}
 
 /// Collects a supplied tx range into an DESC ordered Vec of valid txs,
 /// ensuring they all belong to the same timeline.
@@ -42,7 +68,7 @@ fn collect_ordered_txs_to_move(
 ) -> Result<Vec<Entid>> {
     let mut stmt = conn.prepare("SELECT tx, timeline FROM timelined_transactions WHERE tx >= ? AND timeline = ? GROUP BY tx ORDER BY tx DESC")?;
     let mut rows = stmt.query_and_then(
-        &[&txs_from.start, &timeline],
+        [&txs_from.start, &timeline],
         |row: &rusqlite::Row| -> Result<(Entid, Entid)> { Ok((row.get(0)?, row.get(1)?)) },
     )?;
 
@@ -69,16 +95,27 @@ fn collect_ordered_txs_to_move(
     Ok(txs)
 }
 
+/// Collects the txs of the given timeline into an ASC ordered Vec.
+fn collect_timeline_txs(conn: &rusqlite::Connection, timeline: Entid) -> Result<Vec<Entid>> {
+    let mut stmt = conn.prepare(
+        "SELECT tx FROM timelined_transactions WHERE timeline = ? GROUP BY tx ORDER BY tx ASC",
+    )?;
+    let rows = stmt.query_and_then([&timeline], |row| -> Result<Entid> { Ok(row.get(0)?) })?;
+    rows.collect()
+}
+
 fn move_transactions_to(
     conn: &rusqlite::Connection,
     tx_ids: &[Entid],
+    from_timeline: Entid,
     new_timeline: Entid,
 ) -> Result<()> {
     // Move specified transactions over to a specified timeline.
     conn.execute(
         &format!(
-            "UPDATE timelined_transactions SET timeline = {} WHERE tx IN {}",
+            "UPDATE timelined_transactions SET timeline = {} WHERE timeline = {} AND tx IN {}",
             new_timeline,
+            from_timeline,
             crate::repeat_values(tx_ids.len(), 1)
         ),
         params_from_iter(tx_ids.iter()),
@@ -87,7 +124,7 @@ fn move_transactions_to(
 }
 
 fn remove_tx_from_datoms(conn: &rusqlite::Connection, tx_id: Entid) -> Result<()> {
-    conn.execute("DELETE FROM datoms WHERE e = ?", &[&tx_id])?;
+    conn.execute("DELETE FROM datoms WHERE e = ?", [&tx_id])?;
     Ok(())
 }
 
@@ -95,39 +132,168 @@ fn is_timeline_empty(conn: &rusqlite::Connection, timeline: Entid) -> Result<boo
     let mut stmt = conn.prepare(
         "SELECT timeline FROM timelined_transactions WHERE timeline = ? GROUP BY timeline",
     )?;
-    let rows = stmt.query_and_then(&[&timeline], |row| -> Result<i64> { Ok(row.get(0)?) })?;
+    let rows = stmt.query_and_then([&timeline], |row| -> Result<i64> { Ok(row.get(0)?) })?;
     Ok(rows.count() == 0)
 }
 
-/// Get terms for tx_id, reversing them in meaning (swap add & retract).
-fn reversed_terms_for(
+/// Get terms for tx_id on the given timeline, reversing them in meaning (swap add & retract) if
+/// requested.  Fulltext values are interpolated, so that the transactor can re-intern them.
+pub(crate) fn terms_for(
     conn: &rusqlite::Connection,
+    schema: &Schema,
     tx_id: Entid,
+    timeline: Entid,
+    reversed: bool,
 ) -> Result<Vec<TermWithoutTempIds>> {
-    let mut stmt = conn.prepare("SELECT e, a, v, value_type_tag, tx, added FROM timelined_transactions WHERE tx = ? AND timeline = ? ORDER BY tx DESC")?;
-    let rows = stmt.query_and_then(
-        &[&tx_id, &crate::TIMELINE_MAIN],
-        |row| -> Result<TermWithoutTempIds> {
-            let op = if row.get(5)? {
-                OpType::Retract
-            } else {
-                OpType::Add
-            };
-            Ok(Term::AddOrRetract(
-                op,
-                KnownEntid(row.get(0)?),
-                row.get(1)?,
-                TypedValue::from_sql_value_pair(row.get(2)?, row.get(3)?)?,
-            ))
-        },
-    )?;
+    let mut stmt = conn.prepare("SELECT e, a, v, value_type_tag, added FROM timelined_transactions WHERE tx = ? AND timeline = ?")?;
+    let mut fulltext_stmt = conn.prepare("SELECT text FROM fulltext_values WHERE rowid = ?")?;
+    let rows = stmt.query_and_then([&tx_id, &timeline], |row| -> Result<TermWithoutTempIds> {
+        let a: Entid = row.get(1)?;
+        let op = if row.get::<_, bool>(4)? != reversed {
+            OpType::Add
+        } else {
+            OpType::Retract
+        };
+        let v = if schema.attribute_for_entid(a).is_some_and(|a| a.fulltext) {
+            let rowid: i64 = row.get(2)?;
+            let text: String = fulltext_stmt.query_row([&rowid], |row| row.get(0))?;
+            TypedValue::typed_string(text)
+        } else {
+            TypedValue::from_sql_value_pair(row.get(2)?, row.get(3)?)?
+        };
+        Ok(Term::AddOrRetract(op, KnownEntid(row.get(0)?), a, v))
+    })?;
+    rows.collect()
+}
+
+/// The schema as changed by the transactions processed so far.
+fn current_schema<'s>(schema: &'s Schema, new_schema: &'s Option<Schema>) -> &'s Schema {
+    new_schema.as_ref().unwrap_or(schema)
+}
+
+/// Rewind the given main timeline transactions, in DESC order, and move them to `new_timeline`.
+fn rewind_main_timeline<W>(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    partition_map: &PartitionMap,
+    txs_to_move: &[Entid],
+    new_timeline: Entid,
+    mut watcher: W,
+) -> Result<(Option<Schema>, W)>
+where
+    W: TransactWatcher,
+{
+    let mut last_schema = None;
+    for tx_id in txs_to_move {
+        let reversed_terms = terms_for(
+            conn,
+            current_schema(schema, &last_schema),
+            *tx_id,
+            crate::TIMELINE_MAIN,
+            true,
+        )?;
+
+        // Rewind schema and datoms.
+        let (report, _, new_schema, next_watcher) = transact_terms_with_action(
+            conn,
+            partition_map.clone(),
+            current_schema(schema, &last_schema),
+            current_schema(schema, &last_schema),
+            watcher,
+            reversed_terms.into_iter().map(|t| t.rewrap()),
+            InternSet::new(),
+            TransactorAction::Materialize,
+        )?;
+        watcher = next_watcher;
+
+        // Rewind operation generated a 'tx' and a 'txInstant' assertion, which got
+        // inserted into the 'datoms' table (due to TransactorAction::Materialize).
+        // This is problematic. If we transact a few more times, the transactor will
+        // generate the same 'tx', but with a different 'txInstant'.
+        // The end result will be a transaction which has a phantom
+        // retraction of a txInstant, since transactor operates against the state of
+        // 'datoms', and not against the 'transactions' table.
+        // A quick workaround is to just remove the bad txInstant datom.
+        // See test_clashing_tx_instants test case.
+        remove_tx_from_datoms(conn, report.tx_id)?;
+
+        // Datoms restored by the rewind were asserted by an earlier transaction.
+        conn.execute(
+            "UPDATE datoms SET tx = COALESCE((SELECT MAX(t.tx) FROM timelined_transactions AS t
+               WHERE t.timeline = ? AND t.added IS 1 AND t.tx < ?
+                 AND t.e = datoms.e AND t.a = datoms.a
+                 AND t.value_type_tag = datoms.value_type_tag AND t.v = datoms.v), ?)
+             WHERE tx = ?",
+            [&crate::TIMELINE_MAIN, tx_id, tx_id, &report.tx_id],
+        )?;
+
+        if new_schema.is_some() {
+            last_schema = new_schema;
+        }
+    }
+
+    // Move transactions over to the target timeline.
+    if !txs_to_move.is_empty() {
+        move_transactions_to(conn, txs_to_move, crate::TIMELINE_MAIN, new_timeline)?;
+    }
+
+    Ok((last_schema, watcher))
+}
+
+/// Replay the transactions of `timeline` on top of the main timeline, keeping their tx ids, and
+/// move them to the main timeline.
+fn replay_onto_main_timeline<W>(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    partition_map: &PartitionMap,
+    timeline: Entid,
+    mut watcher: W,
+) -> Result<(Option<Schema>, W)>
+where
+    W: TransactWatcher,
+{
+    let txs_to_move = collect_timeline_txs(conn, timeline)?;
+
+    let mut last_schema = None;
+    for tx_id in &txs_to_move {
+        let terms = terms_for(
+            conn,
+            current_schema(schema, &last_schema),
+            *tx_id,
+            timeline,
+            false,
+        )?;
+
+        let (report, _, new_schema, next_watcher) = transact_terms_with_action(
+            conn,
+            partition_map.clone(),
+            current_schema(schema, &last_schema),
+            current_schema(schema, &last_schema),
+            watcher,
+            terms.into_iter().map(|t| t.rewrap()),
+            InternSet::new(),
+            TransactorAction::Materialize,
+        )?;
+        watcher = next_watcher;
 
-    let mut terms = vec![];
+        // As when rewinding, drop the generated 'txInstant'; the replayed transaction carries its
+        // own, and its datoms belong to it.
+        remove_tx_from_datoms(conn, report.tx_id)?;
+        conn.execute(
+            "UPDATE datoms SET tx = ? WHERE tx = ?",
+            [tx_id, &report.tx_id],
+        )?;
 
-    for row in rows {
-        terms.push(row?);
+        if new_schema.is_some() {
+            last_schema = new_schema;
+        }
     }
-    Ok(terms)
+
+    if !txs_to_move.is_empty() {
+        move_transactions_to(conn, &txs_to_move, timeline, crate::TIMELINE_MAIN)?;
+    }
+
+    Ok((last_schema, watcher))
 }
 
 /// Move specified transaction RangeFrom off of main timeline.
@@ -153,41 +319,281 @@ pub fn move_from_main_timeline(
 
     let txs_to_move = collect_ordered_txs_to_move(conn, txs_from, crate::TIMELINE_MAIN)?;
 
+    let (last_schema, _) = rewind_main_timeline(
+        conn,
+        schema,
+        &partition_map,
+        &txs_to_move,
+        new_timeline,
+        NullWatcher(),
+    )?;
+
+    Ok((last_schema, db::read_partition_map(conn)?))
+}
+
+fn read_timelines(
+    conn: &rusqlite::Connection,
+    condition: &str,
+    params: &[&dyn ToSql],
+) -> Result<Vec<Timeline>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT timeline, name, base, active FROM timelines WHERE {} ORDER BY timeline ASC",
+        condition
+    ))?;
+    let rows = stmt.query_and_then(params, |row| -> Result<Timeline> {
+        Ok(Timeline {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            base: row.get(2)?,
+            active: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// All named timelines.
+pub fn timelines(conn: &rusqlite::Connection) -> Result<Vec<Timeline>> {
+    read_timelines(conn, "1", &[])
+}
+
+/// The named timeline materialized in place of the main timeline, if any.
+pub fn active_timeline(conn: &rusqlite::Connection) -> Result<Option<Timeline>> {
+    Ok(read_timelines(conn, "active IS NOT 0", &[])?
+        .into_iter()
+        .next())
+}
+
+fn timeline_named(conn: &rusqlite::Connection, name: &str) -> Result<Timeline> {
+    read_timelines(conn, "name = ?", &[&name])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| DbErrorKind::TimelinesUnknown(name.to_string()).into())
+}
+
+/// Create a named timeline forking from the main timeline after transaction `base`.  The new
+/// timeline has no transactions of its own until it is switched to and transacted against.
+pub fn fork_timeline(conn: &rusqlite::Connection, name: &str, base: Entid) -> Result<Timeline> {
+    if let Some(active) = active_timeline(conn)? {
+        bail!(DbErrorKind::TimelinesActive(active.name));
+    }
+    if name == TIMELINE_MAIN_NAME || !read_timelines(conn, "name = ?", &[&name])?.is_empty() {
+        bail!(DbErrorKind::TimelinesExists(name.to_string()));
+    }
+    let is_main_tx: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM timelined_transactions WHERE timeline = ? AND tx = ?)",
+        [&crate::TIMELINE_MAIN, &base],
+        |row| row.get(0),
+    )?;
+    if !is_main_tx {
+        bail!(DbErrorKind::TimelinesInvalidRange);
+    }
+
+    conn.execute(
+        "INSERT INTO timelines (timeline, name, base)
+         SELECT COALESCE(MAX(timeline), 0) + 1, ?, ? FROM timelines",
+        [&name as &dyn ToSql, &base],
+    )?;
+    timeline_named(conn, name)
+}
+
+/// Make the named timeline, or the main timeline if `name` is `TIMELINE_MAIN_NAME`, the one
+/// materialized in `datoms`.  Changes to `datoms` are reported to `watcher`.
+pub fn switch_timeline<W>(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    partition_map: PartitionMap,
+    name: &str,
+    watcher: W,
+) -> Result<(Option<Schema>, PartitionMap)>
+where
+    W: TransactWatcher,
+{
+    let target = if name == TIMELINE_MAIN_NAME {
+        None
+    } else {
+        Some(timeline_named(conn, name)?)
+    };
+    let (last_schema, _) = switch_to(conn, schema, &partition_map, target, watcher)?;
+    Ok((last_schema, db::read_partition_map(conn)?))
+}
+
+fn switch_to<W>(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    partition_map: &PartitionMap,
+    target: Option<Timeline>,
+    mut watcher: W,
+) -> Result<(Option<Schema>, W)>
+where
+    W: TransactWatcher,
+{
+    let active = active_timeline(conn)?;
+    if target.as_ref().map(|t| t.id) == active.as_ref().map(|t| t.id) {
+        return Ok((None, watcher));
+    }
+
     let mut last_schema = None;
-    for tx_id in &txs_to_move {
-        let reversed_terms = reversed_terms_for(conn, *tx_id)?;
 
-        // Rewind schema and datoms.
-        let (report, _, new_schema, _) = transact_terms_with_action(
+    // Back to main: rewind the active timeline, and replay what we kept aside.
+    if let Some(active) = active {
+        let txs = collect_ordered_txs_after(conn, active.base)?;
+        let (new_schema, w) =
+            rewind_main_timeline(conn, schema, partition_map, &txs, active.id, watcher)?;
+        last_schema = new_schema.or(last_schema);
+        let (new_schema, w) = replay_onto_main_timeline(
             conn,
-            partition_map.clone(),
-            schema,
-            schema,
-            NullWatcher(),
-            reversed_terms.into_iter().map(|t| t.rewrap()),
-            InternSet::new(),
-            TransactorAction::Materialize,
+            current_schema(schema, &last_schema),
+            partition_map,
+            crate::TIMELINE_STASH,
+            w,
         )?;
+        last_schema = new_schema.or(last_schema);
+        watcher = w;
+        conn.execute("UPDATE timelines SET active = 0", rusqlite::params![])?;
+    }
 
-        // Rewind operation generated a 'tx' and a 'txInstant' assertion, which got
-        // inserted into the 'datoms' table (due to TransactorAction::Materialize).
-        // This is problematic. If we transact a few more times, the transactor will
-        // generate the same 'tx', but with a different 'txInstant'.
-        // The end result will be a transaction which has a phantom
-        // retraction of a txInstant, since transactor operates against the state of
-        // 'datoms', and not against the 'transactions' table.
-        // A quick workaround is to just remove the bad txInstant datom.
-        // See test_clashing_tx_instants test case.
-        remove_tx_from_datoms(conn, report.tx_id)?;
-        last_schema = new_schema;
+    // Then out to the target: rewind main to its base, and replay it.
+    if let Some(target) = target {
+        let txs = collect_ordered_txs_after(conn, target.base)?;
+        let (new_schema, w) = rewind_main_timeline(
+            conn,
+            current_schema(schema, &last_schema),
+            partition_map,
+            &txs,
+            crate::TIMELINE_STASH,
+            watcher,
+        )?;
+        last_schema = new_schema.or(last_schema);
+        let (new_schema, w) = replay_onto_main_timeline(
+            conn,
+            current_schema(schema, &last_schema),
+            partition_map,
+            target.id,
+            w,
+        )?;
+        last_schema = new_schema.or(last_schema);
+        watcher = w;
+        conn.execute(
+            "UPDATE timelines SET active = 1 WHERE timeline = ?",
+            [&target.id],
+        )?;
     }
 
-    // Move transactions over to the target timeline.
-    move_transactions_to(conn, &txs_to_move, new_timeline)?;
+    Ok((last_schema, watcher))
+}
+
+/// Main timeline transactions after `base`, in DESC order.
+fn collect_ordered_txs_after(conn: &rusqlite::Connection, base: Entid) -> Result<Vec<Entid>> {
+    let mut stmt = conn.prepare("SELECT tx FROM timelined_transactions WHERE timeline = ? AND tx > ? GROUP BY tx ORDER BY tx DESC")?;
+    let rows = stmt.query_and_then([&crate::TIMELINE_MAIN, &base], |row| -> Result<Entid> {
+        Ok(row.get(0)?)
+    })?;
+    rows.collect()
+}
+
+/// Transact the changes made on the named timeline on top of the main timeline, as new
+/// transactions, and forget the named timeline.  The main timeline is switched to first.
+pub fn merge_timeline<W>(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    partition_map: PartitionMap,
+    name: &str,
+    watcher: W,
+) -> Result<(Vec<TxReport>, Option<Schema>, PartitionMap)>
+where
+    W: TransactWatcher,
+{
+    let timeline = timeline_named(conn, name)?;
+    let (mut last_schema, mut watcher) = switch_to(conn, schema, &partition_map, None, watcher)?;
+    let mut partition_map = db::read_partition_map(conn)?;
+
+    let mut reports = vec![];
+    for tx_id in collect_timeline_txs(conn, timeline.id)? {
+        let terms: Vec<TermWithoutTempIds> = terms_for(
+            conn,
+            current_schema(schema, &last_schema),
+            tx_id,
+            timeline.id,
+            false,
+        )?
+        .into_iter()
+        // The merged transaction gets its own :db/txInstant.
+        .filter(|Term::AddOrRetract(_, e, a, _)| {
+            !(e.0 == tx_id && *a == crate::entids::DB_TX_INSTANT)
+        })
+        .collect();
+
+        let (report, next_partition_map, new_schema, next_watcher) = transact_terms_with_action(
+            conn,
+            partition_map,
+            current_schema(schema, &last_schema),
+            current_schema(schema, &last_schema),
+            watcher,
+            terms.into_iter().map(|t| t.rewrap()),
+            InternSet::new(),
+            TransactorAction::MaterializeAndCommit,
+        )?;
+        partition_map = next_partition_map;
+        watcher = next_watcher;
+        if new_schema.is_some() {
+            last_schema = new_schema;
+        }
+        reports.push(report);
+    }
+
+    forget_timeline(conn, schema, &timeline)?;
+
+    Ok((reports, last_schema, partition_map))
+}
+
+/// Forget the named timeline and its transactions.  The main timeline is switched to first.
+pub fn discard_timeline<W>(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    partition_map: PartitionMap,
+    name: &str,
+    watcher: W,
+) -> Result<(Option<Schema>, PartitionMap)>
+where
+    W: TransactWatcher,
+{
+    let timeline = timeline_named(conn, name)?;
+    let (last_schema, _) = switch_to(conn, schema, &partition_map, None, watcher)?;
+
+    forget_timeline(conn, current_schema(schema, &last_schema), &timeline)?;
 
     Ok((last_schema, db::read_partition_map(conn)?))
 }
 
+fn forget_timeline(
+    conn: &rusqlite::Connection,
+    schema: &Schema,
+    timeline: &Timeline,
+) -> Result<()> {
+    let mut fulltext_rowids: BTreeSet<i64> = BTreeSet::new();
+    {
+        let mut stmt =
+            conn.prepare("SELECT a, v FROM timelined_transactions WHERE timeline = ?")?;
+        let mut rows = stmt.query([&timeline.id])?;
+        while let Some(row) = rows.next()? {
+            let a: Entid = row.get(0)?;
+            if schema.attribute_for_entid(a).is_some_and(|a| a.fulltext) {
+                fulltext_rowids.insert(row.get(1)?);
+            }
+        }
+    }
+
+    // The timeline's entids and tx ids stay allocated.
+    db::retire_partition_maxima(conn)?;
+    conn.execute(
+        "DELETE FROM timelined_transactions WHERE timeline = ?",
+        [&timeline.id],
+    )?;
+    conn.execute("DELETE FROM timelines WHERE timeline = ?", [&timeline.id])?;
+
+    db::delete_unreferenced_fulltext_values(conn, schema, &fulltext_rowids)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -859,4 +1265,226 @@ mod tests {
         assert_matches!(conn.datoms(), "[]");
         assert_matches!(conn.transactions(), "[]");
     }
+
+    #[test]
+    fn test_named_timelines() {
+        let mut conn = TestConn::default();
+        conn.sanitized_partition_map();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/ident :person/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one :db/unique :db.unique/identity :db/index true}
+            {:db/ident :person/note :db/valueType :db.type/string :db/cardinality :db.cardinality/many :db/index true :db/fulltext true}
+        ]"#
+        );
+        let report = assert_transact!(
+            conn,
+            r#"[{:db/id "i" :person/name "Ivan" :person/note "main"}]"#
+        );
+        let ivan = *report.tempids.get("i").unwrap();
+        let base = report.tx_id;
+        let main = conn.datoms().to_edn();
+
+        let timeline = fork_timeline(&conn.sqlite, "draft", base).expect("forked");
+        assert_eq!(
+            vec![Timeline {
+                id: timeline.id,
+                name: "draft".to_string(),
+                base,
+                active: false,
+            }],
+            timelines(&conn.sqlite).expect("timelines")
+        );
+        assert_eq!(
+            DbErrorKind::TimelinesExists("draft".to_string()),
+            fork_timeline(&conn.sqlite, "draft", base)
+                .expect_err("duplicate name")
+                .kind()
+        );
+
+        // Switch to the draft, and change it.
+        let (new_schema, new_partition_map) = switch_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            "draft",
+            NullWatcher(),
+        )
+        .expect("switched");
+        update_conn(&mut conn, &new_schema, &new_partition_map);
+        assert_eq!(main, conn.datoms().to_edn());
+
+        let report = assert_transact!(
+            conn,
+            format!(
+                r#"[[:db/add {} :person/name "Vanya"]
+                    [:db/retract {} :person/note "main"]
+                    {{:db/id "p" :person/name "Petr" :person/note "draft"}}]"#,
+                ivan, ivan
+            )
+        );
+        let petr = *report.tempids.get("p").unwrap();
+        let draft_tx = report.tx_id;
+        let draft = conn.datoms().to_edn();
+        assert_eq!(
+            Some(timeline.id),
+            active_timeline(&conn.sqlite).expect("active").map(|t| t.id)
+        );
+
+        // Forking isn't allowed while the draft is active.
+        assert_eq!(
+            DbErrorKind::TimelinesActive("draft".to_string()),
+            fork_timeline(&conn.sqlite, "other", base)
+                .expect_err("active")
+                .kind()
+        );
+
+        // Back on main, nothing changed; entids and tx ids used by the draft aren't reused.
+        let (new_schema, new_partition_map) = switch_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            TIMELINE_MAIN_NAME,
+            NullWatcher(),
+        )
+        .expect("switched");
+        update_conn(&mut conn, &new_schema, &new_partition_map);
+        assert_eq!(main, conn.datoms().to_edn());
+        assert_eq!(None, active_timeline(&conn.sqlite).expect("active"));
+
+        let report = assert_transact!(conn, r#"[{:db/id "a" :person/name "Anna"}]"#);
+        let anna = *report.tempids.get("a").unwrap();
+        assert!(anna > petr);
+        assert!(report.tx_id > draft_tx);
+        let main = conn.datoms().to_edn();
+
+        // The draft is as we left it.
+        let (new_schema, new_partition_map) = switch_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            "draft",
+            NullWatcher(),
+        )
+        .expect("switched");
+        update_conn(&mut conn, &new_schema, &new_partition_map);
+        assert_eq!(draft, conn.datoms().to_edn());
+
+        // And so is main.
+        let (new_schema, new_partition_map) = switch_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            TIMELINE_MAIN_NAME,
+            NullWatcher(),
+        )
+        .expect("switched");
+        update_conn(&mut conn, &new_schema, &new_partition_map);
+        assert_eq!(main, conn.datoms().to_edn());
+
+        // Merging applies the draft's changes on top of main, from the draft or from main.
+        let (reports, new_schema, new_partition_map) = merge_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            "draft",
+            NullWatcher(),
+        )
+        .expect("merged");
+        update_conn(&mut conn, &new_schema, &new_partition_map);
+        assert_eq!(1, reports.len());
+        assert!(reports[0].tx_id > report.tx_id);
+
+        assert_matches!(
+            conn.last_transaction(),
+            r#"[[?ivan :person/name "Ivan" ?tx false]
+                [?ivan :person/name "Vanya" ?tx true]
+                [?ivan :person/note 1 ?tx false]
+                [?petr :person/name "Petr" ?tx true]
+                [?petr :person/note 2 ?tx true]
+                [?tx :db/txInstant ?ms ?tx true]]"#
+        );
+        assert_matches!(
+            conn.datoms(),
+            r#"[[?name :db/ident :person/name]
+                [?name :db/valueType :db.type/string]
+                [?name :db/cardinality :db.cardinality/one]
+                [?name :db/unique :db.unique/identity]
+                [?name :db/index true]
+                [?note :db/ident :person/note]
+                [?note :db/valueType :db.type/string]
+                [?note :db/cardinality :db.cardinality/many]
+                [?note :db/index true]
+                [?note :db/fulltext true]
+                [?ivan :person/name "Vanya"]
+                [?petr :person/name "Petr"]
+                [?petr :person/note 2]
+                [?anna :person/name "Anna"]]"#
+        );
+        assert!(timelines(&conn.sqlite).expect("timelines").is_empty());
+        assert_eq!(
+            conn.partition_map,
+            db::read_partition_map(&conn.sqlite).expect("partition map")
+        );
+    }
+
+    #[test]
+    fn test_discard_timeline() {
+        let mut conn = TestConn::default();
+        conn.sanitized_partition_map();
+
+        assert_transact!(
+            conn,
+            r#"[{:db/ident :person/note :db/valueType :db.type/string :db/cardinality :db.cardinality/many :db/index true :db/fulltext true}]"#
+        );
+        let base = conn.last_tx_id();
+        let main = conn.datoms().to_edn();
+
+        fork_timeline(&conn.sqlite, "draft", base).expect("forked");
+        let (new_schema, new_partition_map) = switch_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            "draft",
+            NullWatcher(),
+        )
+        .expect("switched");
+        update_conn(&mut conn, &new_schema, &new_partition_map);
+        let report = assert_transact!(conn, r#"[{:db/id "d" :person/note "draft"}]"#);
+        let draft = *report.tempids.get("d").unwrap();
+        let draft_tx = report.tx_id;
+
+        // Discarding switches back to main, and forgets the draft and its fulltext values.
+        let (new_schema, new_partition_map) = discard_timeline(
+            &conn.sqlite,
+            &conn.schema,
+            conn.partition_map.clone(),
+            "draft",
+            NullWatcher(),
+        )
+        .expect("discarded");
+        update_conn(&mut conn, &new_schema, &new_partition_map);
+        assert_eq!(main, conn.datoms().to_edn());
+        assert_matches!(conn.fulltext_values(), "[]");
+        assert!(timelines(&conn.sqlite).expect("timelines").is_empty());
+
+        // Entids and tx ids used by the draft aren't reused.
+        let report = assert_transact!(conn, r#"[{:db/id "m" :person/note "main"}]"#);
+        assert!(*report.tempids.get("m").unwrap() > draft);
+        assert!(report.tx_id > draft_tx);
+
+        assert_eq!(
+            DbErrorKind::TimelinesUnknown("draft".to_string()),
+            switch_timeline(
+                &conn.sqlite,
+                &conn.schema,
+                conn.partition_map.clone(),
+                "draft",
+                NullWatcher(),
+            )
+            .expect_err("unknown")
+            .kind()
+        );
+    }
 }
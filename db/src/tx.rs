@@ -52,6 +52,7 @@ use std::iter::once;
 use crate::db;
 use crate::db::MentatStoring;
 use crate::entids;
+use crate::excision;
 use crate::internal_types::{
     replace_lookup_ref, AEVTrie, AddAndRetract, KnownEntidOr, LookupRef, LookupRefOrTempId,
     TempIdHandle, TempIdMap, Term, TermWithTempIds, TermWithTempIdsAndLookupRefs,
@@ -867,13 +868,45 @@ where
                 ));
             }
 
+            // :db/ensure is virtual: we check the entity specs it names, but never store it.
+            let ensures = tx_checking::take_entity_specs(&self.schema.ident_map, &mut aev_trie);
+
+            let errors = tx_checking::attribute_predicate_violations(
+                self.store,
+                &self.schema.ident_map,
+                &aev_trie,
+            )?;
+            if !errors.is_empty() {
+                bail!(DbErrorKind::SchemaConstraintViolation(
+                    errors::SchemaConstraintViolation::AttributePredicateViolations {
+                        violating_datoms: errors
+                    }
+                ));
+            }
+
+            let errors = tx_checking::entity_spec_violations(
+                self.store,
+                &self.schema.ident_map,
+                &aev_trie,
+                &ensures,
+            )?;
+            if !errors.is_empty() {
+                bail!(DbErrorKind::SchemaConstraintViolation(
+                    errors::SchemaConstraintViolation::EntitySpecViolations {
+                        missing_attributes: errors
+                    }
+                ));
+            }
+
+            let excisions = excision::excisions(&self.partition_map, self.schema, &aev_trie)?;
+
             // Pipeline stage 4: final terms (after rewriting) -> DB insertions.
             // Collect into non_fts_*.
 
             tx_instant = get_or_insert_tx_instant(&mut aev_trie, &self.schema, self.tx_id)?;
 
             for ((a, attribute), evs) in aev_trie {
-                if entids::might_update_metadata(a) {
+                if entids::might_update_metadata(&self.schema.ident_map, a) {
                     tx_might_update_metadata = true;
                 }
 
@@ -927,6 +960,16 @@ where
                     self.store.commit_mentat_transaction(self.tx_id)?;
                 }
             }
+
+            if !excisions.is_empty() {
+                excision::excise(
+                    self.store,
+                    self.schema,
+                    self.tx_id,
+                    &excisions,
+                    &mut self.watcher,
+                )?;
+            }
         }
 
         self.watcher.done(&self.tx_id, self.schema)?;
@@ -934,14 +977,19 @@ where
         if tx_might_update_metadata {
             // Extract changes to metadata from the store.
             let metadata_assertions = match action {
-                TransactorAction::Materialize => self.store.resolved_metadata_assertions()?,
-                TransactorAction::MaterializeAndCommit => {
-                    db::committed_metadata_assertions(self.store, self.tx_id)?
-                }
+                TransactorAction::Materialize => self
+                    .store
+                    .resolved_metadata_assertions(&self.schema.ident_map)?,
+                TransactorAction::MaterializeAndCommit => db::committed_metadata_assertions(
+                    self.store,
+                    &self.schema.ident_map,
+                    self.tx_id,
+                )?,
             };
             let mut new_schema = (*self.schema_for_mutation).clone(); // Clone the underlying Schema for modification.
             let metadata_report = metadata::update_schema_from_entid_quadruples(
                 &mut new_schema,
+                &self.schema.ident_map,
                 metadata_assertions,
             )?;
             // We might not have made any changes to the schema, even though it looked like we
@@ -8,17 +8,351 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet};
+use std::iter::{once, repeat_n};
+
+use itertools::Itertools;
+use regex::Regex;
+use rusqlite;
+use rusqlite::limits::Limit;
+use rusqlite::types::ToSql;
 
 use core_traits::{Entid, TypedValue, ValueType};
 
-use db_traits::errors::CardinalityConflict;
+use db_traits::errors::{CardinalityConflict, DbErrorKind, Result};
+
+use mentat_core::IdentMap;
+
+use edn;
+use edn::Value;
 
+use crate::db::TypedSQLValue;
+use crate::entids;
 use crate::internal_types::AEVTrie;
 
 /// Map from found [e a v] to expected type.
 pub(crate) type TypeDisagreements = BTreeMap<(Entid, Entid, TypedValue), ValueType>;
 
+/// Map from found [e a v] to the (EDN) predicate that `v` fails.
+pub(crate) type AttributePredicateViolations = BTreeMap<(Entid, Entid, TypedValue), String>;
+
+/// Map from ensured (e, spec) to the required attributes that `e` is missing.
+pub(crate) type EntitySpecViolations = BTreeMap<(Entid, Entid), BTreeSet<Entid>>;
+
+/// A declarative constraint on the values of an attribute, parsed from a `:db.attr/preds` value.
+#[derive(Clone, Debug)]
+pub(crate) enum AttributePredicate {
+    /// `(re-matches "pattern")`: string values must match the regular expression in its entirety.
+    Matches(Regex),
+
+    /// `(range lo hi)`: long, double, and instant values must lie between `lo` and `hi`, inclusive.
+    /// A `nil` bound is unbounded.
+    Range(Option<TypedValue>, Option<TypedValue>),
+
+    /// `(length lo hi)`: string values must have between `lo` and `hi` characters, inclusive.  A
+    /// `nil` bound is unbounded.
+    Length(Option<usize>, Option<usize>),
+}
+
+impl AttributePredicate {
+    pub(crate) fn parse(s: &str) -> Result<AttributePredicate> {
+        let bad = || {
+            DbErrorKind::BadSchemaAssertion(format!(
+                "Expected :db.attr/preds (re-matches \"pattern\"), (range lo hi), or (length lo hi) but got '{}'",
+                s
+            ))
+        };
+
+        let form: Vec<Value> = match edn::parse::value(s).map(|v| v.without_spans()) {
+            Ok(Value::List(form)) => form.into_iter().collect(),
+            _ => bail!(bad()),
+        };
+
+        let op = match form.first() {
+            Some(Value::PlainSymbol(ref op)) => op.0.as_str(),
+            _ => bail!(bad()),
+        };
+
+        match (op, &form[1..]) {
+            ("re-matches", [Value::Text(ref pattern)]) => {
+                // Anchor the pattern so that the entire value has to match, like Clojure's
+                // `re-matches`.
+                Regex::new(&format!("^(?:{})$", pattern))
+                    .map(AttributePredicate::Matches)
+                    .map_err(|e| {
+                        DbErrorKind::BadSchemaAssertion(format!(
+                            "Invalid regular expression in :db.attr/preds '{}': {}",
+                            s, e
+                        ))
+                        .into()
+                    })
+            }
+            ("range", [ref lo, ref hi]) => {
+                let bound = |v: &Value| -> Result<Option<TypedValue>> {
+                    match v {
+                        Value::Nil => Ok(None),
+                        Value::Integer(_) | Value::Float(_) | Value::Instant(_) => {
+                            Ok(TypedValue::from_edn_value(v))
+                        }
+                        _ => bail!(bad()),
+                    }
+                };
+                Ok(AttributePredicate::Range(bound(lo)?, bound(hi)?))
+            }
+            ("length", [ref lo, ref hi]) => {
+                let bound = |v: &Value| -> Result<Option<usize>> {
+                    match *v {
+                        Value::Nil => Ok(None),
+                        Value::Integer(n) if n >= 0 => Ok(Some(n as usize)),
+                        _ => bail!(bad()),
+                    }
+                };
+                Ok(AttributePredicate::Length(bound(lo)?, bound(hi)?))
+            }
+            _ => bail!(bad()),
+        }
+    }
+
+    /// Return `true` if `v` satisfies this predicate.  A value of a type that the predicate
+    /// doesn't apply to, like a long tested with `re-matches`, never satisfies it.
+    pub(crate) fn test(&self, v: &TypedValue) -> bool {
+        match (self, v) {
+            (AttributePredicate::Matches(ref re), TypedValue::String(ref s)) => re.is_match(s),
+            (AttributePredicate::Range(ref lo, ref hi), v) => {
+                let above = lo.as_ref().map_or(Some(true), |lo| {
+                    compare_ordered_values(lo, v).map(|o| o != Ordering::Greater)
+                });
+                let below = hi.as_ref().map_or(Some(true), |hi| {
+                    compare_ordered_values(v, hi).map(|o| o != Ordering::Greater)
+                });
+                above == Some(true) && below == Some(true)
+            }
+            (AttributePredicate::Length(lo, hi), TypedValue::String(ref s)) => {
+                let length = s.chars().count();
+                lo.is_none_or(|lo| lo <= length) && hi.is_none_or(|hi| length <= hi)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compare two numeric or instant values, allowing longs and doubles to be compared with each
+/// other.  Returns `None` if the values can't be compared.
+fn compare_ordered_values(x: &TypedValue, y: &TypedValue) -> Option<Ordering> {
+    match (x, y) {
+        (TypedValue::Long(x), TypedValue::Long(y)) => Some(x.cmp(y)),
+        (TypedValue::Double(x), TypedValue::Double(y)) => Some(x.cmp(y)),
+        (TypedValue::Long(x), TypedValue::Double(y)) => (*x as f64).partial_cmp(&y.into_inner()),
+        (TypedValue::Double(x), TypedValue::Long(y)) => x.into_inner().partial_cmp(&(*y as f64)),
+        (TypedValue::Instant(x), TypedValue::Instant(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+/// Read the values of attribute `a` for each of the given `entities` as they will be once the
+/// terms in `aev_trie` are applied: stored values that aren't retracted, together with added
+/// values.
+fn values_after_transaction<'schema>(
+    conn: &rusqlite::Connection,
+    aev_trie: &AEVTrie<'schema>,
+    a: Entid,
+    entities: &BTreeSet<Entid>,
+) -> Result<BTreeMap<Entid, BTreeSet<TypedValue>>> {
+    let mut values: BTreeMap<Entid, BTreeSet<TypedValue>> = BTreeMap::default();
+
+    // Leave room for the attribute binding.
+    let max_vars = conn.limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER) as usize - 1;
+    let entities: Vec<&Entid> = entities.iter().collect();
+    for chunk in entities.chunks(max_vars) {
+        let s = format!(
+            "SELECT e, v, value_type_tag FROM all_datoms WHERE a = ? AND e IN ({})",
+            repeat_n("?", chunk.len()).join(", ")
+        );
+        let params: Vec<&dyn ToSql> = once(&a as &dyn ToSql)
+            .chain(chunk.iter().map(|e| *e as &dyn ToSql))
+            .collect();
+
+        let mut stmt = conn.prepare(&s)?;
+        let rows: Result<Vec<(Entid, TypedValue)>> = stmt
+            .query_and_then(rusqlite::params_from_iter(&params), |row| {
+                Ok((
+                    row.get(0)?,
+                    TypedValue::from_sql_value_pair(row.get(1)?, row.get(2)?)?,
+                ))
+            })?
+            .collect();
+        for (e, v) in rows? {
+            values.entry(e).or_default().insert(v);
+        }
+    }
+
+    let overlay = aev_trie.iter().find(|&(&(attr, _), _)| attr == a);
+    if let Some((&(_, attribute), evs)) = overlay {
+        for (e, ars) in evs {
+            if !entities.contains(&e) {
+                continue;
+            }
+            let vs = values.entry(*e).or_default();
+            if !attribute.multival && !ars.add.is_empty() {
+                // Asserting a cardinality one value replaces the stored value.
+                vs.clear();
+            }
+            for v in &ars.retract {
+                vs.remove(v);
+            }
+            vs.extend(ars.add.iter().cloned());
+        }
+    }
+
+    values.retain(|_, vs| !vs.is_empty());
+    Ok(values)
+}
+
+/// Ensure that every value added in the given terms satisfies its attribute's `:db.attr/preds`.
+///
+/// Predicates asserted in the same transaction apply immediately.  Newly asserted predicates that
+/// can't be parsed are an error in their own right.
+pub(crate) fn attribute_predicate_violations<'schema>(
+    conn: &rusqlite::Connection,
+    ident_map: &IdentMap,
+    aev_trie: &AEVTrie<'schema>,
+) -> Result<AttributePredicateViolations> {
+    let mut errors = AttributePredicateViolations::default();
+
+    let preds = match entids::core_entid(ident_map, entids::DB_ATTR_PREDS) {
+        Some(preds) => preds,
+        None => return Ok(errors),
+    };
+
+    let mut attributes: BTreeSet<Entid> = BTreeSet::default();
+    for (&(a, _), evs) in aev_trie {
+        for ars in evs.values() {
+            if a == preds {
+                for v in &ars.add {
+                    if let TypedValue::String(ref s) = *v {
+                        AttributePredicate::parse(s)?;
+                    }
+                }
+            }
+            if !ars.add.is_empty() {
+                attributes.insert(a);
+            }
+        }
+    }
+
+    if attributes.is_empty() {
+        return Ok(errors);
+    }
+
+    let mut predicates: BTreeMap<Entid, Vec<(AttributePredicate, String)>> = BTreeMap::default();
+    for (a, vs) in values_after_transaction(conn, aev_trie, preds, &attributes)? {
+        for v in vs {
+            if let TypedValue::String(s) = v {
+                let predicate = AttributePredicate::parse(&s)?;
+                predicates
+                    .entry(a)
+                    .or_default()
+                    .push((predicate, (*s).clone()));
+            }
+        }
+    }
+
+    for (&(a, _), evs) in aev_trie {
+        if let Some(predicates) = predicates.get(&a) {
+            for (&e, ars) in evs {
+                for v in &ars.add {
+                    if let Some((_, s)) = predicates.iter().find(|&(p, _)| !p.test(v)) {
+                        errors.insert((e, a, v.clone()), s.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(errors)
+}
+
+/// Remove the virtual `:db/ensure` attribute from the given terms, returning the ensured
+/// `(e, spec)` pairs.  Retracting `:db/ensure` is meaningless and is dropped.
+pub(crate) fn take_entity_specs<'schema>(
+    ident_map: &IdentMap,
+    aev_trie: &mut AEVTrie<'schema>,
+) -> BTreeSet<(Entid, Entid)> {
+    let ensure = entids::core_entid(ident_map, entids::DB_ENSURE);
+    let key = aev_trie.keys().find(|&&(a, _)| Some(a) == ensure).cloned();
+
+    let mut ensures = BTreeSet::default();
+    if let Some(evs) = key.and_then(|key| aev_trie.remove(&key)) {
+        for (e, ars) in evs {
+            for v in ars.add {
+                if let TypedValue::Ref(spec) = v {
+                    ensures.insert((e, spec));
+                }
+            }
+        }
+    }
+    ensures
+}
+
+/// Ensure that every entity `e` in `ensures` has, once the given terms are applied, at least one
+/// value for every attribute that `spec` requires with `:db.entity/attrs`.
+///
+/// We try to be maximally helpful by yielding every missing attribute of every ensured entity.
+pub(crate) fn entity_spec_violations<'schema>(
+    conn: &rusqlite::Connection,
+    ident_map: &IdentMap,
+    aev_trie: &AEVTrie<'schema>,
+    ensures: &BTreeSet<(Entid, Entid)>,
+) -> Result<EntitySpecViolations> {
+    let mut errors = EntitySpecViolations::default();
+
+    if ensures.is_empty() {
+        return Ok(errors);
+    }
+
+    let entity_attrs = match entids::core_entid(ident_map, entids::DB_ENTITY_ATTRS) {
+        Some(entity_attrs) => entity_attrs,
+        None => return Ok(errors),
+    };
+
+    let specs: BTreeSet<Entid> = ensures.iter().map(|&(_, spec)| spec).collect();
+    let required = values_after_transaction(conn, aev_trie, entity_attrs, &specs)?;
+
+    // Map each required attribute to the entities that must have it, so that we look up each
+    // attribute only once.
+    let mut entities_by_attribute: BTreeMap<Entid, BTreeSet<Entid>> = BTreeMap::default();
+    for &(e, spec) in ensures {
+        for v in required.get(&spec).into_iter().flatten() {
+            if let TypedValue::Ref(a) = *v {
+                entities_by_attribute.entry(a).or_default().insert(e);
+            }
+        }
+    }
+
+    let mut present: BTreeSet<(Entid, Entid)> = BTreeSet::default();
+    for (&a, entities) in &entities_by_attribute {
+        for e in values_after_transaction(conn, aev_trie, a, entities)?.keys() {
+            present.insert((*e, a));
+        }
+    }
+
+    for &(e, spec) in ensures {
+        for v in required.get(&spec).into_iter().flatten() {
+            if let TypedValue::Ref(a) = *v {
+                if !present.contains(&(e, a)) {
+                    errors
+                        .entry((e, spec))
+                        .or_insert_with(BTreeSet::default)
+                        .insert(a);
+                }
+            }
+        }
+    }
+
+    Ok(errors)
+}
+
 /// Ensure that the given terms type check.
 ///
 /// We try to be maximally helpful by yielding every malformed datom, rather than only the first.
@@ -28,6 +28,8 @@ use crate::types::AttributeSet;
 
 use crate::watcher::TransactWatcher;
 
+/// Notified with the ids of committed transactions that touched any of `attributes`.  Those ids
+/// can be queried as the `$delta` source by passing them to `QueryInputs::for_delta`.
 pub struct TxObserver {
     #[allow(clippy::type_complexity)]
     notify_fn: Arc<Box<dyn Fn(&str, IndexMap<&Entid, &AttributeSet>) + Send + Sync>>,
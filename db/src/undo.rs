@@ -0,0 +1,201 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Undoing a transaction transacts its inverse: every datom it asserted is retracted, and every
+//! datom it retracted is asserted again.  The transaction being undone stays in the log; the undo
+//! is a new transaction, and can itself be undone to redo the original changes.
+//!
+//! A transaction can only be undone while no later transaction has changed any of the `(e, a)`
+//! pairs it changed; otherwise the undo would silently clobber the later changes.
+
+use rusqlite;
+
+use core_traits::Entid;
+
+use db_traits::errors::{DbErrorKind, Result};
+
+use mentat_core::{Schema, TxReport};
+
+use edn::InternSet;
+
+use crate::internal_types::{Term, TermWithoutTempIds};
+use crate::timelines::terms_for;
+use crate::tx::{transact_terms_with_action, TransactorAction};
+use crate::types::PartitionMap;
+use crate::watcher::TransactWatcher;
+
+/// Transact the inverse of the main timeline transaction `tx_id`.
+///
+/// Fails with `UndoConflict` if a later transaction changed any `(e, a)` that `tx_id` changed.
+/// The transaction's own metadata, like its `:db/txInstant`, is not undone.
+pub fn undo<W>(
+    conn: &rusqlite::Connection,
+    partition_map: PartitionMap,
+    schema: &Schema,
+    watcher: W,
+    tx_id: Entid,
+) -> Result<(TxReport, PartitionMap, Option<Schema>, W)>
+where
+    W: TransactWatcher,
+{
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM timelined_transactions WHERE timeline = ? AND tx = ?)",
+        [&crate::TIMELINE_MAIN, &tx_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        bail!(DbErrorKind::UndoUnknownTransaction(tx_id));
+    }
+
+    let conflict: Option<Entid> = conn.query_row(
+        "SELECT MIN(later.tx) FROM timelined_transactions AS undone
+         JOIN timelined_transactions AS later ON later.e = undone.e AND later.a = undone.a
+         WHERE undone.timeline = ?1 AND undone.tx = ?2 AND undone.e != ?2
+           AND later.timeline = ?1 AND later.tx > ?2",
+        [&crate::TIMELINE_MAIN, &tx_id],
+        |row| row.get(0),
+    )?;
+    if let Some(later) = conflict {
+        bail!(DbErrorKind::UndoConflict(tx_id, later));
+    }
+
+    let terms: Vec<TermWithoutTempIds> =
+        terms_for(conn, schema, tx_id, crate::TIMELINE_MAIN, true)?
+            .into_iter()
+            .filter(|Term::AddOrRetract(_, e, _, _)| e.0 != tx_id)
+            .collect();
+
+    transact_terms_with_action(
+        conn,
+        partition_map,
+        schema,
+        schema,
+        watcher,
+        terms.into_iter().map(|t| t.rewrap()),
+        InternSet::new(),
+        TransactorAction::MaterializeAndCommit,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::borrow::Borrow;
+
+    use crate::debug::TestConn;
+    use crate::watcher::NullWatcher;
+
+    fn undo_tx(conn: &mut TestConn, tx_id: Entid) -> Result<TxReport> {
+        let (report, partition_map, next_schema, _) = undo(
+            &conn.sqlite,
+            conn.partition_map.clone(),
+            &conn.schema,
+            NullWatcher(),
+            tx_id,
+        )?;
+        conn.partition_map = partition_map;
+        if let Some(schema) = next_schema {
+            conn.schema = schema;
+        }
+        Ok(report)
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+            {:db/id 201 :db/ident :test/note :db/valueType :db.type/string :db/cardinality :db.cardinality/many :db/index true :db/fulltext true}
+        ]"#
+        );
+        assert_transact!(
+            conn,
+            r#"[{:db/id 100 :test/name "Ivan" :test/note "first"}]"#
+        );
+        let before = conn.datoms().to_edn();
+
+        let tx = assert_transact!(
+            conn,
+            r#"[[:db/add 100 :test/name "Vanya"]
+                [:db/retract 100 :test/note "first"]
+                [:db/add 100 :test/note "second"]
+                [:db/add 101 :test/name "Petr"]]"#
+        )
+        .tx_id;
+        let after = conn.datoms().to_edn();
+
+        let undone = undo_tx(&mut conn, tx).expect("undone");
+        assert!(undone.tx_id > tx);
+        assert_eq!(before, conn.datoms().to_edn());
+        assert_matches!(
+            conn.last_transaction(),
+            r#"[[100 :test/name "Ivan" ?tx true]
+                [100 :test/name "Vanya" ?tx false]
+                [100 :test/note 1 ?tx true]
+                [100 :test/note 2 ?tx false]
+                [101 :test/name "Petr" ?tx false]
+                [?tx :db/txInstant ?ms ?tx true]]"#
+        );
+
+        // Undoing the undo redoes the original changes.
+        undo_tx(&mut conn, undone.tx_id).expect("redone");
+        assert_eq!(after, conn.datoms().to_edn());
+    }
+
+    #[test]
+    fn test_undo_conflict() {
+        let mut conn = TestConn::default();
+
+        assert_transact!(
+            conn,
+            r#"[
+            {:db/id 200 :db/ident :test/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+            {:db/id 201 :db/ident :test/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+        ]"#
+        );
+        let tx = assert_transact!(conn, r#"[[:db/add 100 :test/name "Ivan"]]"#).tx_id;
+
+        // Changing other attributes doesn't conflict...
+        assert_transact!(conn, r#"[[:db/add 100 :test/age 30]]"#);
+        let later = assert_transact!(conn, r#"[[:db/add 100 :test/name "Vanya"]]"#).tx_id;
+
+        // ... but changing the same attribute does.
+        assert_eq!(
+            DbErrorKind::UndoConflict(tx, later),
+            undo_tx(&mut conn, tx).expect_err("conflict").kind()
+        );
+        assert_matches!(
+            conn.datoms(),
+            r#"[[100 :test/name "Vanya"]
+                [100 :test/age 30]
+                [200 :db/ident :test/name]
+                [200 :db/valueType :db.type/string]
+                [200 :db/cardinality :db.cardinality/one]
+                [201 :db/ident :test/age]
+                [201 :db/valueType :db.type/long]
+                [201 :db/cardinality :db.cardinality/one]]"#
+        );
+
+        undo_tx(&mut conn, later).expect("undone");
+        assert_eq!(
+            DbErrorKind::UndoConflict(tx, later),
+            undo_tx(&mut conn, tx).expect_err("conflict").kind()
+        );
+
+        assert_eq!(
+            DbErrorKind::UndoUnknownTransaction(tx - 1000),
+            undo_tx(&mut conn, tx - 1000).expect_err("unknown").kind()
+        );
+    }
+}
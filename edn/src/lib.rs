@@ -195,7 +195,9 @@ peg::parser!(pub grammar parse() for str {
 
     rule symbol_namespace() = symbol_char_initial() symbol_char_subsequent()* (namespace_divider() symbol_char_subsequent()+)*
     rule symbol_name() = ( symbol_char_initial()+ symbol_char_subsequent()* )
-    rule plain_symbol_name() = symbol_name() / "..." / "."
+    // A leading '+', '-' or '.' can't be followed by a digit, or the symbol would be a number.
+    rule signed_symbol_name() = ['+' | '-' | '.'] (!digit() symbol_char_subsequent()+)?
+    rule plain_symbol_name() = symbol_name() / "..." / signed_symbol_name() / "/"
 
     rule keyword_prefix() = ":"
 
@@ -339,7 +341,7 @@ peg::parser!(pub grammar parse() for str {
     // whitespace-eating rule or an explicit whitespace eating `__`.
 
     rule query_function() -> query::QueryFunction
-        = __ n:$(symbol_name()) __ {? query::QueryFunction::from_symbol(&PlainSymbol::plain(n)).ok_or("expected query function") }
+        = __ n:$(symbol_name() / signed_symbol_name() / "/") __ {? query::QueryFunction::from_symbol(&PlainSymbol::plain(n)).ok_or("expected query function") }
 
     rule fn_arg() -> query::FnArg
         = v:value() {? query::FnArg::from_value(&v).ok_or("expected query function argument") }
@@ -468,6 +470,11 @@ peg::parser!(pub grammar parse() for str {
              query::WhereClause::NotJoin(query::NotJoin::new(query::UnifyVars::Explicit(vars), clauses))
         }
 
+    rule optional_clause() -> query::WhereClause
+        = __ "(" __ "optional" clauses:where_clause()+ ")" __ {
+             query::WhereClause::OptionalJoin(query::OptionalJoin::new(clauses))
+        }
+
     rule type_annotation() -> query::WhereClause
         = __ "[" __ "(" __ "type" var:variable() __ ty:raw_keyword() __ ")" __ "]" __ {
             query::WhereClause::TypeAnnotation(
@@ -503,6 +510,7 @@ peg::parser!(pub grammar parse() for str {
         / or_clause()
         / not_join_clause()
         / not_clause()
+        / optional_clause()
         / type_annotation()
         / pred()
         / where_fn()
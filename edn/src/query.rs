@@ -927,6 +927,21 @@ impl NotJoin {
     }
 }
 
+/// An `optional` clause: `(optional [?e :person/email ?email])`.
+///
+/// Rows that match the rest of the query are kept whether or not they also match these clauses;
+/// variables bound only here have no value when they don't.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OptionalJoin {
+    pub clauses: Vec<WhereClause>,
+}
+
+impl OptionalJoin {
+    pub fn new(clauses: Vec<WhereClause>) -> OptionalJoin {
+        OptionalJoin { clauses }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct TypeAnnotation {
     pub value_type: Keyword,
@@ -938,6 +953,7 @@ pub struct TypeAnnotation {
 pub enum WhereClause {
     NotJoin(NotJoin),
     OrJoin(OrJoin),
+    OptionalJoin(OptionalJoin),
     Pred(Predicate),
     WhereFn(WhereFn),
     RuleExpr,
@@ -1085,6 +1101,7 @@ impl ContainsVariables for WhereClause {
             Pred(ref p) => p.accumulate_mentioned_variables(acc),
             Pattern(ref p) => p.accumulate_mentioned_variables(acc),
             NotJoin(ref n) => n.accumulate_mentioned_variables(acc),
+            OptionalJoin(ref o) => o.accumulate_mentioned_variables(acc),
             WhereFn(ref f) => f.accumulate_mentioned_variables(acc),
             TypeAnnotation(ref a) => a.accumulate_mentioned_variables(acc),
             RuleExpr => (),
@@ -1145,6 +1162,14 @@ impl ContainsVariables for NotJoin {
     }
 }
 
+impl ContainsVariables for OptionalJoin {
+    fn accumulate_mentioned_variables(&self, acc: &mut BTreeSet<Variable>) {
+        for clause in &self.clauses {
+            clause.accumulate_mentioned_variables(acc);
+        }
+    }
+}
+
 impl ContainsVariables for Predicate {
     fn accumulate_mentioned_variables(&self, acc: &mut BTreeSet<Variable>) {
         for arg in &self.args {
@@ -187,6 +187,33 @@ impl From<ValueAndSpan> for Value {
     }
 }
 
+/// A value that wasn't parsed from text, e.g., one read from JSON, has empty spans.
+impl From<Value> for ValueAndSpan {
+    fn from(src: Value) -> ValueAndSpan {
+        let inner = match src {
+            Value::Nil => SpannedValue::Nil,
+            Value::Boolean(v) => SpannedValue::Boolean(v),
+            Value::Integer(v) => SpannedValue::Integer(v),
+            Value::Instant(v) => SpannedValue::Instant(v),
+            Value::BigInteger(v) => SpannedValue::BigInteger(v),
+            Value::Float(v) => SpannedValue::Float(v),
+            Value::Text(v) => SpannedValue::Text(v),
+            Value::Uuid(v) => SpannedValue::Uuid(v),
+            Value::PlainSymbol(v) => SpannedValue::PlainSymbol(v),
+            Value::NamespacedSymbol(v) => SpannedValue::NamespacedSymbol(v),
+            Value::Keyword(v) => SpannedValue::Keyword(v),
+            Value::Vector(v) => SpannedValue::Vector(v.into_iter().map(|x| x.into()).collect()),
+            Value::List(v) => SpannedValue::List(v.into_iter().map(|x| x.into()).collect()),
+            Value::Set(v) => SpannedValue::Set(v.into_iter().map(|x| x.into()).collect()),
+            Value::Map(v) => {
+                SpannedValue::Map(v.into_iter().map(|(x, y)| (x.into(), y.into())).collect())
+            }
+            Value::Bytes(b) => SpannedValue::Bytes(b),
+        };
+        ValueAndSpan::new(inner, None)
+    }
+}
+
 /// Creates `from_$TYPE` helper functions for Value and SpannedValue,
 /// like `from_float()` or `from_ordered_float()`.
 macro_rules! def_from {
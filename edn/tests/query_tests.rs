@@ -13,8 +13,9 @@ extern crate edn;
 use edn::{Keyword, PlainSymbol};
 
 use edn::query::{
-    Direction, Element, FindSpec, FnArg, Limit, NonIntegerConstant, OrJoin, OrWhereClause, Order,
-    Pattern, PatternNonValuePlace, PatternValuePlace, Predicate, UnifyVars, Variable, WhereClause,
+    Binding, Direction, Element, FindSpec, FnArg, Limit, NonIntegerConstant, OptionalJoin, OrJoin,
+    OrWhereClause, Order, Pattern, PatternNonValuePlace, PatternValuePlace, Predicate, UnifyVars,
+    Variable, WhereClause, WhereFn,
 };
 
 use edn::parse::parse_query;
@@ -54,6 +55,34 @@ fn can_parse_predicates() {
     );
 }
 
+#[test]
+fn can_parse_arithmetic_functions() {
+    let s = "[:find ?y :where [(- ?x 1) ?y] [(/ ?y 2) ?z]]";
+    let p = parse_query(s).unwrap();
+
+    assert_eq!(
+        p.where_clauses,
+        vec![
+            WhereClause::WhereFn(WhereFn {
+                operator: PlainSymbol::plain("-"),
+                args: vec![
+                    FnArg::Variable(Variable::from_valid_name("?x")),
+                    FnArg::EntidOrInteger(1),
+                ],
+                binding: Binding::BindScalar(Variable::from_valid_name("?y")),
+            }),
+            WhereClause::WhereFn(WhereFn {
+                operator: PlainSymbol::plain("/"),
+                args: vec![
+                    FnArg::Variable(Variable::from_valid_name("?y")),
+                    FnArg::EntidOrInteger(2),
+                ],
+                binding: Binding::BindScalar(Variable::from_valid_name("?z")),
+            }),
+        ]
+    );
+}
+
 #[test]
 fn can_parse_simple_or() {
     let s = "[:find ?x . :where (or [?x _ 10] [?x _ 15])]";
@@ -207,6 +236,41 @@ fn can_parse_simple_or_and_join() {
     );
 }
 
+#[test]
+fn can_parse_optional() {
+    let s = "[:find ?x ?y :where [?x :foo/bar _] (optional [?x :foo/baz ?y] [(< ?y 1)])]";
+    let p = parse_query(s).unwrap();
+
+    assert_eq!(
+        p.where_clauses,
+        vec![
+            WhereClause::Pattern(Pattern {
+                source: None,
+                entity: PatternNonValuePlace::Variable(Variable::from_valid_name("?x")),
+                attribute: ident("foo", "bar"),
+                value: PatternValuePlace::Placeholder,
+                tx: PatternNonValuePlace::Placeholder,
+            }),
+            WhereClause::OptionalJoin(OptionalJoin::new(vec![
+                WhereClause::Pattern(Pattern {
+                    source: None,
+                    entity: PatternNonValuePlace::Variable(Variable::from_valid_name("?x")),
+                    attribute: ident("foo", "baz"),
+                    value: PatternValuePlace::Variable(Variable::from_valid_name("?y")),
+                    tx: PatternNonValuePlace::Placeholder,
+                }),
+                WhereClause::Pred(Predicate {
+                    operator: PlainSymbol::plain("<"),
+                    args: vec![
+                        FnArg::Variable(Variable::from_valid_name("?y")),
+                        FnArg::EntidOrInteger(1),
+                    ]
+                }),
+            ])),
+        ]
+    );
+}
+
 #[test]
 fn can_parse_order_by() {
     let invalid = "[:find ?x :where [?x :foo/baz ?y] :order]";
@@ -461,6 +461,11 @@ fn test_symbol() {
     assert_eq!(symbol("$").unwrap(), s_plain("$"));
     assert_eq!(symbol(".").unwrap(), s_plain("."));
     assert_eq!(symbol("...").unwrap(), s_plain("..."));
+    assert_eq!(symbol("+").unwrap(), s_plain("+"));
+    assert_eq!(symbol("-").unwrap(), s_plain("-"));
+    assert_eq!(symbol("/").unwrap(), s_plain("/"));
+    assert_eq!(symbol("->x").unwrap(), s_plain("->x"));
+    assert!(symbol("-5").is_err());
 
     assert_eq!(symbol("hello/world").unwrap(), s_ns("hello", "world"));
     assert_eq!(
@@ -86,25 +86,33 @@ use std::sync::Arc;
 use std::vec;
 
 pub use mentat::{
-    Binding, CacheDirection, Entid, FindSpec, HasSchema, InProgress, KnownEntid, QueryBuilder,
-    QueryInputs, QueryOutput, QueryResults, Queryable, RelResult, Store, TxObserver, TxReport,
-    TypedValue, Uuid, ValueType, Variable,
+    Binding, CacheDirection, Entid, FindSpec, HasSchema, InProgress, KnownEntid, Pullable,
+    QueryBuilder, QueryInputs, QueryOutput, QueryResults, Queryable, RelResult, Store,
+    StructuredMap, TxObserver, TxReport, TypedValue, Uuid, ValueType, Variable,
 };
 
 pub use mentat::entity_builder::{BuildTerms, EntityBuilder, InProgressBuilder};
 
+pub use mentat::vocabulary::{AttributeBuilder, Definition, VersionedStore, VocabularyOutcome};
+
+use mentat::attribute::Unique;
+
 pub mod android;
 pub mod utils;
 
 pub use utils::strings::{c_char_to_string, kw_from_string, string_to_c_char};
 
-use utils::error::{translate_opt_result, translate_result, translate_void_result, ExternError};
+use utils::error::{
+    translate_opt_result, translate_result, translate_string_result, translate_void_result,
+    ExternError,
+};
 
 pub use utils::log;
 
 // type aliases for iterator types.
 pub type BindingIterator = vec::IntoIter<Binding>;
 pub type BindingListIterator = std::slice::Chunks<'static, mentat::Binding>;
+pub type TempIdIterator = vec::IntoIter<(String, Entid)>;
 
 /// Helper macro for asserting one or more pointers are not null at the same time.
 #[macro_export]
@@ -135,6 +143,47 @@ pub struct TxChangeList {
     pub len: c_ulonglong,
 }
 
+/// A C representation of a `:db.type/bytes` value: `len` bytes starting at `data`.
+/// The bytes belong to Rust, and are released with `byte_buffer_destroy`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ByteBuffer {
+    pub data: *mut u8,
+    pub len: c_ulonglong,
+}
+
+impl ByteBuffer {
+    fn from_slice(bytes: &[u8]) -> *mut ByteBuffer {
+        let bytes: Box<[u8]> = bytes.into();
+        let len = bytes.len() as c_ulonglong;
+        Box::into_raw(Box::new(ByteBuffer {
+            data: Box::into_raw(bytes) as *mut u8,
+            len,
+        }))
+    }
+}
+
+/// A C representation of a tempid and the [Entid](mentat::Entid) it was resolved to by a transaction.
+/// Released with `temp_id_destroy`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct TempId {
+    pub tempid: *mut c_char,
+    pub entid: Entid,
+}
+
+/// What a [Binding](mentat::Binding) holds. Only a `Scalar` has a [ValueType](mentat::ValueType);
+/// a `Vec` is read as a list of bindings, and a `Map` as a [StructuredMap](mentat::StructuredMap).
+/// Pull expressions produce maps, and `Nil` where there was nothing to pull.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BindingKind {
+    Scalar,
+    Vec,
+    Map,
+    Nil,
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct InProgressTransactResult<'a, 'c> {
@@ -226,6 +275,55 @@ pub unsafe extern "C" fn store_begin_transaction<'a, 'c>(
     translate_result(store.begin_transaction(), error)
 }
 
+/// Syncs the store with the server at `server_uri` as the user `user_uuid`, and returns a
+/// description of what happened.
+///
+/// The caller is responsible for freeing the pointer returned from this function using
+/// `rust_c_string_destroy`.
+///
+/// TODO: Return the sync reports in a form that callers can act on.
+#[cfg(feature = "syncable")]
+#[no_mangle]
+pub unsafe extern "C" fn store_sync(
+    store: *mut Store,
+    server_uri: *const c_char,
+    user_uuid: *const c_char,
+    error: *mut ExternError,
+) -> *mut c_char {
+    assert_not_null!(store);
+    let store = &mut *store;
+    let server_uri = c_char_to_string(server_uri);
+    let user_uuid = c_char_to_string(user_uuid);
+    let result = store
+        .sync(server_uri, user_uuid)
+        .map(|result| result.to_string());
+    translate_string_result(result, error)
+}
+
+/// Installs or upgrades the vocabulary described by `definition`, in a transaction of its own.
+/// The outcome tells the caller what, if anything, was done.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// The destructor `destroy` is provided for releasing the memory for this pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn store_ensure_vocabulary(
+    store: *mut Store,
+    definition: *const Definition,
+    error: *mut ExternError,
+) -> *mut VocabularyOutcome {
+    assert_not_null!(store, definition);
+    let store = &mut *store;
+    let definition = &*definition;
+    let result = store.begin_transaction().and_then(|mut in_progress| {
+        let outcome = in_progress.ensure_vocabulary(definition)?;
+        in_progress.commit()?;
+        Ok(outcome)
+    });
+    translate_result(result, error)
+}
+
 /// Perform a single transact operation using the current in progress
 /// transaction. Takes edn as a string to transact.
 ///
@@ -289,6 +387,66 @@ pub unsafe extern "C" fn in_progress_rollback<'m>(
     translate_void_result(in_progress.rollback(), error);
 }
 
+/// Installs or upgrades the vocabulary described by `definition` as part of this
+/// in progress transaction.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// The destructor `destroy` is provided for releasing the memory for this pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn in_progress_ensure_vocabulary<'m>(
+    in_progress: *mut InProgress<'m, 'm>,
+    definition: *const Definition,
+    error: *mut ExternError,
+) -> *mut VocabularyOutcome {
+    assert_not_null!(in_progress, definition);
+    let in_progress = &mut *in_progress;
+    let definition = &*definition;
+    translate_result(in_progress.ensure_vocabulary(definition), error)
+}
+
+/// Marks a savepoint called `name` in this in progress transaction, which can later be rolled
+/// back to with `in_progress_rollback_savepoint` or kept with `in_progress_release_savepoint`.
+#[no_mangle]
+pub unsafe extern "C" fn in_progress_savepoint<'m>(
+    in_progress: *mut InProgress<'m, 'm>,
+    name: *const c_char,
+    error: *mut ExternError,
+) {
+    assert_not_null!(in_progress);
+    let in_progress = &*in_progress;
+    let name = c_char_to_string(name);
+    translate_void_result(in_progress.savepoint(name), error);
+}
+
+/// Undoes everything transacted since the savepoint called `name` was marked. The savepoint
+/// remains, and can be rolled back to again.
+#[no_mangle]
+pub unsafe extern "C" fn in_progress_rollback_savepoint<'m>(
+    in_progress: *mut InProgress<'m, 'm>,
+    name: *const c_char,
+    error: *mut ExternError,
+) {
+    assert_not_null!(in_progress);
+    let in_progress = &*in_progress;
+    let name = c_char_to_string(name);
+    translate_void_result(in_progress.rollback_savepoint(name), error);
+}
+
+/// Forgets the savepoint called `name`, keeping everything transacted since it was marked.
+#[no_mangle]
+pub unsafe extern "C" fn in_progress_release_savepoint<'m>(
+    in_progress: *mut InProgress<'m, 'm>,
+    name: *const c_char,
+    error: *mut ExternError,
+) {
+    assert_not_null!(in_progress);
+    let in_progress = &*in_progress;
+    let name = c_char_to_string(name);
+    translate_void_result(in_progress.release_savepoint(name), error);
+}
+
 /// Creates a builder using the in progress transaction to allow for programmatic
 /// assertion of values.
 ///
@@ -621,6 +779,33 @@ pub unsafe extern "C" fn in_progress_builder_add_uuid(
     translate_void_result(builder.add(KnownEntid(entid), kw, value), error);
 }
 
+/// Uses `builder` to assert the `len` bytes at `value` for `kw` on entity `entid`.
+///
+/// # Errors
+///
+/// If `entid` is not present in the store.
+/// If `kw` is not a valid attribute in the store.
+/// If the `:db/type` of the attribute described by `kw` is not `:db.type/bytes`.
+///
+/// # Safety
+/// TODO:
+// TODO: Generalise with macro https://github.com/mozilla/mentat/issues/703
+#[no_mangle]
+pub unsafe extern "C" fn in_progress_builder_add_bytes(
+    builder: *mut InProgressBuilder,
+    entid: c_longlong,
+    kw: *const c_char,
+    value: *const u8,
+    len: usize,
+    error: *mut ExternError,
+) {
+    assert_not_null!(builder, value);
+    let builder = &mut *builder;
+    let kw = kw_from_string(c_char_to_string(kw));
+    let value: TypedValue = slice::from_raw_parts(value, len).into();
+    translate_void_result(builder.add(KnownEntid(entid), kw, value), error);
+}
+
 /// Uses `builder` to retract `value` for `kw` on entity `entid`.
 ///
 /// # Errors
@@ -832,6 +1017,33 @@ pub unsafe extern "C" fn in_progress_builder_retract_uuid(
     translate_void_result(builder.retract(KnownEntid(entid), kw, value), error);
 }
 
+/// Uses `builder` to retract the `len` bytes at `value` for `kw` on entity `entid`.
+///
+/// # Errors
+///
+/// If `entid` is not present in the store.
+/// If `kw` is not a valid attribute in the store.
+/// If the `:db/type` of the attribute described by `kw` is not `:db.type/bytes`.
+///
+/// # Safety
+/// TODO:
+// TODO: Generalise with macro https://github.com/mozilla/mentat/issues/703
+#[no_mangle]
+pub unsafe extern "C" fn in_progress_builder_retract_bytes(
+    builder: *mut InProgressBuilder,
+    entid: c_longlong,
+    kw: *const c_char,
+    value: *const u8,
+    len: usize,
+    error: *mut ExternError,
+) {
+    assert_not_null!(builder, value);
+    let builder = &mut *builder;
+    let kw = kw_from_string(c_char_to_string(kw));
+    let value: TypedValue = slice::from_raw_parts(value, len).into();
+    translate_void_result(builder.retract(KnownEntid(entid), kw, value), error);
+}
+
 /// Transacts and commits all the assertions and retractions that have been performed
 /// using this builder.
 ///
@@ -1075,6 +1287,31 @@ pub unsafe extern "C" fn entity_builder_add_uuid(
     translate_void_result(builder.add(kw, value), error);
 }
 
+/// Uses `builder` to assert the `len` bytes at `value` for `kw`.
+///
+/// # Errors
+///
+/// If `kw` is not a valid attribute in the store.
+/// If the `:db/type` of the attribute described by `kw` is not `:db.type/bytes`.
+///
+/// # Safety
+/// TODO:
+// TODO: Generalise with macro https://github.com/mozilla/mentat/issues/703
+#[no_mangle]
+pub unsafe extern "C" fn entity_builder_add_bytes(
+    builder: *mut EntityBuilder<InProgressBuilder>,
+    kw: *const c_char,
+    value: *const u8,
+    len: usize,
+    error: *mut ExternError,
+) {
+    assert_not_null!(builder, value);
+    let builder = &mut *builder;
+    let kw = kw_from_string(c_char_to_string(kw));
+    let value: TypedValue = slice::from_raw_parts(value, len).into();
+    translate_void_result(builder.add(kw, value), error);
+}
+
 /// Uses `builder` to retract `value` for `kw` on entity `entid`.
 ///
 /// # Errors
@@ -1278,6 +1515,31 @@ pub unsafe extern "C" fn entity_builder_retract_uuid(
     translate_void_result(builder.retract(kw, value), error);
 }
 
+/// Uses `builder` to retract the `len` bytes at `value` for `kw`.
+///
+/// # Errors
+///
+/// If `kw` is not a valid attribute in the store.
+/// If the `:db/type` of the attribute described by `kw` is not `:db.type/bytes`.
+///
+/// # Safety
+/// TODO:
+// TODO: Generalise with macro https://github.com/mozilla/mentat/issues/703
+#[no_mangle]
+pub unsafe extern "C" fn entity_builder_retract_bytes(
+    builder: *mut EntityBuilder<InProgressBuilder>,
+    kw: *const c_char,
+    value: *const u8,
+    len: usize,
+    error: *mut ExternError,
+) {
+    assert_not_null!(builder, value);
+    let builder = &mut *builder;
+    let kw = kw_from_string(c_char_to_string(kw));
+    let value: TypedValue = slice::from_raw_parts(value, len).into();
+    translate_void_result(builder.retract(kw, value), error);
+}
+
 /// Transacts all the assertions and retractions that have been performed
 /// using this builder.
 ///
@@ -1380,6 +1642,48 @@ pub unsafe extern "C" fn tx_report_entity_for_temp_id(
     }
 }
 
+/// Returns an iterator over the tempids in the given [TxReport](mentat::TxReport) and the
+/// [Entid](mentat::Entid)s they were resolved to, ordered by tempid.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `temp_id_iter_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn tx_report_temp_ids_into_iter(
+    tx_report: *mut TxReport,
+) -> *mut TempIdIterator {
+    assert_not_null!(tx_report);
+    let tx_report = &*tx_report;
+    let tempids: Vec<(String, Entid)> = tx_report
+        .tempids
+        .iter()
+        .map(|(tempid, entid)| (tempid.clone(), *entid))
+        .collect();
+    Box::into_raw(Box::new(tempids.into_iter()))
+}
+
+/// Returns the next tempid in the `iter` as a [TempId](TempId).
+/// If there is no next tempid, a null pointer is returned.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `temp_id_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn temp_id_iter_next(iter: *mut TempIdIterator) -> *mut TempId {
+    assert_not_null!(iter);
+    let iter = &mut *iter;
+    iter.next().map_or(std::ptr::null_mut(), |(tempid, entid)| {
+        Box::into_raw(Box::new(TempId {
+            tempid: string_to_c_char(tempid),
+            entid,
+        }))
+    })
+}
+
 /// Adds an attribute to the cache.
 /// `store_cache_attribute_forward` caches values for an attribute keyed by entity
 /// (i.e. find values and entities that have this attribute, or find values of attribute for an entity)
@@ -1430,6 +1734,118 @@ pub unsafe extern "C" fn store_cache_attribute_bi_directional(
     translate_void_result(store.cache(&kw, CacheDirection::Both), error);
 }
 
+/// Creates an [AttributeBuilder](mentat::vocabulary::AttributeBuilder) for an attribute with
+/// values of type `value_type`. The attribute is cardinality one, not unique and not indexed
+/// until told otherwise.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value, either by passing it
+/// to `vocabulary_definition_add_attribute` or by calling the destructor
+/// `attribute_builder_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_new(value_type: ValueType) -> *mut AttributeBuilder {
+    let mut builder = AttributeBuilder::helpful();
+    builder.value_type(value_type).multival(false);
+    Box::into_raw(Box::new(builder))
+}
+
+/// Makes the attribute cardinality many if `multival` is 1, or cardinality one if it is 0.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_multival(builder: *mut AttributeBuilder, multival: i32) {
+    assert_not_null!(builder);
+    let builder = &mut *builder;
+    builder.multival(multival != 0);
+}
+
+/// Makes the attribute `:db.unique/value`.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_unique_value(builder: *mut AttributeBuilder) {
+    assert_not_null!(builder);
+    let builder = &mut *builder;
+    builder.unique(Unique::Value);
+}
+
+/// Makes the attribute `:db.unique/identity`.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_unique_identity(builder: *mut AttributeBuilder) {
+    assert_not_null!(builder);
+    let builder = &mut *builder;
+    builder.unique(Unique::Identity);
+}
+
+/// Indexes the attribute if `index` is 1.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_index(builder: *mut AttributeBuilder, index: i32) {
+    assert_not_null!(builder);
+    let builder = &mut *builder;
+    builder.index(index != 0);
+}
+
+/// Makes the attribute fulltext if `fulltext` is 1. Only string attributes can be fulltext.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_fulltext(builder: *mut AttributeBuilder, fulltext: i32) {
+    assert_not_null!(builder);
+    let builder = &mut *builder;
+    builder.fulltext(fulltext != 0);
+}
+
+/// Makes the attribute a component if `component` is 1. Only ref attributes can be components.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_component(
+    builder: *mut AttributeBuilder,
+    component: i32,
+) {
+    assert_not_null!(builder);
+    let builder = &mut *builder;
+    builder.component(component != 0);
+}
+
+/// Stops keeping the history of the attribute's values if `no_history` is 1.
+#[no_mangle]
+pub unsafe extern "C" fn attribute_builder_no_history(
+    builder: *mut AttributeBuilder,
+    no_history: i32,
+) {
+    assert_not_null!(builder);
+    let builder = &mut *builder;
+    builder.no_history(no_history != 0);
+}
+
+/// Creates an empty [Definition](mentat::vocabulary::Definition) of version `version` of the
+/// vocabulary `name`, given as `:namespace/name`. Upgrading a definition from C runs no code
+/// before or after its attributes are transacted.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `vocabulary_definition_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn vocabulary_definition_new(
+    name: *const c_char,
+    version: u32,
+) -> *mut Definition {
+    let name = kw_from_string(c_char_to_string(name));
+    Box::into_raw(Box::new(Definition::new(name, version, vec![])))
+}
+
+/// Adds the attribute `ident`, given as `:namespace/name`, to `definition`, as described by
+/// `builder`. This consumes `builder`. An attribute that can't be installed, e.g., a fulltext
+/// attribute that isn't a string, is reported when the vocabulary is ensured.
+#[no_mangle]
+pub unsafe extern "C" fn vocabulary_definition_add_attribute(
+    definition: *mut Definition,
+    ident: *const c_char,
+    builder: *mut AttributeBuilder,
+) {
+    assert_not_null!(definition, builder);
+    let definition = &mut *definition;
+    let builder = Box::from_raw(builder);
+    let ident = kw_from_string(c_char_to_string(ident));
+    definition.attributes.push((ident, builder.build()));
+}
+
 /// Creates a [QueryBuilder](mentat::QueryBuilder) from the given store to execute the provided query.
 ///
 /// # Safety
@@ -1600,6 +2016,24 @@ pub unsafe extern "C" fn query_builder_bind_uuid(
     query_builder.bind_value(&var, value);
 }
 
+/// Binds a [TypedValue::Bytes](mentat::TypedValue::Bytes) to a [Variable](mentat::Variable) with the given name.
+/// Takes the `len` bytes starting at `value`, which are copied.
+///
+// TODO Generalise with macro https://github.com/mozilla/mentat/issues/703
+#[no_mangle]
+pub unsafe extern "C" fn query_builder_bind_bytes(
+    query_builder: *mut QueryBuilder,
+    var: *const c_char,
+    value: *const u8,
+    len: usize,
+) {
+    assert_not_null!(query_builder, value);
+    let var = c_char_to_string(var);
+    let value: TypedValue = slice::from_raw_parts(value, len).into();
+    let query_builder = &mut *query_builder;
+    query_builder.bind_value(var, value);
+}
+
 /// Executes a query and returns the results as a [Scalar](mentat::QueryResults::Scalar).
 ///
 /// # Panics
@@ -1622,6 +2056,25 @@ pub unsafe extern "C" fn query_builder_execute_scalar(
     translate_opt_result(results, error)
 }
 
+/// Explains how the query would be run, with the values bound so far, as JSON text: see
+/// [QueryExplanation::to_json](mentat::QueryExplanation::to_json). The bindings are kept,
+/// so the query can still be executed.
+///
+/// The caller is responsible for freeing the pointer returned from this function using
+/// `rust_c_string_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn query_builder_explain(
+    query_builder: *mut QueryBuilder,
+    error: *mut ExternError,
+) -> *mut c_char {
+    assert_not_null!(query_builder);
+    let query_builder = &*query_builder;
+    let result = query_builder
+        .explain()
+        .map(|explanation| explanation.to_json().to_string());
+    translate_string_result(result, error)
+}
+
 /// Executes a query and returns the results as a [Coll](mentat::QueryResults::Coll).
 ///
 /// # Panics
@@ -1822,7 +2275,92 @@ pub unsafe extern "C" fn typed_value_into_uuid(typed_value: *mut Binding) -> *mu
     Box::into_raw(Box::new(*value.as_bytes()))
 }
 
+/// Consumes a [Binding](mentat::Binding) and returns the value as a [ByteBuffer](ByteBuffer).
+///
+/// The caller is responsible for freeing the pointer returned from this function using
+/// `byte_buffer_destroy`.
+///
+/// # Panics
+///
+/// If the [ValueType](mentat::ValueType) of the [Binding](mentat::Binding) is not [ValueType::Bytes](mentat::ValueType::Bytes).
+///
+// TODO Generalise with macro https://github.com/mozilla/mentat/issues/703
+#[no_mangle]
+pub unsafe extern "C" fn typed_value_into_bytes(typed_value: *mut Binding) -> *mut ByteBuffer {
+    assert_not_null!(typed_value);
+    let typed_value = Box::from_raw(typed_value);
+    let value = unwrap_conversion(
+        typed_value.into_scalar().and_then(|v| v.into_bytes()),
+        ValueType::Bytes,
+    );
+    ByteBuffer::from_slice(&value)
+}
+
+/// Consumes a [Binding](mentat::Binding) holding a map, such as the result of a pull expression,
+/// and returns the [StructuredMap](mentat::StructuredMap).
+///
+/// # Panics
+///
+/// If the [BindingKind](BindingKind) of the [Binding](mentat::Binding) is not `Map`.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `structured_map_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn typed_value_into_map(typed_value: *mut Binding) -> *mut StructuredMap {
+    assert_not_null!(typed_value);
+    let typed_value = Box::from_raw(typed_value);
+    let map = typed_value
+        .into_map()
+        .unwrap_or_else(|| panic!("Binding is not a Map"));
+    Box::into_raw(Box::new(
+        Arc::try_unwrap(map).unwrap_or_else(|map| (*map).clone()),
+    ))
+}
+
+/// Consumes a [Binding](mentat::Binding) holding a vector, such as the values of a
+/// cardinality-many attribute in a pull expression, and returns them as a `Vec<Binding>`.
+///
+/// # Panics
+///
+/// If the [BindingKind](BindingKind) of the [Binding](mentat::Binding) is not `Vec`.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `typed_value_list_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn typed_value_into_vec(typed_value: *mut Binding) -> *mut Vec<Binding> {
+    assert_not_null!(typed_value);
+    let typed_value = Box::from_raw(typed_value);
+    let values = typed_value
+        .into_vec()
+        .unwrap_or_else(|| panic!("Binding is not a Vec"));
+    Box::into_raw(Box::new(
+        Arc::try_unwrap(values).unwrap_or_else(|values| (*values).clone()),
+    ))
+}
+
+/// Returns the [BindingKind](BindingKind) of this [Binding](mentat::Binding).
+#[no_mangle]
+pub unsafe extern "C" fn typed_value_binding_kind(typed_value: *mut Binding) -> BindingKind {
+    assert_not_null!(typed_value);
+    match *typed_value {
+        Binding::Scalar(_) => BindingKind::Scalar,
+        Binding::Vec(_) => BindingKind::Vec,
+        Binding::Map(_) => BindingKind::Map,
+        Binding::Nil => BindingKind::Nil,
+    }
+}
+
 /// Returns the [ValueType](mentat::ValueType) of this [Binding](mentat::Binding).
+///
+/// # Panics
+///
+/// If the [BindingKind](BindingKind) of the [Binding](mentat::Binding) is not `Scalar`.
 #[no_mangle]
 pub unsafe extern "C" fn typed_value_value_type(typed_value: *mut Binding) -> ValueType {
     let typed_value = &*typed_value;
@@ -1931,7 +2469,7 @@ pub unsafe extern "C" fn typed_value_list_iter_next(iter: *mut BindingIterator)
 pub unsafe extern "C" fn value_at_index(values: *mut Vec<Binding>, index: c_int) -> *mut Binding {
     assert_not_null!(values);
     let values = &*values;
-    if index < 0 || (index as usize) > values.len() {
+    if index < 0 || (index as usize) >= values.len() {
         std::ptr::null_mut()
     } else {
         // TODO: an older version of this function returned a reference into values. This
@@ -2100,6 +2638,35 @@ pub unsafe extern "C" fn value_at_index_into_uuid(
     Box::into_raw(Box::new(*uuid.as_bytes()))
 }
 
+/// Returns the value of the [Binding](mentat::Binding) at `index` as a [ByteBuffer](ByteBuffer).
+///
+/// The caller is responsible for freeing the pointer returned from this function using
+/// `byte_buffer_destroy`.
+///
+/// # Panics
+///
+/// If the [ValueType](mentat::ValueType) of the [Binding](mentat::Binding) is not [ValueType::Bytes](mentat::ValueType::Bytes).
+/// If there is no value at `index`.
+///
+// TODO Generalise with macro https://github.com/mozilla/mentat/issues/703
+#[no_mangle]
+pub unsafe extern "C" fn value_at_index_into_bytes(
+    values: *mut Vec<Binding>,
+    index: c_int,
+) -> *mut ByteBuffer {
+    assert_not_null!(values);
+    let result = &*values;
+    let value = result.get(index as usize).expect("No value at index");
+    let bytes = unwrap_conversion(
+        value.as_scalar().and_then(|v| match v {
+            TypedValue::Bytes(b) => Some(b),
+            _ => None,
+        }),
+        ValueType::Bytes,
+    );
+    ByteBuffer::from_slice(bytes)
+}
+
 /// Returns a pointer to the the [Binding](mentat::Binding) associated with the `attribute` as
 /// `:namespace/name` for the given `entid`.
 /// If there is a value for that `attribute` on the entity with id `entid` then the value is returned.
@@ -2131,6 +2698,139 @@ pub unsafe extern "C" fn store_value_for_attribute(
     translate_opt_result(result, error)
 }
 
+/// Pulls the values of `attributes` for the entity `entid`, as a [StructuredMap](mentat::StructuredMap)
+/// keyed by attribute. Attributes are given as entids, which can be found with
+/// `store_entid_for_attribute`. Attributes for which the entity has no values are omitted.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `structured_map_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn store_pull_attributes_for_entity(
+    store: *mut Store,
+    entid: Entid,
+    attributes: *const Entid,
+    attributes_len: usize,
+    error: *mut ExternError,
+) -> *mut StructuredMap {
+    assert_not_null!(store, attributes);
+    let store = &*store;
+    let attributes = slice::from_raw_parts(attributes, attributes_len);
+    let result = store.pull_attributes_for_entity(entid, attributes.iter().cloned());
+    translate_result(result, error)
+}
+
+/// Pulls the values of `attributes` for each of `entities`. The result has one value for each
+/// of `entities`, in the same order: a map, as returned by `store_pull_attributes_for_entity`,
+/// or `Nil` if the entity has no values for any of `attributes`.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `typed_value_list_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn store_pull_attributes_for_entities(
+    store: *mut Store,
+    entities: *const Entid,
+    entities_len: usize,
+    attributes: *const Entid,
+    attributes_len: usize,
+    error: *mut ExternError,
+) -> *mut Vec<Binding> {
+    assert_not_null!(store, entities, attributes);
+    let store = &*store;
+    let entities = slice::from_raw_parts(entities, entities_len);
+    let attributes = slice::from_raw_parts(attributes, attributes_len);
+    let result = store
+        .pull_attributes_for_entities(entities.iter().cloned(), attributes.iter().cloned())
+        .map(|mut pulled| {
+            entities
+                .iter()
+                .map(|e| pulled.remove(e).map_or(Binding::Nil, Binding::Map))
+                .collect::<Vec<Binding>>()
+        });
+    translate_result(result, error)
+}
+
+/// Returns the number of entries in the [StructuredMap](mentat::StructuredMap).
+#[no_mangle]
+pub unsafe extern "C" fn structured_map_len(map: *mut StructuredMap) -> c_ulonglong {
+    assert_not_null!(map);
+    let map = &*map;
+    map.len() as c_ulonglong
+}
+
+/// Returns the key of the entry at `index` in the [StructuredMap](mentat::StructuredMap) as a
+/// keyword C `String`. Entries are in the order in which they were pulled.
+/// If there is no entry at `index`, a null pointer is returned.
+///
+/// The caller is responsible for freeing the pointer returned from this function using
+/// `rust_c_string_destroy`.
+#[no_mangle]
+pub unsafe extern "C" fn structured_map_key_at(
+    map: *mut StructuredMap,
+    index: c_int,
+) -> *mut c_char {
+    assert_not_null!(map);
+    let map = &*map;
+    if index < 0 {
+        return std::ptr::null_mut();
+    }
+    map.get_index(index as usize)
+        .map_or(std::ptr::null_mut(), |(key, _)| {
+            string_to_c_char(key.to_string())
+        })
+}
+
+/// Returns the value of the entry at `index` in the [StructuredMap](mentat::StructuredMap).
+/// If there is no entry at `index`, a null pointer is returned.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `typed_value_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn structured_map_value_at(
+    map: *mut StructuredMap,
+    index: c_int,
+) -> *mut Binding {
+    assert_not_null!(map);
+    let map = &*map;
+    if index < 0 {
+        return std::ptr::null_mut();
+    }
+    map.get_index(index as usize)
+        .map_or(std::ptr::null_mut(), |(_, value)| {
+            Box::into_raw(Box::new(value.clone()))
+        })
+}
+
+/// Returns the value for `key`, given as `:namespace/name`, in the
+/// [StructuredMap](mentat::StructuredMap).
+/// If there is no entry for `key`, a null pointer is returned.
+///
+/// # Safety
+///
+/// Callers are responsible for managing the memory for the return value.
+/// A destructor `typed_value_destroy` is provided for releasing the memory for this
+/// pointer type.
+#[no_mangle]
+pub unsafe extern "C" fn structured_map_value_for_key(
+    map: *mut StructuredMap,
+    key: *const c_char,
+) -> *mut Binding {
+    assert_not_null!(map);
+    let map = &*map;
+    let kw = kw_from_string(c_char_to_string(key));
+    map.get(&kw).map_or(std::ptr::null_mut(), |value| {
+        Box::into_raw(Box::new(value.clone()))
+    })
+}
+
 /// Registers a [TxObserver](mentat::TxObserver) with the `key` to observe changes to `attributes`
 /// on this `store`.
 /// Calls `callback` is a relevant transaction occurs.
@@ -2290,6 +2990,18 @@ define_destructor!(destroy, c_void);
 /// destroy function for releasing the memory of UUIDs
 define_destructor!(uuid_destroy, [u8; 16]);
 
+/// Releases the memory of a [ByteBuffer](ByteBuffer) and the bytes it holds.
+#[no_mangle]
+pub unsafe extern "C" fn byte_buffer_destroy(buffer: *mut ByteBuffer) {
+    if !buffer.is_null() {
+        let buffer = Box::from_raw(buffer);
+        let _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len as usize,
+        ));
+    }
+}
+
 /// Destructor for releasing the memory of [InProgressBuilder](mentat::InProgressBuilder).
 define_destructor_with_lifetimes!(in_progress_builder_destroy, InProgressBuilder<'a, 'c>);
 
@@ -2299,6 +3011,24 @@ define_destructor_with_lifetimes!(
     EntityBuilder<InProgressBuilder<'a, 'c>>
 );
 
+/// Releases the memory of a [TempId](TempId) and the tempid string it holds.
+#[no_mangle]
+pub unsafe extern "C" fn temp_id_destroy(temp_id: *mut TempId) {
+    if !temp_id.is_null() {
+        let temp_id = Box::from_raw(temp_id);
+        rust_c_string_destroy(temp_id.tempid);
+    }
+}
+
+/// Destructor for releasing the memory of [TempIdIterator](TempIdIterator).
+define_destructor!(temp_id_iter_destroy, TempIdIterator);
+
+/// Destructor for releasing the memory of [AttributeBuilder](mentat::vocabulary::AttributeBuilder).
+define_destructor!(attribute_builder_destroy, AttributeBuilder);
+
+/// Destructor for releasing the memory of [Definition](mentat::vocabulary::Definition).
+define_destructor!(vocabulary_definition_destroy, Definition);
+
 /// Destructor for releasing the memory of [QueryBuilder](mentat::QueryBuilder) .
 define_destructor!(query_builder_destroy, QueryBuilder);
 
@@ -2317,6 +3047,9 @@ define_destructor!(typed_value_list_destroy, Vec<Binding>);
 /// Destructor for releasing the memory of [BindingIterator](BindingIterator) .
 define_destructor!(typed_value_list_iter_destroy, BindingIterator);
 
+/// Destructor for releasing the memory of [StructuredMap](mentat::StructuredMap).
+define_destructor!(structured_map_destroy, StructuredMap);
+
 /// Destructor for releasing the memory of [RelResult<Binding>](mentat::RelResult).
 define_destructor!(typed_value_result_set_destroy, RelResult<Binding>);
 
@@ -160,6 +160,29 @@ pub mod error {
         }
     }
 
+    /// Like `translate_result`, but for a `Result<String, E>`, which C receives as a string that
+    /// should eventually be freed with `rust_c_string_destroy`.
+    /// # Safety
+    /// Be afraid... TODO
+    pub unsafe fn translate_string_result<E>(
+        result: Result<String, E>,
+        error: *mut ExternError,
+    ) -> *mut c_char
+    where
+        E: Display,
+    {
+        assert!(!error.is_null(), "Error output parameter is not optional");
+        let error = &mut *error;
+        error.message = ptr::null_mut();
+        match result {
+            Ok(s) => string_to_c_char(s),
+            Err(e) => {
+                error.message = string_to_c_char(e.to_string());
+                ptr::null_mut()
+            }
+        }
+    }
+
     /// Identical to `translate_result`, but with additional type checking for the case that we have
     /// a `Result<(), E>` (which we're about to drop on the floor).
     /// # Safety
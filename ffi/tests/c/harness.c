@@ -0,0 +1,442 @@
+/* Copyright 2018 Mozilla
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+ * this file except in compliance with the License. You may obtain a copy of the
+ * License at http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software distributed
+ * under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+ * CONDITIONS OF ANY KIND, either express or implied. See the License for the
+ * specific language governing permissions and limitations under the License. */
+
+/* Exercises Mentat's C API from C. Built and run by `tests/c_harness.rs`; exits non-zero,
+ * having said why, if anything is amiss. */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+struct Store;
+struct InProgress;
+struct TxReport;
+struct Query;
+struct TypedValue;
+struct ByteBuffer { uint8_t *data; uint64_t len; };
+struct AttributeBuilder;
+struct Definition;
+struct TempIdIterator;
+struct StructuredMap;
+struct Values;
+struct TempId { char *tempid; int64_t entid; };
+struct ExternError { char *message; };
+
+enum ValueType {
+    ValueTypeRef = 0,
+    ValueTypeBoolean,
+    ValueTypeInstant,
+    ValueTypeLong,
+    ValueTypeDouble,
+    ValueTypeString,
+    ValueTypeKeyword,
+    ValueTypeUuid,
+    ValueTypeBytes,
+};
+
+enum BindingKind {
+    BindingKindScalar = 0,
+    BindingKindVec,
+    BindingKindMap,
+    BindingKindNil,
+};
+
+enum VocabularyOutcome {
+    VocabularyOutcomeInstalled = 0,
+    VocabularyOutcomeInstalledMissingAttributes,
+    VocabularyOutcomeExisted,
+    VocabularyOutcomeUpgraded,
+};
+
+struct Store *store_open(const char *uri, struct ExternError *error);
+void store_destroy(struct Store *store);
+void destroy(void *obj);
+void rust_c_string_destroy(char *s);
+
+struct AttributeBuilder *attribute_builder_new(enum ValueType value_type);
+void attribute_builder_multival(struct AttributeBuilder *builder, int32_t multival);
+void attribute_builder_unique_identity(struct AttributeBuilder *builder);
+void attribute_builder_fulltext(struct AttributeBuilder *builder, int32_t fulltext);
+struct Definition *vocabulary_definition_new(const char *name, uint32_t version);
+void vocabulary_definition_add_attribute(struct Definition *definition, const char *ident,
+                                         struct AttributeBuilder *builder);
+void vocabulary_definition_destroy(struct Definition *definition);
+enum VocabularyOutcome *store_ensure_vocabulary(struct Store *store,
+                                                const struct Definition *definition,
+                                                struct ExternError *error);
+
+struct TxReport *store_transact(struct Store *store, const char *transaction,
+                                struct ExternError *error);
+int64_t *tx_report_entity_for_temp_id(struct TxReport *report, const char *tempid);
+void tx_report_destroy(struct TxReport *report);
+struct TempIdIterator *tx_report_temp_ids_into_iter(struct TxReport *report);
+struct TempId *temp_id_iter_next(struct TempIdIterator *iter);
+void temp_id_destroy(struct TempId *temp_id);
+void temp_id_iter_destroy(struct TempIdIterator *iter);
+
+struct InProgress *store_begin_transaction(struct Store *store, struct ExternError *error);
+struct TxReport *in_progress_transact(struct InProgress *in_progress, const char *transaction,
+                                      struct ExternError *error);
+void in_progress_savepoint(struct InProgress *in_progress, const char *name,
+                           struct ExternError *error);
+void in_progress_rollback_savepoint(struct InProgress *in_progress, const char *name,
+                                    struct ExternError *error);
+void in_progress_release_savepoint(struct InProgress *in_progress, const char *name,
+                                   struct ExternError *error);
+void in_progress_commit(struct InProgress *in_progress, struct ExternError *error);
+
+struct Query *store_query(struct Store *store, const char *query);
+void query_builder_bind_string(struct Query *query, const char *var, const char *value);
+char *query_builder_explain(struct Query *query, struct ExternError *error);
+struct TypedValue *query_builder_execute_scalar(struct Query *query, struct ExternError *error);
+void query_builder_destroy(struct Query *query);
+int64_t typed_value_into_long(struct TypedValue *value);
+struct ByteBuffer *typed_value_into_bytes(struct TypedValue *value);
+void byte_buffer_destroy(struct ByteBuffer *buffer);
+char *typed_value_into_string(struct TypedValue *value);
+enum BindingKind typed_value_binding_kind(struct TypedValue *value);
+struct StructuredMap *typed_value_into_map(struct TypedValue *value);
+struct Values *typed_value_into_vec(struct TypedValue *value);
+void typed_value_destroy(struct TypedValue *value);
+struct TypedValue *value_at_index(struct Values *values, int32_t index);
+void typed_value_list_destroy(struct Values *values);
+
+int64_t store_entid_for_attribute(struct Store *store, const char *attr);
+struct StructuredMap *store_pull_attributes_for_entity(struct Store *store, int64_t entid,
+                                                       const int64_t *attributes,
+                                                       size_t attributes_len,
+                                                       struct ExternError *error);
+struct Values *store_pull_attributes_for_entities(struct Store *store, const int64_t *entities,
+                                                  size_t entities_len, const int64_t *attributes,
+                                                  size_t attributes_len, struct ExternError *error);
+unsigned long long structured_map_len(struct StructuredMap *map);
+char *structured_map_key_at(struct StructuredMap *map, int32_t index);
+struct TypedValue *structured_map_value_at(struct StructuredMap *map, int32_t index);
+struct TypedValue *structured_map_value_for_key(struct StructuredMap *map, const char *key);
+void structured_map_destroy(struct StructuredMap *map);
+
+static int failures = 0;
+
+#define CHECK(cond)                                                            \
+    do {                                                                       \
+        if (!(cond)) {                                                         \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,  \
+                    #cond);                                                    \
+            failures++;                                                        \
+        }                                                                      \
+    } while (0)
+
+#define CHECK_OK(error)                                                        \
+    do {                                                                       \
+        if ((error).message) {                                                 \
+            fprintf(stderr, "%s:%d: unexpected error: %s\n", __FILE__,         \
+                    __LINE__, (error).message);                                \
+            rust_c_string_destroy((error).message);                            \
+            (error).message = NULL;                                            \
+            failures++;                                                        \
+        }                                                                      \
+    } while (0)
+
+static struct Definition *people_vocabulary(void) {
+    struct Definition *definition = vocabulary_definition_new(":test/people", 1);
+
+    struct AttributeBuilder *name = attribute_builder_new(ValueTypeString);
+    attribute_builder_unique_identity(name);
+    vocabulary_definition_add_attribute(definition, ":person/name", name);
+
+    struct AttributeBuilder *age = attribute_builder_new(ValueTypeLong);
+    vocabulary_definition_add_attribute(definition, ":person/age", age);
+
+    struct AttributeBuilder *photo = attribute_builder_new(ValueTypeBytes);
+    vocabulary_definition_add_attribute(definition, ":person/photo", photo);
+
+    struct AttributeBuilder *nicknames = attribute_builder_new(ValueTypeString);
+    attribute_builder_multival(nicknames, 1);
+    vocabulary_definition_add_attribute(definition, ":person/nickname", nicknames);
+
+    return definition;
+}
+
+static void test_vocabulary(struct Store *store) {
+    struct ExternError error = {NULL};
+    struct Definition *definition = people_vocabulary();
+
+    enum VocabularyOutcome *outcome = store_ensure_vocabulary(store, definition, &error);
+    CHECK_OK(error);
+    CHECK(outcome && *outcome == VocabularyOutcomeInstalled);
+    destroy(outcome);
+
+    outcome = store_ensure_vocabulary(store, definition, &error);
+    CHECK_OK(error);
+    CHECK(outcome && *outcome == VocabularyOutcomeExisted);
+    destroy(outcome);
+
+    vocabulary_definition_destroy(definition);
+
+    /* Only strings can be fulltext. */
+    definition = vocabulary_definition_new(":test/bad", 1);
+    struct AttributeBuilder *bad = attribute_builder_new(ValueTypeLong);
+    attribute_builder_fulltext(bad, 1);
+    vocabulary_definition_add_attribute(definition, ":bad/count", bad);
+    outcome = store_ensure_vocabulary(store, definition, &error);
+    CHECK(outcome == NULL && error.message != NULL);
+    rust_c_string_destroy(error.message);
+    vocabulary_definition_destroy(definition);
+}
+
+static void test_temp_ids(struct Store *store) {
+    struct ExternError error = {NULL};
+    struct TxReport *report = store_transact(
+        store,
+        "[{:db/id \"b\" :person/name \"Bob\" :person/age 40}"
+        " {:db/id \"a\" :person/name \"Alice\" :person/age 30 :person/photo #bytes 0a0b0c}]",
+        &error);
+    CHECK_OK(error);
+    if (!report) {
+        return;
+    }
+
+    const char *expected[] = {"a", "b"};
+    int seen = 0;
+    struct TempIdIterator *iter = tx_report_temp_ids_into_iter(report);
+    struct TempId *temp_id;
+    while ((temp_id = temp_id_iter_next(iter))) {
+        CHECK(seen < 2 && strcmp(temp_id->tempid, expected[seen]) == 0);
+        int64_t *entid = tx_report_entity_for_temp_id(report, temp_id->tempid);
+        CHECK(entid && *entid == temp_id->entid);
+        destroy(entid);
+        temp_id_destroy(temp_id);
+        seen++;
+    }
+    CHECK(seen == 2);
+    temp_id_iter_destroy(iter);
+    tx_report_destroy(report);
+}
+
+static int64_t count_people(struct Store *store) {
+    struct ExternError error = {NULL};
+    struct Query *query = store_query(store, "[:find (count ?e) . :where [?e :person/name _]]");
+    struct TypedValue *count = query_builder_execute_scalar(query, &error);
+    CHECK_OK(error);
+    query_builder_destroy(query);
+    return count ? typed_value_into_long(count) : -1;
+}
+
+static void test_savepoints(struct Store *store) {
+    struct ExternError error = {NULL};
+    struct InProgress *in_progress = store_begin_transaction(store, &error);
+    CHECK_OK(error);
+
+    tx_report_destroy(in_progress_transact(in_progress, "[{:person/name \"Carol\"}]", &error));
+    CHECK_OK(error);
+
+    in_progress_savepoint(in_progress, "before_dave", &error);
+    CHECK_OK(error);
+    tx_report_destroy(in_progress_transact(in_progress, "[{:person/name \"Dave\"}]", &error));
+    CHECK_OK(error);
+    in_progress_rollback_savepoint(in_progress, "before_dave", &error);
+    CHECK_OK(error);
+    in_progress_release_savepoint(in_progress, "before_dave", &error);
+    CHECK_OK(error);
+
+    /* There's no such savepoint any more. */
+    in_progress_rollback_savepoint(in_progress, "before_dave", &error);
+    CHECK(error.message != NULL);
+    rust_c_string_destroy(error.message);
+    error.message = NULL;
+
+    in_progress_commit(in_progress, &error);
+    CHECK_OK(error);
+
+    /* Alice, Bob and Carol. */
+    CHECK(count_people(store) == 3);
+}
+
+static void test_explain(struct Store *store) {
+    struct ExternError error = {NULL};
+    struct Query *query =
+        store_query(store, "[:find ?photo . :in ?name :where [?e :person/name ?name] [?e :person/photo ?photo]]");
+    query_builder_bind_string(query, "?name", "Alice");
+
+    char *json = query_builder_explain(query, &error);
+    CHECK_OK(error);
+    CHECK(json && strstr(json, "\"sql\"") != NULL);
+    CHECK(json && strstr(json, "[ ?e :person/photo ?photo ]") != NULL);
+    rust_c_string_destroy(json);
+
+    /* Explaining kept the binding of ?name. */
+    struct TypedValue *photo = query_builder_execute_scalar(query, &error);
+    CHECK_OK(error);
+    CHECK(photo != NULL);
+    if (photo) {
+        struct ByteBuffer *bytes = typed_value_into_bytes(photo);
+        CHECK(bytes->len == 3 && bytes->data[0] == 0x0a && bytes->data[2] == 0x0c);
+        byte_buffer_destroy(bytes);
+    }
+    query_builder_destroy(query);
+
+    query = store_query(store, "[:find ?e :where [?e :person/nope _]]");
+    json = query_builder_explain(query, &error);
+    CHECK_OK(error);
+    CHECK(json && strstr(json, "\"known-empty\"") != NULL);
+    rust_c_string_destroy(json);
+    query_builder_destroy(query);
+
+    query = store_query(store, "[:find ?e :where");
+    json = query_builder_explain(query, &error);
+    CHECK(json == NULL && error.message != NULL);
+    rust_c_string_destroy(error.message);
+    query_builder_destroy(query);
+}
+
+/* Consumes `value`, which must be a string, and compares it with `expected`. */
+static int string_equals(struct TypedValue *value, const char *expected) {
+    if (!value || typed_value_binding_kind(value) != BindingKindScalar) {
+        return 0;
+    }
+    char *string = typed_value_into_string(value);
+    int equal = strcmp(string, expected) == 0;
+    rust_c_string_destroy(string);
+    return equal;
+}
+
+static void test_pull(struct Store *store) {
+    struct ExternError error = {NULL};
+    struct TxReport *report = store_transact(
+        store,
+        "[[:db/add \"a\" :person/name \"Alice\"]"
+        " [:db/add \"a\" :person/nickname \"Al\"]"
+        " [:db/add \"a\" :person/nickname \"Ally\"]]",
+        &error);
+    CHECK_OK(error);
+    if (!report) {
+        return;
+    }
+    int64_t *found = tx_report_entity_for_temp_id(report, "a");
+    CHECK(found != NULL);
+    int64_t alice = found ? *found : 0;
+    destroy(found);
+    tx_report_destroy(report);
+
+    int64_t name = store_entid_for_attribute(store, ":person/name");
+    int64_t attributes[] = {
+        name,
+        store_entid_for_attribute(store, ":person/age"),
+        store_entid_for_attribute(store, ":person/nickname"),
+    };
+
+    struct StructuredMap *map = store_pull_attributes_for_entity(store, alice, attributes, 3, &error);
+    CHECK_OK(error);
+    if (!map) {
+        return;
+    }
+    CHECK(structured_map_len(map) == 3);
+
+    /* Entries aren't in any particular order. */
+    int names = 0;
+    for (int32_t i = 0; i < 3; i++) {
+        char *key = structured_map_key_at(map, i);
+        CHECK(key != NULL);
+        if (key && strcmp(key, ":person/name") == 0) {
+            CHECK(string_equals(structured_map_value_at(map, i), "Alice"));
+            names++;
+        }
+        rust_c_string_destroy(key);
+    }
+    CHECK(names == 1);
+
+    /* There's nothing before the first entry or after the last. */
+    CHECK(structured_map_key_at(map, -1) == NULL);
+    CHECK(structured_map_key_at(map, 3) == NULL);
+    CHECK(structured_map_value_at(map, -1) == NULL);
+    CHECK(structured_map_value_at(map, 3) == NULL);
+
+    struct TypedValue *age = structured_map_value_for_key(map, ":person/age");
+    CHECK(age && typed_value_binding_kind(age) == BindingKindScalar);
+    CHECK(age && typed_value_into_long(age) == 30);
+
+    /* Alice has no photo in this map, because we didn't ask for one. */
+    CHECK(structured_map_value_for_key(map, ":person/photo") == NULL);
+
+    /* A cardinality-many attribute is pulled as a vector. */
+    struct TypedValue *nicknames = structured_map_value_for_key(map, ":person/nickname");
+    CHECK(nicknames && typed_value_binding_kind(nicknames) == BindingKindVec);
+    if (nicknames && typed_value_binding_kind(nicknames) == BindingKindVec) {
+        struct Values *values = typed_value_into_vec(nicknames);
+        struct TypedValue *first = value_at_index(values, 0);
+        struct TypedValue *second = value_at_index(values, 1);
+        CHECK(first && second);
+        if (first && second) {
+            char *a = typed_value_into_string(first);
+            char *b = typed_value_into_string(second);
+            CHECK((strcmp(a, "Al") == 0 && strcmp(b, "Ally") == 0) ||
+                  (strcmp(a, "Ally") == 0 && strcmp(b, "Al") == 0));
+            rust_c_string_destroy(a);
+            rust_c_string_destroy(b);
+        }
+        CHECK(value_at_index(values, 2) == NULL);
+        CHECK(value_at_index(values, -1) == NULL);
+        typed_value_list_destroy(values);
+    } else if (nicknames) {
+        typed_value_destroy(nicknames);
+    }
+    structured_map_destroy(map);
+
+    /* The attribute itself has none of these attributes, so it's pulled as Nil. */
+    int64_t entities[] = {alice, name};
+    struct Values *pulled = store_pull_attributes_for_entities(store, entities, 2, attributes, 3, &error);
+    CHECK_OK(error);
+    if (!pulled) {
+        return;
+    }
+
+    struct TypedValue *first = value_at_index(pulled, 0);
+    CHECK(first && typed_value_binding_kind(first) == BindingKindMap);
+    if (first && typed_value_binding_kind(first) == BindingKindMap) {
+        map = typed_value_into_map(first);
+        CHECK(structured_map_len(map) == 3);
+        CHECK(string_equals(structured_map_value_for_key(map, ":person/name"), "Alice"));
+        structured_map_destroy(map);
+    } else if (first) {
+        typed_value_destroy(first);
+    }
+
+    struct TypedValue *second = value_at_index(pulled, 1);
+    CHECK(second && typed_value_binding_kind(second) == BindingKindNil);
+    if (second) {
+        typed_value_destroy(second);
+    }
+    CHECK(value_at_index(pulled, 2) == NULL);
+    typed_value_list_destroy(pulled);
+}
+
+int main(void) {
+    struct ExternError error = {NULL};
+    struct Store *store = store_open("", &error);
+    CHECK_OK(error);
+    if (!store) {
+        return 1;
+    }
+
+    test_vocabulary(store);
+    test_temp_ids(store);
+    test_savepoints(store);
+    test_explain(store);
+    test_pull(store);
+
+    store_destroy(store);
+
+    if (failures) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    return 0;
+}
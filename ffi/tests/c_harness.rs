@@ -0,0 +1,68 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Builds `tests/c/harness.c` against the shared library and runs it, so that the C API is
+//! exercised by a C caller. Set `CC` to use a compiler other than `cc`.
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_c_harness() {
+    let source = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("c")
+        .join("harness.c");
+
+    // Test executables live in `target/<profile>/deps`, alongside the shared library that was
+    // built for them. Linking the shared library saves us from listing the native libraries it
+    // depends on.
+    let exe = env::current_exe().expect("test executable");
+    let deps = exe.parent().expect("target directory");
+    let library = deps.join(if cfg!(target_os = "macos") {
+        "libmentat_ffi.dylib"
+    } else {
+        "libmentat_ffi.so"
+    });
+    assert!(library.exists(), "{} wasn't built", library.display());
+
+    let harness = deps.join("mentat_ffi_c_harness");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .arg("-Wall")
+        .arg("-o")
+        .arg(&harness)
+        .arg(&source)
+        .arg(format!("-L{}", deps.display()))
+        .arg("-lmentat_ffi")
+        .status()
+        .expect("ran the C compiler");
+    assert!(status.success(), "couldn't build {}", source.display());
+
+    // Cargo puts `target/<profile>` on the library path for tests, and whatever library was last
+    // built there might be stale.
+    let library_path = if cfg!(target_os = "macos") {
+        "DYLD_LIBRARY_PATH"
+    } else {
+        "LD_LIBRARY_PATH"
+    };
+    let output = Command::new(&harness)
+        .env(library_path, deps)
+        .output()
+        .expect("ran the harness");
+    assert!(
+        output.status.success(),
+        "harness failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -83,6 +83,15 @@ pub enum MentatError {
     #[fail(display = "schema changed since query was prepared")]
     PreparedQuerySchemaMismatch,
 
+    #[fail(display = "can't attach a store as ${}", _0)]
+    InvalidAttachedStoreName(String),
+
+    #[fail(display = "a store is already attached as ${}", _0)]
+    StoreAlreadyAttached(String),
+
+    #[fail(display = "no store is attached as ${}", _0)]
+    StoreNotAttached(String),
+
     #[fail(
         display = "provided value of type {} doesn't match attribute value type {}",
         _0, _1
@@ -92,6 +101,12 @@ pub enum MentatError {
     #[fail(display = "{}", _0)]
     IoError(#[cause] std::io::Error),
 
+    #[fail(display = "bad JSON: {}", _0)]
+    BadJson(String),
+
+    #[fail(display = "bad Transit: {}", _0)]
+    BadTransit(String),
+
     /// We're just not done yet.  Message that the feature is recognized but not yet
     /// implemented.
     #[fail(display = "not yet implemented: {}", _0)]
@@ -31,6 +31,13 @@ pub enum BindingError {
     /// potentially erroneous) bindings.
     ExpectedBindRelOrBindColl,
 
+    /// Expected `?x` but got some other type of binding.  Expression functions produce exactly one
+    /// value.
+    ExpectedBindScalar,
+
+    /// Expected `[?x ?y]` but got some other type of binding.
+    ExpectedBindTuple,
+
     /// Expected `[?x1 … ?xN]` or `[[?x1 … ?xN]]` but got some other number of bindings.  Mentat is
     /// deliberately more strict than Datomic: we prefer placeholders to omission.
     InvalidNumberOfBindings {
@@ -90,6 +97,9 @@ pub enum AlgebrizerError {
     #[fail(display = "no function named {}", _0)]
     UnknownFunction(PlainSymbol),
 
+    #[fail(display = "no source named ${}", _0)]
+    UnknownSource(String),
+
     #[fail(display = ":limit var {} not present in :in", _0)]
     UnknownLimitVar(PlainSymbol),
 
@@ -103,6 +113,12 @@ pub enum AlgebrizerError {
     #[fail(display = "non-matching variables in 'not' clause")]
     NonMatchingVariablesInNotClause,
 
+    #[fail(display = "'optional' clause doesn't match any datoms")]
+    NoPatternInOptionalClause,
+
+    #[fail(display = "variable {} bound to a constant in 'optional' clause", _0)]
+    ConstantInOptionalClause(PlainSymbol),
+
     #[fail(display = "binding error in {}: {:?}", _0, _1)]
     InvalidBinding(PlainSymbol, BindingError),
 
@@ -0,0 +1,75 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use crate::clauses::ConjoiningClauses;
+
+use crate::types::{
+    AliasOrigin, ColumnAlternation, ColumnConstraint, ColumnConstraintOrAlternation,
+    ColumnIntersection, DatomsColumn, DatomsTable, EmptyBecause, EvolvedPattern, QualifiedAlias,
+    QueryValue, SourceAlias,
+};
+
+use crate::Known;
+
+/// The name of the source made up of the datoms asserted and retracted by the transactions
+/// given in `QueryInputs::for_delta`.
+pub const DELTA_SOURCE: &str = "delta";
+
+/// Application of the `$delta` source: `[$delta ?e :foo/bar ?v]` and
+/// `[(tx-data $delta) [[?e ?a ?v ?tx ?added]]]`.
+///
+/// The datoms of the delta live in the `transactions` table, which has the same columns as
+/// `datoms` (and also `added`), so we use it in place of the usual tables, restricted to the
+/// delta's transactions.
+impl ConjoiningClauses {
+    /// Restrict the transactions-table column `tx` to the transactions of `$delta`.
+    pub(crate) fn constrain_to_delta(&mut self, known: Known, tx: QualifiedAlias) {
+        let txes = known.delta.expect("a checked $delta source");
+        let mut constraints = txes
+            .iter()
+            .map(|t| ColumnConstraint::Equals(tx.clone(), QueryValue::Entid(*t)));
+
+        match txes.len() {
+            0 => self.mark_known_empty(EmptyBecause::EmptyDelta),
+            1 => self.wheres.add_intersection(constraints.next().unwrap()),
+            _ => {
+                let alternation = ColumnAlternation(
+                    constraints
+                        .map(|c| ColumnIntersection(vec![c.into()]))
+                        .collect(),
+                );
+                self.wheres
+                    .add(ColumnConstraintOrAlternation::Alternation(alternation));
+            }
+        }
+    }
+
+    /// A pattern over `$delta` matches every datom that its transactions asserted or retracted.
+    /// Values of fulltext attributes aren't resolved to their text.
+    pub(crate) fn apply_delta_pattern(&mut self, known: Known, pattern: EvolvedPattern) {
+        // The attribute and value must agree just as they would for the datoms table.
+        if let Err(reason) = self.table_for_places(known.schema, &pattern.attribute, &pattern.value)
+        {
+            self.mark_known_empty(reason);
+            return;
+        }
+
+        let table = DatomsTable::Transactions;
+        let alias = self.next_alias_for_table(&table);
+        let alias = SourceAlias(table, alias);
+        self.apply_pattern_clause_for_alias(known, &pattern, &alias);
+        self.constrain_to_delta(
+            known,
+            QualifiedAlias::new(alias.1.clone(), DatomsColumn::Tx),
+        );
+        self.record_origin(&alias, AliasOrigin::Pattern(pattern));
+        self.from.push(alias);
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use core_traits::{TypedValue, ValueType, ValueTypeSet};
+
+use mentat_core::Schema;
+
+use edn::query::{Binding, FnArg, NonIntegerConstant, PlainSymbol, Variable, WhereFn};
+
+use crate::clauses::ConjoiningClauses;
+
+use query_algebrizer_traits::errors::{AlgebrizerError, BindingError, Result};
+
+use crate::types::{
+    ArithmeticOperator, Column, ColumnConstraint, Inequality, QueryValue, ValueExpression,
+};
+
+use crate::Known;
+
+/// Application of expression functions: `[(+ ?x 1) ?y]`, `[(str ?a ?b) ?s]`, and friends.
+///
+/// Each binds a single variable to a value computed by SQLite from other values, so each
+/// argument must be a constant or a variable bound earlier in the query.
+impl ConjoiningClauses {
+    /// Expression functions bind exactly one variable.
+    pub(crate) fn scalar_binding(where_fn: &WhereFn) -> Result<Variable> {
+        match where_fn.binding {
+            Binding::BindScalar(ref var) => Ok(var.clone()),
+            _ => bail!(AlgebrizerError::InvalidBinding(
+                where_fn.operator.clone(),
+                BindingError::ExpectedBindScalar
+            )),
+        }
+    }
+
+    /// Bind `var` to the value of `expression`, which is known to have type `value_type`.
+    pub(crate) fn bind_expression(
+        &mut self,
+        schema: &Schema,
+        var: Variable,
+        expression: ValueExpression,
+        value_type: ValueType,
+    ) {
+        self.constrain_var_to_type(var.clone(), value_type);
+        if self.is_known_empty() {
+            return;
+        }
+
+        let alias = self.next_alias_for_expression();
+        self.bind_column_to_var(schema, alias, Column::Expression(Box::new(expression)), var);
+    }
+
+    /// `+`, `-`, and `*` produce a long if every argument is known to be a long, and a double
+    /// otherwise.  A long that would overflow fails the query.  `/` always produces a double;
+    /// `quot` and `mod` take and produce longs.
+    ///
+    /// Rows for which a divisor is zero are discarded.
+    pub(crate) fn apply_arithmetic(
+        &mut self,
+        known: Known,
+        operator: ArithmeticOperator,
+        where_fn: WhereFn,
+    ) -> Result<()> {
+        let integral = matches!(
+            operator,
+            ArithmeticOperator::Quotient | ArithmeticOperator::Modulo
+        );
+
+        let expected = if operator.divides() { 2 } else { 1 };
+        if where_fn.args.len() < expected || (integral && where_fn.args.len() != expected) {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                where_fn.operator.clone(),
+                where_fn.args.len(),
+                expected
+            ));
+        }
+
+        let var = ConjoiningClauses::scalar_binding(&where_fn)?;
+
+        let mut all_longs = true;
+        let mut args = Vec::with_capacity(where_fn.args.len());
+        for (position, arg) in where_fn.args.into_iter().enumerate() {
+            let arg_var = match arg {
+                FnArg::Variable(ref v) => Some(v.clone()),
+                _ => None,
+            };
+            let value = if integral {
+                self.resolve_long_argument(&where_fn.operator, position, arg)?
+            } else {
+                self.resolve_numeric_argument(&where_fn.operator, position, arg)?
+            };
+            all_longs = all_longs
+                && match (&value, arg_var) {
+                    (QueryValue::TypedValue(v), _) => v.value_type() == ValueType::Long,
+                    (_, Some(v)) => self.known_type(&v) == Some(ValueType::Long),
+                    _ => false,
+                };
+            args.push(value);
+        }
+
+        if operator.divides() {
+            // SQLite divides by zero to produce NULL, which we can't bind.
+            for divisor in args.iter().skip(1) {
+                match divisor {
+                    QueryValue::TypedValue(TypedValue::Long(v)) if *v != 0 => continue,
+                    QueryValue::TypedValue(TypedValue::Double(v)) if v.0 != 0.0 => continue,
+                    _ => {}
+                }
+                self.wheres.add_intersection(ColumnConstraint::Inequality {
+                    operator: Inequality::NotEquals,
+                    left: divisor.clone(),
+                    right: QueryValue::TypedValue(TypedValue::Long(0)),
+                });
+            }
+        }
+
+        let result_type = match operator {
+            ArithmeticOperator::Divide => ValueType::Double,
+            _ if all_longs => ValueType::Long,
+            _ => ValueType::Double,
+        };
+
+        let expression = ValueExpression::Arithmetic {
+            operator,
+            args,
+            result_type,
+        };
+        self.bind_expression(known.schema, var, expression, result_type);
+        Ok(())
+    }
+
+    /// `str` joins strings and longs together into a string.
+    pub(crate) fn apply_str(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
+        if where_fn.args.is_empty() {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                where_fn.operator.clone(),
+                0,
+                1
+            ));
+        }
+
+        let var = ConjoiningClauses::scalar_binding(&where_fn)?;
+
+        let mut args = Vec::with_capacity(where_fn.args.len());
+        for (position, arg) in where_fn.args.into_iter().enumerate() {
+            args.push(self.resolve_str_argument(&where_fn.operator, position, arg)?);
+        }
+
+        self.bind_expression(
+            known.schema,
+            var,
+            ValueExpression::Concatenate(args),
+            ValueType::String,
+        );
+        Ok(())
+    }
+
+    /// `subs` takes a string, a start index and an optional end index, counting characters from
+    /// zero.  Indices out of range are clamped as by SQLite's `substr`, and an end before the start
+    /// gives the empty string.
+    pub(crate) fn apply_subs(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
+        if where_fn.args.len() != 2 && where_fn.args.len() != 3 {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                where_fn.operator.clone(),
+                where_fn.args.len(),
+                2
+            ));
+        }
+
+        let var = ConjoiningClauses::scalar_binding(&where_fn)?;
+
+        let mut args = where_fn.args.into_iter();
+        let string = self.resolve_string_argument(&where_fn.operator, 0, args.next().unwrap())?;
+        let start = self.resolve_long_argument(&where_fn.operator, 1, args.next().unwrap())?;
+        let end = match args.next() {
+            Some(end) => Some(self.resolve_long_argument(&where_fn.operator, 2, end)?),
+            None => None,
+        };
+
+        // A range that ends before it starts is a mistake we can catch now.  If we can't, the
+        // substring is empty.
+        if let (
+            QueryValue::TypedValue(TypedValue::Long(start)),
+            Some(QueryValue::TypedValue(TypedValue::Long(end))),
+        ) = (&start, &end)
+        {
+            if end < start {
+                bail!(AlgebrizerError::InvalidArgument(
+                    where_fn.operator.clone(),
+                    "an end index no less than the start index",
+                    2
+                ));
+            }
+        }
+
+        let expression = ValueExpression::Substring { string, start, end };
+        self.bind_expression(known.schema, var, expression, ValueType::String);
+        Ok(())
+    }
+
+    /// `count` is the number of characters in a string.
+    pub(crate) fn apply_count(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
+        if where_fn.args.len() != 1 {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                where_fn.operator.clone(),
+                where_fn.args.len(),
+                1
+            ));
+        }
+
+        let var = ConjoiningClauses::scalar_binding(&where_fn)?;
+
+        let string = self.resolve_string_argument(
+            &where_fn.operator,
+            0,
+            where_fn.args.into_iter().next().unwrap(),
+        )?;
+
+        self.bind_expression(
+            known.schema,
+            var,
+            ValueExpression::Length(string),
+            ValueType::Long,
+        );
+        Ok(())
+    }
+
+    /// Just like `resolve_string_argument`, but also accepting longs, which SQLite turns into
+    /// strings just as Clojure's `str` would.
+    fn resolve_str_argument(
+        &mut self,
+        function: &PlainSymbol,
+        position: usize,
+        arg: FnArg,
+    ) -> Result<QueryValue> {
+        let types = ValueTypeSet::of_one(ValueType::String).union(ValueTypeSet::of_longs());
+        match arg {
+            FnArg::Variable(var) => match self.bound_value(&var) {
+                Some(v) => {
+                    if types.contains(v.value_type()) {
+                        Ok(QueryValue::TypedValue(v))
+                    } else {
+                        bail!(AlgebrizerError::InputTypeDisagreement(
+                            var.name(),
+                            ValueType::String,
+                            v.value_type()
+                        ))
+                    }
+                }
+                None => {
+                    self.narrow_types_for_var(var.clone(), types);
+                    self.column_bindings
+                        .get(&var)
+                        .and_then(|cols| cols.first().map(|col| QueryValue::Column(col.clone())))
+                        .ok_or_else(|| AlgebrizerError::UnboundVariable(var.name()))
+                }
+            },
+            FnArg::EntidOrInteger(i) => Ok(QueryValue::TypedValue(TypedValue::Long(i))),
+            FnArg::Constant(NonIntegerConstant::Text(s)) => {
+                Ok(QueryValue::TypedValue(TypedValue::String(s)))
+            }
+            _ => bail!(AlgebrizerError::InvalidArgumentType(
+                function.clone(),
+                types,
+                position
+            )),
+        }
+    }
+}
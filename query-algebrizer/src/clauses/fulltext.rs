@@ -8,27 +8,76 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
-use core_traits::{TypedValue, ValueType};
+use std::collections::BTreeMap;
 
-use mentat_core::HasSchema;
+use core_traits::attribute::FulltextIndex;
+use core_traits::{Entid, TypedValue, ValueType};
+
+use mentat_core::{HasSchema, Schema};
 
 use mentat_core::util::Either;
 
-use edn::query::{Binding, FnArg, NonIntegerConstant, SrcVar, VariableOrPlaceholder, WhereFn};
+use edn::query::{
+    Binding, FnArg, Keyword, NonIntegerConstant, OrJoin, OrWhereClause, PlainSymbol, SrcVar,
+    UnifyVars, VariableOrPlaceholder, WhereClause, WhereFn,
+};
 
 use crate::clauses::ConjoiningClauses;
 
 use query_algebrizer_traits::errors::{AlgebrizerError, BindingError, Result};
 
 use crate::types::{
-    Column, ColumnConstraint, DatomsColumn, DatomsTable, EmptyBecause, FulltextColumn,
-    QualifiedAlias, QueryValue, SourceAlias,
+    AliasOrigin, Column, ColumnAlternation, ColumnConstraint, ColumnConstraintOrAlternation,
+    ColumnIntersection, DatomsColumn, DatomsTable, EmptyBecause, FulltextColumn, QualifiedAlias,
+    QueryValue, SourceAlias,
 };
 
 use crate::Known;
 
 impl ConjoiningClauses {
-    #[allow(unused_variables)]
+    /// Resolve a single attribute argument to `fulltext`: an ident, an entid, or a variable bound
+    /// to an entid.
+    fn resolve_fulltext_attribute(
+        &self,
+        schema: &Schema,
+        operator: &PlainSymbol,
+        arg: FnArg,
+    ) -> Result<Entid> {
+        // TODO: improve the expression of this matching, possibly by using attribute_for_* uniformly.
+        let a = match arg {
+            FnArg::IdentOrKeyword(i) => schema.get_entid(&i).map(|k| k.into()),
+            // Must be an entid.
+            FnArg::EntidOrInteger(e) => Some(e),
+            FnArg::Variable(v) => {
+                // If it's already bound, then let's expand the variable.
+                // TODO: allow non-constant attributes.
+                match self.bound_value(&v) {
+                    Some(TypedValue::Ref(entid)) => Some(entid),
+                    Some(tv) => bail!(AlgebrizerError::InputTypeDisagreement(
+                        v.name(),
+                        ValueType::Ref,
+                        tv.value_type()
+                    )),
+                    None => bail!(AlgebrizerError::UnboundVariable((*v.0).clone())),
+                }
+            }
+            _ => None,
+        };
+
+        // An unknown ident, or an entity that isn't present in the store, is likely enough to be
+        // a coding error that we choose to bail instead of marking the pattern as known-empty.
+        match a {
+            Some(a) if schema.attribute_for_entid(a).is_some() => Ok(a),
+            _ => bail!(AlgebrizerError::InvalidArgument(
+                operator.clone(),
+                "attribute",
+                1
+            )),
+        }
+    }
+
+    /// Apply `[(fulltext $ attributes search) [[?entity ?value ?tx ?score ?snippet ?highlight
+    /// ?attribute]]]`.  Trailing bindings can be omitted, and any binding can be `_`.
     pub(crate) fn apply_fulltext(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
         if where_fn.args.len() != 3 {
             bail!(AlgebrizerError::InvalidNumberOfArguments(
@@ -54,16 +103,16 @@ impl ConjoiningClauses {
             ));
         }
 
-        // We should have exactly four bindings. Destructure them now.
+        // We should have at most seven bindings. Destructure them now.
         let bindings = match where_fn.binding {
             Binding::BindRel(bindings) => {
                 let bindings_count = bindings.len();
-                if bindings_count < 1 || bindings_count > 4 {
+                if bindings_count < 1 || bindings_count > 7 {
                     bail!(AlgebrizerError::InvalidBinding(
                         where_fn.operator.clone(),
                         BindingError::InvalidNumberOfBindings {
                             number: bindings.len(),
-                            expected: 4,
+                            expected: 7,
                         }
                     ));
                 }
@@ -76,6 +125,7 @@ impl ConjoiningClauses {
                 ))
             }
         };
+        let all_bindings = bindings.clone();
         let mut bindings = bindings.into_iter();
         let b_entity = bindings.next().unwrap();
         let b_value = bindings
@@ -87,6 +137,15 @@ impl ConjoiningClauses {
         let b_score = bindings
             .next()
             .unwrap_or(VariableOrPlaceholder::Placeholder);
+        let b_snippet = bindings
+            .next()
+            .unwrap_or(VariableOrPlaceholder::Placeholder);
+        let b_highlight = bindings
+            .next()
+            .unwrap_or(VariableOrPlaceholder::Placeholder);
+        let b_attribute = bindings
+            .next()
+            .unwrap_or(VariableOrPlaceholder::Placeholder);
 
         let mut args = where_fn.args.into_iter();
 
@@ -102,63 +161,111 @@ impl ConjoiningClauses {
 
         let schema = known.schema;
 
-        // TODO: accept placeholder and set of attributes.  Alternately, consider putting the search
-        // term before the attribute arguments and collect the (variadic) attributes into a set.
-        // let a: Entid = self.resolve_attribute_argument(&where_fn.operator, 1, args.next().unwrap())?;
-        //
-        // TODO: improve the expression of this matching, possibly by using attribute_for_* uniformly.
-        let a = match args.next().unwrap() {
-            FnArg::IdentOrKeyword(i) => schema.get_entid(&i).map(|k| k.into()),
-            // Must be an entid.
-            FnArg::EntidOrInteger(e) => Some(e),
-            FnArg::Variable(v) => {
-                // If it's already bound, then let's expand the variable.
-                // TODO: allow non-constant attributes.
-                match self.bound_value(&v) {
-                    Some(TypedValue::Ref(entid)) => Some(entid),
-                    Some(tv) => bail!(AlgebrizerError::InputTypeDisagreement(
-                        v.name(),
-                        ValueType::Ref,
-                        tv.value_type()
-                    )),
-                    None => bail!(AlgebrizerError::UnboundVariable((*v.0).clone())),
+        // The attributes to search: a single attribute, a vector of attributes, or `:any`.
+        let operator = &where_fn.operator;
+        let attributes: Vec<Entid> = match args.next().unwrap() {
+            FnArg::IdentOrKeyword(ref k) if *k == Keyword::plain("any") => schema
+                .attribute_map
+                .iter()
+                .filter(|(_, attribute)| attribute.fulltext)
+                .map(|(&a, _)| a)
+                .collect(),
+            FnArg::Vector(args) => args
+                .into_iter()
+                .map(|arg| self.resolve_fulltext_attribute(schema, operator, arg))
+                .collect::<Result<_>>()?,
+            arg => vec![self.resolve_fulltext_attribute(schema, &where_fn.operator, arg)?],
+        };
+
+        // Attributes with the same tokenizer settings share an index, which we can search in one
+        // go.  We can never get results from a non-fulltext attribute!
+        let mut by_index: BTreeMap<FulltextIndex, Vec<Entid>> = BTreeMap::new();
+        for &a in attributes.iter() {
+            match schema.attribute_for_entid(a) {
+                Some(attribute) if attribute.fulltext => {
+                    by_index
+                        .entry(attribute.fulltext_index())
+                        .or_default()
+                        .push(a);
                 }
+                _ => {}
             }
-            _ => None,
-        };
+        }
 
-        // An unknown ident, or an entity that isn't present in the store, or isn't a fulltext
-        // attribute, is likely enough to be a coding error that we choose to bail instead of
-        // marking the pattern as known-empty.
-        let op = where_fn.operator.clone(); //TODO(gburd): remove me...
-        let a = a.ok_or_else(move || AlgebrizerError::InvalidArgument(op, "attribute", 1))?;
-        let op = where_fn.operator.clone(); //TODO(gburd): remove me...
-        let attribute = schema
-            .attribute_for_entid(a)
-            .cloned()
-            .ok_or_else(move || AlgebrizerError::InvalidArgument(op, "attribute", 1))?;
-
-        if !attribute.fulltext {
-            // We can never get results from a non-fulltext attribute!
-            println!("Can't run fulltext on non-fulltext attribute {}.", a);
-            self.mark_known_empty(EmptyBecause::NonFulltextAttribute(a));
-            return Ok(());
+        let search_arg = args.next().unwrap();
+
+        if by_index.len() > 1 {
+            // Search each index separately, and union the results.
+            let clauses = by_index
+                .into_values()
+                .map(|attributes| {
+                    OrWhereClause::Clause(WhereClause::WhereFn(WhereFn {
+                        operator: operator.clone(),
+                        args: vec![
+                            FnArg::SrcVar(SrcVar::DefaultSrc),
+                            FnArg::Vector(
+                                attributes.into_iter().map(FnArg::EntidOrInteger).collect(),
+                            ),
+                            search_arg.clone(),
+                        ],
+                        binding: Binding::BindRel(all_bindings.clone()),
+                    }))
+                })
+                .collect();
+            return self.apply_or_join(known, OrJoin::new(UnifyVars::Implicit, clauses));
         }
 
-        let fulltext_values_alias = self.next_alias_for_table(DatomsTable::FulltextValues);
-        let datoms_table_alias = self.next_alias_for_table(DatomsTable::Datoms);
+        let (index, attributes) = match by_index.into_iter().next() {
+            Some(group) => group,
+            None => {
+                let because = match attributes.first() {
+                    Some(&a) => EmptyBecause::NonFulltextAttribute(a),
+                    None => EmptyBecause::NoFulltextAttributes,
+                };
+                self.mark_known_empty(because);
+                return Ok(());
+            }
+        };
 
-        // We do a fulltext lookup by joining the fulltext values table against datoms -- just
-        // like applying a pattern, but two tables contribute instead of one.
-        self.from.push(SourceAlias(
-            DatomsTable::FulltextValues,
-            fulltext_values_alias.clone(),
-        ));
-        self.from
-            .push(SourceAlias(DatomsTable::Datoms, datoms_table_alias.clone()));
+        let fulltext_values_alias = self.next_alias_for_table(&DatomsTable::FulltextIndex(index));
+        let datoms_table_alias = self.next_alias_for_table(&DatomsTable::Datoms);
+
+        // We do a fulltext lookup by joining the fulltext index against datoms -- just like
+        // applying a pattern, but two tables contribute instead of one.
+        for alias in [
+            SourceAlias(
+                DatomsTable::FulltextIndex(index),
+                fulltext_values_alias.clone(),
+            ),
+            SourceAlias(DatomsTable::Datoms, datoms_table_alias.clone()),
+        ] {
+            self.record_origin(&alias, AliasOrigin::Function(where_fn.operator.clone()));
+            self.from.push(alias);
+        }
 
-        // TODO: constrain the type in the more general cases (e.g., `a` is a var).
-        self.constrain_attribute(datoms_table_alias.clone(), a);
+        if let [a] = attributes[..] {
+            self.constrain_attribute(datoms_table_alias.clone(), a);
+        } else {
+            let attribute_column = QualifiedAlias(
+                datoms_table_alias.clone(),
+                Column::Fixed(DatomsColumn::Attribute),
+            );
+            let alternation = ColumnAlternation(
+                attributes
+                    .into_iter()
+                    .map(|a| {
+                        ColumnIntersection(vec![ColumnConstraintOrAlternation::Constraint(
+                            ColumnConstraint::Equals(
+                                attribute_column.clone(),
+                                QueryValue::Entid(a),
+                            ),
+                        )])
+                    })
+                    .collect(),
+            );
+            self.wheres
+                .add(ColumnConstraintOrAlternation::Alternation(alternation));
+        }
 
         // Join the datoms table to the fulltext values table.
         self.wheres.add_intersection(ColumnConstraint::Equals(
@@ -178,7 +285,7 @@ impl ConjoiningClauses {
         // - It's already bound, either by input or by a previous pattern like `ground`.
         // - It's not already bound, but it's a defined input of type Text. Not yet implemented: TODO.
         // - It's not bound. The query cannot be algebrized.
-        let search: Either<TypedValue, QualifiedAlias> = match args.next().unwrap() {
+        let search: Either<TypedValue, QualifiedAlias> = match search_arg {
             FnArg::Constant(NonIntegerConstant::Text(s)) => Either::Left(TypedValue::String(s)),
             FnArg::Variable(in_var) => {
                 match self.bound_value(&in_var) {
@@ -264,7 +371,7 @@ impl ConjoiningClauses {
 
             self.bind_column_to_var(
                 schema,
-                fulltext_values_alias,
+                fulltext_values_alias.clone(),
                 Column::Fulltext(FulltextColumn::Text),
                 var.clone(),
             );
@@ -277,24 +384,65 @@ impl ConjoiningClauses {
                 return Ok(());
             }
 
-            self.bind_column_to_var(schema, datoms_table_alias, DatomsColumn::Tx, var.clone());
+            self.bind_column_to_var(
+                schema,
+                datoms_table_alias.clone(),
+                DatomsColumn::Tx,
+                var.clone(),
+            );
         }
 
-        if let VariableOrPlaceholder::Variable(ref var) = b_score {
-            // Scores are doubles.
-            self.constrain_var_to_type(var.clone(), ValueType::Double);
-
-            // We do not allow the score to be bound.
-            if self.value_bindings.contains_key(var) || self.input_variables.contains(var) {
-                bail!(AlgebrizerError::InvalidBinding(
-                    var.name(),
-                    BindingError::UnexpectedBinding
-                ));
+        if let VariableOrPlaceholder::Variable(ref var) = b_attribute {
+            // Attributes are refs.
+            self.constrain_var_to_type(var.clone(), ValueType::Ref);
+            if self.is_known_empty() {
+                return Ok(());
             }
 
-            // We bind the value ourselves. This handily takes care of substituting into existing uses.
-            // TODO: produce real scores using SQLite's matchinfo.
-            self.bind_value(var, TypedValue::Double(0.0.into()));
+            self.bind_column_to_var(
+                schema,
+                datoms_table_alias,
+                DatomsColumn::Attribute,
+                var.clone(),
+            );
+        }
+
+        // The score, snippet, and highlight are computed by SQLite for each match.  They're
+        // columns, not values, so they can't be bound in advance.
+        let computed = vec![
+            (b_score, FulltextColumn::Score, ValueType::Double),
+            (
+                b_snippet,
+                FulltextColumn::Snippet(index, self.fulltext_markup.clone()),
+                ValueType::String,
+            ),
+            (
+                b_highlight,
+                FulltextColumn::Highlight(index, self.fulltext_markup.clone()),
+                ValueType::String,
+            ),
+        ];
+        for (binding, column, value_type) in computed {
+            if let VariableOrPlaceholder::Variable(ref var) = binding {
+                if self.value_bindings.contains_key(var) || self.input_variables.contains(var) {
+                    bail!(AlgebrizerError::InvalidBinding(
+                        var.name(),
+                        BindingError::UnexpectedBinding
+                    ));
+                }
+
+                self.constrain_var_to_type(var.clone(), value_type);
+                if self.is_known_empty() {
+                    return Ok(());
+                }
+
+                self.bind_column_to_var(
+                    schema,
+                    fulltext_values_alias.clone(),
+                    Column::Fulltext(column),
+                    var.clone(),
+                );
+            }
         }
 
         Ok(())
@@ -388,7 +536,7 @@ mod testing {
             ColumnConstraint::Equals(
                 QualifiedAlias("datoms01".to_string(), Column::Fixed(DatomsColumn::Value)),
                 QueryValue::Column(QualifiedAlias(
-                    "fulltext_values00".to_string(),
+                    "fulltext_index00".to_string(),
                     Column::Fulltext(FulltextColumn::Rowid)
                 ))
             )
@@ -398,7 +546,7 @@ mod testing {
             clauses.0[2],
             ColumnConstraint::Matches(
                 QualifiedAlias(
-                    "fulltext_values00".to_string(),
+                    "fulltext_index00".to_string(),
                     Column::Fulltext(FulltextColumn::Text)
                 ),
                 QueryValue::TypedValue("needle".into())
@@ -407,7 +555,7 @@ mod testing {
         );
 
         let bindings = cc.column_bindings;
-        assert_eq!(bindings.len(), 3);
+        assert_eq!(bindings.len(), 4);
 
         assert_eq!(
             bindings
@@ -425,7 +573,7 @@ mod testing {
                 .expect("column binding for ?value")
                 .clone(),
             vec![QualifiedAlias(
-                "fulltext_values00".to_string(),
+                "fulltext_index00".to_string(),
                 Column::Fulltext(FulltextColumn::Text)
             )]
         );
@@ -440,14 +588,15 @@ mod testing {
             )]
         );
 
-        // Score is a value binding.
-        let values = cc.value_bindings;
         assert_eq!(
-            values
+            bindings
                 .get(&Variable::from_valid_name("?score"))
                 .expect("column binding for ?score")
                 .clone(),
-            TypedValue::Double(0.0.into())
+            vec![QualifiedAlias(
+                "fulltext_index00".to_string(),
+                Column::Fulltext(FulltextColumn::Score)
+            )]
         );
 
         let known_types = cc.known_types;
@@ -0,0 +1,312 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use std::collections::BTreeSet;
+
+use core_traits::{Attribute, Entid, ValueType, ValueTypeSet};
+
+use mentat_core::{HasSchema, Schema};
+
+use edn::query::{
+    Binding, FnArg, NotJoin, Pattern, PatternNonValuePlace, PatternValuePlace, PlainSymbol,
+    Predicate, SrcVar, UnifyVars, VariableOrPlaceholder, WhereClause, WhereFn,
+};
+
+use crate::clauses::ConjoiningClauses;
+
+use query_algebrizer_traits::errors::{AlgebrizerError, BindingError, Result};
+
+use crate::types::{
+    AliasOrigin, Column, ColumnConstraint, DatomsColumn, DatomsTable, QualifiedAlias, QueryValue,
+    SourceAlias, ValueExpression,
+};
+
+use crate::Known;
+
+/// Application of the functions that handle optional attributes: `get-else`, `get-some`, and
+/// `missing?`.
+impl ConjoiningClauses {
+    fn check_default_source(function: &PlainSymbol, arg: FnArg) -> Result<()> {
+        // TODO: process source variables.
+        match arg {
+            FnArg::SrcVar(SrcVar::DefaultSrc) => Ok(()),
+            _ => bail!(AlgebrizerError::InvalidArgument(
+                function.clone(),
+                "source variable",
+                0
+            )),
+        }
+    }
+
+    /// Resolve a keyword or entid to an attribute in the schema.
+    fn resolve_attribute_argument<'s>(
+        schema: &'s Schema,
+        function: &PlainSymbol,
+        position: usize,
+        arg: FnArg,
+        cardinality_one: bool,
+    ) -> Result<(Entid, &'s Attribute)> {
+        let entid = match arg {
+            FnArg::IdentOrKeyword(ref k) => schema
+                .get_entid(k)
+                .map(|e| e.0)
+                .ok_or_else(|| AlgebrizerError::UnrecognizedIdent(k.to_string()))?,
+            FnArg::EntidOrInteger(e) => e,
+            _ => bail!(AlgebrizerError::InvalidArgument(
+                function.clone(),
+                "attribute",
+                position
+            )),
+        };
+
+        match schema.attribute_for_entid(entid) {
+            Some(attribute) if cardinality_one && attribute.multival => {
+                bail!(AlgebrizerError::InvalidArgument(
+                    function.clone(),
+                    "cardinality-one attribute",
+                    position
+                ))
+            }
+            Some(attribute) => Ok((entid, attribute)),
+            None => bail!(AlgebrizerError::InvalidArgument(
+                function.clone(),
+                "attribute",
+                position
+            )),
+        }
+    }
+
+    /// `[(get-else $ ?e :some/attr default) ?v]` binds `?v` to the value of the cardinality-one
+    /// attribute `:some/attr` of `?e`, or to `default` if `?e` has no such value.
+    pub(crate) fn apply_get_else(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
+        if where_fn.args.len() != 4 {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                where_fn.operator.clone(),
+                where_fn.args.len(),
+                4
+            ));
+        }
+
+        let var = ConjoiningClauses::scalar_binding(&where_fn)?;
+
+        let schema = known.schema;
+        let mut args = where_fn.args.into_iter();
+
+        ConjoiningClauses::check_default_source(&where_fn.operator, args.next().unwrap())?;
+        let entity =
+            self.resolve_ref_argument(schema, &where_fn.operator, 1, args.next().unwrap())?;
+        let (attribute, a) = ConjoiningClauses::resolve_attribute_argument(
+            schema,
+            &where_fn.operator,
+            2,
+            args.next().unwrap(),
+            true,
+        )?;
+        let default = self.resolve_typed_argument(
+            schema,
+            &where_fn.operator,
+            3,
+            args.next().unwrap(),
+            a.value_type,
+        )?;
+
+        // Fulltext values are stored out of line.
+        let table = if a.fulltext {
+            DatomsTable::FulltextDatoms
+        } else {
+            DatomsTable::Datoms
+        };
+        let alias = self.next_alias_for_table(&table);
+        let source = SourceAlias(table, alias);
+
+        let expression = ValueExpression::AttributeValue {
+            source,
+            entity,
+            attribute,
+            default,
+            value_type: a.value_type,
+        };
+        self.bind_expression(schema, var, expression, a.value_type);
+        Ok(())
+    }
+
+    /// `[(get-some $ ?e :some/attr :other/attr …) [?a ?v]]` binds `?a` and `?v` to the first of
+    /// the given cardinality-one attributes that `?e` has a value for, and to that value.  If `?e`
+    /// has none of them, the clause doesn't match.
+    pub(crate) fn apply_get_some(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
+        if where_fn.args.len() < 3 {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                where_fn.operator.clone(),
+                where_fn.args.len(),
+                3
+            ));
+        }
+
+        if where_fn.binding.is_empty() {
+            // The binding must introduce at least one bound variable.
+            bail!(AlgebrizerError::InvalidBinding(
+                where_fn.operator.clone(),
+                BindingError::NoBoundVariable
+            ));
+        }
+
+        if !where_fn.binding.is_valid() {
+            // The binding must not duplicate bound variables.
+            bail!(AlgebrizerError::InvalidBinding(
+                where_fn.operator.clone(),
+                BindingError::RepeatedBoundVariable
+            ));
+        }
+
+        // We should have exactly two bindings. Destructure them now.
+        let (a_var, v_var) = match where_fn.binding {
+            Binding::BindTuple(bindings) => {
+                let bindings_count = bindings.len();
+                if bindings_count != 2 {
+                    bail!(AlgebrizerError::InvalidBinding(
+                        where_fn.operator.clone(),
+                        BindingError::InvalidNumberOfBindings {
+                            number: bindings_count,
+                            expected: 2,
+                        }
+                    ));
+                }
+                let mut bindings = bindings.into_iter();
+                (bindings.next().unwrap(), bindings.next().unwrap())
+            }
+            Binding::BindScalar(_) | Binding::BindColl(_) | Binding::BindRel(_) => {
+                bail!(AlgebrizerError::InvalidBinding(
+                    where_fn.operator.clone(),
+                    BindingError::ExpectedBindTuple
+                ))
+            }
+        };
+
+        let schema = known.schema;
+        let mut args = where_fn.args.into_iter();
+
+        ConjoiningClauses::check_default_source(&where_fn.operator, args.next().unwrap())?;
+        let entity =
+            self.resolve_ref_argument(schema, &where_fn.operator, 1, args.next().unwrap())?;
+
+        let mut attributes = Vec::with_capacity(args.len());
+        let mut value_types = ValueTypeSet::none();
+        let mut fulltext = BTreeSet::new();
+        for (i, arg) in args.enumerate() {
+            let (attribute, a) = ConjoiningClauses::resolve_attribute_argument(
+                schema,
+                &where_fn.operator,
+                i + 2,
+                arg,
+                true,
+            )?;
+            attributes.push(attribute);
+            value_types.insert(a.value_type);
+            fulltext.insert(a.fulltext);
+        }
+
+        let table = match (fulltext.contains(&true), fulltext.contains(&false)) {
+            (true, false) => DatomsTable::FulltextDatoms,
+            (false, true) => DatomsTable::Datoms,
+            _ => DatomsTable::AllDatoms,
+        };
+        let datoms = self.next_alias_for_table(&table);
+        let alias = SourceAlias(table, datoms.clone());
+        self.record_origin(&alias, AliasOrigin::Function(where_fn.operator.clone()));
+        self.from.push(alias);
+
+        // Every datom is in `datoms`, so that's where we look for the first attribute.
+        let source = SourceAlias(
+            DatomsTable::Datoms,
+            self.next_alias_for_table(&DatomsTable::Datoms),
+        );
+        let first = QualifiedAlias(
+            self.next_alias_for_expression(),
+            Column::Expression(Box::new(ValueExpression::FirstAttribute {
+                source,
+                entity: entity.clone(),
+                attributes,
+            })),
+        );
+
+        self.wheres.add_intersection(ColumnConstraint::Equals(
+            QualifiedAlias::new(datoms.clone(), DatomsColumn::Entity),
+            entity,
+        ));
+        self.wheres.add_intersection(ColumnConstraint::Equals(
+            QualifiedAlias::new(datoms.clone(), DatomsColumn::Attribute),
+            QueryValue::Column(first),
+        ));
+
+        if let VariableOrPlaceholder::Variable(var) = a_var {
+            self.constrain_var_to_type(var.clone(), ValueType::Ref);
+            self.bind_column_to_var(schema, datoms.clone(), DatomsColumn::Attribute, var);
+        }
+
+        if let VariableOrPlaceholder::Variable(var) = v_var {
+            self.narrow_types_for_var(var.clone(), value_types);
+            self.bind_column_to_var(schema, datoms, DatomsColumn::Value, var);
+        }
+
+        Ok(())
+    }
+
+    /// `[(missing? $ ?e :some/attr)]` holds when `?e` has no value for `:some/attr`.  This is
+    /// exactly `(not [?e :some/attr])`, and that's how we implement it.
+    pub(crate) fn apply_missing(&mut self, known: Known, predicate: Predicate) -> Result<()> {
+        if predicate.args.len() != 3 {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                predicate.operator.clone(),
+                predicate.args.len(),
+                3
+            ));
+        }
+
+        let mut args = predicate.args.into_iter();
+
+        ConjoiningClauses::check_default_source(&predicate.operator, args.next().unwrap())?;
+
+        let mut unified = BTreeSet::new();
+        let entity = match args.next().unwrap() {
+            FnArg::Variable(var) => {
+                unified.insert(var.clone());
+                PatternNonValuePlace::Variable(var)
+            }
+            FnArg::EntidOrInteger(e) => PatternNonValuePlace::Entid(e),
+            FnArg::IdentOrKeyword(k) => PatternNonValuePlace::Ident(k.into()),
+            _ => bail!(AlgebrizerError::InvalidArgumentType(
+                predicate.operator.clone(),
+                ValueType::Ref.into(),
+                1
+            )),
+        };
+
+        let (attribute, _) = ConjoiningClauses::resolve_attribute_argument(
+            known.schema,
+            &predicate.operator,
+            2,
+            args.next().unwrap(),
+            false,
+        )?;
+
+        let pattern = Pattern::simple(
+            entity,
+            PatternNonValuePlace::Entid(attribute),
+            PatternValuePlace::Placeholder,
+        )
+        .expect("a forward attribute");
+
+        let not_join = NotJoin::new(
+            UnifyVars::Explicit(unified),
+            vec![WhereClause::Pattern(pattern)],
+        );
+        self.apply_not_join(known, not_join)
+    }
+}
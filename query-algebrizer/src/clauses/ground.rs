@@ -51,7 +51,7 @@ impl ConjoiningClauses {
         };
 
         let table = self.computed_tables.push_computed(named_values);
-        let alias = self.next_alias_for_table(table);
+        let alias = self.next_alias_for_table(&table);
 
         // Stitch the computed table into column_bindings, so we get cross-linking.
         for (name, ty) in names.iter().zip(types.into_iter()) {
@@ -8,14 +8,16 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use core_traits::{TypedValue, ValueType};
+use core_traits::{Entid, TypedValue, ValueType};
 
 use edn::query::Variable;
 
 use query_algebrizer_traits::errors::{AlgebrizerError, Result};
 
+use crate::types::FulltextMarkup;
+
 /// Define the inputs to a query. This is in two parts: a set of values known now, and a set of
 /// types known now.
 /// The separate map of types is to allow queries to be algebrized without full knowledge of
@@ -25,6 +27,12 @@ use query_algebrizer_traits::errors::{AlgebrizerError, Result};
 pub struct QueryInputs {
     pub(crate) types: BTreeMap<Variable, ValueType>,
     pub(crate) values: BTreeMap<Variable, TypedValue>,
+
+    /// The transactions whose datoms make up the `$delta` source.
+    pub(crate) delta: Option<BTreeSet<Entid>>,
+
+    /// The markup used by `fulltext` snippet and highlight bindings.
+    pub(crate) fulltext_markup: FulltextMarkup,
 }
 
 impl Default for QueryInputs {
@@ -32,6 +40,8 @@ impl Default for QueryInputs {
         QueryInputs {
             types: BTreeMap::default(),
             values: BTreeMap::default(),
+            delta: None,
+            fulltext_markup: FulltextMarkup::default(),
         }
     }
 }
@@ -46,6 +56,8 @@ impl QueryInputs {
         QueryInputs {
             types: types.into_iter().collect(),
             values: BTreeMap::default(),
+            delta: None,
+            fulltext_markup: FulltextMarkup::default(),
         }
     }
 
@@ -56,9 +68,38 @@ impl QueryInputs {
                 .map(|(var, val)| (var.clone(), val.value_type()))
                 .collect(),
             values,
+            delta: None,
+            fulltext_markup: FulltextMarkup::default(),
         }
     }
 
+    /// Query the datoms asserted and retracted by `txes` as `$delta`.
+    pub fn for_delta<I>(txes: I) -> QueryInputs
+    where
+        I: IntoIterator<Item = Entid>,
+    {
+        let mut inputs = QueryInputs::default();
+        inputs.set_delta(txes);
+        inputs
+    }
+
+    pub fn set_delta<I>(&mut self, txes: I)
+    where
+        I: IntoIterator<Item = Entid>,
+    {
+        self.delta = Some(txes.into_iter().collect());
+    }
+
+    pub fn has_delta(&self) -> bool {
+        self.delta.is_some()
+    }
+
+    /// Mark up the terms matched by `fulltext` snippet and highlight bindings with `markup`,
+    /// rather than with `<b>` and `</b>`.
+    pub fn set_fulltext_markup(&mut self, markup: FulltextMarkup) {
+        self.fulltext_markup = markup;
+    }
+
     pub fn new(
         mut types: BTreeMap<Variable, ValueType>,
         values: BTreeMap<Variable, TypedValue>,
@@ -73,6 +114,11 @@ impl QueryInputs {
                 }
             }
         }
-        Ok(QueryInputs { types, values })
+        Ok(QueryInputs {
+            types,
+            values,
+            delta: None,
+            fulltext_markup: FulltextMarkup::default(),
+        })
     }
 }
@@ -16,6 +16,8 @@ use std::collections::btree_map::Entry;
 
 use std::fmt::{Debug, Formatter};
 
+use std::ops::Range;
+
 use core_traits::{Attribute, Entid, KnownEntid, TypedValue, ValueType, ValueTypeSet};
 
 use mentat_core::{Cloned, HasSchema, Schema};
@@ -28,23 +30,32 @@ use query_algebrizer_traits::errors::{AlgebrizerError, Result};
 
 use crate::types::{
     Column, ColumnConstraint, ColumnIntersection, ComputedTable, DatomsColumn, DatomsTable,
-    EmptyBecause, EvolvedNonValuePlace, EvolvedPattern, EvolvedValuePlace, FulltextColumn,
-    PlaceOrEmpty, QualifiedAlias, QueryValue, SourceAlias, TableAlias,
+    EmptyBecause, EvolvedNonValuePlace, EvolvedPattern, EvolvedValuePlace, FulltextMarkup,
+    LeftJoin, PlaceOrEmpty, QualifiedAlias, QueryValue, SourceAlias, TableAlias,
 };
 
 mod convert; // Converting args to values.
+mod delta;
 mod inputs;
 mod not;
+mod optional;
 mod or;
 mod pattern;
+mod plan;
 mod predicate;
+mod provenance;
 mod resolve;
+mod sources;
 
+mod expression;
 mod fulltext;
+mod get_else;
 mod ground;
 mod tx_log_api;
 mod where_fn;
 
+use self::provenance::Provenance;
+
 use crate::validate::{validate_not_join, validate_or_join};
 
 pub use self::inputs::QueryInputs;
@@ -134,6 +145,9 @@ pub struct ConjoiningClauses {
     /// A data source used to generate an alias for a table -- e.g., from "datoms" to "datoms123".
     alias_counter: RcCounter,
 
+    /// The clauses from which our tables came, used to explain the query.
+    provenance: Provenance,
+
     /// A vector of source/alias pairs used to construct a SQL `FROM` list.
     pub from: Vec<SourceAlias>,
 
@@ -141,6 +155,14 @@ pub struct ConjoiningClauses {
     /// an identifier in a `DatomsTable::Computed(c)` table reference.
     pub computed_tables: Vec<ComputedTable>,
 
+    /// Tables joined to those in `from` with `LEFT OUTER JOIN`, in order.
+    pub left_joins: Vec<LeftJoin>,
+
+    /// The ranges of `from` that hold runs of patterns ordered by the planner. SQLite should join
+    /// the tables of a run in that order. Runs are planned independently of each other and of
+    /// the tables around them.
+    pub planned: Vec<Range<usize>>,
+
     /// A list of fragments that can be joined by `AND`.
     pub wheres: ColumnIntersection,
 
@@ -176,6 +198,9 @@ pub struct ConjoiningClauses {
 
     /// Map of variables to the set of type requirements we have for them.
     required_types: BTreeMap<Variable, ValueTypeSet>,
+
+    /// The markup with which `fulltext` snippet and highlight bindings mark up matching terms.
+    fulltext_markup: FulltextMarkup,
 }
 
 impl PartialEq for ConjoiningClauses {
@@ -183,6 +208,8 @@ impl PartialEq for ConjoiningClauses {
         self.empty_because.eq(&other.empty_because)
             && self.from.eq(&other.from)
             && self.computed_tables.eq(&other.computed_tables)
+            && self.left_joins.eq(&other.left_joins)
+            && self.planned.eq(&other.planned)
             && self.wheres.eq(&other.wheres)
             && self.column_bindings.eq(&other.column_bindings)
             && self.input_variables.eq(&other.input_variables)
@@ -190,6 +217,7 @@ impl PartialEq for ConjoiningClauses {
             && self.known_types.eq(&other.known_types)
             && self.extracted_types.eq(&other.extracted_types)
             && self.required_types.eq(&other.required_types)
+            && self.fulltext_markup.eq(&other.fulltext_markup)
     }
 }
 
@@ -201,6 +229,8 @@ impl Debug for ConjoiningClauses {
             .field("empty_because", &self.empty_because)
             .field("from", &self.from)
             .field("computed_tables", &self.computed_tables)
+            .field("left_joins", &self.left_joins)
+            .field("planned", &self.planned)
             .field("wheres", &self.wheres)
             .field("column_bindings", &self.column_bindings)
             .field("input_variables", &self.input_variables)
@@ -208,6 +238,7 @@ impl Debug for ConjoiningClauses {
             .field("known_types", &self.known_types)
             .field("extracted_types", &self.extracted_types)
             .field("required_types", &self.required_types)
+            .field("fulltext_markup", &self.fulltext_markup)
             .finish()
     }
 }
@@ -218,8 +249,11 @@ impl Default for ConjoiningClauses {
         ConjoiningClauses {
             empty_because: None,
             alias_counter: RcCounter::new(),
+            provenance: Provenance::default(),
             from: vec![],
             computed_tables: vec![],
+            left_joins: vec![],
+            planned: vec![],
             wheres: ColumnIntersection::default(),
             required_types: BTreeMap::new(),
             input_variables: BTreeSet::new(),
@@ -227,6 +261,7 @@ impl Default for ConjoiningClauses {
             value_bindings: BTreeMap::new(),
             known_types: BTreeMap::new(),
             extracted_types: BTreeMap::new(),
+            fulltext_markup: FulltextMarkup::default(),
         }
     }
 }
@@ -273,6 +308,8 @@ impl ConjoiningClauses {
             Some(QueryInputs {
                 mut types,
                 mut values,
+                fulltext_markup,
+                ..
             }) => {
                 // Discard any bindings not mentioned in our :in clause.
                 types.keep_intersected_keys(&in_variables);
@@ -282,6 +319,7 @@ impl ConjoiningClauses {
                     alias_counter,
                     input_variables: in_variables,
                     value_bindings: values,
+                    fulltext_markup,
                     ..Default::default()
                 };
 
@@ -313,6 +351,7 @@ impl ConjoiningClauses {
     fn make_receptacle(&self) -> ConjoiningClauses {
         ConjoiningClauses {
             alias_counter: self.alias_counter.clone(),
+            provenance: self.provenance.clone(),
             empty_because: self.empty_because.clone(),
             input_variables: self.input_variables.clone(),
             value_bindings: self.value_bindings.clone(),
@@ -328,12 +367,14 @@ impl ConjoiningClauses {
     fn use_as_template(&self, vars: &BTreeSet<Variable>) -> ConjoiningClauses {
         ConjoiningClauses {
             alias_counter: self.alias_counter.clone(),
+            provenance: self.provenance.clone(),
             empty_because: self.empty_because.clone(),
             input_variables: self.input_variables.intersection(vars).cloned().collect(),
             value_bindings: self.value_bindings.with_intersected_keys(&vars),
             known_types: self.known_types.with_intersected_keys(&vars),
             extracted_types: self.extracted_types.with_intersected_keys(&vars),
             required_types: self.required_types.with_intersected_keys(&vars),
+            fulltext_markup: self.fulltext_markup.clone(),
             ..Default::default()
         }
     }
@@ -427,14 +468,14 @@ impl ConjoiningClauses {
                     self.constrain_column_to_constant(table, column, bound_val);
                 }
 
-                Column::Transactions(_) => {
+                Column::Transactions(_) | Column::Expression(_) => {
                     self.constrain_column_to_constant(table, column, bound_val);
                 }
 
-                Column::Fulltext(FulltextColumn::Rowid)
-                | Column::Fulltext(FulltextColumn::Text) => {
-                    // We never expose `rowid` via queries.  We do expose `text`, but only
-                    // indirectly, by joining against `datoms`.  Therefore, these are meaningless.
+                Column::Fulltext(_) => {
+                    // We never expose `rowid` via queries.  We do expose `text` and the match
+                    // functions, but only indirectly, by joining against `datoms`.  Therefore,
+                    // these are meaningless.
                     unimplemented!()
                 }
 
@@ -885,13 +926,18 @@ impl ConjoiningClauses {
         }
     }
 
-    pub(crate) fn next_alias_for_table(&mut self, table: DatomsTable) -> TableAlias {
+    pub(crate) fn next_alias_for_table(&mut self, table: &DatomsTable) -> TableAlias {
         match table {
             DatomsTable::Computed(u) => format!("{}{:02}", table.name(), u),
             _ => format!("{}{:02}", table.name(), self.alias_counter.next()),
         }
     }
 
+    /// Expressions aren't tables, but naming them like tables keeps their columns distinct.
+    pub(crate) fn next_alias_for_expression(&mut self) -> TableAlias {
+        format!("expression{:02}", self.alias_counter.next())
+    }
+
     /// Produce a (table, alias) pair to handle the provided pattern.
     /// This is a mutating method because it mutates the aliaser function!
     /// Note that if this function decides that a pattern cannot match, it will flip
@@ -905,7 +951,10 @@ impl ConjoiningClauses {
             .map_err(|reason| {
                 self.mark_known_empty(reason);
             })
-            .map(|table: DatomsTable| SourceAlias(table, self.next_alias_for_table(table)))
+            .map(|table: DatomsTable| {
+                let alias = self.next_alias_for_table(&table);
+                SourceAlias(table, alias)
+            })
             .ok()
     }
 
@@ -1108,8 +1157,10 @@ impl ConjoiningClauses {
     fn apply_evolved_patterns(
         &mut self,
         known: Known,
-        mut patterns: VecDeque<EvolvedPattern>,
+        patterns: VecDeque<EvolvedPattern>,
     ) -> Result<()> {
+        let start = self.from.len();
+        let (mut patterns, planned) = self.plan_patterns(known, patterns);
         while let Some(pattern) = patterns.pop_front() {
             match self.evolve_pattern(known, pattern) {
                 PlaceOrEmpty::Place(re_evolved) => self.apply_pattern(known, re_evolved),
@@ -1119,6 +1170,9 @@ impl ConjoiningClauses {
                 }
             }
         }
+        if planned {
+            self.planned.push(start..self.from.len());
+        }
         Ok(())
     }
 
@@ -1165,13 +1219,16 @@ impl ConjoiningClauses {
                 continue;
             }
             match clause {
-                WhereClause::Pattern(p) => match self.make_evolved_pattern(known, p) {
-                    PlaceOrEmpty::Place(evolved) => patterns.push_back(evolved),
-                    PlaceOrEmpty::Empty(because) => {
-                        self.mark_known_empty(because);
-                        return Ok(());
+                WhereClause::Pattern(p) => {
+                    ConjoiningClauses::check_pattern_source(known, &p)?;
+                    match self.make_evolved_pattern(known, p) {
+                        PlaceOrEmpty::Place(evolved) => patterns.push_back(evolved),
+                        PlaceOrEmpty::Empty(because) => {
+                            self.mark_known_empty(because);
+                            return Ok(());
+                        }
                     }
-                },
+                }
                 _ => {
                     if !patterns.is_empty() {
                         self.apply_evolved_patterns(known, patterns)?;
@@ -1189,6 +1246,7 @@ impl ConjoiningClauses {
     pub(crate) fn apply_clause(&mut self, known: Known, where_clause: WhereClause) -> Result<()> {
         match where_clause {
             WhereClause::Pattern(p) => {
+                ConjoiningClauses::check_pattern_source(known, &p)?;
                 match self.make_evolved_pattern(known, p) {
                     PlaceOrEmpty::Place(evolved) => self.apply_pattern(known, evolved),
                     PlaceOrEmpty::Empty(because) => self.mark_known_empty(because),
@@ -1205,6 +1263,7 @@ impl ConjoiningClauses {
                 validate_not_join(&n)?;
                 self.apply_not_join(known, n)
             }
+            WhereClause::OptionalJoin(o) => self.apply_optional_join(known, o),
             WhereClause::TypeAnnotation(anno) => self.apply_type_anno(&anno),
             _ => unimplemented!(),
         }
@@ -1248,11 +1307,11 @@ mod tests {
     #[test]
     fn test_aliasing_through_template() {
         let mut starter = ConjoiningClauses::default();
-        let alias_zero = starter.next_alias_for_table(DatomsTable::Datoms);
+        let alias_zero = starter.next_alias_for_table(&DatomsTable::Datoms);
         let mut first = starter.use_as_template(&BTreeSet::new());
         let mut second = starter.use_as_template(&BTreeSet::new());
-        let alias_one = first.next_alias_for_table(DatomsTable::Datoms);
-        let alias_two = second.next_alias_for_table(DatomsTable::Datoms);
+        let alias_one = first.next_alias_for_table(&DatomsTable::Datoms);
+        let alias_two = second.next_alias_for_table(&DatomsTable::Datoms);
         assert!(alias_zero != alias_one);
         assert!(alias_one != alias_two);
     }
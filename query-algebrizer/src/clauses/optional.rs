@@ -0,0 +1,126 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use std::collections::BTreeSet;
+
+use edn::query::{ContainsVariables, OptionalJoin, Variable};
+
+use crate::clauses::ConjoiningClauses;
+
+use query_algebrizer_traits::errors::{AlgebrizerError, Result};
+
+use crate::types::{Column, DatomsTable, LeftJoin, QualifiedAlias, SourceAlias, ValueExpression};
+
+use crate::Known;
+
+/// Computed tables are identified by their index, so moving them from one CC to another means
+/// renumbering the references to them.
+fn offset_computed(from: Vec<SourceAlias>, offset: usize) -> Vec<SourceAlias> {
+    from.into_iter()
+        .map(|source| match source {
+            SourceAlias(DatomsTable::Computed(i), alias) => {
+                SourceAlias(DatomsTable::Computed(i + offset), alias)
+            }
+            source => source,
+        })
+        .collect()
+}
+
+impl ConjoiningClauses {
+    /// An `optional` clause is algebrized just like a `not`: in a template CC that shares the
+    /// bindings of those variables that earlier clauses have bound.  Rather than becoming a
+    /// `NOT EXISTS` constraint, its tables are joined to ours with `LEFT OUTER JOIN`, and the
+    /// variables it introduces are bound to their columns, which are `NULL` when it doesn't
+    /// match.
+    pub(crate) fn apply_optional_join(
+        &mut self,
+        known: Known,
+        optional: OptionalJoin,
+    ) -> Result<()> {
+        if self.is_known_empty() {
+            return Ok(());
+        }
+
+        let (unified, introduced): (BTreeSet<Variable>, BTreeSet<Variable>) = optional
+            .collect_mentioned_variables()
+            .into_iter()
+            .partition(|v| self.column_bindings.contains_key(v) || self.bound_value(v).is_some());
+
+        let mut template = self.use_as_template(&unified);
+        for v in unified.iter() {
+            if let Some(col) = self.column_bindings.get(v).and_then(|cols| cols.first()) {
+                template
+                    .column_bindings
+                    .insert(v.clone(), vec![col.clone()]);
+            }
+        }
+
+        template.apply_clauses(known, optional.clauses)?;
+        template.expand_column_bindings();
+        template.prune_extracted_types();
+        template.process_required_types()?;
+
+        if template.is_known_empty() {
+            // The clauses can never match, so nothing they introduce ever has a value.
+            for var in introduced {
+                let null = QualifiedAlias(
+                    self.next_alias_for_expression(),
+                    Column::Expression(Box::new(ValueExpression::Null)),
+                );
+                self.extracted_types.insert(var.clone(), null.clone());
+                self.column_bindings.insert(var, vec![null]);
+            }
+            return Ok(());
+        }
+
+        if template.from.is_empty() {
+            bail!(AlgebrizerError::NoPatternInOptionalClause);
+        }
+
+        for var in introduced {
+            if template.bound_value(&var).is_some() {
+                bail!(AlgebrizerError::ConstantInOptionalClause(var.name()));
+            }
+
+            if let Some(col) = template
+                .column_bindings
+                .get(&var)
+                .and_then(|cols| cols.first())
+            {
+                self.column_bindings.insert(var.clone(), vec![col.clone()]);
+            }
+            if let Some(types) = template.known_types.get(&var) {
+                self.known_types.insert(var.clone(), *types);
+            }
+            if let Some(alias) = template.extracted_types.get(&var) {
+                self.extracted_types.insert(var, alias.clone());
+            }
+        }
+
+        let offset = self.computed_tables.len();
+        self.computed_tables.append(&mut template.computed_tables);
+
+        self.left_joins.push(LeftJoin {
+            from: offset_computed(template.from, offset),
+            on: template.wheres,
+        });
+
+        // Any `optional` clauses nested within this one join against its tables, which are `NULL`
+        // whenever it doesn't match, so we can simply join them after it.
+        for nested in template.left_joins {
+            self.left_joins.push(LeftJoin {
+                from: offset_computed(nested.from, offset),
+                on: nested.on,
+            });
+        }
+
+        Ok(())
+    }
+}
@@ -14,8 +14,8 @@ use std::collections::{BTreeMap, BTreeSet};
 use core_traits::ValueTypeSet;
 
 use edn::query::{
-    OrJoin, OrWhereClause, Pattern, PatternNonValuePlace, PatternValuePlace, UnifyVars, Variable,
-    WhereClause,
+    OrJoin, OrWhereClause, Pattern, PatternNonValuePlace, PatternValuePlace, SrcVar, UnifyVars,
+    Variable, WhereClause,
 };
 
 use crate::clauses::{ConjoiningClauses, PushComputed};
@@ -23,9 +23,9 @@ use crate::clauses::{ConjoiningClauses, PushComputed};
 use query_algebrizer_traits::errors::Result;
 
 use crate::types::{
-    ColumnAlternation, ColumnConstraintOrAlternation, ColumnIntersection, ComputedTable,
-    DatomsTable, EmptyBecause, EvolvedPattern, PlaceOrEmpty, QualifiedAlias, SourceAlias,
-    VariableColumn,
+    AliasOrigin, ColumnAlternation, ColumnConstraintOrAlternation, ColumnIntersection,
+    ComputedTable, DatomsTable, EmptyBecause, EvolvedPattern, PlaceOrEmpty, QualifiedAlias,
+    SourceAlias, VariableColumn,
 };
 
 use crate::Known;
@@ -187,65 +187,71 @@ impl ConjoiningClauses {
         let mut clauses = join_clauses.into_iter();
         while let Some(clause) = clauses.next() {
             // If we fail half-way through processing, we want to reconstitute the input.
-            // Keep a handle to the clause itself here to smooth over the moved `if let` below.
+            // Keep a handle to the clause itself here to smooth over the moved `match` below.
             let last: OrWhereClause;
 
-            if let OrWhereClause::Clause(WhereClause::Pattern(p)) = clause {
-                // Compute the table for the pattern. If we can't figure one out, it means
-                // the pattern cannot succeed; we drop it.
-                // Inside an `or` it's not a failure for a pattern to be unable to match, which
-                use self::PlaceOrEmpty::*;
-                let table = match self.make_evolved_attribute(&known, p.attribute.clone()) {
-                    Place((aaa, value_type)) => {
-                        match self.make_evolved_value(&known, value_type, p.value.clone()) {
-                            Place(v) => self.table_for_places(known.schema, &aaa, &v),
-                            Empty(e) => Err(e),
+            match clause {
+                // Patterns over other sources don't use our tables, so they're never simple.
+                OrWhereClause::Clause(WhereClause::Pattern(p))
+                    if matches!(p.source, None | Some(SrcVar::DefaultSrc)) =>
+                {
+                    // Compute the table for the pattern. If we can't figure one out, it means
+                    // the pattern cannot succeed; we drop it.
+                    // Inside an `or` it's not a failure for a pattern to be unable to match, which
+                    use self::PlaceOrEmpty::*;
+                    let table = match self.make_evolved_attribute(&known, p.attribute.clone()) {
+                        Place((aaa, value_type)) => {
+                            match self.make_evolved_value(&known, value_type, p.value.clone()) {
+                                Place(v) => self.table_for_places(known.schema, &aaa, &v),
+                                Empty(e) => Err(e),
+                            }
                         }
-                    }
-                    Empty(e) => Err(e),
-                };
+                        Empty(e) => Err(e),
+                    };
 
-                match table {
-                    Err(e) => {
-                        empty_because = Some(e);
+                    match table {
+                        Err(e) => {
+                            empty_because = Some(e);
 
-                        // Do not accumulate this pattern at all. Add lightness!
-                        continue;
-                    }
-                    Ok(table) => {
-                        // Check the shape of the pattern against a previous pattern.
-                        let same_shape = if let Some(template) = patterns.get(0) {
-                            template.source == p.source &&     // or-arms all use the same source anyway.
+                            // Do not accumulate this pattern at all. Add lightness!
+                            continue;
+                        }
+                        Ok(table) => {
+                            // Check the shape of the pattern against a previous pattern.
+                            let same_shape = if let Some(template) = patterns.get(0) {
+                                template.source == p.source &&     // or-arms all use the same source anyway.
                                 _simply_matches_place(&template.entity, &p.entity) &&
                                 _simply_matches_place(&template.attribute, &p.attribute) &&
                                 _simply_matches_value_place(&template.value, &p.value) &&
                                 _simply_matches_place(&template.tx, &p.tx)
-                        } else {
-                            // No previous pattern.
-                            true
-                        };
-
-                        // All of our clauses that _do_ yield a table -- that are possible --
-                        // must use the same table in order for this to be a simple `or`!
-                        if same_shape {
-                            if expected_table == Some(table) {
-                                patterns.push(p);
-                                continue;
+                            } else {
+                                // No previous pattern.
+                                true
+                            };
+
+                            // All of our clauses that _do_ yield a table -- that are possible --
+                            // must use the same table in order for this to be a simple `or`!
+                            if same_shape {
+                                if expected_table.as_ref() == Some(&table) {
+                                    patterns.push(p);
+                                    continue;
+                                }
+                                if expected_table.is_none() {
+                                    expected_table = Some(table);
+                                    patterns.push(p);
+                                    continue;
+                                }
                             }
-                            if expected_table.is_none() {
-                                expected_table = Some(table);
-                                patterns.push(p);
-                                continue;
-                            }
-                        }
 
-                        // Otherwise, we need to keep this pattern so we can reconstitute.
-                        // We'll fall through to reconstruction.
+                            // Otherwise, we need to keep this pattern so we can reconstitute.
+                            // We'll fall through to reconstruction.
+                        }
                     }
+                    last = OrWhereClause::Clause(WhereClause::Pattern(p));
+                }
+                clause => {
+                    last = clause;
                 }
-                last = OrWhereClause::Clause(WhereClause::Pattern(p));
-            } else {
-                last = clause;
             }
 
             // If we get here, it means one of our checks above failed. Reconstruct and bail.
@@ -380,6 +386,7 @@ impl ConjoiningClauses {
         let source_alias = self
             .alias_table(known.schema, &patterns[0])
             .expect("couldn't get table");
+        self.record_origin(&source_alias, AliasOrigin::Or(patterns.clone()));
 
         // This is where we'll collect everything we eventually add to the destination CC.
         let mut folded = ConjoiningClauses::default();
@@ -677,7 +684,7 @@ impl ConjoiningClauses {
             arms: acc,
         };
         let table = self.computed_tables.push_computed(union);
-        let alias = self.next_alias_for_table(table);
+        let alias = self.next_alias_for_table(&table);
 
         // Stitch the computed table into column_bindings, so we get cross-linking.
         let schema = known.schema;
@@ -18,11 +18,12 @@ use edn::query::{
     NonIntegerConstant, Pattern, PatternNonValuePlace, PatternValuePlace, SrcVar, Variable,
 };
 
+use crate::clauses::delta::DELTA_SOURCE;
 use crate::clauses::ConjoiningClauses;
 
 use crate::types::{
-    ColumnConstraint, DatomsColumn, EmptyBecause, EvolvedNonValuePlace, EvolvedPattern,
-    EvolvedValuePlace, PlaceOrEmpty, SourceAlias,
+    AliasOrigin, ColumnConstraint, DatomsColumn, EmptyBecause, EvolvedNonValuePlace,
+    EvolvedPattern, EvolvedValuePlace, PlaceOrEmpty, SourceAlias,
 };
 
 use crate::Known;
@@ -281,6 +282,7 @@ impl ConjoiningClauses {
         val: &TypedValue,
     ) -> bool {
         if let Some(attribute) = known.schema.attribute_for_entid(attr) {
+            self.record_cached_attribute(attr);
             let unique = attribute.unique.is_some();
             if unique {
                 match known.get_entid_for_value(attr, val) {
@@ -398,6 +400,7 @@ impl ConjoiningClauses {
                             match pattern.value {
                                 EvolvedValuePlace::Variable(ref var) => {
                                     if cached_forward {
+                                        self.record_cached_attribute(attr);
                                         match known.get_value_for_entid(known.schema, attr, entity)
                                         {
                                             None => {
@@ -577,6 +580,9 @@ impl ConjoiningClauses {
             pattern.tx,
             pattern.source,
         );
+        let source = source.unwrap_or(SrcVar::DefaultSrc);
+        // Idents are resolved in the store that the pattern matches.
+        let known = known.for_source(&source);
         use self::PlaceOrEmpty::*;
         match self.make_evolved_entity(&known, e) {
             Empty(because) => Empty(because),
@@ -587,7 +593,7 @@ impl ConjoiningClauses {
                     Place(v) => match self.make_evolved_tx(&known, tx) {
                         Empty(because) => Empty(because),
                         Place(tx) => PlaceOrEmpty::Place(EvolvedPattern {
-                            source: source.unwrap_or(SrcVar::DefaultSrc),
+                            source,
                             entity: e,
                             attribute: a,
                             value: v,
@@ -662,9 +668,15 @@ impl ConjoiningClauses {
     }
 
     pub(crate) fn apply_pattern(&mut self, known: Known, pattern: EvolvedPattern) {
-        // For now we only support the default source.
-        if pattern.source != SrcVar::DefaultSrc {
-            unimplemented!();
+        // Other sources were checked by `check_source`.
+        if let SrcVar::NamedSrc(ref name) = pattern.source {
+            let name = name.clone();
+            if name == DELTA_SOURCE {
+                self.apply_delta_pattern(known, pattern);
+            } else {
+                self.apply_attached_pattern(known.for_source(&pattern.source), &name, pattern);
+            }
+            return;
         }
 
         if self.attempt_cache_lookup(known, &pattern) {
@@ -673,6 +685,7 @@ impl ConjoiningClauses {
 
         if let Some(alias) = self.alias_table(known.schema, &pattern) {
             self.apply_pattern_clause_for_alias(known, &pattern, &alias);
+            self.record_origin(&alias, AliasOrigin::Pattern(pattern));
             self.from.push(alias);
         } else {
             // We didn't determine a table, likely because there was a mismatch
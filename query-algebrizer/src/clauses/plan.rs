@@ -0,0 +1,138 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use std::collections::{BTreeSet, VecDeque};
+
+use core_traits::ValueType;
+
+use mentat_core::{HasSchema, Schema, Statistics};
+
+use edn::query::{SrcVar, Variable};
+
+use crate::clauses::ConjoiningClauses;
+
+use crate::types::{EvolvedNonValuePlace, EvolvedPattern, EvolvedValuePlace};
+
+use crate::Known;
+
+/// The number of rows we expect to find for each of `distinct` keys among `rows`.
+fn rows_per(rows: usize, distinct: usize) -> usize {
+    rows.div_ceil(distinct.max(1))
+}
+
+fn is_bound_place(place: &EvolvedNonValuePlace, bound: &BTreeSet<Variable>) -> bool {
+    match place {
+        EvolvedNonValuePlace::Placeholder => false,
+        EvolvedNonValuePlace::Variable(ref var) => bound.contains(var),
+        EvolvedNonValuePlace::Entid(_) => true,
+    }
+}
+
+fn is_bound_value_place(place: &EvolvedValuePlace, bound: &BTreeSet<Variable>) -> bool {
+    match place {
+        EvolvedValuePlace::Placeholder => false,
+        EvolvedValuePlace::Variable(ref var) => bound.contains(var),
+        _ => true,
+    }
+}
+
+/// Roughly how many datoms SQLite will visit to match `pattern`, given that the variables in
+/// `bound` have been bound by the patterns joined before it.  A bound entity is found with the
+/// `eavt` index, and a bound value with `avet` or `vaet` if the attribute has one; otherwise
+/// we scan all of the attribute's datoms.
+fn estimate_datoms(
+    schema: &Schema,
+    statistics: &Statistics,
+    bound: &BTreeSet<Variable>,
+    pattern: &EvolvedPattern,
+) -> usize {
+    // We know nothing about other stores.
+    if pattern.source != SrcVar::DefaultSrc {
+        return statistics.datoms;
+    }
+
+    let entity = is_bound_place(&pattern.entity, bound);
+    let value = is_bound_value_place(&pattern.value, bound);
+
+    match pattern.attribute {
+        EvolvedNonValuePlace::Entid(a) => {
+            // An attribute we have no statistics for had no datoms when we counted.
+            let counts = statistics.attributes.get(&a).cloned().unwrap_or_default();
+            let indexed = schema
+                .attribute_for_entid(a)
+                .map(|attribute| {
+                    attribute.index
+                        || attribute.unique.is_some()
+                        || attribute.value_type == ValueType::Ref
+                })
+                .unwrap_or(false);
+            match (entity, value) {
+                (true, true) => 1,
+                (true, false) => rows_per(counts.datoms, counts.entities),
+                (false, true) if indexed => rows_per(counts.datoms, counts.values),
+                _ => counts.datoms,
+            }
+        }
+        _ => {
+            if entity {
+                rows_per(statistics.datoms, statistics.entities)
+            } else {
+                statistics.datoms
+            }
+        }
+    }
+}
+
+impl ConjoiningClauses {
+    /// Order a run of patterns so that each is the cheapest to match given those before it, which
+    /// tends to start from the most selective pattern and follow the variables it binds.  Ties
+    /// keep the order in which the patterns were written.  Without statistics, that's the order
+    /// we keep, and we say that we didn't plan them.
+    pub(crate) fn plan_patterns(
+        &self,
+        known: Known,
+        patterns: VecDeque<EvolvedPattern>,
+    ) -> (VecDeque<EvolvedPattern>, bool) {
+        let statistics = match known.statistics {
+            Some(statistics) if patterns.len() > 1 => statistics,
+            _ => return (patterns, false),
+        };
+
+        let mut bound: BTreeSet<Variable> = self.column_bindings.keys().cloned().collect();
+        let mut remaining: Vec<EvolvedPattern> = patterns.into_iter().collect();
+        let mut planned = VecDeque::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let cheapest = remaining
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, pattern)| {
+                    estimate_datoms(known.schema, statistics, &bound, pattern)
+                })
+                .map(|(i, _)| i)
+                .unwrap();
+            let pattern = remaining.remove(cheapest);
+            if let EvolvedNonValuePlace::Variable(ref var) = pattern.entity {
+                bound.insert(var.clone());
+            }
+            if let EvolvedNonValuePlace::Variable(ref var) = pattern.attribute {
+                bound.insert(var.clone());
+            }
+            if let EvolvedValuePlace::Variable(ref var) = pattern.value {
+                bound.insert(var.clone());
+            }
+            if let EvolvedNonValuePlace::Variable(ref var) = pattern.tx {
+                bound.insert(var.clone());
+            }
+            planned.push_back(pattern);
+        }
+
+        (planned, true)
+    }
+}
@@ -8,7 +8,7 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
-use core_traits::{ValueType, ValueTypeSet};
+use core_traits::{TypedValue, ValueType, ValueTypeSet};
 
 use mentat_core::Schema;
 
@@ -20,7 +20,7 @@ use crate::clauses::convert::ValueTypes;
 
 use query_algebrizer_traits::errors::{AlgebrizerError, Result};
 
-use crate::types::{ColumnConstraint, EmptyBecause, Inequality, QueryValue};
+use crate::types::{ColumnConstraint, EmptyBecause, Inequality, QueryValue, StringPredicate};
 
 use crate::Known;
 
@@ -29,14 +29,23 @@ impl ConjoiningClauses {
     /// There are several kinds of predicates in our Datalog:
     /// - A limited set of binary comparison operators: < > <= >= !=.
     ///   These are converted into SQLite binary comparisons and some type constraints.
-    /// - In the future, some predicates that are implemented via function calls in SQLite.
-    ///
-    /// At present we have implemented only the five built-in comparison binary operators.
+    /// - String predicates: `starts-with?`, `ends-with?`, `includes?`, `re-find`, and
+    ///   `equals-ignore-case?`.  These are converted into SQLite string comparisons and functions.
+    ///   Like SQLite's `lower`, `equals-ignore-case?` only ignores the case of ASCII letters: "É"
+    ///   and "é" differ.
+    /// - `missing?`, which is converted into a `not` clause.
+    /// - In the future, user-specified predicates.
     pub(crate) fn apply_predicate(&mut self, known: Known, predicate: Predicate) -> Result<()> {
         // Because we'll be growing the set of built-in predicates, handling each differently,
         // and ultimately allowing user-specified predicates, we match on the predicate name first.
         if let Some(op) = Inequality::from_datalog_operator(predicate.operator.0.as_str()) {
             self.apply_inequality(known, op, predicate)
+        } else if let Some(op) =
+            StringPredicate::from_datalog_operator(predicate.operator.0.as_str())
+        {
+            self.apply_string_predicate(op, predicate)
+        } else if predicate.operator.0 == "missing?" {
+            self.apply_missing(known, predicate)
         } else {
             bail!(AlgebrizerError::UnknownFunction(predicate.operator.clone()))
         }
@@ -175,6 +184,61 @@ impl ConjoiningClauses {
     }
 }
 
+impl ConjoiningClauses {
+    /// Accumulate a `StringPredicate` constraint into the `wheres` list.  Both arguments must be
+    /// strings: string constants, or variables bound to strings.
+    pub(crate) fn apply_string_predicate(
+        &mut self,
+        operator: StringPredicate,
+        predicate: Predicate,
+    ) -> Result<()> {
+        if predicate.args.len() != 2 {
+            bail!(AlgebrizerError::InvalidNumberOfArguments(
+                predicate.operator.clone(),
+                predicate.args.len(),
+                2
+            ));
+        }
+
+        let mut args = predicate.args.into_iter();
+        let left = args.next().expect("two args");
+        let right = args.next().expect("two args");
+
+        let left_var = match left {
+            FnArg::Variable(ref var) => Some(var.clone()),
+            _ => None,
+        };
+
+        // Any variables that aren't bound by this point in the linear processing of clauses will
+        // cause the application of the predicate to fail.
+        let left = self.resolve_string_argument(&predicate.operator, 0, left)?;
+        let right = self.resolve_string_argument(&predicate.operator, 1, right)?;
+
+        // Every string starts with the empty string, so all that's left to check is that the
+        // value is a string, which we only need to do if its type is only known at runtime.
+        if operator == StringPredicate::StartsWith
+            && right == QueryValue::TypedValue(TypedValue::typed_string(""))
+        {
+            if let Some(type_tag) = left_var.and_then(|var| self.extracted_types.get(&var)) {
+                self.wheres.add_intersection(ColumnConstraint::HasTypes {
+                    value: type_tag.0.clone(),
+                    value_types: ValueTypeSet::of_one(ValueType::String),
+                    check_value: true,
+                });
+            }
+            return Ok(());
+        }
+
+        self.wheres
+            .add_intersection(ColumnConstraint::StringPredicate {
+                operator,
+                left,
+                right,
+            });
+        Ok(())
+    }
+}
+
 impl Inequality {
     fn to_constraint(self, left: QueryValue, right: QueryValue) -> ColumnConstraint {
         match self {
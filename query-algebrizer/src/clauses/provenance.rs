@@ -0,0 +1,59 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use core_traits::Entid;
+
+use crate::clauses::ConjoiningClauses;
+
+use crate::types::{AliasOrigin, SourceAlias};
+
+#[derive(Default)]
+struct Recorded {
+    origins: Vec<(SourceAlias, AliasOrigin)>,
+    cached_attributes: BTreeSet<Entid>,
+}
+
+/// Where the tables in a query came from, and which cached attributes we used instead of
+/// tables. Like the alias counter, this is shared by a CC and all of the CCs made from it, so
+/// it also records tables that were later discarded -- e.g., in an `or` arm that can't match.
+#[derive(Clone, Default)]
+pub(crate) struct Provenance(Rc<RefCell<Recorded>>);
+
+impl ConjoiningClauses {
+    pub(crate) fn record_origin(&self, alias: &SourceAlias, origin: AliasOrigin) {
+        self.provenance
+            .0
+            .borrow_mut()
+            .origins
+            .push((alias.clone(), origin));
+    }
+
+    pub(crate) fn record_cached_attribute(&self, attribute: Entid) {
+        self.provenance
+            .0
+            .borrow_mut()
+            .cached_attributes
+            .insert(attribute);
+    }
+
+    /// The clause for which each table alias was made, in the order in which they were made.
+    pub fn alias_origins(&self) -> Vec<(SourceAlias, AliasOrigin)> {
+        self.provenance.0.borrow().origins.clone()
+    }
+
+    /// The attributes whose cached values were used instead of querying the store.
+    pub fn cached_attributes(&self) -> BTreeSet<Entid> {
+        self.provenance.0.borrow().cached_attributes.clone()
+    }
+}
@@ -116,6 +116,173 @@ impl ConjoiningClauses {
         }
     }
 
+    /// Take a function argument and turn it into a `QueryValue` suitable for use in a concrete
+    /// constraint.
+    /// Additionally, mark the pattern as known-empty if the argument is known non-string, and
+    /// mark any variable encountered as a string.
+    pub(crate) fn resolve_string_argument(
+        &mut self,
+        function: &PlainSymbol,
+        position: usize,
+        arg: FnArg,
+    ) -> Result<QueryValue> {
+        use self::FnArg::*;
+        match arg {
+            FnArg::Variable(var) => match self.bound_value(&var) {
+                Some(TypedValue::String(v)) => Ok(QueryValue::TypedValue(TypedValue::String(v))),
+                Some(v) => bail!(AlgebrizerError::InputTypeDisagreement(
+                    var.name(),
+                    ValueType::String,
+                    v.value_type()
+                )),
+                None => {
+                    self.constrain_var_to_type(var.clone(), ValueType::String);
+                    self.column_bindings
+                        .get(&var)
+                        .and_then(|cols| cols.first().map(|col| QueryValue::Column(col.clone())))
+                        .ok_or_else(|| AlgebrizerError::UnboundVariable(var.name()))
+                }
+            },
+            Constant(NonIntegerConstant::Text(v)) => {
+                Ok(QueryValue::TypedValue(TypedValue::String(v)))
+            }
+
+            EntidOrInteger(_)
+            | IdentOrKeyword(_)
+            | SrcVar(_)
+            | Constant(NonIntegerConstant::Boolean(_))
+            | Constant(NonIntegerConstant::Float(_))
+            | Constant(NonIntegerConstant::Instant(_))
+            | Constant(NonIntegerConstant::Uuid(_))
+            | Constant(NonIntegerConstant::BigInteger(_))
+            | Vector(_) => {
+                self.mark_known_empty(EmptyBecause::NonStringArgument);
+                bail!(AlgebrizerError::InvalidArgumentType(
+                    function.clone(),
+                    ValueType::String.into(),
+                    position
+                ))
+            }
+        }
+    }
+
+    /// Just like `resolve_numeric_argument`, but for `ValueType::Long` alone.
+    pub(crate) fn resolve_long_argument(
+        &mut self,
+        function: &PlainSymbol,
+        position: usize,
+        arg: FnArg,
+    ) -> Result<QueryValue> {
+        use self::FnArg::*;
+        match arg {
+            FnArg::Variable(var) => match self.bound_value(&var) {
+                Some(TypedValue::Long(v)) => Ok(QueryValue::TypedValue(TypedValue::Long(v))),
+                Some(v) => bail!(AlgebrizerError::InputTypeDisagreement(
+                    var.name(),
+                    ValueType::Long,
+                    v.value_type()
+                )),
+                None => {
+                    self.constrain_var_to_long(var.clone());
+                    self.column_bindings
+                        .get(&var)
+                        .and_then(|cols| cols.first().map(|col| QueryValue::Column(col.clone())))
+                        .ok_or_else(|| AlgebrizerError::UnboundVariable(var.name()))
+                }
+            },
+            EntidOrInteger(i) => Ok(QueryValue::TypedValue(TypedValue::Long(i))),
+
+            IdentOrKeyword(_)
+            | SrcVar(_)
+            | Constant(NonIntegerConstant::Boolean(_))
+            | Constant(NonIntegerConstant::Float(_))
+            | Constant(NonIntegerConstant::Text(_))
+            | Constant(NonIntegerConstant::Uuid(_))
+            | Constant(NonIntegerConstant::Instant(_))
+            | Constant(NonIntegerConstant::BigInteger(_))
+            | Vector(_) => {
+                self.mark_known_empty(EmptyBecause::NonNumericArgument);
+                bail!(AlgebrizerError::InvalidArgumentType(
+                    function.clone(),
+                    ValueType::Long.into(),
+                    position
+                ))
+            }
+        }
+    }
+
+    /// Take a function argument that must have the given type -- typically the type of an
+    /// attribute -- and turn it into a `QueryValue` suitable for use in a concrete constraint.
+    pub(crate) fn resolve_typed_argument(
+        &mut self,
+        schema: &Schema,
+        function: &PlainSymbol,
+        position: usize,
+        arg: FnArg,
+        value_type: ValueType,
+    ) -> Result<QueryValue> {
+        use self::FnArg::*;
+        match value_type {
+            ValueType::Ref => return self.resolve_ref_argument(schema, function, position, arg),
+            ValueType::Long => return self.resolve_long_argument(function, position, arg),
+            ValueType::String => return self.resolve_string_argument(function, position, arg),
+            ValueType::Instant => return self.resolve_instant_argument(function, position, arg),
+            _ => {}
+        }
+
+        let constant = match arg {
+            FnArg::Variable(var) => {
+                return match self.bound_value(&var) {
+                    Some(v) => {
+                        if v.value_type() == value_type {
+                            Ok(QueryValue::TypedValue(v))
+                        } else {
+                            bail!(AlgebrizerError::InputTypeDisagreement(
+                                var.name(),
+                                value_type,
+                                v.value_type()
+                            ))
+                        }
+                    }
+                    None => {
+                        self.constrain_var_to_type(var.clone(), value_type);
+                        self.column_bindings
+                            .get(&var)
+                            .and_then(|cols| {
+                                cols.first().map(|col| QueryValue::Column(col.clone()))
+                            })
+                            .ok_or_else(|| AlgebrizerError::UnboundVariable(var.name()))
+                    }
+                };
+            }
+            Constant(NonIntegerConstant::Boolean(v)) => Some(TypedValue::Boolean(v)),
+            Constant(NonIntegerConstant::Float(f)) => Some(TypedValue::Double(f)),
+            // Doubles are stored as reals, so integer constants must become reals too.
+            EntidOrInteger(i) if value_type == ValueType::Double => {
+                Some(TypedValue::Double((i as f64).into()))
+            }
+            Constant(NonIntegerConstant::Uuid(u)) => Some(TypedValue::Uuid(u)),
+            IdentOrKeyword(k) => Some(TypedValue::Keyword(k.into())),
+            EntidOrInteger(_)
+            | SrcVar(_)
+            | Constant(NonIntegerConstant::Text(_))
+            | Constant(NonIntegerConstant::Instant(_))
+            | Constant(NonIntegerConstant::BigInteger(_))
+            | Vector(_) => None,
+        };
+
+        match constant {
+            Some(constant) if constant.value_type() == value_type => {
+                Ok(QueryValue::TypedValue(constant))
+            }
+            _ => bail!(AlgebrizerError::InvalidArgumentType(
+                function.clone(),
+                value_type.into(),
+                position
+            )),
+        }
+    }
+
     /// Take a function argument and turn it into a `QueryValue` suitable for use in a concrete
     /// constraint.
     pub(crate) fn resolve_ref_argument(
@@ -0,0 +1,73 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use edn::query::{Pattern, SrcVar};
+
+use crate::clauses::delta::DELTA_SOURCE;
+use crate::clauses::ConjoiningClauses;
+
+use query_algebrizer_traits::errors::{AlgebrizerError, Result};
+
+use crate::types::{AliasOrigin, DatomsTable, EvolvedPattern, SourceAlias};
+
+use crate::Known;
+
+/// Sources other than the default: `$delta`, and the stores attached to ours.
+impl ConjoiningClauses {
+    /// Sources other than the default must be given to the query.
+    pub(crate) fn check_source(known: Known, source: &SrcVar) -> Result<()> {
+        match source {
+            SrcVar::DefaultSrc => Ok(()),
+            SrcVar::NamedSrc(ref name) if name == DELTA_SOURCE && known.delta.is_some() => Ok(()),
+            SrcVar::NamedSrc(ref name)
+                if known
+                    .attached
+                    .map(|attached| attached.contains_key(name))
+                    .unwrap_or(false) =>
+            {
+                Ok(())
+            }
+            SrcVar::NamedSrc(ref name) => bail!(AlgebrizerError::UnknownSource(name.clone())),
+        }
+    }
+
+    pub(crate) fn check_pattern_source(known: Known, pattern: &Pattern) -> Result<()> {
+        match pattern.source {
+            Some(ref source) => ConjoiningClauses::check_source(known, source),
+            None => Ok(()),
+        }
+    }
+
+    /// A pattern over an attached store, `[$other ?e :foo/bar ?v]`, is just like one over our own
+    /// store, but its idents were resolved with the attached store's schema (see
+    /// `Known::for_source`) and it matches the attached store's tables.  Entids mean different
+    /// things in different stores, so it's usually values, not entities, that are joined across
+    /// them.
+    pub(crate) fn apply_attached_pattern(
+        &mut self,
+        known: Known,
+        database: &str,
+        pattern: EvolvedPattern,
+    ) {
+        let table = match self.table_for_places(known.schema, &pattern.attribute, &pattern.value) {
+            Ok(table) => DatomsTable::Attached(database.to_string(), Box::new(table)),
+            Err(reason) => {
+                self.mark_known_empty(reason);
+                return;
+            }
+        };
+
+        let alias = self.next_alias_for_table(&table);
+        let alias = SourceAlias(table, alias);
+        self.apply_pattern_clause_for_alias(known, &pattern, &alias);
+        self.record_origin(&alias, AliasOrigin::Pattern(pattern));
+        self.from.push(alias);
+    }
+}
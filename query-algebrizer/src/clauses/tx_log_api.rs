@@ -12,13 +12,14 @@ use core_traits::ValueType;
 
 use edn::query::{Binding, FnArg, SrcVar, VariableOrPlaceholder, WhereFn};
 
+use crate::clauses::delta::DELTA_SOURCE;
 use crate::clauses::ConjoiningClauses;
 
 use query_algebrizer_traits::errors::{AlgebrizerError, BindingError, Result};
 
 use crate::types::{
-    Column, ColumnConstraint, DatomsTable, Inequality, QualifiedAlias, QueryValue, SourceAlias,
-    TransactionsColumn,
+    AliasOrigin, Column, ColumnConstraint, DatomsTable, Inequality, QualifiedAlias, QueryValue,
+    SourceAlias, TransactionsColumn,
 };
 
 use crate::Known;
@@ -107,7 +108,7 @@ impl ConjoiningClauses {
         let tx2 =
             self.resolve_tx_argument(&known.schema, &where_fn.operator, 2, args.next().unwrap())?;
 
-        let transactions = self.next_alias_for_table(DatomsTable::Transactions);
+        let transactions = self.next_alias_for_table(&DatomsTable::Transactions);
 
         self.from
             .push(SourceAlias(DatomsTable::Transactions, transactions.clone()));
@@ -148,8 +149,16 @@ impl ConjoiningClauses {
         Ok(())
     }
 
+    /// `[(tx-data $ ?tx) [[?e ?a ?v ?tx ?added]]]` binds the datoms asserted and retracted by a
+    /// transaction.  Over `$delta` the transaction is optional, and defaults to all of them.
+    /// Over an attached store, it's one of that store's transactions.
     pub(crate) fn apply_tx_data(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
-        if where_fn.args.len() != 2 {
+        let delta = matches!(
+            where_fn.args.first(),
+            Some(FnArg::SrcVar(SrcVar::NamedSrc(ref name))) if name == DELTA_SOURCE
+        );
+
+        if where_fn.args.len() != 2 && !(delta && where_fn.args.len() == 1) {
             bail!(AlgebrizerError::InvalidNumberOfArguments(
                 where_fn.operator.clone(),
                 where_fn.args.len(),
@@ -214,32 +223,42 @@ impl ConjoiningClauses {
 
         let mut args = where_fn.args.into_iter();
 
-        // TODO: process source variables.
-        match args.next().unwrap() {
-            FnArg::SrcVar(SrcVar::DefaultSrc) => {}
+        let source = match args.next().unwrap() {
+            FnArg::SrcVar(source) => source,
             _ => bail!(AlgebrizerError::InvalidArgument(
                 where_fn.operator.clone(),
                 "source variable",
                 0
             )),
-        }
+        };
+        ConjoiningClauses::check_source(known, &source)?;
+        let known = known.for_source(&source);
 
-        let tx =
-            self.resolve_tx_argument(&known.schema, &where_fn.operator, 1, args.next().unwrap())?;
+        let table = match source {
+            SrcVar::NamedSrc(name) if !delta => {
+                DatomsTable::Attached(name, Box::new(DatomsTable::Transactions))
+            }
+            _ => DatomsTable::Transactions,
+        };
 
-        let transactions = self.next_alias_for_table(DatomsTable::Transactions);
+        let transactions = self.next_alias_for_table(&table);
 
-        self.from
-            .push(SourceAlias(DatomsTable::Transactions, transactions.clone()));
+        let alias = SourceAlias(table, transactions.clone());
+        self.record_origin(&alias, AliasOrigin::Function(where_fn.operator.clone()));
+        self.from.push(alias);
 
-        let tx_constraint = ColumnConstraint::Equals(
-            QualifiedAlias(
-                transactions.clone(),
-                Column::Transactions(TransactionsColumn::Tx),
-            ),
-            tx,
+        let tx_column = QualifiedAlias(
+            transactions.clone(),
+            Column::Transactions(TransactionsColumn::Tx),
         );
-        self.wheres.add_intersection(tx_constraint);
+        if delta {
+            self.constrain_to_delta(known, tx_column.clone());
+        }
+        if let Some(tx) = args.next() {
+            let tx = self.resolve_tx_argument(&known.schema, &where_fn.operator, 1, tx)?;
+            self.wheres
+                .add_intersection(ColumnConstraint::Equals(tx_column, tx));
+        }
 
         if let VariableOrPlaceholder::Variable(ref var) = b_e {
             // It must be a ref.
@@ -14,14 +14,17 @@ use crate::clauses::ConjoiningClauses;
 
 use query_algebrizer_traits::errors::{AlgebrizerError, Result};
 
+use crate::types::ArithmeticOperator;
+
 use crate::Known;
 
 /// Application of `where` functions.
 impl ConjoiningClauses {
     /// There are several kinds of functions binding variables in our Datalog:
-    /// - A set of functions like `ground`, fulltext` and `get-else` that are translated into SQL
+    /// - A set of functions like `ground`, `fulltext` and `get-some` that are translated into SQL
     ///   `VALUES`, `MATCH`, or `JOIN`, yielding bindings.
-    /// - In the future, some functions that are implemented via function calls in SQLite.
+    /// - Expression functions like `+`, `str` and `get-else` that are evaluated by SQLite and
+    ///   bind a single variable.
     ///
     /// At present we have implemented only a limited selection of functions.
     pub(crate) fn apply_where_fn(&mut self, known: Known, where_fn: WhereFn) -> Result<()> {
@@ -32,7 +35,15 @@ impl ConjoiningClauses {
             "ground" => self.apply_ground(known, where_fn),
             "tx-data" => self.apply_tx_data(known, where_fn),
             "tx-ids" => self.apply_tx_ids(known, where_fn),
-            _ => bail!(AlgebrizerError::UnknownFunction(where_fn.operator.clone())),
+            "get-else" => self.apply_get_else(known, where_fn),
+            "get-some" => self.apply_get_some(known, where_fn),
+            "str" => self.apply_str(known, where_fn),
+            "subs" => self.apply_subs(known, where_fn),
+            "count" => self.apply_count(known, where_fn),
+            _ => match ArithmeticOperator::from_datalog_operator(where_fn.operator.0.as_str()) {
+                Some(op) => self.apply_arithmetic(known, op, where_fn),
+                None => bail!(AlgebrizerError::UnknownFunction(where_fn.operator.clone())),
+            },
         }
     }
 }
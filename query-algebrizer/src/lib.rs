@@ -16,7 +16,7 @@ extern crate mentat_core;
 extern crate core_traits;
 extern crate query_algebrizer_traits;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::Sub;
 use std::rc::Rc;
 
@@ -26,7 +26,7 @@ mod validate;
 
 use core_traits::{Entid, TypedValue, ValueType};
 
-use mentat_core::{parse_query, CachedAttributes, Schema};
+use mentat_core::{parse_query, CachedAttributes, Schema, Statistics};
 
 use mentat_core::counter::RcCounter;
 
@@ -38,7 +38,12 @@ pub use crate::clauses::{QueryInputs, VariableBindings};
 
 pub use crate::types::{EmptyBecause, FindQuery};
 
-/// A convenience wrapper around things known in memory: the schema and caches.
+/// The schemas of the stores attached to ours, by the name of the source that refers to them.
+pub type AttachedSchemas = BTreeMap<String, Schema>;
+
+/// A convenience wrapper around things known in memory: the schema, caches, the
+/// transactions that make up the `$delta` source, if any, the schemas of attached stores, and
+/// statistics to plan with.
 /// We use a trait object here to avoid making dozens of functions generic over the type
 /// of the cache. If performance becomes a concern, we should hard-code specific kinds of
 /// cache right here, and/or eliminate the Option.
@@ -46,6 +51,9 @@ pub use crate::types::{EmptyBecause, FindQuery};
 pub struct Known<'s, 'c> {
     pub schema: &'s Schema,
     pub cache: Option<&'c dyn CachedAttributes>,
+    pub delta: Option<&'c BTreeSet<Entid>>,
+    pub attached: Option<&'s AttachedSchemas>,
+    pub statistics: Option<&'c Statistics>,
 }
 
 impl<'s, 'c> Known<'s, 'c> {
@@ -53,6 +61,9 @@ impl<'s, 'c> Known<'s, 'c> {
         Known {
             schema: s,
             cache: None,
+            delta: None,
+            attached: None,
+            statistics: None,
         }
     }
 
@@ -60,6 +71,43 @@ impl<'s, 'c> Known<'s, 'c> {
         Known {
             schema: s,
             cache: c,
+            delta: None,
+            attached: None,
+            statistics: None,
+        }
+    }
+
+    pub fn with_attached(self, attached: &'s AttachedSchemas) -> Known<'s, 'c> {
+        Known {
+            attached: Some(attached),
+            ..self
+        }
+    }
+
+    /// Without statistics, patterns are matched in the order they're written.
+    pub fn with_statistics(self, statistics: &'c Statistics) -> Known<'s, 'c> {
+        Known {
+            statistics: Some(statistics),
+            ..self
+        }
+    }
+
+    /// What's known about the store that `source` refers to.  Attached stores are known only
+    /// by their schemas.
+    pub(crate) fn for_source(self, source: &SrcVar) -> Known<'s, 'c> {
+        let schema = match source {
+            SrcVar::NamedSrc(ref name) => self.attached.and_then(|attached| attached.get(name)),
+            SrcVar::DefaultSrc => None,
+        };
+        match schema {
+            Some(schema) => Known {
+                schema,
+                cache: None,
+                delta: None,
+                attached: None,
+                statistics: None,
+            },
+            None => self,
         }
     }
 }
@@ -302,8 +350,14 @@ pub fn algebrize_with_inputs(
     known: Known,
     parsed: FindQuery,
     counter: usize,
-    inputs: QueryInputs,
+    mut inputs: QueryInputs,
 ) -> Result<AlgebraicQuery> {
+    let delta = inputs.delta.take();
+    let known = Known {
+        delta: delta.as_ref().or(known.delta),
+        ..known
+    };
+
     let alias_counter = RcCounter::with_initial(counter);
     let mut cc =
         ConjoiningClauses::with_inputs_and_alias_counter(parsed.in_vars, inputs, alias_counter);
@@ -350,9 +404,11 @@ pub fn algebrize_with_inputs(
 pub use crate::clauses::ConjoiningClauses;
 
 pub use crate::types::{
-    Column, ColumnAlternation, ColumnConstraint, ColumnConstraintOrAlternation, ColumnIntersection,
-    ColumnName, ComputedTable, DatomsColumn, DatomsTable, FulltextColumn, OrderBy, QualifiedAlias,
-    QueryValue, SourceAlias, TableAlias, VariableColumn,
+    AliasOrigin, ArithmeticOperator, Column, ColumnAlternation, ColumnConstraint,
+    ColumnConstraintOrAlternation, ColumnIntersection, ColumnName, ComputedTable, DatomsColumn,
+    DatomsTable, EvolvedNonValuePlace, EvolvedPattern, EvolvedValuePlace, FulltextColumn,
+    FulltextMarkup, LeftJoin, OrderBy, QualifiedAlias, QueryValue, SourceAlias, StringPredicate,
+    TableAlias, ValueExpression, VariableColumn,
 };
 
 impl FindQuery {
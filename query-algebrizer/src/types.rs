@@ -11,22 +11,29 @@
 use std::collections::BTreeSet;
 use std::fmt::{Debug, Formatter};
 
+use core_traits::attribute::FulltextIndex;
 use core_traits::{Entid, TypedValue, ValueType, ValueTypeSet};
 
 use mentat_core::ValueRc;
 
-use edn::query::{Direction, FindSpec, Keyword, Limit, Order, SrcVar, Variable, WhereClause};
+use edn::query::{
+    Direction, FindSpec, Keyword, Limit, Order, PlainSymbol, SrcVar, Variable, WhereClause,
+};
 
 /// This enum models the fixed set of default tables we have -- two
-/// tables and two views -- and computed tables defined in the enclosing CC.
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+/// tables and two views -- computed tables defined in the enclosing CC, and
+/// those same tables in attached stores.
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub enum DatomsTable {
-    Datoms,          // The non-fulltext datoms table.
-    FulltextValues,  // The virtual table mapping IDs to strings.
-    FulltextDatoms,  // The fulltext-datoms view.
-    AllDatoms,       // Fulltext and non-fulltext datoms.
-    Computed(usize), // A computed table, tracked elsewhere in the query.
-    Transactions,    // The transactions table, which makes the tx-data log API efficient.
+    Datoms,                       // The non-fulltext datoms table.
+    FulltextValues,               // The table mapping IDs to strings.
+    FulltextIndex(FulltextIndex), // An FTS5 index over the fulltext values table.
+    FulltextDatoms,               // The fulltext-datoms view.
+    AllDatoms,                    // Fulltext and non-fulltext datoms.
+    Computed(usize),              // A computed table, tracked elsewhere in the query.
+    Transactions, // The transactions table, which makes the tx-data log API efficient.
+    // A table of the store attached with the given name.
+    Attached(String, Box<DatomsTable>),
 }
 
 /// A source of rows that isn't a named table -- typically a subquery or union.
@@ -44,15 +51,34 @@ pub enum ComputedTable {
     },
 }
 
+/// Tables joined to the rest of a query with `LEFT OUTER JOIN ... ON` the given constraints, as
+/// produced by an `optional` clause.  Rows of the query that don't match get `NULL` for every
+/// column of these tables.
+#[derive(PartialEq, Eq, Debug)]
+pub struct LeftJoin {
+    pub from: Vec<SourceAlias>,
+    pub on: ColumnIntersection,
+}
+
 impl DatomsTable {
     pub fn name(&self) -> &'static str {
         match *self {
             DatomsTable::Datoms => "datoms",
             DatomsTable::FulltextValues => "fulltext_values",
+            DatomsTable::FulltextIndex(ref index) => index.table_name(),
             DatomsTable::FulltextDatoms => "fulltext_datoms",
             DatomsTable::AllDatoms => "all_datoms",
             DatomsTable::Computed(_) => "c",
             DatomsTable::Transactions => "transactions",
+            DatomsTable::Attached(_, ref table) => table.name(),
+        }
+    }
+
+    /// The name of the attached store that this table belongs to, if it isn't ours.
+    pub fn database(&self) -> Option<&str> {
+        match *self {
+            DatomsTable::Attached(ref database, _) => Some(database.as_str()),
+            _ => None,
         }
     }
 }
@@ -71,11 +97,37 @@ pub enum DatomsColumn {
     ValueTypeTag,
 }
 
-/// One of the named columns of our fulltext values table.
+/// One of the named columns of our fulltext index tables, or one of the FTS5 auxiliary functions
+/// evaluated against the current match.
 #[derive(PartialEq, Eq, Clone)]
 pub enum FulltextColumn {
     Rowid,
     Text,
+    /// The BM25 relevance of the match; higher is better.
+    Score,
+    /// A short fragment of the matched text, with the matching terms marked up.
+    Snippet(FulltextIndex, FulltextMarkup),
+    /// The matched text, with the matching terms marked up.
+    Highlight(FulltextIndex, FulltextMarkup),
+}
+
+/// The text that `snippet` and `highlight` bindings put around each matching term, and that a
+/// snippet puts where it leaves text out.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FulltextMarkup {
+    pub open: String,
+    pub close: String,
+    pub ellipsis: String,
+}
+
+impl Default for FulltextMarkup {
+    fn default() -> FulltextMarkup {
+        FulltextMarkup {
+            open: "<b>".to_string(),
+            close: "</b>".to_string(),
+            ellipsis: "…".to_string(),
+        }
+    }
 }
 
 /// One of the named columns of our transactions table.
@@ -101,6 +153,8 @@ pub enum Column {
     Fulltext(FulltextColumn),
     Variable(VariableColumn),
     Transactions(TransactionsColumn),
+    /// A value computed from other columns and values, rather than read from a table.
+    Expression(Box<ValueExpression>),
 }
 
 impl From<DatomsColumn> for Column {
@@ -182,6 +236,7 @@ impl Debug for Column {
             Column::Fulltext(ref c) => c.fmt(f),
             Column::Variable(ref v) => v.fmt(f),
             Column::Transactions(ref t) => t.fmt(f),
+            Column::Expression(ref e) => e.fmt(f),
         }
     }
 }
@@ -192,6 +247,9 @@ impl FulltextColumn {
         match *self {
             Rowid => "rowid",
             Text => "text",
+            Score => "rank",
+            Snippet(..) => "snippet",
+            Highlight(..) => "highlight",
         }
     }
 }
@@ -276,6 +334,7 @@ impl QualifiedAlias {
             Column::Fulltext(_) => None,
             Column::Variable(_) => None,
             Column::Transactions(ref c) => c.associated_type_tag_column().map(Column::Transactions),
+            Column::Expression(_) => None,
         }
         .map(|d| QualifiedAlias(self.0.clone(), d))
     }
@@ -407,6 +466,158 @@ impl Debug for Inequality {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq)]
+/// The string predicates that we support.  These apply to strings only.
+pub enum StringPredicate {
+    StartsWith,
+    EndsWith,
+    Includes,
+    /// Does the string contain a match for the regular expression?
+    ReFind,
+    /// Equality ignoring the case of ASCII letters, but not of any others.
+    EqualsIgnoreCase,
+}
+
+impl StringPredicate {
+    pub fn from_datalog_operator(s: &str) -> Option<StringPredicate> {
+        match s {
+            "starts-with?" => Some(StringPredicate::StartsWith),
+            "ends-with?" => Some(StringPredicate::EndsWith),
+            "includes?" => Some(StringPredicate::Includes),
+            "re-find" => Some(StringPredicate::ReFind),
+            "equals-ignore-case?" => Some(StringPredicate::EqualsIgnoreCase),
+            _ => None,
+        }
+    }
+}
+
+impl Debug for StringPredicate {
+    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+        use self::StringPredicate::*;
+        f.write_str(match self {
+            StartsWith => "starts-with?",
+            EndsWith => "ends-with?",
+            Includes => "includes?",
+            ReFind => "re-find",
+            EqualsIgnoreCase => "equals-ignore-case?",
+        })
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+/// The arithmetic functions that we support in expression bindings.  These apply to numbers only.
+pub enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    /// Division that always produces a double.
+    Divide,
+    /// Integer division, truncating towards zero.
+    Quotient,
+    /// The integer modulus, which takes the sign of the divisor.
+    Modulo,
+}
+
+impl ArithmeticOperator {
+    pub fn from_datalog_operator(s: &str) -> Option<ArithmeticOperator> {
+        match s {
+            "+" => Some(ArithmeticOperator::Add),
+            "-" => Some(ArithmeticOperator::Subtract),
+            "*" => Some(ArithmeticOperator::Multiply),
+            "/" => Some(ArithmeticOperator::Divide),
+            "quot" => Some(ArithmeticOperator::Quotient),
+            "mod" => Some(ArithmeticOperator::Modulo),
+            _ => None,
+        }
+    }
+
+    pub fn to_sql_operator(self) -> &'static str {
+        use self::ArithmeticOperator::*;
+        match self {
+            Add => "+",
+            Subtract => "-",
+            Multiply => "*",
+            Divide => "/",
+            Quotient => "/",
+            Modulo => "%",
+        }
+    }
+
+    /// The function that applies this operator to longs, failing where SQLite's own operator would
+    /// overflow into a double.  These are registered on each connection.
+    pub fn to_checked_sql_function(self) -> Option<&'static str> {
+        use self::ArithmeticOperator::*;
+        match self {
+            Add => Some("checked_add"),
+            Subtract => Some("checked_sub"),
+            Multiply => Some("checked_mul"),
+            Divide | Quotient | Modulo => None,
+        }
+    }
+
+    /// Whether every argument after the first is a divisor, and so must not be zero.
+    pub fn divides(self) -> bool {
+        use self::ArithmeticOperator::*;
+        match self {
+            Add | Subtract | Multiply => false,
+            Divide | Quotient | Modulo => true,
+        }
+    }
+}
+
+impl Debug for ArithmeticOperator {
+    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+        use self::ArithmeticOperator::*;
+        f.write_str(match self {
+            Add => "+",
+            Subtract => "-",
+            Multiply => "*",
+            Divide => "/",
+            Quotient => "quot",
+            Modulo => "mod",
+        })
+    }
+}
+
+/// A value computed by SQLite from other values, as bound by a function like `[(+ ?x 1) ?y]`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum ValueExpression {
+    /// Apply `operator` to the arguments from left to right.  The result is a double, computed in
+    /// floating point, unless `result_type` is `Long`.
+    Arithmetic {
+        operator: ArithmeticOperator,
+        args: Vec<QueryValue>,
+        result_type: ValueType,
+    },
+    /// The arguments, as strings, joined together.
+    Concatenate(Vec<QueryValue>),
+    /// The characters of `string` from `start` up to `end`, counting from zero.
+    Substring {
+        string: QueryValue,
+        start: QueryValue,
+        end: Option<QueryValue>,
+    },
+    /// The number of characters in a string.
+    Length(QueryValue),
+    /// The value of the cardinality-one `attribute` of `entity`, looked up in `source`, or
+    /// `default` if there isn't one.  Both have the attribute's `value_type`.
+    AttributeValue {
+        source: SourceAlias,
+        entity: QueryValue,
+        attribute: Entid,
+        default: QueryValue,
+        value_type: ValueType,
+    },
+    /// The first of `attributes` that `entity` has a value for, looked up in `source`.
+    FirstAttribute {
+        source: SourceAlias,
+        entity: QueryValue,
+        attributes: Vec<Entid>,
+    },
+    /// No value at all, for a variable bound by an `optional` clause that can never match.
+    Null,
+}
+
 #[derive(PartialEq, Eq)]
 pub enum ColumnConstraint {
     Equals(QualifiedAlias, QueryValue),
@@ -422,6 +633,11 @@ pub enum ColumnConstraint {
     },
     NotExists(ComputedTable),
     Matches(QualifiedAlias, QueryValue),
+    StringPredicate {
+        operator: StringPredicate,
+        left: QueryValue,
+        right: QueryValue,
+    },
 }
 
 impl ColumnConstraint {
@@ -544,6 +760,12 @@ impl Debug for ColumnConstraint {
 
             Matches(ref qa, ref thing) => write!(f, "{:?} MATCHES {:?}", qa, thing),
 
+            StringPredicate {
+                operator,
+                ref left,
+                ref right,
+            } => write!(f, "{:?} {:?} {:?}", left, operator, right),
+
             HasTypes {
                 ref value,
                 ref value_types,
@@ -611,13 +833,16 @@ pub enum EmptyBecause {
     NonInstantArgument,
     NonNumericArgument,
     NonEntityArgument,
+    NonStringArgument,
     NonStringFulltextValue,
     NonFulltextAttribute(Entid),
+    NoFulltextAttributes,
     UnresolvedIdent(Keyword),
     InvalidAttributeIdent(Keyword),
     InvalidAttributeEntid(Entid),
     InvalidBinding(Column, TypedValue),
     ValueTypeMismatch(ValueType, TypedValue),
+    EmptyDelta,
     AttributeLookupFailed, // Catch-all, because the table lookup code is lazy. TODO
 }
 
@@ -663,12 +888,14 @@ impl Debug for EmptyBecause {
             NonAttributeArgument => write!(f, "Non-attribute argument in attribute place"),
             NonInstantArgument => write!(f, "Non-instant argument in instant place"),
             NonEntityArgument => write!(f, "Non-entity argument in entity place"),
+            NonStringArgument => write!(f, "Non-string argument in string place"),
             NonNumericArgument => write!(f, "Non-numeric argument in numeric place"),
             NonStringFulltextValue => write!(f, "Non-string argument for fulltext attribute"),
             UnresolvedIdent(ref kw) => write!(f, "Couldn't resolve keyword {}", kw),
             InvalidAttributeIdent(ref kw) => write!(f, "{} does not name an attribute", kw),
             InvalidAttributeEntid(entid) => write!(f, "{} is not an attribute", entid),
             NonFulltextAttribute(entid) => write!(f, "{} is not a fulltext attribute", entid),
+            NoFulltextAttributes => write!(f, "no fulltext attributes to search"),
             InvalidBinding(ref column, ref tv) => {
                 write!(f, "{:?} cannot name column {:?}", tv, column)
             }
@@ -677,6 +904,7 @@ impl Debug for EmptyBecause {
                 "Type mismatch: {:?} doesn't match attribute type {:?}",
                 typed_value, value_type
             ),
+            EmptyDelta => write!(f, "$delta has no transactions"),
             AttributeLookupFailed => write!(f, "Attribute lookup failed"),
         }
     }
@@ -701,7 +929,7 @@ pub struct FindQuery {
 
 // Intermediate data structures for resolving patterns.
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum EvolvedNonValuePlace {
     Placeholder,
     Variable(Variable),
@@ -709,7 +937,7 @@ pub enum EvolvedNonValuePlace {
 }
 
 // TODO: some of these aren't necessary?
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum EvolvedValuePlace {
     Placeholder,
     Variable(Variable),
@@ -733,6 +961,7 @@ impl<T> PlaceOrEmpty<T> {
     }
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct EvolvedPattern {
     pub source: SrcVar,
     pub entity: EvolvedNonValuePlace,
@@ -740,3 +969,17 @@ pub struct EvolvedPattern {
     pub value: EvolvedValuePlace,
     pub tx: EvolvedNonValuePlace,
 }
+
+/// The clause for which we matched a table, recorded so that we can explain a query's SQL in
+/// terms of the query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AliasOrigin {
+    /// A pattern, like `[?e :foo/bar ?v]`.
+    Pattern(EvolvedPattern),
+
+    /// A simple `or`, all of whose patterns match the same table.
+    Or(Vec<EvolvedPattern>),
+
+    /// A function, like `fulltext` or `tx-data`, that matches datoms.
+    Function(PlainSymbol),
+}
@@ -0,0 +1,78 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Query results as JSON and as Transit-JSON. Values are encoded as `core_traits::json`
+//! describes.
+//!
+//! Results are shaped by the find spec: a value or `null` for `[:find ?x . …]`, an array for
+//! `[:find [?x ...] …]`, an array or `null` for `[:find [?x ?y] …]`, and an array of arrays for
+//! `[:find ?x ?y …]`. `QueryOutput` adds the columns, as written in the query:
+//!
+//! ```json
+//! {"columns": ["?name", "?age"], "results": [["Alice", 30], ["Bob", 40]]}
+//! ```
+
+use serde_json::Value;
+
+use core_traits::json::transit_map;
+use core_traits::Binding;
+
+use crate::{QueryOutput, QueryResults};
+
+fn shape<F>(results: &QueryResults, encode: F) -> Value
+where
+    F: Fn(&Binding) -> Value,
+{
+    let row = |bindings: &[Binding]| Value::Array(bindings.iter().map(&encode).collect());
+    match results {
+        QueryResults::Scalar(Some(binding)) => encode(binding),
+        QueryResults::Scalar(None) | QueryResults::Tuple(None) => Value::Null,
+        QueryResults::Tuple(Some(bindings)) | QueryResults::Coll(bindings) => row(bindings),
+        QueryResults::Rel(rel) => Value::Array(rel.rows().map(row).collect()),
+    }
+}
+
+impl QueryResults {
+    pub fn to_json(&self) -> Value {
+        shape(self, Binding::to_json)
+    }
+
+    /// These results in Transit-JSON, to be nested in a document.
+    pub fn to_transit(&self) -> Value {
+        shape(self, Binding::to_transit)
+    }
+}
+
+impl QueryOutput {
+    fn columns(&self) -> Value {
+        Value::Array(
+            self.spec
+                .columns()
+                .map(|column| Value::String(column.to_string()))
+                .collect(),
+        )
+    }
+
+    /// `{"columns": […], "results": …}`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "columns": self.columns(),
+            "results": self.results.to_json(),
+        })
+    }
+
+    /// `["^ ", "~:columns", […], "~:results", …]`: a Transit map, keyed by keywords.
+    pub fn to_transit(&self) -> Value {
+        transit_map(vec![
+            ("columns", self.columns()),
+            ("results", self.results.to_transit()),
+        ])
+    }
+}
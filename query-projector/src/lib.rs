@@ -12,6 +12,8 @@ extern crate failure;
 
 extern crate indexmap;
 extern crate rusqlite;
+#[macro_use]
+extern crate serde_json;
 
 extern crate db_traits;
 extern crate edn;
@@ -51,6 +53,7 @@ pub mod translate;
 
 mod binding_tuple;
 pub use crate::binding_tuple::BindingTuple;
+mod json;
 mod project;
 mod projectors;
 mod pull;
@@ -315,6 +318,17 @@ impl QueryResults {
             QueryResults::Rel(r) => Ok(r),
         }
     }
+
+    /// These results as rows, just as `Projector::project_rows` would produce them: a scalar, or
+    /// each member of a collection, is a row of one binding.
+    pub fn into_rows(self) -> Vec<Vec<Binding>> {
+        match self {
+            QueryResults::Scalar(o) => o.into_iter().map(|b| vec![b]).collect(),
+            QueryResults::Coll(c) => c.into_iter().map(|b| vec![b]).collect(),
+            QueryResults::Tuple(t) => t.into_iter().collect(),
+            QueryResults::Rel(r) => r.into_iter().collect(),
+        }
+    }
 }
 
 type Index = usize; // See rusqlite::RowIndex.
@@ -338,18 +352,27 @@ impl TypedIndex {
     ///
     /// This function will return a runtime error if the type tag is unknown, or the value is
     /// otherwise not convertible by the DB layer.
+    ///
+    /// A `NULL` value, as produced for a variable bound by an `optional` clause that didn't
+    /// match, is `Binding::Nil`.
     fn lookup<'a>(&self, row: &Row<'a>) -> Result<Binding> {
         use crate::TypedIndex::*;
 
         match *self {
             Known(value_index, value_type) => {
                 let v: rusqlite::types::Value = row.get(value_index).unwrap();
+                if v == rusqlite::types::Value::Null {
+                    return Ok(Binding::Nil);
+                }
                 TypedValue::from_sql_value_pair(v, value_type)
                     .map(|v| v.into())
                     .map_err(|e| e.into())
             }
             Unknown(value_index, type_index) => {
                 let v: rusqlite::types::Value = row.get(value_index).unwrap();
+                if v == rusqlite::types::Value::Null {
+                    return Ok(Binding::Nil);
+                }
                 let value_type_tag: i32 = row.get(type_index).unwrap();
                 TypedValue::from_sql_value_pair(v, value_type_tag)
                     .map(|v| v.into())
@@ -10,7 +10,7 @@
 
 use std::rc::Rc;
 
-use crate::{rusqlite, Element, FindSpec, QueryOutput, QueryResults, Rows, Schema};
+use crate::{rusqlite, Binding, Element, FindSpec, QueryOutput, QueryResults, Rows, Schema};
 
 use query_projector_traits::errors::Result;
 
@@ -53,6 +53,19 @@ impl Projector for ConstantProjector {
         self.project_without_rows()
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        _schema: &Schema,
+        _sqlite: &'s rusqlite::Connection,
+        _rows: &mut Rows<'stmt>,
+        _limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        // There are no rows to read: everything is projected at once.
+        out.extend((self.results_factory)().into_rows());
+        Ok(0)
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
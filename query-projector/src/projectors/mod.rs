@@ -8,7 +8,7 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
-use super::{rusqlite, Element, QueryOutput, Rows, Schema};
+use super::{rusqlite, Binding, Element, QueryOutput, Row, Rows, Schema};
 
 use query_projector_traits::errors::Result;
 
@@ -19,9 +19,40 @@ pub trait Projector {
         sqlite: &'s rusqlite::Connection,
         rows: Rows<'stmt>,
     ) -> Result<QueryOutput>;
+
+    /// Project no more than `limit` of `rows` into `out`, one vector of bindings per row, without
+    /// reading any further. Pull expressions are resolved for just those rows. Returns how many
+    /// rows were read: fewer than `limit` means that there are no more.
+    ///
+    /// Unlike `project`, the results of a `coll` pull are in the order of the rows.
+    fn project_rows<'stmt, 's>(
+        &self,
+        schema: &Schema,
+        sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize>;
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's>;
 }
 
+/// Call `f` for each of no more than `limit` of `rows`, returning how many there were.
+fn take_rows<'stmt, F>(rows: &mut Rows<'stmt>, limit: usize, mut f: F) -> Result<usize>
+where
+    F: FnMut(&Row) -> Result<()>,
+{
+    let mut count = 0;
+    while count < limit {
+        match rows.next()? {
+            Some(row) => f(row)?,
+            None => break,
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
 mod constant;
 mod pull_two_stage;
 mod simple;
@@ -25,7 +25,7 @@ use crate::pull::{PullConsumer, PullOperation, PullTemplate};
 
 use query_projector_traits::errors::Result;
 
-use super::Projector;
+use super::{take_rows, Projector};
 
 pub(crate) struct ScalarTwoStagePullProjector {
     spec: Rc<FindSpec>,
@@ -69,17 +69,23 @@ impl Projector for ScalarTwoStagePullProjector {
         mut rows: Rows<'stmt>,
     ) -> Result<QueryOutput> {
         // Scalar is pretty straightforward -- zero or one entity, do the pull directly.
-        let results = if let Some(r) = rows.next().unwrap() {
+        let results = if let Some(r) = rows.next()? {
             let row = r;
-            let entity: Entid = row.get(0).unwrap(); // This will always be 0 and a ref.
-            let bindings = self.puller.pull(schema, sqlite, once(entity))?;
-            let m = Binding::Map(
-                bindings
-                    .get(&entity)
-                    .cloned()
-                    .unwrap_or_else(Default::default),
-            );
-            QueryResults::Scalar(Some(m))
+            // This will always be 0 and a ref, or `NULL` if bound by an `optional` clause.
+            let entity: Option<Entid> = row.get(0).unwrap();
+            match entity {
+                Some(entity) => {
+                    let bindings = self.puller.pull(schema, sqlite, once(entity))?;
+                    let m = Binding::Map(
+                        bindings
+                            .get(&entity)
+                            .cloned()
+                            .unwrap_or_else(Default::default),
+                    );
+                    QueryResults::Scalar(Some(m))
+                }
+                None => QueryResults::Scalar(Some(Binding::Nil)),
+            }
         } else {
             QueryResults::Scalar(None)
         };
@@ -90,6 +96,32 @@ impl Projector for ScalarTwoStagePullProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        schema: &Schema,
+        sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        let mut entities: Vec<Option<Entid>> = Vec::new();
+        let count = take_rows(rows, limit, |row| {
+            entities.push(row.get(0)?);
+            Ok(())
+        })?;
+
+        let pulled = self
+            .puller
+            .pull(schema, sqlite, entities.iter().flatten().cloned())?;
+        out.extend(entities.into_iter().map(|entity| match entity {
+            Some(entity) => vec![Binding::Map(
+                pulled.get(&entity).cloned().unwrap_or_else(Default::default),
+            )],
+            None => vec![Binding::Nil],
+        }));
+        Ok(count)
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
@@ -153,7 +185,7 @@ impl Projector for TupleTwoStagePullProjector {
         sqlite: &'s rusqlite::Connection,
         mut rows: Rows<'stmt>,
     ) -> Result<QueryOutput> {
-        let results = if let Some(r) = rows.next().unwrap() {
+        let results = if let Some(r) = rows.next()? {
             let row = r;
 
             // Keeping the compiler happy.
@@ -191,6 +223,41 @@ impl Projector for TupleTwoStagePullProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        schema: &Schema,
+        sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        let pull_consumers: Result<Vec<PullConsumer>> = self
+            .pulls
+            .iter()
+            .map(|op| PullConsumer::for_template(schema, op))
+            .collect();
+        let mut pull_consumers = pull_consumers?;
+
+        let start = out.len();
+        let count = take_rows(rows, limit, |row| {
+            for p in pull_consumers.iter_mut() {
+                p.collect_entity(row);
+            }
+            out.push(self.collect_bindings(row)?);
+            Ok(())
+        })?;
+
+        for p in pull_consumers.iter_mut() {
+            p.pull(sqlite)?;
+        }
+        for bindings in out[start..].iter_mut() {
+            for p in pull_consumers.iter() {
+                p.expand(bindings);
+            }
+        }
+        Ok(count)
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
@@ -280,7 +347,7 @@ impl Projector for RelTwoStagePullProjector {
         let mut pull_consumers = pull_consumers?;
 
         // Collect the usual bindings and accumulate entity IDs for pull.
-        while let Some(r) = rows.next().unwrap() {
+        while let Some(r) = rows.next()? {
             let row = r;
             for p in pull_consumers.iter_mut() {
                 p.collect_entity(&row);
@@ -306,6 +373,43 @@ impl Projector for RelTwoStagePullProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        schema: &Schema,
+        sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        let pull_consumers: Result<Vec<PullConsumer>> = self
+            .pulls
+            .iter()
+            .map(|op| PullConsumer::for_template(schema, op))
+            .collect();
+        let mut pull_consumers = pull_consumers?;
+
+        let start = out.len();
+        let count = take_rows(rows, limit, |row| {
+            for p in pull_consumers.iter_mut() {
+                p.collect_entity(row);
+            }
+            let mut bindings = Vec::with_capacity(self.len);
+            self.collect_bindings_into(row, &mut bindings)?;
+            out.push(bindings);
+            Ok(())
+        })?;
+
+        for p in pull_consumers.iter_mut() {
+            p.pull(sqlite)?;
+        }
+        for bindings in out[start..].iter_mut() {
+            for p in pull_consumers.iter() {
+                p.expand(bindings);
+            }
+        }
+        Ok(count)
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
@@ -347,7 +451,7 @@ impl Projector for CollTwoStagePullProjector {
     ) -> Result<QueryOutput> {
         let mut pull_consumer = PullConsumer::for_operation(schema, &self.pull)?;
 
-        while let Some(r) = rows.next().unwrap() {
+        while let Some(r) = rows.next()? {
             let row = r;
             pull_consumer.collect_entity(&row);
         }
@@ -364,6 +468,35 @@ impl Projector for CollTwoStagePullProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        schema: &Schema,
+        sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        let mut pull_consumer = PullConsumer::for_operation(schema, &self.pull)?;
+
+        let mut entities: Vec<Option<Entid>> = Vec::new();
+        let count = take_rows(rows, limit, |row| {
+            entities.push(pull_consumer.collect_entity(row));
+            Ok(())
+        })?;
+
+        pull_consumer.pull(sqlite)?;
+
+        // Just as with `project`, entities without any of the pulled attributes are omitted.
+        out.extend(
+            entities
+                .into_iter()
+                .flatten()
+                .filter_map(|entity| pull_consumer.pulled(entity))
+                .map(|binding| vec![binding]),
+        );
+        Ok(count)
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
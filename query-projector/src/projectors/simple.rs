@@ -17,7 +17,7 @@ use crate::{
 
 use query_projector_traits::errors::Result;
 
-use super::Projector;
+use super::{take_rows, Projector};
 
 pub(crate) struct ScalarProjector {
     spec: Rc<FindSpec>,
@@ -50,7 +50,7 @@ impl Projector for ScalarProjector {
         _sqlite: &'s rusqlite::Connection,
         mut rows: Rows<'stmt>,
     ) -> Result<QueryOutput> {
-        let results = if let Some(r) = rows.next().unwrap() {
+        let results = if let Some(r) = rows.next()? {
             let row = r;
             let binding = self.template.lookup(&row)?;
             QueryResults::Scalar(Some(binding))
@@ -63,6 +63,20 @@ impl Projector for ScalarProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        _schema: &Schema,
+        _sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        take_rows(rows, limit, |row| {
+            out.push(vec![self.template.lookup(row)?]);
+            Ok(())
+        })
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
@@ -122,7 +136,7 @@ impl Projector for TupleProjector {
         _sqlite: &'s rusqlite::Connection,
         mut rows: Rows<'stmt>,
     ) -> Result<QueryOutput> {
-        let results = if let Some(r) = rows.next().unwrap() {
+        let results = if let Some(r) = rows.next()? {
             let row = r;
             let bindings = self.collect_bindings(row)?;
             QueryResults::Tuple(Some(bindings))
@@ -135,6 +149,20 @@ impl Projector for TupleProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        _schema: &Schema,
+        _sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        take_rows(rows, limit, |row| {
+            out.push(self.collect_bindings(row)?);
+            Ok(())
+        })
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
@@ -207,7 +235,7 @@ impl Projector for RelProjector {
         let width = self.len;
         let mut values: Vec<_> = Vec::with_capacity(5 * width);
 
-        while let Some(r) = rows.next().unwrap() {
+        while let Some(r) = rows.next()? {
             let row = r;
             self.collect_bindings_into(row, &mut values)?;
         }
@@ -218,6 +246,22 @@ impl Projector for RelProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        _schema: &Schema,
+        _sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        take_rows(rows, limit, |row| {
+            let mut bindings = Vec::with_capacity(self.len);
+            self.collect_bindings_into(row, &mut bindings)?;
+            out.push(bindings);
+            Ok(())
+        })
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
@@ -263,7 +307,7 @@ impl Projector for CollProjector {
         mut rows: Rows<'stmt>,
     ) -> Result<QueryOutput> {
         let mut out: Vec<_> = vec![];
-        while let Some(r) = rows.next().unwrap() {
+        while let Some(r) = rows.next()? {
             let row = r;
             let binding = self.template.lookup(&row)?;
             out.push(binding);
@@ -274,6 +318,20 @@ impl Projector for CollProjector {
         })
     }
 
+    fn project_rows<'stmt, 's>(
+        &self,
+        _schema: &Schema,
+        _sqlite: &'s rusqlite::Connection,
+        rows: &mut Rows<'stmt>,
+        limit: usize,
+        out: &mut Vec<Vec<Binding>>,
+    ) -> Result<usize> {
+        take_rows(rows, limit, |row| {
+            out.push(vec![self.template.lookup(row)?]);
+            Ok(())
+        })
+    }
+
     fn columns<'s>(&'s self) -> Box<dyn Iterator<Item = &Element> + 's> {
         self.spec.columns()
     }
@@ -89,9 +89,12 @@ impl<'schema> PullConsumer<'schema> {
         ))
     }
 
-    pub(crate) fn collect_entity<'a>(&mut self, row: &rusqlite::Row<'a>) -> Entid {
+    pub(crate) fn collect_entity<'a>(&mut self, row: &rusqlite::Row<'a>) -> Option<Entid> {
+        // The entity is `NULL` if it's bound by an `optional` clause that didn't match.
         let entity = row.get(self.indices.sql_index).unwrap();
-        self.entities.insert(entity);
+        if let Some(entity) = entity {
+            self.entities.insert(entity);
+        }
         entity
     }
 
@@ -112,6 +115,11 @@ impl<'schema> PullConsumer<'schema> {
         }
     }
 
+    /// The pulled attributes of `entity`, if it had any.
+    pub(crate) fn pulled(&self, entity: Entid) -> Option<Binding> {
+        self.results.get(&entity).cloned().map(Binding::Map)
+    }
+
     // TODO: do we need to include empty maps for entities that didn't match any pull?
     pub(crate) fn into_coll_results(self) -> Vec<Binding> {
         self.results.values().cloned().map(Binding::Map).collect()
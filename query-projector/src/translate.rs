@@ -19,7 +19,7 @@ use edn::query::Limit;
 use mentat_query_algebrizer::{
     AlgebraicQuery, ColumnAlternation, ColumnConstraint, ColumnConstraintOrAlternation,
     ColumnIntersection, ColumnName, ComputedTable, ConjoiningClauses, DatomsColumn, DatomsTable,
-    OrderBy, QualifiedAlias, QueryValue, SourceAlias, TableAlias, VariableColumn,
+    OrderBy, QualifiedAlias, QueryValue, SourceAlias, StringPredicate, TableAlias, VariableColumn,
 };
 
 use crate::{
@@ -27,8 +27,8 @@ use crate::{
 };
 
 use mentat_query_sql::{
-    ColumnOrExpression, Constraint, FromClause, GroupBy, Op, ProjectedColumn, Projection,
-    SelectQuery, TableList, TableOrSubquery, Values,
+    ColumnOrExpression, Constraint, Expression, FromClause, GroupBy, Join, JoinOp, Op,
+    ProjectedColumn, Projection, SelectQuery, TableList, TableOrSubquery, Values,
 };
 
 use std::collections::HashMap;
@@ -227,7 +227,172 @@ impl ToConstraint for ColumnConstraint {
                 let subquery = table_for_computed(computed_table, TableAlias::new());
                 Constraint::NotExists { subquery }
             }
+
+            StringPredicate {
+                operator,
+                left,
+                right,
+            } => string_predicate_constraint(operator, left, right),
+        }
+    }
+}
+
+fn sql_function(name: &'static str, args: Vec<ColumnOrExpression>) -> ColumnOrExpression {
+    ColumnOrExpression::Expression(
+        Box::new(Expression::Function { name, args }),
+        ValueType::String,
+    )
+}
+
+fn sql_length(arg: QueryValue) -> ColumnOrExpression {
+    ColumnOrExpression::Expression(
+        Box::new(Expression::Unary {
+            sql_op: "length",
+            arg: arg.into(),
+        }),
+        ValueType::Long,
+    )
+}
+
+fn sql_lower(arg: QueryValue) -> ColumnOrExpression {
+    ColumnOrExpression::Expression(
+        Box::new(Expression::Unary {
+            sql_op: "lower",
+            arg: arg.into(),
+        }),
+        ValueType::String,
+    )
+}
+
+fn sql_string(s: String) -> ColumnOrExpression {
+    ColumnOrExpression::Value(TypedValue::from(s))
+}
+
+/// Escape the GLOB metacharacters in `s` so that it matches only itself.
+fn escape_glob(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '*' => escaped.push_str("[*]"),
+            '?' => escaped.push_str("[?]"),
+            '[' => escaped.push_str("[[]"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The smallest string that is greater than every string starting with `prefix`, if there is one.
+/// SQLite compares text by its UTF-8 bytes, which agrees with comparing code points.
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        let next = match last {
+            '\u{D7FF}' => Some('\u{E000}'),
+            c => ::std::char::from_u32(c as u32 + 1),
+        };
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+fn starts_with(left: QueryValue, right: QueryValue) -> Constraint {
+    Constraint::equal(
+        sql_function(
+            "substr",
+            vec![
+                left.into(),
+                ColumnOrExpression::Integer(1),
+                sql_length(right.clone()),
+            ],
+        ),
+        right.into(),
+    )
+}
+
+/// Lower a string predicate to SQL.
+///
+/// When the right-hand side is a constant we can do better than a function call: a prefix becomes
+/// a range over the value, which can use an index, and a suffix or substring becomes a `GLOB`,
+/// which is case-sensitive like the rest of our string comparisons. Strings sort after numbers
+/// and before blobs in SQLite, so the range can't accidentally match values of other types.
+fn string_predicate_constraint(
+    operator: StringPredicate,
+    left: QueryValue,
+    right: QueryValue,
+) -> Constraint {
+    use self::StringPredicate::*;
+
+    let constant = match right {
+        QueryValue::TypedValue(TypedValue::String(ref s)) => Some(s.to_string()),
+        _ => None,
+    };
+
+    match (operator, constant) {
+        (StartsWith, Some(prefix)) => match prefix_upper_bound(&prefix) {
+            Some(upper) => Constraint::And {
+                constraints: vec![
+                    Constraint::Infix {
+                        op: Op(">="),
+                        left: left.clone().into(),
+                        right: sql_string(prefix),
+                    },
+                    Constraint::Infix {
+                        op: Op("<"),
+                        left: left.into(),
+                        right: sql_string(upper),
+                    },
+                ],
+            },
+            // On its own, `v >= prefix` would match blobs, too.
+            None => starts_with(left, right),
+        },
+        (StartsWith, None) => starts_with(left, right),
+        (EndsWith, Some(suffix)) => Constraint::Infix {
+            op: Op("GLOB"),
+            left: left.into(),
+            right: sql_string(format!("*{}", escape_glob(&suffix))),
+        },
+        (EndsWith, None) => {
+            // `substr(x, -0)` is all of `x`, so the empty suffix needs its own case.
+            let negated_length = ColumnOrExpression::Expression(
+                Box::new(Expression::Unary {
+                    sql_op: "-",
+                    arg: sql_length(right.clone()),
+                }),
+                ValueType::Long,
+            );
+            Constraint::Or {
+                constraints: vec![
+                    Constraint::equal(right.clone().into(), sql_string("".to_string())),
+                    Constraint::equal(
+                        sql_function("substr", vec![left.into(), negated_length]),
+                        right.into(),
+                    ),
+                ],
+            }
         }
+        (Includes, Some(infix)) => Constraint::Infix {
+            op: Op("GLOB"),
+            left: left.into(),
+            right: sql_string(format!("*{}*", escape_glob(&infix))),
+        },
+        (Includes, None) => Constraint::Infix {
+            op: Op(">"),
+            left: sql_function("instr", vec![left.into(), right.into()]),
+            right: ColumnOrExpression::Integer(0),
+        },
+        // `x REGEXP y` calls the `regexp(y, x)` function registered on each connection.
+        (ReFind, _) => Constraint::Infix {
+            op: Op("REGEXP"),
+            left: left.into(),
+            right: right.into(),
+        },
+        // SQLite's `lower` only folds ASCII, which is all that `equals-ignore-case?` promises.
+        (EqualsIgnoreCase, _) => Constraint::equal(sql_lower(left), sql_lower(right)),
     }
 }
 
@@ -348,7 +513,7 @@ fn cc_to_select_query(
     order: Option<Vec<OrderBy>>,
     limit: Limit,
 ) -> SelectQuery {
-    let from = if cc.from.is_empty() {
+    let from = if cc.from.is_empty() && cc.left_joins.is_empty() {
         FromClause::Nothing
     } else {
         // Move these out of the CC.
@@ -359,15 +524,70 @@ fn cc_to_select_query(
         // a CTE (`WITH`). They're typically equivalent, but some SQL systems (notably Postgres)
         // treat CTEs as optimization barriers, so a `WITH` can be significantly slower. Given that
         // this is easy enough to change later, we'll opt for using direct inclusion in `FROM`.
-        let tables = from.into_iter().map(|source_alias| match source_alias {
-            SourceAlias(DatomsTable::Computed(i), alias) => {
-                let comp = computed.take_dangerously(i);
-                table_for_computed(comp, alias)
-            }
-            _ => TableOrSubquery::Table(source_alias),
-        });
+        let mut table_list = |from: Vec<SourceAlias>| {
+            let tables = from.into_iter().map(|source_alias| match source_alias {
+                SourceAlias(DatomsTable::Computed(i), alias) => {
+                    let comp = computed.take_dangerously(i);
+                    table_for_computed(comp, alias)
+                }
+                _ => TableOrSubquery::Table(source_alias),
+            });
+            TableList(tables.collect())
+        };
+
+        // SQLite is free to reorder the tables in a list, so we cross join those that the planner
+        // ordered.  The right-hand table of a `CROSS JOIN` follows every table to its left, so we
+        // can only do that for one run of patterns without ordering it against the others: we
+        // take the longest, put it first, and inner join everything else.  We only do so when the
+        // run is all datoms tables: we don't estimate the cost of anything else.
+        let run = cc
+            .planned
+            .iter()
+            .filter(|run| run.len() > 1)
+            .filter(|run| {
+                from[run.start..run.end].iter().all(|source| {
+                    matches!(
+                        source.0,
+                        DatomsTable::Datoms | DatomsTable::FulltextDatoms | DatomsTable::AllDatoms
+                    )
+                })
+            })
+            // The earliest of the longest.
+            .rev()
+            .max_by_key(|run| run.len())
+            .cloned();
+
+        let tables = table_list(from);
+        let (tables, mut joins) = if let Some(run) = run {
+            let mut tables = tables.0;
+            let mut planned = tables.drain(run).collect::<Vec<_>>().into_iter();
+            let first = TableList(planned.next().into_iter().collect());
+            let cross = planned.map(|table| Join {
+                op: JoinOp::Cross,
+                right: TableList(vec![table]),
+                on: vec![],
+            });
+            let inner = tables.into_iter().map(|table| Join {
+                op: JoinOp::Inner,
+                right: TableList(vec![table]),
+                on: vec![],
+            });
+            (first, cross.chain(inner).collect())
+        } else {
+            (tables, vec![])
+        };
+
+        joins.extend(cc.left_joins.into_iter().map(|join| Join {
+            op: JoinOp::LeftOuter,
+            right: table_list(join.from),
+            on: join.on.into_iter().map(|c| c.to_constraint()).collect(),
+        }));
 
-        FromClause::TableList(TableList(tables.collect()))
+        if joins.is_empty() {
+            FromClause::TableList(tables)
+        } else {
+            FromClause::Join(tables, joins)
+        }
     };
 
     let order = order.map_or(vec![], |vec| vec.into_iter().collect());
@@ -21,12 +21,13 @@ use std::rc::Rc;
 
 use edn::query::{FindSpec, Keyword, Variable};
 
+use core_traits::attribute::FulltextTokenizer;
 use core_traits::{Attribute, Entid, TypedValue, ValueType};
 
-use mentat_core::Schema;
+use mentat_core::{AttributeStatistics, Schema, Statistics};
 
 use mentat_query_algebrizer::{
-    algebrize, algebrize_with_inputs, parse_find_string, Known, QueryInputs,
+    algebrize, algebrize_with_inputs, parse_find_string, AttachedSchemas, Known, QueryInputs,
 };
 
 use mentat_query_projector::ConstantProjector;
@@ -460,6 +461,254 @@ fn test_numeric_gte_known_attribute() {
     assert_eq!(args, vec![]);
 }
 
+#[test]
+fn test_string_predicates() {
+    let schema = prepopulated_typed_schema(ValueType::String);
+
+    // A constant prefix becomes a range over the value, which can use the AVET index.
+    let query = r#"[:find ?x :where [?x :foo/bar ?y] [(starts-with? ?y "ab")]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND (`datoms00`.v >= $v0 AND `datoms00`.v < $v1)");
+    assert_eq!(args, vec![make_arg("$v0", "ab"), make_arg("$v1", "ac")]);
+
+    // Every string starts with the empty prefix, so only the type constraint is left.
+    let query = r#"[:find ?x :where [?x _ ?y] [(starts-with? ?y "")]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `all_datoms00`.e AS `?x` FROM `all_datoms` AS `all_datoms00` WHERE (`all_datoms00`.value_type_tag = 10)");
+    assert_eq!(args, vec![]);
+
+    let query = r#"[:find ?x :where [?x :foo/bar ?y] [(starts-with? ?y "")]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99");
+    assert_eq!(args, vec![]);
+
+    // A prefix with no upper bound can't be a range: `v >= prefix` alone would match blobs.
+    let query = "[:find ?x :where [?x :foo/bar ?y] [(starts-with? ?y \"\u{10FFFF}\")]]";
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND substr(`datoms00`.v, 1, length($v0)) = $v0");
+    assert_eq!(args, vec![make_arg("$v0", "\u{10FFFF}")]);
+
+    // Constant suffixes and substrings become escaped GLOBs.
+    let query = r#"[:find ?x :where [?x :foo/bar ?y] [(ends-with? ?y "*b")]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND `datoms00`.v GLOB $v0");
+    assert_eq!(args, vec![make_arg("$v0", "*[*]b")]);
+
+    let query = r#"[:find ?x :where [?x :foo/bar ?y] [(includes? ?y "a?")]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND `datoms00`.v GLOB $v0");
+    assert_eq!(args, vec![make_arg("$v0", "*a[?]*")]);
+
+    // Variables on the right-hand side fall back to string functions.
+    let query = r#"[:find ?x :where [?x :foo/bar ?y] [?x :foo/bar ?z] [(starts-with? ?y ?z)]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00`, `datoms` AS `datoms01` WHERE `datoms00`.a = 99 AND `datoms01`.a = 99 AND substr(`datoms00`.v, 1, length(`datoms01`.v)) = `datoms01`.v AND `datoms00`.e = `datoms01`.e");
+
+    let query = r#"[:find ?x :where [?x :foo/bar ?y] [(re-find ?y "^a+$")]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND `datoms00`.v REGEXP $v0");
+    assert_eq!(args, vec![make_arg("$v0", "^a+$")]);
+
+    let query = r#"[:find ?x :where [?x :foo/bar ?y] [(equals-ignore-case? ?y "AB")]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND lower(`datoms00`.v) = lower($v0)");
+    assert_eq!(args, vec![make_arg("$v0", "AB")]);
+}
+
+#[test]
+fn test_expression_functions() {
+    let schema = prepopulated_typed_schema(ValueType::Long);
+
+    // Arithmetic on longs fails, rather than overflowing into a double.
+    let query = r#"[:find ?x ?y :where [?x :foo/bar ?v] [(+ ?v 1) ?y]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x`, checked_add(`datoms00`.v, 1) AS `?y` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99");
+    assert_eq!(args, vec![]);
+
+    let query = r#"[:find ?y . :where [?x :foo/bar ?v] [(- ?v) ?y]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT checked_neg(`datoms00`.v) AS `?y` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 LIMIT 1");
+
+    // Division is computed in floating point.
+    let query = r#"[:find ?y . :where [?x :foo/bar ?v] [(/ ?v 2) ?y]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT (CAST(`datoms00`.v AS REAL) / 2) AS `?y` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 LIMIT 1");
+
+    // Divisors can't be zero.
+    let query = r#"[:find ?y . :where [?x :foo/bar ?v] [(quot 10 ?v) ?y]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT (10 / `datoms00`.v) AS `?y` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND `datoms00`.v <> 0 LIMIT 1");
+
+    let query = r#"[:find ?y . :where [?x :foo/bar ?v] [(mod ?v 3) ?y]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT (((`datoms00`.v % 3) + 3) % 3) AS `?y` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 LIMIT 1");
+
+    // Results can be constrained like any other binding.
+    let query = r#"[:find ?x :where [?x :foo/bar ?v] [(* ?v 2) ?y] [(> ?y 10)]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?x` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 AND checked_mul(`datoms00`.v, 2) > 10");
+
+    let query = r#"[:find ?y . :where [?x :foo/bar ?v] [(str "n" ?v) ?y]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT (CAST($v0 AS TEXT) || `datoms00`.v) AS `?y` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 LIMIT 1");
+    assert_eq!(args, vec![make_arg("$v0", "n")]);
+
+    // An end before the start gives the empty string, rather than the characters before the start.
+    let query = r#"[:find ?y . :where [?x :foo/bar ?v] [(subs "needle" 2 ?v) ?y]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(sql, "SELECT substr($v0, (2 + 1), max((`datoms00`.v - 2), 0)) AS `?y` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 99 LIMIT 1");
+    assert_eq!(args, vec![make_arg("$v0", "needle")]);
+}
+
+#[test]
+fn test_get_else() {
+    let schema = prepopulated_typed_schema(ValueType::Long);
+
+    let query = r#"[:find ?e ?v :where [?e :foo/fts _] [(get-else $ ?e :foo/bar 0) ?v]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e`, coalesce((SELECT `datoms01`.v AS `v` FROM `datoms` AS `datoms01` WHERE `datoms01`.e = `datoms00`.e AND `datoms01`.a = 99), 0) AS `?v` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 100");
+
+    let query = r#"[:find ?e :where [?e :foo/fts _] [(missing? $ ?e :foo/bar)]]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 100 AND NOT EXISTS (SELECT 1 FROM `datoms` AS `datoms01` WHERE `datoms01`.a = 99 AND `datoms00`.e = `datoms01`.e)");
+}
+
+#[test]
+fn test_optional() {
+    let schema = prepopulated_typed_schema(ValueType::Long);
+
+    let query = r#"[:find ?e ?v :where [?e :foo/fts _] (optional [?e :foo/bar ?v] [(> ?v 2)])]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e`, `datoms01`.v AS `?v` FROM `datoms` AS `datoms00` LEFT OUTER JOIN `datoms` AS `datoms01` ON `datoms01`.a = 99 AND `datoms01`.v > 2 AND `datoms00`.e = `datoms01`.e WHERE `datoms00`.a = 100");
+
+    // Clauses that can never match bind nothing.
+    let query =
+        r#"[:find ?e ?v :where [?e :foo/fts _] (optional [?e :foo/bar "x"] [?e :foo/bar ?v])]"#;
+    let SQLQuery { sql, .. } = translate(&schema, query);
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e`, NULL AS `?v`, NULL AS `?v_value_type_tag` FROM `datoms` AS `datoms00` WHERE `datoms00`.a = 100");
+}
+
+#[test]
+fn test_delta() {
+    let schema = prepopulated_typed_schema(ValueType::Long);
+
+    let query = r#"[:find ?e ?v :where [$delta ?e :foo/bar ?v] [?e :foo/fts _]]"#;
+    let SQLQuery { sql, .. } =
+        translate_with_inputs(&schema, query, QueryInputs::for_delta(vec![1000]));
+    assert_eq!(sql, "SELECT DISTINCT `transactions00`.e AS `?e`, `transactions00`.v AS `?v` FROM `transactions` AS `transactions00`, `datoms` AS `datoms01` WHERE `transactions00`.a = 99 AND `transactions00`.tx = 1000 AND `datoms01`.a = 100 AND `transactions00`.e = `datoms01`.e");
+
+    let query = r#"[:find ?e ?added :where [(tx-data $delta) [[?e _ _ _ ?added]]]]"#;
+    let SQLQuery { sql, .. } =
+        translate_with_inputs(&schema, query, QueryInputs::for_delta(vec![1000, 1001]));
+    assert_eq!(sql, "SELECT DISTINCT `transactions00`.e AS `?e`, `transactions00`.added AS `?added` FROM `transactions` AS `transactions00` WHERE ((`transactions00`.tx = 1000) OR (`transactions00`.tx = 1001))");
+}
+
+#[test]
+fn test_attached() {
+    let schema = prepopulated_typed_schema(ValueType::Long);
+    let mut other = Schema::default();
+    associate_ident(&mut other, Keyword::namespaced("foo", "count"), 65);
+    add_attribute(
+        &mut other,
+        65,
+        Attribute {
+            value_type: ValueType::Long,
+            ..Default::default()
+        },
+    );
+    let mut attached = AttachedSchemas::new();
+    attached.insert("other".to_string(), other);
+    let known = Known::for_schema(&schema).with_attached(&attached);
+
+    let translate = |query: &str| -> String {
+        let parsed = parse_find_string(query).expect("parse to succeed");
+        let algebrized = algebrize(known, parsed).expect("algebrize to succeed");
+        query_to_sql(query_to_select(&schema, algebrized).expect("translate to succeed")).sql
+    };
+
+    // `:foo/count` is only known to the attached store.
+    let sql = translate(
+        r#"[:find ?e ?n :where [?e :foo/bar ?v] [$other ?o :foo/count ?n] [$other ?o :foo/count ?v]]"#,
+    );
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e`, `datoms01`.v AS `?n` FROM `datoms` AS `datoms00`, `other`.`datoms` AS `datoms01`, `other`.`datoms` AS `datoms02` WHERE `datoms00`.a = 99 AND `datoms01`.a = 65 AND `datoms02`.a = 65 AND `datoms01`.e = `datoms02`.e AND `datoms00`.v = `datoms02`.v");
+
+    let sql = translate(r#"[:find ?e :where [(tx-data $other 1000) [[?e]]]]"#);
+    assert_eq!(sql, "SELECT DISTINCT `transactions00`.e AS `?e` FROM `other`.`transactions` AS `transactions00` WHERE `transactions00`.tx = 1000");
+}
+
+#[test]
+fn test_planned() {
+    let mut schema = Schema::default();
+    associate_ident(&mut schema, Keyword::namespaced("foo", "tag"), 65);
+    associate_ident(&mut schema, Keyword::namespaced("foo", "name"), 66);
+    add_attribute(
+        &mut schema,
+        65,
+        Attribute {
+            value_type: ValueType::String,
+            multival: true,
+            ..Default::default()
+        },
+    );
+    add_attribute(
+        &mut schema,
+        66,
+        Attribute {
+            value_type: ValueType::String,
+            index: true,
+            ..Default::default()
+        },
+    );
+
+    let mut statistics = Statistics {
+        datoms: 11000,
+        entities: 1000,
+        ..Default::default()
+    };
+    statistics.attributes.insert(
+        65,
+        AttributeStatistics {
+            datoms: 10000,
+            entities: 1000,
+            values: 50,
+        },
+    );
+    statistics.attributes.insert(
+        66,
+        AttributeStatistics {
+            datoms: 1000,
+            entities: 1000,
+            values: 1000,
+        },
+    );
+
+    let query = r#"[:find ?e :where [?e :foo/tag "x"] [?e :foo/name "Ann"]]"#;
+    let translate = |known: Known| -> String {
+        let parsed = parse_find_string(query).expect("parse to succeed");
+        let algebrized = algebrize(known, parsed).expect("algebrize to succeed");
+        query_to_sql(query_to_select(&schema, algebrized).expect("translate to succeed")).sql
+    };
+
+    // Without statistics, patterns are joined in the order they're written, and SQLite decides.
+    let sql = translate(Known::for_schema(&schema));
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e` FROM `datoms` AS `datoms00`, `datoms` AS `datoms01` WHERE `datoms00`.a = 65 AND `datoms00`.v = $v0 AND `datoms01`.a = 66 AND `datoms01`.v = $v1 AND `datoms00`.e = `datoms01`.e");
+
+    // With them, the name is looked up first, with the index, and its entity's tags are checked.
+    let sql = translate(Known::for_schema(&schema).with_statistics(&statistics));
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e` FROM `datoms` AS `datoms00` CROSS JOIN `datoms` AS `datoms01` WHERE `datoms00`.a = 66 AND `datoms00`.v = $v0 AND `datoms01`.a = 65 AND `datoms01`.v = $v1 AND `datoms00`.e = `datoms01`.e");
+
+    // Runs of patterns separated by another clause are planned separately, and only one of them,
+    // the longest, keeps its order. The `or` contributes a table of its own, which isn't ordered.
+    let query = r#"[:find ?e :where [?e :foo/tag "x"] [?e :foo/name ?n]
+                                    (or [?f :foo/tag "y"] [?f :foo/tag "z"])
+                                    [?f :foo/name ?n] [?g :foo/name ?n] [?g :foo/tag "w"]]"#;
+    let parsed = parse_find_string(query).expect("parse to succeed");
+    let known = Known::for_schema(&schema).with_statistics(&statistics);
+    let algebrized = algebrize(known, parsed).expect("algebrize to succeed");
+    let sql = query_to_sql(query_to_select(&schema, algebrized).expect("translate to succeed")).sql;
+    assert_eq!(sql, "SELECT DISTINCT `datoms00`.e AS `?e` FROM `datoms` AS `datoms03` CROSS JOIN `datoms` AS `datoms04` CROSS JOIN `datoms` AS `datoms05` JOIN `datoms` AS `datoms00` ON 1 JOIN `datoms` AS `datoms01` ON 1 JOIN `datoms` AS `datoms02` ON 1 WHERE `datoms00`.a = 66 AND `datoms01`.a = 65 AND `datoms01`.v = $v0 AND ((`datoms02`.a = 65 AND `datoms02`.v = $v1) OR (`datoms02`.a = 65 AND `datoms02`.v = $v2)) AND `datoms03`.a = 66 AND `datoms04`.a = 66 AND `datoms05`.a = 65 AND `datoms05`.v = $v3 AND `datoms00`.e = `datoms01`.e AND `datoms02`.e = `datoms03`.e AND `datoms04`.e = `datoms05`.e AND `datoms00`.v = `datoms03`.v AND `datoms00`.v = `datoms04`.v");
+}
+
 #[test]
 fn test_numeric_not_equals_known_attribute() {
     let schema = prepopulated_typed_schema(ValueType::Long);
@@ -1092,30 +1341,53 @@ fn test_fulltext() {
     assert_eq!(
         sql,
         "SELECT DISTINCT `datoms01`.e AS `?entity`, \
-         `fulltext_values00`.text AS `?value`, \
+         `fulltext_index00`.text AS `?value`, \
          `datoms01`.tx AS `?tx`, \
-         0e0 AS `?score` \
-         FROM `fulltext_values` AS `fulltext_values00`, \
+         -`fulltext_index00`.rank AS `?score` \
+         FROM `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0"
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0"
     );
     assert_eq!(args, vec![make_arg("$v0", "needle"),]);
 
+    let query = r#"[:find ?entity ?snippet ?highlight :where [(fulltext $ :foo/fts "needle") [[?entity _ _ _ ?snippet ?highlight]]]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(
+        sql,
+        "SELECT DISTINCT `datoms01`.e AS `?entity`, \
+         snippet(`fulltext_index00`.fulltext_index, 0, $v0, $v1, $v2, 16) AS `?snippet`, \
+         highlight(`fulltext_index00`.fulltext_index, 0, $v0, $v1) AS `?highlight` \
+         FROM `fulltext_index` AS `fulltext_index00`, \
+         `datoms` AS `datoms01` \
+         WHERE `datoms01`.a = 100 \
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v3"
+    );
+    assert_eq!(
+        args,
+        vec![
+            make_arg("$v0", "<b>"),
+            make_arg("$v1", "</b>"),
+            make_arg("$v2", "…"),
+            make_arg("$v3", "needle"),
+        ]
+    );
+
     let query = r#"[:find ?entity ?value ?tx :where [(fulltext $ :foo/fts "needle") [[?entity ?value ?tx ?score]]]]"#;
     let SQLQuery { sql, args } = translate(&schema, query);
     // Observe that the computed table isn't dropped, even though `?score` isn't bound in the final conjoining clause.
     assert_eq!(
         sql,
         "SELECT DISTINCT `datoms01`.e AS `?entity`, \
-         `fulltext_values00`.text AS `?value`, \
+         `fulltext_index00`.text AS `?value`, \
          `datoms01`.tx AS `?tx` \
-         FROM `fulltext_values` AS `fulltext_values00`, \
+         FROM `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0"
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0"
     );
     assert_eq!(args, vec![make_arg("$v0", "needle"),]);
 
@@ -1125,13 +1397,13 @@ fn test_fulltext() {
     assert_eq!(
         sql,
         "SELECT DISTINCT `datoms01`.e AS `?entity`, \
-         `fulltext_values00`.text AS `?value`, \
+         `fulltext_index00`.text AS `?value`, \
          `datoms01`.tx AS `?tx` \
-         FROM `fulltext_values` AS `fulltext_values00`, \
+         FROM `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0"
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0"
     );
     assert_eq!(args, vec![make_arg("$v0", "needle"),]);
 
@@ -1140,17 +1412,17 @@ fn test_fulltext() {
     assert_eq!(
         sql,
         "SELECT DISTINCT `datoms01`.e AS `?entity`, \
-         `fulltext_values00`.text AS `?value`, \
+         `fulltext_index00`.text AS `?value`, \
          `datoms01`.tx AS `?tx` \
-         FROM `fulltext_values` AS `fulltext_values00`, \
+         FROM `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01`, \
          `datoms` AS `datoms02` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0 \
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0 \
          AND `datoms02`.a = 99 \
-         AND `datoms02`.v = 0e0 \
-         AND `datoms01`.e = `datoms02`.e"
+         AND `datoms01`.e = `datoms02`.e \
+         AND -`fulltext_index00`.rank = `datoms02`.v"
     );
     assert_eq!(args, vec![make_arg("$v0", "needle"),]);
 
@@ -1159,17 +1431,83 @@ fn test_fulltext() {
     assert_eq!(
         sql,
         "SELECT DISTINCT `datoms00`.e AS `?entity`, \
-         `fulltext_values01`.text AS `?value`, \
+         `fulltext_index01`.text AS `?value`, \
          `datoms02`.tx AS `?tx` \
          FROM `datoms` AS `datoms00`, \
-         `fulltext_values` AS `fulltext_values01`, \
+         `fulltext_index` AS `fulltext_index01`, \
          `datoms` AS `datoms02` \
          WHERE `datoms00`.a = 99 \
          AND `datoms02`.a = 100 \
-         AND `datoms02`.v = `fulltext_values01`.rowid \
-         AND `fulltext_values01`.text MATCH $v0 \
-         AND `datoms00`.v = 0e0 \
-         AND `datoms00`.e = `datoms02`.e"
+         AND `datoms02`.v = `fulltext_index01`.rowid \
+         AND `fulltext_index01`.text MATCH $v0 \
+         AND `datoms00`.e = `datoms02`.e \
+         AND `datoms00`.v = -`fulltext_index01`.rank"
+    );
+    assert_eq!(args, vec![make_arg("$v0", "needle"),]);
+}
+
+#[test]
+fn test_fulltext_across_attributes() {
+    let mut schema = prepopulated_schema();
+    associate_ident(&mut schema, Keyword::namespaced("foo", "title"), 101);
+    add_attribute(
+        &mut schema,
+        101,
+        Attribute {
+            value_type: ValueType::String,
+            index: true,
+            fulltext: true,
+            ..Default::default()
+        },
+    );
+
+    // Attributes sharing an index are searched together.
+    let query =
+        r#"[:find ?entity ?attr :where [(fulltext $ :any "needle") [[?entity _ _ _ _ _ ?attr]]]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(
+        sql,
+        "SELECT DISTINCT `datoms01`.e AS `?entity`, \
+         `datoms01`.a AS `?attr` \
+         FROM `fulltext_index` AS `fulltext_index00`, \
+         `datoms` AS `datoms01` \
+         WHERE ((`datoms01`.a = 100) OR (`datoms01`.a = 101)) \
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0"
+    );
+    assert_eq!(args, vec![make_arg("$v0", "needle"),]);
+
+    // Attributes with different tokenizers are searched separately.
+    add_attribute(
+        &mut schema,
+        101,
+        Attribute {
+            value_type: ValueType::String,
+            index: true,
+            fulltext: true,
+            fulltext_tokenizer: FulltextTokenizer::Porter,
+            ..Default::default()
+        },
+    );
+    let query =
+        r#"[:find ?entity :where [(fulltext $ [:foo/fts :foo/title] "needle") [[?entity]]]]"#;
+    let SQLQuery { sql, args } = translate(&schema, query);
+    assert_eq!(
+        sql,
+        "SELECT DISTINCT `c00`.`?entity` AS `?entity` \
+         FROM (SELECT `datoms01`.e AS `?entity` \
+         FROM `fulltext_index` AS `fulltext_index00`, \
+         `datoms` AS `datoms01` \
+         WHERE `datoms01`.a = 100 \
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0 \
+         UNION \
+         SELECT `datoms03`.e AS `?entity` \
+         FROM `fulltext_index_porter` AS `fulltext_index_porter02`, \
+         `datoms` AS `datoms03` \
+         WHERE `datoms03`.a = 101 \
+         AND `datoms03`.v = `fulltext_index_porter02`.rowid \
+         AND `fulltext_index_porter02`.text MATCH $v0) AS `c00`"
     );
     assert_eq!(args, vec![make_arg("$v0", "needle"),]);
 }
@@ -1190,13 +1528,13 @@ fn test_fulltext_inputs() {
     let SQLQuery { sql, args } = translate_with_inputs(&schema, query, inputs);
     assert_eq!(
         sql,
-        "SELECT DISTINCT `fulltext_values00`.text AS `?val` \
+        "SELECT DISTINCT `fulltext_index00`.text AS `?val` \
          FROM \
-         `fulltext_values` AS `fulltext_values00`, \
+         `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0"
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0"
     );
     assert_eq!(args, vec![make_arg("$v0", "hello"),]);
 
@@ -1208,13 +1546,13 @@ fn test_fulltext_inputs() {
     let SQLQuery { sql, args } = translate_with_inputs(&schema, query, inputs);
     assert_eq!(
         sql,
-        "SELECT DISTINCT `fulltext_values00`.text AS `?val` \
+        "SELECT DISTINCT `fulltext_index00`.text AS `?val` \
          FROM \
-         `fulltext_values` AS `fulltext_values00`, \
+         `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0 \
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0 \
          AND `datoms01`.e = 111"
     );
     assert_eq!(args, vec![make_arg("$v0", "hello"),]);
@@ -1231,11 +1569,11 @@ fn test_fulltext_inputs() {
     assert_eq!(
         sql,
         "SELECT 111 AS `?entity` FROM \
-         `fulltext_values` AS `fulltext_values00`, \
+         `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0 \
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0 \
          AND `datoms01`.e = 111 \
          LIMIT 1"
     );
@@ -1255,15 +1593,15 @@ fn test_fulltext_inputs() {
     assert_eq!(
         sql,
         "SELECT DISTINCT 121 AS `?entity`, \
-         `fulltext_values00`.text AS `?value`, \
+         `fulltext_index00`.text AS `?value`, \
          `datoms02`.v AS `?friend` \
          FROM \
-         `fulltext_values` AS `fulltext_values00`, \
+         `fulltext_index` AS `fulltext_index00`, \
          `datoms` AS `datoms01`, \
          `datoms` AS `datoms02` \
          WHERE `datoms01`.a = 100 \
-         AND `datoms01`.v = `fulltext_values00`.rowid \
-         AND `fulltext_values00`.text MATCH $v0 \
+         AND `datoms01`.v = `fulltext_index00`.rowid \
+         AND `fulltext_index00`.text MATCH $v0 \
          AND `datoms01`.e = 121 \
          AND `datoms02`.e = 121 \
          AND `datoms02`.a = 99"
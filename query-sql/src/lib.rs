@@ -25,7 +25,8 @@ use mentat_core::SQLTypeAffinity;
 use edn::query::{Direction, Limit, Variable};
 
 use mentat_query_algebrizer::{
-    Column, OrderBy, QualifiedAlias, QueryValue, SourceAlias, TableAlias, VariableColumn,
+    ArithmeticOperator, Column, DatomsColumn, FulltextColumn, OrderBy, QualifiedAlias, QueryValue,
+    SourceAlias, TableAlias, ValueExpression, VariableColumn,
 };
 
 use sql_traits::errors::{BuildQueryResult, SQLError};
@@ -48,6 +49,7 @@ pub enum ColumnOrExpression {
     Integer(i32), // We use these for type codes etc.
     Long(i64),
     Value(TypedValue),
+    Null,
     // Some aggregates (`min`, `max`, `avg`) can be over 0 rows, and therefore can be `NULL`; that
     // needs special treatment.
     NullableAggregate(Box<Expression>, ValueType), // Track the return type.
@@ -59,6 +61,25 @@ pub enum Expression {
         sql_op: &'static str,
         arg: ColumnOrExpression,
     },
+    /// A call to a built-in SQL function, or one we register on each connection, with any number
+    /// of arguments.
+    Function {
+        name: &'static str,
+        args: Vec<ColumnOrExpression>,
+    },
+    /// A binary operator, like `+` or `||`.
+    Infix {
+        sql_op: &'static str,
+        left: ColumnOrExpression,
+        right: ColumnOrExpression,
+    },
+    /// Like `CAST(x AS REAL)`.
+    Cast {
+        arg: ColumnOrExpression,
+        sql_type: &'static str,
+    },
+    /// A subquery producing a single value.
+    Subquery(Box<SelectQuery>),
 }
 
 /// `QueryValue` and `ColumnOrExpression` are almost identical… merge somehow?
@@ -159,9 +180,10 @@ impl Constraint {
     }
 }
 
-#[allow(dead_code)]
-enum JoinOp {
+pub enum JoinOp {
     Inner,
+    LeftOuter,
+    Cross, // An inner join that SQLite won't reorder.
 }
 
 // Short-hand for a list of tables all inner-joined.
@@ -173,11 +195,11 @@ impl TableList {
     }
 }
 
+/// A join of `right` against everything to its left in the `FROM` clause.
 pub struct Join {
-    left: TableOrSubquery,
-    op: JoinOp,
-    right: TableOrSubquery,
-    // TODO: constraints (ON, USING).
+    pub op: JoinOp,
+    pub right: TableList,
+    pub on: Vec<Constraint>,
 }
 
 #[allow(dead_code)]
@@ -201,7 +223,7 @@ pub enum Values {
 
 pub enum FromClause {
     TableList(TableList), // Short-hand for a pile of inner joins.
-    Join(Join),
+    Join(TableList, Vec<Join>),
     Nothing,
 }
 
@@ -239,6 +261,8 @@ fn push_column(qb: &mut dyn QueryBuilder, col: &Column) -> BuildQueryResult {
             qb.push_sql(d.as_str());
             Ok(())
         }
+        // Expressions aren't qualified by their alias.
+        Column::Expression(ref e) => value_expression(e).push_sql(qb),
     }
 }
 
@@ -264,6 +288,10 @@ impl QueryFragment for ColumnOrExpression {
                 Ok(())
             }
             Value(ref v) => out.push_typed_value(v),
+            Null => {
+                out.push_sql("NULL");
+                Ok(())
+            }
             NullableAggregate(ref e, _) | &Expression(ref e, _) => e.push_sql(out),
         }
     }
@@ -282,6 +310,41 @@ impl QueryFragment for Expression {
                 out.push_sql(")");
                 Ok(())
             }
+            Expression::Function { name, ref args } => {
+                out.push_sql(name); // No need to escape built-ins.
+                out.push_sql("(");
+                interpose!(arg, args, { arg.push_sql(out)? }, { out.push_sql(", ") });
+                out.push_sql(")");
+                Ok(())
+            }
+            Expression::Infix {
+                sql_op,
+                ref left,
+                ref right,
+            } => {
+                out.push_sql("(");
+                left.push_sql(out)?;
+                out.push_sql(" ");
+                out.push_sql(sql_op);
+                out.push_sql(" ");
+                right.push_sql(out)?;
+                out.push_sql(")");
+                Ok(())
+            }
+            Expression::Cast { ref arg, sql_type } => {
+                out.push_sql("CAST(");
+                arg.push_sql(out)?;
+                out.push_sql(" AS ");
+                out.push_sql(sql_type);
+                out.push_sql(")");
+                Ok(())
+            }
+            Expression::Subquery(ref subquery) => {
+                out.push_sql("(");
+                subquery.push_sql(out)?;
+                out.push_sql(")");
+                Ok(())
+            }
         }
     }
 }
@@ -407,21 +470,303 @@ impl QueryFragment for Constraint {
 
 impl QueryFragment for JoinOp {
     fn push_sql(&self, out: &mut dyn QueryBuilder) -> BuildQueryResult {
-        out.push_sql(" JOIN ");
+        match self {
+            JoinOp::Inner => out.push_sql(" JOIN "),
+            JoinOp::LeftOuter => out.push_sql(" LEFT OUTER JOIN "),
+            JoinOp::Cross => out.push_sql(" CROSS JOIN "),
+        }
         Ok(())
     }
 }
 
 // We don't own QualifiedAlias or QueryFragment, so we can't implement the trait.
 fn qualified_alias_push_sql(out: &mut dyn QueryBuilder, qa: &QualifiedAlias) -> BuildQueryResult {
+    // FTS5 auxiliary functions take the name of the index table, qualified by its alias.  The
+    // markup is bound, not inlined, so that callers can choose it.
+    let (function, index, markup, snippet) = match qa.1 {
+        Column::Fulltext(FulltextColumn::Snippet(index, ref markup)) => {
+            ("snippet", index, markup, true)
+        }
+        Column::Fulltext(FulltextColumn::Highlight(index, ref markup)) => {
+            ("highlight", index, markup, false)
+        }
+        Column::Expression(_) => {
+            return push_column(out, &qa.1);
+        }
+        Column::Fulltext(FulltextColumn::Score) => {
+            // BM25 ranks are negative, and better matches are more negative.
+            out.push_sql("-");
+            out.push_identifier(qa.0.as_str())?;
+            out.push_sql(".");
+            return push_column(out, &qa.1);
+        }
+        _ => {
+            out.push_identifier(qa.0.as_str())?;
+            out.push_sql(".");
+            return push_column(out, &qa.1);
+        }
+    };
+
+    out.push_sql(function);
+    out.push_sql("(");
     out.push_identifier(qa.0.as_str())?;
     out.push_sql(".");
-    push_column(out, &qa.1)
+    out.push_sql(index.table_name());
+    out.push_sql(", 0, ");
+    out.push_typed_value(&TypedValue::typed_string(markup.open.as_str()))?;
+    out.push_sql(", ");
+    out.push_typed_value(&TypedValue::typed_string(markup.close.as_str()))?;
+    if snippet {
+        out.push_sql(", ");
+        out.push_typed_value(&TypedValue::typed_string(markup.ellipsis.as_str()))?;
+        out.push_sql(", 16");
+    }
+    out.push_sql(")");
+    Ok(())
+}
+
+fn expression(expression: Expression, value_type: ValueType) -> ColumnOrExpression {
+    ColumnOrExpression::Expression(Box::new(expression), value_type)
+}
+
+fn infix(
+    sql_op: &'static str,
+    left: ColumnOrExpression,
+    right: ColumnOrExpression,
+    value_type: ValueType,
+) -> ColumnOrExpression {
+    expression(
+        Expression::Infix {
+            sql_op,
+            left,
+            right,
+        },
+        value_type,
+    )
+}
+
+/// `(SELECT source.column FROM source WHERE source.e = entity AND source.a = attribute)`.
+fn attribute_subquery(
+    source: &SourceAlias,
+    entity: &QueryValue,
+    attribute: Entid,
+    column: DatomsColumn,
+    value_type: ValueType,
+) -> ColumnOrExpression {
+    let alias = &source.1;
+    let name = column.as_str().to_string();
+    let query = SelectQuery {
+        distinct: false,
+        projection: Projection::Columns(vec![ProjectedColumn(
+            ColumnOrExpression::Column(QualifiedAlias::new(alias.clone(), column)),
+            name,
+        )]),
+        from: FromClause::TableList(TableList(vec![TableOrSubquery::Table(source.clone())])),
+        constraints: vec![
+            Constraint::equal(
+                ColumnOrExpression::Column(QualifiedAlias::new(
+                    alias.clone(),
+                    DatomsColumn::Entity,
+                )),
+                entity.clone().into(),
+            ),
+            Constraint::equal(
+                ColumnOrExpression::Column(QualifiedAlias::new(
+                    alias.clone(),
+                    DatomsColumn::Attribute,
+                )),
+                ColumnOrExpression::Entid(attribute),
+            ),
+        ],
+        group_by: vec![],
+        order: vec![],
+        limit: Limit::None,
+    };
+    expression(Expression::Subquery(Box::new(query)), value_type)
+}
+
+/// Turn an expression computed by the algebrizer into SQL.
+fn value_expression(e: &ValueExpression) -> ColumnOrExpression {
+    match e {
+        ValueExpression::Arithmetic {
+            operator,
+            ref args,
+            result_type,
+        } => {
+            let result_type = *result_type;
+            let mut first: ColumnOrExpression = args[0].clone().into();
+            if result_type == ValueType::Double {
+                // Make sure SQLite doesn't do integer arithmetic.
+                first = expression(
+                    Expression::Cast {
+                        arg: first,
+                        sql_type: "REAL",
+                    },
+                    result_type,
+                );
+            }
+
+            let checked = match operator.to_checked_sql_function() {
+                Some(name) if result_type == ValueType::Long => Some(name),
+                _ => None,
+            };
+
+            match operator {
+                ArithmeticOperator::Subtract if args.len() == 1 && checked.is_some() => expression(
+                    Expression::Function {
+                        name: "checked_neg",
+                        args: vec![first],
+                    },
+                    result_type,
+                ),
+                ArithmeticOperator::Subtract if args.len() == 1 => expression(
+                    Expression::Unary {
+                        sql_op: "-",
+                        arg: first,
+                    },
+                    result_type,
+                ),
+                ArithmeticOperator::Modulo => {
+                    // SQLite's `%` takes the sign of the dividend; `mod` takes that of the divisor.
+                    let divisor = &args[1];
+                    let remainder = infix("%", first, divisor.clone().into(), result_type);
+                    let shifted = infix("+", remainder, divisor.clone().into(), result_type);
+                    infix("%", shifted, divisor.clone().into(), result_type)
+                }
+                _ => args[1..].iter().fold(first, |left, right| match checked {
+                    Some(name) => expression(
+                        Expression::Function {
+                            name,
+                            args: vec![left, right.clone().into()],
+                        },
+                        result_type,
+                    ),
+                    None => infix(
+                        operator.to_sql_operator(),
+                        left,
+                        right.clone().into(),
+                        result_type,
+                    ),
+                }),
+            }
+        }
+
+        ValueExpression::Concatenate(ref args) => {
+            let first = expression(
+                Expression::Cast {
+                    arg: args[0].clone().into(),
+                    sql_type: "TEXT",
+                },
+                ValueType::String,
+            );
+            args[1..].iter().fold(first, |left, right| {
+                infix("||", left, right.clone().into(), ValueType::String)
+            })
+        }
+
+        ValueExpression::Substring {
+            ref string,
+            ref start,
+            ref end,
+        } => {
+            // SQLite counts characters from one.
+            let mut args = vec![
+                string.clone().into(),
+                infix(
+                    "+",
+                    start.clone().into(),
+                    ColumnOrExpression::Integer(1),
+                    ValueType::Long,
+                ),
+            ];
+            if let Some(ref end) = *end {
+                // A negative length would count backwards from the start.
+                let length = infix(
+                    "-",
+                    end.clone().into(),
+                    start.clone().into(),
+                    ValueType::Long,
+                );
+                args.push(expression(
+                    Expression::Function {
+                        name: "max",
+                        args: vec![length, ColumnOrExpression::Integer(0)],
+                    },
+                    ValueType::Long,
+                ));
+            }
+            expression(
+                Expression::Function {
+                    name: "substr",
+                    args,
+                },
+                ValueType::String,
+            )
+        }
+
+        ValueExpression::Length(ref string) => expression(
+            Expression::Unary {
+                sql_op: "length",
+                arg: string.clone().into(),
+            },
+            ValueType::Long,
+        ),
+
+        ValueExpression::AttributeValue {
+            ref source,
+            ref entity,
+            attribute,
+            ref default,
+            value_type,
+        } => {
+            let value_type = *value_type;
+            let value =
+                attribute_subquery(source, entity, *attribute, DatomsColumn::Value, value_type);
+            expression(
+                Expression::Function {
+                    name: "coalesce",
+                    args: vec![value, default.clone().into()],
+                },
+                value_type,
+            )
+        }
+
+        ValueExpression::FirstAttribute {
+            ref source,
+            ref entity,
+            ref attributes,
+        } => {
+            let mut args: Vec<ColumnOrExpression> = attributes
+                .iter()
+                .map(|a| {
+                    attribute_subquery(source, entity, *a, DatomsColumn::Attribute, ValueType::Ref)
+                })
+                .collect();
+            if args.len() == 1 {
+                // `coalesce` needs at least two arguments.
+                args.pop().unwrap()
+            } else {
+                expression(
+                    Expression::Function {
+                        name: "coalesce",
+                        args,
+                    },
+                    ValueType::Ref,
+                )
+            }
+        }
+
+        ValueExpression::Null => ColumnOrExpression::Null,
+    }
 }
 
 // We don't own SourceAlias or QueryFragment, so we can't implement the trait.
 fn source_alias_push_sql(out: &mut dyn QueryBuilder, sa: &SourceAlias) -> BuildQueryResult {
     let &SourceAlias(ref table, ref alias) = sa;
+    if let Some(database) = table.database() {
+        out.push_identifier(database)?;
+        out.push_sql(".");
+    }
     out.push_identifier(table.name())?;
     out.push_sql(" AS ");
     out.push_identifier(alias.as_str())
@@ -440,9 +785,28 @@ impl QueryFragment for TableList {
 
 impl QueryFragment for Join {
     fn push_sql(&self, out: &mut dyn QueryBuilder) -> BuildQueryResult {
-        self.left.push_sql(out)?;
         self.op.push_sql(out)?;
-        self.right.push_sql(out)
+        if self.right.0.len() == 1 {
+            self.right.push_sql(out)?;
+        } else {
+            out.push_sql("(");
+            self.right.push_sql(out)?;
+            out.push_sql(")");
+        }
+
+        if let JoinOp::Cross = self.op {
+            return Ok(());
+        }
+
+        out.push_sql(" ON ");
+        if self.on.is_empty() {
+            out.push_sql("1");
+        } else {
+            interpose!(constraint, self.on, { constraint.push_sql(out)? }, {
+                out.push_sql(" AND ")
+            });
+        }
+        Ok(())
     }
 }
 
@@ -538,9 +902,18 @@ impl QueryFragment for FromClause {
                     table_list.push_sql(out)
                 }
             }
-            Join(ref join) => {
+            Join(ref table_list, ref joins) => {
                 out.push_sql(" FROM ");
-                join.push_sql(out)
+                if table_list.is_empty() {
+                    // There must be something to join against.
+                    out.push_sql("(SELECT 1)");
+                } else {
+                    table_list.push_sql(out)?;
+                }
+                for join in joins.iter() {
+                    join.push_sql(out)?;
+                }
+                Ok(())
             }
             Nothing => Ok(()),
         }
@@ -41,11 +41,21 @@ public interface JNA extends Library {
     class InProgress extends PointerType {}
     class InProgressBuilder extends PointerType {}
     class EntityBuilder extends PointerType {}
+    class StructuredMap extends PointerType {}
+    class AttributeBuilder extends PointerType {}
+    class VocabularyDefinition extends PointerType {}
+    class TempIdIterator extends PointerType {}
 
     Store store_open(String dbPath, RustError.ByReference err);
 
     void destroy(Pointer obj);
     void uuid_destroy(Pointer obj);
+    void byte_buffer_destroy(Pointer obj);
+    void structured_map_destroy(StructuredMap obj);
+    void attribute_builder_destroy(AttributeBuilder obj);
+    void vocabulary_definition_destroy(VocabularyDefinition obj);
+    void temp_id_destroy(Pointer obj);
+    void temp_id_iter_destroy(TempIdIterator obj);
     void query_builder_destroy(QueryBuilder obj);
     void store_destroy(Store obj);
     void typed_value_destroy(TypedValue obj);
@@ -69,12 +79,18 @@ public interface JNA extends Library {
     Pointer tx_report_entity_for_temp_id(TxReport report, String tempid); // returns a pointer to a 64 bit int on the heap
     long tx_report_get_entid(TxReport report);
     long tx_report_get_tx_instant(TxReport report);
+    TempIdIterator tx_report_temp_ids_into_iter(TxReport report);
+    Pointer temp_id_iter_next(TempIdIterator iter); // returns a pointer to a TempId
     InProgress store_begin_transaction(Store store, RustError.ByReference error);
 
     // in progress
     TxReport in_progress_transact(InProgress in_progress, String transaction, RustError.ByReference err);
     void in_progress_commit(InProgress in_progress, RustError.ByReference err);
     void in_progress_rollback(InProgress in_progress, RustError.ByReference err);
+    void in_progress_savepoint(InProgress in_progress, String name, RustError.ByReference err);
+    void in_progress_rollback_savepoint(InProgress in_progress, String name, RustError.ByReference err);
+    void in_progress_release_savepoint(InProgress in_progress, String name, RustError.ByReference err);
+    Pointer in_progress_ensure_vocabulary(InProgress in_progress, VocabularyDefinition definition, RustError.ByReference err); // returns a pointer to a VocabularyOutcome
     InProgressBuilder in_progress_builder(InProgress in_progress);
     EntityBuilder in_progress_entity_builder_from_temp_id(InProgress in_progress, String temp_id);
     EntityBuilder in_progress_entity_builder_from_entid(InProgress in_progress, long entid);
@@ -89,6 +105,7 @@ public interface JNA extends Library {
     void in_progress_builder_add_boolean(InProgressBuilder builder, long entid, String kw, int value, RustError.ByReference err);
     void in_progress_builder_add_double(InProgressBuilder builder, long entid, String kw, double value, RustError.ByReference err);
     void in_progress_builder_add_uuid(InProgressBuilder builder, long entid, String kw, Pointer value, RustError.ByReference err);
+    void in_progress_builder_add_bytes(InProgressBuilder builder, long entid, String kw, byte[] value, long len, RustError.ByReference err);
     void in_progress_builder_retract_string(InProgressBuilder builder, long entid, String kw, String value, RustError.ByReference err);
     void in_progress_builder_retract_long(InProgressBuilder builder, long entid, String kw, long value, RustError.ByReference err);
     void in_progress_builder_retract_ref(InProgressBuilder builder, long entid, String kw, long value, RustError.ByReference err);
@@ -97,6 +114,7 @@ public interface JNA extends Library {
     void in_progress_builder_retract_boolean(InProgressBuilder builder, long entid, String kw, int value, RustError.ByReference err);
     void in_progress_builder_retract_double(InProgressBuilder builder, long entid, String kw, double value, RustError.ByReference err);
     void in_progress_builder_retract_uuid(InProgressBuilder builder, long entid, String kw, Pointer value, RustError.ByReference err);
+    void in_progress_builder_retract_bytes(InProgressBuilder builder, long entid, String kw, byte[] value, long len, RustError.ByReference err);
     InProgressTransactionResult.ByValue in_progress_builder_transact(InProgressBuilder builder);
     TxReport in_progress_builder_commit(InProgressBuilder builder, RustError.ByReference err);
 
@@ -111,6 +129,7 @@ public interface JNA extends Library {
     void entity_builder_add_double(EntityBuilder builder, String kw, double value, RustError.ByReference err);
     void entity_builder_add_timestamp(EntityBuilder builder, String kw, long value, RustError.ByReference err);
     void entity_builder_add_uuid(EntityBuilder builder, String kw, Pointer value, RustError.ByReference err);
+    void entity_builder_add_bytes(EntityBuilder builder, String kw, byte[] value, long len, RustError.ByReference err);
     void entity_builder_retract_string(EntityBuilder builder, String kw, String value, RustError.ByReference err);
     void entity_builder_retract_long(EntityBuilder builder, String kw, long value, RustError.ByReference err);
     void entity_builder_retract_ref(EntityBuilder builder, String kw, long value, RustError.ByReference err);
@@ -119,9 +138,26 @@ public interface JNA extends Library {
     void entity_builder_retract_double(EntityBuilder builder, String kw, double value, RustError.ByReference err);
     void entity_builder_retract_timestamp(EntityBuilder builder, String kw, long value, RustError.ByReference err);
     void entity_builder_retract_uuid(EntityBuilder builder, String kw, Pointer value, RustError.ByReference err);
+    void entity_builder_retract_bytes(EntityBuilder builder, String kw, byte[] value, long len, RustError.ByReference err);
     InProgressTransactionResult.ByValue entity_builder_transact(EntityBuilder builder);
     TxReport entity_builder_commit(EntityBuilder builder, RustError.ByReference err);
 
+    // vocabulary
+    AttributeBuilder attribute_builder_new(int valueType);
+    void attribute_builder_multival(AttributeBuilder builder, int multival);
+    void attribute_builder_unique_value(AttributeBuilder builder);
+    void attribute_builder_unique_identity(AttributeBuilder builder);
+    void attribute_builder_index(AttributeBuilder builder, int index);
+    void attribute_builder_fulltext(AttributeBuilder builder, int fulltext);
+    void attribute_builder_component(AttributeBuilder builder, int component);
+    void attribute_builder_no_history(AttributeBuilder builder, int noHistory);
+    VocabularyDefinition vocabulary_definition_new(String name, int version);
+    void vocabulary_definition_add_attribute(VocabularyDefinition definition, String ident, AttributeBuilder builder);
+    Pointer store_ensure_vocabulary(Store store, VocabularyDefinition definition, RustError.ByReference err); // returns a pointer to a VocabularyOutcome
+
+    // sync; only in libraries built with the `syncable` feature
+    Pointer store_sync(Store store, String serverUri, String userUuid, RustError.ByReference err);
+
     // observers
     void store_register_observer(Store store, String key, Pointer attributes, int len, TxObserverCallback callback);
     void store_unregister_observer(Store store, String key);
@@ -130,6 +166,14 @@ public interface JNA extends Library {
     // Query Building
     QueryBuilder store_query(Store store, String query);
     TypedValue store_value_for_attribute(Store store, long entid, String attribute, RustError.ByReference err);
+
+    // Pull
+    StructuredMap store_pull_attributes_for_entity(Store store, long entid, Pointer attributes, long attributesLen, RustError.ByReference err);
+    TypedValueList store_pull_attributes_for_entities(Store store, Pointer entities, long entitiesLen, Pointer attributes, long attributesLen, RustError.ByReference err);
+    long structured_map_len(StructuredMap map);
+    Pointer structured_map_key_at(StructuredMap map, int index);
+    TypedValue structured_map_value_at(StructuredMap map, int index);
+    TypedValue structured_map_value_for_key(StructuredMap map, String key);
     void query_builder_bind_long(QueryBuilder query, String var, long value);
     void query_builder_bind_ref(QueryBuilder query, String var, long value);
     void query_builder_bind_ref_kw(QueryBuilder query, String var, String value);
@@ -139,12 +183,14 @@ public interface JNA extends Library {
     void query_builder_bind_timestamp(QueryBuilder query, String var, long value);
     void query_builder_bind_string(QueryBuilder query, String var, String value);
     void query_builder_bind_uuid(QueryBuilder query, String var, Pointer value);
+    void query_builder_bind_bytes(QueryBuilder query, String var, byte[] value, long len);
 
     // Query Execution
     RelResult query_builder_execute(QueryBuilder query, RustError.ByReference err);
     TypedValue query_builder_execute_scalar(QueryBuilder query, RustError.ByReference err);
     TypedValueList query_builder_execute_coll(QueryBuilder query, RustError.ByReference err);
     TypedValueList query_builder_execute_tuple(QueryBuilder query, RustError.ByReference err);
+    Pointer query_builder_explain(QueryBuilder query, RustError.ByReference err);
 
     // Query Result Processing
     long typed_value_into_long(TypedValue value);
@@ -155,7 +201,11 @@ public interface JNA extends Library {
     int typed_value_into_boolean(TypedValue value);
     double typed_value_into_double(TypedValue value);
     long typed_value_into_timestamp(TypedValue value);
+    Pointer typed_value_into_bytes(TypedValue value); // returns a pointer to a ByteBuffer
+    StructuredMap typed_value_into_map(TypedValue value);
+    TypedValueList typed_value_into_vec(TypedValue value);
     int typed_value_value_type(TypedValue value);
+    int typed_value_binding_kind(TypedValue value);
 
     TypedValueList row_at_index(RelResult rows, int index);
     RelResultIter typed_value_result_set_into_iter(RelResult rows);
@@ -170,6 +220,7 @@ public interface JNA extends Library {
     Pointer value_at_index_into_kw(TypedValueList rows, int index);
     Pointer value_at_index_into_string(TypedValueList rows, int index);
     Pointer value_at_index_into_uuid(TypedValueList rows, int index);
+    Pointer value_at_index_into_bytes(TypedValueList rows, int index); // returns a pointer to a ByteBuffer
     int value_at_index_into_boolean(TypedValueList rows, int index);
     double value_at_index_into_double(TypedValueList rows, int index);
     long value_at_index_into_timestamp(TypedValueList rows, int index);
@@ -0,0 +1,447 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Node.js bindings for Mentat, built with [napi-rs](https://napi.rs).
+//!
+//! ```js
+//! const mentat = require('mentat');
+//!
+//! const store = await mentat.open('/path/to/store.db');
+//! const names = await store.query('[:find [?name ...] :where [_ :person/name ?name]]').execute();
+//! ```
+//!
+//! Anything that touches the store runs on libuv's thread pool and returns a `Promise`; the
+//! store itself is behind a mutex, so work on one store happens one piece at a time.
+//! Observer callbacks are queued onto the JS event loop.
+
+#[macro_use]
+extern crate napi_derive;
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use napi::bindgen_prelude::{AsyncTask, Either, This};
+use napi::threadsafe_function::{
+    ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+};
+use napi::{Env, Error, JsFunction, JsObject, JsUnknown, Result, Task};
+
+use mentat::{
+    Entid, HasSchema, Keyword, QueryBuilder, QueryInputs, QueryResults, Queryable, TxObserver,
+    TypedValue, Uuid, Variable,
+};
+
+mod values;
+
+use values::{invalid_arg, parse_keyword, results_to_js, to_typed_value, typed_value_to_js};
+
+fn mentat_error<E: Display>(error: E) -> Error {
+    Error::from_reason(error.to_string())
+}
+
+fn lock(store: &Mutex<mentat::Store>) -> Result<MutexGuard<'_, mentat::Store>> {
+    store
+        .lock()
+        .map_err(|_| Error::from_reason("the store is unusable after a panic"))
+}
+
+pub struct Open {
+    path: String,
+}
+
+impl Task for Open {
+    type Output = mentat::Store;
+    type JsValue = Store;
+
+    fn compute(&mut self) -> Result<mentat::Store> {
+        mentat::Store::open(&self.path).map_err(mentat_error)
+    }
+
+    fn resolve(&mut self, _env: Env, store: mentat::Store) -> Result<Store> {
+        Ok(Store {
+            store: Arc::new(Mutex::new(store)),
+        })
+    }
+}
+
+/// Opens the store at `path`, or an in-memory store if `path` is empty or missing.
+#[napi(ts_return_type = "Promise<Store>")]
+pub fn open(path: Option<String>) -> AsyncTask<Open> {
+    AsyncTask::new(Open {
+        path: path.unwrap_or_default(),
+    })
+}
+
+pub struct Transact {
+    store: Arc<Mutex<mentat::Store>>,
+    transaction: String,
+}
+
+impl Task for Transact {
+    type Output = mentat::TxReport;
+    type JsValue = JsObject;
+
+    fn compute(&mut self) -> Result<mentat::TxReport> {
+        lock(&self.store)?
+            .transact(self.transaction.as_str())
+            .map_err(mentat_error)
+    }
+
+    /// Resolves to `{ txId, txInstant, tempids }`, where `tempids` maps each named tempid to
+    /// its entid.
+    fn resolve(&mut self, env: Env, report: mentat::TxReport) -> Result<JsObject> {
+        let mut object = env.create_object()?;
+        object.set_named_property("txId", env.create_int64(report.tx_id)?)?;
+        object.set_named_property(
+            "txInstant",
+            typed_value_to_js(&env, report.tx_instant.into())?,
+        )?;
+        let mut tempids = env.create_object()?;
+        for (tempid, entid) in report.tempids {
+            tempids.set_named_property(&tempid, env.create_int64(entid)?)?;
+        }
+        object.set_named_property("tempids", tempids)?;
+        Ok(object)
+    }
+}
+
+pub struct QueryOnce {
+    store: Arc<Mutex<mentat::Store>>,
+    query: String,
+    inputs: Vec<(String, TypedValue)>,
+}
+
+impl Task for QueryOnce {
+    type Output = QueryResults;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> Result<QueryResults> {
+        let inputs = QueryInputs::with_value_sequence(
+            self.inputs
+                .iter()
+                .map(|(name, value)| (Variable::from_valid_name(name), value.clone()))
+                .collect(),
+        );
+        lock(&self.store)?
+            .q_once(self.query.as_str(), inputs)
+            .map(|output| output.results)
+            .map_err(mentat_error)
+    }
+
+    fn resolve(&mut self, env: Env, results: QueryResults) -> Result<JsUnknown> {
+        results_to_js(&env, results)
+    }
+}
+
+/// The changes reported to an observer: the attributes that each transaction touched.
+type Changes = (String, Vec<(Entid, Vec<Entid>)>);
+
+/// A Mentat store.
+#[napi]
+pub struct Store {
+    store: Arc<Mutex<mentat::Store>>,
+}
+
+#[napi]
+impl Store {
+    /// Transacts an EDN string.
+    #[napi(
+        ts_return_type = "Promise<{ txId: number, txInstant: Date, tempids: Record<string, number> }>"
+    )]
+    pub fn transact(&self, transaction: String) -> AsyncTask<Transact> {
+        AsyncTask::new(Transact {
+            store: self.store.clone(),
+            transaction,
+        })
+    }
+
+    /// Runs `query` once, binding the values in `inputs`, an object such as `{ '?name': 'Alice' }`.
+    #[napi(ts_return_type = "Promise<unknown>")]
+    pub fn q(&self, query: String, inputs: Option<JsObject>) -> Result<AsyncTask<QueryOnce>> {
+        let mut values = vec![];
+        if let Some(inputs) = inputs {
+            let names = inputs.get_property_names()?;
+            for i in 0..names.get_array_length()? {
+                let name: String = names
+                    .get_element::<napi::JsString>(i)?
+                    .into_utf8()?
+                    .into_owned()?;
+                let value = inputs.get_named_property::<JsUnknown>(&name)?;
+                values.push((name, to_typed_value(value)?));
+            }
+        }
+        Ok(AsyncTask::new(QueryOnce {
+            store: self.store.clone(),
+            query,
+            inputs: values,
+        }))
+    }
+
+    /// Makes a `Query` to bind values to and then execute.
+    #[napi]
+    pub fn query(&self, query: String) -> Query {
+        Query {
+            store: self.store.clone(),
+            query,
+            inputs: vec![],
+        }
+    }
+
+    /// Calls `callback(key, changes)` after each committed transaction that touches any of
+    /// `attributes`. `changes` is an array of `[txId, [attributeEntid, ...]]` pairs.
+    ///
+    /// Observers don't keep Node running.
+    #[napi(
+        ts_args_type = "key: string, attributes: string[], callback: (key: string, changes: [number, number[]][]) => void"
+    )]
+    pub fn register_observer(
+        &self,
+        env: Env,
+        key: String,
+        attributes: Vec<String>,
+        callback: JsFunction,
+    ) -> Result<()> {
+        let mut store = lock(&self.store)?;
+        let schema = store.conn().current_schema();
+        let attributes = attributes
+            .iter()
+            .map(|attribute| {
+                let attribute = parse_keyword(attribute)?;
+                schema
+                    .get_entid(&attribute)
+                    .map(|entid| entid.0)
+                    .ok_or_else(|| invalid_arg(format!("no such attribute: {}", attribute)))
+            })
+            .collect::<Result<BTreeSet<_>>>()?;
+
+        let mut notify: ThreadsafeFunction<Changes, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(0, |ctx: ThreadSafeCallContext<Changes>| {
+                let (key, batch) = ctx.value;
+                let mut changes = ctx.env.create_array_with_length(batch.len())?;
+                for (i, (tx_id, attributes)) in batch.into_iter().enumerate() {
+                    let mut change = ctx.env.create_array_with_length(2)?;
+                    change.set_element(0, ctx.env.create_int64(tx_id)?)?;
+                    let mut touched = ctx.env.create_array_with_length(attributes.len())?;
+                    for (j, attribute) in attributes.into_iter().enumerate() {
+                        touched.set_element(j as u32, ctx.env.create_int64(attribute)?)?;
+                    }
+                    change.set_element(1, touched)?;
+                    changes.set_element(i as u32, change)?;
+                }
+                Ok(vec![
+                    ctx.env.create_string(&key)?.into_unknown(),
+                    changes.into_unknown(),
+                ])
+            })?;
+        notify.unref(&env)?;
+
+        let observer = TxObserver::new(attributes, move |key, batch| {
+            let batch = batch
+                .into_iter()
+                .map(|(tx_id, attributes)| (*tx_id, attributes.iter().cloned().collect()))
+                .collect();
+            notify.call(
+                (key.to_string(), batch),
+                ThreadsafeFunctionCallMode::NonBlocking,
+            );
+        });
+        store.register_observer(key, Arc::new(observer));
+        Ok(())
+    }
+
+    #[napi]
+    pub fn unregister_observer(&self, key: String) -> Result<()> {
+        lock(&self.store)?.unregister_observer(&key);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+enum Input {
+    Value(TypedValue),
+    /// An entity named by its ident, which is looked up when the query runs.
+    Ident(Keyword),
+}
+
+/// Which `QueryBuilder::execute*` to run.
+#[derive(Clone, Copy)]
+enum Shape {
+    Any,
+    Scalar,
+    Coll,
+    Tuple,
+    Rel,
+}
+
+pub struct Execute {
+    store: Arc<Mutex<mentat::Store>>,
+    query: String,
+    inputs: Vec<(String, Input)>,
+    shape: Shape,
+}
+
+impl Task for Execute {
+    type Output = QueryResults;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> Result<QueryResults> {
+        let mut store = lock(&self.store)?;
+        let schema = store.conn().current_schema();
+        let mut builder = QueryBuilder::new(&mut store, self.query.as_str());
+        for (var, input) in &self.inputs {
+            let value = match input {
+                Input::Value(value) => value.clone(),
+                Input::Ident(ident) => schema
+                    .get_entid(ident)
+                    .map(TypedValue::from)
+                    .ok_or_else(|| invalid_arg(format!("no such ident: {}", ident)))?,
+            };
+            builder.bind_value(var, value);
+        }
+        match self.shape {
+            Shape::Any => builder.execute().map(|output| output.results),
+            Shape::Scalar => builder.execute_scalar().map(QueryResults::Scalar),
+            Shape::Coll => builder.execute_coll().map(QueryResults::Coll),
+            Shape::Tuple => builder.execute_tuple().map(QueryResults::Tuple),
+            Shape::Rel => builder.execute_rel().map(QueryResults::Rel),
+        }
+        .map_err(mentat_error)
+    }
+
+    fn resolve(&mut self, env: Env, results: QueryResults) -> Result<JsUnknown> {
+        results_to_js(&env, results)
+    }
+}
+
+/// A query, and the values bound to its inputs. Binding methods return the query, so that
+/// they can be chained:
+///
+/// ```js
+/// await store.query('[:find ?e . :in ?name :where [?e :person/name ?name]]').bind('?name', 'Alice').scalar();
+/// ```
+#[napi]
+pub struct Query {
+    store: Arc<Mutex<mentat::Store>>,
+    query: String,
+    inputs: Vec<(String, Input)>,
+}
+
+impl Query {
+    fn execute_as(&self, shape: Shape) -> AsyncTask<Execute> {
+        AsyncTask::new(Execute {
+            store: self.store.clone(),
+            query: self.query.clone(),
+            inputs: self.inputs.clone(),
+            shape,
+        })
+    }
+}
+
+#[napi]
+impl Query {
+    /// Binds `value`, whose Mentat type follows from its JS type.
+    #[napi(ts_return_type = "this")]
+    pub fn bind(
+        &mut self,
+        this: This<JsObject>,
+        var: String,
+        value: JsUnknown,
+    ) -> Result<JsObject> {
+        self.inputs
+            .push((var, Input::Value(to_typed_value(value)?)));
+        Ok(this)
+    }
+
+    /// Binds an entity, given as an entid or as an ident such as `":person/name"`.
+    #[napi(ts_return_type = "this")]
+    pub fn bind_ref(
+        &mut self,
+        this: This<JsObject>,
+        var: String,
+        entity: Either<i64, String>,
+    ) -> Result<JsObject> {
+        let input = match entity {
+            Either::A(entid) => Input::Value(TypedValue::Ref(entid)),
+            Either::B(ident) => Input::Ident(parse_keyword(&ident)?),
+        };
+        self.inputs.push((var, input));
+        Ok(this)
+    }
+
+    #[napi(ts_return_type = "this")]
+    pub fn bind_keyword(
+        &mut self,
+        this: This<JsObject>,
+        var: String,
+        keyword: String,
+    ) -> Result<JsObject> {
+        let keyword = parse_keyword(&keyword)?;
+        self.inputs
+            .push((var, Input::Value(TypedValue::Keyword(keyword.into()))));
+        Ok(this)
+    }
+
+    /// Binds a double, which `bind` can't tell from a long if it's integral.
+    #[napi(ts_return_type = "this")]
+    pub fn bind_double(
+        &mut self,
+        this: This<JsObject>,
+        var: String,
+        value: f64,
+    ) -> Result<JsObject> {
+        self.inputs
+            .push((var, Input::Value(TypedValue::Double(value.into()))));
+        Ok(this)
+    }
+
+    #[napi(ts_return_type = "this")]
+    pub fn bind_uuid(
+        &mut self,
+        this: This<JsObject>,
+        var: String,
+        uuid: String,
+    ) -> Result<JsObject> {
+        let uuid = Uuid::parse_str(&uuid).map_err(|e| invalid_arg(e.to_string()))?;
+        self.inputs
+            .push((var, Input::Value(TypedValue::Uuid(uuid))));
+        Ok(this)
+    }
+
+    /// Runs the query, resolving to whatever shape of result its find spec asks for.
+    #[napi(ts_return_type = "Promise<unknown>")]
+    pub fn execute(&self) -> AsyncTask<Execute> {
+        self.execute_as(Shape::Any)
+    }
+
+    /// Runs a `[:find ?x . …]` query.
+    #[napi(ts_return_type = "Promise<unknown>")]
+    pub fn scalar(&self) -> AsyncTask<Execute> {
+        self.execute_as(Shape::Scalar)
+    }
+
+    /// Runs a `[:find [?x ...] …]` query.
+    #[napi(ts_return_type = "Promise<unknown[]>")]
+    pub fn coll(&self) -> AsyncTask<Execute> {
+        self.execute_as(Shape::Coll)
+    }
+
+    /// Runs a `[:find [?x ?y] …]` query.
+    #[napi(ts_return_type = "Promise<unknown[] | null>")]
+    pub fn tuple(&self) -> AsyncTask<Execute> {
+        self.execute_as(Shape::Tuple)
+    }
+
+    /// Runs a `[:find ?x ?y …]` query.
+    #[napi(ts_return_type = "Promise<unknown[][]>")]
+    pub fn rel(&self) -> AsyncTask<Execute> {
+        self.execute_as(Shape::Rel)
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Moving values between JavaScript and Mentat.
+//!
+//! Results come out as JS-native values: refs and doubles as numbers, longs as numbers when
+//! they're safe integers and `BigInt`s otherwise, instants as `Date`s (to the millisecond),
+//! bytes as `Buffer`s, and keywords and UUIDs as strings. Pulled entities are objects keyed by
+//! keyword strings such as `":person/name"`.
+
+use std::convert::TryFrom;
+
+use napi::{
+    Env, Error, JsBigInt, JsBuffer, JsDate, JsNumber, JsObject, JsString, JsUnknown, Result,
+    Status, ValueType as JsValueType,
+};
+
+use mentat::edn;
+use mentat::{Binding, DateTime, FromMillis, Keyword, QueryResults, TypedValue, Utc};
+
+/// The largest integer that a JS number holds exactly: `Number.MAX_SAFE_INTEGER`.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+pub fn invalid_arg<T: Into<String>>(message: T) -> Error {
+    Error::new(Status::InvalidArg, message.into())
+}
+
+pub fn parse_keyword(keyword: &str) -> Result<Keyword> {
+    match edn::parse::value(keyword).map(|v| v.without_spans()) {
+        Ok(edn::Value::Keyword(keyword)) => Ok(keyword),
+        _ => Err(invalid_arg(format!("'{}' isn't a keyword", keyword))),
+    }
+}
+
+/// Converts `value` to a `TypedValue`, guessing its type from its JS type: booleans, strings,
+/// `Date`s and `Buffer`s are obvious; integral numbers and `BigInt`s become longs, and other
+/// numbers doubles. Keywords, refs, UUIDs and integral doubles have to be bound explicitly.
+pub fn to_typed_value(value: JsUnknown) -> Result<TypedValue> {
+    match value.get_type()? {
+        JsValueType::Boolean => Ok(TypedValue::Boolean(value.coerce_to_bool()?.get_value()?)),
+        JsValueType::Number => {
+            let number = JsNumber::try_from(value)?.get_double()?;
+            if number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER as f64 {
+                Ok(TypedValue::Long(number as i64))
+            } else {
+                Ok(TypedValue::Double(number.into()))
+            }
+        }
+        JsValueType::BigInt => {
+            let (long, lossless) = unsafe { value.cast::<JsBigInt>() }.get_i64()?;
+            if lossless {
+                Ok(TypedValue::Long(long))
+            } else {
+                Err(invalid_arg("BigInt is too big for a long"))
+            }
+        }
+        JsValueType::String => {
+            let s = JsString::try_from(value)?.into_utf8()?;
+            Ok(TypedValue::typed_string(s.as_str()?))
+        }
+        JsValueType::Object if value.is_date()? => {
+            let millis = unsafe { value.cast::<JsDate>() }.value_of()?;
+            Ok(DateTime::<Utc>::from_millis(millis as i64).into())
+        }
+        JsValueType::Object if value.is_buffer()? => {
+            let buffer = unsafe { value.cast::<JsBuffer>() }.into_value()?;
+            Ok(TypedValue::Bytes(buffer.to_vec().into()))
+        }
+        other => Err(invalid_arg(format!(
+            "can't use a value of type {} as a Mentat value",
+            other
+        ))),
+    }
+}
+
+fn long_to_js(env: &Env, long: i64) -> Result<JsUnknown> {
+    if long.abs() <= MAX_SAFE_INTEGER {
+        env.create_int64(long).map(|n| n.into_unknown())
+    } else {
+        env.create_bigint_from_i64(long)?.into_unknown()
+    }
+}
+
+pub fn typed_value_to_js(env: &Env, value: TypedValue) -> Result<JsUnknown> {
+    match value {
+        TypedValue::Ref(entid) => env.create_int64(entid).map(|n| n.into_unknown()),
+        TypedValue::Boolean(b) => env.get_boolean(b).map(|b| b.into_unknown()),
+        TypedValue::Long(long) => long_to_js(env, long),
+        TypedValue::Double(d) => env.create_double(d.into_inner()).map(|n| n.into_unknown()),
+        TypedValue::Instant(instant) => env
+            .create_date(instant.timestamp_millis() as f64)
+            .map(|date| date.into_unknown()),
+        TypedValue::String(s) => env.create_string(&s).map(|s| s.into_unknown()),
+        TypedValue::Keyword(keyword) => env
+            .create_string(&keyword.to_string())
+            .map(|s| s.into_unknown()),
+        TypedValue::Uuid(uuid) => env
+            .create_string(&uuid.to_hyphenated().to_string())
+            .map(|s| s.into_unknown()),
+        TypedValue::Bytes(bytes) => env
+            .create_buffer_with_data(bytes.to_vec())
+            .map(|buffer| buffer.into_unknown()),
+    }
+}
+
+pub fn binding_to_js(env: &Env, binding: Binding) -> Result<JsUnknown> {
+    match binding {
+        Binding::Scalar(value) => typed_value_to_js(env, value),
+        Binding::Vec(values) => bindings_to_array(env, values.to_vec()).map(|a| a.into_unknown()),
+        Binding::Map(map) => {
+            let mut object = env.create_object()?;
+            for (keyword, value) in map.iter() {
+                object
+                    .set_named_property(&keyword.to_string(), binding_to_js(env, value.clone())?)?;
+            }
+            Ok(object.into_unknown())
+        }
+        Binding::Nil => env.get_null().map(|null| null.into_unknown()),
+    }
+}
+
+pub fn bindings_to_array(env: &Env, bindings: Vec<Binding>) -> Result<JsObject> {
+    let mut array = env.create_array_with_length(bindings.len())?;
+    for (i, binding) in bindings.into_iter().enumerate() {
+        array.set_element(i as u32, binding_to_js(env, binding)?)?;
+    }
+    Ok(array)
+}
+
+/// Shapes results as their find spec asks: a value or `null` for `.`, an array for
+/// `[?x ...]`, an array or `null` for `[?x ?y]`, and an array of arrays otherwise.
+pub fn results_to_js(env: &Env, results: QueryResults) -> Result<JsUnknown> {
+    match results {
+        QueryResults::Scalar(Some(binding)) => binding_to_js(env, binding),
+        QueryResults::Tuple(Some(bindings)) | QueryResults::Coll(bindings) => {
+            bindings_to_array(env, bindings).map(|a| a.into_unknown())
+        }
+        QueryResults::Scalar(None) | QueryResults::Tuple(None) => {
+            env.get_null().map(|null| null.into_unknown())
+        }
+        QueryResults::Rel(rel) => {
+            let mut rows = env.create_array_with_length(rel.row_count())?;
+            for (i, row) in rel.into_iter().enumerate() {
+                rows.set_element(i as u32, bindings_to_array(env, row)?)?;
+            }
+            Ok(rows.into_unknown())
+        }
+    }
+}
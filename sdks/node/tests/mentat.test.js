@@ -0,0 +1,156 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+'use strict';
+
+const assert = require('node:assert');
+const { test } = require('node:test');
+
+const mentat = require(process.env.MENTAT_NODE_ADDON || '..');
+
+const SCHEMA = `[
+  {:db/ident :person/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one :db/unique :db.unique/identity :db/index true}
+  {:db/ident :person/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+  {:db/ident :person/height :db/valueType :db.type/double :db/cardinality :db.cardinality/one}
+  {:db/ident :person/born :db/valueType :db.type/instant :db/cardinality :db.cardinality/one}
+  {:db/ident :person/id :db/valueType :db.type/uuid :db/cardinality :db.cardinality/one}
+  {:db/ident :person/photo :db/valueType :db.type/bytes :db/cardinality :db.cardinality/one}
+  {:db/ident :person/admin :db/valueType :db.type/boolean :db/cardinality :db.cardinality/one}
+  {:db/ident :person/role :db/valueType :db.type/keyword :db/cardinality :db.cardinality/one}
+  {:db/ident :person/friend :db/valueType :db.type/ref :db/cardinality :db.cardinality/many}
+]`;
+
+async function people() {
+  const store = await mentat.open();
+  await store.transact(SCHEMA);
+  await store.transact(`[{:db/id "alice" :person/name "Alice" :person/age 30}
+                         {:db/id "bob" :person/name "Bob" :person/age 40 :person/friend "alice"}]`);
+  return store;
+}
+
+test('transact reports tempids', async () => {
+  const store = await people();
+  const report = await store.transact('[{:db/id "carol" :person/name "Carol"}]');
+  assert.strictEqual(typeof report.txId, 'number');
+  assert.ok(report.txInstant instanceof Date);
+  assert.deepStrictEqual(Object.keys(report.tempids), ['carol']);
+  const carol = await store.query('[:find ?e . :where [?e :person/name "Carol"]]').scalar();
+  assert.strictEqual(report.tempids.carol, carol);
+});
+
+test('query shapes', async () => {
+  const store = await people();
+  const names = await store.query('[:find [?name ...] :where [_ :person/name ?name]]').coll();
+  assert.deepStrictEqual(names.sort(), ['Alice', 'Bob']);
+
+  const age = await store.query('[:find ?age . :in ?name :where [?e :person/name ?name] [?e :person/age ?age]]')
+    .bind('?name', 'Bob')
+    .scalar();
+  assert.strictEqual(age, 40);
+
+  const rows = await store.query('[:find ?name ?age :where [?e :person/name ?name] [?e :person/age ?age]]').rel();
+  assert.deepStrictEqual(rows.sort(), [['Alice', 30], ['Bob', 40]]);
+
+  const pair = await store.query('[:find [?name ?age] :where [?e :person/name ?name] [?e :person/age ?age] [(> ?age 35)]]').tuple();
+  assert.deepStrictEqual(pair, ['Bob', 40]);
+
+  assert.strictEqual(await store.query('[:find ?e . :where [?e :person/name "Carol"]]').execute(), null);
+});
+
+test('q binds inputs', async () => {
+  const store = await people();
+  const names = await store.q('[:find [?name ...] :in ?min :where [?e :person/age ?age] [(>= ?age ?min)] [?e :person/name ?name]]',
+                              { '?min': 35 });
+  assert.deepStrictEqual(names, ['Bob']);
+  assert.deepStrictEqual((await store.q('[:find [?name ...] :where [_ :person/name ?name]]')).sort(), ['Alice', 'Bob']);
+});
+
+test('bindRef and bindKeyword', async () => {
+  const store = await people();
+  const alice = await store.query('[:find ?e . :where [?e :person/name "Alice"]]').scalar();
+  const friendOf = await store.query('[:find ?name . :in ?friend :where [?e :person/friend ?friend] [?e :person/name ?name]]')
+    .bindRef('?friend', alice)
+    .scalar();
+  assert.strictEqual(friendOf, 'Bob');
+
+  const ident = await store.query('[:find ?ident . :in ?e :where [?e :db/ident ?ident]]').bindRef('?e', ':person/age').scalar();
+  assert.strictEqual(ident, ':person/age');
+
+  const attribute = await store.query('[:find ?e . :in ?ident :where [?e :db/ident ?ident]]').bindKeyword('?ident', ':person/age').scalar();
+  assert.strictEqual(typeof attribute, 'number');
+});
+
+test('pull makes objects', async () => {
+  const store = await people();
+  const bob = await store.query('[:find (pull ?e [:person/name :person/age]) . :where [?e :person/name "Bob"]]').scalar();
+  assert.deepStrictEqual(bob, { ':person/name': 'Bob', ':person/age': 40 });
+});
+
+test('values round trip', async () => {
+  const store = await people();
+  const born = new Date(Date.UTC(1990, 4, 17, 12, 30));
+  const id = '0f3e2b9c-3a5f-4c63-9d3c-6b2bf2bfa0a1';
+  await store.transact(`[{:person/name "Carol" :person/height 1.75 :person/born #inst "${born.toISOString()}"
+                          :person/id #uuid "${id}" :person/admin true :person/role :role/engineer
+                          :person/photo #bytes 000102}]`);
+  const row = await store.query(`[:find [?height ?born ?id ?photo ?admin ?role]
+                                  :in ?name
+                                  :where [?e :person/name ?name] [?e :person/height ?height] [?e :person/born ?born]
+                                         [?e :person/id ?id] [?e :person/photo ?photo]
+                                         [?e :person/admin ?admin] [?e :person/role ?role]]`)
+    .bind('?name', 'Carol')
+    .tuple();
+  assert.deepStrictEqual(row, [1.75, born, id, Buffer.from([0, 1, 2]), true, ':role/engineer']);
+
+  assert.strictEqual(await store.query('[:find ?e . :in ?born :where [?e :person/born ?born]]').bind('?born', born).scalar(),
+                     await store.query('[:find ?e . :where [?e :person/name "Carol"]]').scalar());
+  assert.strictEqual(await store.query('[:find ?e . :in ?photo :where [?e :person/photo ?photo]]').bind('?photo', Buffer.from([0, 1, 2])).scalar(),
+                     await store.query('[:find ?e . :where [?e :person/name "Carol"]]').scalar());
+  assert.strictEqual(await store.query('[:find ?e . :in ?id :where [?e :person/id ?id]]').bindUuid('?id', id).scalar(),
+                     await store.query('[:find ?e . :where [?e :person/name "Carol"]]').scalar());
+});
+
+test('big longs are BigInts', async () => {
+  const store = await people();
+  await store.transact('[{:person/name "Old" :person/age 9007199254740993}]');
+  const age = await store.query('[:find ?age . :where [?e :person/name "Old"] [?e :person/age ?age]]').scalar();
+  assert.strictEqual(age, 9007199254740993n);
+  const name = await store.query('[:find ?name . :in ?age :where [?e :person/age ?age] [?e :person/name ?name]]')
+    .bind('?age', 9007199254740993n)
+    .scalar();
+  assert.strictEqual(name, 'Old');
+});
+
+test('errors reject', async () => {
+  const store = await people();
+  await assert.rejects(store.query('[:find ?e :where').execute());
+  await assert.rejects(store.transact('[[:db/add "x" :person/nope 1]]'));
+  await assert.rejects(store.query('[:find ?e . :in ?e :where [?e :db/ident _]]').bindRef('?e', ':person/nope').scalar());
+  assert.throws(() => store.query('[:find ?e :in ?x :where [?e :person/age ?x]]').bind('?x', {}));
+  assert.throws(() => store.registerObserver('nope', [':person/nope'], () => {}));
+});
+
+test('observers are called on the event loop', async () => {
+  const store = await people();
+  const seen = new Promise((resolve) => {
+    store.registerObserver('ages', [':person/age'], (key, changes) => resolve({ key, changes }));
+  });
+  await store.transact('[{:person/name "Carol"}]');
+  const report = await store.transact('[{:person/name "Dave" :person/age 20}]');
+  const { key, changes } = await seen;
+  assert.strictEqual(key, 'ages');
+  // Only the second transaction touched an age.
+  assert.strictEqual(changes.length, 1);
+  const [txId, attributes] = changes[0];
+  assert.strictEqual(txId, report.txId);
+  const age = await store.query('[:find ?e . :where [?e :db/ident :person/age]]').scalar();
+  assert.ok(attributes.includes(age));
+  store.unregisterObserver('ages');
+});
@@ -0,0 +1,53 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Runs the tests in `tests/*.test.js` against the addon that was built for this test. Set
+//! `NODE` to use a `node` other than the one on the `PATH`.
+
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_node() {
+    let tests = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    // Test executables live in `target/<profile>/deps`, alongside the shared library that was
+    // built for them. Node only loads addons whose names end in `.node`.
+    let exe = env::current_exe().expect("test executable");
+    let deps = exe.parent().expect("target directory");
+    let library = deps.join(if cfg!(target_os = "macos") {
+        "libmentat_node.dylib"
+    } else {
+        "libmentat_node.so"
+    });
+    assert!(library.exists(), "{} wasn't built", library.display());
+
+    let addon = deps.join("mentat_node_module").join("mentat.node");
+    fs::create_dir_all(addon.parent().unwrap()).expect("module directory");
+    fs::copy(&library, &addon).expect("copied the addon");
+
+    let node = env::var("NODE").unwrap_or_else(|_| "node".to_string());
+    let output = Command::new(&node)
+        .arg("--test")
+        .arg(&tests)
+        .env("MENTAT_NODE_ADDON", &addon)
+        .output()
+        .expect("ran Node");
+    assert!(
+        output.status.success(),
+        "Node tests failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
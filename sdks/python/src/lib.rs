@@ -0,0 +1,221 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Python bindings for Mentat, built with [PyO3](https://pyo3.rs).
+//!
+//! The module is called `mentat`:
+//!
+//! ```python
+//! import mentat
+//!
+//! store = mentat.Store("/path/to/store.db")
+//! names = store.query("[:find [?name ...] :where [_ :person/name ?name]]").coll()
+//! ```
+//!
+//! A `Store` owns its `mentat::Store`. While a transaction begun with `begin_transaction` is
+//! open, the transaction holds the store instead, and the `Store`'s other methods raise
+//! `MentatError`. `Query`s hold on to their store, and use it only while executing.
+//!
+//! Every failure inside Mentat is raised as `MentatError`; values of the wrong Python type
+//! raise `TypeError`.
+
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use mentat::vocabulary::VersionedStore;
+use mentat::{HasSchema, TxObserver};
+
+mod query;
+mod transaction;
+mod values;
+mod vocabulary;
+
+pub use query::Query;
+pub use transaction::{InProgress, TermBuilder, TxReport};
+pub use values::Keyword;
+pub use vocabulary::{Attribute, Definition};
+
+create_exception!(
+    mentat,
+    MentatError,
+    PyException,
+    "An error reported by Mentat."
+);
+
+pub(crate) fn to_py_err<E: Display>(error: E) -> PyErr {
+    MentatError::new_err(error.to_string())
+}
+
+/// Where a `Store` keeps its `mentat::Store`. It's empty while a transaction or a query has the
+/// store.
+pub(crate) type StoreSlot = Rc<Cell<Option<Box<mentat::Store>>>>;
+
+/// Lends the store in `slot` to `f`. Anything that tries to use the store in the meantime,
+/// e.g., a `Store` method called while a transaction is open, fails.
+pub(crate) fn with_store<T, F>(slot: &StoreSlot, f: F) -> PyResult<T>
+where
+    F: FnOnce(&mut mentat::Store) -> PyResult<T>,
+{
+    let mut store = slot
+        .take()
+        .ok_or_else(|| MentatError::new_err("the store is in use by a transaction"))?;
+    let result = f(&mut store);
+    slot.set(Some(store));
+    result
+}
+
+/// A Mentat store: a SQLite database. Opening `""` makes an in-memory store.
+#[pyclass(unsendable, module = "mentat")]
+pub struct Store {
+    slot: StoreSlot,
+}
+
+#[pymethods]
+impl Store {
+    #[new]
+    #[pyo3(signature = (path = ""))]
+    fn new(path: &str) -> PyResult<Self> {
+        let store = mentat::Store::open(path).map_err(to_py_err)?;
+        Ok(Store {
+            slot: Rc::new(Cell::new(Some(Box::new(store)))),
+        })
+    }
+
+    /// Transacts an EDN string, committing it straight away.
+    fn transact(&self, transaction: &str) -> PyResult<TxReport> {
+        with_store(&self.slot, |store| {
+            store
+                .transact(transaction)
+                .map(TxReport::from)
+                .map_err(to_py_err)
+        })
+    }
+
+    /// Transacts the terms in `builder`, committing them straight away.
+    fn transact_builder(&self, py: Python<'_>, builder: &TermBuilder) -> PyResult<TxReport> {
+        with_store(&self.slot, |store| {
+            let mut in_progress = store.begin_transaction().map_err(to_py_err)?;
+            let terms = builder.build(py, &in_progress.schema)?;
+            let report = in_progress.transact_builder(terms).map_err(to_py_err)?;
+            in_progress.commit().map_err(to_py_err)?;
+            Ok(TxReport::from(report))
+        })
+    }
+
+    /// Begins a transaction. Use it as a context manager to commit it when the block ends, or
+    /// to roll it back if the block raises.
+    fn begin_transaction(&self) -> PyResult<InProgress> {
+        InProgress::begin(self.slot.clone())
+    }
+
+    /// Makes a `Query` to bind values to and then execute.
+    fn query(&self, query: String) -> Query {
+        Query::new(self.slot.clone(), query)
+    }
+
+    /// Makes sure that the vocabulary described by `definition` is installed, returning
+    /// `"installed"`, `"installed_missing_attributes"`, `"existed"` or `"upgraded"`.
+    fn ensure_vocabulary(&self, definition: &Definition) -> PyResult<&'static str> {
+        with_store(&self.slot, |store| {
+            let mut in_progress = store.begin_transaction().map_err(to_py_err)?;
+            let outcome = in_progress
+                .ensure_vocabulary(&definition.definition)
+                .map_err(to_py_err)?;
+            in_progress.commit().map_err(to_py_err)?;
+            Ok(vocabulary::outcome_name(&outcome))
+        })
+    }
+
+    /// The entid of `ident`, or `None` if there isn't one.
+    fn entid(&self, ident: &Bound<'_, PyAny>) -> PyResult<Option<i64>> {
+        let ident = values::extract_keyword(ident)?;
+        with_store(&self.slot, |store| {
+            Ok(store.conn().current_schema().get_entid(&ident).map(|e| e.0))
+        })
+    }
+
+    /// Calls `callback(key, changes)` after each committed transaction that touches any of
+    /// `attributes`. `changes` maps each transaction's id to the entids of the attributes it
+    /// touched.
+    ///
+    /// Callbacks run on another thread, once that thread can take the GIL.
+    fn register_observer(
+        &self,
+        key: String,
+        attributes: &Bound<'_, PyAny>,
+        callback: PyObject,
+    ) -> PyResult<()> {
+        let attributes = attributes
+            .try_iter()?
+            .map(|attribute| values::extract_keyword(&attribute?))
+            .collect::<PyResult<Vec<_>>>()?;
+        with_store(&self.slot, |store| {
+            let schema = store.conn().current_schema();
+            let attributes = attributes
+                .iter()
+                .map(|attribute| {
+                    schema
+                        .get_entid(attribute)
+                        .map(|entid| entid.0)
+                        .ok_or_else(|| {
+                            MentatError::new_err(format!("no such attribute: {}", attribute))
+                        })
+                })
+                .collect::<PyResult<BTreeSet<_>>>()?;
+            let observer = TxObserver::new(attributes, move |key, batch| {
+                Python::with_gil(|py| {
+                    let notify = || -> PyResult<()> {
+                        let changes = PyDict::new(py);
+                        for (tx_id, attributes) in batch {
+                            changes.set_item(tx_id, PyList::new(py, attributes.iter())?)?;
+                        }
+                        callback.call1(py, (key, changes))?;
+                        Ok(())
+                    };
+                    if let Err(e) = notify() {
+                        e.write_unraisable(py, Some(callback.bind(py)));
+                    }
+                })
+            });
+            store.register_observer(key, Arc::new(observer));
+            Ok(())
+        })
+    }
+
+    fn unregister_observer(&self, key: &str) -> PyResult<()> {
+        with_store(&self.slot, |store| {
+            store.unregister_observer(key);
+            Ok(())
+        })
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "mentat")]
+fn mentat_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("MentatError", m.py().get_type::<MentatError>())?;
+    m.add_class::<Store>()?;
+    m.add_class::<Query>()?;
+    m.add_class::<InProgress>()?;
+    m.add_class::<TermBuilder>()?;
+    m.add_class::<TxReport>()?;
+    m.add_class::<Keyword>()?;
+    m.add_class::<Attribute>()?;
+    m.add_class::<Definition>()?;
+    Ok(())
+}
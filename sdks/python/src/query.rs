@@ -0,0 +1,156 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyInt, PyList};
+use pyo3::IntoPyObjectExt;
+
+use mentat::{Binding, HasSchema, QueryBuilder, QueryResults, RelResult, TypedValue};
+
+use crate::values::{binding_to_py, bindings_to_tuple, extract_keyword, to_typed_value};
+use crate::{to_py_err, with_store, MentatError, StoreSlot};
+
+/// A query, and the values bound to its inputs. Binding methods return the query, so that
+/// they can be chained:
+///
+/// ```python
+/// store.query("[:find ?e . :in ?name :where [?e :person/name ?name]]").bind("?name", "Alice").scalar()
+/// ```
+#[pyclass(unsendable, module = "mentat")]
+pub struct Query {
+    store: StoreSlot,
+    query: String,
+    values: Vec<(String, TypedValue)>,
+}
+
+impl Query {
+    pub(crate) fn new(store: StoreSlot, query: String) -> Query {
+        Query {
+            store,
+            query,
+            values: vec![],
+        }
+    }
+
+    fn run<T, F>(&self, f: F) -> PyResult<T>
+    where
+        F: FnOnce(&mut QueryBuilder<'_>) -> mentat::Result<T>,
+    {
+        with_store(&self.store, |store| {
+            let mut builder = QueryBuilder::new(store, self.query.as_str());
+            for (var, value) in &self.values {
+                builder.bind_value(var, value.clone());
+            }
+            f(&mut builder).map_err(to_py_err)
+        })
+    }
+}
+
+#[pymethods]
+impl Query {
+    /// Binds `var` to `value`, whose Mentat type follows from its Python type. Use `bind_ref`
+    /// to bind an entity.
+    fn bind<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        var: String,
+        value: &Bound<'py, PyAny>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let value = to_typed_value(value)?;
+        slf.values.push((var, value));
+        Ok(slf)
+    }
+
+    /// Binds `var` to an entity, given as an entid or as an ident.
+    fn bind_ref<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        var: String,
+        entity: &Bound<'py, PyAny>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let entid = if entity.is_instance_of::<PyInt>() {
+            entity.extract()?
+        } else {
+            let ident = extract_keyword(entity)
+                .map_err(|_| PyTypeError::new_err("expected an entid or an ident to bind_ref"))?;
+            with_store(&slf.store, |store| {
+                store
+                    .conn()
+                    .current_schema()
+                    .get_entid(&ident)
+                    .map(|entid| entid.0)
+                    .ok_or_else(|| MentatError::new_err(format!("no such ident: {}", ident)))
+            })?
+        };
+        slf.values.push((var, TypedValue::Ref(entid)));
+        Ok(slf)
+    }
+
+    /// Runs the query, returning whatever shape of result its find spec asks for: a value or
+    /// `None` for `.`, a list for `[?x ...]`, a tuple or `None` for `[?x ?y]`, and a list of
+    /// tuples otherwise.
+    fn execute(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self.run(|builder| builder.execute())?.results {
+            QueryResults::Scalar(binding) => match binding {
+                Some(binding) => binding_to_py(py, binding),
+                None => Ok(py.None()),
+            },
+            QueryResults::Tuple(bindings) => match bindings {
+                Some(bindings) => bindings_to_tuple(py, bindings),
+                None => Ok(py.None()),
+            },
+            QueryResults::Coll(bindings) => coll_to_py(py, bindings),
+            QueryResults::Rel(rel) => rel_to_py(py, rel),
+        }
+    }
+
+    /// Runs a `[:find ?x . …]` query.
+    fn scalar(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self.run(|builder| builder.execute_scalar())? {
+            Some(binding) => binding_to_py(py, binding),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Runs a `[:find [?x ...] …]` query.
+    fn coll(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let bindings = self.run(|builder| builder.execute_coll())?;
+        coll_to_py(py, bindings)
+    }
+
+    /// Runs a `[:find [?x ?y] …]` query.
+    fn tuple(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self.run(|builder| builder.execute_tuple())? {
+            Some(bindings) => bindings_to_tuple(py, bindings),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Runs a `[:find ?x ?y …]` query, returning a list of tuples.
+    fn rel(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let rel = self.run(|builder| builder.execute_rel())?;
+        rel_to_py(py, rel)
+    }
+}
+
+fn coll_to_py(py: Python<'_>, bindings: Vec<Binding>) -> PyResult<PyObject> {
+    let list = PyList::empty(py);
+    for binding in bindings {
+        list.append(binding_to_py(py, binding)?)?;
+    }
+    list.into_py_any(py)
+}
+
+fn rel_to_py(py: Python<'_>, rel: RelResult<Binding>) -> PyResult<PyObject> {
+    let rows = PyList::empty(py);
+    for row in rel {
+        rows.append(bindings_to_tuple(py, row)?)?;
+    }
+    rows.into_py_any(py)
+}
@@ -0,0 +1,334 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use std::collections::BTreeMap;
+use std::ptr;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyInt, PyString, PyTuple};
+
+use mentat::edn::entities::{EntityPlace, LookupRef, OpType, ValuePlace};
+use mentat::entity_builder::{self, BuildTerms};
+use mentat::vocabulary::VersionedStore;
+use mentat::{DateTime, Entid, HasSchema, KnownEntid, Schema, TypedValue, Utc, ValueType};
+
+use crate::values::{extract_keyword, to_typed_value_of_type, Keyword};
+use crate::vocabulary::{outcome_name, Definition};
+use crate::{to_py_err, MentatError, StoreSlot};
+
+/// What a transaction did.
+#[pyclass(frozen, module = "mentat")]
+pub struct TxReport {
+    #[pyo3(get)]
+    tx_id: Entid,
+    #[pyo3(get)]
+    tx_instant: DateTime<Utc>,
+    /// Maps each named tempid to the entid it resolved to.
+    #[pyo3(get)]
+    tempids: BTreeMap<String, Entid>,
+}
+
+impl From<mentat::TxReport> for TxReport {
+    fn from(report: mentat::TxReport) -> TxReport {
+        TxReport {
+            tx_id: report.tx_id,
+            tx_instant: report.tx_instant,
+            tempids: report.tempids,
+        }
+    }
+}
+
+#[pymethods]
+impl TxReport {
+    fn __repr__(&self) -> String {
+        format!("TxReport(tx_id={}, tempids={:?})", self.tx_id, self.tempids)
+    }
+}
+
+struct Term {
+    op: OpType,
+    e: PyObject,
+    a: mentat::Keyword,
+    v: PyObject,
+}
+
+/// Assertions and retractions to transact with `Store.transact_builder` or
+/// `InProgress.transact_builder`.
+///
+/// An entity is an entid, an ident `Keyword`, a string naming a tempid, or an
+/// `(attribute, value)` lookup ref. A value is converted to the type of its attribute; the
+/// value of a ref attribute can be anything that names an entity.
+#[pyclass(module = "mentat")]
+#[derive(Default)]
+pub struct TermBuilder {
+    terms: Vec<Term>,
+}
+
+impl TermBuilder {
+    fn push(
+        &mut self,
+        op: OpType,
+        entity: &Bound<'_, PyAny>,
+        attribute: &Bound<'_, PyAny>,
+        value: &Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        self.terms.push(Term {
+            op,
+            e: entity.clone().unbind(),
+            a: extract_keyword(attribute)?,
+            v: value.clone().unbind(),
+        });
+        Ok(())
+    }
+
+    /// Converts the terms into something Mentat can transact, typing values according to
+    /// `schema`.
+    pub(crate) fn build(
+        &self,
+        py: Python<'_>,
+        schema: &Schema,
+    ) -> PyResult<entity_builder::TermBuilder> {
+        let mut builder = entity_builder::TermBuilder::new();
+        for term in &self.terms {
+            let e = entity_place(&mut builder, term.e.bind(py), schema)?;
+            let value_type = attribute_type(schema, &term.a)?;
+            let v = value_place(&mut builder, term.v.bind(py), value_type, schema)?;
+            match term.op {
+                OpType::Add => builder.add(e, term.a.clone(), v),
+                OpType::Retract => builder.retract(e, term.a.clone(), v),
+            }
+            .map_err(to_py_err)?;
+        }
+        Ok(builder)
+    }
+}
+
+#[pymethods]
+impl TermBuilder {
+    #[new]
+    fn new() -> Self {
+        TermBuilder::default()
+    }
+
+    fn add(
+        &mut self,
+        entity: &Bound<'_, PyAny>,
+        attribute: &Bound<'_, PyAny>,
+        value: &Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        self.push(OpType::Add, entity, attribute, value)
+    }
+
+    fn retract(
+        &mut self,
+        entity: &Bound<'_, PyAny>,
+        attribute: &Bound<'_, PyAny>,
+        value: &Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        self.push(OpType::Retract, entity, attribute, value)
+    }
+
+    fn __len__(&self) -> usize {
+        self.terms.len()
+    }
+}
+
+fn attribute_type(schema: &Schema, attribute: &mentat::Keyword) -> PyResult<ValueType> {
+    schema
+        .attribute_for_ident(attribute)
+        .map(|(attribute, _)| attribute.value_type)
+        .ok_or_else(|| MentatError::new_err(format!("no such attribute: {}", attribute)))
+}
+
+fn lookup_ref(
+    value: &Bound<'_, PyTuple>,
+    schema: &Schema,
+) -> PyResult<Option<LookupRef<TypedValue>>> {
+    if value.len() != 2 {
+        return Ok(None);
+    }
+    let a = extract_keyword(&value.get_item(0)?)?;
+    let v = value.get_item(1)?;
+    let v = match attribute_type(schema, &a)? {
+        ValueType::Ref if v.is_instance_of::<PyInt>() => TypedValue::Ref(v.extract()?),
+        value_type => to_typed_value_of_type(&v, value_type)?,
+    };
+    Ok(Some(LookupRef { a: a.into(), v }))
+}
+
+fn not_an_entity(value: &Bound<'_, PyAny>) -> PyErr {
+    PyTypeError::new_err(format!(
+        "{} doesn't name an entity",
+        value
+            .repr()
+            .map(|repr| repr.to_string())
+            .unwrap_or_default()
+    ))
+}
+
+fn entity_place(
+    builder: &mut entity_builder::TermBuilder,
+    value: &Bound<'_, PyAny>,
+    schema: &Schema,
+) -> PyResult<EntityPlace<TypedValue>> {
+    if value.is_instance_of::<PyInt>() && !value.is_instance_of::<PyBool>() {
+        Ok(KnownEntid(value.extract()?).into())
+    } else if let Ok(tempid) = value.downcast::<PyString>() {
+        Ok(builder.named_tempid(tempid.to_str()?).into())
+    } else if let Ok(ident) = value.downcast::<Keyword>() {
+        Ok(ident.get().0.clone().into())
+    } else if let Ok(tuple) = value.downcast::<PyTuple>() {
+        lookup_ref(tuple, schema)?
+            .map(EntityPlace::LookupRef)
+            .ok_or_else(|| not_an_entity(value))
+    } else {
+        Err(not_an_entity(value))
+    }
+}
+
+fn value_place(
+    builder: &mut entity_builder::TermBuilder,
+    value: &Bound<'_, PyAny>,
+    value_type: ValueType,
+    schema: &Schema,
+) -> PyResult<ValuePlace<TypedValue>> {
+    if value_type != ValueType::Ref {
+        return to_typed_value_of_type(value, value_type).map(ValuePlace::Atom);
+    }
+    match entity_place(builder, value, schema)? {
+        EntityPlace::Entid(entid) => Ok(entid.into()),
+        EntityPlace::TempId(tempid) => Ok(tempid.into()),
+        EntityPlace::LookupRef(lookup_ref) => Ok(lookup_ref.into()),
+        EntityPlace::TxFunction(_) => Err(not_an_entity(value)),
+    }
+}
+
+/// An open transaction. Its changes are visible to nobody else until it commits.
+///
+/// While it's open, the transaction has its `Store`'s `mentat::Store`, and gives it back once
+/// it commits, rolls back, or is dropped (which rolls back).
+#[pyclass(unsendable, module = "mentat")]
+pub struct InProgress {
+    // This borrows from `store`, so it must be dropped first.
+    in_progress: Option<mentat::InProgress<'static, 'static>>,
+    store: *mut mentat::Store,
+    slot: StoreSlot,
+}
+
+impl InProgress {
+    pub(crate) fn begin(slot: StoreSlot) -> PyResult<InProgress> {
+        let store = slot
+            .take()
+            .ok_or_else(|| MentatError::new_err("the store is in use by a transaction"))?;
+        let store = Box::into_raw(store);
+        // The store won't move or be touched until `give_back`, which first drops this.
+        match unsafe { (*store).begin_transaction() } {
+            Ok(in_progress) => Ok(InProgress {
+                in_progress: Some(in_progress),
+                store,
+                slot,
+            }),
+            Err(e) => {
+                slot.set(Some(unsafe { Box::from_raw(store) }));
+                Err(to_py_err(e))
+            }
+        }
+    }
+
+    fn in_progress(&mut self) -> PyResult<&mut mentat::InProgress<'static, 'static>> {
+        self.in_progress
+            .as_mut()
+            .ok_or_else(|| MentatError::new_err("the transaction has finished"))
+    }
+
+    fn finish(&mut self, commit: bool) -> PyResult<()> {
+        let in_progress = self
+            .in_progress
+            .take()
+            .ok_or_else(|| MentatError::new_err("the transaction has finished"))?;
+        let result = if commit {
+            in_progress.commit()
+        } else {
+            in_progress.rollback()
+        };
+        self.give_back();
+        result.map_err(to_py_err)
+    }
+
+    fn give_back(&mut self) {
+        self.in_progress = None;
+        if !self.store.is_null() {
+            self.slot.set(Some(unsafe { Box::from_raw(self.store) }));
+            self.store = ptr::null_mut();
+        }
+    }
+}
+
+impl Drop for InProgress {
+    fn drop(&mut self) {
+        self.give_back();
+    }
+}
+
+#[pymethods]
+impl InProgress {
+    /// Transacts an EDN string.
+    fn transact(&mut self, transaction: &str) -> PyResult<TxReport> {
+        self.in_progress()?
+            .transact(transaction)
+            .map(TxReport::from)
+            .map_err(to_py_err)
+    }
+
+    /// Transacts the terms in `builder`.
+    fn transact_builder(&mut self, py: Python<'_>, builder: &TermBuilder) -> PyResult<TxReport> {
+        let in_progress = self.in_progress()?;
+        let terms = builder.build(py, &in_progress.schema)?;
+        in_progress
+            .transact_builder(terms)
+            .map(TxReport::from)
+            .map_err(to_py_err)
+    }
+
+    /// Like `Store.ensure_vocabulary`, but within this transaction.
+    fn ensure_vocabulary(&mut self, definition: &Definition) -> PyResult<&'static str> {
+        self.in_progress()?
+            .ensure_vocabulary(&definition.definition)
+            .map(|outcome| outcome_name(&outcome))
+            .map_err(to_py_err)
+    }
+
+    fn commit(&mut self) -> PyResult<()> {
+        self.finish(true)
+    }
+
+    fn rollback(&mut self) -> PyResult<()> {
+        self.finish(false)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Commits, unless the block raised, in which case rolls back. Either way, the exception
+    /// isn't swallowed.
+    fn __exit__(
+        &mut self,
+        exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> PyResult<bool> {
+        if self.in_progress.is_some() {
+            self.finish(exc_type.is_none())?;
+        }
+        Ok(false)
+    }
+}
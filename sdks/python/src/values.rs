@@ -0,0 +1,242 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Moving values between Python and Mentat.
+//!
+//! Results come out as the obvious Python types: entids and longs as `int`, instants as
+//! timezone-aware `datetime`s, UUIDs as `uuid.UUID`, keywords as `mentat.Keyword`, pulled
+//! entities as `dict`s keyed by `Keyword`, and so on.
+//!
+//! Going the other way we usually know the type that Mentat wants, because we know the
+//! attribute being transacted; `to_typed_value` does its best when we don't.
+
+use chrono::{DateTime, FixedOffset, Utc};
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use pyo3::IntoPyObjectExt;
+
+use mentat::edn;
+use mentat::{Binding, TypedValue, Uuid, ValueType};
+
+/// An EDN keyword, like `:person/name`.
+#[pyclass(frozen, eq, ord, hash, module = "mentat")]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Keyword(pub edn::Keyword);
+
+#[pymethods]
+impl Keyword {
+    #[new]
+    fn new(keyword: &str) -> PyResult<Self> {
+        parse_keyword(keyword).map(Keyword)
+    }
+
+    /// The namespace, or `None` for a plain keyword such as `:foo`.
+    #[getter]
+    fn namespace(&self) -> Option<&str> {
+        self.0.namespace()
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Keyword('{}')", self.0)
+    }
+}
+
+fn parse_keyword(keyword: &str) -> PyResult<edn::Keyword> {
+    match edn::parse::value(keyword).map(|v| v.without_spans()) {
+        Ok(edn::Value::Keyword(keyword)) => Ok(keyword),
+        _ => Err(PyValueError::new_err(format!(
+            "'{}' isn't a keyword",
+            keyword
+        ))),
+    }
+}
+
+/// Accepts either a `Keyword` or its string form.
+pub fn extract_keyword(value: &Bound<'_, PyAny>) -> PyResult<edn::Keyword> {
+    if let Ok(keyword) = value.downcast::<Keyword>() {
+        Ok(keyword.get().0.clone())
+    } else if let Ok(s) = value.downcast::<PyString>() {
+        parse_keyword(s.to_str()?)
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "expected a keyword, got {}",
+            value.get_type().name()?
+        )))
+    }
+}
+
+/// Accepts a `:db.type/*` keyword, or just its name: `"long"`, `"string"`, and so on.
+pub fn extract_value_type(value: &Bound<'_, PyAny>) -> PyResult<ValueType> {
+    let keyword = match value.downcast::<PyString>() {
+        Ok(s) if !s.to_str()?.starts_with(':') => edn::Keyword::namespaced("db.type", s.to_str()?),
+        _ => extract_keyword(value)?,
+    };
+    ValueType::from_keyword(&keyword)
+        .ok_or_else(|| PyValueError::new_err(format!("{} isn't a value type", keyword)))
+}
+
+fn uuid_class<'py>(py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    py.import("uuid")?.getattr("UUID")
+}
+
+fn extract_uuid(value: &Bound<'_, PyAny>) -> PyResult<Option<Uuid>> {
+    if !value.is_instance(&uuid_class(value.py())?)? {
+        return Ok(None);
+    }
+    let bytes = value.getattr("bytes")?;
+    Uuid::from_slice(bytes.downcast::<PyBytes>()?.as_bytes())
+        .map(Some)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn extract_instant(value: &Bound<'_, PyAny>) -> PyResult<TypedValue> {
+    let instant: DateTime<FixedOffset> = value.extract()?;
+    Ok(instant.with_timezone(&Utc).into())
+}
+
+fn type_error(value: &Bound<'_, PyAny>, value_type: ValueType) -> PyErr {
+    let type_name = value
+        .get_type()
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_default();
+    PyTypeError::new_err(format!(
+        "can't use a {} as a {}",
+        type_name,
+        value_type.into_keyword()
+    ))
+}
+
+/// Converts `value` to a `TypedValue`, guessing its type from its Python type. There's no
+/// way to guess a ref: an `int` becomes a long.
+pub fn to_typed_value(value: &Bound<'_, PyAny>) -> PyResult<TypedValue> {
+    // `bool` is a subclass of `int`, so check for it first.
+    if let Ok(b) = value.downcast::<PyBool>() {
+        Ok(TypedValue::Boolean(b.is_true()))
+    } else if value.is_instance_of::<PyInt>() {
+        Ok(TypedValue::Long(value.extract()?))
+    } else if let Ok(f) = value.downcast::<PyFloat>() {
+        Ok(TypedValue::Double(f.value().into()))
+    } else if let Ok(s) = value.downcast::<PyString>() {
+        Ok(TypedValue::typed_string(s.to_str()?))
+    } else if let Ok(keyword) = value.downcast::<Keyword>() {
+        Ok(TypedValue::Keyword(keyword.get().0.clone().into()))
+    } else if let Ok(bytes) = value.downcast::<PyBytes>() {
+        Ok(TypedValue::Bytes(bytes.as_bytes().to_vec().into()))
+    } else if let Some(uuid) = extract_uuid(value)? {
+        Ok(TypedValue::Uuid(uuid))
+    } else {
+        extract_instant(value).map_err(|_| {
+            PyTypeError::new_err(format!(
+                "can't use a {} as a Mentat value",
+                value
+                    .get_type()
+                    .name()
+                    .map(|name| name.to_string())
+                    .unwrap_or_default()
+            ))
+        })
+    }
+}
+
+/// Converts `value` to a `TypedValue` of the given type. Refs aren't handled here: they can
+/// name entities in ways that only make sense while transacting.
+pub fn to_typed_value_of_type(
+    value: &Bound<'_, PyAny>,
+    value_type: ValueType,
+) -> PyResult<TypedValue> {
+    let converted = match value_type {
+        ValueType::Boolean => value
+            .downcast::<PyBool>()
+            .ok()
+            .map(|b| TypedValue::Boolean(b.is_true())),
+        ValueType::Long if !value.is_instance_of::<PyBool>() => {
+            value.extract::<i64>().ok().map(TypedValue::Long)
+        }
+        ValueType::Double if !value.is_instance_of::<PyBool>() => value
+            .extract::<f64>()
+            .ok()
+            .map(|f| TypedValue::Double(f.into())),
+        ValueType::Instant => extract_instant(value).ok(),
+        ValueType::String => value
+            .downcast::<PyString>()
+            .ok()
+            .and_then(|s| s.to_str().ok().map(TypedValue::typed_string)),
+        ValueType::Keyword => extract_keyword(value)
+            .ok()
+            .map(|keyword| TypedValue::Keyword(keyword.into())),
+        ValueType::Uuid => extract_uuid(value)?.map(TypedValue::Uuid),
+        ValueType::Bytes => value
+            .downcast::<PyBytes>()
+            .ok()
+            .map(|bytes| TypedValue::Bytes(bytes.as_bytes().to_vec().into())),
+        ValueType::Ref | ValueType::Long | ValueType::Double => None,
+    };
+    converted.ok_or_else(|| type_error(value, value_type))
+}
+
+pub fn typed_value_to_py(py: Python<'_>, value: TypedValue) -> PyResult<PyObject> {
+    match value {
+        TypedValue::Ref(entid) => entid.into_py_any(py),
+        TypedValue::Boolean(b) => b.into_py_any(py),
+        TypedValue::Long(l) => l.into_py_any(py),
+        TypedValue::Double(d) => d.into_inner().into_py_any(py),
+        TypedValue::Instant(instant) => instant.into_py_any(py),
+        TypedValue::String(s) => s.as_str().into_py_any(py),
+        TypedValue::Keyword(keyword) => Keyword((*keyword).clone()).into_py_any(py),
+        TypedValue::Uuid(uuid) => Ok(uuid_class(py)?
+            .call1((uuid.to_hyphenated().to_string(),))?
+            .unbind()),
+        TypedValue::Bytes(bytes) => PyBytes::new(py, &bytes).into_py_any(py),
+    }
+}
+
+pub fn binding_to_py(py: Python<'_>, binding: Binding) -> PyResult<PyObject> {
+    match binding {
+        Binding::Scalar(value) => typed_value_to_py(py, value),
+        Binding::Vec(values) => {
+            let list = PyList::empty(py);
+            for value in values.iter() {
+                list.append(binding_to_py(py, value.clone())?)?;
+            }
+            list.into_py_any(py)
+        }
+        Binding::Map(map) => {
+            let dict = PyDict::new(py);
+            for (keyword, value) in map.iter() {
+                dict.set_item(
+                    Keyword((**keyword).clone()),
+                    binding_to_py(py, value.clone())?,
+                )?;
+            }
+            dict.into_py_any(py)
+        }
+        Binding::Nil => Ok(py.None()),
+    }
+}
+
+pub fn bindings_to_tuple(py: Python<'_>, bindings: Vec<Binding>) -> PyResult<PyObject> {
+    let values = bindings
+        .into_iter()
+        .map(|binding| binding_to_py(py, binding))
+        .collect::<PyResult<Vec<_>>>()?;
+    PyTuple::new(py, values)?.into_py_any(py)
+}
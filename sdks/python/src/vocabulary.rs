@@ -0,0 +1,129 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+
+use mentat::attribute::Unique;
+use mentat::vocabulary::{self, AttributeBuilder, VocabularyOutcome};
+
+use crate::values::{extract_keyword, extract_value_type};
+
+/// An attribute in a vocabulary `Definition`, e.g.,
+/// `Attribute("string", unique="identity")`. The value type is a `:db.type/*` keyword or just
+/// its name. Identity and fulltext attributes are indexed.
+#[pyclass(frozen, module = "mentat")]
+#[derive(Clone)]
+pub struct Attribute {
+    attribute: mentat::Attribute,
+}
+
+#[pymethods]
+impl Attribute {
+    #[new]
+    #[pyo3(signature = (
+        value_type,
+        *,
+        multival = false,
+        unique = None,
+        index = false,
+        fulltext = false,
+        component = false,
+        no_history = false
+    ))]
+    fn new(
+        value_type: &Bound<'_, PyAny>,
+        multival: bool,
+        unique: Option<&str>,
+        index: bool,
+        fulltext: bool,
+        component: bool,
+        no_history: bool,
+    ) -> PyResult<Self> {
+        let mut builder = AttributeBuilder::helpful();
+        builder
+            .value_type(extract_value_type(value_type)?)
+            .multival(multival)
+            .fulltext(fulltext)
+            .component(component)
+            .no_history(no_history);
+        match unique {
+            None => {}
+            Some("value") => {
+                builder.unique(Unique::Value);
+            }
+            Some("identity") => {
+                builder.unique(Unique::Identity);
+            }
+            Some(other) => {
+                return Err(PyValueError::new_err(format!(
+                    "unique must be \"value\" or \"identity\", not \"{}\"",
+                    other
+                )))
+            }
+        }
+        if index {
+            builder.index(true);
+        }
+        Ok(Attribute {
+            attribute: builder.build(),
+        })
+    }
+}
+
+/// A named, versioned vocabulary: its attributes are given as a dict, or a list of pairs,
+/// from ident to `Attribute`.
+#[pyclass(frozen, module = "mentat")]
+pub struct Definition {
+    pub(crate) definition: vocabulary::Definition,
+}
+
+#[pymethods]
+impl Definition {
+    #[new]
+    fn new(
+        name: &Bound<'_, PyAny>,
+        version: vocabulary::Version,
+        attributes: &Bound<'_, PyAny>,
+    ) -> PyResult<Self> {
+        let pairs = match attributes.downcast::<PyDict>() {
+            Ok(dict) => dict.items().into_any(),
+            Err(_) => attributes.clone(),
+        };
+        let attributes = pairs
+            .try_iter()?
+            .map(|pair| {
+                let pair = pair?;
+                let pair = pair.downcast::<PyTuple>()?;
+                let ident = extract_keyword(&pair.get_item(0)?)?;
+                let attribute = pair.get_item(1)?.downcast::<Attribute>()?.get().clone();
+                Ok((ident, attribute.attribute))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Definition {
+            definition: vocabulary::Definition::new(extract_keyword(name)?, version, attributes),
+        })
+    }
+
+    #[getter]
+    fn version(&self) -> vocabulary::Version {
+        self.definition.version
+    }
+}
+
+pub(crate) fn outcome_name(outcome: &VocabularyOutcome) -> &'static str {
+    match outcome {
+        VocabularyOutcome::Installed => "installed",
+        VocabularyOutcome::InstalledMissingAttributes => "installed_missing_attributes",
+        VocabularyOutcome::Existed => "existed",
+        VocabularyOutcome::Upgraded => "upgraded",
+    }
+}
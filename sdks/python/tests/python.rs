@@ -0,0 +1,53 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Runs `tests/test_mentat.py` against the extension module that was built for this test. Set
+//! `PYO3_PYTHON` to use an interpreter other than `python3`; it should be the one that PyO3
+//! built against.
+
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_python() {
+    let tests = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    // Test executables live in `target/<profile>/deps`, alongside the shared library that was
+    // built for them. Python wants to find it as `mentat.so`.
+    let exe = env::current_exe().expect("test executable");
+    let deps = exe.parent().expect("target directory");
+    let library = deps.join(if cfg!(target_os = "macos") {
+        "libmentat_python.dylib"
+    } else {
+        "libmentat_python.so"
+    });
+    assert!(library.exists(), "{} wasn't built", library.display());
+
+    let module_dir = deps.join("mentat_python_module");
+    fs::create_dir_all(&module_dir).expect("module directory");
+    fs::copy(&library, module_dir.join("mentat.so")).expect("copied the module");
+
+    let python = env::var("PYO3_PYTHON").unwrap_or_else(|_| "python3".to_string());
+    let output = Command::new(&python)
+        .args(["-m", "unittest", "discover", "-s"])
+        .arg(&tests)
+        .env("PYTHONPATH", &module_dir)
+        .output()
+        .expect("ran Python");
+    assert!(
+        output.status.success(),
+        "Python tests failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
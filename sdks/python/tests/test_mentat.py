@@ -0,0 +1,253 @@
+# Copyright 2018 Mozilla
+#
+# Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+# this file except in compliance with the License. You may obtain a copy of the
+# License at http://www.apache.org/licenses/LICENSE-2.0
+# Unless required by applicable law or agreed to in writing, software distributed
+# under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+# CONDITIONS OF ANY KIND, either express or implied. See the License for the
+# specific language governing permissions and limitations under the License.
+
+import datetime
+import threading
+import unittest
+import uuid
+
+from mentat import Attribute, Definition, Keyword, MentatError, Store, TermBuilder
+
+PEOPLE = Definition(":test/people", 1, {
+    ":person/name": Attribute("string", unique="identity"),
+    ":person/age": Attribute("long"),
+    ":person/height": Attribute(":db.type/double"),
+    ":person/born": Attribute("instant"),
+    ":person/id": Attribute("uuid"),
+    ":person/photo": Attribute("bytes"),
+    ":person/admin": Attribute("boolean"),
+    ":person/role": Attribute("keyword"),
+    ":person/friend": Attribute("ref", multival=True),
+})
+
+
+def people():
+    store = Store()
+    store.ensure_vocabulary(PEOPLE)
+    store.transact("""[{:db/id "alice" :person/name "Alice" :person/age 30}
+                       {:db/id "bob" :person/name "Bob" :person/age 40 :person/friend "alice"}]""")
+    return store
+
+
+class TestKeyword(unittest.TestCase):
+    def test_keyword(self):
+        keyword = Keyword(":person/name")
+        self.assertEqual(keyword.namespace, "person")
+        self.assertEqual(keyword.name, "name")
+        self.assertEqual(str(keyword), ":person/name")
+        self.assertEqual(keyword, Keyword(":person/name"))
+        self.assertEqual(len({keyword, Keyword(":person/name")}), 1)
+        self.assertIsNone(Keyword(":plain").namespace)
+        with self.assertRaises(ValueError):
+            Keyword("person/name")
+
+
+class TestVocabulary(unittest.TestCase):
+    def test_ensure(self):
+        store = Store()
+        self.assertEqual(store.ensure_vocabulary(PEOPLE), "installed")
+        self.assertEqual(store.ensure_vocabulary(PEOPLE), "existed")
+        self.assertIsNotNone(store.entid(":person/name"))
+        self.assertIsNone(store.entid(":person/nope"))
+
+    def test_bad_attribute(self):
+        with self.assertRaises(ValueError):
+            Attribute("text")
+        with self.assertRaises(ValueError):
+            Attribute("string", unique="sometimes")
+
+
+class TestQuery(unittest.TestCase):
+    def test_shapes(self):
+        store = people()
+        names = store.query("[:find [?name ...] :where [_ :person/name ?name]]").coll()
+        self.assertEqual(sorted(names), ["Alice", "Bob"])
+
+        age = (store.query("[:find ?age . :in ?name :where [?e :person/name ?name] [?e :person/age ?age]]")
+               .bind("?name", "Bob")
+               .scalar())
+        self.assertEqual(age, 40)
+
+        rows = store.query("[:find ?name ?age :where [?e :person/name ?name] [?e :person/age ?age]]").rel()
+        self.assertEqual(sorted(rows), [("Alice", 30), ("Bob", 40)])
+
+        pair = store.query("[:find [?name ?age] :where [?e :person/name ?name] [?e :person/age ?age] [(> ?age 35)]]").tuple()
+        self.assertEqual(pair, ("Bob", 40))
+
+        self.assertIsNone(store.query("[:find ?e . :where [?e :person/name \"Carol\"]]").execute())
+        self.assertEqual(store.query("[:find ?age . :where [_ :person/age ?age] [(< ?age 35)]]").execute(), 30)
+
+    def test_bind_ref(self):
+        store = people()
+        alice = store.query("[:find ?e . :where [?e :person/name \"Alice\"]]").scalar()
+        friend_of = (store.query("[:find ?name . :in ?friend :where [?e :person/friend ?friend] [?e :person/name ?name]]")
+                     .bind_ref("?friend", alice)
+                     .scalar())
+        self.assertEqual(friend_of, "Bob")
+        ident = store.query("[:find ?ident . :in ?e :where [?e :db/ident ?ident]]").bind_ref("?e", ":person/age").scalar()
+        self.assertEqual(ident, Keyword(":person/age"))
+
+    def test_pull(self):
+        store = people()
+        bob = store.query("[:find (pull ?e [:person/name :person/age]) . :where [?e :person/name \"Bob\"]]").scalar()
+        self.assertEqual(bob, {Keyword(":person/name"): "Bob", Keyword(":person/age"): 40})
+
+    def test_errors(self):
+        store = people()
+        with self.assertRaises(MentatError):
+            store.query("[:find ?e :where").execute()
+        with self.assertRaises(MentatError):
+            store.query("[:find ?e . :where [?e :person/nope _]]").coll()
+        with self.assertRaises(TypeError):
+            store.query("[:find ?e :in ?x :where [?e :person/age ?x]]").bind("?x", object())
+
+
+class TestTransact(unittest.TestCase):
+    def test_values_round_trip(self):
+        store = people()
+        born = datetime.datetime(1990, 5, 17, 12, 30, tzinfo=datetime.timezone.utc)
+        ident = uuid.uuid4()
+        builder = TermBuilder()
+        builder.add("carol", ":person/name", "Carol")
+        builder.add("carol", ":person/height", 1.75)
+        builder.add("carol", ":person/born", born)
+        builder.add("carol", ":person/id", ident)
+        builder.add("carol", ":person/photo", b"\x00\x01\x02")
+        builder.add("carol", ":person/admin", True)
+        builder.add("carol", ":person/role", Keyword(":role/engineer"))
+        builder.add("carol", ":person/friend", (":person/name", "Alice"))
+        builder.add("carol", ":person/friend", "dave")
+        builder.add("dave", ":person/name", "Dave")
+        self.assertEqual(len(builder), 10)
+        report = store.transact_builder(builder)
+        self.assertEqual(sorted(report.tempids), ["carol", "dave"])
+        self.assertIsInstance(report.tx_instant, datetime.datetime)
+
+        carol = report.tempids["carol"]
+        row = (store.query("""[:find [?height ?born ?id ?photo ?admin ?role]
+                               :in ?e
+                               :where [?e :person/height ?height] [?e :person/born ?born]
+                                      [?e :person/id ?id] [?e :person/photo ?photo]
+                                      [?e :person/admin ?admin] [?e :person/role ?role]]""")
+               .bind_ref("?e", carol)
+               .tuple())
+        self.assertEqual(row, (1.75, born, ident, b"\x00\x01\x02", True, Keyword(":role/engineer")))
+
+        friends = (store.query("[:find [?name ...] :in ?e :where [?e :person/friend ?f] [?f :person/name ?name]]")
+                   .bind_ref("?e", carol)
+                   .coll())
+        self.assertEqual(sorted(friends), ["Alice", "Dave"])
+
+    def test_bad_values(self):
+        store = people()
+        builder = TermBuilder()
+        builder.add("x", ":person/age", "forty")
+        with self.assertRaises(TypeError):
+            store.transact_builder(builder)
+
+        builder = TermBuilder()
+        builder.add("x", ":person/nope", 1)
+        with self.assertRaises(MentatError):
+            store.transact_builder(builder)
+
+        builder = TermBuilder()
+        builder.add("x", ":person/born", datetime.datetime(1990, 5, 17))
+        with self.assertRaises(TypeError):
+            store.transact_builder(builder)
+
+    def test_retract(self):
+        store = people()
+        builder = TermBuilder()
+        builder.retract((":person/name", "Bob"), ":person/age", 40)
+        store.transact_builder(builder)
+        self.assertIsNone(store.query("[:find ?age . :where [?e :person/name \"Bob\"] [?e :person/age ?age]]").scalar())
+
+
+class TestInProgress(unittest.TestCase):
+    def count(self, store):
+        return store.query("[:find (count ?e) . :where [?e :person/name _]]").scalar()
+
+    def test_commit(self):
+        store = people()
+        with store.begin_transaction() as in_progress:
+            in_progress.transact("[{:person/name \"Carol\"}]")
+            # The store is lent to the transaction until it's done.
+            with self.assertRaises(MentatError):
+                self.count(store)
+        self.assertEqual(self.count(store), 3)
+
+    def test_rollback_on_exception(self):
+        store = people()
+        with self.assertRaises(RuntimeError):
+            with store.begin_transaction() as in_progress:
+                in_progress.transact("[{:person/name \"Carol\"}]")
+                raise RuntimeError("never mind")
+        self.assertEqual(self.count(store), 2)
+
+    def test_explicit(self):
+        store = people()
+        in_progress = store.begin_transaction()
+        builder = TermBuilder()
+        builder.add("carol", ":person/name", "Carol")
+        in_progress.transact_builder(builder)
+        in_progress.rollback()
+        with self.assertRaises(MentatError):
+            in_progress.commit()
+        self.assertEqual(self.count(store), 2)
+
+        in_progress = store.begin_transaction()
+        in_progress.transact_builder(builder)
+        in_progress.commit()
+        self.assertEqual(self.count(store), 3)
+
+    def test_dropped(self):
+        store = people()
+        in_progress = store.begin_transaction()
+        in_progress.transact("[{:person/name \"Carol\"}]")
+        del in_progress
+        self.assertEqual(self.count(store), 2)
+
+    def test_vocabulary(self):
+        store = Store()
+        with store.begin_transaction() as in_progress:
+            self.assertEqual(in_progress.ensure_vocabulary(PEOPLE), "installed")
+        self.assertEqual(store.ensure_vocabulary(PEOPLE), "existed")
+
+
+class TestObservers(unittest.TestCase):
+    def test_observer(self):
+        store = people()
+        seen = []
+        done = threading.Event()
+
+        def observe(key, changes):
+            seen.append((key, changes))
+            done.set()
+
+        store.register_observer("ages", [":person/age"], observe)
+        store.transact("[{:person/name \"Carol\"}]")
+        report = store.transact("[{:person/name \"Dave\" :person/age 20}]")
+        self.assertTrue(done.wait(10))
+        # Only the second transaction touched an age.
+        self.assertEqual(len(seen), 1)
+        key, changes = seen[0]
+        self.assertEqual(key, "ages")
+        self.assertEqual(list(changes), [report.tx_id])
+        self.assertIn(store.entid(":person/age"), changes[report.tx_id])
+        store.unregister_observer("ages")
+
+    def test_unknown_attribute(self):
+        store = people()
+        with self.assertRaises(MentatError):
+            store.register_observer("nope", [":person/nope"], lambda key, changes: None)
+
+
+if __name__ == "__main__":
+    unittest.main()
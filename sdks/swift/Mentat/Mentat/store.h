@@ -35,6 +35,10 @@ struct QueryRowIterator; // Note: a `mentat::BindingIterator`
 struct Store;
 struct TxReport;
 struct TypedValue; // Note: a `mentat::Binding`
+struct StructuredMap; // Note: a `mentat::StructuredMap`, the result of a pull
+struct AttributeBuilder; // Note: a `mentat::vocabulary::AttributeBuilder`
+struct VocabularyDefinition; // Note: a `mentat::vocabulary::Definition`
+struct TempIdIterator;
 
 
 /*
@@ -87,6 +91,20 @@ struct InProgressTransactResult {
 };
 typedef struct InProgressTransactResult InProgressTransactResult;
 
+// A `:db.type/bytes` value, released with `byte_buffer_destroy`.
+struct ByteBuffer {
+    uint8_t* _Nonnull data;
+    uint64_t len;
+};
+typedef struct ByteBuffer ByteBuffer;
+
+// A tempid and the entid it was resolved to, released with `temp_id_destroy`.
+struct TempId {
+    char* _Nonnull tempid;
+    int64_t entid;
+};
+typedef struct TempId TempId;
+
 /*
  A Mapping for the ValueType Rust object.
  */
@@ -98,7 +116,28 @@ typedef NS_ENUM(NSInteger, ValueType) {
     ValueTypeDouble,
     ValueTypeString,
     ValueTypeKeyword,
-    ValueTypeUuid
+    ValueTypeUuid,
+    ValueTypeBytes
+};
+
+/*
+ A Mapping for the BindingKind Rust object: what a TypedValue holds.
+ */
+typedef NS_ENUM(NSInteger, BindingKind) {
+    BindingKindScalar = 0,
+    BindingKindVec,
+    BindingKindMap,
+    BindingKindNil
+};
+
+/*
+ A Mapping for the VocabularyOutcome Rust object.
+ */
+typedef NS_ENUM(NSInteger, VocabularyOutcome) {
+    VocabularyOutcomeInstalled = 0,
+    VocabularyOutcomeInstalledMissingAttributes,
+    VocabularyOutcomeExisted,
+    VocabularyOutcomeUpgraded
 };
 
 // Store
@@ -107,6 +146,12 @@ struct Store*_Nonnull store_open(const char*_Nonnull uri, struct RustError* _Non
 // Destructors.
 void destroy(void* _Nullable obj);
 void uuid_destroy(uuid_t* _Nullable obj);
+void byte_buffer_destroy(struct ByteBuffer* _Nullable obj);
+void structured_map_destroy(struct StructuredMap* _Nullable obj);
+void attribute_builder_destroy(struct AttributeBuilder* _Nullable obj);
+void vocabulary_definition_destroy(struct VocabularyDefinition* _Nullable obj);
+void temp_id_destroy(struct TempId* _Nullable obj);
+void temp_id_iter_destroy(struct TempIdIterator* _Nullable obj);
 void query_builder_destroy(struct Query* _Nullable obj);
 void store_destroy(struct Store* _Nonnull obj);
 void tx_report_destroy(struct TxReport* _Nonnull obj);
@@ -129,12 +174,18 @@ struct TxReport*_Nullable store_transact(struct Store*_Nonnull store, const char
 int64_t* _Nullable tx_report_entity_for_temp_id(const struct TxReport* _Nonnull report, const char* _Nonnull tempid);
 int64_t  tx_report_get_entid(const struct TxReport* _Nonnull report);
 int64_t tx_report_get_tx_instant(const struct TxReport* _Nonnull report);
+struct TempIdIterator* _Nonnull tx_report_temp_ids_into_iter(const struct TxReport* _Nonnull report);
+struct TempId* _Nullable temp_id_iter_next(struct TempIdIterator* _Nonnull iter);
 struct InProgress *_Nullable store_begin_transaction(struct Store*_Nonnull store, struct RustError* _Nonnull error);
 
 // in progress
 struct TxReport*_Nullable in_progress_transact(struct InProgress*_Nonnull in_progress, const char* _Nonnull transaction, struct RustError*_Nonnull err);
 void in_progress_commit(struct InProgress*_Nonnull in_progress, struct RustError* _Nonnull error);
 void in_progress_rollback(struct InProgress*_Nonnull in_progress, struct RustError* _Nonnull error);
+void in_progress_savepoint(struct InProgress*_Nonnull in_progress, const char* _Nonnull name, struct RustError* _Nonnull error);
+void in_progress_rollback_savepoint(struct InProgress*_Nonnull in_progress, const char* _Nonnull name, struct RustError* _Nonnull error);
+void in_progress_release_savepoint(struct InProgress*_Nonnull in_progress, const char* _Nonnull name, struct RustError* _Nonnull error);
+enum VocabularyOutcome* _Nullable in_progress_ensure_vocabulary(struct InProgress*_Nonnull in_progress, const struct VocabularyDefinition* _Nonnull definition, struct RustError* _Nonnull error);
 
 // in_progress entity building
 struct InProgressBuilder*_Nullable store_in_progress_builder(struct Store*_Nonnull store, struct RustError* _Nonnull error);
@@ -151,6 +202,7 @@ void in_progress_builder_add_timestamp(struct InProgressBuilder*_Nonnull builder
 void in_progress_builder_add_boolean(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const int32_t value, struct RustError* _Nonnull error);
 void in_progress_builder_add_double(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const double value, struct RustError* _Nonnull error);
 void in_progress_builder_add_uuid(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const uuid_t* _Nonnull value, struct RustError* _Nonnull error);
+void in_progress_builder_add_bytes(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const uint8_t* _Nonnull value, size_t len, struct RustError* _Nonnull error);
 void in_progress_builder_retract_string(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const char*_Nonnull value, struct RustError* _Nonnull error);
 void in_progress_builder_retract_long(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const int64_t value, struct RustError* _Nonnull error);
 void in_progress_builder_retract_ref(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const int64_t value, struct RustError* _Nonnull error);
@@ -159,6 +211,7 @@ void in_progress_builder_retract_timestamp(struct InProgressBuilder*_Nonnull bui
 void in_progress_builder_retract_boolean(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const int32_t value, struct RustError* _Nonnull error);
 void in_progress_builder_retract_double(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const double value, struct RustError* _Nonnull error);
 void in_progress_builder_retract_uuid(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const uuid_t* _Nonnull value, struct RustError* _Nonnull error);
+void in_progress_builder_retract_bytes(struct InProgressBuilder*_Nonnull builder, const int64_t entid, const char*_Nonnull kw, const uint8_t* _Nonnull value, size_t len, struct RustError* _Nonnull error);
 struct InProgressTransactResult in_progress_builder_transact(struct InProgressBuilder*_Nonnull builder);
 struct TxReport*_Nullable in_progress_builder_commit(struct InProgressBuilder*_Nonnull builder, struct RustError* _Nonnull error);
 
@@ -173,6 +226,7 @@ void entity_builder_add_boolean(struct EntityBuilder*_Nonnull builder, const cha
 void entity_builder_add_double(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const double value, struct RustError* _Nonnull error);
 void entity_builder_add_timestamp(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const int64_t value, struct RustError* _Nonnull error);
 void entity_builder_add_uuid(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const uuid_t* _Nonnull value, struct RustError* _Nonnull error);
+void entity_builder_add_bytes(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const uint8_t* _Nonnull value, size_t len, struct RustError* _Nonnull error);
 
 void entity_builder_retract_string(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const char*_Nonnull value, struct RustError* _Nonnull error);
 void entity_builder_retract_long(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const int64_t value, struct RustError* _Nonnull error);
@@ -182,10 +236,27 @@ void entity_builder_retract_boolean(struct EntityBuilder*_Nonnull builder, const
 void entity_builder_retract_double(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const double value, struct RustError* _Nonnull error);
 void entity_builder_retract_timestamp(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const int64_t value, struct RustError* _Nonnull error);
 void entity_builder_retract_uuid(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const uuid_t* _Nonnull value, struct RustError* _Nonnull error);
+void entity_builder_retract_bytes(struct EntityBuilder*_Nonnull builder, const char*_Nonnull kw, const uint8_t* _Nonnull value, size_t len, struct RustError* _Nonnull error);
 
 struct InProgressTransactResult entity_builder_transact(struct EntityBuilder*_Nonnull builder);
 struct TxReport*_Nullable entity_builder_commit(struct EntityBuilder*_Nonnull builder, struct RustError* _Nonnull error);
 
+// Vocabulary
+struct AttributeBuilder*_Nonnull attribute_builder_new(enum ValueType value_type);
+void attribute_builder_multival(struct AttributeBuilder*_Nonnull builder, const int32_t multival);
+void attribute_builder_unique_value(struct AttributeBuilder*_Nonnull builder);
+void attribute_builder_unique_identity(struct AttributeBuilder*_Nonnull builder);
+void attribute_builder_index(struct AttributeBuilder*_Nonnull builder, const int32_t index);
+void attribute_builder_fulltext(struct AttributeBuilder*_Nonnull builder, const int32_t fulltext);
+void attribute_builder_component(struct AttributeBuilder*_Nonnull builder, const int32_t component);
+void attribute_builder_no_history(struct AttributeBuilder*_Nonnull builder, const int32_t no_history);
+struct VocabularyDefinition*_Nonnull vocabulary_definition_new(const char* _Nonnull name, const uint32_t version);
+void vocabulary_definition_add_attribute(struct VocabularyDefinition*_Nonnull definition, const char* _Nonnull ident, struct AttributeBuilder*_Nonnull builder);
+enum VocabularyOutcome* _Nullable store_ensure_vocabulary(struct Store*_Nonnull store, const struct VocabularyDefinition* _Nonnull definition, struct RustError* _Nonnull error);
+
+// Sync; only in libraries built with the `syncable` feature.
+char* _Nullable store_sync(struct Store*_Nonnull store, const char* _Nonnull server_uri, const char* _Nonnull user_uuid, struct RustError* _Nonnull error);
+
 // Observers
 void store_register_observer(struct Store*_Nonnull  store, const char* _Nonnull key, const int64_t* _Nonnull attributes, const int64_t len, void (*_Nonnull callback_fn)(const char* _Nonnull key, const struct TxChangeList* _Nonnull reports));
 void store_unregister_observer(struct Store*_Nonnull  store, const char* _Nonnull key);
@@ -196,6 +267,14 @@ int64_t changelist_entry_at(const struct TxChange* _Nonnull report, size_t index
 struct Query*_Nonnull store_query(struct Store*_Nonnull store, const char* _Nonnull query);
 struct TypedValue*_Nullable store_value_for_attribute(struct Store*_Nonnull store, const int64_t entid, const char* _Nonnull attribute, struct RustError* _Nonnull error);
 
+// Pull
+struct StructuredMap*_Nullable store_pull_attributes_for_entity(struct Store*_Nonnull store, const int64_t entid, const int64_t* _Nonnull attributes, size_t attributes_len, struct RustError* _Nonnull error);
+struct QueryResultRow*_Nullable store_pull_attributes_for_entities(struct Store*_Nonnull store, const int64_t* _Nonnull entities, size_t entities_len, const int64_t* _Nonnull attributes, size_t attributes_len, struct RustError* _Nonnull error);
+uint64_t structured_map_len(struct StructuredMap*_Nonnull map);
+char* _Nullable structured_map_key_at(struct StructuredMap*_Nonnull map, const int32_t index);
+struct TypedValue* _Nullable structured_map_value_at(struct StructuredMap*_Nonnull map, const int32_t index);
+struct TypedValue* _Nullable structured_map_value_for_key(struct StructuredMap*_Nonnull map, const char* _Nonnull key);
+
 // Query Variable Binding
 void query_builder_bind_long(struct Query*_Nonnull query, const char* _Nonnull var, const int64_t value);
 void query_builder_bind_ref(struct Query*_Nonnull query, const char* _Nonnull var, const int64_t value);
@@ -206,12 +285,14 @@ void query_builder_bind_double(struct Query*_Nonnull query, const char* _Nonnull
 void query_builder_bind_timestamp(struct Query*_Nonnull query, const char* _Nonnull var, const int64_t value);
 void query_builder_bind_string(struct Query*_Nonnull query, const char* _Nonnull var, const char* _Nonnull value);
 void query_builder_bind_uuid(struct Query*_Nonnull query, const char* _Nonnull var, const uuid_t* _Nonnull value);
+void query_builder_bind_bytes(struct Query*_Nonnull query, const char* _Nonnull var, const uint8_t* _Nonnull value, size_t len);
 
 // Query execution
 struct QueryResultRows* _Nullable query_builder_execute(struct Query*_Nonnull query, struct RustError* _Nonnull error);
 struct TypedValue* _Nullable query_builder_execute_scalar(struct Query*_Nonnull query, struct RustError* _Nonnull error);
 struct QueryResultRow* _Nullable query_builder_execute_coll(struct Query*_Nonnull query, struct RustError* _Nonnull error);
 struct QueryResultRow* _Nullable query_builder_execute_tuple(struct Query*_Nonnull query, struct RustError* _Nonnull error);
+char* _Nullable query_builder_explain(struct Query*_Nonnull query, struct RustError* _Nonnull error);
 
 // Query Result Processing
 int64_t typed_value_into_long(struct TypedValue*_Nonnull  value);
@@ -222,7 +303,11 @@ double typed_value_into_double(struct TypedValue*_Nonnull  value);
 int64_t typed_value_into_timestamp(struct TypedValue*_Nonnull  value);
 char* _Nonnull typed_value_into_string(struct TypedValue*_Nonnull  value);
 uuid_t* _Nonnull typed_value_into_uuid(struct TypedValue*_Nonnull  value);
+struct ByteBuffer* _Nonnull typed_value_into_bytes(struct TypedValue*_Nonnull  value);
+struct StructuredMap* _Nonnull typed_value_into_map(struct TypedValue*_Nonnull  value);
+struct QueryResultRow* _Nonnull typed_value_into_vec(struct TypedValue*_Nonnull  value);
 enum ValueType typed_value_value_type(struct TypedValue*_Nonnull value);
+enum BindingKind typed_value_binding_kind(struct TypedValue*_Nonnull value);
 
 struct QueryResultRow* _Nullable row_at_index(struct QueryResultRows* _Nonnull rows, const int32_t index);
 struct QueryRowsIterator* _Nonnull typed_value_result_set_into_iter(struct QueryResultRows* _Nonnull rows);
@@ -239,6 +324,7 @@ double value_at_index_into_double(struct QueryResultRow* _Nonnull row, const int
 int64_t value_at_index_into_timestamp(struct QueryResultRow* _Nonnull row, const int32_t index);
 char* _Nonnull value_at_index_into_string(struct QueryResultRow* _Nonnull row, const int32_t index);
 uuid_t* _Nonnull value_at_index_into_uuid(struct QueryResultRow* _Nonnull row, const int32_t index);
+struct ByteBuffer* _Nonnull value_at_index_into_bytes(struct QueryResultRow* _Nonnull row, const int32_t index);
 
 // Transaction change lists
 const struct TxChange* _Nonnull tx_change_list_entry_at(const struct TxChangeList* _Nonnull list, size_t index);
@@ -20,13 +20,16 @@ use rusqlite::TransactionBehavior;
 
 pub use core_traits::{Attribute, Entid, KnownEntid, StructuredMap, TypedValue, ValueType};
 
-use mentat_core::{HasSchema, Keyword, Schema, TxReport, ValueRc};
+use core_traits::Binding;
+
+use mentat_core::{HasSchema, Keyword, Schema, Statistics, TxReport, ValueRc};
 
 use mentat_db::cache::{InProgressSQLiteAttributeCache, SQLiteAttributeCache};
 
 use mentat_db::db;
 use mentat_db::{
-    InProgressObserverTransactWatcher, PartitionMap, TxObservationService, TxObserver,
+    read_statistics, InProgressObserverTransactWatcher, PartitionMap, TxObservationService,
+    TxObserver,
 };
 
 use mentat_query_pull::{pull_attributes_for_entities, pull_attributes_for_entity};
@@ -37,9 +40,18 @@ use public_traits::errors::{MentatError, Result};
 
 use mentat_transaction::query::{
     lookup_value_for_attribute, lookup_values_for_attribute, q_explain, q_once, q_prepare,
-    q_uncached, Known, PreparedResult, QueryExplanation, QueryInputs, QueryOutput,
+    q_uncached, AttachedSchemas, Known, PreparedResult, QueryExplanation, QueryInputs, QueryOutput,
 };
 
+/// What's known about the store as of `metadata`.
+fn known(metadata: &Metadata) -> Known {
+    let known = Known::new(&metadata.schema, Some(&metadata.attribute_cache));
+    match metadata.statistics {
+        Some(ref statistics) => known.with_statistics(statistics),
+        None => known,
+    }
+}
+
 /// A mutable, safe reference to the current Mentat store.
 pub struct Conn {
     /// `Mutex` since all reads and writes need to be exclusive.  Internally, owned data for the
@@ -113,6 +125,19 @@ impl Conn {
         metadata.partition_map[":db.part/tx"].next_entid() - 1
     }
 
+    pub fn current_statistics(&self) -> Option<Arc<Statistics>> {
+        self.metadata.lock().unwrap().statistics.clone()
+    }
+
+    /// Run SQLite's `ANALYZE` and count the store's datoms, so that queries are planned using
+    /// those counts.  They're kept until the next `analyze`, however the store changes.
+    pub fn analyze(&self, sqlite: &rusqlite::Connection) -> Result<()> {
+        sqlite.execute_batch("ANALYZE")?;
+        let statistics = read_statistics(sqlite)?;
+        self.metadata.lock().unwrap().statistics = Some(Arc::new(statistics));
+        Ok(())
+    }
+
     /// Query the Mentat store, using the given connection and the current metadata.
     pub fn q_once<T>(
         &self,
@@ -125,10 +150,21 @@ impl Conn {
     {
         // Doesn't clone, unlike `current_schema`.
         let metadata = self.metadata.lock().unwrap();
-        let known = Known::new(&*metadata.schema, Some(&metadata.attribute_cache));
+        let known = known(&metadata);
         q_once(sqlite, known, query, inputs)
     }
 
+    /// Run `f` with what's known about the current metadata and the stores attached to the given
+    /// connection.
+    pub(crate) fn with_attached<F, R>(&self, attached: &AttachedSchemas, f: F) -> R
+    where
+        F: FnOnce(Known) -> R,
+    {
+        let metadata = self.metadata.lock().unwrap();
+        let known = known(&metadata);
+        f(known.with_attached(attached))
+    }
+
     /// Query the Mentat store, using the given connection and the current metadata,
     /// but without using the cache.
     pub fn q_uncached<T>(
@@ -159,10 +195,30 @@ impl Conn {
         T: Into<Option<QueryInputs>>,
     {
         let metadata = self.metadata.lock().unwrap();
-        let known = Known::new(&*metadata.schema, Some(&metadata.attribute_cache));
+        let known = known(&metadata);
         q_prepare(sqlite, known, query, inputs)
     }
 
+    /// Query the Mentat store, handing each row of the results to `f` as it's read rather than
+    /// collecting them all first. See `QueryRows`.
+    pub fn q_for_each_row<T, F>(
+        &self,
+        sqlite: &rusqlite::Connection,
+        query: &str,
+        inputs: T,
+        mut f: F,
+    ) -> Result<()>
+    where
+        T: Into<Option<QueryInputs>>,
+        F: FnMut(Vec<Binding>) -> Result<()>,
+    {
+        let mut prepared = self.q_prepare(sqlite, query, inputs)?;
+        for row in prepared.rows()? {
+            f(row?)?;
+        }
+        Ok(())
+    }
+
     pub fn q_explain<T>(
         &self,
         sqlite: &rusqlite::Connection,
@@ -173,7 +229,7 @@ impl Conn {
         T: Into<Option<QueryInputs>>,
     {
         let metadata = self.metadata.lock().unwrap();
-        let known = Known::new(&*metadata.schema, Some(&metadata.attribute_cache));
+        let known = known(&metadata);
         q_explain(sqlite, known, query, inputs)
     }
 
@@ -235,7 +291,7 @@ impl Conn {
         behavior: TransactionBehavior,
     ) -> Result<InProgress<'m, 'conn>> {
         let tx = sqlite.transaction_with_behavior(behavior)?;
-        let (current_generation, current_partition_map, current_schema, cache_cow) = {
+        let (current_generation, current_partition_map, current_schema, cache_cow, statistics) = {
             // The mutex is taken during this block.
             let current: &Metadata = &(*self.metadata.lock().unwrap());
             (
@@ -245,6 +301,7 @@ impl Conn {
                 // Cheap.
                 current.schema.clone(),
                 current.attribute_cache.clone(),
+                current.statistics.clone(),
             )
         };
 
@@ -256,6 +313,8 @@ impl Conn {
             schema: (*current_schema).clone(),
             cache: InProgressSQLiteAttributeCache::from_cache(cache_cow),
             use_caching: true,
+            statistics,
+            attached: None,
             tx_observer: &self.tx_observer_service,
             tx_observer_watcher: InProgressObserverTransactWatcher::new(),
         })
@@ -17,7 +17,7 @@ pub use edn;
 extern crate core_traits;
 
 pub use core_traits::{
-    now, Attribute, Binding, Entid, KnownEntid, StructuredMap, TypedValue, ValueType,
+    attribute, now, Attribute, Binding, Entid, KnownEntid, StructuredMap, TypedValue, ValueType,
 };
 
 pub use mentat_core::{DateTime, HasSchema, Keyword, Schema, TxReport, Utc, Uuid};
@@ -25,9 +25,11 @@ pub use mentat_core::{DateTime, HasSchema, Keyword, Schema, TxReport, Utc, Uuid}
 pub use edn::query::FindSpec;
 
 pub use mentat_db::{
-    new_connection, AttributeSet, TxObserver, CORE_SCHEMA_VERSION, DB_SCHEMA_CORE,
+    new_connection, AttributeSet, HistoryBound, TxObserver, CORE_SCHEMA_VERSION, DB_SCHEMA_CORE,
 };
 
+pub use mentat_db::timelines::{Timeline, TIMELINE_MAIN_NAME};
+
 #[cfg(feature = "sqlcipher")]
 pub use mentat_db::{change_encryption_key, new_connection_with_key};
 
@@ -84,6 +86,7 @@ macro_rules! kw {
 pub use public_traits::errors;
 pub use public_traits::errors::{MentatError, Result};
 
+pub use db_traits::errors::{DbError, DbErrorKind};
 pub use edn::{FromMicros, FromMillis, ParseError, ToMicros, ToMillis};
 pub use mentat_query_projector::BindingTuple;
 pub use query_algebrizer_traits::errors::AlgebrizerError;
@@ -94,11 +97,13 @@ pub use sql_traits::errors::SQLError;
 pub use mentat_transaction::Metadata;
 
 pub use mentat_transaction::entity_builder;
+pub use mentat_transaction::json;
 pub use mentat_transaction::query;
 
 pub use mentat_transaction::query::{
-    q_once, IntoResult, PlainSymbol, QueryExecutionResult, QueryExplanation, QueryInputs,
-    QueryOutput, QueryPlanStep, QueryResults, RelResult, Variable,
+    q_once, FulltextMarkup, IntoResult, PlainSymbol, QueryExecutionResult, QueryExplanation,
+    QueryInputs, QueryOutput, QueryPlan, QueryPlanStep, QueryResults, QueryRows, RelResult,
+    TableExplanation, Variable,
 };
 
 pub mod conn;
@@ -15,7 +15,9 @@ pub use core_traits::{Binding, Entid, TypedValue, ValueType};
 
 use mentat_core::{DateTime, Keyword, Utc};
 
-use super::{HasSchema, QueryInputs, QueryOutput, Queryable, RelResult, Store, Variable};
+use super::{
+    HasSchema, QueryExplanation, QueryInputs, QueryOutput, Queryable, RelResult, Store, Variable,
+};
 
 use public_traits::errors::{MentatError, Result};
 
@@ -106,6 +108,30 @@ impl<'a> QueryBuilder<'a> {
         read.q_once(&self.query, query_inputs).map_err(|e| e)
     }
 
+    /// Run the query, handing each row of the results to `f` as it's read rather than collecting
+    /// them all first. A scalar or `coll` query has a single binding in each row.
+    pub fn execute_for_each_row<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(Vec<Binding>) -> Result<()>,
+    {
+        let values = ::std::mem::take(&mut self.values);
+        let types = ::std::mem::take(&mut self.types);
+        let query_inputs = QueryInputs::new(types, values)?;
+        let read = self.store.begin_read()?;
+        let mut prepared = read.q_prepare(&self.query, query_inputs)?;
+        for row in prepared.rows()? {
+            f(row?)?;
+        }
+        Ok(())
+    }
+
+    /// Explain how the query would be run with the values and types bound so far. Unlike
+    /// `execute`, this leaves the bindings in place, so the query can be run afterwards.
+    pub fn explain(&self) -> Result<QueryExplanation> {
+        let query_inputs = QueryInputs::new(self.types.clone(), self.values.clone())?;
+        self.store.q_explain(&self.query, query_inputs)
+    }
+
     pub fn execute_scalar(&mut self) -> Result<Option<Binding>> {
         let results = self.execute()?;
         results.into_scalar().map_err(|e| e.into())
@@ -130,6 +156,7 @@ impl<'a> QueryBuilder<'a> {
 #[cfg(test)]
 mod test {
     use super::{QueryBuilder, Store, TypedValue};
+    use crate::QueryPlan;
 
     #[test]
     fn test_scalar_query() {
@@ -538,4 +565,81 @@ mod test {
             25
         );
     }
+
+    #[test]
+    fn test_for_each_row() {
+        let mut store = Store::open("").expect("store connection");
+        store
+            .transact(
+                r#"[
+            [:db/add "s" :db/ident :foo/long]
+            [:db/add "s" :db/valueType :db.type/long]
+            [:db/add "s" :db/cardinality :db.cardinality/one]
+        ]"#,
+            )
+            .expect("successful transaction");
+        store
+            .transact(
+                r#"[
+            [:db/add "l" :foo/long 25]
+            [:db/add "m" :foo/long 26]
+            [:db/add "n" :foo/long 27]
+        ]"#,
+            )
+            .expect("successful transaction");
+
+        let mut longs = vec![];
+        QueryBuilder::new(
+            &mut store,
+            r#"[:find ?e ?i
+                :in ?min
+                :where [?e :foo/long ?i] [(>= ?i ?min)]
+                :order ?i]"#,
+        )
+        .bind_long("?min", 26)
+        .execute_for_each_row(|row| {
+            assert_eq!(row.len(), 2);
+            longs.push(row[1].clone().into_long().expect("long"));
+            Ok(())
+        })
+        .expect("rows");
+        assert_eq!(longs, vec![26, 27]);
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut store = Store::open("").expect("store connection");
+        store
+            .transact(
+                r#"[
+            [:db/add "s" :db/ident :foo/long]
+            [:db/add "s" :db/valueType :db.type/long]
+            [:db/add "s" :db/cardinality :db.cardinality/one]
+        ]"#,
+            )
+            .expect("successful transaction");
+        store
+            .transact(r#"[[:db/add "l" :foo/long 25]]"#)
+            .expect("successful transaction");
+
+        let mut query = QueryBuilder::new(
+            &mut store,
+            r#"[:find ?e . :in ?i :where [?e :foo/long ?i]]"#,
+        );
+        query.bind_long("?i", 25);
+        let explanation = query.explain().expect("explained");
+        match explanation.plan {
+            QueryPlan::ExecutionPlan { ref query, .. } => {
+                assert!(query.sql.contains("25"));
+            }
+            _ => panic!("Expected an execution plan"),
+        }
+
+        // Explaining doesn't use up the bindings.
+        let entid = query
+            .execute_scalar()
+            .expect("results")
+            .and_then(|e| e.into_entid());
+        assert!(entid.is_some());
+    }
 }
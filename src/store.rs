@@ -17,7 +17,8 @@ use std::sync::Arc;
 use core_traits::{Entid, StructuredMap, TypedValue};
 
 use mentat_core::{Keyword, TxReport, ValueRc};
-use mentat_db::TxObserver;
+use mentat_db::timelines::Timeline;
+use mentat_db::{read_attached_schema, HistoryBound, TxObserver};
 
 use mentat_transaction::{
     CacheAction, CacheDirection, InProgress, InProgressRead, Pullable, Queryable,
@@ -25,12 +26,15 @@ use mentat_transaction::{
 
 use crate::conn::Conn;
 
-use public_traits::errors::Result;
+use public_traits::errors::{MentatError, Result};
 
-use mentat_transaction::query::{PreparedResult, QueryExplanation, QueryInputs, QueryOutput};
+use mentat_transaction::query::{
+    q_explain, q_once, q_prepare, AttachedSchemas, PreparedResult, QueryExplanation, QueryInputs,
+    QueryOutput,
+};
 
 #[cfg(feature = "syncable")]
-use mentat_tolstoy::{SyncFollowup, SyncReport, SyncResult};
+use mentat_tolstoy::{SyncFollowup, SyncMetadata, SyncReport, SyncResult};
 
 #[cfg(feature = "syncable")]
 use crate::sync::Syncable;
@@ -40,6 +44,10 @@ use crate::sync::Syncable;
 pub struct Store {
     conn: Conn,
     sqlite: rusqlite::Connection,
+    /// For each undo session, the undo transactions that can be redone, most recent last.
+    redo_stacks: BTreeMap<String, Vec<Entid>>,
+    /// The schemas of the stores attached to this one, as they were when attached.
+    attached: AttachedSchemas,
 }
 
 impl Store {
@@ -50,6 +58,8 @@ impl Store {
         Ok(Store {
             conn,
             sqlite: connection,
+            redo_stacks: Default::default(),
+            attached: Default::default(),
         })
     }
 
@@ -60,6 +70,22 @@ impl Store {
         Ok(report)
     }
 
+    /// Transacts a transaction written in JSON. See `mentat::json` for the format.
+    pub fn transact_json(&mut self, transaction: &str) -> Result<TxReport> {
+        let mut ip = self.begin_transaction()?;
+        let report = ip.transact_json(transaction)?;
+        ip.commit()?;
+        Ok(report)
+    }
+
+    /// Transacts a transaction written in Transit-JSON.
+    pub fn transact_transit(&mut self, transaction: &str) -> Result<TxReport> {
+        let mut ip = self.begin_transaction()?;
+        let report = ip.transact_transit(transaction)?;
+        ip.commit()?;
+        Ok(report)
+    }
+
     #[cfg(feature = "syncable")]
     pub fn sync(&mut self, server_uri: &str, user_uuid: &str) -> Result<SyncResult> {
         let mut reports = vec![];
@@ -98,6 +124,8 @@ impl Store {
         Ok(Store {
             conn,
             sqlite: connection,
+            redo_stacks: Default::default(),
+            attached: Default::default(),
         })
     }
 
@@ -135,12 +163,22 @@ impl Store {
         &self.conn
     }
 
+    /// The store's SQLite connection, for reading what the store doesn't otherwise expose.
+    /// Don't write through it: the store's schema and caches wouldn't know.
+    pub fn sqlite(&self) -> &rusqlite::Connection {
+        &self.sqlite
+    }
+
     pub fn begin_read<'m>(&'m mut self) -> Result<InProgressRead<'m, 'm>> {
-        self.conn.begin_read(&mut self.sqlite)
+        let mut read = self.conn.begin_read(&mut self.sqlite)?;
+        read.in_progress.attached = Some(&self.attached);
+        Ok(read)
     }
 
     pub fn begin_transaction<'m>(&'m mut self) -> Result<InProgress<'m, 'm>> {
-        self.conn.begin_transaction(&mut self.sqlite)
+        let mut in_progress = self.conn.begin_transaction(&mut self.sqlite)?;
+        in_progress.attached = Some(&self.attached);
+        Ok(in_progress)
     }
 
     pub fn cache(&mut self, attr: &Keyword, direction: CacheDirection) -> Result<()> {
@@ -165,6 +203,150 @@ impl Store {
     pub fn last_tx_id(&self) -> Entid {
         self.conn.last_tx_id()
     }
+
+    /// Gather the statistics used to plan queries.  See `Conn::analyze`.
+    pub fn analyze(&mut self) -> Result<()> {
+        self.conn.analyze(&self.sqlite)
+    }
+
+    /// Compact the history of this store before `bound`, keeping the full history of the
+    /// attributes in `keep_history`.  Current datoms are untouched.  Transactions that have not
+    /// yet been uploaded by sync are never compacted.  Returns the number of transaction log rows
+    /// removed.
+    pub fn compact_history(
+        &mut self,
+        bound: HistoryBound,
+        keep_history: &[Keyword],
+    ) -> Result<usize> {
+        let mut ip = self.begin_transaction()?;
+        let before_tx = ip.history_bound_tx(bound)?;
+
+        #[cfg(feature = "syncable")]
+        let before_tx = SyncMetadata::compaction_bound(&ip.transaction, before_tx)?;
+
+        let removed = ip.compact_history(before_tx, keep_history)?;
+        ip.commit()?;
+        Ok(removed)
+    }
+
+    /// Undo transaction `tx_id` by transacting its inverse.  Fails if a later transaction has
+    /// changed any of the same attributes of the same entities.
+    pub fn undo(&mut self, tx_id: Entid) -> Result<TxReport> {
+        let mut ip = self.begin_transaction()?;
+        let report = ip.undo(tx_id)?;
+        ip.commit()?;
+        Ok(report)
+    }
+
+    /// Like `undo`, but remember the undo so that it can be redone with `redo` in the same
+    /// `session`.
+    pub fn undo_in_session(&mut self, session: &str, tx_id: Entid) -> Result<TxReport> {
+        let report = self.undo(tx_id)?;
+        self.redo_stacks
+            .entry(session.to_string())
+            .or_default()
+            .push(report.tx_id);
+        Ok(report)
+    }
+
+    /// Redo the most recent undo in `session`, if there is one.  Fails, leaving the undo to be
+    /// redone, if a later transaction has changed any of the same attributes of the same entities.
+    pub fn redo(&mut self, session: &str) -> Result<Option<TxReport>> {
+        let undo_tx_id = match self.redo_stacks.get(session).and_then(|stack| stack.last()) {
+            Some(&tx_id) => tx_id,
+            None => return Ok(None),
+        };
+        let report = self.undo(undo_tx_id)?;
+        if let Some(stack) = self.redo_stacks.get_mut(session) {
+            stack.pop();
+        }
+        Ok(Some(report))
+    }
+
+    /// Forget the undos that could be redone in `session`.
+    pub fn clear_redo(&mut self, session: &str) {
+        self.redo_stacks.remove(session);
+    }
+
+    /// Create a named timeline forking from the main timeline after transaction `base`.
+    pub fn fork_timeline(&mut self, name: &str, base: Entid) -> Result<Timeline> {
+        let mut ip = self.begin_transaction()?;
+        let timeline = ip.fork_timeline(name, base)?;
+        ip.commit()?;
+        Ok(timeline)
+    }
+
+    /// Switch to the named timeline, or back to the main timeline if `name` is
+    /// `TIMELINE_MAIN_NAME`.
+    pub fn switch_timeline(&mut self, name: &str) -> Result<()> {
+        let mut ip = self.begin_transaction()?;
+        ip.switch_timeline(name)?;
+        ip.commit()
+    }
+
+    /// Transact the changes made on the named timeline on top of the main timeline, and forget
+    /// the named timeline.
+    pub fn merge_timeline(&mut self, name: &str) -> Result<Vec<TxReport>> {
+        let mut ip = self.begin_transaction()?;
+        let reports = ip.merge_timeline(name)?;
+        ip.commit()?;
+        Ok(reports)
+    }
+
+    /// Forget the named timeline and the changes made on it.
+    pub fn discard_timeline(&mut self, name: &str) -> Result<()> {
+        let mut ip = self.begin_transaction()?;
+        ip.discard_timeline(name)?;
+        ip.commit()
+    }
+
+    /// Attach the Mentat store at `path` as `name`, so that queries made through this store can
+    /// match its datoms with the source `$name`, as in `[$name ?e :foo/bar ?v]`.  Its idents
+    /// are resolved with its own schema, as it is now.
+    pub fn attach(&mut self, name: &str, path: &str) -> Result<()> {
+        let reserved = ["delta", "main", "temp"];
+        let valid = !name.is_empty()
+            && !reserved.contains(&name)
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            bail!(MentatError::InvalidAttachedStoreName(name.to_string()));
+        }
+        if self.attached.contains_key(name) {
+            bail!(MentatError::StoreAlreadyAttached(name.to_string()));
+        }
+
+        self.sqlite
+            .execute("ATTACH DATABASE ? AS ?", rusqlite::params![path, name])?;
+        match read_attached_schema(&self.sqlite, name) {
+            Ok(schema) => {
+                self.attached.insert(name.to_string(), schema);
+                Ok(())
+            }
+            Err(e) => {
+                // Most likely not a Mentat store at all.
+                self.sqlite
+                    .execute("DETACH DATABASE ?", rusqlite::params![name])?;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Detach the store attached as `name`.
+    pub fn detach(&mut self, name: &str) -> Result<()> {
+        if self.attached.remove(name).is_none() {
+            bail!(MentatError::StoreNotAttached(name.to_string()));
+        }
+        self.sqlite
+            .execute("DETACH DATABASE ?", rusqlite::params![name])?;
+        Ok(())
+    }
+
+    /// The names of the attached stores.
+    pub fn attached(&self) -> Vec<&str> {
+        self.attached.keys().map(|name| name.as_str()).collect()
+    }
 }
 
 impl Queryable for Store {
@@ -172,21 +354,27 @@ impl Queryable for Store {
     where
         T: Into<Option<QueryInputs>>,
     {
-        self.conn.q_once(&self.sqlite, query, inputs)
+        self.conn.with_attached(&self.attached, |known| {
+            q_once(&self.sqlite, known, query, inputs)
+        })
     }
 
     fn q_prepare<T>(&self, query: &str, inputs: T) -> PreparedResult<'_>
     where
         T: Into<Option<QueryInputs>>,
     {
-        self.conn.q_prepare(&self.sqlite, query, inputs)
+        self.conn.with_attached(&self.attached, |known| {
+            q_prepare(&self.sqlite, known, query, inputs)
+        })
     }
 
     fn q_explain<T>(&self, query: &str, inputs: T) -> Result<QueryExplanation>
     where
         T: Into<Option<QueryInputs>>,
     {
-        self.conn.q_explain(&self.sqlite, query, inputs)
+        self.conn.with_attached(&self.attached, |known| {
+            q_explain(&self.sqlite, known, query, inputs)
+        })
     }
 
     fn lookup_values_for_attribute<E>(
@@ -862,4 +1050,62 @@ mod tests {
         assert_eq!(o.txids, tx_ids);
         assert_eq!(o.changes, changesets);
     }
+
+    #[test]
+    fn test_undo_redo_session() {
+        let mut store = Store::open("").expect("opened");
+        store
+            .transact(
+                r#"[{:db/ident :todo/title
+                     :db/valueType :db.type/string
+                     :db/cardinality :db.cardinality/one}]"#,
+            )
+            .expect("transacted schema");
+        let report = store
+            .transact(r#"[{:db/id "t" :todo/title "Buy milk"}]"#)
+            .expect("transacted");
+        let todo = *report.tempids.get("t").expect("todo");
+        let edit = store
+            .transact(&format!(
+                r#"[[:db/add {} :todo/title "Buy oat milk"]]"#,
+                todo
+            ))
+            .expect("transacted")
+            .tx_id;
+
+        let title = |store: &Store| {
+            store
+                .lookup_value_for_attribute(todo, &kw!(:todo/title))
+                .expect("looked up")
+        };
+
+        assert!(store.redo("editor").expect("redo").is_none());
+
+        store.undo_in_session("editor", edit).expect("undone");
+        assert_eq!(title(&store), Some(TypedValue::typed_string("Buy milk")));
+
+        // Other sessions have nothing to redo.
+        assert!(store.redo("other").expect("redo").is_none());
+
+        store.redo("editor").expect("redo").expect("redone");
+        assert_eq!(
+            title(&store),
+            Some(TypedValue::typed_string("Buy oat milk"))
+        );
+        assert!(store.redo("editor").expect("redo").is_none());
+
+        // Undos and redos that conflict with later changes fail.
+        assert!(store.undo_in_session("editor", edit).is_err());
+        let latest = store.last_tx_id();
+        store.undo_in_session("editor", latest).expect("undone");
+        store
+            .transact(&format!(r#"[[:db/add {} :todo/title "Buy bread"]]"#, todo))
+            .expect("transacted");
+        assert!(store.redo("editor").is_err());
+        assert!(store.redo("editor").is_err());
+        assert_eq!(title(&store), Some(TypedValue::typed_string("Buy bread")));
+
+        store.clear_redo("editor");
+        assert!(store.redo("editor").expect("redo").is_none());
+    }
 }
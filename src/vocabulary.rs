@@ -94,7 +94,7 @@ use std::collections::BTreeMap;
 
 use core_traits::KnownEntid;
 
-use core_traits::attribute::Unique;
+use core_traits::attribute::{FulltextTokenizer, Unique};
 
 use super::{
     Attribute, Binding, Entid, HasSchema, IntoResult, Keyword, TypedValue, ValueType,
@@ -305,6 +305,10 @@ lazy_static! {
     static ref DB_VALUE_TYPE: Keyword = Keyword::namespaced("db", "valueType");
     static ref DB_INDEX: Keyword = kw!(:db/index);
     static ref DB_FULLTEXT: Keyword = kw!(:db/fulltext);
+    static ref DB_FULLTEXT_TOKENIZER: Keyword = kw!(:db.fulltext/tokenizer);
+    static ref DB_FULLTEXT_TOKENIZER_PORTER: Keyword = kw!(:db.fulltext.tokenizer/porter);
+    static ref DB_FULLTEXT_TOKENIZER_TRIGRAM: Keyword = kw!(:db.fulltext.tokenizer/trigram);
+    static ref DB_FULLTEXT_PREFIX: Keyword = kw!(:db.fulltext/prefix);
     static ref DB_CARDINALITY: Keyword = kw!(:db/cardinality);
     static ref DB_CARDINALITY_ONE: Keyword = kw!(:db.cardinality/one);
     static ref DB_CARDINALITY_MANY: Keyword = kw!(:db.cardinality/many);
@@ -423,6 +427,30 @@ impl Definition {
                 TypedValue::Boolean(attr.no_history),
             )?;
 
+            // Fulltext settings can't be altered, so we only describe those that differ from the
+            // defaults.
+            if attr.fulltext {
+                let tokenizer = match attr.fulltext_tokenizer {
+                    FulltextTokenizer::Unicode61 => None,
+                    FulltextTokenizer::Porter => Some(&*DB_FULLTEXT_TOKENIZER_PORTER),
+                    FulltextTokenizer::Trigram => Some(&*DB_FULLTEXT_TOKENIZER_TRIGRAM),
+                };
+                if let Some(tokenizer) = tokenizer {
+                    builder.add(
+                        tempid.clone(),
+                        via.core_attribute(&DB_FULLTEXT_TOKENIZER)?,
+                        via.core_entid(tokenizer)?,
+                    )?;
+                }
+                if attr.fulltext_prefix {
+                    builder.add(
+                        tempid.clone(),
+                        via.core_attribute(&DB_FULLTEXT_PREFIX)?,
+                        TypedValue::Boolean(true),
+                    )?;
+                }
+            }
+
             if let Some(u) = attr.unique {
                 let uu = match u {
                     Unique::Identity => v_unique_identity,
@@ -507,6 +535,7 @@ pub enum VocabularyCheck<'definition> {
 /// This enum captures the outcome of attempting to ensure that a vocabulary definition is present
 /// and up-to-date in the store.
 #[derive(Debug, Eq, PartialEq)]
+#[repr(C)]
 pub enum VocabularyOutcome {
     /// The vocabulary was absent and has been installed.
     Installed,
@@ -1011,7 +1040,7 @@ mod tests {
             .expect("OK");
         assert_eq!(vocabularies.len(), 1);
         let core = vocabularies.get(&kw!(:db.schema/core)).expect("exists");
-        assert_eq!(core.version, 1);
+        assert_eq!(core.version, 2);
     }
 
     #[test]
@@ -1021,7 +1050,7 @@ mod tests {
         let vocab = in_progress.read_vocabularies().expect("vocabulary");
         assert_eq!(1, vocab.len());
         assert_eq!(
-            1,
+            2,
             vocab
                 .get(&kw!(:db.schema/core))
                 .expect("core vocab")
@@ -360,3 +360,40 @@ fn test_fetch_attribute_values_for_entid() {
         ]
     );
 }
+
+#[test]
+fn test_excision_evicts_from_cache() {
+    let mut store = populate_db();
+    store
+        .cache(&kw!(:foo/bar), mentat::CacheDirection::Forward)
+        .expect("cached");
+
+    let entid = {
+        let mut write = store.begin_transaction().expect("began transaction");
+        let report = write
+            .transact(r#"[{:db/id "e" :foo/bar 300}]"#)
+            .expect("transaction expected to succeed");
+        write.commit().expect("committed");
+        *report.tempids.get("e").expect("allocated")
+    };
+    assert_eq!(
+        store
+            .lookup_value_for_attribute(entid, &kw!(:foo/bar))
+            .expect("looked up"),
+        Some(TypedValue::Long(300))
+    );
+
+    {
+        let mut write = store.begin_transaction().expect("began transaction");
+        write
+            .transact(format!("[{{:db/excise {}}}]", entid))
+            .expect("transaction expected to succeed");
+        write.commit().expect("committed");
+    }
+    assert_eq!(
+        store
+            .lookup_value_for_attribute(entid, &kw!(:foo/bar))
+            .expect("looked up"),
+        None
+    );
+}
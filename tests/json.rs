@@ -0,0 +1,178 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+#[macro_use]
+extern crate serde_json;
+
+extern crate mentat;
+
+use mentat::{Binding, MentatError, Queryable, Store, TypedValue};
+
+fn people() -> Store {
+    let mut store = Store::open("").expect("opened");
+    store
+        .transact(
+            r#"[{:db/ident :person/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+                 :db/unique :db.unique/identity :db/index true}
+                {:db/ident :person/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+                {:db/ident :person/height :db/valueType :db.type/double :db/cardinality :db.cardinality/one}
+                {:db/ident :person/born :db/valueType :db.type/instant :db/cardinality :db.cardinality/one}
+                {:db/ident :person/photo :db/valueType :db.type/bytes :db/cardinality :db.cardinality/one}
+                {:db/ident :person/role :db/valueType :db.type/keyword :db/cardinality :db.cardinality/one}
+                {:db/ident :person/friend :db/valueType :db.type/ref :db/cardinality :db.cardinality/many}]"#,
+        )
+        .expect("transacted schema");
+    store
+}
+
+#[test]
+fn test_transact_json() {
+    let mut store = people();
+    let report = store
+        .transact_json(
+            r#"[{":db/id": "alice", ":person/name": "Al\"ice", ":person/age": 30,
+                 ":person/height": 1.0, ":person/role": {"keyword": ":role/admin"},
+                 ":person/born": {"instant": "1990-05-17T12:30:00.000001Z"},
+                 ":person/photo": {"bytes": "AAEC"}},
+                [":db/add", "bob", ":person/name", "Bob"],
+                [":db/add", "bob", ":person/friend", "alice"]]"#,
+        )
+        .expect("transacted JSON");
+    let alice = report.tempids["alice"];
+
+    // A value read back and written out again is what went in.
+    let output = store
+        .q_once(
+            "[:find [?name ?age ?height ?role ?born ?photo] :in ?e
+              :where [?e :person/name ?name] [?e :person/age ?age] [?e :person/height ?height]
+                     [?e :person/role ?role] [?e :person/born ?born] [?e :person/photo ?photo]]",
+            mentat::QueryInputs::with_value_sequence(vec![(
+                mentat::Variable::from_valid_name("?e"),
+                TypedValue::Ref(alice),
+            )]),
+        )
+        .expect("queried");
+    assert_eq!(
+        output.to_json(),
+        json!({
+            "columns": ["?name", "?age", "?height", "?role", "?born", "?photo"],
+            "results": ["Al\"ice", 30, 1.0, {"keyword": ":role/admin"},
+                        {"instant": "1990-05-17T12:30:00.000001Z"}, {"bytes": "AAEC"}],
+        })
+    );
+
+    // Lookup refs and refs work where entities are expected.
+    store
+        .transact_json(&format!(
+            r#"[[":db/retract", {{"lookup-ref": [":person/name", "Bob"]}}, ":person/friend", {{"ref": {}}}],
+                [":db/add", {{"lookup-ref": [":person/name", "Bob"]}}, ":person/age", 40]]"#,
+            alice
+        ))
+        .expect("transacted lookup refs");
+    let friends = store
+        .q_once(
+            "[:find [?f ...] :where [?e :person/name \"Bob\"] [?e :person/friend ?f]]",
+            None,
+        )
+        .expect("queried");
+    assert_eq!(friends.results.to_json(), json!([]));
+    let age = store
+        .q_once(
+            "[:find ?age . :where [?e :person/name \"Bob\"] [?e :person/age ?age]]",
+            None,
+        )
+        .expect("queried");
+    assert_eq!(age.results.to_json(), json!(40));
+}
+
+#[test]
+fn test_transact_transit() {
+    let mut store = people();
+    store
+        .transact_transit(
+            r#"[["^ ", "~:db/id", "alice", "~:person/name", "~~alice", "~:person/role", "~:role/admin",
+                 "~:person/born", "~t1990-05-17T12:30:00.000001Z", "~:person/age", "~i30"],
+                ["^ ", "^0", "bob", "^1", "Bob", "~:person/friend", "alice"]]"#,
+        )
+        .expect("transacted Transit");
+
+    let output = store
+        .q_once(
+            "[:find ?name ?role ?born ?friend :where [?e :person/name ?name] [?e :person/role ?role]
+                                                     [?e :person/born ?born] [?b :person/friend ?e]
+                                                     [?b :person/name ?friend]]",
+            None,
+        )
+        .expect("queried");
+    assert_eq!(
+        output.to_transit(),
+        json!([
+            "^ ",
+            "~:columns",
+            ["?name", "?role", "?born", "?friend"],
+            "~:results",
+            [[
+                "~~alice",
+                "~:role/admin",
+                "~t1990-05-17T12:30:00.000001Z",
+                "Bob"
+            ]]
+        ])
+    );
+
+    let age = store
+        .q_once("[:find ?age . :where [_ :person/age ?age]]", None)
+        .expect("queried");
+    assert_eq!(
+        age.to_transit(),
+        json!(["^ ", "~:columns", ["?age"], "~:results", 30])
+    );
+}
+
+#[test]
+fn test_pull_json() {
+    let mut store = people();
+    store
+        .transact_json(r#"[{":person/name": "Alice", ":person/age": 30}]"#)
+        .expect("transacted");
+    let output = store
+        .q_once(
+            "[:find (pull ?e [:person/name :person/age]) . :where [?e :person/name _]]",
+            None,
+        )
+        .expect("queried");
+    assert_eq!(
+        output.results.to_json(),
+        json!({":person/name": "Alice", ":person/age": 30})
+    );
+    assert_eq!(
+        output.results.to_transit(),
+        json!(["^ ", "~:person/name", "Alice", "~:person/age", 30])
+    );
+    assert_eq!(Binding::Nil.to_json(), serde_json::Value::Null);
+}
+
+#[test]
+fn test_bad_json() {
+    let mut store = people();
+    match store.transact_json(r#"[{"person/name": "Alice"}]"#) {
+        Err(MentatError::BadJson(_)) => {}
+        result => panic!("expected a JSON error, got {:?}", result),
+    }
+    match store.transact_transit(r#"[["~:db/add", "e", "~:person/name", "~qx"]]"#) {
+        Err(MentatError::BadTransit(_)) => {}
+        result => panic!("expected a Transit error, got {:?}", result),
+    }
+    // Well-formed input still has to suit the schema.
+    match store.transact_json(r#"[[":db/add", "e", ":person/age", "forty"]]"#) {
+        Err(MentatError::DbError(_)) => {}
+        result => panic!("expected a schema error, got {:?}", result),
+    }
+}
@@ -26,8 +26,9 @@ use mentat_core::{DateTime, HasSchema, Utc, Uuid};
 use query_projector_traits::aggregates::SimpleAggregationOp;
 
 use mentat::{
-    new_connection, Binding, IntoResult, Keyword, PlainSymbol, QueryInputs, QueryResults,
-    Queryable, RelResult, Store, TxReport, TypedValue, Variable,
+    new_connection, Binding, CacheDirection, FulltextMarkup, IntoResult, Keyword, PlainSymbol,
+    QueryBuilder, QueryInputs, QueryPlan, QueryResults, Queryable, RelResult, Store, TxReport,
+    TypedValue, Variable,
 };
 
 use mentat::query::q_uncached;
@@ -36,8 +37,6 @@ use mentat::conn::Conn;
 
 use public_traits::errors::MentatError;
 
-use assert_approx_eq::assert_approx_eq;
-
 #[test]
 fn test_rel() {
     let mut c = new_connection("").expect("Couldn't open conn.");
@@ -56,7 +55,7 @@ fn test_rel() {
     let end = time::Instant::now();
 
     // This will need to change each time we add a default ident.
-    assert_eq!(40, results.len());
+    assert_eq!(48, results.len());
 
     // Every row is a pair of a Ref and a Keyword.
     if let QueryResults::Rel(rel) = results {
@@ -187,7 +186,7 @@ fn test_coll() {
     .results;
     let end = time::Instant::now();
 
-    assert_eq!(40, results.len());
+    assert_eq!(48, results.len());
 
     if let QueryResults::Coll(ref coll) = results {
         assert!(coll.iter().all(|item| item.matches_type(ValueType::Ref)));
@@ -471,7 +470,7 @@ fn test_fulltext() {
                 ) => {
                     assert_eq!(x, v);
                     assert_eq!(text.as_str(), "hello darkness my old friend");
-                    assert_approx_eq!(score, OrderedFloat(0.0f64));
+                    assert!(score > OrderedFloat(0.0f64));
                 }
                 _ => panic!("Unexpected results."),
             }
@@ -556,6 +555,463 @@ fn test_fulltext() {
     }
 }
 
+#[test]
+fn test_fulltext_ranking() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let mut conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :foo/fts :db/valueType :db.type/string :db/cardinality :db.cardinality/many
+         :db/index true :db/fulltext true}
+    ]"#,
+    )
+    .unwrap();
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/id "a" :foo/fts "A song about the sound of silence"}
+        {:db/id "b" :foo/fts "Silence, silence, silence!"}
+        {:db/id "c" :foo/fts "Nothing to see here"}
+    ]"#,
+    )
+    .unwrap();
+
+    // Better matches score higher, so we can order by relevance.
+    let r = conn
+        .q_once(
+            &c,
+            r#"[:find ?val ?score
+                :where [(fulltext $ :foo/fts "silence") [[_ ?val _ ?score]]]
+                :order (desc ?score)]"#,
+            None,
+        )
+        .expect("results")
+        .into_rel()
+        .expect("rel");
+    let values: Vec<Vec<Binding>> = r.into_iter().collect();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[0][0], "Silence, silence, silence!".into());
+    assert_eq!(values[1][0], "A song about the sound of silence".into());
+    match (&values[0][1], &values[1][1]) {
+        (
+            Binding::Scalar(TypedValue::Double(first)),
+            Binding::Scalar(TypedValue::Double(second)),
+        ) => {
+            assert!(first > second);
+            assert!(*second > OrderedFloat(0.0f64));
+        }
+        _ => panic!("Expected scores."),
+    }
+
+    // Snippets and highlights mark up the matching terms.
+    let r = conn
+        .q_once(
+            &c,
+            r#"[:find [?snippet ?highlight]
+                :where [(fulltext $ :foo/fts "sound") [[_ _ _ _ ?snippet ?highlight]]]]"#,
+            None,
+        )
+        .expect("results")
+        .into_tuple()
+        .expect("tuple");
+    assert_eq!(
+        r,
+        Some(vec![
+            "A song about the <b>sound</b> of silence".into(),
+            "A song about the <b>sound</b> of silence".into(),
+        ])
+    );
+
+    // Snippets are limited to a handful of tokens around the match.
+    conn.transact(
+        &mut c,
+        r#"[{:db/id "d" :foo/fts "one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen seventeen eighteen nineteen twenty"}]"#,
+    )
+    .unwrap();
+    let r = conn
+        .q_once(
+            &c,
+            r#"[:find ?snippet .
+                :where [(fulltext $ :foo/fts "nineteen") [[_ _ _ _ ?snippet]]]]"#,
+            None,
+        )
+        .expect("results")
+        .into_scalar()
+        .expect("scalar");
+    assert_eq!(
+        r,
+        Some("…five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen seventeen eighteen <b>nineteen</b> twenty".into())
+    );
+
+    // The markup can be chosen by the caller.
+    let mut inputs = QueryInputs::default();
+    inputs.set_fulltext_markup(FulltextMarkup {
+        open: "[".to_string(),
+        close: "]".to_string(),
+        ellipsis: "...".to_string(),
+    });
+    let r = conn
+        .q_once(
+            &c,
+            r#"[:find [?snippet ?highlight]
+                :where [(fulltext $ :foo/fts "nineteen") [[_ _ _ _ ?snippet ?highlight]]]]"#,
+            inputs,
+        )
+        .expect("results")
+        .into_tuple()
+        .expect("tuple");
+    assert_eq!(
+        r,
+        Some(vec![
+            "...five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen seventeen eighteen [nineteen] twenty".into(),
+            "one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen seventeen eighteen [nineteen] twenty".into(),
+        ])
+    );
+
+    // Scores are computed, not bound.
+    let inputs = QueryInputs::with_value_sequence(vec![(
+        Variable::from_valid_name("?score"),
+        TypedValue::Double(1.0.into()),
+    )]);
+    let r = conn.q_once(
+        &c,
+        r#"[:find ?val
+            :in ?score
+            :where [(fulltext $ :foo/fts "silence") [[_ ?val _ ?score]]]]"#,
+        inputs,
+    );
+    match r.expect_err("expected query to fail") {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::InvalidBinding(_, _),
+        ) => {}
+        e => panic!("Unexpected error {:?}.", e),
+    }
+}
+
+#[test]
+fn test_fulltext_tokenizers() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let mut conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :foo/plain :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/index true :db/fulltext true}
+        {:db/ident :foo/stemmed :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/index true :db/fulltext true :db.fulltext/tokenizer :db.fulltext.tokenizer/porter}
+        {:db/ident :foo/prefixed :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/index true :db/fulltext true :db.fulltext/prefix true}
+        {:db/ident :foo/trigram :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/index true :db/fulltext true :db.fulltext/tokenizer :db.fulltext.tokenizer/trigram}
+    ]"#,
+    )
+    .unwrap();
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/id "x" :foo/plain "running" :foo/stemmed "running" :foo/prefixed "running"
+         :foo/trigram "running"}
+    ]"#,
+    )
+    .unwrap();
+
+    let matches = |attribute: &str, search: &str| -> usize {
+        let query = format!(
+            r#"[:find [?e ...] :where [(fulltext $ {} "{}") [[?e]]]]"#,
+            attribute, search
+        );
+        conn.q_once(&c, query.as_str(), None)
+            .expect("results")
+            .into_coll()
+            .expect("coll")
+            .len()
+    };
+
+    // Each attribute only matches its own values.
+    assert_eq!(matches(":foo/plain", "running"), 1);
+    assert_eq!(matches(":foo/stemmed", "running"), 1);
+
+    // The default tokenizer matches whole words.
+    assert_eq!(matches(":foo/plain", "runs"), 0);
+    assert_eq!(matches(":foo/plain", "run*"), 1);
+
+    // Porter stemming matches other forms of the same word.
+    assert_eq!(matches(":foo/stemmed", "runs"), 1);
+    assert_eq!(matches(":foo/prefixed", "runs"), 0);
+
+    // Prefix indexes answer prefix queries.
+    assert_eq!(matches(":foo/prefixed", "ru*"), 1);
+
+    // Trigrams match substrings.
+    assert_eq!(matches(":foo/trigram", "unni"), 1);
+    assert_eq!(matches(":foo/plain", "unni"), 0);
+}
+
+#[test]
+fn test_fulltext_across_attributes() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let mut conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :note/title :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/index true :db/fulltext true}
+        {:db/ident :note/body :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/index true :db/fulltext true}
+        {:db/ident :note/comment :db/valueType :db.type/string :db/cardinality :db.cardinality/many
+         :db/index true :db/fulltext true :db.fulltext/tokenizer :db.fulltext.tokenizer/porter}
+        {:db/ident :note/tag :db/valueType :db.type/string :db/cardinality :db.cardinality/many}
+    ]"#,
+    )
+    .unwrap();
+    let report = conn
+        .transact(
+            &mut c,
+            r#"[
+        {:db/id "a" :note/title "Shopping" :note/body "Buy apples and pears" :note/tag "apples"}
+        {:db/id "b" :note/title "Apples" :note/body "Nothing much"}
+        {:db/id "c" :note/title "Orchard" :note/comment "Picked apples"}
+    ]"#,
+        )
+        .unwrap();
+    let a = *report.tempids.get("a").unwrap();
+    let b = *report.tempids.get("b").unwrap();
+    let c_ = *report.tempids.get("c").unwrap();
+
+    let schema = conn.current_schema();
+
+    let title = schema.get_entid(&kw!(:note/title)).unwrap().0;
+    let body = schema.get_entid(&kw!(:note/body)).unwrap().0;
+    let comment = schema.get_entid(&kw!(:note/comment)).unwrap().0;
+
+    let search = |query: &str| -> Vec<Vec<Binding>> {
+        let mut results: Vec<Vec<Binding>> = conn
+            .q_once(&c, query, None)
+            .expect("results")
+            .into_rel()
+            .expect("rel")
+            .into_iter()
+            .collect();
+        results.sort_by_key(|row| format!("{:?}", row));
+        results
+    };
+    let row = |e: Entid, a: Entid| {
+        vec![
+            Binding::Scalar(TypedValue::Ref(e)),
+            Binding::Scalar(TypedValue::Ref(a)),
+        ]
+    };
+
+    // `:any` searches every fulltext attribute, whatever its tokenizer, and binds the attribute
+    // that matched.  Non-fulltext attributes aren't searched.
+    let mut expected = vec![row(a, body), row(b, title), row(c_, comment)];
+    expected.sort_by_key(|row| format!("{:?}", row));
+    assert_eq!(
+        search(r#"[:find ?e ?a :where [(fulltext $ :any "apples") [[?e _ _ _ _ _ ?a]]]]"#),
+        expected
+    );
+
+    // A vector restricts the search to the given attributes.
+    let mut expected = vec![row(a, body), row(b, title)];
+    expected.sort_by_key(|row| format!("{:?}", row));
+    assert_eq!(
+        search(
+            r#"[:find ?e ?a :where [(fulltext $ [:note/title :note/body] "apples") [[?e _ _ _ _ _ ?a]]]]"#
+        ),
+        expected
+    );
+    assert_eq!(
+        search(
+            r#"[:find ?e ?a :where [(fulltext $ [:note/title :note/comment] "apples") [[?e _ _ _ _ _ ?a]]]]"#
+        ),
+        {
+            let mut expected = vec![row(b, title), row(c_, comment)];
+            expected.sort_by_key(|row| format!("{:?}", row));
+            expected
+        }
+    );
+
+    // Each attribute is searched with its own tokenizer.
+    assert_eq!(
+        search(r#"[:find ?e ?a :where [(fulltext $ :any "apple") [[?e _ _ _ _ _ ?a]]]]"#),
+        vec![row(c_, comment)]
+    );
+
+    // The matched attribute joins like any other binding.
+    assert_eq!(
+        search(
+            r#"[:find ?e ?v :where [(fulltext $ :any "apples") [[?e ?v _ _ _ _ ?a]]] [?a :db/ident :note/title]]"#
+        ),
+        vec![vec![Binding::Scalar(TypedValue::Ref(b)), "Apples".into()]]
+    );
+}
+
+#[test]
+fn test_string_predicates() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let mut conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :foo/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/index true}
+        {:db/ident :foo/tag :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/count :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/uuid :db/valueType :db.type/uuid :db/cardinality :db.cardinality/one}
+    ]"#,
+    )
+    .unwrap();
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/id "a" :foo/name "Apple pie" :foo/tag "pie" :foo/count 1}
+        {:db/id "b" :foo/name "apricot" :foo/tag "apr" :foo/count 2}
+        {:db/id "c" :foo/name "banana*split" :foo/tag "" :foo/count 3
+         :foo/uuid #uuid "550e8400-e29b-41d4-a716-446655440000"}
+    ]"#,
+    )
+    .unwrap();
+
+    let names = |predicate: &str| -> Vec<String> {
+        let query = format!(
+            r#"[:find [?name ...] :where [?e :foo/name ?name] [?e :foo/tag ?tag] [({})]]"#,
+            predicate
+        );
+        let mut names: Vec<String> = conn
+            .q_once(&c, query.as_str(), None)
+            .expect("results")
+            .into_coll()
+            .expect("coll")
+            .into_iter()
+            .map(|name| name.into_string().expect("string").to_string())
+            .collect();
+        names.sort();
+        names
+    };
+
+    // Matching is case-sensitive.
+    assert_eq!(names(r#"starts-with? ?name "ap""#), vec!["apricot"]);
+    assert_eq!(names(r#"starts-with? ?name "Ap""#), vec!["Apple pie"]);
+    assert_eq!(names(r#"starts-with? ?name """#).len(), 3);
+    assert_eq!(names(r#"ends-with? ?name "pie""#), vec!["Apple pie"]);
+    assert_eq!(
+        names(r#"includes? ?name "p""#),
+        vec!["Apple pie", "apricot", "banana*split"]
+    );
+
+    // GLOB metacharacters are matched literally.
+    assert_eq!(names(r#"includes? ?name "*""#), vec!["banana*split"]);
+    assert_eq!(names(r#"includes? ?name "a?""#), Vec::<String>::new());
+
+    // The right-hand side can be bound by the query.
+    assert_eq!(
+        names("starts-with? ?name ?tag"),
+        vec!["apricot", "banana*split"]
+    );
+    assert_eq!(
+        names("ends-with? ?name ?tag"),
+        vec!["Apple pie", "banana*split"]
+    );
+    assert_eq!(
+        names("includes? ?name ?tag"),
+        vec!["Apple pie", "apricot", "banana*split"]
+    );
+
+    assert_eq!(names(r#"re-find ?name "^[a-z]+$""#), vec!["apricot"]);
+    assert_eq!(
+        names(r#"re-find ?name "(?i)^a""#),
+        vec!["Apple pie", "apricot"]
+    );
+    assert_eq!(
+        names(r#"equals-ignore-case? ?name "APRICOT""#),
+        vec!["apricot"]
+    );
+
+    // Bound inputs work too.
+    let inputs = QueryInputs::with_value_sequence(vec![(
+        Variable::from_valid_name("?prefix"),
+        "ban".into(),
+    )]);
+    let results = conn
+        .q_once(
+            &c,
+            r#"[:find ?name . :in ?prefix :where [_ :foo/name ?name] [(starts-with? ?name ?prefix)]]"#,
+            inputs,
+        )
+        .expect("results")
+        .into_scalar()
+        .expect("scalar");
+    assert_eq!(results, Some(Binding::Scalar("banana*split".into())));
+
+    // String predicates only apply to strings.
+    let results = conn
+        .q_once(
+            &c,
+            r#"[:find ?e :where [?e :foo/count ?n] [(starts-with? ?n "1")]]"#,
+            None,
+        )
+        .expect("results")
+        .into_rel()
+        .expect("rel");
+    assert!(results.is_empty());
+
+    // Even with the empty prefix, which every string has.
+    let results = conn
+        .q_once(
+            &c,
+            r#"[:find [?v ...] :where [_ _ ?v] [(starts-with? ?v "")]]"#,
+            None,
+        )
+        .expect("results")
+        .into_coll()
+        .expect("coll");
+    assert!(!results.is_empty());
+    assert!(results
+        .iter()
+        .all(|v| v.value_type() == Some(ValueType::String)));
+
+    let r = conn.q_once(
+        &c,
+        r#"[:find ?e :where [?e :foo/name ?name] [(starts-with? ?name 1)]]"#,
+        None,
+    );
+    match r.expect_err("expected query to fail") {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::InvalidArgumentType(
+                PlainSymbol(s),
+                ty,
+                i,
+            ),
+        ) => {
+            assert_eq!(s, "starts-with?");
+            assert_eq!(ty, ValueTypeSet::of_one(ValueType::String));
+            assert_eq!(i, 1);
+        }
+        _ => panic!("Expected query to fail."),
+    }
+
+    // `equals-ignore-case?` only folds ASCII letters.
+    conn.transact(&mut c, r#"[{:foo/name "École"}]"#).unwrap();
+    let equal_ignoring_case = |other: &str| -> Vec<Binding> {
+        let query = format!(
+            r#"[:find [?name ...] :where [_ :foo/name ?name] [(equals-ignore-case? ?name "{}")]]"#,
+            other
+        );
+        conn.q_once(&c, query.as_str(), None)
+            .expect("results")
+            .into_coll()
+            .expect("coll")
+    };
+    assert_eq!(equal_ignoring_case("ÉCOLE"), vec![Binding::from("École")]);
+    assert!(equal_ignoring_case("école").is_empty());
+    assert!(equal_ignoring_case("éCOLE").is_empty());
+}
+
 #[test]
 fn test_instant_range_query() {
     let mut c = new_connection("").expect("Couldn't open conn.");
@@ -801,8 +1257,8 @@ fn test_aggregates_type_handling() {
         .expect("results")
         .unwrap();
 
-    // Yes, the current version is in the store as a Long!
-    let total = 30i64 + 20i64 + 10i64 + ::mentat_db::db::CURRENT_VERSION as i64;
+    // Yes, the version of the core vocabulary is in the store as a Long!
+    let total = 30i64 + 20i64 + 10i64 + ::mentat_db::CORE_SCHEMA_VERSION as i64;
     assert_eq!(Binding::Scalar(TypedValue::Long(total)), r);
 
     let r = store
@@ -1974,3 +2430,941 @@ fn test_encrypted() {
     // so the specific test we use doesn't matter that much.
     run_tx_data_test(Store::open_with_key("", "secret").expect("opened"));
 }
+
+#[test]
+fn test_expression_functions() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let mut conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :foo/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/count :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/weight :db/valueType :db.type/double :db/cardinality :db.cardinality/one}
+    ]"#,
+    )
+    .unwrap();
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :foo/a :foo/name "apple" :foo/count 7 :foo/weight 1.5}
+        {:db/ident :foo/z :foo/name "zero" :foo/count 0 :foo/weight 2.0}
+    ]"#,
+    )
+    .unwrap();
+
+    let value = |function: &str| -> Option<Binding> {
+        let query = format!(
+            r#"[:find ?x . :where [:foo/a :foo/name ?name] [:foo/a :foo/count ?count]
+                                  [:foo/a :foo/weight ?weight] [({}) ?x]]"#,
+            function
+        );
+        conn.q_once(&c, query.as_str(), None)
+            .expect("results")
+            .into_scalar()
+            .expect("scalar")
+    };
+
+    // Longs stay longs; anything involving a double is a double.
+    assert_eq!(value("+ ?count 1"), Some(TypedValue::Long(8).into()));
+    assert_eq!(value("- ?count"), Some(TypedValue::Long(-7).into()));
+    assert_eq!(
+        value("* ?count ?count 2"),
+        Some(TypedValue::Long(98).into())
+    );
+    assert_eq!(
+        value("+ ?count ?weight"),
+        Some(TypedValue::Double(8.5.into()).into())
+    );
+    assert_eq!(
+        value("/ ?count 2"),
+        Some(TypedValue::Double(3.5.into()).into())
+    );
+    assert_eq!(value("quot ?count 2"), Some(TypedValue::Long(3).into()));
+    assert_eq!(value("quot -7 2"), Some(TypedValue::Long(-3).into()));
+    assert_eq!(value("mod -7 2"), Some(TypedValue::Long(1).into()));
+    assert_eq!(value("mod ?count -2"), Some(TypedValue::Long(-1).into()));
+
+    // Arithmetic on longs fails, rather than overflowing into a double.
+    assert_eq!(
+        value("+ ?count 9223372036854775800"),
+        Some(TypedValue::Long(i64::MAX).into())
+    );
+    let overflow = |function: &str| -> String {
+        let query = format!(
+            r#"[:find ?x . :where [:foo/a :foo/count ?count] [({}) ?x]]"#,
+            function
+        );
+        conn.q_once(&c, query.as_str(), None)
+            .expect_err("overflowed")
+            .to_string()
+    };
+    assert!(overflow("+ 9223372036854775807 1").contains("integer overflow"));
+    assert!(overflow("+ ?count 9223372036854775801").contains("integer overflow"));
+    assert!(overflow("- -9223372036854775807 ?count").contains("integer overflow"));
+    assert!(overflow("* ?count 2000000000000000000").contains("integer overflow"));
+    assert!(overflow("- -9223372036854775808").contains("integer overflow"));
+
+    assert_eq!(value(r#"str ?name "-" ?count"#), Some("apple-7".into()));
+    assert_eq!(value("str ?count"), Some("7".into()));
+    assert_eq!(value("subs ?name 1"), Some("pple".into()));
+    assert_eq!(value("subs ?name 1 3"), Some("pp".into()));
+    assert_eq!(value("subs ?name 1 10"), Some("pple".into()));
+    assert_eq!(value("subs ?name ?count 2"), Some("".into()));
+    assert_eq!(value("count ?name"), Some(TypedValue::Long(5).into()));
+
+    // Results can be used by later clauses.
+    let results = conn
+        .q_once(
+            &c,
+            r#"[:find [?name ...]
+                :where [?e :foo/name ?name] [?e :foo/count ?count]
+                       [(* ?count 2) ?double] [(> ?double 10)]]"#,
+            None,
+        )
+        .expect("results")
+        .into_coll()
+        .expect("coll");
+    assert_eq!(results, vec!["apple".into()]);
+
+    // Binding a variable that's already bound constrains it.
+    let results = conn
+        .q_once(
+            &c,
+            r#"[:find [?name ...]
+                :where [?e :foo/name ?name] [?e :foo/count ?count] [(count ?name) ?count]]"#,
+            None,
+        )
+        .expect("results")
+        .into_coll()
+        .expect("coll");
+    assert_eq!(results, Vec::<Binding>::new());
+
+    // Dividing by zero doesn't match.
+    let results = conn
+        .q_once(
+            &c,
+            r#"[:find [?name ...]
+                :where [?e :foo/name ?name] [?e :foo/count ?count] [(quot 10 ?count) ?x]]"#,
+            None,
+        )
+        .expect("results")
+        .into_coll()
+        .expect("coll");
+    assert_eq!(results, vec!["apple".into()]);
+
+    // A constant range can't end before it starts.
+    let r = conn.q_once(
+        &c,
+        r#"[:find ?x :where [:foo/a :foo/name ?name] [(subs ?name 3 1) ?x]]"#,
+        None,
+    );
+    match r.expect_err("expected query to fail") {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::InvalidArgument(PlainSymbol(s), _, 2),
+        ) => {
+            assert_eq!(s, "subs");
+        }
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    // Arguments must be bound.
+    let r = conn.q_once(&c, r#"[:find ?x :where [(+ ?y 1) ?x]]"#, None);
+    match r.expect_err("expected query to fail") {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::UnboundVariable(PlainSymbol(s)),
+        ) => {
+            assert_eq!(s, "?y");
+        }
+        e => panic!("Unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_optional_attributes() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let mut conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :foo/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/nickname :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/bio :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/fulltext true :db/index true}
+        {:db/ident :foo/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/tag :db/valueType :db.type/string :db/cardinality :db.cardinality/many}
+    ]"#,
+    )
+    .unwrap();
+    conn.transact(
+        &mut c,
+        r#"[
+        {:foo/name "Alice" :foo/nickname "Al" :foo/bio "Writes code." :foo/age 30}
+        {:foo/name "Bob" :foo/age 40}
+        {:foo/name "Carol" :foo/bio "Reads books."}
+    ]"#,
+    )
+    .unwrap();
+
+    let schema = conn.current_schema();
+    let nickname = schema.get_entid(&kw!(:foo/nickname)).unwrap().0;
+    let bio = schema.get_entid(&kw!(:foo/bio)).unwrap().0;
+
+    let rel = |query: &str| -> Vec<Vec<TypedValue>> {
+        let mut rows = conn
+            .q_once(&c, query, None)
+            .expect("results")
+            .into_rel()
+            .expect("rel")
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|b| b.into_scalar().expect("scalar"))
+                    .collect()
+            })
+            .collect::<Vec<Vec<TypedValue>>>();
+        rows.sort();
+        rows
+    };
+
+    assert_eq!(
+        rel(r#"[:find ?name ?nick
+                :where [?e :foo/name ?name] [(get-else $ ?e :foo/nickname "-") ?nick]]"#),
+        vec![
+            vec!["Alice".into(), "Al".into()],
+            vec!["Bob".into(), "-".into()],
+            vec!["Carol".into(), "-".into()],
+        ]
+    );
+
+    assert_eq!(
+        rel(r#"[:find ?name ?age
+                :where [?e :foo/name ?name] [(get-else $ ?e :foo/age 0) ?age]]"#),
+        vec![
+            vec!["Alice".into(), TypedValue::Long(30)],
+            vec!["Bob".into(), TypedValue::Long(40)],
+            vec!["Carol".into(), TypedValue::Long(0)],
+        ]
+    );
+
+    // Fulltext values are looked up by their text.
+    assert_eq!(
+        rel(r#"[:find ?name ?bio
+                :where [?e :foo/name ?name] [(get-else $ ?e :foo/bio "") ?bio]]"#),
+        vec![
+            vec!["Alice".into(), "Writes code.".into()],
+            vec!["Bob".into(), "".into()],
+            vec!["Carol".into(), "Reads books.".into()],
+        ]
+    );
+
+    assert_eq!(
+        rel(r#"[:find ?name ?a ?v
+                :where [?e :foo/name ?name] [(get-some $ ?e :foo/nickname :foo/bio) [?a ?v]]]"#),
+        vec![
+            vec!["Alice".into(), TypedValue::Ref(nickname), "Al".into()],
+            vec!["Carol".into(), TypedValue::Ref(bio), "Reads books.".into()],
+        ]
+    );
+
+    assert_eq!(
+        rel(r#"[:find ?name ?v
+                :where [?e :foo/name ?name] [(get-some $ ?e :foo/age :foo/nickname) [_ ?v]]]"#),
+        vec![
+            vec!["Alice".into(), TypedValue::Long(30)],
+            vec!["Bob".into(), TypedValue::Long(40)],
+        ]
+    );
+
+    assert_eq!(
+        rel(r#"[:find ?name :where [?e :foo/name ?name] [(missing? $ ?e :foo/age)]]"#),
+        vec![vec!["Carol".into()]]
+    );
+
+    // Cardinality-many attributes don't have a single value to fall back from.
+    let r = conn.q_once(
+        &c,
+        r#"[:find ?t :where [?e :foo/name _] [(get-else $ ?e :foo/tag "") ?t]]"#,
+        None,
+    );
+    match r.expect_err("expected query to fail") {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::InvalidArgument(
+                PlainSymbol(s),
+                ty,
+                i,
+            ),
+        ) => {
+            assert_eq!(s, "get-else");
+            assert_eq!(ty, "cardinality-one attribute");
+            assert_eq!(i, 2);
+        }
+        e => panic!("Unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_optional_clauses() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let mut conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/ident :foo/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/nickname :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+        {:db/ident :foo/tag :db/valueType :db.type/keyword :db/cardinality :db.cardinality/many}
+        {:db/ident :foo/friend :db/valueType :db.type/ref :db/cardinality :db.cardinality/one}
+    ]"#,
+    )
+    .unwrap();
+    conn.transact(
+        &mut c,
+        r#"[
+        {:db/id "a" :foo/name "Alice" :foo/nickname "Al" :foo/age 30 :foo/tag [:tag/x :tag/y]}
+        {:db/id "b" :foo/name "Bob" :foo/age 40 :foo/friend "a"}
+        {:foo/name "Carol" :foo/friend "b"}
+    ]"#,
+    )
+    .unwrap();
+
+    let rel = |query: &str| -> Vec<Vec<Option<TypedValue>>> {
+        let mut rows = conn
+            .q_once(&c, query, None)
+            .expect("results")
+            .into_rel()
+            .expect("rel")
+            .into_iter()
+            .map(|row| row.into_iter().map(|b| b.into_scalar()).collect())
+            .collect::<Vec<Vec<Option<TypedValue>>>>();
+        rows.sort();
+        rows
+    };
+
+    assert_eq!(
+        rel(r#"[:find ?name ?nick
+                :where [?e :foo/name ?name] (optional [?e :foo/nickname ?nick])]"#),
+        vec![
+            vec![Some("Alice".into()), Some("Al".into())],
+            vec![Some("Bob".into()), None],
+            vec![Some("Carol".into()), None],
+        ]
+    );
+
+    // Every clause must match for any of them to.
+    assert_eq!(
+        rel(r#"[:find ?name ?age
+                :where [?e :foo/name ?name] (optional [?e :foo/age ?age] [(> ?age 35)])]"#),
+        vec![
+            vec![Some("Alice".into()), None],
+            vec![Some("Bob".into()), Some(TypedValue::Long(40))],
+            vec![Some("Carol".into()), None],
+        ]
+    );
+
+    assert_eq!(
+        rel(r#"[:find ?name ?tag
+                :where [?e :foo/name ?name] (optional [?e :foo/tag ?tag])]"#),
+        vec![
+            vec![
+                Some("Alice".into()),
+                Some(TypedValue::typed_ns_keyword("tag", "x"))
+            ],
+            vec![
+                Some("Alice".into()),
+                Some(TypedValue::typed_ns_keyword("tag", "y"))
+            ],
+            vec![Some("Bob".into()), None],
+            vec![Some("Carol".into()), None],
+        ]
+    );
+
+    // Optional clauses can follow on from one another.
+    assert_eq!(
+        rel(r#"[:find ?name ?friend ?nick
+                :where [?e :foo/name ?name]
+                       (optional [?e :foo/friend ?f] [?f :foo/name ?friend])
+                       (optional [?f :foo/nickname ?nick])]"#),
+        vec![
+            vec![Some("Alice".into()), None, None],
+            vec![Some("Bob".into()), Some("Alice".into()), Some("Al".into())],
+            vec![Some("Carol".into()), Some("Bob".into()), None],
+        ]
+    );
+
+    // Values of unknown type.
+    assert_eq!(
+        rel(r#"[:find ?name ?v
+                :where [?e :foo/name ?name]
+                       (optional [?e :foo/friend ?f] [?f ?a ?v] [?a :db/ident :foo/age])]"#),
+        vec![
+            vec![Some("Alice".into()), None],
+            vec![Some("Bob".into()), Some(TypedValue::Long(30))],
+            vec![Some("Carol".into()), Some(TypedValue::Long(40))],
+        ]
+    );
+
+    assert_eq!(
+        rel(r#"[:find ?name (count ?tag)
+                :where [?e :foo/name ?name] (optional [?e :foo/tag ?tag])]"#),
+        vec![
+            vec![Some("Alice".into()), Some(TypedValue::Long(2))],
+            vec![Some("Bob".into()), Some(TypedValue::Long(0))],
+            vec![Some("Carol".into()), Some(TypedValue::Long(0))],
+        ]
+    );
+
+    // Optional clauses must bind something to join against.
+    let r = conn.q_once(
+        &c,
+        r#"[:find ?age :where [?e :foo/name _] (optional [(get-else $ ?e :foo/age 0) ?age])]"#,
+        None,
+    );
+    match r.expect_err("expected query to fail") {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::NoPatternInOptionalClause,
+        ) => {}
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    // ... and can't bind constants, which would have a value even when they don't match.
+    let r = conn.q_once(
+        &c,
+        r#"[:find ?g :where [?e :foo/name _] (optional [?e :foo/age _] [(ground 1) ?g])]"#,
+        None,
+    );
+    match r.expect_err("expected query to fail") {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::ConstantInOptionalClause(
+                PlainSymbol(s),
+            ),
+        ) => assert_eq!(s, "?g"),
+        e => panic!("Unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_delta_source() {
+    let mut store = Store::open("").expect("opened");
+    store
+        .transact(
+            r#"[
+        {:db/ident :project/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :project/completed :db/valueType :db.type/boolean :db/cardinality :db.cardinality/one}
+        {:db/ident :task/project :db/valueType :db.type/ref :db/cardinality :db.cardinality/one}
+    ]"#,
+        )
+        .expect("transacted schema");
+    let report = store
+        .transact(
+            r#"[
+        {:db/id "done" :project/name "done" :project/completed true}
+        {:db/id "open" :project/name "open" :project/completed false}
+    ]"#,
+        )
+        .expect("transacted projects");
+    let done = report.tempids.get("done").cloned().expect("done");
+    let open = report.tempids.get("open").cloned().expect("open");
+
+    let report = store
+        .transact(&format!(
+            "[[:db/add \"t1\" :task/project {}] [:db/add \"t2\" :task/project {}]]",
+            done, open
+        ))
+        .expect("transacted tasks");
+    let t1 = report.tempids.get("t1").cloned().expect("t1");
+    let t2 = report.tempids.get("t2").cloned().expect("t2");
+    let tasks_tx = report.tx_id;
+
+    // Tasks added to completed projects by the transaction.
+    let query =
+        r#"[:find [?t ...] :where [$delta ?t :task/project ?p] [?p :project/completed true]]"#;
+    let results = store
+        .q_once(query, QueryInputs::for_delta(vec![tasks_tx]))
+        .into_coll_result()
+        .expect("coll");
+    assert_eq!(results, vec![TypedValue::Ref(t1).into()]);
+
+    // Earlier transactions aren't part of the delta.
+    let results = store
+        .q_once(
+            r#"[:find [?p ...] :where [$delta ?p :project/completed _]]"#,
+            QueryInputs::for_delta(vec![tasks_tx]),
+        )
+        .into_coll_result()
+        .expect("coll");
+    assert!(results.is_empty());
+
+    // Retractions are part of the delta, and `tx-data` tells them apart.
+    let report = store
+        .transact(&format!("[[:db/retract {} :task/project {}]]", t2, open))
+        .expect("retracted");
+    let query = r#"[:find ?e ?added :where [(tx-data $delta) [[?e ?a _ _ ?added]]] [?a :db/ident :task/project]]"#;
+    let mut rows = store
+        .q_once(query, QueryInputs::for_delta(vec![tasks_tx, report.tx_id]))
+        .into_rel_result()
+        .expect("rel")
+        .into_iter()
+        .map(|row| row.into_iter().map(|b| b.into_scalar()).collect())
+        .collect::<Vec<Vec<Option<TypedValue>>>>();
+    rows.sort();
+    let mut expected = vec![
+        vec![Some(TypedValue::Ref(t1)), Some(TypedValue::Boolean(true))],
+        vec![Some(TypedValue::Ref(t2)), Some(TypedValue::Boolean(true))],
+        vec![Some(TypedValue::Ref(t2)), Some(TypedValue::Boolean(false))],
+    ];
+    expected.sort();
+    assert_eq!(rows, expected);
+
+    // An empty delta matches nothing.
+    let results = store
+        .q_once(
+            r#"[:find [?t ...] :where [$delta ?t :task/project _]]"#,
+            QueryInputs::for_delta(vec![]),
+        )
+        .into_coll_result()
+        .expect("coll");
+    assert!(results.is_empty());
+
+    // Within a transaction, the delta is what has been transacted so far.
+    let mut in_progress = store.begin_transaction().expect("began");
+    let report = in_progress
+        .transact(format!("[[:db/add \"t3\" :task/project {}]]", done))
+        .expect("transacted");
+    let t3 = report.tempids.get("t3").cloned().expect("t3");
+    let results = in_progress
+        .q_once(
+            r#"[:find [?t ...] :where [$delta ?t :task/project _]]"#,
+            None,
+        )
+        .into_coll_result()
+        .expect("coll");
+    assert_eq!(results, vec![TypedValue::Ref(t3).into()]);
+    in_progress.rollback().expect("rolled back");
+
+    // Without a delta, `$delta` is unknown.
+    match store
+        .q_once(
+            r#"[:find [?t ...] :where [$delta ?t :task/project _]]"#,
+            None,
+        )
+        .expect_err("expected query to fail")
+    {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::UnknownSource(s),
+        ) => assert_eq!(s, "delta"),
+        e => panic!("Unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn test_attached_stores() {
+    let path = std::env::temp_dir().join(format!("mentat-attached-{}.db", std::process::id()));
+    let path = path.to_str().expect("path").to_string();
+    let _ = std::fs::remove_file(&path);
+
+    let profile_tx = {
+        let mut profile = Store::open(&path).expect("opened profile");
+        profile
+            .transact(
+                r#"[
+            {:db/ident :profile/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+            {:db/ident :profile/email :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        ]"#,
+            )
+            .expect("transacted schema");
+        profile
+            .transact(
+                r#"[
+            {:profile/name "Ann" :profile/email "ann@example.com"}
+            {:profile/name "Bob" :profile/email "bob@example.com"}
+        ]"#,
+            )
+            .expect("transacted profiles")
+            .tx_id
+    };
+
+    let mut store = Store::open("").expect("opened");
+    store
+        .transact(
+            r#"[
+        {:db/ident :contact/email :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :contact/phone :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+    ]"#,
+        )
+        .expect("transacted schema");
+    store
+        .transact(
+            r#"[
+        {:contact/email "ann@example.com" :contact/phone "555-1234"}
+        {:contact/email "cat@example.com" :contact/phone "555-9876"}
+    ]"#,
+        )
+        .expect("transacted contacts");
+
+    store.attach("work", &path).expect("attached");
+    assert_eq!(store.attached(), vec!["work"]);
+
+    // The attached store's attributes aren't ours.
+    let query = r#"[:find ?name ?phone
+                    :where [$work ?p :profile/email ?email]
+                           [$work ?p :profile/name ?name]
+                           [?c :contact/email ?email]
+                           [?c :contact/phone ?phone]]"#;
+    let results = store
+        .q_once(query, None)
+        .into_rel_result()
+        .expect("rel")
+        .into_iter()
+        .map(|row| row.into_iter().map(|b| b.into_scalar()).collect())
+        .collect::<Vec<Vec<Option<TypedValue>>>>();
+    assert_eq!(
+        results,
+        vec![vec![Some("Ann".into()), Some("555-1234".into())]]
+    );
+
+    let query = r#"[:find [?name ...]
+                    :where (or [$work ?p :profile/email "ann@example.com"]
+                               [$work ?p :profile/email "bob@example.com"])
+                           [$work ?p :profile/name ?name]]"#;
+    let mut results = store.q_once(query, None).into_coll_result().expect("coll");
+    results.sort_by_key(|b| format!("{:?}", b));
+    assert_eq!(results, vec!["Ann".into(), "Bob".into()]);
+
+    let query = format!(
+        "[:find (count ?e) . :where [(tx-data $work {}) [[?e]]]]",
+        profile_tx
+    );
+    let count = store
+        .q_once(query.as_str(), None)
+        .into_scalar_result()
+        .expect("scalar");
+    // Two profiles, and the transaction itself.
+    assert_eq!(count, Some(TypedValue::Long(3).into()));
+
+    // Queries made through reads, transactions, and the query builder see the attached store too.
+    let query = r#"[:find ?name .
+                    :in ?email
+                    :where [$work ?p :profile/email ?email]
+                           [$work ?p :profile/name ?name]]"#;
+    let name = QueryBuilder::new(&mut store, query)
+        .bind_value("?email", "bob@example.com")
+        .execute_scalar()
+        .expect("scalar");
+    assert_eq!(name, Some("Bob".into()));
+
+    let inputs = || {
+        QueryInputs::with_value_sequence(vec![(
+            Variable::from_valid_name("?email"),
+            "ann@example.com".into(),
+        )])
+    };
+    let name = store
+        .begin_read()
+        .expect("read")
+        .q_once(query, inputs())
+        .into_scalar_result()
+        .expect("scalar");
+    assert_eq!(name, Some("Ann".into()));
+    let name = store
+        .begin_transaction()
+        .expect("transaction")
+        .q_once(query, inputs())
+        .into_scalar_result()
+        .expect("scalar");
+    assert_eq!(name, Some("Ann".into()));
+
+    match store
+        .attach("work", &path)
+        .expect_err("expected attach to fail")
+    {
+        MentatError::StoreAlreadyAttached(name) => assert_eq!(name, "work"),
+        e => panic!("Unexpected error {:?}", e),
+    }
+    match store
+        .attach("delta", &path)
+        .expect_err("expected attach to fail")
+    {
+        MentatError::InvalidAttachedStoreName(name) => assert_eq!(name, "delta"),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    store.detach("work").expect("detached");
+    match store
+        .q_once(r#"[:find ?p :where [$work ?p :profile/name _]]"#, None)
+        .expect_err("expected query to fail")
+    {
+        MentatError::AlgebrizerError(
+            query_algebrizer_traits::errors::AlgebrizerError::UnknownSource(s),
+        ) => assert_eq!(s, "work"),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_planned_queries() {
+    let mut store = Store::open("").expect("opened");
+    store
+        .transact(
+            r#"[
+        {:db/ident :item/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one :db/index true}
+        {:db/ident :item/tag :db/valueType :db.type/string :db/cardinality :db.cardinality/many}
+        {:db/ident :item/owner :db/valueType :db.type/ref :db/cardinality :db.cardinality/one}
+    ]"#,
+        )
+        .expect("transacted schema");
+    store
+        .transact(
+            r#"[
+        {:db/id "o" :item/name "owner"}
+        {:item/name "a" :item/tag ["x" "y"] :item/owner "o"}
+        {:item/name "b" :item/tag ["x"] :item/owner "o"}
+        {:item/name "c" :item/tag ["y"]}
+    ]"#,
+        )
+        .expect("transacted items");
+
+    let queries = [
+        r#"[:find ?n :where [?e :item/tag "x"] [?e :item/name ?n]]"#,
+        r#"[:find ?n ?o :where [?e :item/tag "y"] [?e :item/name ?n] (optional [?e :item/owner ?p] [?p :item/name ?o])]"#,
+        r#"[:find ?n :where [?e :item/tag _] [?e :item/name ?n] (not [?e :item/owner _])]"#,
+        r#"[:find ?n :where [?e :item/owner ?o] [?o :item/name "owner"] [?e :item/name ?n]]"#,
+    ];
+    let run = |store: &Store| -> Vec<Vec<Vec<Option<TypedValue>>>> {
+        queries
+            .iter()
+            .map(|query| {
+                let mut rows = store
+                    .q_once(query, None)
+                    .into_rel_result()
+                    .expect("rel")
+                    .into_iter()
+                    .map(|row| row.into_iter().map(|b| b.into_scalar()).collect())
+                    .collect::<Vec<Vec<Option<TypedValue>>>>();
+                rows.sort();
+                rows
+            })
+            .collect()
+    };
+
+    let unplanned = run(&store);
+    assert!(store.conn().current_statistics().is_none());
+
+    store.analyze().expect("analyzed");
+    let statistics = store.conn().current_statistics().expect("statistics");
+    let tag = store
+        .conn()
+        .current_schema()
+        .get_entid(&kw!(:item/tag))
+        .expect("tag")
+        .0;
+    let tags = statistics.attributes.get(&tag).expect("tag statistics");
+    assert_eq!((tags.datoms, tags.entities, tags.values), (4, 3, 2));
+
+    assert_eq!(run(&store), unplanned);
+}
+
+#[test]
+fn test_explain() {
+    let mut store = Store::open("").expect("opened");
+    store
+        .transact(
+            r#"[
+        {:db/ident :item/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one :db/index true :db/unique :db.unique/identity}
+        {:db/ident :item/tag :db/valueType :db.type/string :db/cardinality :db.cardinality/many}
+    ]"#,
+        )
+        .expect("transacted schema");
+    store
+        .transact(r#"[{:item/name "a" :item/tag ["x" "y"]}]"#)
+        .expect("transacted items");
+
+    let explanation = store
+        .q_explain(
+            r#"[:find ?n ?t :where [?e :item/name ?n] [?e :item/tag ?t]]"#,
+            None,
+        )
+        .expect("explained");
+    match explanation.plan {
+        QueryPlan::ExecutionPlan {
+            ref query,
+            ref tables,
+            ref steps,
+        } => {
+            let tables: Vec<(&str, &str, String)> = tables
+                .iter()
+                .map(|t| (t.alias.as_str(), t.table.as_str(), t.clause.to_string()))
+                .collect();
+            assert_eq!(
+                tables,
+                vec![
+                    ("datoms00", "datoms", "[ ?e :item/name ?n ]".to_string()),
+                    ("datoms01", "datoms", "[ ?e :item/tag ?t ]".to_string()),
+                ]
+            );
+            assert!(query.sql.contains("`datoms01`"));
+            assert!(!steps.is_empty());
+        }
+        _ => panic!("Expected an execution plan"),
+    }
+    assert_eq!(
+        explanation.types.get(&var!(?e)),
+        Some(&ValueTypeSet::of_one(ValueType::Ref))
+    );
+    assert_eq!(
+        explanation.types.get(&var!(?t)),
+        Some(&ValueTypeSet::of_one(ValueType::String))
+    );
+    assert!(explanation.cached_attributes.is_empty());
+
+    let edn = explanation.to_edn().to_string();
+    assert!(edn.contains(r#":clause [ ?e :item/tag ?t ]"#));
+    assert!(edn.contains(r#"?e #{ :db.type/ref }"#));
+    let json = explanation.to_json();
+    assert_eq!(json["tables"][1]["clause"], "[ ?e :item/tag ?t ]");
+    assert_eq!(json["types"]["?t"][0], ":db.type/string");
+
+    // Patterns that can't match explain why.
+    let explanation = store
+        .q_explain(r#"[:find ?e :where [?e :item/name 5]]"#, None)
+        .expect("explained");
+    match explanation.plan {
+        QueryPlan::KnownEmpty(_) => {}
+        _ => panic!("Expected a known-empty query"),
+    }
+    assert!(explanation.to_json()["known-empty"].is_string());
+
+    // Cached lookups don't need tables at all.
+    store
+        .cache(&kw!(:item/name), CacheDirection::Reverse)
+        .expect("cached");
+    let explanation = store
+        .q_explain(r#"[:find ?e . :where [?e :item/name "a"]]"#, None)
+        .expect("explained");
+    assert_eq!(explanation.cached_attributes, vec![kw!(:item/name)]);
+    match explanation.plan {
+        QueryPlan::KnownConstant => {}
+        _ => panic!("Expected a constant query"),
+    }
+}
+
+#[test]
+fn test_query_rows() {
+    let mut store = Store::open("").expect("opened");
+    store
+        .transact(
+            r#"[
+        {:db/ident :item/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :item/rank :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+    ]"#,
+        )
+        .expect("transacted schema");
+    let items: Vec<String> = (0..10)
+        .map(|i| format!(r#"{{:item/name "item{}" :item/rank {}}}"#, i, i))
+        .collect();
+    store
+        .transact(format!("[{}]", items.join(" ")).as_str())
+        .expect("transacted items");
+
+    // Rows come a batch at a time, but they're the same rows.
+    let query = r#"[:find ?n ?r :where [?e :item/name ?n] [?e :item/rank ?r] :order ?r]"#;
+    let expected: Vec<Vec<Binding>> = store
+        .q_once(query, None)
+        .into_rel_result()
+        .expect("rel")
+        .into_iter()
+        .collect();
+    assert_eq!(expected.len(), 10);
+    let mut prepared = store.q_prepare(query, None).expect("prepared");
+    let rows: Vec<Vec<Binding>> = prepared
+        .rows()
+        .expect("rows")
+        .with_batch_size(3)
+        .collect::<Result<_, _>>()
+        .expect("rows");
+    assert_eq!(rows, expected);
+
+    // Pull expressions are resolved for each batch, and keep the order of their rows.
+    let query =
+        r#"[:find [(pull ?e [:item/name]) ...] :where [?e :item/rank ?r] :order (desc ?r)]"#;
+    let mut prepared = store.q_prepare(query, None).expect("prepared");
+    let names: Vec<String> = prepared
+        .rows()
+        .expect("rows")
+        .with_batch_size(4)
+        .map(|row| {
+            let mut row = row.expect("row");
+            assert_eq!(row.len(), 1);
+            match row.pop().expect("pulled") {
+                Binding::Map(m) => {
+                    m.0.get(&kw!(:item/name))
+                        .and_then(|name| name.clone().into_string())
+                        .map(|name| (*name).clone())
+                        .expect("name")
+                }
+                b => panic!("Expected a map, got {:?}", b),
+            }
+        })
+        .collect();
+    let expected: Vec<String> = (0..10).rev().map(|i| format!("item{}", i)).collect();
+    assert_eq!(names, expected);
+
+    // Scalar and empty queries have at most one row, and none.
+    let mut prepared = store
+        .q_prepare(
+            r#"[:find ?n . :where [?e :item/rank 3] [?e :item/name ?n]]"#,
+            None,
+        )
+        .expect("prepared");
+    let rows: Vec<Vec<Binding>> = prepared
+        .rows()
+        .expect("rows")
+        .collect::<Result<_, _>>()
+        .expect("rows");
+    assert_eq!(rows, vec![vec![Binding::from("item3")]]);
+    let mut prepared = store
+        .q_prepare(r#"[:find ?e :where [?e :item/rank "three"]]"#, None)
+        .expect("prepared");
+    assert_eq!(prepared.rows().expect("rows").count(), 0);
+}
+
+#[test]
+fn test_conn_for_each_row() {
+    let mut c = new_connection("").expect("Couldn't open conn.");
+    let conn = Conn::connect(&mut c).expect("Couldn't open DB.");
+
+    let mut count = 0;
+    conn.q_for_each_row(
+        &c,
+        "[:find ?x ?ident :where [?x :db/ident ?ident]]",
+        None,
+        |row| {
+            assert_eq!(row.len(), 2);
+            count += 1;
+            Ok(())
+        },
+    )
+    .expect("rows");
+
+    // This will need to change each time we add a default ident.
+    assert_eq!(count, 48);
+
+    // Errors returned by the callback stop the query.
+    let mut seen = 0;
+    let result = conn.q_for_each_row(
+        &c,
+        "[:find ?x ?ident :where [?x :db/ident ?ident]]",
+        None,
+        |_| {
+            seen += 1;
+            Err(MentatError::UnexpectedLostTransactRace)
+        },
+    );
+    assert!(result.is_err());
+    assert_eq!(seen, 1);
+}
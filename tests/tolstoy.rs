@@ -25,12 +25,14 @@ mod tolstoy_tests {
     use mentat_db::{assert_matches, TX0};
 
     use mentat_tolstoy::{
-        debug::parts_to_datoms, GlobalTransactionLog, SyncFollowup, SyncReport, Syncer, Tx, TxPart,
+        debug::parts_to_datoms, GlobalTransactionLog, SyncFollowup, SyncMetadata, SyncReport,
+        Syncer, Tx, TxPart,
     };
 
     use mentat_tolstoy::debug::txs_after;
 
     use core_traits::{Entid, TypedValue, ValueType};
+    use db_traits::errors::DbErrorKind;
     use mentat_tolstoy::tx_processor::{Processor, TxReceiver};
     use public_traits::errors::{MentatError, Result};
     use tolstoy_traits::errors::TolstoyError;
@@ -349,10 +351,19 @@ mod tolstoy_tests {
             [:db.schema/core :db.schema/attribute 11 ?tx true]
             [:db.schema/core :db.schema/attribute 12 ?tx true]
             [:db.schema/core :db.schema/attribute 13 ?tx true]
+            [:db.schema/core :db.schema/attribute 18 ?tx true]
+            [:db.schema/core :db.schema/attribute 19 ?tx true]
+            [:db.schema/core :db.schema/attribute 20 ?tx true]
+            [:db.schema/core :db.schema/attribute 21 ?tx true]
             [:db.schema/core :db.schema/attribute 22 ?tx true]
             [:db.schema/core :db.schema/attribute 37 ?tx true]
             [:db.schema/core :db.schema/attribute 38 ?tx true]
             [:db.schema/core :db.schema/attribute 39 ?tx true]
+            [:db.schema/core :db.schema/attribute 41 ?tx true]
+            [:db.schema/core :db.schema/attribute 42 ?tx true]
+            [:db.schema/core :db.schema/attribute 43 ?tx true]
+            [:db.schema/core :db.schema/attribute 44 ?tx true]
+            [:db.schema/core :db.schema/attribute 45 ?tx true]
             [:db/ident :db/ident :db/ident ?tx true]
             [:db.part/db :db/ident :db.part/db ?tx true]
             [:db/txInstant :db/ident :db/txInstant ?tx true]
@@ -393,6 +404,14 @@ mod tolstoy_tests {
             [:db.schema/version :db/ident :db.schema/version ?tx true]
             [:db.schema/attribute :db/ident :db.schema/attribute ?tx true]
             [:db.schema/core :db/ident :db.schema/core ?tx true]
+            [:db.attr/preds :db/ident :db.attr/preds ?tx true]
+            [:db/ensure :db/ident :db/ensure ?tx true]
+            [:db.entity/attrs :db/ident :db.entity/attrs ?tx true]
+            [:db.fulltext/tokenizer :db/ident :db.fulltext/tokenizer ?tx true]
+            [:db.fulltext/prefix :db/ident :db.fulltext/prefix ?tx true]
+            [:db.fulltext.tokenizer/unicode61 :db/ident :db.fulltext.tokenizer/unicode61 ?tx true]
+            [:db.fulltext.tokenizer/porter :db/ident :db.fulltext.tokenizer/porter ?tx true]
+            [:db.fulltext.tokenizer/trigram :db/ident :db.fulltext.tokenizer/trigram ?tx true]
             [?tx :db/txInstant ?ms ?tx true]
             [:db/ident :db/valueType 24 ?tx true]
             [:db/txInstant :db/valueType 31 ?tx true]
@@ -406,10 +425,19 @@ mod tolstoy_tests {
             [:db/index :db/valueType 30 ?tx true]
             [:db/fulltext :db/valueType 30 ?tx true]
             [:db/noHistory :db/valueType 30 ?tx true]
+            [:db/excise :db/valueType 23 ?tx true]
+            [:db.excise/attrs :db/valueType 23 ?tx true]
+            [:db.excise/beforeT :db/valueType 25 ?tx true]
+            [:db.excise/before :db/valueType 31 ?tx true]
             [:db.alter/attribute :db/valueType 23 ?tx true]
             [:db/doc :db/valueType 27 ?tx true]
             [:db.schema/version :db/valueType 25 ?tx true]
             [:db.schema/attribute :db/valueType 23 ?tx true]
+            [:db.attr/preds :db/valueType 27 ?tx true]
+            [:db/ensure :db/valueType 23 ?tx true]
+            [:db.entity/attrs :db/valueType 23 ?tx true]
+            [:db.fulltext/tokenizer :db/valueType 23 ?tx true]
+            [:db.fulltext/prefix :db/valueType 30 ?tx true]
             [:db/ident :db/cardinality 33 ?tx true]
             [:db/txInstant :db/cardinality 33 ?tx true]
             [:db.install/partition :db/cardinality 34 ?tx true]
@@ -422,16 +450,25 @@ mod tolstoy_tests {
             [:db/index :db/cardinality 33 ?tx true]
             [:db/fulltext :db/cardinality 33 ?tx true]
             [:db/noHistory :db/cardinality 33 ?tx true]
+            [:db/excise :db/cardinality 33 ?tx true]
+            [:db.excise/attrs :db/cardinality 34 ?tx true]
+            [:db.excise/beforeT :db/cardinality 33 ?tx true]
+            [:db.excise/before :db/cardinality 33 ?tx true]
             [:db.alter/attribute :db/cardinality 34 ?tx true]
             [:db/doc :db/cardinality 33 ?tx true]
             [:db.schema/version :db/cardinality 33 ?tx true]
             [:db.schema/attribute :db/cardinality 34 ?tx true]
+            [:db.attr/preds :db/cardinality 34 ?tx true]
+            [:db/ensure :db/cardinality 34 ?tx true]
+            [:db.entity/attrs :db/cardinality 34 ?tx true]
+            [:db.fulltext/tokenizer :db/cardinality 33 ?tx true]
+            [:db.fulltext/prefix :db/cardinality 33 ?tx true]
             [:db/ident :db/unique 36 ?tx true]
             [:db.schema/attribute :db/unique 35 ?tx true]
             [:db/ident :db/index true ?tx true]
             [:db/txInstant :db/index true ?tx true]
             [:db.schema/attribute :db/index true ?tx true]
-            [:db.schema/core :db.schema/version 1 ?tx true]]"
+            [:db.schema/core :db.schema/version 2 ?tx true]]"
         );
     }
 
@@ -1882,4 +1919,204 @@ mod tolstoy_tests {
             conn_2, sqlite_2, remote_client
         );
     }
+
+    #[test]
+    fn test_excision_propagates() {
+        let mut sqlite_1 = new_connection("").unwrap();
+        let mut sqlite_2 = new_connection("").unwrap();
+
+        let mut conn_1 = Conn::connect(&mut sqlite_1).unwrap();
+        let mut conn_2 = Conn::connect(&mut sqlite_2).unwrap();
+
+        let mut remote_client = TestRemoteClient::new();
+
+        conn_1
+            .transact(
+                &mut sqlite_1,
+                "[
+            {:db/ident :person/email
+              :db/valueType :db.type/string
+              :db/cardinality :db.cardinality/one}]",
+            )
+            .expect("transacted");
+
+        let ids = conn_1
+            .transact(
+                &mut sqlite_1,
+                r#"[{:db/id "p" :person/email "ivan@example.com"}]"#,
+            )
+            .expect("transacted")
+            .tempids;
+        let person = *ids.get("p").unwrap();
+
+        assert_sync!(
+            SyncReport::RemoteFastForward,
+            conn_1,
+            sqlite_1,
+            remote_client
+        );
+        assert_sync!(
+            SyncReport::Merge(SyncFollowup::None),
+            conn_2,
+            sqlite_2,
+            remote_client
+        );
+
+        // 1 excises the person; the excision is an ordinary transaction, and syncs like one.
+        conn_1
+            .transact(&mut sqlite_1, format!("[{{:db/excise {}}}]", person))
+            .expect("transacted");
+
+        assert_sync!(
+            SyncReport::RemoteFastForward,
+            conn_1,
+            sqlite_1,
+            remote_client
+        );
+        assert_sync!(
+            SyncReport::LocalFastForward,
+            conn_2,
+            sqlite_2,
+            remote_client
+        );
+
+        // 2 has purged the email, and kept the excision as a record.
+        assert_transactions!(sqlite_2, conn_2,
+            schema =>
+            "[[:person/email :db/ident :person/email ?tx true]
+            [:person/email :db/valueType :db.type/string ?tx true]
+            [:person/email :db/cardinality :db.cardinality/one ?tx true]
+            [?tx :db/txInstant ?ms ?tx true]]",
+
+            "[[?tx :db/txInstant ?ms ?tx true]]",
+
+            "[[?x :db/excise ?p ?tx true]
+            [?tx :db/txInstant ?ms ?tx true]]"
+        );
+    }
+
+    #[test]
+    fn test_compaction_preserves_unsynced() {
+        let mut sqlite = new_connection("").unwrap();
+        let mut conn = Conn::connect(&mut sqlite).unwrap();
+
+        let mut remote_client = TestRemoteClient::new();
+
+        conn.transact(
+            &mut sqlite,
+            "[
+            {:db/ident :person/email
+              :db/valueType :db.type/string
+              :db/cardinality :db.cardinality/one}]",
+        )
+        .expect("transacted");
+
+        let ids = conn
+            .transact(
+                &mut sqlite,
+                r#"[{:db/id "p" :person/email "ivan@example.com"}
+                    {:db/id "q" :person/email "petr@example.com"}]"#,
+            )
+            .expect("transacted")
+            .tempids;
+        let person = *ids.get("p").unwrap();
+        // The history of the partition's last entid is kept, so compact someone else's.
+        assert!(person < *ids.get("q").unwrap());
+
+        conn.transact(
+            &mut sqlite,
+            format!(r#"[[:db/add {} :person/email "ivan@example.org"]]"#, person),
+        )
+        .expect("transacted");
+
+        assert_sync!(SyncReport::RemoteFastForward, conn, sqlite, remote_client);
+
+        // Not yet uploaded.
+        conn.transact(
+            &mut sqlite,
+            format!(r#"[[:db/add {} :person/email "ivan@example.net"]]"#, person),
+        )
+        .expect("transacted");
+
+        {
+            let mut ip = conn.begin_transaction(&mut sqlite).expect("begun");
+            let before = SyncMetadata::compaction_bound(&ip.transaction, ip.last_tx_id() + 1)
+                .expect("bound");
+            assert_eq!(ip.last_tx_id(), before);
+            ip.compact_history(before, &[]).expect("compacted");
+            ip.commit().expect("committed");
+        }
+
+        // History is gone from uploaded transactions, but not from the last one; the uploaded
+        // assertion it retracts is kept.
+        assert_transactions!(sqlite, conn,
+            schema =>
+            "[[:person/email :db/ident :person/email ?tx true]
+            [:person/email :db/valueType :db.type/string ?tx true]
+            [:person/email :db/cardinality :db.cardinality/one ?tx true]
+            [?tx :db/txInstant ?ms ?tx true]]",
+
+            "[[?q :person/email \"petr@example.com\" ?tx true]
+            [?tx :db/txInstant ?ms ?tx true]]",
+
+            "[[?p :person/email \"ivan@example.org\" ?tx true]
+            [?tx :db/txInstant ?ms ?tx true]]",
+
+            "[[?p :person/email \"ivan@example.net\" ?tx true]
+            [?p :person/email \"ivan@example.org\" ?tx false]
+            [?tx :db/txInstant ?ms ?tx true]]"
+        );
+
+        assert_sync!(SyncReport::RemoteFastForward, conn, sqlite, remote_client);
+    }
+    #[test]
+    fn test_sync_refuses_active_timeline() {
+        let mut sqlite = new_connection("").unwrap();
+        let mut conn = Conn::connect(&mut sqlite).unwrap();
+
+        let mut remote_client = TestRemoteClient::new();
+
+        let base = conn
+            .transact(
+                &mut sqlite,
+                "[
+            {:db/ident :person/email
+              :db/valueType :db.type/string
+              :db/cardinality :db.cardinality/one}]",
+            )
+            .expect("transacted")
+            .tx_id;
+
+        {
+            let mut ip = conn.begin_transaction(&mut sqlite).expect("begun");
+            ip.fork_timeline("draft", base).expect("forked");
+            ip.switch_timeline("draft").expect("switched");
+            ip.commit().expect("committed");
+        }
+        conn.transact(&mut sqlite, r#"[{:person/email "ivan@example.com"}]"#)
+            .expect("transacted");
+
+        {
+            let mut ip = conn.begin_transaction(&mut sqlite).expect("begun");
+            match Syncer::sync(&mut ip, &mut remote_client)
+                .expect_err("expected sync to fail, but did not")
+            {
+                MentatError::DbError(e) => {
+                    assert_eq!(e.kind(), DbErrorKind::TimelinesActive("draft".to_string()))
+                }
+                we => panic!("Failed with wrong error: {:?}", we),
+            }
+        }
+
+        // Nothing was uploaded from the draft.
+        assert_eq!(Uuid::nil(), remote_client.head);
+
+        {
+            let mut ip = conn.begin_transaction(&mut sqlite).expect("begun");
+            ip.switch_timeline(mentat::TIMELINE_MAIN_NAME)
+                .expect("switched");
+            ip.commit().expect("committed");
+        }
+        assert_sync!(SyncReport::RemoteFastForward, conn, sqlite, remote_client);
+    }
 }
@@ -75,6 +75,6 @@ mod tests {
         assert_eq!(1, remote_txs.len());
 
         let bh = BootstrapHelper::new(&remote_txs[0]);
-        assert_eq!(1, bh.core_schema_version().expect("schema version"));
+        assert_eq!(2, bh.core_schema_version().expect("schema version"));
     }
 }
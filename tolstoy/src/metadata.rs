@@ -173,6 +173,31 @@ impl SyncMetadata {
         })?;
         Ok(count > 0)
     }
+
+    /// Clamp the transaction `before` which history is to be compacted so that transactions that
+    /// have not yet been uploaded are preserved.  If this store has never been synced, `before`
+    /// is returned unchanged.
+    pub fn compaction_bound(db_tx: &rusqlite::Transaction<'_>, before: Entid) -> Result<Entid> {
+        let synced: bool = db_tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'tolstoy_tu')",
+            rusqlite::params![],
+            |row| row.get(0),
+        )?;
+        if !synced {
+            return Ok(before);
+        }
+
+        let last_uploaded: Option<Entid> = db_tx.query_row(
+            "SELECT MAX(tx) FROM tolstoy_tu",
+            rusqlite::params![],
+            |row| row.get(0),
+        )?;
+        match last_uploaded {
+            Some(tx) => Ok(before.min(tx + 1)),
+            // Nothing has been uploaded yet: everything after the root must be preserved.
+            None => Ok(before.min(SyncMetadata::root_and_head_tx(db_tx)?.0)),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -270,4 +295,36 @@ mod tests {
         assert_eq!(268435456, root_tx);
         assert_eq!(268435457, last_tx);
     }
+
+    #[test]
+    fn test_compaction_bound() {
+        let mut conn = schema::tests::setup_conn_bare();
+        db::ensure_current_version(&mut conn).expect("mentat db init");
+        let mut db_tx = conn.transaction().expect("transaction");
+
+        // Never synced: nothing to preserve.
+        assert_eq!(
+            268435500,
+            SyncMetadata::compaction_bound(&db_tx, 268435500).expect("bound")
+        );
+
+        // Synced, but nothing uploaded: preserve everything after the root.
+        schema::ensure_current_version(&mut db_tx).expect("tolstoy init");
+        assert_eq!(
+            268435456,
+            SyncMetadata::compaction_bound(&db_tx, 268435500).expect("bound")
+        );
+
+        // Preserve everything after the last uploaded transaction.
+        let uuid = Uuid::new_v4();
+        TxMapper::set_lg_mapping(&mut db_tx, (268435460, &uuid).into()).expect("mapped");
+        assert_eq!(
+            268435461,
+            SyncMetadata::compaction_bound(&db_tx, 268435500).expect("bound")
+        );
+        assert_eq!(
+            268435458,
+            SyncMetadata::compaction_bound(&db_tx, 268435458).expect("bound")
+        );
+    }
 }
@@ -16,6 +16,7 @@ use uuid::Uuid;
 
 use core_traits::{Entid, KnownEntid, TypedValue};
 
+use db_traits::errors::{DbError, DbErrorKind};
 use edn::entities::{EntityPlace, LookupRef, TxFunction};
 use edn::PlainSymbol;
 use mentat_db::{entids, timelines, PartitionMap, CORE_SCHEMA_VERSION};
@@ -486,7 +487,7 @@ impl Syncer {
                         }
                         _ => panic!("programming error: wrong value type for a local ident"),
                     }
-                } else if entids::is_a_schema_attribute(part.a)
+                } else if entids::is_a_schema_attribute(&ip.schema.ident_map, part.a)
                     && !will_not_alter_installed_attribute.contains(&part.e)
                 {
                     might_alter_installed_attributes.insert(part.e);
@@ -779,6 +780,11 @@ impl Syncer {
 
         ensure_current_version(&mut ip.transaction)?;
 
+        // Only the main timeline is synced; don't upload, or merge into, another one.
+        if let Some(active) = timelines::active_timeline(&ip.transaction)? {
+            bail!(DbError::from(DbErrorKind::TimelinesActive(active.name)));
+        }
+
         let remote_head = remote_client.head()?;
         d(&format!("remote head {:?}", remote_head));
 
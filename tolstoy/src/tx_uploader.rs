@@ -185,7 +185,7 @@ pub mod tests {
         let new_map = allocate_partition_map_for_entids(entids.into_iter(), &bootstrap_map);
         assert_eq!(65537, new_map.get(PARTITION_USER).unwrap().next_entid());
         // Other partitions are untouched.
-        assert_eq!(41, new_map.get(PARTITION_DB).unwrap().next_entid());
+        assert_eq!(49, new_map.get(PARTITION_DB).unwrap().next_entid());
         assert_eq!(268435456, new_map.get(PARTITION_TX).unwrap().next_entid());
 
         // Only tx partition.
@@ -194,12 +194,12 @@ pub mod tests {
         assert_eq!(268435667, new_map.get(PARTITION_TX).unwrap().next_entid());
         // Other partitions are untouched.
         assert_eq!(65536, new_map.get(PARTITION_USER).unwrap().next_entid());
-        assert_eq!(41, new_map.get(PARTITION_DB).unwrap().next_entid());
+        assert_eq!(49, new_map.get(PARTITION_DB).unwrap().next_entid());
 
         // Only DB partition.
-        let entids = vec![41];
+        let entids = vec![49];
         let new_map = allocate_partition_map_for_entids(entids.into_iter(), &bootstrap_map);
-        assert_eq!(42, new_map.get(PARTITION_DB).unwrap().next_entid());
+        assert_eq!(50, new_map.get(PARTITION_DB).unwrap().next_entid());
         // Other partitions are untouched.
         assert_eq!(65536, new_map.get(PARTITION_USER).unwrap().next_entid());
         assert_eq!(268435456, new_map.get(PARTITION_TX).unwrap().next_entid());
@@ -210,13 +210,13 @@ pub mod tests {
         assert_eq!(65538, new_map.get(PARTITION_USER).unwrap().next_entid());
         assert_eq!(268435457, new_map.get(PARTITION_TX).unwrap().next_entid());
         // DB partition is untouched.
-        assert_eq!(41, new_map.get(PARTITION_DB).unwrap().next_entid());
+        assert_eq!(49, new_map.get(PARTITION_DB).unwrap().next_entid());
 
         // DB, user and tx partitions.
-        let entids = vec![41, 65666, 268435457];
+        let entids = vec![49, 65666, 268435457];
         let new_map = allocate_partition_map_for_entids(entids.into_iter(), &bootstrap_map);
         assert_eq!(65667, new_map.get(PARTITION_USER).unwrap().next_entid());
         assert_eq!(268435458, new_map.get(PARTITION_TX).unwrap().next_entid());
-        assert_eq!(42, new_map.get(PARTITION_DB).unwrap().next_entid());
+        assert_eq!(50, new_map.get(PARTITION_DB).unwrap().next_entid());
     }
 }
@@ -0,0 +1,406 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Running a single query or transaction without the REPL, for shell pipelines and tests:
+//!
+//! ```sh
+//! mentat_cli -d store.db transact -f schema.edn
+//! mentat_cli -d store.db query '[:find ?name :where [_ :person/name ?name]]' --format csv
+//! echo '[:find (count ?p) . :where [?p :person/name]]' | mentat_cli -d store.db query
+//! ```
+//!
+//! The query or transaction is the argument after the command, or the contents of the file given
+//! by `--file`, or, if neither is given or either is `-`, standard input.  Query results are
+//! written as a table unless `--format` says otherwise; a transaction's report is written in EDN.
+//!
+//! The exit status is `EXIT_SUCCESS` if all went well, `EXIT_USAGE` if the command line doesn't
+//! make sense, and `EXIT_FAILURE` otherwise: if the store can't be opened, the input can't be
+//! read, or the query or transaction fails.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use failure::{err_msg, Error};
+
+use mentat::json::format_instant;
+use mentat::{Binding, QueryOutput, QueryResults, Queryable, Store, TxReport};
+
+use repl::write_table;
+use transfer::{csv_field, csv_value, edn_string, edn_value};
+use CliError;
+
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+pub static BATCH_QUERY: &str = "query";
+pub static BATCH_TRANSACT: &str = "transact";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Table,
+    Edn,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        match s {
+            "table" => Ok(Format::Table),
+            "edn" => Ok(Format::Edn),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => bail!(CliError::Usage(format!(
+                "Unknown format {:?}: expected table, edn, json or csv",
+                s
+            ))),
+        }
+    }
+}
+
+/// A store, and how to write what comes out of it.
+pub struct Batch {
+    /// Empty for an in-memory store.
+    pub path: String,
+    pub key: Option<String>,
+    pub format: Option<Format>,
+}
+
+impl Batch {
+    /// Run `command` with `args`, reporting any error to standard error, and return the exit
+    /// status.
+    pub fn run(&self, program: &str, command: &str, args: &[String], file: Option<&str>) -> i32 {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        match self.execute(command, args, file, stdin.lock(), stdout.lock()) {
+            Ok(()) => EXIT_SUCCESS,
+            Err(e) => {
+                eprintln!("{}: {}", program, e);
+                exit_status(&e)
+            }
+        }
+    }
+
+    pub fn execute<R, W>(
+        &self,
+        command: &str,
+        args: &[String],
+        file: Option<&str>,
+        stdin: R,
+        stdout: W,
+    ) -> Result<(), Error>
+    where
+        R: Read,
+        W: Write,
+    {
+        if command != BATCH_QUERY && command != BATCH_TRANSACT {
+            bail!(CliError::Usage(format!(
+                "Unknown command {:?}: expected {} or {}",
+                command, BATCH_QUERY, BATCH_TRANSACT
+            )));
+        }
+        let input = read_input(args, file, stdin)?;
+        let mut store = self.open()?;
+        if command == BATCH_QUERY {
+            let output = store.q_once(&input, None)?;
+            write_query(stdout, output, self.format.unwrap_or(Format::Table))
+        } else {
+            let report = store.transact(&input)?;
+            write_report(stdout, &report, self.format.unwrap_or(Format::Edn))
+        }
+    }
+
+    fn open(&self) -> Result<Store, Error> {
+        match self.key {
+            #[cfg(feature = "sqlcipher")]
+            Some(ref key) => Ok(Store::open_with_key(&self.path, key)?),
+            #[cfg(not(feature = "sqlcipher"))]
+            Some(_) => bail!(CliError::Usage(
+                "Keys require the sqlcipher Mentat feature".to_string()
+            )),
+            None => Ok(Store::open(&self.path)?),
+        }
+    }
+}
+
+/// How to exit after `error`.
+pub fn exit_status(error: &Error) -> i32 {
+    match error.downcast_ref::<CliError>() {
+        Some(CliError::Usage(_)) => EXIT_USAGE,
+        _ => EXIT_FAILURE,
+    }
+}
+
+fn read_input<R: Read>(args: &[String], file: Option<&str>, mut stdin: R) -> Result<String, Error> {
+    let source = match (args, file) {
+        ([], file) => file,
+        ([arg], None) if arg == "-" => None,
+        ([arg], None) => return Ok(arg.clone()),
+        ([_], Some(_)) => bail!(CliError::Usage(
+            "Expected an argument or a file, not both".to_string()
+        )),
+        (args, _) => bail!(CliError::Usage(format!(
+            "Unrecognized argument {:?}",
+            args[1]
+        ))),
+    };
+    let mut input = String::new();
+    match source {
+        Some(path) if path != "-" => {
+            File::open(path)
+                .and_then(|mut f| f.read_to_string(&mut input))
+                .map_err(|e| err_msg(format!("Error reading file {}: {}", path, e)))?;
+        }
+        _ => {
+            stdin.read_to_string(&mut input)?;
+        }
+    }
+    Ok(input)
+}
+
+fn binding_edn(binding: &Binding) -> String {
+    match binding {
+        Binding::Scalar(value) => edn_value(value),
+        Binding::Vec(values) => format!(
+            "[{}]",
+            values.iter().map(binding_edn).collect::<Vec<_>>().join(" ")
+        ),
+        Binding::Map(map) => format!(
+            "{{{}}}",
+            map.0
+                .iter()
+                .map(|(k, v)| format!("{} {}", k, binding_edn(v)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        Binding::Nil => "nil".to_string(),
+    }
+}
+
+/// Results in EDN, shaped like the find spec, just as `QueryResults::to_json` shapes them.
+fn results_edn(results: &QueryResults) -> String {
+    let row = |bindings: &[Binding]| {
+        format!(
+            "[{}]",
+            bindings
+                .iter()
+                .map(binding_edn)
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+    };
+    match results {
+        QueryResults::Scalar(Some(binding)) => binding_edn(binding),
+        QueryResults::Scalar(None) | QueryResults::Tuple(None) => "nil".to_string(),
+        QueryResults::Tuple(Some(bindings)) | QueryResults::Coll(bindings) => row(bindings),
+        QueryResults::Rel(rel) => {
+            format!("[{}]", rel.rows().map(row).collect::<Vec<_>>().join(" "))
+        }
+    }
+}
+
+/// Values are written as text; anything else, such as a pulled entity, in EDN.
+fn csv_cell(binding: &Binding) -> String {
+    match binding {
+        Binding::Scalar(value) => csv_field(&csv_value(value)),
+        binding => csv_field(&binding_edn(binding)),
+    }
+}
+
+fn write_query<W: Write>(mut out: W, output: QueryOutput, format: Format) -> Result<(), Error> {
+    match format {
+        Format::Table => return write_table(out, output),
+        Format::Json => writeln!(out, "{}", output.to_json())?,
+        Format::Edn => writeln!(out, "{}", results_edn(&output.results))?,
+        Format::Csv => {
+            let columns: Vec<String> = output
+                .spec
+                .columns()
+                .map(|column| csv_field(&column.to_string()))
+                .collect();
+            writeln!(out, "{}", columns.join(","))?;
+
+            let write_row = |out: &mut W, bindings: &[Binding]| -> io::Result<()> {
+                let cells: Vec<String> = bindings.iter().map(csv_cell).collect();
+                writeln!(out, "{}", cells.join(","))
+            };
+            match output.results {
+                QueryResults::Scalar(Some(ref binding)) => {
+                    write_row(&mut out, ::std::slice::from_ref(binding))?
+                }
+                QueryResults::Scalar(None) | QueryResults::Tuple(None) => (),
+                QueryResults::Tuple(Some(ref bindings)) => write_row(&mut out, bindings)?,
+                QueryResults::Coll(ref bindings) => {
+                    for binding in bindings {
+                        write_row(&mut out, ::std::slice::from_ref(binding))?;
+                    }
+                }
+                QueryResults::Rel(ref rel) => {
+                    for bindings in rel.rows() {
+                        write_row(&mut out, bindings)?;
+                    }
+                }
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_report<W: Write>(mut out: W, report: &TxReport, format: Format) -> Result<(), Error> {
+    match format {
+        Format::Json => writeln!(
+            out,
+            "{}",
+            json!({
+                "tx-id": report.tx_id,
+                "tx-instant": format_instant(&report.tx_instant),
+                "tempids": report.tempids,
+            })
+        )?,
+        Format::Csv => {
+            writeln!(out, "tempid,entid")?;
+            for (tempid, entid) in &report.tempids {
+                writeln!(out, "{},{}", csv_field(tempid), entid)?;
+            }
+        }
+        Format::Edn | Format::Table => {
+            let tempids: Vec<String> = report
+                .tempids
+                .iter()
+                .map(|(tempid, entid)| format!("{} {}", edn_string(tempid), entid))
+                .collect();
+            writeln!(
+                out,
+                "{{:tx-id {} :tx-instant #inst {} :tempids {{{}}}}}",
+                report.tx_id,
+                edn_string(&format_instant(&report.tx_instant)),
+                tempids.join(" ")
+            )?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    const SCHEMA: &str = r#"[
+        {:db/ident :person/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+        {:db/ident :person/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+    ]"#;
+
+    fn run(
+        batch: &Batch,
+        command: &str,
+        args: &[&str],
+        file: Option<&str>,
+        stdin: &str,
+    ) -> Result<String, Error> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut stdout = vec![];
+        batch.execute(command, &args, file, stdin.as_bytes(), &mut stdout)?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_transact_and_query() {
+        let dir = tempdir().unwrap();
+        let schema = dir.path().join("schema.edn");
+        fs::write(&schema, SCHEMA).unwrap();
+        let mut batch = Batch {
+            path: dir.path().join("test.db").to_str().unwrap().to_string(),
+            key: None,
+            format: None,
+        };
+
+        let report = run(&batch, "transact", &[], schema.to_str(), "").expect("transacted");
+        assert!(report.starts_with("{:tx-id "));
+        assert!(report.ends_with(":tempids {}}\n"));
+
+        // From standard input.
+        let data = r#"[{:db/id "a" :person/name "Alice, A." :person/age 30} {:person/name "Bob" :person/age 40}]"#;
+        batch.format = Some(Format::Json);
+        let report = run(&batch, "transact", &["-"], None, data).expect("transacted");
+        let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert!(report["tempids"]["a"].is_i64());
+
+        let query =
+            "[:find ?name ?age :where [?p :person/name ?name] [?p :person/age ?age] :order ?age]";
+        assert_eq!(
+            run(&batch, "query", &[query], None, "").expect("queried"),
+            "{\"columns\":[\"?name\",\"?age\"],\"results\":[[\"Alice, A.\",30],[\"Bob\",40]]}\n"
+        );
+
+        batch.format = Some(Format::Csv);
+        assert_eq!(
+            run(&batch, "query", &[], None, query).expect("queried"),
+            "?name,?age\n\"Alice, A.\",30\nBob,40\n"
+        );
+
+        batch.format = Some(Format::Edn);
+        assert_eq!(
+            run(&batch, "query", &[query], None, "").expect("queried"),
+            "[[\"Alice, A.\" 30] [\"Bob\" 40]]\n"
+        );
+        assert_eq!(
+            run(
+                &batch,
+                "query",
+                &["[:find ?age . :where [_ :person/age ?age] [(> ?age 100)]]"],
+                None,
+                ""
+            )
+            .expect("queried"),
+            "nil\n"
+        );
+
+        batch.format = None;
+        let table = run(&batch, "query", &[query], None, "").expect("queried");
+        assert!(table.contains("| \"Bob\""), "{}", table);
+    }
+
+    #[test]
+    fn test_errors() {
+        let batch = Batch {
+            path: "".to_string(),
+            key: None,
+            format: None,
+        };
+
+        let err = run(&batch, "explain", &[], None, "").expect_err("unknown command");
+        assert_eq!(exit_status(&err), EXIT_USAGE);
+        let err =
+            run(&batch, "query", &["[:find ?x]", "[:find ?y]"], None, "").expect_err("too many");
+        assert_eq!(exit_status(&err), EXIT_USAGE);
+        let err = run(&batch, "query", &["[:find ?x]"], Some("query.edn"), "").expect_err("both");
+        assert_eq!(exit_status(&err), EXIT_USAGE);
+        let err = "xml".parse::<Format>().expect_err("unknown format");
+        assert_eq!(exit_status(&err), EXIT_USAGE);
+
+        let err = run(&batch, "query", &[], Some("/no/such/query.edn"), "").expect_err("no file");
+        assert_eq!(exit_status(&err), EXIT_FAILURE);
+        let err = run(&batch, "query", &[], None, "[:find ?x :where").expect_err("bad query");
+        assert_eq!(exit_status(&err), EXIT_FAILURE);
+        let err = run(&batch, "transact", &["[[:db/add 1 :no/such 1]]"], None, "")
+            .expect_err("bad transaction");
+        assert_eq!(exit_status(&err), EXIT_FAILURE);
+    }
+}
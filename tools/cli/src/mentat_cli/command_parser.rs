@@ -14,18 +14,24 @@ use combine::{any, choice, eof, look_ahead, many1, satisfy, sep_end_by, token, P
 
 use CliError;
 
-use edn;
-
 use failure::Error;
 
 use combine::error::StringStreamError;
 use mentat::CacheDirection;
 
+use nesting::Nesting;
+
+use transfer;
+
 pub static COMMAND_CACHE: &str = &"cache";
 pub static COMMAND_CLOSE: &str = &"close";
+pub static COMMAND_COMPACT: &str = &"compact";
+pub static COMMAND_DESCRIBE: &str = &"describe";
 pub static COMMAND_EXIT_LONG: &str = &"exit";
 pub static COMMAND_EXIT_SHORT: &str = &"e";
+pub static COMMAND_EXPORT: &str = &"export";
 pub static COMMAND_HELP: &str = &"help";
+pub static COMMAND_HISTORY: &str = &"history";
 pub static COMMAND_IMPORT_LONG: &str = &"import";
 pub static COMMAND_IMPORT_SHORT: &str = &"i";
 pub static COMMAND_OPEN: &str = &"open";
@@ -40,14 +46,20 @@ pub static COMMAND_SYNC: &str = &"sync";
 pub static COMMAND_TIMER_LONG: &str = &"timer";
 pub static COMMAND_TRANSACT_LONG: &str = &"transact";
 pub static COMMAND_TRANSACT_SHORT: &str = &"t";
+pub static COMMAND_TX: &str = &"tx";
+pub static COMMAND_VOCABULARIES: &str = &"vocabularies";
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Command {
     Cache(String, CacheDirection),
     Close,
+    Compact(String, Vec<String>),
+    Describe(String),
     Exit,
+    Export(String, transfer::Format, bool),
     Help(Vec<String>),
-    Import(String),
+    History(String),
+    Import(String, Option<usize>, bool),
     Open(String),
     OpenEncrypted(String, String),
     Query(String),
@@ -57,41 +69,55 @@ pub enum Command {
     Sync(Vec<String>),
     Timer(bool),
     Transact(String),
+    Tx(i64),
+    Vocabularies,
 }
 
 impl Command {
     /// is_complete returns true if no more input is required for the command to be successfully executed.
     /// false is returned if the command is not considered valid.
     /// Defaults to true for all commands except Query and Transact.
-    /// TODO: for query and transact commands, they will be considered complete if a parsable EDN has been entered as an argument
+    /// Query and transact commands are complete once their EDN closes every bracket and string it
+    /// opens; if it doesn't parse, executing the command reports why.
     pub fn is_complete(&self) -> bool {
         match self {
             &Command::Query(ref args)
             | &Command::QueryExplain(ref args)
             | &Command::QueryPrepared(ref args)
-            | &Command::Transact(ref args) => edn::parse::value(&args).is_ok(),
+            | &Command::Transact(ref args) => Nesting::of(&args).is_complete(),
             &Command::Cache(_, _)
             | &Command::Close
+            | &Command::Compact(_, _)
+            | &Command::Describe(_)
             | &Command::Exit
+            | &Command::Export(_, _, _)
             | &Command::Help(_)
-            | &Command::Import(_)
+            | &Command::History(_)
+            | &Command::Import(_, _, _)
             | &Command::Open(_)
             | &Command::OpenEncrypted(_, _)
             | &Command::Timer(_)
             | &Command::Schema
-            | &Command::Sync(_) => true,
+            | &Command::Sync(_)
+            | &Command::Tx(_)
+            | &Command::Vocabularies => true,
         }
     }
 
     pub fn is_timed(&self) -> bool {
         match self {
-            &Command::Import(_)
+            &Command::Compact(_, _)
+            | &Command::Export(_, _, _)
+            | &Command::History(_)
+            | &Command::Import(_, _, _)
             | &Command::Query(_)
             | &Command::QueryPrepared(_)
-            | &Command::Transact(_) => true,
+            | &Command::Transact(_)
+            | &Command::Tx(_) => true,
 
             &Command::Cache(_, _)
             | &Command::Close
+            | &Command::Describe(_)
             | &Command::Exit
             | &Command::Help(_)
             | &Command::Open(_)
@@ -99,7 +125,8 @@ impl Command {
             | &Command::QueryExplain(_)
             | &Command::Timer(_)
             | &Command::Schema
-            | &Command::Sync(_) => false,
+            | &Command::Sync(_)
+            | &Command::Vocabularies => false,
         }
     }
 
@@ -109,9 +136,27 @@ impl Command {
                 format!(".{} {} {:?}", COMMAND_CACHE, attr, direction)
             }
             Command::Close => format!(".{}", COMMAND_CLOSE),
+            Command::Compact(ref bound, ref keep) => {
+                format!(".{} {} {}", COMMAND_COMPACT, bound, keep.join(" "))
+            }
+            Command::Describe(ref attribute) => format!(".{} {}", COMMAND_DESCRIBE, attribute),
             Command::Exit => format!(".{}", COMMAND_EXIT_LONG),
             Command::Help(ref args) => format!(".{} {:?}", COMMAND_HELP, args),
-            Command::Import(ref args) => format!(".{} {}", COMMAND_IMPORT_LONG, args),
+            Command::History(ref entity) => format!(".{} {}", COMMAND_HISTORY, entity),
+            Command::Export(ref path, format, history) => format!(
+                ".{} {} {}{}",
+                COMMAND_EXPORT,
+                path,
+                format.name(),
+                if *history { " history" } else { "" }
+            ),
+            Command::Import(ref path, batch_size, resume) => format!(
+                ".{} {}{}{}",
+                COMMAND_IMPORT_LONG,
+                path,
+                batch_size.map_or(String::new(), |n| format!(" {}", n)),
+                if *resume { " resume" } else { "" }
+            ),
             Command::Open(ref args) => format!(".{} {}", COMMAND_OPEN, args),
             Command::OpenEncrypted(ref db, ref key) => {
                 format!(".{} {} {}", COMMAND_OPEN_ENCRYPTED, db, key)
@@ -125,12 +170,13 @@ impl Command {
             Command::Sync(ref args) => format!(".{} {:?}", COMMAND_SYNC, args),
             Command::Timer(on) => format!(".{} {}", COMMAND_TIMER_LONG, on),
             Command::Transact(ref args) => format!(".{} {}", COMMAND_TRANSACT_LONG, args),
+            Command::Tx(tx) => format!(".{} {}", COMMAND_TX, tx),
+            Command::Vocabularies => format!(".{}", COMMAND_VOCABULARIES),
         }
     }
 }
 
 pub fn command(s: &str) -> Result<Command, Error> {
-    let path = || many1::<String, _, _>(satisfy(|c: char| !c.is_whitespace()));
     let argument = || many1::<String, _, _>(satisfy(|c: char| !c.is_whitespace()));
     let arguments = || {
         sep_end_by::<Vec<_>, _, _, _>(
@@ -198,6 +244,21 @@ pub fn command(s: &str) -> Result<Command, Error> {
         Ok(Command::Close)
     });
 
+    let compact_parser = string(COMMAND_COMPACT)
+        .with(spaces())
+        .with(arguments())
+        .map(|args: Vec<String>| {
+            if args.is_empty() {
+                bail!(CliError::CommandParse(
+                    "Missing required argument".to_string()
+                ));
+            }
+            Ok(Command::Compact(args[0].clone(), args[1..].to_vec()))
+        });
+
+    let describe_parser = opener(COMMAND_DESCRIBE, 1)
+        .map(|args_res| args_res.map(|args| Command::Describe(args[0].clone())));
+
     let exit_parser = attempt(string(COMMAND_EXIT_LONG))
         .or(attempt(string(COMMAND_EXIT_SHORT)))
         .with(no_arg_parser())
@@ -216,16 +277,77 @@ pub fn command(s: &str) -> Result<Command, Error> {
         .with(edn_arg_parser())
         .map(|x| Ok(Command::QueryExplain(x)));
 
+    let export_parser =
+        string(COMMAND_EXPORT)
+            .with(spaces())
+            .with(arguments())
+            .map(|args: Vec<String>| {
+                let path = match args.first() {
+                    Some(path) => path.clone(),
+                    None => bail!(CliError::CommandParse(
+                        "Missing required argument".to_string()
+                    )),
+                };
+                let mut format = None;
+                let mut history = false;
+                for arg in &args[1..] {
+                    match arg.as_str() {
+                        "history" if !history => history = true,
+                        arg if format.is_none() && arg.parse::<transfer::Format>().is_ok() => {
+                            format = arg.parse().ok()
+                        }
+                        arg => bail!(CliError::CommandParse(format!(
+                            "Unrecognized argument {:?}",
+                            arg
+                        ))),
+                    }
+                }
+                // Guess the format from the file name, if it isn't given.
+                let format = format.unwrap_or_else(|| {
+                    if path.ends_with(".json") || path.ends_with(".jsonl") {
+                        transfer::Format::Json
+                    } else {
+                        transfer::Format::Edn
+                    }
+                });
+                Ok(Command::Export(path, format, history))
+            });
+
     let help_parser = string(COMMAND_HELP)
         .with(spaces())
         .with(arguments())
         .map(|args| Ok(Command::Help(args)));
 
+    let history_parser = opener(COMMAND_HISTORY, 1)
+        .map(|args_res| args_res.map(|args| Command::History(args[0].clone())));
+
     let import_parser = attempt(string(COMMAND_IMPORT_LONG))
         .or(attempt(string(COMMAND_IMPORT_SHORT)))
         .with(spaces())
-        .with(path())
-        .map(|x| Ok(Command::Import(x)));
+        .with(arguments())
+        .map(|args: Vec<String>| {
+            let path = match args.first() {
+                Some(path) => path.clone(),
+                None => bail!(CliError::CommandParse(
+                    "Missing required argument".to_string()
+                )),
+            };
+            let mut batch_size = None;
+            let mut resume = false;
+            for arg in &args[1..] {
+                match arg.as_str() {
+                    "resume" if !resume => resume = true,
+                    arg if batch_size.is_none() && arg.parse::<usize>().is_ok() => {
+                        batch_size = arg.parse().ok()
+                    }
+                    arg => bail!(CliError::CommandParse(format!(
+                        "Unrecognized argument {:?}",
+                        arg
+                    ))),
+                }
+            }
+            Ok(Command::Import(path, batch_size, resume))
+        });
 
     let open_parser =
         opener(COMMAND_OPEN, 1).map(|args_res| args_res.map(|args| Command::Open(args[0].clone())));
@@ -281,21 +403,50 @@ pub fn command(s: &str) -> Result<Command, Error> {
         .with(edn_arg_parser())
         .map(|x| Ok(Command::Transact(x)));
 
+    let tx_parser = opener(COMMAND_TX, 1).map(|args_res| {
+        let args = args_res?;
+        match args[0].parse() {
+            Ok(tx) => Ok(Command::Tx(tx)),
+            Err(_) => bail!(CliError::CommandParse(format!(
+                "Invalid transaction {:?}",
+                args[0]
+            ))),
+        }
+    });
+
+    let vocabularies_parser = string(COMMAND_VOCABULARIES)
+        .with(no_arg_parser())
+        .map(|args| {
+            if !args.is_empty() {
+                bail!(CliError::CommandParse(format!(
+                    "Unrecognized argument {:?}",
+                    args[0]
+                )));
+            }
+            Ok(Command::Vocabularies)
+        });
+
     let parsers = choice((
         attempt(help_parser),
+        attempt(history_parser),
         attempt(import_parser),
+        attempt(export_parser),
         attempt(timer_parser),
         attempt(cache_parser),
         attempt(open_encrypted_parser),
         attempt(open_parser),
         attempt(close_parser),
+        attempt(compact_parser),
+        attempt(describe_parser),
         attempt(explain_query_parser),
         attempt(exit_parser),
         attempt(query_prepared_parser),
         attempt(query_parser),
         attempt(schema_parser),
         attempt(sync_parser),
+        attempt(tx_parser),
         attempt(transact_parser),
+        attempt(vocabularies_parser),
     ));
     spaces()
         .skip(token('.'))
@@ -426,6 +577,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compact_parser() {
+        let input = ".compact 2018-01-01T00:00:00Z :person/name :person/email";
+        let cmd = command(&input).expect("Expected compact command");
+        match cmd {
+            Command::Compact(bound, keep) => {
+                assert_eq!(bound, "2018-01-01T00:00:00Z".to_string());
+                assert_eq!(
+                    keep,
+                    vec![":person/name".to_string(), ":person/email".to_string()]
+                );
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_compact_parser_no_args() {
+        let input = ".compact";
+        let err = command(&input).expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Missing required argument");
+    }
+
     #[test]
     fn test_open_parser_file_arg() {
         let input = ".open my.db";
@@ -595,11 +769,90 @@ mod tests {
         let input = ".import /foo/bar/";
         let cmd = command(&input).expect("Expected import command");
         match cmd {
-            Command::Import(path) => assert_eq!(path, "/foo/bar/"),
+            Command::Import(path, None, false) => assert_eq!(path, "/foo/bar/"),
             _ => panic!("Wrong command!"),
         }
     }
 
+    #[test]
+    fn test_import_parser_options() {
+        let cmd = command(".i data.edn 500 resume").expect("Expected import command");
+        assert_eq!(
+            cmd,
+            Command::Import("data.edn".to_string(), Some(500), true)
+        );
+
+        let cmd = command(".import data.edn resume").expect("Expected import command");
+        assert_eq!(cmd, Command::Import("data.edn".to_string(), None, true));
+
+        let err = command(".import data.edn lots").expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Unrecognized argument \"lots\"");
+
+        let err = command(".import").expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Missing required argument");
+    }
+
+    #[test]
+    fn test_introspection_parsers() {
+        assert_eq!(
+            command(".describe :foo/bar").expect("Expected describe command"),
+            Command::Describe(":foo/bar".to_string())
+        );
+        assert_eq!(
+            command(".history 65536").expect("Expected history command"),
+            Command::History("65536".to_string())
+        );
+        assert_eq!(
+            command(".tx 268435457").expect("Expected tx command"),
+            Command::Tx(268435457)
+        );
+        assert_eq!(
+            command(".vocabularies").expect("Expected vocabularies command"),
+            Command::Vocabularies
+        );
+
+        let err = command(".tx :foo/bar").expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Invalid transaction \":foo/bar\"");
+        let err = command(".history").expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Missing required argument");
+        let err = command(".vocabularies :foo").expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Invalid command \".vocabularies :foo\"");
+
+        // `.tx` isn't mistaken for `.t`.
+        match command(".t [[:db/add \"a\" :db/ident :foo/bar]]").expect("Expected transact command")
+        {
+            Command::Transact(_) => (),
+            cmd => panic!("Wrong command {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn test_export_parser() {
+        let cmd = command(".export backup.edn").expect("Expected export command");
+        assert_eq!(
+            cmd,
+            Command::Export("backup.edn".to_string(), transfer::Format::Edn, false)
+        );
+
+        let cmd = command(".export backup.jsonl history").expect("Expected export command");
+        assert_eq!(
+            cmd,
+            Command::Export("backup.jsonl".to_string(), transfer::Format::Json, true)
+        );
+
+        let cmd = command(".export backup csv").expect("Expected export command");
+        assert_eq!(
+            cmd,
+            Command::Export("backup".to_string(), transfer::Format::Csv, false)
+        );
+
+        let err = command(".export backup.edn xml").expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Unrecognized argument \"xml\"");
+
+        let err = command(".export").expect_err("Expected an error");
+        assert_eq!(err.to_string(), "Missing required argument");
+    }
+
     #[test]
     fn test_transact_parser_complete_edn() {
         let input = ".t [[:db/add \"s\" :db/ident :foo/uuid] [:db/add \"r\" :db/ident :bar/uuid]]";
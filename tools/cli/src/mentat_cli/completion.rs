@@ -0,0 +1,292 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Tab completion in the REPL: dot-commands at the start of a line, paths for the commands that
+//! take one, idents from the current schema, and the variables of the query being written.
+
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+
+use linefeed::complete::{complete_path, word_break_start};
+use linefeed::{Completer, Completion, Prompter, Terminal};
+
+use mentat::Schema;
+
+use command_parser::{
+    COMMAND_CACHE, COMMAND_CLOSE, COMMAND_COMPACT, COMMAND_DESCRIBE, COMMAND_EXIT_LONG,
+    COMMAND_EXIT_SHORT, COMMAND_EXPORT, COMMAND_HELP, COMMAND_HISTORY, COMMAND_IMPORT_LONG,
+    COMMAND_IMPORT_SHORT, COMMAND_OPEN, COMMAND_QUERY_EXPLAIN_LONG, COMMAND_QUERY_EXPLAIN_SHORT,
+    COMMAND_QUERY_LONG, COMMAND_QUERY_PREPARED_LONG, COMMAND_QUERY_SHORT, COMMAND_SCHEMA,
+    COMMAND_TIMER_LONG, COMMAND_TRANSACT_LONG, COMMAND_TRANSACT_SHORT, COMMAND_TX,
+    COMMAND_VOCABULARIES,
+};
+
+#[cfg(feature = "sqlcipher")]
+use command_parser::COMMAND_OPEN_ENCRYPTED;
+
+#[cfg(feature = "syncable")]
+use command_parser::COMMAND_SYNC;
+
+/// A word ends at whitespace, or at a delimiter of EDN collections and strings.
+const WORD_BREAK_CHARS: &str = " \t\n,()[]{}\"";
+
+/// Keywords of queries and transactions that aren't idents in any schema.
+const KEYWORDS: &[&str] = &[
+    ":db/add",
+    ":db/id",
+    ":db/retract",
+    ":find",
+    ":in",
+    ":limit",
+    ":order",
+    ":where",
+    ":with",
+];
+
+lazy_static! {
+    static ref COMMANDS: Vec<&'static str> = {
+        vec![
+            COMMAND_CACHE,
+            COMMAND_CLOSE,
+            COMMAND_COMPACT,
+            COMMAND_DESCRIBE,
+            COMMAND_EXIT_LONG,
+            COMMAND_EXIT_SHORT,
+            COMMAND_EXPORT,
+            COMMAND_HELP,
+            COMMAND_HISTORY,
+            COMMAND_IMPORT_LONG,
+            COMMAND_IMPORT_SHORT,
+            COMMAND_OPEN,
+            #[cfg(feature = "sqlcipher")]
+            COMMAND_OPEN_ENCRYPTED,
+            COMMAND_QUERY_EXPLAIN_LONG,
+            COMMAND_QUERY_EXPLAIN_SHORT,
+            COMMAND_QUERY_LONG,
+            COMMAND_QUERY_PREPARED_LONG,
+            COMMAND_QUERY_SHORT,
+            COMMAND_SCHEMA,
+            #[cfg(feature = "syncable")]
+            COMMAND_SYNC,
+            COMMAND_TIMER_LONG,
+            COMMAND_TRANSACT_LONG,
+            COMMAND_TRANSACT_SHORT,
+            COMMAND_TX,
+            COMMAND_VOCABULARIES,
+        ]
+    };
+
+    /// The commands whose first argument is a path.
+    static ref PATH_COMMANDS: Vec<String> = {
+        vec![
+            COMMAND_EXPORT,
+            COMMAND_IMPORT_LONG,
+            COMMAND_IMPORT_SHORT,
+            COMMAND_OPEN,
+            #[cfg(feature = "sqlcipher")]
+            COMMAND_OPEN_ENCRYPTED,
+        ].into_iter().map(|command| format!(".{}", command)).collect()
+    };
+}
+
+#[derive(Default)]
+struct State {
+    /// Every ident in the schema: attributes, vocabularies, and enumerated values alike.
+    idents: BTreeSet<String>,
+    /// The lines of a command read so far, when it spans several.
+    pending: String,
+}
+
+/// Completes REPL input.  The input reader shares it with the line editor, and keeps it up to
+/// date with the schema of the open store and with any command that's still being written.
+#[derive(Default)]
+pub(crate) struct InputCompleter {
+    state: RwLock<State>,
+}
+
+impl InputCompleter {
+    pub(crate) fn set_schema(&self, schema: &Schema) {
+        let mut state = self.state.write().unwrap();
+        state.idents = schema
+            .ident_map
+            .keys()
+            .map(|ident| ident.to_string())
+            .collect();
+    }
+
+    pub(crate) fn set_pending(&self, pending: &str) {
+        self.state.write().unwrap().pending = pending.to_string();
+    }
+
+    /// The completions for the word of `line` between `start` and `end`.
+    pub(crate) fn completions(&self, line: &str, start: usize, end: usize) -> Vec<Completion> {
+        let state = self.state.read().unwrap();
+        let word = &line[start..end];
+        let mut preceding = line[..start].split_whitespace();
+        let command = if state.pending.is_empty() {
+            preceding.next()
+        } else {
+            None
+        };
+
+        if state.pending.is_empty() && start == 0 && word.starts_with('.') {
+            return complete_from(COMMANDS.iter().map(|command| format!(".{}", command)), word);
+        }
+        if command.is_some_and(|command| PATH_COMMANDS.iter().any(|c| c == command))
+            && preceding.next().is_none()
+        {
+            return complete_path(word);
+        }
+        if command.is_some_and(|command| command == format!(".{}", COMMAND_HELP)) {
+            return complete_from(COMMANDS.iter().map(|command| command.to_string()), word);
+        }
+
+        if word.starts_with(':') {
+            let keywords = KEYWORDS.iter().map(|keyword| keyword.to_string());
+            complete_from(state.idents.iter().cloned().chain(keywords), word)
+        } else if word.starts_with('?') {
+            // The variables used anywhere else in the command.
+            let text = format!("{}\n{} {}", state.pending, &line[..start], &line[end..]);
+            let variables = text
+                .split(|c: char| WORD_BREAK_CHARS.contains(c))
+                .filter(|token| token.len() > 1 && token.starts_with('?'))
+                .map(|variable| variable.to_string());
+            complete_from(variables, word)
+        } else {
+            vec![]
+        }
+    }
+}
+
+fn complete_from<I>(candidates: I, word: &str) -> Vec<Completion>
+where
+    I: Iterator<Item = String>,
+{
+    candidates
+        .filter(|candidate| candidate.starts_with(word))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(Completion::simple)
+        .collect()
+}
+
+impl<Term: Terminal> Completer<Term> for InputCompleter {
+    fn complete(
+        &self,
+        _word: &str,
+        prompter: &Prompter<Term>,
+        start: usize,
+        end: usize,
+    ) -> Option<Vec<Completion>> {
+        Some(self.completions(prompter.buffer(), start, end))
+    }
+
+    fn word_start(&self, line: &str, end: usize, _prompter: &Prompter<Term>) -> usize {
+        word_break_start(&line[..end], WORD_BREAK_CHARS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mentat::Store;
+
+    fn complete(completer: &InputCompleter, line: &str) -> Vec<String> {
+        let start = word_break_start(line, WORD_BREAK_CHARS);
+        completer
+            .completions(line, start, line.len())
+            .into_iter()
+            .map(|completion| completion.completion)
+            .collect()
+    }
+
+    fn completer() -> InputCompleter {
+        let mut store = Store::open("").expect("opened");
+        store
+            .transact(
+                r#"[{:db/ident :my.very.long.namespace/attribute
+                     :db/valueType :db.type/string :db/cardinality :db.cardinality/one}
+                    {:db/ident :my.very.long.namespace/other
+                     :db/valueType :db.type/long :db/cardinality :db.cardinality/many}]"#,
+            )
+            .expect("schema");
+        store
+            .transact(
+                r#"[{:db/ident :org.example/people :db.schema/version 1
+                     :db.schema/attribute :my.very.long.namespace/attribute}]"#,
+            )
+            .expect("vocabulary");
+        let completer = InputCompleter::default();
+        completer.set_schema(&store.conn().current_schema());
+        completer
+    }
+
+    #[test]
+    fn test_complete_commands() {
+        let completer = completer();
+        assert_eq!(complete(&completer, ".vo"), vec![".vocabularies"]);
+        assert_eq!(
+            complete(&completer, ".t"),
+            vec![".t", ".timer", ".transact", ".tx"]
+        );
+        assert_eq!(complete(&completer, ".help tr"), vec!["transact"]);
+        assert!(complete(&completer, ".q .").is_empty());
+
+        // A dot in a command that's still being written isn't a command.
+        completer.set_pending(".q [:find ?x");
+        assert!(complete(&completer, ".").is_empty());
+    }
+
+    #[test]
+    fn test_complete_idents() {
+        let completer = completer();
+        assert_eq!(
+            complete(&completer, ".q [:find ?x :where [?x :my.very"),
+            vec![
+                ":my.very.long.namespace/attribute",
+                ":my.very.long.namespace/other",
+            ]
+        );
+        assert_eq!(
+            complete(&completer, ".describe :my.very.long.namespace/a"),
+            vec![":my.very.long.namespace/attribute"]
+        );
+        assert_eq!(complete(&completer, ":org"), vec![":org.example/people"]);
+        assert_eq!(complete(&completer, ".q [:wh"), vec![":where"]);
+        assert_eq!(
+            complete(&completer, "[{:db/valueType :db.type/str"),
+            vec![":db.type/string"]
+        );
+    }
+
+    #[test]
+    fn test_complete_variables() {
+        let completer = completer();
+        completer.set_pending(".q [:find ?person ?name\n:in ?place");
+        assert_eq!(complete(&completer, ":where [?pe"), vec!["?person"]);
+        assert_eq!(complete(&completer, "[?p"), vec!["?person", "?place"]);
+        assert!(complete(&completer, "[?q").is_empty());
+    }
+
+    #[test]
+    fn test_complete_paths() {
+        let completer = completer();
+        let dir = ::tempfile::tempdir().expect("tempdir");
+        ::std::fs::write(dir.path().join("people.edn"), "").expect("written");
+        let prefix = format!("{}/peo", dir.path().display());
+        let expected = format!("{}/people.edn", dir.path().display());
+        assert_eq!(
+            complete(&completer, &format!(".import {}", prefix)),
+            vec![expected]
+        );
+        assert!(complete(&completer, &format!(".import data.edn {}", prefix)).is_empty());
+    }
+}
@@ -8,7 +8,9 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
+use std::fs;
 use std::io::{stdin, stdout, Write};
+use std::sync::Arc;
 
 use linefeed::{DefaultTerminal, Interface, ReadResult, Signal};
 
@@ -17,13 +19,17 @@ use termion::color;
 use self::InputResult::*;
 
 use command_parser::{command, Command};
+use completion::InputCompleter;
+use nesting::Nesting;
+
+use mentat::Schema;
 
 use failure::Error;
 
 /// Starting prompt
 const DEFAULT_PROMPT: &str = "mentat=> ";
-/// Prompt when further input is being read
-// TODO: Should this actually reflect the current open brace?
+/// Prompt when further input is being read, showing the innermost bracket or string still open,
+/// as in "mentat[> "
 const MORE_PROMPT: &str = "mentat.> ";
 
 /// Possible results from reading input from `InputReader`
@@ -44,6 +50,9 @@ pub struct InputReader {
     buffer: String,
     interface: Option<Interface<DefaultTerminal>>,
     in_process_cmd: Option<Command>,
+    completer: Arc<InputCompleter>,
+    /// The path of the open database, whose history we read and write.
+    database: String,
 }
 
 enum UserAction {
@@ -62,12 +71,15 @@ enum UserAction {
 impl InputReader {
     /// Constructs a new `InputReader` reading from `stdin`.
     pub fn new(interface: Option<Interface<DefaultTerminal>>) -> InputReader {
+        let completer = Arc::new(InputCompleter::default());
         if let Some(ref interface) = interface {
             // It's fine to fail to load history.
-            let p = ::history_file_path();
+            let p = ::history_file_path("");
             let loaded = interface.load_history(&p);
             debug!("history read from {}: {}", p.display(), loaded.is_ok());
 
+            interface.set_completer(completer.clone());
+
             let mut r = interface.lock_reader();
             // Handle SIGINT (Ctrl-C)
             r.set_report_signal(Signal::Interrupt, true);
@@ -78,6 +90,31 @@ impl InputReader {
             buffer: String::new(),
             interface,
             in_process_cmd: None,
+            completer,
+            database: String::new(),
+        }
+    }
+
+    /// Switches to the history of the database at `path`, saving that of the previous one.
+    pub fn set_database(&mut self, path: &str) {
+        if path == self.database {
+            return;
+        }
+        self.save_history();
+        self.database = path.to_string();
+        if let Some(ref interface) = self.interface {
+            interface.clear_history();
+            // It's fine to fail to load history.
+            let p = ::history_file_path(path);
+            let loaded = interface.load_history(&p);
+            debug!("history read from {}: {}", p.display(), loaded.is_ok());
+        }
+    }
+
+    /// Completes idents from `schema` from now on.
+    pub fn set_schema(&self, schema: &Schema) {
+        if self.interface.is_some() {
+            self.completer.set_schema(schema);
         }
     }
 
@@ -91,9 +128,12 @@ impl InputReader {
     /// In this case, the input received so far is buffered internally.
     pub fn read_input(&mut self) -> Result<InputResult, Error> {
         let prompt = if self.in_process_cmd.is_some() {
-            MORE_PROMPT
+            match Nesting::of(&self.buffer).innermost() {
+                Some(open) => format!("mentat{}> ", open),
+                None => MORE_PROMPT.to_string(),
+            }
         } else {
-            DEFAULT_PROMPT
+            DEFAULT_PROMPT.to_string()
         };
         let prompt = format!(
             "{blue}{prompt}{reset}",
@@ -101,6 +141,7 @@ impl InputReader {
             prompt = prompt,
             reset = color::Fg(color::Reset)
         );
+        self.completer.set_pending(&self.buffer);
         let line = match self.read_line(prompt.as_str()) {
             UserAction::TextInput(s) => s,
             UserAction::Interrupt if self.in_process_cmd.is_some() => {
@@ -218,9 +259,12 @@ impl InputReader {
 
     pub fn save_history(&self) {
         if let Some(ref interface) = self.interface {
-            let p = ::history_file_path();
+            let p = ::history_file_path(&self.database);
             // It's okay to fail to save history.
-            let saved = interface.save_history(&p);
+            let saved = p
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| interface.save_history(&p));
             debug!("history saved to {}: {}", p.display(), saved.is_ok());
         }
     }
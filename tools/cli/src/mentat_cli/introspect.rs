@@ -0,0 +1,370 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Looking around a store from the REPL: `.describe`, `.vocabularies`, `.history` and `.tx`.
+
+use std::io::Write;
+
+use failure::{err_msg, Error};
+
+use tabwriter::TabWriter;
+
+use mentat::json::{format_instant, parse_keyword};
+use mentat::vocabulary::HasVocabularies;
+use mentat::{attribute, Entid, HasSchema, Keyword, Schema, Store, TypedValue};
+
+use mentat_db::TypedSQLValue;
+
+use transfer::edn_value;
+
+/// `entity` is an entid or an ident.
+fn resolve(schema: &Schema, entity: &str) -> Result<Entid, Error> {
+    if let Ok(entid) = entity.parse() {
+        return Ok(entid);
+    }
+    parse_keyword(entity)
+        .and_then(|ident| schema.get_entid(&ident))
+        .map(Entid::from)
+        .ok_or_else(|| err_msg(format!("No entity {}", entity)))
+}
+
+/// A value as EDN, with entities named by their idents, if they have them.
+fn value_string(schema: &Schema, value: &TypedValue) -> String {
+    match value {
+        TypedValue::Ref(e) => match schema.get_ident(*e) {
+            Some(ident) => ident.to_string(),
+            None => e.to_string(),
+        },
+        value => edn_value(value),
+    }
+}
+
+fn entity_string(schema: &Schema, e: Entid) -> String {
+    value_string(schema, &TypedValue::Ref(e))
+}
+
+fn is_fulltext(schema: &Schema, a: Entid) -> bool {
+    schema
+        .attribute_for_entid(a)
+        .is_some_and(|attribute| attribute.fulltext)
+}
+
+/// Reads the value of a datom, looking up the text of a fulltext value.
+fn typed_value(
+    sqlite: &rusqlite::Connection,
+    schema: &Schema,
+    a: Entid,
+    v: rusqlite::types::Value,
+    value_type_tag: i32,
+) -> Result<TypedValue, Error> {
+    if let (true, rusqlite::types::Value::Integer(rowid)) = (is_fulltext(schema, a), &v) {
+        let text: String = sqlite.query_row(
+            "SELECT text FROM fulltext_values WHERE rowid = ?",
+            [rowid],
+            |row| row.get(0),
+        )?;
+        return Ok(TypedValue::typed_string(text));
+    }
+    Ok(TypedValue::from_sql_value_pair(v, value_type_tag)?)
+}
+
+/// When the transaction `tx` happened.
+fn tx_instant(sqlite: &rusqlite::Connection, schema: &Schema, tx: Entid) -> Result<String, Error> {
+    let tx_instant = schema
+        .get_entid(&Keyword::namespaced("db", "txInstant"))
+        .map(Entid::from)
+        .ok_or_else(|| err_msg("No :db/txInstant"))?;
+    let mut stmt =
+        sqlite.prepare("SELECT v, value_type_tag FROM datoms WHERE e = ? AND a = ? LIMIT 1")?;
+    let mut rows = stmt.query([tx, tx_instant])?;
+    Ok(match rows.next()? {
+        Some(row) => match TypedValue::from_sql_value_pair(row.get(0)?, row.get(1)?)? {
+            TypedValue::Instant(instant) => format_instant(&instant),
+            value => edn_value(&value),
+        },
+        None => "".to_string(),
+    })
+}
+
+/// Everything the schema knows about `attribute`, and how many datoms use it.
+pub fn describe<W: Write>(out: W, store: &mut Store, attribute: &str) -> Result<(), Error> {
+    let schema = store.conn().current_schema();
+    let entid = resolve(&schema, attribute)?;
+    let definition = schema
+        .attribute_for_entid(entid)
+        .ok_or_else(|| err_msg(format!("{} is not an attribute", attribute)))?
+        .clone();
+
+    let vocabularies = store.begin_read()?.read_vocabularies()?;
+    let vocabulary = vocabularies.iter().find(|(_, vocabulary)| {
+        vocabulary
+            .attributes()
+            .iter()
+            .any(|&(attribute, _)| attribute == entid)
+    });
+
+    let sqlite: &rusqlite::Connection = store.sqlite_mut();
+    let count: i64 =
+        sqlite.query_row("SELECT count(*) FROM datoms WHERE a = ?", [entid], |row| {
+            row.get(0)
+        })?;
+    let doc: Option<String> = match schema.get_entid(&Keyword::namespaced("db", "doc")) {
+        Some(doc) => {
+            let mut stmt = sqlite.prepare("SELECT v FROM datoms WHERE e = ? AND a = ?")?;
+            let mut rows = stmt.query([entid, doc.into()])?;
+            match rows.next()? {
+                Some(row) => row.get(0)?,
+                None => None,
+            }
+        }
+        None => None,
+    };
+
+    let mut out = TabWriter::new(out);
+    writeln!(out, "{}\t", entity_string(&schema, entid))?;
+    writeln!(out, "  entid\t{}", entid)?;
+    writeln!(out, "  type\t{}", definition.value_type.into_keyword())?;
+    writeln!(
+        out,
+        "  cardinality\t{}",
+        if definition.multival { "many" } else { "one" }
+    )?;
+    let unique = match definition.unique {
+        Some(attribute::Unique::Identity) => "identity",
+        Some(attribute::Unique::Value) => "value",
+        None => "no",
+    };
+    writeln!(out, "  unique\t{}", unique)?;
+    writeln!(out, "  index\t{}", definition.index)?;
+    writeln!(out, "  fulltext\t{}", definition.fulltext)?;
+    writeln!(out, "  component\t{}", definition.component)?;
+    writeln!(out, "  no history\t{}", definition.no_history)?;
+    if let Some((name, vocabulary)) = vocabulary {
+        writeln!(out, "  vocabulary\t{} version {}", name, vocabulary.version)?;
+    }
+    if let Some(doc) = doc {
+        writeln!(out, "  doc\t{}", doc)?;
+    }
+    writeln!(out, "  datoms\t{}", count)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Every vocabulary installed in the store, with its version.
+pub fn vocabularies<W: Write>(out: W, store: &mut Store) -> Result<(), Error> {
+    let vocabularies = store.begin_read()?.read_vocabularies()?;
+
+    let mut out = TabWriter::new(out);
+    writeln!(out, "| vocabulary\t| version\t| attributes\t")?;
+    writeln!(out, "---\t---\t---\t")?;
+    for (name, vocabulary) in vocabularies.iter() {
+        writeln!(
+            out,
+            "| {}\t| {}\t| {}\t",
+            name,
+            vocabulary.version,
+            vocabulary.attributes().len()
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Every transaction that asserted or retracted something about `entity`, oldest first.
+pub fn history<W: Write>(out: W, store: &mut Store, entity: &str) -> Result<(), Error> {
+    let schema = store.conn().current_schema();
+    let entid = resolve(&schema, entity)?;
+    let sqlite: &rusqlite::Connection = store.sqlite_mut();
+
+    let mut stmt = sqlite.prepare(
+        "SELECT tx, a, v, value_type_tag, added FROM transactions WHERE e = ?
+         ORDER BY tx, a, value_type_tag, v, added",
+    )?;
+    let mut rows = stmt.query([entid])?;
+
+    let mut out = TabWriter::new(out);
+    writeln!(out, "| tx\t| instant\t| attribute\t| value\t| added\t")?;
+    writeln!(out, "---\t---\t---\t---\t---\t")?;
+    let mut last_tx = None;
+    while let Some(row) = rows.next()? {
+        let tx: Entid = row.get(0)?;
+        let a: Entid = row.get(1)?;
+        let v = typed_value(sqlite, &schema, a, row.get(2)?, row.get(3)?)?;
+        let added: bool = row.get(4)?;
+
+        // Name each transaction once.
+        let (tx_string, instant) = if last_tx == Some(tx) {
+            ("".to_string(), "".to_string())
+        } else {
+            (tx.to_string(), tx_instant(sqlite, &schema, tx)?)
+        };
+        last_tx = Some(tx);
+        writeln!(
+            out,
+            "| {}\t| {}\t| {}\t| {}\t| {}\t",
+            tx_string,
+            instant,
+            entity_string(&schema, a),
+            value_string(&schema, &v),
+            added
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The datoms that transaction `tx` asserted and retracted.
+pub fn transaction<W: Write>(out: W, store: &mut Store, tx: Entid) -> Result<(), Error> {
+    let schema = store.conn().current_schema();
+    let sqlite: &rusqlite::Connection = store.sqlite_mut();
+
+    let mut stmt = sqlite.prepare(
+        "SELECT e, a, v, value_type_tag, added FROM transactions WHERE tx = ?
+         ORDER BY e, a, value_type_tag, v, added",
+    )?;
+    let datoms = stmt
+        .query_and_then([tx], |row| -> Result<_, Error> {
+            let a: Entid = row.get(1)?;
+            let v = typed_value(sqlite, &schema, a, row.get(2)?, row.get(3)?)?;
+            Ok((row.get::<_, Entid>(0)?, a, v, row.get::<_, bool>(4)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if datoms.is_empty() {
+        return Err(err_msg(format!("No transaction {}", tx)));
+    }
+
+    let mut out = TabWriter::new(out);
+    writeln!(out, "{}\t{}", tx, tx_instant(sqlite, &schema, tx)?)?;
+    writeln!(out, "| e\t| a\t| v\t| added\t")?;
+    writeln!(out, "---\t---\t---\t---\t")?;
+    for (e, a, v, added) in datoms {
+        writeln!(
+            out,
+            "| {}\t| {}\t| {}\t| {}\t",
+            entity_string(&schema, e),
+            entity_string(&schema, a),
+            value_string(&schema, &v),
+            added
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output<F>(f: F) -> String
+    where
+        F: FnOnce(&mut Vec<u8>) -> Result<(), Error>,
+    {
+        let mut out = vec![];
+        f(&mut out).expect("written");
+        String::from_utf8(out).unwrap()
+    }
+
+    fn store() -> Store {
+        let mut store = Store::open("").expect("opened");
+        store
+            .transact(
+                r#"[{:db/ident :person/name :db/valueType :db.type/string
+                     :db/cardinality :db.cardinality/one :db/unique :db.unique/identity
+                     :db/index true :db/doc "A person's name."}
+                    {:db/ident :person/bio :db/valueType :db.type/string
+                     :db/cardinality :db.cardinality/one :db/fulltext true :db/index true}]"#,
+            )
+            .expect("schema");
+        store
+            .transact(r#"[{:db/ident :org.example/people :db.schema/version 3 :db.schema/attribute [:person/name :person/bio]}]"#)
+            .expect("vocabulary");
+        store
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut store = store();
+        store
+            .transact(r#"[{:person/name "Alice"} {:person/name "Bob"}]"#)
+            .expect("data");
+
+        let description = output(|out| describe(out, &mut store, ":person/name"));
+        let lines: Vec<String> = description
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(lines[0], ":person/name");
+        assert!(lines.iter().any(|line| line == "type :db.type/string"));
+        assert!(lines.iter().any(|line| line == "cardinality one"));
+        assert!(lines.iter().any(|line| line == "unique identity"));
+        assert!(lines
+            .iter()
+            .any(|line| line == "vocabulary :org.example/people version 3"));
+        assert!(lines.iter().any(|line| line == "doc A person's name."));
+        assert!(lines.iter().any(|line| line == "datoms 2"));
+
+        let err =
+            describe(vec![], &mut store, ":org.example/people").expect_err("not an attribute");
+        assert_eq!(err.to_string(), ":org.example/people is not an attribute");
+    }
+
+    #[test]
+    fn test_vocabularies() {
+        let mut store = store();
+        let listing = output(|out| vocabularies(out, &mut store));
+        assert!(
+            listing.contains("| :org.example/people  | 3"),
+            "{}",
+            listing
+        );
+        assert!(listing.contains("| :db.schema/core"), "{}", listing);
+    }
+
+    #[test]
+    fn test_history_and_tx() {
+        let mut store = store();
+        let report = store
+            .transact(r#"[{:db/id "a" :person/name "Alice" :person/bio "Writes."}]"#)
+            .expect("data");
+        let alice = report.tempids["a"];
+        let retraction = store
+            .transact(r#"[[:db/retract (lookup-ref :person/name "Alice") :person/bio "Writes."]]"#)
+            .expect("retracted");
+
+        let changes = output(|out| history(out, &mut store, &alice.to_string()));
+        let rows: Vec<&str> = changes.lines().skip(2).collect();
+        assert_eq!(rows.len(), 3, "{}", changes);
+        assert!(rows[0].starts_with(&format!("| {}", report.tx_id)));
+        assert!(rows.iter().any(|row| row.contains(":person/bio")
+            && row.contains("\"Writes.\"")
+            && row.trim_end().ends_with("false")));
+        assert!(rows[2].starts_with(&format!("| {}", retraction.tx_id)));
+
+        let datoms = output(|out| transaction(out, &mut store, retraction.tx_id));
+        assert!(datoms.starts_with(&retraction.tx_id.to_string()));
+        assert!(datoms.contains(":person/bio"), "{}", datoms);
+        assert!(datoms.contains("\"Writes.\""), "{}", datoms);
+        assert!(datoms.contains(":db/txInstant"));
+
+        // Only the datoms of the transaction we asked about, not of those that followed it.
+        let datoms = output(|out| transaction(out, &mut store, report.tx_id));
+        let rows: Vec<&str> = datoms.lines().skip(3).collect();
+        assert_eq!(rows.len(), 3, "{}", datoms);
+        assert!(rows.iter().all(|row| row.trim_end().ends_with("true")));
+
+        let err = transaction(vec![], &mut store, retraction.tx_id + 1).expect_err("no such tx");
+        assert_eq!(
+            err.to_string(),
+            format!("No transaction {}", retraction.tx_id + 1)
+        );
+        let err = history(vec![], &mut store, ":no/such").expect_err("no such entity");
+        assert_eq!(err.to_string(), "No entity :no/such");
+    }
+}
@@ -24,9 +24,14 @@ extern crate dirs;
 extern crate env_logger;
 extern crate failure;
 extern crate getopts;
+extern crate hex;
 extern crate linefeed;
 extern crate rusqlite;
+#[macro_use]
+extern crate serde_json;
 extern crate tabwriter;
+#[cfg(test)]
+extern crate tempfile;
 extern crate termion;
 extern crate time;
 
@@ -45,23 +50,49 @@ static HISTORY_FILE_PATH: &str = ".mentat_history";
 /// The Mentat CLI stores input history in a readline-compatible file like "~/.mentat_history".
 /// This accords with main other tools which prefix with "." and suffix with "_history": lein,
 /// node_repl, python, and sqlite, at least.
-pub(crate) fn history_file_path() -> PathBuf {
+///
+/// That file holds the history of the in-memory database.  Each database on disk has its own,
+/// in "~/.mentat_history.d", named for the database's path with anything but alphanumerics,
+/// '.', '-' and '_' percent-encoded: "%2Fhome%2Fme%2Ftodo.db".
+pub(crate) fn history_file_path(database: &str) -> PathBuf {
     let mut p = dirs::home_dir().unwrap_or_default();
-    p.push(::HISTORY_FILE_PATH);
+    if database.is_empty() {
+        p.push(::HISTORY_FILE_PATH);
+        return p;
+    }
+
+    let database = std::fs::canonicalize(database).unwrap_or_else(|_| PathBuf::from(database));
+    let mut name = String::new();
+    for b in database.to_string_lossy().bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' => name.push(b as char),
+            _ => name.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    p.push(format!("{}.d", ::HISTORY_FILE_PATH));
+    p.push(name);
     p
 }
 
 static BLUE: color::Rgb = color::Rgb(0x99, 0xaa, 0xFF);
 static GREEN: color::Rgb = color::Rgb(0x77, 0xFF, 0x99);
 
+pub mod batch;
 pub mod command_parser;
+mod completion;
 pub mod input;
+pub mod introspect;
+mod nesting;
 pub mod repl;
+pub mod transfer;
 
 #[derive(Debug, Fail)]
 pub enum CliError {
     #[fail(display = "{}", _0)]
     CommandParse(String),
+
+    #[fail(display = "{}", _0)]
+    Usage(String),
 }
 
 pub fn run() -> i32 {
@@ -98,6 +129,18 @@ pub fn run() -> i32 {
         "Execute an import on startup. Imports are executed before queries.",
         "PATH",
     );
+    opts.optopt(
+        "f",
+        "file",
+        "Read the query or transaction for `query` or `transact` from a file, or stdin for `-`",
+        "PATH",
+    );
+    opts.optopt(
+        "",
+        "format",
+        "How `query` and `transact` write their output: table, edn, json or csv",
+        "FORMAT",
+    );
     opts.optflag("v", "version", "Print version and exit");
     opts.optflag(
         "",
@@ -129,6 +172,28 @@ pub fn run() -> i32 {
         false => None,
     };
 
+    // `mentat_cli [-d DATABASE] query|transact …` runs one command and exits.
+    if let Some(command) = matches.free.first() {
+        let format = match matches.opt_str("format").map(|f| f.parse()).transpose() {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("{}: {}", args[0], e);
+                return batch::EXIT_USAGE;
+            }
+        };
+        let batch = batch::Batch {
+            path: matches.opt_str("d").unwrap_or_default(),
+            key,
+            format,
+        };
+        return batch.run(
+            &args[0],
+            command,
+            &matches.free[1..],
+            matches.opt_str("file").as_deref(),
+        );
+    }
+
     let mut last_arg: Option<&str> = None;
 
     let cmds: Vec<command_parser::Command> = args
@@ -151,7 +216,7 @@ pub fn run() -> i32 {
             }
             Some("-i") => {
                 last_arg = None;
-                Some(command_parser::Command::Import(arg.clone()))
+                Some(command_parser::Command::Import(arg.clone(), None, false))
             }
             Some("-t") => {
                 last_arg = None;
@@ -185,7 +250,10 @@ pub fn version() -> &'static str {
 fn print_usage(arg0: &str, opts: &Options) {
     print!(
         "{}",
-        opts.usage(&format!("Usage: {} [OPTIONS] [FILE]", arg0))
+        opts.usage(&format!(
+            "Usage: {} [OPTIONS] [query|transact [QUERY|TRANSACTION]]",
+            arg0
+        ))
     );
 }
 
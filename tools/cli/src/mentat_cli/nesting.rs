@@ -0,0 +1,126 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+/// Tracks the brackets and strings left open by the EDN read so far, without parsing it, so
+/// that we know whether input ends between top-level forms or inside a collection or a string.
+#[derive(Debug, Default)]
+pub(crate) struct Nesting {
+    /// The brackets still open, innermost last.
+    open: Vec<char>,
+    in_string: bool,
+    in_comment: bool,
+    escaped: bool,
+    /// Whether anything but whitespace and comments has been read.
+    content: bool,
+}
+
+impl Nesting {
+    pub(crate) fn of(text: &str) -> Nesting {
+        let mut nesting = Nesting::default();
+        nesting.read(text);
+        nesting
+    }
+
+    pub(crate) fn read(&mut self, text: &str) {
+        for c in text.chars() {
+            if self.escaped {
+                // The character after a backslash is never a delimiter, in a string or out.
+                self.escaped = false;
+                continue;
+            }
+            if self.in_comment {
+                self.in_comment = c != '\n';
+                continue;
+            }
+            if self.in_string {
+                match c {
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => (),
+                }
+                continue;
+            }
+            match c {
+                // A comment runs to the end of the line.
+                ';' => {
+                    self.in_comment = true;
+                    continue;
+                }
+                '\\' => self.escaped = true,
+                '"' => self.in_string = true,
+                '[' | '(' | '{' => self.open.push(c),
+                // A mismatched or unmatched closing bracket is for the parser to report.
+                ']' | ')' | '}' => {
+                    self.open.pop();
+                }
+                _ => (),
+            }
+            if !c.is_whitespace() && c != ',' {
+                self.content = true;
+            }
+        }
+    }
+
+    pub(crate) fn has_content(&self) -> bool {
+        self.content
+    }
+
+    pub(crate) fn is_between_forms(&self) -> bool {
+        self.open.is_empty() && !self.in_string
+    }
+
+    /// Whether there's at least one form, and no bracket or string left open.
+    pub(crate) fn is_complete(&self) -> bool {
+        self.content && self.is_between_forms()
+    }
+
+    /// The innermost bracket, or string quote, still open.
+    pub(crate) fn innermost(&self) -> Option<char> {
+        if self.in_string {
+            Some('"')
+        } else {
+            self.open.last().cloned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting() {
+        let mut nesting = Nesting::default();
+        nesting.read("; a comment [\n");
+        assert!(nesting.is_between_forms());
+        assert!(!nesting.has_content());
+        nesting.read("[{:a \"]\\\"\" ; [\n");
+        assert!(!nesting.is_between_forms());
+        assert_eq!(nesting.innermost(), Some('{'));
+        nesting.read("}]\n");
+        assert!(nesting.is_between_forms());
+        assert!(nesting.is_complete());
+    }
+
+    #[test]
+    fn test_innermost() {
+        assert_eq!(Nesting::of("").innermost(), None);
+        assert_eq!(Nesting::of("[:find ?x :where [?x").innermost(), Some('['));
+        assert_eq!(Nesting::of("[:find (pull ?x").innermost(), Some('('));
+        assert_eq!(
+            Nesting::of("[[:db/add 1 :foo/bar \"a [").innermost(),
+            Some('"')
+        );
+        assert_eq!(Nesting::of("[\\] \\(").innermost(), Some('['));
+        assert!(!Nesting::of("").is_complete());
+        assert!(Nesting::of("[:find ?x)").is_complete());
+        assert!(Nesting::of(":find ?x").is_complete());
+    }
+}
@@ -9,6 +9,7 @@
 // specific language governing permissions and limitations under the License.
 
 use std::io::Write;
+use std::path::Path;
 
 use failure::Error;
 
@@ -23,17 +24,18 @@ use time::{Duration, Instant};
 use core_traits::StructuredMap;
 
 use mentat::{
-    Binding, CacheDirection, Keyword, QueryExplanation, QueryOutput, QueryResults, Queryable,
-    Store, TxReport, TypedValue,
+    Binding, CacheDirection, DateTime, HistoryBound, Keyword, QueryOutput, QueryPlan, QueryResults,
+    Queryable, Store, TxReport, TypedValue, Utc,
 };
 
 use command_parser::Command;
 
 use command_parser::{
-    COMMAND_CACHE, COMMAND_EXIT_LONG, COMMAND_EXIT_SHORT, COMMAND_HELP, COMMAND_IMPORT_LONG,
-    COMMAND_OPEN, COMMAND_QUERY_EXPLAIN_LONG, COMMAND_QUERY_EXPLAIN_SHORT, COMMAND_QUERY_LONG,
+    COMMAND_CACHE, COMMAND_COMPACT, COMMAND_DESCRIBE, COMMAND_EXIT_LONG, COMMAND_EXIT_SHORT,
+    COMMAND_EXPORT, COMMAND_HELP, COMMAND_HISTORY, COMMAND_IMPORT_LONG, COMMAND_OPEN,
+    COMMAND_QUERY_EXPLAIN_LONG, COMMAND_QUERY_EXPLAIN_SHORT, COMMAND_QUERY_LONG,
     COMMAND_QUERY_PREPARED_LONG, COMMAND_QUERY_SHORT, COMMAND_SCHEMA, COMMAND_TIMER_LONG,
-    COMMAND_TRANSACT_LONG, COMMAND_TRANSACT_SHORT,
+    COMMAND_TRANSACT_LONG, COMMAND_TRANSACT_SHORT, COMMAND_TX, COMMAND_VOCABULARIES,
 };
 
 // These are still defined when this feature is disabled (so that we can
@@ -46,6 +48,9 @@ use command_parser::COMMAND_OPEN_ENCRYPTED;
 #[cfg(feature = "syncable")]
 use command_parser::COMMAND_SYNC;
 
+use introspect;
+use transfer;
+
 use input::InputReader;
 use input::InputResult::{Empty, Eof, MetaCommand, More};
 
@@ -64,7 +69,17 @@ lazy_static! {
 
             (COMMAND_SCHEMA, "Output the schema for the current open database."),
 
-            (COMMAND_IMPORT_LONG, "Transact the contents of a file against the current open database."),
+            (COMMAND_DESCRIBE, "Describe an attribute, and count the datoms that use it. Usage: `.describe :foo/bar`"),
+
+            (COMMAND_VOCABULARIES, "List the vocabularies installed in the current open database, with their versions."),
+
+            (COMMAND_HISTORY, "Show every transaction that touched an entity, given its entid or ident. Usage: `.history 65536`"),
+
+            (COMMAND_TX, "Show the datoms a transaction asserted and retracted. Usage: `.tx 268435457`"),
+
+            (COMMAND_IMPORT_LONG, "Transact the contents of a file against the current open database, in batches. Usage: `.import data.edn [BATCH_SIZE] [resume]`"),
+
+            (COMMAND_EXPORT, "Write the current open database to a file, or for CSV a directory. Usage: `.export data.edn [edn|json|csv] [history]`"),
 
             (COMMAND_QUERY_LONG, "Execute a query against the current open database."),
             (COMMAND_QUERY_SHORT, "Shortcut for `.query`. Execute a query against the current open database."),
@@ -81,6 +96,8 @@ lazy_static! {
 
             (COMMAND_CACHE, "Cache an attribute. Usage: `.cache :foo/bar reverse`"),
 
+            (COMMAND_COMPACT, "Compact history before a tx or instant, keeping the history of the given attributes. Usage: `.compact 2018-01-01T00:00:00Z :foo/bar`"),
+
             #[cfg(feature = "syncable")]
             (COMMAND_SYNC, "Synchronize the database against a Mentat Sync Server URL for a provided user UUID."),
         ]
@@ -204,7 +221,9 @@ impl Repl {
         println!("mentat version a.b.c.d");
         println!("Enter \".help\" for instructions");
         loop {
-
+            // Any command might have changed the schema.
+            self.input_reader
+                .set_schema(&self.store.conn().current_schema());
             let res = self.input_reader.read_input();
 
             match res {
@@ -239,6 +258,33 @@ impl Repl {
         }
     }
 
+    fn compact(&mut self, bound: String, keep: Vec<String>) {
+        let bound = match bound.parse::<i64>() {
+            Ok(tx) => HistoryBound::Tx(tx),
+            Err(_) => match bound.parse::<DateTime<Utc>>() {
+                Ok(instant) => HistoryBound::Instant(instant),
+                Err(_) => {
+                    eprintln!("Invalid tx or instant {}", bound);
+                    return;
+                }
+            },
+        };
+        let mut attrs: Vec<Keyword> = Vec::with_capacity(keep.len());
+        for attr in keep {
+            match parse_namespaced_keyword(attr.as_str()) {
+                Some(kw) => attrs.push(kw),
+                None => {
+                    eprintln!("Invalid attribute {}", attr);
+                    return;
+                }
+            }
+        }
+        match self.store.compact_history(bound, &attrs) {
+            Ok(removed) => println!("Removed {} rows of history.", removed),
+            Err(e) => eprintln!("Couldn't compact history: {}", e),
+        };
+    }
+
     /// Runs a single command input.
     fn handle_command(&mut self, cmd: Command) -> bool {
         let should_print_times = self.timer_on && cmd.is_timed();
@@ -253,6 +299,15 @@ impl Repl {
             Command::Close => {
                 self.close();
             }
+            Command::Compact(bound, keep) => {
+                self.compact(bound, keep);
+            }
+            Command::Describe(attribute) => {
+                let stdout = ::std::io::stdout();
+                if let Err(e) = introspect::describe(stdout.lock(), &mut self.store, &attribute) {
+                    eprintln!("{}", e);
+                }
+            }
             Command::Exit => {
                 eprintln!("Exiting…");
                 return false;
@@ -260,8 +315,17 @@ impl Repl {
             Command::Help(args) => {
                 self.help_command(args);
             }
-            Command::Import(path) => {
-                self.execute_import(path);
+            Command::Export(path, format, history) => {
+                self.execute_export(path, format, history);
+            }
+            Command::History(entity) => {
+                let stdout = ::std::io::stdout();
+                if let Err(e) = introspect::history(stdout.lock(), &mut self.store, &entity) {
+                    eprintln!("{}", e);
+                }
+            }
+            Command::Import(path, batch_size, resume) => {
+                self.execute_import(path, batch_size, resume);
             }
             Command::Open(db) => {
                 match self.open(db) {
@@ -344,6 +408,18 @@ impl Repl {
             Command::Transact(transaction) => {
                 self.execute_transact(transaction);
             }
+            Command::Tx(tx) => {
+                let stdout = ::std::io::stdout();
+                if let Err(e) = introspect::transaction(stdout.lock(), &mut self.store, tx) {
+                    eprintln!("{}", e);
+                }
+            }
+            Command::Vocabularies => {
+                let stdout = ::std::io::stdout();
+                if let Err(e) = introspect::vocabularies(stdout.lock(), &mut self.store) {
+                    eprintln!("{}", e);
+                }
+            }
         }
 
         let end = end.unwrap_or_else(Instant::now);
@@ -356,16 +432,39 @@ impl Repl {
         true
     }
 
-    fn execute_import<T>(&mut self, path: T)
-    where
-        T: Into<String>,
-    {
-        use std::io::Read;
-        let path = path.into();
-        let mut content: String = "".to_string();
-        match ::std::fs::File::open(path.clone()).and_then(|mut f| f.read_to_string(&mut content)) {
-            Ok(_) => self.execute_transact(content),
-            Err(e) => eprintln!("Error reading file {}: {}", path, e),
+    fn execute_export(&mut self, path: String, format: transfer::Format, history: bool) {
+        match transfer::export(&mut self.store, &path, format, history) {
+            Ok(count) => println!("Exported {} datoms to {}", count, path),
+            Err(e) => eprintln!("Error exporting to {}: {}", path, e),
+        }
+    }
+
+    fn execute_import(&mut self, path: String, batch_size: Option<usize>, resume: bool) {
+        let batch_size = batch_size.unwrap_or(transfer::DEFAULT_BATCH_SIZE);
+        let imported = transfer::import(&mut self.store, &path, batch_size, resume, |progress| {
+            let percent = (progress.bytes * 100)
+                .checked_div(progress.total_bytes)
+                .unwrap_or(100);
+            eprint!(
+                "\r{}%: {} entities in {} transactions",
+                percent, progress.entities, progress.transactions
+            );
+        });
+        eprintln!();
+        match imported {
+            Ok(progress) => println!(
+                "Imported {} entities from {} lines of {}",
+                progress.entities, progress.lines, path
+            ),
+            Err(e) => {
+                eprintln!("Error importing {}: {}", path, e);
+                if Path::new(&format!("{}.progress", path)).exists() {
+                    eprintln!(
+                        "Fix the problem and run `.{} {} {} resume` to carry on.",
+                        COMMAND_IMPORT_LONG, path, batch_size
+                    );
+                }
+            }
         }
     }
 
@@ -389,6 +488,7 @@ impl Repl {
             };
             self.path = path;
             self.store = next;
+            self.input_reader.set_database(&self.path);
         }
 
         Ok(())
@@ -450,73 +550,49 @@ impl Repl {
 
     fn print_results(&self, query_output: QueryOutput) -> Result<(), Error> {
         let stdout = ::std::io::stdout();
-        let mut output = TabWriter::new(stdout.lock());
-
-        // Print the column headers.
-        for e in query_output.spec.columns() {
-            write!(output, "| {}\t", e)?;
-        }
-        writeln!(output, "|")?;
-        for _ in 0..query_output.spec.expected_column_count() {
-            write!(output, "---\t")?;
-        }
-        writeln!(output)?;
-
-        match query_output.results {
-            QueryResults::Scalar(v) => {
-                if let Some(val) = v {
-                    writeln!(output, "| {}\t |", &self.binding_as_string(&val))?;
-                }
-            }
-
-            QueryResults::Tuple(vv) => {
-                if let Some(vals) = vv {
-                    for val in vals {
-                        write!(output, "| {}\t", self.binding_as_string(&val))?;
-                    }
-                    writeln!(output, "|")?;
-                }
-            }
-
-            QueryResults::Coll(vv) => {
-                for val in vv {
-                    writeln!(output, "| {}\t|", self.binding_as_string(&val))?;
-                }
-            }
-
-            QueryResults::Rel(vvv) => {
-                for vv in vvv {
-                    for v in vv {
-                        write!(output, "| {}\t", self.binding_as_string(&v))?;
-                    }
-                    writeln!(output, "|")?;
-                }
-            }
-        }
-        for _ in 0..query_output.spec.expected_column_count() {
-            write!(output, "---\t")?;
-        }
-        writeln!(output)?;
-        output.flush()?;
-        Ok(())
+        write_table(stdout.lock(), query_output)
     }
 
     pub fn explain_query(&self, query: String) {
-        match self.store.q_explain(query.as_str(), None) {
-            Result::Err(err) => println!("{:?}.", err),
-            Result::Ok(QueryExplanation::KnownConstant) => println!("Query is known constant!"),
-            Result::Ok(QueryExplanation::KnownEmpty(empty_because)) => {
+        let explanation = match self.store.q_explain(query.as_str(), None) {
+            Result::Err(err) => {
+                println!("{:?}.", err);
+                return;
+            }
+            Result::Ok(explanation) => explanation,
+        };
+        match explanation.plan {
+            QueryPlan::KnownConstant => println!("Query is known constant!"),
+            QueryPlan::KnownEmpty(ref empty_because) => {
                 println!("Query is known empty: {:?}", empty_because)
             }
-            Result::Ok(QueryExplanation::ExecutionPlan { query, steps }) => {
+            QueryPlan::ExecutionPlan {
+                ref query,
+                ref tables,
+                ref steps,
+            } => {
                 println!("SQL: {}", query.sql);
                 if !query.args.is_empty() {
                     println!("  Bindings:");
-                    for (arg_name, value) in query.args {
+                    for (arg_name, value) in query.args.iter() {
                         println!("    {} = {:?}", arg_name, *value)
                     }
                 }
 
+                println!("Tables: alias | table | clause");
+                let max_alias = tables.iter().map(|t| t.alias.len()).max().unwrap_or(0);
+                let max_table = tables.iter().map(|t| t.table.len()).max().unwrap_or(0);
+                for table in tables {
+                    println!(
+                        "  {:<alias_cols$}|{:<table_cols$}|{}",
+                        table.alias,
+                        table.table,
+                        table.clause,
+                        alias_cols = max_alias,
+                        table_cols = max_table
+                    );
+                }
+
                 println!("Plan: select id | order | from | detail");
                 // Compute the number of columns we need for order, select id, and from,
                 // so that longer query plans don't become misaligned.
@@ -547,7 +623,26 @@ impl Repl {
                     );
                 }
             }
-        };
+        }
+
+        if !explanation.types.is_empty() {
+            println!("Types:");
+            for (var, types) in explanation.types.iter() {
+                let types: Vec<String> = types
+                    .into_iter()
+                    .map(|t| t.into_keyword().to_string())
+                    .collect();
+                println!("  {} {}", var, types.join(" "));
+            }
+        }
+        if !explanation.cached_attributes.is_empty() {
+            let cached: Vec<String> = explanation
+                .cached_attributes
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            println!("Cached attributes: {}", cached.join(" "));
+        }
     }
 
     pub fn execute_transact(&mut self, transaction: String) {
@@ -563,59 +658,113 @@ impl Repl {
         tx.commit()?;
         Ok(report)
     }
+}
 
-    fn binding_as_string(&self, value: &Binding) -> String {
-        use self::Binding::*;
-        match value {
-            Scalar(ref v) => self.value_as_string(v),
-            Map(ref v) => self.map_as_string(v),
-            Vec(ref v) => self.vec_as_string(v),
-        }
+/// Writes query results as a table, one row per line.
+pub(crate) fn write_table<W: Write>(output: W, query_output: QueryOutput) -> Result<(), Error> {
+    let mut output = TabWriter::new(output);
+
+    // Print the column headers.
+    for e in query_output.spec.columns() {
+        write!(output, "| {}\t", e)?;
     }
+    writeln!(output, "|")?;
+    for _ in 0..query_output.spec.expected_column_count() {
+        write!(output, "---\t")?;
+    }
+    writeln!(output)?;
+
+    match query_output.results {
+        QueryResults::Scalar(v) => {
+            if let Some(val) = v {
+                writeln!(output, "| {}\t |", &binding_as_string(&val))?;
+            }
+        }
+
+        QueryResults::Tuple(vv) => {
+            if let Some(vals) = vv {
+                for val in vals {
+                    write!(output, "| {}\t", binding_as_string(&val))?;
+                }
+                writeln!(output, "|")?;
+            }
+        }
+
+        QueryResults::Coll(vv) => {
+            for val in vv {
+                writeln!(output, "| {}\t|", binding_as_string(&val))?;
+            }
+        }
 
-    fn vec_as_string(&self, value: &[Binding]) -> String {
-        let mut out: String = "[".to_string();
-        let vals: Vec<String> = value.iter().map(|v| self.binding_as_string(v)).collect();
+        QueryResults::Rel(vvv) => {
+            for vv in vvv {
+                for v in vv {
+                    write!(output, "| {}\t", binding_as_string(&v))?;
+                }
+                writeln!(output, "|")?;
+            }
+        }
+    }
+    for _ in 0..query_output.spec.expected_column_count() {
+        write!(output, "---\t")?;
+    }
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
 
-        out.push_str(vals.join(", ").as_str());
-        out.push(']');
-        out
+fn binding_as_string(value: &Binding) -> String {
+    use self::Binding::*;
+    match value {
+        Scalar(ref v) => value_as_string(v),
+        Map(ref v) => map_as_string(v),
+        Vec(ref v) => vec_as_string(v),
+        Nil => "nil".to_string(),
     }
+}
 
-    fn map_as_string(&self, value: &StructuredMap) -> String {
-        let mut out: String = "{".to_string();
-        let mut first = true;
-        for (k, v) in value.0.iter() {
-            if !first {
-                out.push_str(", ");
-                first = true;
-            }
-            out.push_str(&k.to_string());
-            out.push(' ');
-            out.push_str(self.binding_as_string(v).as_str());
+fn vec_as_string(value: &[Binding]) -> String {
+    let mut out: String = "[".to_string();
+    let vals: Vec<String> = value.iter().map(binding_as_string).collect();
+
+    out.push_str(vals.join(", ").as_str());
+    out.push(']');
+    out
+}
+
+fn map_as_string(value: &StructuredMap) -> String {
+    let mut out: String = "{".to_string();
+    let mut first = true;
+    for (k, v) in value.0.iter() {
+        if !first {
+            out.push_str(", ");
+            first = true;
         }
-        out.push('}');
-        out
+        out.push_str(&k.to_string());
+        out.push(' ');
+        out.push_str(binding_as_string(v).as_str());
     }
+    out.push('}');
+    out
+}
 
-    fn value_as_string(&self, value: &TypedValue) -> String {
-        use self::TypedValue::*;
-        match value {
-            Boolean(b) => {
-                if *b {
-                    "true".to_string()
-                } else {
-                    "false".to_string()
-                }
+fn value_as_string(value: &TypedValue) -> String {
+    use self::TypedValue::*;
+    match value {
+        Boolean(b) => {
+            if *b {
+                "true".to_string()
+            } else {
+                "false".to_string()
             }
-            Double(d) => format!("{}", d),
-            Instant(ref i) => format!("{}", i),
-            Keyword(ref k) => format!("{}", k),
-            Long(l) => format!("{}", l),
-            Ref(r) => format!("{}", r),
-            String(ref s) => format!("{:?}", s.to_string()),
-            Uuid(ref u) => format!("{}", u),
-            Bytes(b) => format!("#bytes {:?}", b.to_vec()),
         }
+        Double(d) => format!("{}", d),
+        Instant(ref i) => format!("{}", i),
+        Keyword(ref k) => format!("{}", k),
+        Long(l) => format!("{}", l),
+        Ref(r) => format!("{}", r),
+        String(ref s) => format!("{:?}", s.to_string()),
+        Uuid(ref u) => format!("{}", u),
+        Bytes(b) => format!("#bytes {:?}", b.to_vec()),
     }
 }
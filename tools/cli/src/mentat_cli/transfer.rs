@@ -0,0 +1,1071 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Moving the contents of a store between environments: `.export` and `.import`.
+//!
+//! An EDN or JSON export is a file of transactions, one after another, that rebuilds the store when
+//! imported into an empty one.  Entities are named by string tempids, `"e65536"`, except for
+//! those every store shares, such as `:db.type/string`, which are named by their idents.
+//!
+//! ```edn
+//! [[:db/add "e65" :db/ident :person/name] [:db/add "e65" :db/valueType :db.type/string] …]
+//! [[:db/add "e65536" :person/name "Alice"] [:db/add "e65536" :person/friend "e65537"]]
+//! ```
+//!
+//! The first line installs the schema and the rest assert the current datoms.  An export of
+//! the full history instead replays every transaction, with its `:db/txInstant`.  A CSV export
+//! is a directory holding one file per attribute, with columns `e,v`, or `e,v,tx,added` for
+//! the full history.
+//!
+//! An import reads any file of EDN transactions, or of JSON transactions one per line, a batch
+//! at a time.  A tempid names the same entity throughout the file, not just within its own
+//! transaction.  Each committed batch is recorded alongside the file, in `FILE.progress`, so
+//! that an import that fails part of the way through can be resumed.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use failure::{err_msg, Error};
+
+use serde_json::Value as JsonValue;
+
+use edn::entities::{
+    AttributePlace, EntidOrIdent, Entity, EntityPlace, MapNotation, TempId, ValuePlace,
+};
+use edn::{SpannedValue, ValueAndSpan};
+
+use mentat::json::{encode_base64, entities_from_json, format_instant};
+use mentat::{Entid, HasSchema, Keyword, Schema, Store, TypedValue, ValueType};
+
+use mentat_db::{TypedSQLValue, TX0};
+
+use nesting::Nesting;
+
+/// How many forms an import transacts at a time, unless told otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// An export splits an entity with more datoms than this over several transactions.
+const MAX_DATOMS_PER_FORM: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Edn,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        match s {
+            "edn" => Ok(Format::Edn),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(err_msg(format!("Unknown format {:?}", s))),
+        }
+    }
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Edn => "edn",
+            Format::Json => "json",
+            Format::Csv => "csv",
+        }
+    }
+}
+
+/// An entity or value in an exported transaction.
+enum Term {
+    /// An entity that every store has.
+    Ident(Keyword),
+    TempId(Entid),
+    /// The transaction being replayed.
+    Transaction,
+    Value(TypedValue),
+}
+
+struct Op {
+    added: bool,
+    e: Term,
+    a: Keyword,
+    v: Term,
+}
+
+/// The EDN parser reads `\n` as `n`, so only quotes and backslashes are escaped: a string may
+/// span lines.
+pub(crate) fn edn_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub(crate) fn edn_value(value: &TypedValue) -> String {
+    match value {
+        TypedValue::Boolean(b) => b.to_string(),
+        TypedValue::Long(l) => l.to_string(),
+        TypedValue::Ref(e) => e.to_string(),
+        // `Debug` always writes a decimal point or an exponent, so this reads back as a double.
+        TypedValue::Double(d) if d.is_finite() => format!("{:?}", d.into_inner()),
+        TypedValue::Double(d) if d.is_nan() => "#f NaN".to_string(),
+        TypedValue::Double(d) if d.into_inner() > 0.0 => "#f +Infinity".to_string(),
+        TypedValue::Double(_) => "#f -Infinity".to_string(),
+        TypedValue::Instant(i) => format!("#inst {}", edn_string(&format_instant(i))),
+        TypedValue::Uuid(u) => format!("#uuid \"{}\"", u.to_hyphenated()),
+        TypedValue::Keyword(k) => k.to_string(),
+        TypedValue::String(s) => edn_string(s),
+        TypedValue::Bytes(b) => format!("#bytes {}", hex::encode(b)),
+    }
+}
+
+fn tempid(e: Entid) -> String {
+    format!("e{}", e)
+}
+
+impl Term {
+    fn to_edn(&self) -> String {
+        match self {
+            Term::Ident(ident) => ident.to_string(),
+            Term::TempId(e) => edn_string(&tempid(*e)),
+            Term::Transaction => "(transaction-tx)".to_string(),
+            Term::Value(value) => edn_value(value),
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        match self {
+            Term::Ident(ident) => json!({ "keyword": ident.to_string() }),
+            Term::TempId(e) => JsonValue::String(tempid(*e)),
+            Term::Transaction => json!({ "tx-function": "transaction-tx" }),
+            Term::Value(value) => value.to_json(),
+        }
+    }
+}
+
+impl Op {
+    fn name(&self) -> &'static str {
+        if self.added {
+            ":db/add"
+        } else {
+            ":db/retract"
+        }
+    }
+}
+
+/// Whether transacting `attribute` might change the schema, or the transaction itself.
+fn is_special(attribute: &Keyword) -> bool {
+    match attribute.namespace() {
+        Some("db") => attribute.name() != "doc",
+        Some(ns) => ns.starts_with("db."),
+        None => false,
+    }
+}
+
+/// Where an export is written: a file of transactions, or a directory of CSV files.
+enum Sink {
+    Edn(BufWriter<File>),
+    Json(BufWriter<File>),
+    Csv {
+        directory: String,
+        history: bool,
+        file: Option<(Entid, BufWriter<File>)>,
+    },
+}
+
+impl Sink {
+    fn write_form(&mut self, ops: &[Op]) -> Result<(), Error> {
+        match self {
+            Sink::Edn(out) => {
+                let ops: Vec<String> = ops
+                    .iter()
+                    .map(|op| {
+                        format!(
+                            "[{} {} {} {}]",
+                            op.name(),
+                            op.e.to_edn(),
+                            op.a,
+                            op.v.to_edn()
+                        )
+                    })
+                    .collect();
+                writeln!(out, "[{}]", ops.join(" "))?;
+            }
+            Sink::Json(out) => {
+                let ops: Vec<JsonValue> = ops
+                    .iter()
+                    .map(|op| json!([op.name(), op.e.to_json(), op.a.to_string(), op.v.to_json()]))
+                    .collect();
+                writeln!(out, "{}", JsonValue::Array(ops))?;
+            }
+            Sink::Csv { .. } => unreachable!("CSV is written a datom at a time"),
+        }
+        Ok(())
+    }
+
+    fn write_csv(
+        &mut self,
+        attribute: (Entid, &Keyword),
+        e: Entid,
+        v: &TypedValue,
+        tx: Option<(Entid, bool)>,
+    ) -> Result<(), Error> {
+        if let Sink::Csv {
+            directory,
+            history,
+            file,
+        } = self
+        {
+            if file.as_ref().map(|&(a, _)| a) != Some(attribute.0) {
+                let name = attribute.1.to_string()[1..].replace('/', ".");
+                let mut out = BufWriter::new(File::create(
+                    Path::new(directory).join(format!("{}.csv", name)),
+                )?);
+                writeln!(out, "{}", if *history { "e,v,tx,added" } else { "e,v" })?;
+                if let Some((_, mut previous)) = file.take() {
+                    previous.flush()?;
+                }
+                *file = Some((attribute.0, out));
+            }
+            let out = &mut file.as_mut().unwrap().1;
+            write!(out, "{},{}", e, csv_field(&csv_value(v)))?;
+            if let Some((tx, added)) = tx {
+                write!(out, ",{},{}", tx, added)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self {
+            Sink::Edn(mut out) | Sink::Json(mut out) => out.flush()?,
+            Sink::Csv { file, .. } => {
+                if let Some((_, mut out)) = file {
+                    out.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn csv_value(value: &TypedValue) -> String {
+    match value {
+        TypedValue::Instant(i) => format_instant(i),
+        TypedValue::String(s) => s.to_string(),
+        TypedValue::Bytes(b) => encode_base64(b),
+        TypedValue::Double(d) => d.to_string(),
+        value => edn_value(value),
+    }
+}
+
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes out a store, naming entities as the module describes.
+struct Exporter {
+    schema: Arc<Schema>,
+    /// The entities that every store has.
+    core: BTreeSet<Entid>,
+}
+
+impl Exporter {
+    fn new(store: &Store) -> Result<Exporter, Error> {
+        let empty = Store::open("")?;
+        Ok(Exporter {
+            schema: store.conn().current_schema(),
+            core: empty
+                .conn()
+                .current_schema()
+                .entid_map
+                .keys()
+                .cloned()
+                .collect(),
+        })
+    }
+
+    fn entity(&self, e: Entid, tx: Option<Entid>) -> Term {
+        if Some(e) == tx {
+            return Term::Transaction;
+        }
+        match self.schema.get_ident(e) {
+            Some(ident) if self.core.contains(&e) => Term::Ident(ident.clone()),
+            _ => Term::TempId(e),
+        }
+    }
+
+    fn value(&self, v: TypedValue, tx: Option<Entid>) -> Term {
+        match v {
+            TypedValue::Ref(e) => self.entity(e, tx),
+            v => Term::Value(v),
+        }
+    }
+
+    /// The transaction that installs every attribute not in an empty store.
+    fn schema_form(&self) -> Vec<Op> {
+        let mut ops = vec![];
+        for (&entid, attribute) in self.schema.attribute_map.iter() {
+            if self.core.contains(&entid) {
+                continue;
+            }
+            if let edn::Value::Map(map) =
+                attribute.to_edn_value(self.schema.get_ident(entid).cloned())
+            {
+                for (a, v) in map {
+                    let v = match v {
+                        edn::Value::Keyword(k) => TypedValue::Keyword(k.into()),
+                        edn::Value::Boolean(b) => TypedValue::Boolean(b),
+                        _ => continue,
+                    };
+                    if let edn::Value::Keyword(a) = a {
+                        ops.push(Op {
+                            added: true,
+                            e: Term::TempId(entid),
+                            a,
+                            v: Term::Value(v),
+                        });
+                    }
+                }
+            }
+        }
+        ops
+    }
+
+    /// Whether the current datom `[e a]` belongs in an export of the current state: it isn't
+    /// about a transaction, or about an entity that every store has, or part of the definition
+    /// of an attribute, which `schema_form` covers.
+    fn exports(&self, e: Entid, a: &Keyword) -> bool {
+        e < TX0
+            && !self.core.contains(&e)
+            && !(self.schema.attribute_map.contains_key(&e) && is_special(a))
+    }
+}
+
+/// Reads a datom's value, whose fulltext strings `all_datoms` has already looked up.
+fn typed_value(row: &rusqlite::Row, index: usize) -> Result<TypedValue, Error> {
+    let v: rusqlite::types::Value = row.get(index)?;
+    let value_type_tag: i32 = row.get(index + 1)?;
+    Ok(TypedValue::from_sql_value_pair(v, value_type_tag)?)
+}
+
+/// Write the store to `path`, a file or, for CSV, a directory.  Returns how many datoms were
+/// written.
+pub fn export(
+    store: &mut Store,
+    path: &str,
+    format: Format,
+    history: bool,
+) -> Result<usize, Error> {
+    let exporter = Exporter::new(store)?;
+    let mut sink = match format {
+        Format::Edn => Sink::Edn(BufWriter::new(File::create(path)?)),
+        Format::Json => Sink::Json(BufWriter::new(File::create(path)?)),
+        Format::Csv => {
+            fs::create_dir_all(path)?;
+            Sink::Csv {
+                directory: path.to_string(),
+                history,
+                file: None,
+            }
+        }
+    };
+    let sqlite: &rusqlite::Connection = store.sqlite_mut();
+    let count = if history {
+        export_history(&exporter, sqlite, &mut sink, format)?
+    } else {
+        export_datoms(&exporter, sqlite, &mut sink, format)?
+    };
+    sink.finish()?;
+    Ok(count)
+}
+
+fn export_datoms(
+    exporter: &Exporter,
+    sqlite: &rusqlite::Connection,
+    sink: &mut Sink,
+    format: Format,
+) -> Result<usize, Error> {
+    let order = if format == Format::Csv {
+        "a, e"
+    } else {
+        "e, a"
+    };
+    let mut stmt = sqlite.prepare(&format!(
+        "SELECT e, a, v, value_type_tag FROM all_datoms ORDER BY {}, value_type_tag, v",
+        order
+    ))?;
+    let mut rows = stmt.query([])?;
+
+    let mut count = 0;
+    let mut form: Vec<Op> = vec![];
+    if format != Format::Csv {
+        let schema = exporter.schema_form();
+        count += schema.len();
+        if !schema.is_empty() {
+            sink.write_form(&schema)?;
+        }
+    }
+
+    while let Some(row) = rows.next()? {
+        let e: Entid = row.get(0)?;
+        let a: Entid = row.get(1)?;
+        let ident = match exporter.schema.get_ident(a) {
+            Some(ident) => ident,
+            None => continue,
+        };
+        if !exporter.exports(e, ident) {
+            continue;
+        }
+        let v = typed_value(row, 2)?;
+        count += 1;
+
+        if format == Format::Csv {
+            sink.write_csv((a, ident), e, &v, None)?;
+            continue;
+        }
+
+        let same_entity = match form.last() {
+            Some(Op {
+                e: Term::TempId(last),
+                ..
+            }) => *last == e,
+            _ => false,
+        };
+        if (!same_entity || form.len() == MAX_DATOMS_PER_FORM) && !form.is_empty() {
+            sink.write_form(&form)?;
+            form.clear();
+        }
+        form.push(Op {
+            added: true,
+            e: Term::TempId(e),
+            a: ident.clone(),
+            v: exporter.value(v, None),
+        });
+    }
+    if !form.is_empty() {
+        sink.write_form(&form)?;
+    }
+    Ok(count)
+}
+
+fn export_history(
+    exporter: &Exporter,
+    sqlite: &rusqlite::Connection,
+    sink: &mut Sink,
+    format: Format,
+) -> Result<usize, Error> {
+    let order = if format == Format::Csv {
+        "a, tx, e"
+    } else {
+        "tx, e, a"
+    };
+    let mut stmt = sqlite.prepare(&format!(
+        "SELECT e, a, v, value_type_tag, tx, added FROM transactions WHERE tx > ?
+         ORDER BY {}, value_type_tag, v, added",
+        order
+    ))?;
+    let mut fulltext = sqlite.prepare("SELECT text FROM fulltext_values WHERE rowid = ?")?;
+    let mut rows = stmt.query([TX0])?;
+
+    let mut count = 0;
+    let mut form: Vec<Op> = vec![];
+    let mut form_tx = None;
+    while let Some(row) = rows.next()? {
+        let e: Entid = row.get(0)?;
+        let a: Entid = row.get(1)?;
+        let tx: Entid = row.get(4)?;
+        let added: bool = row.get(5)?;
+        let ident = match exporter.schema.get_ident(a) {
+            Some(ident) => ident,
+            None => continue,
+        };
+        let fulltext_attribute = exporter
+            .schema
+            .attribute_for_entid(a)
+            .is_some_and(|attribute| attribute.fulltext);
+        let v = if fulltext_attribute {
+            let rowid: i64 = row.get(2)?;
+            let text: String = fulltext.query_row([rowid], |row| row.get(0))?;
+            TypedValue::typed_string(text)
+        } else {
+            typed_value(row, 2)?
+        };
+        count += 1;
+
+        if format == Format::Csv {
+            sink.write_csv((a, ident), e, &v, Some((tx, added)))?;
+            continue;
+        }
+
+        // A transaction is replayed whole, however large.
+        if form_tx != Some(tx) && !form.is_empty() {
+            sink.write_form(&form)?;
+            form.clear();
+        }
+        form_tx = Some(tx);
+        form.push(Op {
+            added,
+            e: exporter.entity(e, Some(tx)),
+            a: ident.clone(),
+            v: exporter.value(v, Some(tx)),
+        });
+    }
+    if !form.is_empty() {
+        sink.write_form(&form)?;
+    }
+    Ok(count)
+}
+
+/// How far an import has got.
+#[derive(Clone, Debug, Default)]
+pub struct ImportProgress {
+    /// The lines read so far, including any skipped when resuming.
+    pub lines: usize,
+    pub bytes: u64,
+    pub total_bytes: u64,
+    pub transactions: usize,
+    pub entities: usize,
+}
+
+/// What an import has committed, as recorded in its progress file: the lines of the input that
+/// it has transacted, and the entities allocated for the tempids therein.
+#[derive(Default)]
+struct Checkpoint {
+    lines: usize,
+    tempids: HashMap<String, Entid>,
+}
+
+impl Checkpoint {
+    fn path(path: &str) -> String {
+        format!("{}.progress", path)
+    }
+
+    /// Each line of a progress file records a batch: `{"lines": n, "tempids": {…}}`.  A
+    /// partially written last line is ignored.
+    fn read(path: &str) -> Result<Checkpoint, Error> {
+        let mut checkpoint = Checkpoint::default();
+        for line in BufReader::new(File::open(Checkpoint::path(path))?).lines() {
+            let batch: JsonValue = match serde_json::from_str(&line?) {
+                Ok(batch) => batch,
+                Err(_) => break,
+            };
+            if let Some(lines) = batch["lines"].as_u64() {
+                checkpoint.lines = lines as usize;
+            }
+            if let Some(tempids) = batch["tempids"].as_object() {
+                for (tempid, e) in tempids {
+                    if let Some(e) = e.as_i64() {
+                        checkpoint.tempids.insert(tempid.clone(), e);
+                    }
+                }
+            }
+        }
+        Ok(checkpoint)
+    }
+}
+
+/// Whether `a` is an attribute whose values are entities.
+fn refers(a: &EntidOrIdent, schema: &Schema) -> bool {
+    let attribute = match a {
+        EntidOrIdent::Ident(ident) if ident.is_backward() => return true,
+        EntidOrIdent::Ident(ident) => schema.attribute_for_ident(ident).map(|(a, _)| a),
+        EntidOrIdent::Entid(e) => schema.attribute_for_entid(*e),
+    };
+    attribute.is_some_and(|attribute| attribute.value_type == ValueType::Ref)
+}
+
+/// Replaces the tempids that earlier batches have allocated with their entities.
+struct Resolver<'a> {
+    tempids: &'a HashMap<String, Entid>,
+    schema: &'a Schema,
+}
+
+impl<'a> Resolver<'a> {
+    fn tempid(&self, tempid: &TempId) -> Option<Entid> {
+        match tempid {
+            TempId::External(name) => self.tempids.get(name).cloned(),
+            TempId::Internal(_) => None,
+        }
+    }
+
+    fn entity(&self, entity: Entity<ValueAndSpan>) -> Entity<ValueAndSpan> {
+        match entity {
+            Entity::AddOrRetract { op, e, a, v } => {
+                let e = match e {
+                    EntityPlace::TempId(ref tempid) => match self.tempid(tempid) {
+                        Some(entid) => EntityPlace::Entid(EntidOrIdent::Entid(entid)),
+                        None => e,
+                    },
+                    e => e,
+                };
+                let AttributePlace::Entid(ref attribute) = a;
+                let v = if refers(attribute, self.schema) {
+                    self.value(v)
+                } else {
+                    v
+                };
+                Entity::AddOrRetract { op, e, a, v }
+            }
+            Entity::MapNotation(map) => Entity::MapNotation(self.map(map)),
+        }
+    }
+
+    fn map(&self, map: MapNotation<ValueAndSpan>) -> MapNotation<ValueAndSpan> {
+        map.into_iter()
+            .map(|(a, v)| {
+                let is_id = match a {
+                    EntidOrIdent::Ident(ref ident) => {
+                        ident.namespace() == Some("db") && ident.name() == "id"
+                    }
+                    EntidOrIdent::Entid(_) => false,
+                };
+                if is_id || refers(&a, self.schema) {
+                    (a, self.value(v))
+                } else {
+                    (a, v)
+                }
+            })
+            .collect()
+    }
+
+    fn value(&self, v: ValuePlace<ValueAndSpan>) -> ValuePlace<ValueAndSpan> {
+        let entid = match v {
+            ValuePlace::TempId(ref tempid) => self.tempid(tempid),
+            ValuePlace::Atom(ValueAndSpan {
+                inner: SpannedValue::Text(ref name),
+                ..
+            }) => self.tempids.get(name).cloned(),
+            ValuePlace::Vector(values) => {
+                return ValuePlace::Vector(values.into_iter().map(|v| self.value(v)).collect())
+            }
+            ValuePlace::MapNotation(map) => return ValuePlace::MapNotation(self.map(map)),
+            _ => None,
+        };
+        match entid {
+            Some(entid) => ValuePlace::Entid(EntidOrIdent::Entid(entid)),
+            None => v,
+        }
+    }
+}
+
+/// Whether `entities` must be transacted by themselves: because they might change the schema,
+/// which later entities might use, or say something about their own transaction.
+fn is_alone(entities: &[Entity<ValueAndSpan>]) -> bool {
+    fn special(a: &EntidOrIdent) -> bool {
+        match a {
+            EntidOrIdent::Ident(ident) => is_special(ident),
+            EntidOrIdent::Entid(_) => false,
+        }
+    }
+    entities.iter().any(|entity| match entity {
+        Entity::AddOrRetract { e, a, v, .. } => {
+            let AttributePlace::Entid(ref a) = a;
+            special(a)
+                || matches!(e, EntityPlace::TxFunction(_))
+                || matches!(v, ValuePlace::TxFunction(_))
+        }
+        Entity::MapNotation(map) => map.keys().any(|a| {
+            special(a) && !matches!(a, EntidOrIdent::Ident(ident) if ident.name() == "id")
+        }),
+    })
+}
+
+/// Transacts the contents of a file, reporting as it goes.  See the module documentation.
+struct Importer<'s, F> {
+    store: &'s mut Store,
+    path: String,
+    batch_size: usize,
+    checkpoint: Checkpoint,
+    progress_file: File,
+    progress: ImportProgress,
+    report: F,
+    batch: Vec<Entity<ValueAndSpan>>,
+    /// The line on which the batch starts.
+    batch_start: usize,
+}
+
+impl<'s, F> Importer<'s, F>
+where
+    F: FnMut(&ImportProgress),
+{
+    fn add(&mut self, entities: Vec<Entity<ValueAndSpan>>, start: usize) -> Result<(), Error> {
+        if is_alone(&entities) {
+            // The batch so far ends where these entities start.
+            self.commit(start)?;
+            self.batch_start = start;
+            self.batch = entities;
+            return self.commit(self.progress.lines);
+        }
+        if self.batch.is_empty() {
+            self.batch_start = start;
+        }
+        self.batch.extend(entities);
+        if self.batch.len() >= self.batch_size {
+            self.commit(self.progress.lines)?;
+        }
+        Ok(())
+    }
+
+    /// Transact the batch, which takes the input up to line `lines`.
+    fn commit(&mut self, lines: usize) -> Result<(), Error> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let schema = self.store.conn().current_schema();
+        let entities: Vec<_> = {
+            let resolver = Resolver {
+                tempids: &self.checkpoint.tempids,
+                schema: &schema,
+            };
+            self.batch.drain(..).map(|e| resolver.entity(e)).collect()
+        };
+        let count = entities.len();
+
+        let result = self.store.begin_transaction().and_then(|mut in_progress| {
+            let report = in_progress.transact_entities(entities)?;
+            in_progress.commit()?;
+            Ok(report)
+        });
+        let report = result.map_err(|e| {
+            err_msg(format!(
+                "{}, line {}: {}",
+                self.path,
+                self.batch_start + 1,
+                e
+            ))
+        })?;
+
+        self.checkpoint.lines = lines;
+        writeln!(
+            self.progress_file,
+            "{}",
+            json!({ "lines": lines, "tempids": report.tempids })
+        )?;
+        self.progress_file.flush()?;
+        self.checkpoint.tempids.extend(report.tempids);
+
+        self.progress.transactions += 1;
+        self.progress.entities += count;
+        (self.report)(&self.progress);
+        Ok(())
+    }
+}
+
+/// Transact the file at `path`, `batch_size` entities at a time, continuing an earlier import if
+/// `resume` is set.  `report` is called after each transaction.
+pub fn import<F>(
+    store: &mut Store,
+    path: &str,
+    batch_size: usize,
+    resume: bool,
+    report: F,
+) -> Result<ImportProgress, Error>
+where
+    F: FnMut(&ImportProgress),
+{
+    let json = path.ends_with(".json") || path.ends_with(".jsonl") || path.ends_with(".ndjson");
+    let file = File::open(path).map_err(|e| err_msg(format!("{}: {}", path, e)))?;
+    let total_bytes = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    let checkpoint = if resume {
+        Checkpoint::read(path).map_err(|e| err_msg(format!("Can't resume {}: {}", path, e)))?
+    } else {
+        Checkpoint::default()
+    };
+    let progress_file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resume)
+        .truncate(!resume)
+        .open(Checkpoint::path(path))?;
+
+    let mut importer = Importer {
+        store,
+        path: path.to_string(),
+        batch_size: batch_size.max(1),
+        checkpoint,
+        progress_file,
+        progress: ImportProgress {
+            total_bytes,
+            ..Default::default()
+        },
+        report,
+        batch: vec![],
+        batch_start: 0,
+    };
+
+    let mut line = String::new();
+    let mut form = String::new();
+    let mut form_start = 0;
+    let mut nesting = Nesting::default();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+        importer.progress.lines += 1;
+        importer.progress.bytes += read as u64;
+        if importer.progress.lines <= importer.checkpoint.lines {
+            continue;
+        }
+
+        if form.is_empty() {
+            form_start = importer.progress.lines - 1;
+        }
+        form.push_str(&line);
+        nesting.read(&line);
+        if !json && !nesting.is_between_forms() {
+            continue;
+        }
+        if nesting.has_content() {
+            let entities = if json {
+                entities_from_json(&form)?
+            } else {
+                edn::parse::entities(&form)
+                    .map_err(|e| err_msg(format!("{}, line {}: {}", path, form_start + 1, e)))?
+            };
+            importer.add(entities, form_start)?;
+        }
+        form.clear();
+        nesting = Nesting::default();
+    }
+    if nesting.has_content() {
+        bail!(err_msg(format!(
+            "{}, line {}: unexpected end of file",
+            path,
+            form_start + 1
+        )));
+    }
+    let lines = importer.progress.lines;
+    importer.commit(lines)?;
+
+    let progress = importer.progress.clone();
+    drop(importer);
+    fs::remove_file(Checkpoint::path(path))?;
+    Ok(progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mentat::Queryable;
+    use tempfile::tempdir;
+
+    const SCHEMA: &str = r#"[
+        {:db/ident :person/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/unique :db.unique/identity :db/index true :db/doc "A person's name."}
+        {:db/ident :person/friend :db/valueType :db.type/ref :db/cardinality :db.cardinality/many}
+        {:db/ident :person/bio :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+         :db/fulltext true :db/index true}
+        {:db/ident :person/score :db/valueType :db.type/double :db/cardinality :db.cardinality/one}
+        {:db/ident :person/role :db/valueType :db.type/ref :db/cardinality :db.cardinality/one}
+        {:db/ident :role/admin}
+    ]"#;
+
+    const DATA: &str = r#"[
+        {:db/id "a" :person/name "Alice \"Al\"
+Smith" :person/friend "b" :person/score 1.0
+         :person/role :role/admin :person/bio "Likes, commas"}
+        {:db/id "b" :person/name "Bob" :person/friend "a" :person/score 2.5}
+    ]"#;
+
+    const QUERY: &str = r#"[:find ?name ?friend ?score ?role ?bio
+        :where [?p :person/name ?name]
+               [?p :person/friend ?f] [?f :person/name ?friend]
+               [?p :person/score ?score]
+               [(get-else $ ?p :person/bio "") ?bio]
+               [(get-else $ ?p :person/role :db/ident) ?r] [?r :db/ident ?role]
+        :order ?name]"#;
+
+    fn populated() -> Store {
+        let mut store = Store::open("").expect("opened");
+        store.transact(SCHEMA).expect("schema");
+        store.transact(DATA).expect("data");
+        store
+            .transact(r#"[[:db/retract (lookup-ref :person/name "Bob") :person/score 2.5]]"#)
+            .expect("retracted");
+        store
+    }
+
+    fn contents(store: &mut Store) -> String {
+        format!("{:?}", store.q_once(QUERY, None).expect("queried").results)
+    }
+
+    fn round_trip(format: Format, history: bool) {
+        let mut store = populated();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(format!("export.{}", format.name()));
+        let path = path.to_str().unwrap();
+        export(&mut store, path, format, history).expect("exported");
+
+        let mut copy = Store::open("").expect("opened");
+        let mut reports = 0;
+        import(&mut copy, path, 2, false, |_| reports += 1).expect("imported");
+        assert!(reports > 1);
+        assert_eq!(contents(&mut copy), contents(&mut store));
+        assert!(!Path::new(&Checkpoint::path(path)).exists());
+
+        let doc = "[:find ?doc . :where [:person/name :db/doc ?doc]]";
+        assert_eq!(
+            copy.q_once(doc, None).unwrap().results,
+            store.q_once(doc, None).unwrap().results
+        );
+    }
+
+    #[test]
+    fn test_edn_round_trip() {
+        round_trip(Format::Edn, false);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        round_trip(Format::Json, false);
+    }
+
+    #[test]
+    fn test_history_round_trip() {
+        round_trip(Format::Edn, true);
+
+        let mut store = populated();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.edn");
+        let path = path.to_str().unwrap();
+        export(&mut store, path, Format::Edn, true).expect("exported");
+        let mut copy = Store::open("").expect("opened");
+        import(&mut copy, path, DEFAULT_BATCH_SIZE, false, |_| ()).expect("imported");
+
+        // The retraction, and the instants, survive.
+        let history = r#"[:find ?score ?added ?instant
+            :where [?p :person/name "Bob"]
+                   [(tx-ids $ 0 10000000000) [?tx ...]]
+                   [(tx-data $ ?tx) [[?p ?a ?score ?t ?added]]]
+                   [?a :db/ident :person/score]
+                   [?tx :db/txInstant ?instant]
+            :order ?added]"#;
+        assert_eq!(
+            copy.q_once(history, None).unwrap().results,
+            store.q_once(history, None).unwrap().results
+        );
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let mut store = populated();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("csv");
+        export(&mut store, path.to_str().unwrap(), Format::Csv, false).expect("exported");
+
+        let names = fs::read_to_string(path.join("person.name.csv")).unwrap();
+        let mut lines = names.lines();
+        assert_eq!(lines.next(), Some("e,v"));
+        assert!(
+            names.contains(",\"Alice \"\"Al\"\"\nSmith\"\n"),
+            "{}",
+            names
+        );
+        assert!(names.contains(",Bob\n"));
+        let bios = fs::read_to_string(path.join("person.bio.csv")).unwrap();
+        assert!(bios.ends_with(",\"Likes, commas\"\n"));
+    }
+
+    #[test]
+    fn test_import_resumes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("import.edn");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            format!(
+                "{}\n{}\n[[:db/add \"c\" :person/name \"Carol\"]\n [:db/add \"c\" :person/friend \"a\"]]\n[[:db/add \"d\" :no/such 1]]\n",
+                SCHEMA.replace('\n', " "),
+                DATA.replace('\n', " ")
+            ),
+        )
+        .unwrap();
+
+        let mut store = Store::open("").expect("opened");
+        let err = import(&mut store, path, 1, false, |_| ()).expect_err("unknown attribute");
+        assert!(err.to_string().contains("line 5"), "{}", err);
+        assert!(Path::new(&Checkpoint::path(path)).exists());
+
+        // Fix the bad line, and carry on from it: "a" is still Alice.
+        let text = fs::read_to_string(path).unwrap();
+        fs::write(path, text.replace(":no/such 1", ":person/name \"Dan\"")).unwrap();
+        let progress = import(&mut store, path, 1, true, |_| ()).expect("resumed");
+        assert_eq!(progress.lines, 5);
+        assert_eq!(progress.transactions, 1);
+
+        let friends = r#"[:find [?name ...] :where [?p :person/friend ?a] [?a :person/score 1.0] [?p :person/name ?name]]"#;
+        let mut names = match store.q_once(friends, None).unwrap().results {
+            mentat::QueryResults::Coll(names) => names,
+            results => panic!("unexpected {:?}", results),
+        };
+        names.sort_by_key(|name| format!("{:?}", name));
+        assert_eq!(
+            format!("{:?}", names),
+            "[Scalar(String(\"Bob\")), Scalar(String(\"Carol\"))]"
+        );
+        assert!(!Path::new(&Checkpoint::path(path)).exists());
+    }
+
+    #[test]
+    fn test_import_resumes_at_schema() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("import.edn");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            "[[:db/add \"c\" :person/name \"Carol\"]]\n\
+             [{:db/ident :person/age :db/valueType :db.type/long :db/cardinality :db.cardinality/none}]\n\
+             [[:db/add \"c\" :person/age 30]]\n",
+        )
+        .unwrap();
+
+        let mut store = Store::open("").expect("opened");
+        store.transact(SCHEMA).expect("schema");
+        let err = import(&mut store, path, DEFAULT_BATCH_SIZE, false, |_| ())
+            .expect_err("unknown cardinality");
+        assert!(err.to_string().contains("line 2"), "{}", err);
+
+        // The batch before the schema was committed, but the schema wasn't.
+        let text = fs::read_to_string(path).unwrap();
+        fs::write(path, text.replace("/none", "/one")).unwrap();
+        let progress = import(&mut store, path, DEFAULT_BATCH_SIZE, true, |_| ()).expect("resumed");
+        assert_eq!(progress.transactions, 2);
+
+        let age = r#"[:find ?age . :where [?p :person/name "Carol"] [?p :person/age ?age]]"#;
+        assert_eq!(
+            format!("{:?}", store.q_once(age, None).unwrap().results),
+            "Scalar(Some(Scalar(Long(30))))"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! The store behind the server, shared by every request.
+//!
+//! Writes are serialized through a single `Store`.  Reads don't wait for them: each runs on a
+//! SQLite connection of its own, taken from a pool, against the schema as of the last commit.
+//! The store is in WAL mode, so readers see the last committed state while a write is under way.
+//! An in-memory store can't be opened twice, so there reads share the writer's connection.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use mentat::{AttributeSet, HasSchema, Result, Schema, Store, TxObserver, TxReport};
+
+/// The key under which the server observes its store.
+const OBSERVER_KEY: &str = "mentatweb";
+
+/// How many transactions a slow `/events` subscriber can fall behind before it misses some.
+const EVENT_BACKLOG: usize = 256;
+
+/// How a transaction is written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Edn,
+    Json,
+    Transit,
+}
+
+pub struct Database {
+    /// Empty for an in-memory store.
+    path: String,
+    store: Mutex<Store>,
+    readers: Mutex<Vec<rusqlite::Connection>>,
+    schema: RwLock<Arc<Schema>>,
+    events: broadcast::Sender<String>,
+}
+
+impl Database {
+    /// Open the store at `path`, or an in-memory store if `path` is empty.
+    pub fn open(path: &str) -> Result<Database> {
+        let mut store = Store::open(path)?;
+        let schema = store.conn().current_schema();
+        let (events, _) = broadcast::channel(EVENT_BACKLOG);
+        observe(&mut store, &events, schema.clone());
+        Ok(Database {
+            path: path.to_string(),
+            store: Mutex::new(store),
+            readers: Mutex::new(Vec::new()),
+            schema: RwLock::new(schema),
+            events,
+        })
+    }
+
+    /// The schema as of the last commit.
+    pub fn schema(&self) -> Arc<Schema> {
+        self.schema.read().unwrap().clone()
+    }
+
+    /// Each transaction committed from now on, as a JSON document: its `tx-id`, and the
+    /// `attributes` it changed.
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.events.subscribe()
+    }
+
+    /// Run `f` with a connection of its own, unless the store is in memory, and the schema.
+    pub fn read<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&rusqlite::Connection, &Schema) -> Result<R>,
+    {
+        if self.path.is_empty() {
+            let store = self.store.lock().unwrap();
+            let schema = store.conn().current_schema();
+            return f(store.sqlite(), &schema);
+        }
+
+        let schema = self.schema();
+        let pooled = self.readers.lock().unwrap().pop();
+        let sqlite = match pooled {
+            Some(sqlite) => sqlite,
+            None => mentat::new_connection(&self.path)?,
+        };
+        let result = f(&sqlite, &schema);
+        self.readers.lock().unwrap().push(sqlite);
+        result
+    }
+
+    pub fn transact(&self, transaction: &str, format: Format) -> Result<TxReport> {
+        let mut store = self.store.lock().unwrap();
+        let report = match format {
+            Format::Edn => store.transact(transaction),
+            Format::Json => store.transact_json(transaction),
+            Format::Transit => store.transact_transit(transaction),
+        }?;
+
+        let schema = store.conn().current_schema();
+        let mut current = self.schema.write().unwrap();
+        if !Arc::ptr_eq(&current, &schema) {
+            observe(&mut store, &self.events, schema.clone());
+            *current = schema;
+        }
+        Ok(report)
+    }
+}
+
+/// Observe every attribute in `schema`, publishing each transaction to `events`.  An observer
+/// only hears about the attributes it was registered with, so this is redone whenever the schema
+/// changes.
+fn observe(store: &mut Store, events: &broadcast::Sender<String>, schema: Arc<Schema>) {
+    let attributes: AttributeSet = schema.attribute_map.keys().cloned().collect();
+    let events = events.clone();
+    let observer = TxObserver::new(attributes, move |_key, reports| {
+        for (tx_id, attributes) in reports {
+            let attributes: Vec<Value> = attributes
+                .iter()
+                .map(|attribute| match schema.get_ident(*attribute) {
+                    Some(ident) => Value::String(ident.to_string()),
+                    None => Value::from(*attribute),
+                })
+                .collect();
+            // It's fine for nobody to be listening.
+            let _ = events.send(json!({ "tx-id": tx_id, "attributes": attributes }).to_string());
+        }
+    });
+    store.register_observer(OBSERVER_KEY.to_string(), Arc::new(observer));
+}
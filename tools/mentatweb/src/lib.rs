@@ -0,0 +1,403 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! An HTTP server for a Mentat store, so that services not written in Rust, and debugging tools,
+//! can share one.  Results are JSON, encoded as `mentat::json` describes.
+//!
+//! - `POST /query`: an EDN query, or `{"query": "…", "inputs": {"?x": …}}` as JSON.  Answers
+//!   `{"columns": […], "results": …}`, in Transit if that's what the request accepts.
+//! - `POST /transact`: a transaction in EDN, JSON (`application/json`) or Transit
+//!   (`application/transit+json`).  Answers `{"tx-id": …, "tx-instant": …, "tempids": {…}}`.
+//! - `GET /schema`: the schema's attributes, as `/transact` would take them in JSON.
+//! - `GET /entity/:id`: every attribute of the entity with the given entid or ident.
+//! - `GET /events`: server-sent `tx-report` events, one per transaction committed.
+
+#[macro_use]
+extern crate serde_json;
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::body::Bytes;
+use hyper::header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+
+use mentat::json::{format_instant, parse_keyword};
+use mentat::query::q_uncached;
+use mentat::{
+    edn, Binding, DbError, DbErrorKind, Entid, HasSchema, MentatError, ProjectorError, PullError,
+    QueryInputs, QueryResults, Schema, TxReport, TypedValue, Variable,
+};
+
+mod database;
+
+pub use crate::database::{Database, Format};
+
+const TRANSIT: &str = "application/transit+json";
+
+enum Error {
+    BadRequest(String),
+    NotFound(String),
+    Mentat(MentatError),
+    Internal(String),
+}
+
+impl Error {
+    fn into_response(self) -> Response<Body> {
+        let (status, message) = match self {
+            Error::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            Error::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            Error::Mentat(e) if is_bad_request(&e) => (StatusCode::BAD_REQUEST, e.to_string()),
+            Error::Mentat(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            Error::Internal(message) => (StatusCode::INTERNAL_SERVER_ERROR, message),
+        };
+        json_response(status, &json!({ "error": message }))
+    }
+}
+
+/// Whether Mentat refused what the request asked of it, rather than failing to do it.
+fn is_bad_request(error: &MentatError) -> bool {
+    match error {
+        MentatError::BadUuid(_)
+        | MentatError::UnboundVariables(_)
+        | MentatError::InvalidArgumentName(_)
+        | MentatError::UnknownAttribute(_)
+        | MentatError::ValueTypeMismatch(..)
+        | MentatError::BadJson(_)
+        | MentatError::BadTransit(_)
+        | MentatError::EdnParseError(_)
+        | MentatError::AlgebrizerError(_) => true,
+        MentatError::ProjectorError(e) => is_bad_projection(e),
+        MentatError::PullError(e) => is_bad_pull(e),
+        MentatError::DbError(e) => is_bad_transaction(e),
+        _ => false,
+    }
+}
+
+fn is_bad_projection(error: &ProjectorError) -> bool {
+    match error {
+        ProjectorError::CannotProjectImpossibleBinding(_)
+        | ProjectorError::CannotApplyAggregateOperationToTypes(..)
+        | ProjectorError::InvalidProjection(_)
+        | ProjectorError::UnboundVariable(_)
+        | ProjectorError::NoTypeAvailableForVariable(_)
+        | ProjectorError::AmbiguousAggregates(..) => true,
+        ProjectorError::PullError(e) => is_bad_pull(e),
+        ProjectorError::DbError(e) => is_bad_transaction(e),
+        _ => false,
+    }
+}
+
+fn is_bad_pull(error: &PullError) -> bool {
+    match error {
+        PullError::UnnamedAttribute(_) | PullError::RepeatedDbId => true,
+        PullError::DbError(e) => is_bad_transaction(e),
+    }
+}
+
+fn is_bad_transaction(error: &DbError) -> bool {
+    matches!(
+        error.kind(),
+        DbErrorKind::BadValuePair(..)
+            | DbErrorKind::BadSchemaAssertion(_)
+            | DbErrorKind::UnrecognizedIdent(_)
+            | DbErrorKind::UnrecognizedEntid(_)
+            | DbErrorKind::UnallocatedEntid(_)
+            | DbErrorKind::UnknownAttribute(_)
+            | DbErrorKind::SchemaAlterationFailed(_)
+            | DbErrorKind::BadExcision(_)
+            | DbErrorKind::SchemaConstraintViolation(_)
+            | DbErrorKind::InputError(_)
+            | DbErrorKind::WrongTypeValueForFtsAssertion
+    )
+}
+
+/// Bind to `addr`, yielding the address bound to -- `addr` may ask for any port -- and the server
+/// to run.
+pub fn bind(
+    database: Database,
+    addr: &SocketAddr,
+) -> hyper::Result<(SocketAddr, impl Future<Output = hyper::Result<()>>)> {
+    let database = Arc::new(database);
+    let make_service = make_service_fn(move |_| {
+        let database = database.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| route(database.clone(), request))) }
+    });
+    let server = Server::try_bind(addr)?.serve(make_service);
+    Ok((server.local_addr(), server))
+}
+
+async fn route(
+    database: Arc<Database>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = match (method, path.as_str()) {
+        (Method::POST, "/query") => query(database, request).await,
+        (Method::POST, "/transact") => transact(database, request).await,
+        (Method::GET, "/schema") => Ok(json_response(
+            StatusCode::OK,
+            &schema_to_json(&database.schema()),
+        )),
+        (Method::GET, "/events") => Ok(events(&database)),
+        (Method::GET, path) if path.starts_with("/entity/") => {
+            entity(database, path["/entity/".len()..].to_string()).await
+        }
+        (_, path) => Err(Error::NotFound(format!("no such resource: {}", path))),
+    };
+    Ok(response.unwrap_or_else(Error::into_response))
+}
+
+async fn query(database: Arc<Database>, request: Request<Body>) -> Result<Response<Body>, Error> {
+    let transit = header_starts_with(&request, ACCEPT, TRANSIT);
+    let format = format_of(&request);
+    let body = body_text(request).await?;
+    let (query, inputs) = match format {
+        Format::Edn => (body, vec![]),
+        Format::Json => query_from_json(&body)?,
+        Format::Transit => {
+            return Err(Error::BadRequest(
+                "queries are EDN or JSON, not Transit".to_string(),
+            ))
+        }
+    };
+
+    // Query output isn't `Send`: encode it where it's produced.
+    let output = blocking(move || {
+        database.read(|sqlite, schema| {
+            let inputs = inputs
+                .into_iter()
+                .map(|(name, value)| (Variable::from_valid_name(&name), value))
+                .collect();
+            let output = q_uncached(
+                sqlite,
+                schema,
+                &query,
+                QueryInputs::with_value_sequence(inputs),
+            )?;
+            Ok(if transit {
+                output.to_transit()
+            } else {
+                output.to_json()
+            })
+        })
+    })
+    .await?;
+
+    Ok(if transit {
+        response(StatusCode::OK, TRANSIT, output.to_string())
+    } else {
+        json_response(StatusCode::OK, &output)
+    })
+}
+
+/// `{"query": "[:find …]", "inputs": {"?name": …}}`, inputs being optional.
+fn query_from_json(body: &str) -> Result<(String, Vec<(String, TypedValue)>), Error> {
+    let json: Value =
+        serde_json::from_str(body).map_err(|e| Error::BadRequest(format!("bad JSON: {}", e)))?;
+    let query = json
+        .get("query")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::BadRequest("expected a \"query\" string".to_string()))?
+        .to_string();
+
+    let mut inputs = vec![];
+    match json.get("inputs") {
+        None | Some(Value::Null) => (),
+        Some(Value::Object(values)) => {
+            for (name, value) in values {
+                if name.len() < 2 || !name.starts_with('?') {
+                    return Err(Error::BadRequest(format!("bad input variable: {}", name)));
+                }
+                let value = TypedValue::from_json(value).ok_or_else(|| {
+                    Error::BadRequest(format!("bad value for {}: {}", name, value))
+                })?;
+                inputs.push((name.clone(), value));
+            }
+        }
+        Some(_) => {
+            return Err(Error::BadRequest(
+                "expected \"inputs\" to be an object".to_string(),
+            ))
+        }
+    }
+    Ok((query, inputs))
+}
+
+async fn transact(
+    database: Arc<Database>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Error> {
+    let format = format_of(&request);
+    let body = body_text(request).await?;
+    let report = blocking(move || database.transact(&body, format)).await?;
+    Ok(json_response(StatusCode::OK, &report_to_json(&report)))
+}
+
+fn report_to_json(report: &TxReport) -> Value {
+    json!({
+        "tx-id": report.tx_id,
+        "tx-instant": format_instant(&report.tx_instant),
+        "tempids": report.tempids,
+    })
+}
+
+/// Every attribute, as a map like those that define it: `{":db/ident": {"keyword": …}, …}`.
+fn schema_to_json(schema: &Schema) -> Value {
+    fn convert(value: &edn::Value) -> Value {
+        match value {
+            edn::Value::Vector(values) => Value::Array(values.iter().map(convert).collect()),
+            edn::Value::Map(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| (key.to_string(), convert(value)))
+                    .collect(),
+            ),
+            edn::Value::Keyword(keyword) => json!({ "keyword": keyword.to_string() }),
+            edn::Value::Boolean(b) => Value::Bool(*b),
+            value => Value::String(value.to_string()),
+        }
+    }
+    convert(&schema.to_edn_value())
+}
+
+/// `id` is an entid or an ident.
+async fn entity(database: Arc<Database>, id: String) -> Result<Response<Body>, Error> {
+    let lookup = id.clone();
+    let pulled = blocking(move || {
+        database.read(|sqlite, schema| {
+            let entid: Option<Entid> = match lookup.parse() {
+                Ok(entid) => Some(entid),
+                Err(_) => parse_keyword(&lookup)
+                    .and_then(|ident| schema.get_entid(&ident))
+                    .map(Into::into),
+            };
+            let entid = match entid {
+                Some(entid) => entid,
+                None => return Ok(None),
+            };
+            let inputs = QueryInputs::with_value_sequence(vec![(
+                Variable::from_valid_name("?e"),
+                TypedValue::Ref(entid),
+            )]);
+            let output = q_uncached(
+                sqlite,
+                schema,
+                "[:find (pull ?e [*]) . :in ?e :where [?e _ _]]",
+                inputs,
+            )?;
+            Ok(match output.results {
+                QueryResults::Scalar(Some(Binding::Map(ref map))) if map.0.is_empty() => None,
+                QueryResults::Scalar(Some(binding)) => Some(binding.to_json()),
+                _ => None,
+            })
+        })
+    })
+    .await?;
+
+    match pulled {
+        Some(entity) => Ok(json_response(StatusCode::OK, &entity)),
+        None => Err(Error::NotFound(format!("no such entity: {}", id))),
+    }
+}
+
+fn events(database: &Database) -> Response<Body> {
+    let mut receiver = database.subscribe();
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        // Tell the client it's subscribed, so that it knows it won't miss what comes next.
+        if sender
+            .send_data(Bytes::from_static(b": subscribed\n\n"))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                // A client too slow to keep up misses some transactions, rather than holding
+                // everyone else back.
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            };
+            let chunk = format!("event: tx-report\ndata: {}\n\n", event);
+            if sender.send_data(Bytes::from(chunk)).await.is_err() {
+                // The client went away.
+                return;
+            }
+        }
+    });
+
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(body)
+        .unwrap()
+}
+
+/// Run `f`, which talks to SQLite, off the async workers.
+async fn blocking<F, R>(f: F) -> Result<R, Error>
+where
+    F: FnOnce() -> mentat::Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Internal(e.to_string()))?
+        .map_err(Error::Mentat)
+}
+
+fn format_of(request: &Request<Body>) -> Format {
+    if header_starts_with(request, CONTENT_TYPE, TRANSIT) {
+        Format::Transit
+    } else if header_starts_with(request, CONTENT_TYPE, "application/json") {
+        Format::Json
+    } else {
+        Format::Edn
+    }
+}
+
+fn header_starts_with(
+    request: &Request<Body>,
+    header: hyper::header::HeaderName,
+    prefix: &str,
+) -> bool {
+    request
+        .headers()
+        .get(header)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(prefix))
+}
+
+async fn body_text(request: Request<Body>) -> Result<String, Error> {
+    let bytes = hyper::body::to_bytes(request.into_body())
+        .await
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| Error::BadRequest("expected a UTF-8 body".to_string()))
+}
+
+fn response(status: StatusCode, content_type: &str, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn json_response(status: StatusCode, body: &Value) -> Response<Body> {
+    response(status, "application/json", body.to_string())
+}
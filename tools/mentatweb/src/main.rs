@@ -8,54 +8,89 @@
 // CONDITIONS OF ANY KIND, either express or implied. See the License for the
 // specific language governing permissions and limitations under the License.
 
-extern crate clap;
-
-#[macro_use]
-extern crate nickel;
-
-extern crate mentat;
-
-use std::u16;
-use std::str::FromStr;
-
-use clap::{App, Arg, SubCommand, AppSettings};
-
-use nickel::{Nickel, HttpRouter};
-
-fn main() {
-    let app = App::new("Mentat").setting(AppSettings::ArgRequiredElseHelp);
-    let matches = app.subcommand(SubCommand::with_name("serve")
-            .about("Starts a server")
-            .arg(Arg::with_name("debug")
-                .long("debug")
-                .help("Print debugging info"))
-            .arg(Arg::with_name("database")
-                .short("d")
-                .long("database")
-                .value_name("FILE")
-                .help("Path to the Mentat database to serve")
-                .default_value("")
-                .takes_value(true))
-            .arg(Arg::with_name("port")
-                .short("p")
-                .long("port")
-                .value_name("INTEGER")
-                .help("Port to serve from, i.e. `localhost:PORT`")
-                .default_value("3333")
-                .takes_value(true)))
-        .get_matches();
-    if let Some(ref matches) = matches.subcommand_matches("serve") {
-        let debug = matches.is_present("debug");
-        let port = u16::from_str(matches.value_of("port").unwrap()).expect("Port must be an integer");
-        if debug {
-            println!("This doesn't do anything yet, but it will eventually serve up the following database: {} \
-                      on port: {}.",
-                     matches.value_of("database").unwrap(),
-                     matches.value_of("port").unwrap());
+use std::net::{Ipv4Addr, SocketAddr};
+use std::process;
+
+use getopts::Options;
+
+use mentatweb::Database;
+
+fn print_usage(program: &str, opts: &Options) {
+    let brief = format!("Usage: {} serve [options]", program);
+    print!("{}", opts.usage(&brief));
+}
+
+#[tokio::main]
+async fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let mut opts = Options::new();
+
+    opts.optflag("", "debug", "Print debugging info");
+    opts.optopt(
+        "d",
+        "database",
+        "Path to the Mentat database to serve; in memory if omitted",
+        "FILE",
+    );
+    opts.optopt(
+        "p",
+        "port",
+        "Port to serve from, i.e. `localhost:PORT`; 3333 if omitted",
+        "INTEGER",
+    );
+    opts.optflag("h", "help", "Print this help message and exit");
+
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            process::exit(1);
         }
+    };
+
+    if matches.opt_present("help") || matches.free != ["serve"] {
+        print_usage(&args[0], &opts);
+        process::exit(if matches.opt_present("help") { 0 } else { 1 });
+    }
+
+    let path = matches.opt_str("database").unwrap_or_default();
+    let port = match matches.opt_str("port").map(|port| port.parse::<u16>()) {
+        None => 3333,
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            eprintln!("{}: port must be an integer", args[0]);
+            process::exit(1);
+        }
+    };
+
+    let database = match Database::open(&path) {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("{}: couldn't open {:?}: {}", args[0], path, e);
+            process::exit(1);
+        }
+    };
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let (addr, server) = match mentatweb::bind(database, &addr) {
+        Ok(bound) => bound,
+        Err(e) => {
+            eprintln!("{}: couldn't listen on {}: {}", args[0], addr, e);
+            process::exit(1);
+        }
+    };
+
+    if matches.opt_present("debug") {
+        let database = if path.is_empty() {
+            "(in memory)"
+        } else {
+            &path
+        };
+        println!("Serving {} on http://{}", database, addr);
+    }
 
-        let mut server = Nickel::new();
-        server.get("/", middleware!("This doesn't do anything yet"));
-        server.listen(("127.0.0.1", port)).expect("Failed to launch server");
+    if let Err(e) = server.await {
+        eprintln!("{}: {}", args[0], e);
+        process::exit(1);
     }
 }
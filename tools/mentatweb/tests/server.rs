@@ -0,0 +1,241 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+#[macro_use]
+extern crate serde_json;
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use hyper::body::HttpBody;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use serde_json::Value;
+
+use mentatweb::Database;
+
+const SCHEMA: &str = r#"[
+    {:db/ident :person/name :db/valueType :db.type/string :db/cardinality :db.cardinality/one
+     :db/unique :db.unique/identity :db/index true}
+    {:db/ident :person/age :db/valueType :db.type/long :db/cardinality :db.cardinality/one}
+]"#;
+
+/// Serve `path` on a port of its own, returning the base URL.
+fn start(path: &str) -> String {
+    let database = Database::open(path).expect("opened");
+    let (addr, server) =
+        mentatweb::bind(database, &SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).expect("bound");
+    tokio::spawn(server);
+    format!("http://{}", addr)
+}
+
+async fn request(
+    method: Method,
+    url: &str,
+    content_type: Option<&str>,
+    body: &str,
+) -> (StatusCode, Value) {
+    let mut builder = Request::builder().method(method).uri(url);
+    if let Some(content_type) = content_type {
+        builder = builder.header(CONTENT_TYPE, content_type);
+    }
+    let request = builder.body(Body::from(body.to_string())).unwrap();
+    let response = Client::new().request(request).await.expect("response");
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&bytes).expect("JSON"))
+}
+
+async fn post(url: &str, content_type: Option<&str>, body: &str) -> (StatusCode, Value) {
+    request(Method::POST, url, content_type, body).await
+}
+
+async fn get(url: &str) -> (StatusCode, Value) {
+    request(Method::GET, url, None, "").await
+}
+
+#[tokio::test]
+async fn test_transact_and_query() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = start(dir.path().join("test.db").to_str().unwrap());
+
+    let (status, _) = post(&format!("{}/transact", base), None, SCHEMA).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, report) = post(
+        &format!("{}/transact", base),
+        None,
+        r#"[{:db/id "a" :person/name "Alice" :person/age 30}]"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(report["tx-id"].is_i64());
+    assert!(report["tx-instant"].is_string());
+    assert!(report["tempids"]["a"].is_i64());
+
+    let (status, report) = post(
+        &format!("{}/transact", base),
+        Some("application/json"),
+        r#"[{":person/name": "Bob", ":person/age": 40}]"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", report);
+
+    let (status, output) = post(
+        &format!("{}/query", base),
+        None,
+        "[:find ?name ?age :where [?p :person/name ?name] [?p :person/age ?age] :order ?age]",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        output,
+        json!({"columns": ["?name", "?age"], "results": [["Alice", 30], ["Bob", 40]]})
+    );
+
+    let (status, output) = post(
+        &format!("{}/query", base),
+        Some("application/json"),
+        &json!({
+            "query": "[:find ?age . :in ?name :where [?p :person/name ?name] [?p :person/age ?age]]",
+            "inputs": {"?name": "Bob"},
+        })
+        .to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(output["results"], json!(40));
+}
+
+#[tokio::test]
+async fn test_errors() {
+    let base = start("");
+
+    let (status, error) = post(&format!("{}/query", base), None, "[:find ?x :where").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(error["error"].is_string());
+
+    let (status, _) = post(
+        &format!("{}/query", base),
+        Some("application/json"),
+        r#"{"query": "[:find ?x . :in ?x]", "inputs": {"x": 1}}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = post(
+        &format!("{}/transact", base),
+        None,
+        "[[:db/add 1 :no/such 1]]",
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = get(&format!("{}/nowhere", base)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_internal_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("test.db");
+    let base = start(path.to_str().unwrap());
+    let (status, _) = post(&format!("{}/transact", base), None, SCHEMA).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A perfectly good query that the store can't answer is our fault, not the client's.
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute_batch("DROP TABLE datoms")
+        .unwrap();
+    let (status, error) = post(
+        &format!("{}/query", base),
+        None,
+        "[:find ?name :where [_ :person/name ?name]]",
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{}", error);
+    assert!(error["error"].is_string());
+}
+
+#[tokio::test]
+async fn test_schema_and_entity() {
+    let base = start("");
+    post(&format!("{}/transact", base), None, SCHEMA).await;
+    let (_, report) = post(
+        &format!("{}/transact", base),
+        None,
+        r#"[{:db/id "a" :person/name "Alice" :person/age 30}]"#,
+    )
+    .await;
+    let alice = report["tempids"]["a"].as_i64().unwrap();
+
+    let (status, schema) = get(&format!("{}/schema", base)).await;
+    assert_eq!(status, StatusCode::OK);
+    let name = schema
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|attribute| attribute[":db/ident"] == json!({"keyword": ":person/name"}))
+        .expect(":person/name");
+    assert_eq!(name[":db/valueType"], json!({"keyword": ":db.type/string"}));
+    assert_eq!(
+        name[":db/unique"],
+        json!({"keyword": ":db.unique/identity"})
+    );
+    assert_eq!(name[":db/index"], json!(true));
+
+    let (status, entity) = get(&format!("{}/entity/{}", base, alice)).await;
+    assert_eq!(status, StatusCode::OK, "{}", entity);
+    assert_eq!(entity[":person/name"], json!("Alice"));
+    assert_eq!(entity[":person/age"], json!(30));
+
+    let (status, entity) = get(&format!("{}/entity/:person/age", base)).await;
+    assert_eq!(status, StatusCode::OK, "{}", entity);
+    assert!(entity[":db/valueType"]["ref"].is_i64());
+
+    let (status, _) = get(&format!("{}/entity/:person/nobody", base)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get(&format!("{}/entity/{}", base, alice + 1000)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_events() {
+    let base = start("");
+    post(&format!("{}/transact", base), None, SCHEMA).await;
+
+    let response = Client::new()
+        .get(format!("{}/events", base).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+    let mut body = response.into_body();
+    let subscribed = body.data().await.unwrap().unwrap();
+    assert_eq!(&subscribed[..], b": subscribed\n\n");
+
+    let (_, report) = post(
+        &format!("{}/transact", base),
+        None,
+        r#"[{:person/name "Alice" :person/age 30}]"#,
+    )
+    .await;
+
+    let event = body.data().await.unwrap().unwrap();
+    let event = std::str::from_utf8(&event).unwrap();
+    let data = event
+        .strip_prefix("event: tx-report\ndata: ")
+        .and_then(|event| event.strip_suffix("\n\n"))
+        .expect("a tx-report event");
+    let data: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(data["tx-id"], report["tx-id"]);
+    let attributes = data["attributes"].as_array().unwrap();
+    assert!(attributes.contains(&json!(":person/name")));
+    assert!(attributes.contains(&json!(":person/age")));
+}
@@ -0,0 +1,330 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Machine-readable forms of a `QueryExplanation`.
+//!
+//! As EDN, an explanation is a map like
+//!
+//! ```edn
+//! {:sql "SELECT DISTINCT `datoms00`.e AS `?e` FROM `datoms` AS `datoms00` WHERE ..."
+//!  :args {"$v0" "Alice"}
+//!  :tables [{:alias "datoms00" :table "datoms" :clause [?e :person/name "Alice"]}]
+//!  :steps [{:select-id 2 :order 0 :from 0 :detail "SEARCH datoms00 USING INDEX ..."}]
+//!  :types {?e #{:db.type/ref}}
+//!  :cached-attributes []}
+//! ```
+//!
+//! A query known to be empty has `:known-empty` and the reason instead of `:sql`, `:args`,
+//! `:tables` and `:steps`, and a constant query has `:known-constant true`.
+//!
+//! The JSON form has the same shape, with string keys. Clauses, variables, attributes and types
+//! are given as EDN text, and binary arguments as hex.
+
+use std::collections::BTreeMap;
+
+use rusqlite;
+
+use serde_json;
+
+use edn;
+use edn::query::{Keyword, PlainSymbol, SrcVar};
+
+use core_traits::{Entid, TypedValue, ValueType};
+
+use mentat_core::{HasSchema, Schema};
+
+use mentat_db::TypedSQLValue;
+
+use mentat_query_algebrizer::{
+    AliasOrigin, EvolvedNonValuePlace, EvolvedPattern, EvolvedValuePlace, Known,
+};
+
+use crate::query::{QueryExplanation, QueryPlan};
+
+fn keyword(name: &str) -> edn::Value {
+    edn::Value::Keyword(Keyword::plain(name))
+}
+
+fn symbol(name: &str) -> edn::Value {
+    edn::Value::PlainSymbol(PlainSymbol::plain(name))
+}
+
+fn entid_to_edn(schema: &Schema, entid: Entid) -> edn::Value {
+    match schema.get_ident(entid) {
+        Some(ident) => edn::Value::Keyword(ident.clone()),
+        None => edn::Value::Integer(entid),
+    }
+}
+
+fn non_value_place_to_edn(schema: &Schema, place: &EvolvedNonValuePlace) -> edn::Value {
+    match place {
+        EvolvedNonValuePlace::Placeholder => symbol("_"),
+        EvolvedNonValuePlace::Variable(ref var) => edn::Value::PlainSymbol(var.name()),
+        EvolvedNonValuePlace::Entid(entid) => entid_to_edn(schema, *entid),
+    }
+}
+
+fn value_place_to_edn(schema: &Schema, place: &EvolvedValuePlace) -> edn::Value {
+    match place {
+        EvolvedValuePlace::Placeholder => symbol("_"),
+        EvolvedValuePlace::Variable(ref var) => edn::Value::PlainSymbol(var.name()),
+        EvolvedValuePlace::Entid(entid) => entid_to_edn(schema, *entid),
+        EvolvedValuePlace::Value(ref value) => value.to_edn_value_pair().0,
+        EvolvedValuePlace::EntidOrInteger(i) => edn::Value::Integer(*i),
+        EvolvedValuePlace::IdentOrKeyword(ref keyword) => {
+            edn::Value::Keyword(keyword.as_ref().clone())
+        }
+    }
+}
+
+/// Write a pattern much as it appeared in the query, with attributes as idents.
+fn pattern_to_edn(known: Known, pattern: &EvolvedPattern) -> edn::Value {
+    let mut places = Vec::with_capacity(5);
+
+    // Patterns over attached stores were resolved with those stores' schemas.
+    let schema = match pattern.source {
+        SrcVar::DefaultSrc => known.schema,
+        SrcVar::NamedSrc(ref name) => {
+            places.push(symbol(&format!("${}", name)));
+            known
+                .attached
+                .and_then(|attached| attached.get(name))
+                .unwrap_or(known.schema)
+        }
+    };
+
+    places.push(non_value_place_to_edn(schema, &pattern.entity));
+    places.push(non_value_place_to_edn(schema, &pattern.attribute));
+    places.push(value_place_to_edn(schema, &pattern.value));
+    if pattern.tx != EvolvedNonValuePlace::Placeholder {
+        places.push(non_value_place_to_edn(schema, &pattern.tx));
+    }
+    edn::Value::Vector(places)
+}
+
+pub(crate) fn origin_to_edn(known: Known, origin: &AliasOrigin) -> edn::Value {
+    match origin {
+        AliasOrigin::Pattern(ref pattern) => pattern_to_edn(known, pattern),
+        AliasOrigin::Or(ref patterns) => edn::Value::List(
+            ::std::iter::once(symbol("or"))
+                .chain(
+                    patterns
+                        .iter()
+                        .map(|pattern| pattern_to_edn(known, pattern)),
+                )
+                .collect(),
+        ),
+        AliasOrigin::Function(ref operator) => edn::Value::PlainSymbol(operator.clone()),
+    }
+}
+
+fn arg_to_edn(value: &rusqlite::types::Value) -> edn::Value {
+    use rusqlite::types::Value::*;
+    match value {
+        Null => edn::Value::Nil,
+        Integer(i) => edn::Value::Integer(*i),
+        Real(f) => edn::Value::Float((*f).into()),
+        Text(s) => edn::Value::Text(s.clone()),
+        Blob(b) => TypedValue::Bytes(b.clone().into()).to_edn_value_pair().0,
+    }
+}
+
+fn arg_to_json(value: &rusqlite::types::Value) -> serde_json::Value {
+    use rusqlite::types::Value::*;
+    match value {
+        Null => serde_json::Value::Null,
+        Integer(i) => (*i).into(),
+        Real(f) => (*f).into(),
+        Text(s) => s.clone().into(),
+        Blob(b) => b
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+            .into(),
+    }
+}
+
+impl QueryExplanation {
+    pub fn to_edn(&self) -> edn::Value {
+        let mut map: BTreeMap<edn::Value, edn::Value> = BTreeMap::new();
+        match self.plan {
+            QueryPlan::KnownEmpty(ref because) => {
+                map.insert(
+                    keyword("known-empty"),
+                    edn::Value::Text(format!("{:?}", because)),
+                );
+            }
+            QueryPlan::KnownConstant => {
+                map.insert(keyword("known-constant"), edn::Value::Boolean(true));
+            }
+            QueryPlan::ExecutionPlan {
+                ref query,
+                ref tables,
+                ref steps,
+            } => {
+                map.insert(keyword("sql"), edn::Value::Text(query.sql.clone()));
+                map.insert(
+                    keyword("args"),
+                    edn::Value::Map(
+                        query
+                            .args
+                            .iter()
+                            .map(|(name, value)| {
+                                (edn::Value::Text(name.clone()), arg_to_edn(value))
+                            })
+                            .collect(),
+                    ),
+                );
+                map.insert(
+                    keyword("tables"),
+                    edn::Value::Vector(
+                        tables
+                            .iter()
+                            .map(|table| {
+                                let mut m = BTreeMap::new();
+                                m.insert(keyword("alias"), edn::Value::Text(table.alias.clone()));
+                                m.insert(keyword("table"), edn::Value::Text(table.table.clone()));
+                                m.insert(keyword("clause"), table.clause.clone());
+                                edn::Value::Map(m)
+                            })
+                            .collect(),
+                    ),
+                );
+                map.insert(
+                    keyword("steps"),
+                    edn::Value::Vector(
+                        steps
+                            .iter()
+                            .map(|step| {
+                                let mut m = BTreeMap::new();
+                                m.insert(
+                                    keyword("select-id"),
+                                    edn::Value::Integer(step.select_id.into()),
+                                );
+                                m.insert(keyword("order"), edn::Value::Integer(step.order.into()));
+                                m.insert(keyword("from"), edn::Value::Integer(step.from.into()));
+                                m.insert(keyword("detail"), edn::Value::Text(step.detail.clone()));
+                                edn::Value::Map(m)
+                            })
+                            .collect(),
+                    ),
+                );
+            }
+        }
+        map.insert(
+            keyword("types"),
+            edn::Value::Map(
+                self.types
+                    .iter()
+                    .map(|(var, types)| {
+                        (
+                            edn::Value::PlainSymbol(var.name()),
+                            edn::Value::Set(
+                                types.into_iter().map(ValueType::into_edn_value).collect(),
+                            ),
+                        )
+                    })
+                    .collect(),
+            ),
+        );
+        map.insert(
+            keyword("cached-attributes"),
+            edn::Value::Vector(
+                self.cached_attributes
+                    .iter()
+                    .cloned()
+                    .map(edn::Value::Keyword)
+                    .collect(),
+            ),
+        );
+        edn::Value::Map(map)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut map = serde_json::Map::new();
+        match self.plan {
+            QueryPlan::KnownEmpty(ref because) => {
+                map.insert("known-empty".into(), format!("{:?}", because).into());
+            }
+            QueryPlan::KnownConstant => {
+                map.insert("known-constant".into(), true.into());
+            }
+            QueryPlan::ExecutionPlan {
+                ref query,
+                ref tables,
+                ref steps,
+            } => {
+                map.insert("sql".into(), query.sql.clone().into());
+                map.insert(
+                    "args".into(),
+                    serde_json::Value::Object(
+                        query
+                            .args
+                            .iter()
+                            .map(|(name, value)| (name.clone(), arg_to_json(value)))
+                            .collect(),
+                    ),
+                );
+                map.insert(
+                    "tables".into(),
+                    tables
+                        .iter()
+                        .map(|table| {
+                            json!({
+                                "alias": table.alias,
+                                "table": table.table,
+                                "clause": table.clause.to_string(),
+                            })
+                        })
+                        .collect(),
+                );
+                map.insert(
+                    "steps".into(),
+                    steps
+                        .iter()
+                        .map(|step| {
+                            json!({
+                                "select-id": step.select_id,
+                                "order": step.order,
+                                "from": step.from,
+                                "detail": step.detail,
+                            })
+                        })
+                        .collect(),
+                );
+            }
+        }
+        map.insert(
+            "types".into(),
+            serde_json::Value::Object(
+                self.types
+                    .iter()
+                    .map(|(var, types)| {
+                        (
+                            var.to_string(),
+                            types
+                                .into_iter()
+                                .map(|t| t.into_keyword().to_string())
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+        );
+        map.insert(
+            "cached-attributes".into(),
+            self.cached_attributes
+                .iter()
+                .map(|a| a.to_string())
+                .collect(),
+        );
+        serde_json::Value::Object(map)
+    }
+}
@@ -0,0 +1,574 @@
+// Copyright 2018 Mozilla
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Transactions written as JSON or as Transit-JSON rather than as EDN.
+//!
+//! A JSON transaction has the same shape as an EDN one: an array of entities, each of which is
+//! either an operation or a map.
+//!
+//! ```json
+//! [[":db/add", "alice", ":person/name", "Alice"],
+//!  {":db/id": "bob", ":person/name": "Bob", ":person/friend": "alice",
+//!   ":person/role": {"keyword": ":role/admin"}},
+//!  [":db/retract", {"lookup-ref": [":person/name", "Carol"]}, ":person/age", 40]]
+//! ```
+//!
+//! Operations, attributes and map keys are keyword text. Everywhere else, values are written
+//! as `TypedValue::to_json` writes them, so a string is a tempid where an entity is expected,
+//! just as in EDN. Two more tags name entities: `{"lookup-ref": [attribute, value]}`,
+//! and `{"tx-function": "transaction-tx"}`.
+//!
+//! A Transit transaction is EDN written as Transit-JSON, in normal or verbose mode, cached or
+//! not: `[["~:db/add", "alice", "~:person/name", "Alice"]]`. Refs may be given as
+//! `["~#ref", 65536]`, and lookup refs as lists, `["~#list", ["~$lookup-ref", …]]`.
+
+use std::collections::BTreeMap;
+
+use serde_json;
+use serde_json::Value;
+
+use edn;
+use edn::entities::{
+    AttributePlace, EntidOrIdent, Entity, EntityPlace, LookupRef, MapNotation, OpType, TempId,
+    TxFunction, ValuePlace,
+};
+use edn::{FromMillis, NamespacedSymbol, PlainSymbol, ValueAndSpan};
+
+pub use core_traits::json::{
+    decode_base64, encode_base64, format_instant, parse_instant, parse_keyword, transit_map,
+};
+use core_traits::TypedValue;
+
+use mentat_db::TypedSQLValue;
+
+use public_traits::errors::{MentatError, Result};
+
+/// Makes the error for malformed input in one of the encodings.
+type Bad = fn(String) -> MentatError;
+
+fn bad_json<T, S: Into<String>>(message: S) -> Result<T> {
+    Err(MentatError::BadJson(message.into()))
+}
+
+fn bad_transit<T, S: Into<String>>(message: S) -> Result<T> {
+    Err(MentatError::BadTransit(message.into()))
+}
+
+/// Parses a JSON transaction.
+pub fn entities_from_json(transaction: &str) -> Result<Vec<Entity<ValueAndSpan>>> {
+    let json: Value =
+        serde_json::from_str(transaction).map_err(|e| MentatError::BadJson(e.to_string()))?;
+    let edn = match json {
+        Value::Array(ref entities) => edn::Value::Vector(
+            entities
+                .iter()
+                .map(json_entity)
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        _ => return bad_json("a transaction is an array of entities"),
+    };
+    entities_from_edn(edn, MentatError::BadJson)
+}
+
+/// Parses a Transit-JSON transaction.
+pub fn entities_from_transit(transaction: &str) -> Result<Vec<Entity<ValueAndSpan>>> {
+    let json: Value =
+        serde_json::from_str(transaction).map_err(|e| MentatError::BadTransit(e.to_string()))?;
+    let edn = TransitReader::default().read(&json, false)?;
+    entities_from_edn(edn, MentatError::BadTransit)
+}
+
+fn json_keyword(json: &Value) -> Result<edn::Value> {
+    match json {
+        Value::String(s) => match parse_keyword(s) {
+            Some(keyword) => Ok(edn::Value::Keyword(keyword)),
+            None => bad_json(format!("expected a keyword, got \"{}\"", s)),
+        },
+        _ => bad_json(format!("expected a keyword, got {}", json)),
+    }
+}
+
+fn json_attribute(json: &Value) -> Result<edn::Value> {
+    match json.as_i64() {
+        Some(entid) => Ok(edn::Value::Integer(entid)),
+        None => json_keyword(json),
+    }
+}
+
+fn json_entity(json: &Value) -> Result<edn::Value> {
+    match json {
+        Value::Array(parts) if parts.len() == 4 => Ok(edn::Value::Vector(vec![
+            json_keyword(&parts[0])?,
+            json_value(&parts[1])?,
+            json_attribute(&parts[2])?,
+            json_value(&parts[3])?,
+        ])),
+        Value::Object(map) => json_map(map),
+        _ => bad_json(format!("expected [op, e, a, v] or a map, got {}", json)),
+    }
+}
+
+fn json_map(map: &serde_json::Map<String, Value>) -> Result<edn::Value> {
+    map.iter()
+        .map(|(key, value)| match parse_keyword(key) {
+            Some(keyword) => Ok((edn::Value::Keyword(keyword), json_value(value)?)),
+            None => bad_json(format!("expected a keyword key, got \"{}\"", key)),
+        })
+        .collect::<Result<BTreeMap<_, _>>>()
+        .map(edn::Value::Map)
+}
+
+fn json_value(json: &Value) -> Result<edn::Value> {
+    match json {
+        Value::Array(values) => values
+            .iter()
+            .map(json_value)
+            .collect::<Result<Vec<_>>>()
+            .map(edn::Value::Vector),
+        Value::Object(map) if map.len() == 1 && !map.keys().any(|k| k.starts_with(':')) => {
+            let (tag, value) = map.iter().next().unwrap();
+            match (tag.as_str(), value) {
+                ("lookup-ref", Value::Array(av)) if av.len() == 2 => Ok(edn::Value::List(
+                    vec![
+                        edn::Value::PlainSymbol(PlainSymbol::plain("lookup-ref")),
+                        json_attribute(&av[0])?,
+                        json_value(&av[1])?,
+                    ]
+                    .into_iter()
+                    .collect(),
+                )),
+                ("tx-function", Value::String(op)) => Ok(edn::Value::List(
+                    vec![edn::Value::PlainSymbol(PlainSymbol::plain(op.as_str()))]
+                        .into_iter()
+                        .collect(),
+                )),
+                _ => match TypedValue::from_json(json) {
+                    Some(value) => Ok(value.to_edn_value_pair().0),
+                    None => bad_json(format!("bad tagged value {}", json)),
+                },
+            }
+        }
+        Value::Object(map) => json_map(map),
+        Value::Null => bad_json("null isn't a value"),
+        Value::Bool(_) | Value::Number(_) | Value::String(_) => match TypedValue::from_json(json) {
+            Some(value) => Ok(value.to_edn_value_pair().0),
+            None => bad_json(format!("bad value {}", json)),
+        },
+    }
+}
+
+/// Transit's cache holds up to 44² strings, each named by one or two digits from `'0'` up.
+const CACHE_DIGITS: usize = 44;
+const CACHE_SIZE: usize = CACHE_DIGITS * CACHE_DIGITS;
+
+/// Reads Transit-JSON as EDN, keeping track of the strings that the writer may have cached.
+#[derive(Default)]
+struct TransitReader {
+    cache: Vec<String>,
+}
+
+impl TransitReader {
+    /// Resolves a cache reference, or remembers `s` if the writer would have cached it.
+    fn resolve(&mut self, s: &str, as_key: bool) -> Result<String> {
+        if s.starts_with('^') && s != "^ " {
+            let digits: Vec<usize> = s[1..]
+                .bytes()
+                .map(|b| (b as usize).wrapping_sub(48))
+                .collect();
+            let index = match digits.as_slice() {
+                [a] if *a < CACHE_DIGITS => *a,
+                [a, b] if *a < CACHE_DIGITS && *b < CACHE_DIGITS => a * CACHE_DIGITS + b,
+                _ => return bad_transit(format!("bad cache reference \"{}\"", s)),
+            };
+            return match self.cache.get(index) {
+                Some(cached) => Ok(cached.clone()),
+                None => bad_transit(format!("bad cache reference \"{}\"", s)),
+            };
+        }
+        let cacheable = s.len() > 3
+            && (as_key || s.starts_with("~:") || s.starts_with("~$") || s.starts_with("~#"));
+        if cacheable {
+            if self.cache.len() == CACHE_SIZE {
+                self.cache.clear();
+            }
+            self.cache.push(s.to_string());
+        }
+        Ok(s.to_string())
+    }
+
+    fn read(&mut self, json: &Value, as_key: bool) -> Result<edn::Value> {
+        match json {
+            Value::Null => Ok(edn::Value::Nil),
+            Value::Bool(b) => Ok(edn::Value::Boolean(*b)),
+            Value::Number(n) if n.is_f64() => Ok(edn::Value::Float(n.as_f64().unwrap().into())),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Ok(edn::Value::Integer(i)),
+                None => bad_transit(format!("{} is too big for a long", n)),
+            },
+            Value::String(s) => {
+                let s = self.resolve(s, as_key)?;
+                read_string(&s)
+            }
+            Value::Array(items) => {
+                let mut rest = items.iter();
+                let first = match rest.next() {
+                    None => return Ok(edn::Value::Vector(vec![])),
+                    Some(Value::String(s)) if s == "^ " => return self.read_map(rest),
+                    Some(Value::String(s)) => {
+                        let s = self.resolve(s, false)?;
+                        if let (Some(tag), 1) = (s.strip_prefix("~#"), rest.len()) {
+                            return self.read_tagged(tag, rest.next().unwrap());
+                        }
+                        read_string(&s)?
+                    }
+                    Some(first) => self.read(first, false)?,
+                };
+                let mut values = vec![first];
+                for item in rest {
+                    values.push(self.read(item, false)?);
+                }
+                Ok(edn::Value::Vector(values))
+            }
+            // Verbose mode.
+            Value::Object(map) => {
+                if map.len() == 1 {
+                    let (key, value) = map.iter().next().unwrap();
+                    let key = self.resolve(key, true)?;
+                    if let Some(tag) = key.strip_prefix("~#") {
+                        return self.read_tagged(tag, value);
+                    }
+                    let key = read_string(&key)?;
+                    let value = self.read(value, false)?;
+                    return Ok(edn::Value::Map(vec![(key, value)].into_iter().collect()));
+                }
+                let mut entries = BTreeMap::new();
+                for (key, value) in map {
+                    let key = self.resolve(key, true)?;
+                    entries.insert(read_string(&key)?, self.read(value, false)?);
+                }
+                Ok(edn::Value::Map(entries))
+            }
+        }
+    }
+
+    /// Reads the alternating keys and values of a `["^ ", …]` map.
+    fn read_map<'a, I>(&mut self, mut items: I) -> Result<edn::Value>
+    where
+        I: Iterator<Item = &'a Value>,
+    {
+        let mut entries = BTreeMap::new();
+        while let Some(key) = items.next() {
+            let key = self.read(key, true)?;
+            let value = match items.next() {
+                Some(value) => self.read(value, false)?,
+                None => return bad_transit("a map has a key without a value"),
+            };
+            entries.insert(key, value);
+        }
+        Ok(edn::Value::Map(entries))
+    }
+
+    fn read_tagged(&mut self, tag: &str, value: &Value) -> Result<edn::Value> {
+        let items = |reader: &mut Self| -> Result<Vec<edn::Value>> {
+            match value {
+                Value::Array(items) => items.iter().map(|item| reader.read(item, false)).collect(),
+                _ => bad_transit(format!("expected an array for the tag {}", tag)),
+            }
+        };
+        match tag {
+            "'" => self.read(value, false),
+            "list" => items(self).map(|items| edn::Value::List(items.into_iter().collect())),
+            "set" => items(self).map(|items| edn::Value::Set(items.into_iter().collect())),
+            "cmap" => {
+                let items = items(self)?;
+                if items.len() % 2 != 0 {
+                    return bad_transit("a map has a key without a value");
+                }
+                let mut entries = BTreeMap::new();
+                let mut items = items.into_iter();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    entries.insert(key, value);
+                }
+                Ok(edn::Value::Map(entries))
+            }
+            "ref" => match value.as_i64() {
+                Some(entid) => Ok(edn::Value::Integer(entid)),
+                None => bad_transit(format!("bad ref {}", value)),
+            },
+            _ => bad_transit(format!("unknown tag {}", tag)),
+        }
+    }
+}
+
+/// Reads a Transit string, which may be an escaped scalar like `"~:person/name"`.
+fn read_string(s: &str) -> Result<edn::Value> {
+    if !s.starts_with('~') || s.len() < 2 {
+        return Ok(edn::Value::Text(s.to_string()));
+    }
+    let rest = &s[2..];
+    let value = match s.as_bytes()[1] {
+        b'~' | b'^' | b'`' => Some(edn::Value::Text(s[1..].to_string())),
+        b':' => parse_keyword(&format!(":{}", rest)).map(edn::Value::Keyword),
+        b'$' => Some(match rest.find('/') {
+            Some(i) if i > 0 && i < rest.len() - 1 => edn::Value::NamespacedSymbol(
+                NamespacedSymbol::namespaced(&rest[..i], &rest[i + 1..]),
+            ),
+            _ => edn::Value::PlainSymbol(PlainSymbol::plain(rest)),
+        }),
+        b'i' => rest.parse().ok().map(edn::Value::Integer),
+        b'd' => rest
+            .parse::<f64>()
+            .ok()
+            .map(|d| edn::Value::Float(d.into())),
+        b'z' => match rest {
+            "NaN" => Some(f64::NAN),
+            "INF" => Some(f64::INFINITY),
+            "-INF" => Some(f64::NEG_INFINITY),
+            _ => None,
+        }
+        .map(|d| edn::Value::Float(d.into())),
+        b'm' => rest
+            .parse()
+            .ok()
+            .map(|millis| edn::Value::Instant(edn::DateTime::<edn::Utc>::from_millis(millis))),
+        b't' => parse_instant(rest).map(edn::Value::Instant),
+        b'u' => edn::Uuid::parse_str(rest).ok().map(edn::Value::Uuid),
+        b'b' => decode_base64(rest).map(|bytes| edn::Value::Bytes(bytes.into())),
+        b'_' if rest.is_empty() => Some(edn::Value::Nil),
+        b'?' => match rest {
+            "t" => Some(edn::Value::Boolean(true)),
+            "f" => Some(edn::Value::Boolean(false)),
+            _ => None,
+        },
+        _ => None,
+    };
+    match value {
+        Some(value) => Ok(value),
+        None => bad_transit(format!("can't read \"{}\"", s)),
+    }
+}
+
+/// Turns the EDN form of a transaction into entities, as `edn::parse::entities` would have
+/// parsed it from text.
+fn entities_from_edn(transaction: edn::Value, bad: Bad) -> Result<Vec<Entity<ValueAndSpan>>> {
+    match transaction {
+        edn::Value::Vector(entities) => entities
+            .into_iter()
+            .map(|entity| edn_entity(entity, bad))
+            .collect(),
+        _ => Err(bad("a transaction is a vector of entities".to_string())),
+    }
+}
+
+fn edn_entity(entity: edn::Value, bad: Bad) -> Result<Entity<ValueAndSpan>> {
+    match entity {
+        edn::Value::Vector(parts) if parts.len() == 4 => {
+            let mut parts = parts.into_iter();
+            let (op, e, a, v) = (
+                parts.next().unwrap(),
+                parts.next().unwrap(),
+                parts.next().unwrap(),
+                parts.next().unwrap(),
+            );
+            let op = match op {
+                edn::Value::Keyword(ref k) if k.namespace() == Some("db") && k.name() == "add" => {
+                    OpType::Add
+                }
+                edn::Value::Keyword(ref k)
+                    if k.namespace() == Some("db") && k.name() == "retract" =>
+                {
+                    OpType::Retract
+                }
+                _ => return Err(bad(format!("expected :db/add or :db/retract, got {}", op))),
+            };
+            match a {
+                // `[op v :backward/_attribute e]` is `[op e :backward/attribute v]`.
+                edn::Value::Keyword(ref a) if a.is_namespaced() && a.is_backward() => {
+                    Ok(Entity::AddOrRetract {
+                        op,
+                        e: edn_entity_place(v, bad)?,
+                        a: AttributePlace::Entid(EntidOrIdent::Ident(a.to_reversed())),
+                        v: edn_value_place(e, bad)?,
+                    })
+                }
+                _ => Ok(Entity::AddOrRetract {
+                    op,
+                    e: edn_entity_place(e, bad)?,
+                    a: AttributePlace::Entid(edn_entid(a, bad)?),
+                    v: edn_value_place(v, bad)?,
+                }),
+            }
+        }
+        edn::Value::Map(map) => edn_map_notation(map, bad).map(Entity::MapNotation),
+        _ => Err(bad(format!("expected an entity, got {}", entity))),
+    }
+}
+
+fn edn_entid(value: edn::Value, bad: Bad) -> Result<EntidOrIdent> {
+    match value {
+        edn::Value::Integer(entid) => Ok(EntidOrIdent::Entid(entid)),
+        edn::Value::Keyword(keyword) if keyword.is_namespaced() => Ok(EntidOrIdent::Ident(keyword)),
+        _ => Err(bad(format!("expected an entid or an ident, got {}", value))),
+    }
+}
+
+/// Reads `(lookup-ref a v)` or `(tx-function)`.
+fn edn_list(list: Vec<edn::Value>, bad: Bad) -> Result<ValuePlace<ValueAndSpan>> {
+    let mut items = list.into_iter();
+    match (items.next(), items.next(), items.next(), items.next()) {
+        (Some(edn::Value::PlainSymbol(ref op)), Some(a), Some(v), None) if op.0 == "lookup-ref" => {
+            Ok(ValuePlace::LookupRef(LookupRef {
+                a: AttributePlace::Entid(edn_entid(a, bad)?),
+                v: v.into(),
+            }))
+        }
+        (Some(edn::Value::PlainSymbol(op)), None, None, None) => {
+            Ok(ValuePlace::TxFunction(TxFunction { op }))
+        }
+        _ => Err(bad(
+            "expected (lookup-ref a v) or a transaction function".to_string()
+        )),
+    }
+}
+
+fn edn_entity_place(value: edn::Value, bad: Bad) -> Result<EntityPlace<ValueAndSpan>> {
+    match value {
+        edn::Value::Text(tempid) => Ok(EntityPlace::TempId(TempId::External(tempid).into())),
+        edn::Value::List(list) => match edn_list(list.into_iter().collect(), bad)? {
+            ValuePlace::LookupRef(lookup_ref) => Ok(EntityPlace::LookupRef(lookup_ref)),
+            ValuePlace::TxFunction(function) => Ok(EntityPlace::TxFunction(function)),
+            _ => unreachable!(),
+        },
+        _ => edn_entid(value, bad).map(EntityPlace::Entid),
+    }
+}
+
+fn edn_map_notation(
+    map: BTreeMap<edn::Value, edn::Value>,
+    bad: Bad,
+) -> Result<MapNotation<ValueAndSpan>> {
+    map.into_iter()
+        .map(|(key, value)| Ok((edn_entid(key, bad)?, edn_value_place(value, bad)?)))
+        .collect()
+}
+
+fn edn_value_place(value: edn::Value, bad: Bad) -> Result<ValuePlace<ValueAndSpan>> {
+    match value {
+        edn::Value::List(list) => edn_list(list.into_iter().collect(), bad),
+        edn::Value::Vector(values) => values
+            .into_iter()
+            .map(|value| edn_value_place(value, bad))
+            .collect::<Result<Vec<_>>>()
+            .map(ValuePlace::Vector),
+        edn::Value::Map(map) => edn_map_notation(map, bad).map(ValuePlace::MapNotation),
+        _ => Ok(ValuePlace::Atom(value.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(edn: &str) -> Vec<Entity<ValueAndSpan>> {
+        edn::parse::entities(edn).expect("parsed EDN")
+    }
+
+    /// Spans differ between parsed and built values, so compare entities without them.
+    fn strip(entities: Vec<Entity<ValueAndSpan>>) -> String {
+        let debug = format!("{:?}", entities);
+        let mut parts = debug.split("span: Span(");
+        let mut stripped = parts.next().unwrap_or("").to_string();
+        for part in parts {
+            stripped.push_str(part.split_once(')').map_or("", |x| x.1));
+        }
+        stripped
+    }
+
+    #[test]
+    fn test_json_entities() {
+        let json = r#"[[":db/add", "alice", ":person/name", "Al\"ice"],
+                       [":db/retract", {"lookup-ref": [":person/name", "Bob"]}, ":person/age", 40],
+                       [":db/add", "alice", ":person/_friend", {"keyword": ":person/bob"}],
+                       {":db/id": "carol", ":person/height": 1.5, ":person/friend": ["alice", {"ref": 65536}],
+                        ":person/born": {"instant": "2018-01-01T00:00:00.000001Z"},
+                        ":person/tx": {"tx-function": "transaction-tx"}}]"#;
+        let edn = r#"[[:db/add "alice" :person/name "Al\"ice"]
+                      [:db/retract (lookup-ref :person/name "Bob") :person/age 40]
+                      [:db/add "alice" :person/_friend :person/bob]
+                      {:db/id "carol" :person/height 1.5 :person/friend ["alice" 65536]
+                       :person/born #instmicros 1514764800000001
+                       :person/tx (transaction-tx)}]"#;
+        assert_eq!(
+            strip(entities_from_json(json).expect("read JSON")),
+            strip(parsed(edn))
+        );
+    }
+
+    #[test]
+    fn test_bad_json() {
+        for bad in &[
+            "{}",
+            "[[\":db/add\", \"e\", \"person/name\", \"x\"]]",
+            "[[\":db/frob\", \"e\", \":person/name\", \"x\"]]",
+            "[[\":db/add\", \"e\", \":person/name\", null]]",
+            "[{\"person/name\": \"x\"}]",
+            "[[\":db/add\", \"e\", \":person/name\", {\"when\": 1}]]",
+            "[",
+        ] {
+            match entities_from_json(bad) {
+                Err(MentatError::BadJson(_)) => {}
+                result => panic!("{} gave {:?}", bad, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_transit_entities() {
+        // Later entities refer to keywords, keys and tags cached from earlier ones.
+        let transit = r#"[["~:db/add", "alice", "~:person/name", "~~alice"],
+                          ["^0", ["~#list", ["~$lookup-ref", "^1", "Bob"]], "~:person/age", "~i40"],
+                          ["^ ", "~:person/nickname", "x", "~:person/friend", ["~#ref", 65536]],
+                          ["^ ", "^5", "y", "^6", ["^7", 65537], "~:person/born", "~m1514764800000",
+                           "~:person/id", "~u550e8400-e29b-41d4-a716-446655440000",
+                           "~:person/photo", "~bAAEC", "~:person/height", "~zNaN"],
+                          {"~:db/id": "dave", "~:person/admin": true}]"#;
+        let edn = r#"[[:db/add "alice" :person/name "~alice"]
+                      [:db/add (lookup-ref :person/name "Bob") :person/age 40]
+                      {:person/nickname "x" :person/friend 65536}
+                      {:person/nickname "y" :person/friend 65537 :person/born #inst "2018-01-01T00:00:00.000Z"
+                       :person/id #uuid "550e8400-e29b-41d4-a716-446655440000"
+                       :person/photo #bytes 000102 :person/height #f NaN}
+                      {:db/id "dave" :person/admin true}]"#;
+        assert_eq!(
+            strip(entities_from_transit(transit).expect("read Transit")),
+            strip(parsed(edn))
+        );
+        assert_eq!(
+            strip(entities_from_transit(r#"["~#'", []]"#).expect("read quoted")),
+            strip(vec![])
+        );
+    }
+
+    #[test]
+    fn test_bad_transit() {
+        for bad in &[
+            "{}",
+            "[[\"~:db/add\", \"e\", \"~:person/name\", \"~qx\"]]",
+            "[[\"~:db/add\", \"e\", \"^9\", 1]]",
+            "[[\"~:db/add\", \"e\", \"~:person/name\", [\"~#point\", [1, 2]]]]",
+            "[[\"^ \", \"~:person/name\"]]",
+        ] {
+            match entities_from_transit(bad) {
+                Err(MentatError::BadTransit(_)) => {}
+                result => panic!("{} gave {:?}", bad, result),
+            }
+        }
+    }
+}
@@ -10,6 +10,8 @@
 
 extern crate failure;
 extern crate rusqlite;
+#[macro_use]
+extern crate serde_json;
 
 extern crate edn;
 extern crate public_traits;
@@ -29,7 +31,7 @@ use std::io::Read;
 
 use std::borrow::Borrow;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use std::fs::File;
 
@@ -42,20 +44,28 @@ use core_traits::{Attribute, Entid, KnownEntid, StructuredMap, TypedValue, Value
 
 use public_traits::errors::{MentatError, Result};
 
-use mentat_core::{HasSchema, Schema, TxReport, ValueRc};
+use mentat_core::{HasSchema, Schema, Statistics, TxReport, ValueRc};
 
 use mentat_query_pull::{pull_attributes_for_entities, pull_attributes_for_entity};
 
 use mentat_db::{
-    transact, transact_terms, InProgressObserverTransactWatcher, PartitionMap, TransactWatcher,
-    TransactableValue, TxObservationService,
+    compact_history, resolve_history_bound, transact, transact_terms, undo, HistoryBound,
+    InProgressObserverTransactWatcher, PartitionMap, TransactWatcher, TransactableValue,
+    TxObservationService,
 };
 
 use mentat_db::internal_types::TermWithTempIds;
 
+use mentat_db::timelines::{
+    active_timeline, discard_timeline, fork_timeline, merge_timeline, switch_timeline, timelines,
+    Timeline,
+};
+
 use mentat_db::cache::{InProgressCacheTransactWatcher, InProgressSQLiteAttributeCache};
 
 pub mod entity_builder;
+mod explain;
+pub mod json;
 pub mod metadata;
 pub mod query;
 
@@ -65,7 +75,7 @@ pub use crate::metadata::Metadata;
 
 use crate::query::{
     lookup_value_for_attribute, lookup_values_for_attribute, q_explain, q_once, q_prepare,
-    q_uncached, Known, PreparedResult, QueryExplanation, QueryInputs, QueryOutput,
+    q_uncached, AttachedSchemas, Known, PreparedResult, QueryExplanation, QueryInputs, QueryOutput,
 };
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -93,6 +103,10 @@ pub struct InProgress<'a, 'c> {
     pub schema: Schema,
     pub cache: InProgressSQLiteAttributeCache,
     pub use_caching: bool,
+    pub statistics: Option<Arc<Statistics>>,
+    /// The schemas of the stores attached to the connection, which queries can match with
+    /// `$name`.
+    pub attached: Option<&'a AttachedSchemas>,
     pub tx_observer: &'a Mutex<TxObservationService>,
     pub tx_observer_watcher: InProgressObserverTransactWatcher,
 }
@@ -153,6 +167,31 @@ impl<'a, 'c> InProgress<'a, 'c> {
         self.use_caching = yesno;
     }
 
+    fn known(&self) -> Known<'_, '_> {
+        let known = Known::new(&self.schema, Some(&self.cache));
+        let known = match self.statistics {
+            Some(ref statistics) => known.with_statistics(statistics),
+            None => known,
+        };
+        match self.attached {
+            Some(attached) => known.with_attached(attached),
+            None => known,
+        }
+    }
+
+    /// Queries made before committing see the transactions made so far as `$delta`, unless
+    /// they're given transactions of their own.
+    fn with_delta<T>(&self, inputs: T) -> QueryInputs
+    where
+        T: Into<Option<QueryInputs>>,
+    {
+        let mut inputs = inputs.into().unwrap_or_default();
+        if !inputs.has_delta() {
+            inputs.set_delta(self.tx_observer_watcher.txes.keys().cloned());
+        }
+        inputs
+    }
+
     /// If you only have a reference to an `InProgress`, you can't use the easy builder.
     /// This exists so you can make your own.
     pub fn transact_builder(&mut self, builder: TermBuilder) -> Result<TxReport> {
@@ -185,6 +224,27 @@ impl<'a, 'c> InProgress<'a, 'c> {
         Ok(report)
     }
 
+    /// Transact the inverse of transaction `tx_id`, failing if a later transaction has changed
+    /// any of the same attributes of the same entities.  Undo the returned transaction to redo.
+    pub fn undo(&mut self, tx_id: Entid) -> Result<TxReport> {
+        let w = InProgressTransactWatcher::new(
+            &mut self.tx_observer_watcher,
+            self.cache.transact_watcher(),
+        );
+        let (report, next_partition_map, next_schema, _watcher) = undo(
+            &self.transaction,
+            self.partition_map.clone(),
+            &self.schema,
+            w,
+            tx_id,
+        )?;
+        self.partition_map = next_partition_map;
+        if let Some(schema) = next_schema {
+            self.schema = schema;
+        }
+        Ok(report)
+    }
+
     pub fn transact_entities<I, V: TransactableValue>(&mut self, entities: I) -> Result<TxReport>
     where
         I: IntoIterator<Item = edn::entities::Entity<V>>,
@@ -224,6 +284,24 @@ impl<'a, 'c> InProgress<'a, 'c> {
         self.transact_entities(entities)
     }
 
+    /// Transacts a transaction written in JSON. See `json` for the format.
+    pub fn transact_json<B>(&mut self, transaction: B) -> Result<TxReport>
+    where
+        B: Borrow<str>,
+    {
+        let entities = json::entities_from_json(transaction.borrow())?;
+        self.transact_entities(entities)
+    }
+
+    /// Transacts a transaction written in Transit-JSON. See `json` for the format.
+    pub fn transact_transit<B>(&mut self, transaction: B) -> Result<TxReport>
+    where
+        B: Borrow<str>,
+    {
+        let entities = json::entities_from_transit(transaction.borrow())?;
+        self.transact_entities(entities)
+    }
+
     pub fn import<P>(&mut self, path: P) -> Result<TxReport>
     where
         P: AsRef<Path>,
@@ -316,6 +394,100 @@ impl<'a, 'c> InProgress<'a, 'c> {
         self.partition_map[":db.part/tx"].next_entid() - 1
     }
 
+    /// Return the transaction before which `bound` compacts history.
+    pub fn history_bound_tx(&self, bound: HistoryBound) -> Result<Entid> {
+        resolve_history_bound(&self.transaction, bound).map_err(|e| e.into())
+    }
+
+    /// Compact the history of the store before the transaction `before_tx`, keeping the full
+    /// history of the attributes in `keep_history`.  Current datoms are untouched.  Returns the
+    /// number of transaction log rows removed.
+    ///
+    /// This doesn't consult sync metadata: callers that sync must not compact transactions that
+    /// have not yet been uploaded.
+    pub fn compact_history(&mut self, before_tx: Entid, keep_history: &[Keyword]) -> Result<usize> {
+        let keep_history = keep_history
+            .iter()
+            .map(|attribute| {
+                self.schema
+                    .attribute_for_ident(attribute)
+                    .map(|(_, entid)| entid.into())
+                    .ok_or_else(|| MentatError::UnknownAttribute(attribute.to_string()))
+            })
+            .collect::<Result<BTreeSet<Entid>>>()?;
+        compact_history(&self.transaction, &self.schema, before_tx, &keep_history)
+            .map_err(|e| e.into())
+    }
+
+    /// The named timelines in the store.
+    pub fn timelines(&self) -> Result<Vec<Timeline>> {
+        timelines(&self.transaction).map_err(|e| e.into())
+    }
+
+    /// The named timeline currently in place of the main timeline, if any.
+    pub fn active_timeline(&self) -> Result<Option<Timeline>> {
+        active_timeline(&self.transaction).map_err(|e| e.into())
+    }
+
+    /// Create a named timeline forking from the main timeline after transaction `base`.
+    pub fn fork_timeline(&mut self, name: &str, base: Entid) -> Result<Timeline> {
+        fork_timeline(&self.transaction, name, base).map_err(|e| e.into())
+    }
+
+    /// Switch to the named timeline, or back to the main timeline if `name` is
+    /// `TIMELINE_MAIN_NAME`.  Subsequent transactions land on that timeline.
+    pub fn switch_timeline(&mut self, name: &str) -> Result<()> {
+        let (next_schema, next_partition_map) = switch_timeline(
+            &self.transaction,
+            &self.schema,
+            self.partition_map.clone(),
+            name,
+            self.cache.transact_watcher(),
+        )?;
+        self.partition_map = next_partition_map;
+        if let Some(schema) = next_schema {
+            self.schema = schema;
+        }
+        Ok(())
+    }
+
+    /// Transact the changes made on the named timeline on top of the main timeline, and forget
+    /// the named timeline.  Returns a report for each new transaction.
+    pub fn merge_timeline(&mut self, name: &str) -> Result<Vec<TxReport>> {
+        let w = InProgressTransactWatcher::new(
+            &mut self.tx_observer_watcher,
+            self.cache.transact_watcher(),
+        );
+        let (reports, next_schema, next_partition_map) = merge_timeline(
+            &self.transaction,
+            &self.schema,
+            self.partition_map.clone(),
+            name,
+            w,
+        )?;
+        self.partition_map = next_partition_map;
+        if let Some(schema) = next_schema {
+            self.schema = schema;
+        }
+        Ok(reports)
+    }
+
+    /// Forget the named timeline and the changes made on it.
+    pub fn discard_timeline(&mut self, name: &str) -> Result<()> {
+        let (next_schema, next_partition_map) = discard_timeline(
+            &self.transaction,
+            &self.schema,
+            self.partition_map.clone(),
+            name,
+            self.cache.transact_watcher(),
+        )?;
+        self.partition_map = next_partition_map;
+        if let Some(schema) = next_schema {
+            self.schema = schema;
+        }
+        Ok(())
+    }
+
     pub fn savepoint(&self, name: &str) -> Result<()> {
         self.transaction
             .execute(&format!("SAVEPOINT {}", name), rusqlite::params![])?;
@@ -416,8 +588,9 @@ impl<'a, 'c> Queryable for InProgress<'a, 'c> {
     where
         T: Into<Option<QueryInputs>>,
     {
+        let inputs = self.with_delta(inputs);
         if self.use_caching {
-            let known = Known::new(&self.schema, Some(&self.cache));
+            let known = self.known();
             q_once(&*(self.transaction), known, query, inputs)
         } else {
             q_uncached(&*(self.transaction), &self.schema, query, inputs)
@@ -428,16 +601,16 @@ impl<'a, 'c> Queryable for InProgress<'a, 'c> {
     where
         T: Into<Option<QueryInputs>>,
     {
-        let known = Known::new(&self.schema, Some(&self.cache));
-        q_prepare(&*(self.transaction), known, query, inputs)
+        let known = self.known();
+        q_prepare(&*(self.transaction), known, query, self.with_delta(inputs))
     }
 
     fn q_explain<T>(&self, query: &str, inputs: T) -> Result<QueryExplanation>
     where
         T: Into<Option<QueryInputs>>,
     {
-        let known = Known::new(&self.schema, Some(&self.cache));
-        q_explain(&*(self.transaction), known, query, inputs)
+        let known = self.known();
+        q_explain(&*(self.transaction), known, query, self.with_delta(inputs))
     }
 
     fn lookup_values_for_attribute<E>(
@@ -6,7 +6,7 @@
 /// See https://github.com/mozilla/mentat/wiki/Thoughts:-modeling-db-conn-in-Rust.
 use std::sync::Arc;
 
-use mentat_core::Schema;
+use mentat_core::{Schema, Statistics};
 
 use mentat_db::PartitionMap;
 
@@ -17,6 +17,8 @@ pub struct Metadata {
     pub partition_map: PartitionMap,
     pub schema: Arc<Schema>,
     pub attribute_cache: SQLiteAttributeCache,
+    /// Estimates used to plan queries, if the store has been analyzed.
+    pub statistics: Option<Arc<Statistics>>,
 }
 
 impl Metadata {
@@ -32,6 +34,7 @@ impl Metadata {
             partition_map,
             schema,
             attribute_cache: cache,
+            statistics: None,
         }
     }
 }
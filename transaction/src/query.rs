@@ -11,17 +11,19 @@
 use rusqlite;
 use rusqlite::types::ToSql;
 
+use std::collections::{BTreeMap, VecDeque};
 use std::rc::Rc;
 
-use core_traits::{Binding, Entid, KnownEntid, TypedValue};
+use core_traits::{Binding, Entid, KnownEntid, TypedValue, ValueTypeSet};
 
 use mentat_core::{HasSchema, Schema};
 
 use mentat_query_algebrizer::{
-    algebrize_with_inputs, parse_find_string, AlgebraicQuery, EmptyBecause, FindQuery,
+    algebrize_with_inputs, parse_find_string, AlgebraicQuery, EmptyBecause, FindQuery, SourceAlias,
+    TableAlias,
 };
 
-pub use mentat_query_algebrizer::QueryInputs;
+pub use mentat_query_algebrizer::{FulltextMarkup, QueryInputs};
 
 pub use edn::query::{Keyword, PlainSymbol, Variable};
 
@@ -35,7 +37,7 @@ use mentat_query_projector::translate::{query_to_select, ProjectedSelect};
 
 use mentat_sql::SQLQuery;
 
-pub use mentat_query_algebrizer::Known;
+pub use mentat_query_algebrizer::{AttachedSchemas, Known};
 
 pub use mentat_query_projector::{
     QueryOutput,  // Includes the columns/find spec.
@@ -45,6 +47,8 @@ pub use mentat_query_projector::{
 
 use public_traits::errors::{MentatError, Result};
 
+use crate::explain;
+
 pub type QueryExecutionResult = Result<QueryOutput>;
 pub type PreparedResult<'sqlite> = Result<PreparedQuery<'sqlite>>;
 
@@ -88,6 +92,106 @@ impl<'sqlite> PreparedQuery<'sqlite> {
             }
         }
     }
+
+    /// Run the query, reading its results lazily: see `QueryRows`.
+    pub fn rows(&mut self) -> Result<QueryRows<'_>> {
+        match self {
+            PreparedQuery::Empty { .. } => Ok(QueryRows::from_rows(vec![])),
+            PreparedQuery::Constant { ref select } => Ok(QueryRows::from_rows(
+                select.project_without_rows()?.results.into_rows(),
+            )),
+            PreparedQuery::Bound {
+                ref mut statement,
+                ref schema,
+                connection,
+                ref args,
+                ref projector,
+            } => {
+                let rows = run_statement(statement, args)?;
+                Ok(QueryRows {
+                    source: Some(RowSource {
+                        rows,
+                        schema,
+                        connection,
+                        projector: projector.as_ref(),
+                    }),
+                    batch: VecDeque::new(),
+                    batch_size: DEFAULT_ROWS_PER_BATCH,
+                })
+            }
+        }
+    }
+}
+
+/// How many rows `QueryRows` reads from SQLite at a time, unless told otherwise.
+pub const DEFAULT_ROWS_PER_BATCH: usize = 256;
+
+struct RowSource<'stmt> {
+    rows: rusqlite::Rows<'stmt>,
+    schema: &'stmt Schema,
+    connection: &'stmt rusqlite::Connection,
+    projector: &'stmt dyn Projector,
+}
+
+/// The results of a query, read from SQLite as they're needed rather than all at once. Each
+/// row is a vector of bindings, with a single binding for a scalar or `coll` query.
+///
+/// Rows are read and projected a batch at a time, and pull expressions are resolved for a whole
+/// batch, so memory use depends on the size of a batch rather than on the number of results.
+/// Unlike `QueryOutput`, the maps pulled by a `coll` query are in the order of their rows.
+pub struct QueryRows<'stmt> {
+    source: Option<RowSource<'stmt>>,
+    batch: VecDeque<Vec<Binding>>,
+    batch_size: usize,
+}
+
+impl<'stmt> QueryRows<'stmt> {
+    fn from_rows(rows: Vec<Vec<Binding>>) -> QueryRows<'stmt> {
+        QueryRows {
+            source: None,
+            batch: rows.into(),
+            batch_size: DEFAULT_ROWS_PER_BATCH,
+        }
+    }
+
+    /// Read `batch_size` rows at a time.
+    pub fn with_batch_size(mut self, batch_size: usize) -> QueryRows<'stmt> {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+}
+
+impl<'stmt> Iterator for QueryRows<'stmt> {
+    type Item = Result<Vec<Binding>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.batch.pop_front() {
+                return Some(Ok(row));
+            }
+
+            let source = self.source.as_mut()?;
+            let mut out = Vec::with_capacity(self.batch_size);
+            match source.projector.project_rows(
+                source.schema,
+                source.connection,
+                &mut source.rows,
+                self.batch_size,
+                &mut out,
+            ) {
+                Ok(count) => {
+                    if count < self.batch_size {
+                        self.source = None;
+                    }
+                    self.batch.extend(out);
+                }
+                Err(e) => {
+                    self.source = None;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
 }
 
 pub trait IntoResult {
@@ -115,8 +219,21 @@ impl IntoResult for QueryExecutionResult {
     }
 }
 
-/// A struct describing information about how Mentat would execute a query.
-pub enum QueryExplanation {
+/// A description of how Mentat would execute a query, in terms of the query itself.
+/// See `to_edn` and `to_json` for machine-readable forms.
+pub struct QueryExplanation {
+    /// What we would do to run the query.
+    pub plan: QueryPlan,
+
+    /// The types we inferred for the query's variables. A variable whose type can't be known
+    /// until the query runs isn't included.
+    pub types: BTreeMap<Variable, ValueTypeSet>,
+
+    /// The attributes whose cached values were used instead of querying the store.
+    pub cached_attributes: Vec<Keyword>,
+}
+
+pub enum QueryPlan {
     /// A query known in advance to be empty, and why we believe that.
     KnownEmpty(EmptyBecause),
 
@@ -127,11 +244,23 @@ pub enum QueryExplanation {
     ExecutionPlan {
         /// The translated query and any bindings.
         query: SQLQuery,
+        /// The clause that each table in `query` matches.
+        tables: Vec<TableExplanation>,
         /// The output of SQLite's `EXPLAIN QUERY PLAN`.
         steps: Vec<QueryPlanStep>,
     },
 }
 
+/// A table in the SQL we run for a query, and the query clause for which we match it.
+pub struct TableExplanation {
+    /// The table's alias in the SQL: e.g., `datoms00`.
+    pub alias: TableAlias,
+    /// The table itself: e.g., `datoms`, or `other.transactions` for an attached store.
+    pub table: String,
+    /// The clause: e.g., `[?e :foo/bar ?v]`, or `fulltext` for a function.
+    pub clause: edn::Value,
+}
+
 /// A single row in the output of SQLite's `EXPLAIN QUERY PLAN`.
 /// See https://www.sqlite.org/eqp.html for an explanation of each field.
 pub struct QueryPlanStep {
@@ -451,29 +580,64 @@ where
     T: Into<Option<QueryInputs>>,
 {
     let algebrized = algebrize_query_str(known, query, inputs)?;
-    if algebrized.is_known_empty() {
-        return Ok(QueryExplanation::KnownEmpty(
-            algebrized.cc.empty_because.unwrap(),
-        ));
-    }
-    match query_to_select(known.schema, algebrized)? {
-        ProjectedSelect::Constant(_constant) => Ok(QueryExplanation::KnownConstant),
-        ProjectedSelect::Query {
-            query,
-            projector: _projector,
-        } => {
-            let query = query.to_sql_query()?;
-
-            let plan_sql = format!("EXPLAIN QUERY PLAN {}", query.sql);
-
-            let steps = run_sql_query(sqlite, &plan_sql, &query.args, |row| QueryPlanStep {
-                select_id: row.get(0).unwrap(),
-                order: row.get(1).unwrap(),
-                from: row.get(2).unwrap(),
-                detail: row.get(3).unwrap(),
-            })?;
-
-            Ok(QueryExplanation::ExecutionPlan { query, steps })
+    let types = algebrized.cc.known_types.clone();
+    let cached_attributes = algebrized
+        .cc
+        .cached_attributes()
+        .into_iter()
+        .filter_map(|a| known.schema.get_ident(a).cloned())
+        .collect();
+    let origins = algebrized.cc.alias_origins();
+
+    let plan = if algebrized.is_known_empty() {
+        QueryPlan::KnownEmpty(algebrized.cc.empty_because.unwrap())
+    } else {
+        match query_to_select(known.schema, algebrized)? {
+            ProjectedSelect::Constant(_constant) => QueryPlan::KnownConstant,
+            ProjectedSelect::Query {
+                query,
+                projector: _projector,
+            } => {
+                let query = query.to_sql_query()?;
+
+                let plan_sql = format!("EXPLAIN QUERY PLAN {}", query.sql);
+
+                let steps = run_sql_query(sqlite, &plan_sql, &query.args, |row| QueryPlanStep {
+                    select_id: row.get(0).unwrap(),
+                    order: row.get(1).unwrap(),
+                    from: row.get(2).unwrap(),
+                    detail: row.get(3).unwrap(),
+                })?;
+
+                // We record tables for clauses that turn out not to matter -- e.g., `or` arms that
+                // can't match -- so only explain those that made it into the SQL.
+                let tables = origins
+                    .into_iter()
+                    .filter(|(SourceAlias(_, alias), _)| {
+                        query.sql.contains(&format!("`{}`", alias))
+                    })
+                    .map(|(SourceAlias(table, alias), origin)| TableExplanation {
+                        table: match table.database() {
+                            Some(database) => format!("{}.{}", database, table.name()),
+                            None => table.name().to_string(),
+                        },
+                        alias,
+                        clause: explain::origin_to_edn(known, &origin),
+                    })
+                    .collect();
+
+                QueryPlan::ExecutionPlan {
+                    query,
+                    tables,
+                    steps,
+                }
+            }
         }
-    }
+    };
+
+    Ok(QueryExplanation {
+        plan,
+        types,
+        cached_attributes,
+    })
 }